@@ -0,0 +1,47 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::path::PathBuf;
+
+use buck2_core::fs::fs_util;
+use starlark::docs::render_docs_as_html;
+use starlark::docs::Doc;
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+pub(crate) struct HtmlFileOptions {
+    #[structopt(
+        long = "html-files-destination-dir",
+        required_if_eq("format", "html_files")
+    )]
+    destination_dir: Option<PathBuf>,
+}
+
+/// Render the docs as a set of cross-linked HTML pages, with an `index.html` to start from.
+pub(crate) fn generate_html_files(opts: &HtmlFileOptions, docs: Vec<Doc>) -> anyhow::Result<()> {
+    let destination_dir = opts
+        .destination_dir
+        .as_ref()
+        .expect("clap enforces when --format=html_files");
+
+    let abs_destination = if destination_dir.is_relative() {
+        std::env::current_dir()?.join(destination_dir)
+    } else {
+        destination_dir.to_owned()
+    };
+    fs_util::create_dir_all(&abs_destination)?;
+
+    for file in render_docs_as_html(&docs) {
+        let path = abs_destination.join(&file.path);
+        buck2_client_ctx::eprintln!("Writing to {}", path.to_str().unwrap())?;
+
+        fs_util::write(&path, &file.contents)?;
+    }
+
+    Ok(())
+}
@@ -30,6 +30,7 @@ use buck2_build_api::interpreter::rule_defs::cmd_args::CommandLineArtifactVisito
 use buck2_build_api::interpreter::rule_defs::cmd_args::DefaultCommandLineContext;
 use buck2_build_api::interpreter::rule_defs::cmd_args::SimpleCommandLineArtifactVisitor;
 use buck2_build_api::interpreter::rule_defs::cmd_args::ValueAsCommandLineLike;
+use buck2_common::file_ops::TrackedFileDigest;
 use buck2_core::category::Category;
 use buck2_core::directory::FingerprintedDirectory;
 use buck2_core::fs::buck_out_path::BuckOutPath;
@@ -43,6 +44,8 @@ use buck2_execute::execute::request::CommandExecutionOutput;
 use buck2_execute::execute::request::CommandExecutionPaths;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::request::WorkerProtocol;
+use buck2_execute::execute::request::WorkerSpec;
 use dupe::Dupe;
 use gazebo::prelude::*;
 use host_sharing::HostSharingRequirements;
@@ -144,6 +147,9 @@ pub(crate) struct UnregisteredRunAction {
     pub(crate) no_outputs_cleanup: bool,
     pub(crate) allow_cache_upload: bool,
     pub(crate) force_full_hybrid_if_capable: bool,
+    pub(crate) local_sandbox: Option<bool>,
+    pub(crate) worker_protocol: WorkerProtocol,
+    pub(crate) worker_multiplex: bool,
 }
 
 impl UnregisteredAction for UnregisteredRunAction {
@@ -172,13 +178,19 @@ impl RunAction {
     ) -> Option<(
         &dyn CommandLineArgLike,
         Vec<(&str, &dyn CommandLineArgLike)>,
+        Option<&dyn CommandLineArgLike>,
     )> {
-        // We expect (CmdArgs, Option<Dict<String, CmdArgs>>) in the Starlark value
-        let (cli, env) = match TupleRef::from_value(args.value())?.content() {
-            [cli, env] => (*cli, *env),
+        // We expect (CmdArgs, Option<Dict<String, CmdArgs>>, Option<CmdArgs>) in the Starlark value
+        let (cli, env, worker) = match TupleRef::from_value(args.value())?.content() {
+            [cli, env, worker] => (*cli, *env, *worker),
             _ => return None,
         };
         let cli = cli.as_command_line()?;
+        let worker = if worker.is_none() {
+            None
+        } else {
+            Some(worker.as_command_line()?)
+        };
         let env = if env.is_none() {
             Vec::new()
         } else {
@@ -189,19 +201,28 @@ impl RunAction {
             }
             res
         };
-        Some((cli, env))
+        Some((cli, env, worker))
     }
 
-    /// Get the command line expansion for this RunAction.
+    /// Get the command line expansion for this RunAction, and the worker command line it starts
+    /// with if it uses a worker.
     fn expand_command_line(
         &self,
         fs: &ExecutorFs,
         artifact_visitor: &mut impl CommandLineArtifactVisitor,
-    ) -> anyhow::Result<ExpandedCommandLine> {
+    ) -> anyhow::Result<(ExpandedCommandLine, Option<Vec<String>>)> {
         let mut cli_rendered = Vec::<String>::new();
         let mut ctx = DefaultCommandLineContext::new(fs);
 
-        let (cli, env) = Self::unpack(&self.starlark_cli).unwrap();
+        let (cli, env, worker) = Self::unpack(&self.starlark_cli).unwrap();
+        let worker_rendered = match worker {
+            Some(worker) => {
+                worker.add_to_command_line(&mut cli_rendered, &mut ctx)?;
+                worker.visit_artifacts(artifact_visitor)?;
+                Some(cli_rendered.clone())
+            }
+            None => None,
+        };
         cli.add_to_command_line(&mut cli_rendered, &mut ctx)?;
         cli.visit_artifacts(artifact_visitor)?;
 
@@ -217,10 +238,13 @@ impl RunAction {
             })
             .collect();
 
-        Ok(ExpandedCommandLine {
-            cli: cli_rendered,
-            env: cli_env?,
-        })
+        Ok((
+            ExpandedCommandLine {
+                cli: cli_rendered,
+                env: cli_env?,
+            },
+            worker_rendered,
+        ))
     }
 
     pub(crate) fn new(
@@ -243,6 +267,30 @@ impl RunAction {
         }
     }
 
+    /// A digest of the artifacts the worker command line refers to, so that a worker is restarted
+    /// when e.g. the tool it runs is rebuilt, even though its command line is unchanged.
+    fn worker_inputs_digest(
+        &self,
+        ctx: &dyn ActionExecutionCtx,
+    ) -> anyhow::Result<Option<TrackedFileDigest>> {
+        let worker = match Self::unpack(&self.starlark_cli).unwrap() {
+            (_, _, Some(worker)) => worker,
+            (_, _, None) => return Ok(None),
+        };
+        let mut artifact_visitor = SimpleCommandLineArtifactVisitor::new();
+        worker.visit_artifacts(&mut artifact_visitor)?;
+        if artifact_visitor.inputs.is_empty() {
+            return Ok(None);
+        }
+        let inputs: Vec<&ArtifactGroupValues> = artifact_visitor
+            .inputs
+            .iter()
+            .map(|group| ctx.artifact_values(group))
+            .collect();
+        let (_, digest) = metadata_content(ctx.fs(), &inputs, ctx.digest_config())?;
+        Ok(Some(digest))
+    }
+
     fn prepare(
         &self,
         visitor: &mut impl RunActionVisitor,
@@ -250,7 +298,16 @@ impl RunAction {
     ) -> anyhow::Result<PreparedRunAction> {
         let fs = ctx.fs();
 
-        let expanded = self.expand_command_line(&ctx.executor_fs(), visitor)?;
+        let (expanded, worker_exe) = self.expand_command_line(&ctx.executor_fs(), visitor)?;
+        let worker = match worker_exe {
+            Some(exe) => Some(WorkerSpec {
+                exe,
+                protocol: self.inner.worker_protocol,
+                multiplex: self.inner.worker_multiplex,
+                inputs_digest: self.worker_inputs_digest(&*ctx)?,
+            }),
+            None => None,
+        };
 
         // TODO (@torozco): At this point, might as well just receive the list already. Finding
         // those things in a HashMap is just not very useful.
@@ -301,6 +358,7 @@ impl RunAction {
             expanded,
             extra_env,
             paths,
+            worker,
         })
     }
 }
@@ -309,6 +367,7 @@ struct PreparedRunAction {
     expanded: ExpandedCommandLine,
     extra_env: Option<(String, String)>,
     paths: CommandExecutionPaths,
+    worker: Option<WorkerSpec>,
 }
 
 impl PreparedRunAction {
@@ -317,13 +376,14 @@ impl PreparedRunAction {
             expanded: ExpandedCommandLine { cli, mut env },
             extra_env,
             paths,
+            worker,
         } = self;
 
         for (k, v) in extra_env.into_iter() {
             env.insert(k, v);
         }
 
-        CommandExecutionRequest::new(cli, paths, env)
+        CommandExecutionRequest::new(cli, paths, env).with_worker(worker)
     }
 }
 
@@ -358,12 +418,15 @@ impl Action for RunAction {
     }
 
     fn inputs(&self) -> anyhow::Result<Cow<'_, [ArtifactGroup]>> {
-        let (cli, env) = Self::unpack(&self.starlark_cli).unwrap();
+        let (cli, env, worker) = Self::unpack(&self.starlark_cli).unwrap();
         let mut artifact_visitor = SimpleCommandLineArtifactVisitor::new();
         cli.visit_artifacts(&mut artifact_visitor)?;
         for (_, v) in env.iter() {
             v.visit_artifacts(&mut artifact_visitor)?;
         }
+        if let Some(worker) = worker {
+            worker.visit_artifacts(&mut artifact_visitor)?;
+        }
         Ok(Cow::Owned(artifact_visitor.inputs.into_iter().collect()))
     }
 
@@ -390,10 +453,20 @@ impl Action for RunAction {
     fn aquery_attributes(&self, fs: &ExecutorFs) -> indexmap::IndexMap<String, String> {
         let mut cli_rendered = Vec::<String>::new();
         let mut ctx = DefaultCommandLineContext::new(fs);
-        let (cli, _env) = Self::unpack(&self.starlark_cli).unwrap();
+        let (cli, _env, worker) = Self::unpack(&self.starlark_cli).unwrap();
         cli.add_to_command_line(&mut cli_rendered, &mut ctx)
             .unwrap();
         let cmd = format!("[{}]", cli_rendered.iter().join(", "));
+        let worker = match worker {
+            Some(worker) => {
+                let mut worker_rendered = Vec::<String>::new();
+                worker
+                    .add_to_command_line(&mut worker_rendered, &mut ctx)
+                    .unwrap();
+                format!("[{}]", worker_rendered.iter().join(", "))
+            }
+            None => "None".to_owned(),
+        };
         indexmap! {
             "cmd".to_owned() => cmd,
             "executor_preference".to_owned() => self.inner.executor_preference.to_string(),
@@ -405,6 +478,7 @@ impl Action for RunAction {
                 Some(x) => x.to_string(),
             },
             "no_outputs_cleanup".to_owned() => self.inner.no_outputs_cleanup.to_string(),
+            "worker".to_owned() => worker,
         }
     }
 }
@@ -489,6 +563,7 @@ impl IncrementalActionExecutable for RunAction {
             .with_allow_cache_upload(self.inner.allow_cache_upload)
             .with_local_environment_inheritance(EnvironmentInheritance::local_command_exclusions())
             .with_force_full_hybrid_if_capable(self.inner.force_full_hybrid_if_capable)
+            .with_local_sandbox(self.inner.local_sandbox)
             .with_custom_tmpdir(ctx.target().custom_tmpdir());
 
         let (outputs, meta) = ctx.exec_cmd(&req).await?;
@@ -36,6 +36,7 @@ use buck2_core::category::Category;
 use buck2_core::collections::ordered_set::OrderedSet;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_execute::execute::request::OutputType;
+use buck2_execute::execute::request::WorkerProtocol;
 use buck2_execute::materialize::http::Checksum;
 use buck2_interpreter::starlark_promise::StarlarkPromise;
 use buck2_interpreter_for_build::rule::FrozenRuleCallable;
@@ -131,6 +132,8 @@ enum RunActionError {
         "Recursion limit exceeded when visiting artifacts: do you have a cycle in your inputs or outputs?"
     )]
     ArtifactVisitRecursionLimitExceeded,
+    #[error("`worker_protocol` must be `json` or `proto`, got `{0}`")]
+    InvalidWorkerProtocol(String),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -529,6 +532,10 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
     /// * `arguments`: must be of type `cmd_args`, or a type convertible to such (such as a list of strings and artifacts) and must contain at least one `.as_output()` artifact
     /// * `category`: category and identifier - when used together, identify the action in Buck2's event stream, and must be unique for a given target
     /// * `weight`: used to note how heavy the command is and will typically be set to a higher value to indicate that less such commands should be run in parallel (if running locally)
+    /// * `worker`: a command line which starts a persistent worker (with `--persistent_worker` appended), in which case `arguments` are sent to the worker instead of starting a new process when the command runs locally; when the worker can't be used (e.g. on remote execution), the worker command line followed by `arguments` is run
+    ///     * `worker_protocol`: how to talk to the worker: `json` (one `WorkRequest` or `WorkResponse` per line) or `proto` (length-delimited protobuf messages), as in the Bazel worker protocol
+    ///     * `worker_multiplex`: whether the worker can process several requests concurrently
+    /// * `local_sandbox`: whether to run the command in a sandbox which only exposes its inputs and outputs when it runs locally (Linux only); if unset, the `use_local_sandbox` setting of the execution platform is used
     /// * `no_outputs_cleanup`: if this flag is set then Buck2 won't clean the outputs of a previous build that might be present on a disk; in which case, command from arguments should be responsible for the cleanup (that is useful, for example, when an action is supporting incremental mode and its outputs are based on result from a previous build)
     /// * `metadata_env_var` and `meadata_path` should be used together: both set or both unset
     ///     * `metadata_path`: defines a path relative to the result directory for a file with action metadata, which will be created right before the command will be run.
@@ -553,6 +560,10 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
         #[starlark(require = named, default = false)] no_outputs_cleanup: bool,
         #[starlark(require = named, default = false)] allow_cache_upload: bool,
         #[starlark(require = named, default = false)] force_full_hybrid_if_capable: bool,
+        #[starlark(require = named, default = NoneOr::None)] local_sandbox: NoneOr<bool>,
+        #[starlark(require = named)] worker: Option<Value<'v>>,
+        #[starlark(require = named, default = "json")] worker_protocol: &str,
+        #[starlark(require = named, default = false)] worker_multiplex: bool,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<NoneType> {
         struct RunCommandArtifactVisitor {
@@ -610,6 +621,24 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
         let starlark_cli = StarlarkCommandLine::try_from_value(arguments)?;
         starlark_cli.visit_artifacts(&mut artifact_visitor)?;
 
+        let starlark_worker = match worker {
+            None => Value::new_none(),
+            Some(worker) => {
+                let worker = StarlarkCommandLine::try_from_value(worker)?;
+                worker.visit_artifacts(&mut artifact_visitor)?;
+                eval.heap().alloc(worker)
+            }
+        };
+        let worker_protocol = match worker_protocol {
+            "json" => WorkerProtocol::Json,
+            "proto" => WorkerProtocol::Proto,
+            _ => {
+                return Err(
+                    RunActionError::InvalidWorkerProtocol(worker_protocol.to_owned()).into(),
+                );
+            }
+        };
+
         let weight = match (weight, weight_percentage) {
             (None, None) => WeightClass::Permits(1),
             (Some(v), None) => {
@@ -698,7 +727,9 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
         if artifacts.outputs.is_empty() {
             return Err(RunActionError::NoOutputsSpecified.into());
         }
-        let starlark = eval.heap().alloc((starlark_cli, starlark_env));
+        let starlark = eval
+            .heap()
+            .alloc((starlark_cli, starlark_env, starlark_worker));
 
         let action = UnregisteredRunAction {
             category,
@@ -711,6 +742,9 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
             no_outputs_cleanup,
             allow_cache_upload,
             force_full_hybrid_if_capable,
+            local_sandbox: local_sandbox.into_option(),
+            worker_protocol,
+            worker_multiplex,
         };
         this.state().register_action(
             artifacts.inputs,
@@ -407,6 +407,10 @@ async fn command_details(
             queue_time: command.timing.re_queue_time.and_then(|d| d.try_into().ok()),
         }
         .into(),
+        CommandExecutionKind::LocalActionCache { digest } => buck2_data::LocalActionCacheHit {
+            action_digest: digest.to_string(),
+        }
+        .into(),
     });
 
     buck2_data::CommandExecutionDetails {
@@ -10,6 +10,7 @@
 //! Common utilities for bxl
 use std::sync::Arc;
 
+use buck2_common::executor_config::CacheUploadBehavior;
 use buck2_common::executor_config::CommandExecutorConfig;
 use buck2_common::executor_config::CommandGenerationOptions;
 use buck2_common::executor_config::Executor;
@@ -27,7 +28,10 @@ pub static EXECUTION_PLATFORM: Lazy<ExecutionPlatformResolution> = Lazy::new(||
     ExecutionPlatformResolution::new(
         Some(ExecutionPlatform::legacy_execution_platform(
             Arc::new(CommandExecutorConfig {
-                executor: Executor::Local(LocalExecutorOptions {}),
+                executor: Executor::Local {
+                    options: LocalExecutorOptions::default(),
+                    cache_upload_behavior: CacheUploadBehavior::Disabled,
+                },
                 options: CommandGenerationOptions {
                     path_separator: PathSeparatorKind::system_default(),
                     output_paths_behavior: Default::default(),
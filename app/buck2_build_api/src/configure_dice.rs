@@ -16,6 +16,8 @@ use buck2_common::legacy_configs::dice::SetLegacyConfigs;
 use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_execute::digest_config::DigestConfig;
 use buck2_execute::digest_config::SetDigestConfig;
+use buck2_interpreter::module_cache::SetStarlarkModuleCache;
+use buck2_interpreter::module_cache::StarlarkModuleCache;
 use dice::DetectCycles;
 use dice::Dice;
 use dice::WhichDice;
@@ -26,6 +28,7 @@ pub async fn configure_dice_for_buck(
     io: Arc<dyn IoProvider>,
     digest_config: DigestConfig,
     root_config: Option<&LegacyBuckConfig>,
+    starlark_module_cache: Option<Arc<StarlarkModuleCache>>,
     detect_cycles: Option<DetectCycles>,
     which_dice: Option<WhichDice>,
 ) -> anyhow::Result<Arc<Dice>> {
@@ -56,6 +59,9 @@ pub async fn configure_dice_for_buck(
     };
     dice.set_io_provider(io);
     dice.set_digest_config(digest_config);
+    if let Some(starlark_module_cache) = starlark_module_cache {
+        dice.set_starlark_module_cache(starlark_module_cache);
+    }
 
     let dice = dice.build(detect_cycles);
     let mut dice_ctx = dice.updater();
@@ -78,10 +78,13 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
     /// * `allow_limited_hybrid_fallbacks`: Whether to allow fallbacks
     /// * `allow_hybrid_fallbacks_on_failure`: Whether to allow fallbacks when the result is failure (i.e. the command failed on the primary, but the infra worked)
     /// * `use_windows_path_separators`: Whether to use Windows path separators in command line arguments
-    /// * `allow_cache_uploads`: Whether to upload local actions to the RE cache
+    /// * `allow_cache_uploads`: Whether to upload local actions to the RE cache, or to the local
+    /// action cache if it is enabled
     /// * `max_cache_upload_mebibytes`: Maximum size to upload in cache uploads
     /// * `experimental_low_pass_filter`: Whether to use the experimental low pass filter
     /// * `remote_output_paths`: How to express output paths to RE
+    /// * `use_local_sandbox`: Whether to run local actions in a sandbox which only exposes their
+    /// inputs and outputs. Only supported on Linux
     #[starlark(type = "command_executor_config")]
     fn CommandExecutorConfig<'v>(
         local_enabled: bool,
@@ -104,6 +107,7 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
         >,
         #[starlark(default = false, require = named)] experimental_low_pass_filter: bool,
         #[starlark(default = NoneOr::None, require = named)] remote_output_paths: NoneOr<&str>,
+        #[starlark(default = false, require = named)] use_local_sandbox: bool,
         heap: &'v Heap,
     ) -> anyhow::Result<Value<'v>> {
         let command_executor_config = {
@@ -144,7 +148,9 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
             };
 
             let local_options = if local_enabled {
-                Some(LocalExecutorOptions {})
+                Some(LocalExecutorOptions {
+                    use_sandbox: use_local_sandbox,
+                })
             } else {
                 None
             };
@@ -249,7 +255,10 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
                     cache_upload_behavior,
                     remote_cache_enabled: true,
                 },
-                (Some(local), None, false) => Executor::Local(local),
+                (Some(local), None, false) => Executor::Local {
+                    options: local,
+                    cache_upload_behavior,
+                },
                 (None, None, _) => {
                     return Err(CommandExecutorConfigErrors::NoExecutor.into());
                 }
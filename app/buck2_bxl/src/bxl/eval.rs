@@ -22,6 +22,7 @@ use buck2_build_api::deferred::types::DeferredTable;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::dice::data::HasIoProvider;
 use buck2_common::events::HasEvents;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
 use buck2_common::target_aliases::BuckConfigTargetAliasResolver;
 use buck2_common::target_aliases::HasTargetAliasResolver;
 use buck2_core::cells::CellAliasResolver;
@@ -41,6 +42,7 @@ use buck2_interpreter::starlark_profiler::StarlarkProfileModeOrInstrumentation;
 use buck2_interpreter::starlark_profiler::StarlarkProfiler;
 use buck2_interpreter::starlark_profiler::StarlarkProfilerOrInstrumentation;
 use buck2_interpreter_for_build::interpreter::calculation::InterpreterCalculation;
+use buck2_interpreter_for_build::interpreter::eval_budget::StarlarkEvalBudget;
 use buck2_interpreter_for_build::interpreter::print_handler::EventDispatcherPrintHandler;
 use clap::ErrorKind;
 use dashmap::DashMap;
@@ -90,6 +92,10 @@ pub async fn eval(
 
     let digest_config = ctx.global_data().get_digest_config();
 
+    // The BXL function blocks on builds while it runs, so it has its own timeout.
+    let eval_budget =
+        StarlarkEvalBudget::from_buckconfig_for_bxl(&ctx.get_legacy_root_config_on_dice().await?)?;
+
     // The bxl function may trigger async operations like builds, analysis, parsing etc, but those
     // will be blocking calls so that starlark can remain synchronous.
     // To avoid blocking a tokio thread, we spawn bxl as a blocking tokio task
@@ -154,6 +160,7 @@ pub async fn eval(
                     let bxl_function_name = key.label().name.clone();
                     let frozen_callable = get_bxl_callable(key.label(), &bxl_module)?;
                     eval.set_print_handler(&print);
+                    eval_budget.apply(&mut eval);
 
                     let bxl_ctx = BxlContext::new(
                         eval.heap(),
@@ -96,6 +96,7 @@ enum BuckProfileMode {
     Bytecode,
     BytecodePairs,
     Typecheck,
+    Coverage,
 }
 
 #[derive(Debug, clap::Parser)]
@@ -148,6 +149,8 @@ pub struct ProfileCommonOptions {
     /// This is probably what you want when profiling analysis.
     ///
     /// `-allocated` means allocated memory, including memory which is later garbage collected.
+    ///
+    /// `coverage` writes LCOV, or Cobertura XML if the output path ends in `.xml`.
     #[clap(long, short = 'm', value_enum)]
     mode: BuckProfileMode,
 }
@@ -168,6 +171,7 @@ fn profile_mode_to_profile(mode: &BuckProfileMode) -> Profiler {
         BuckProfileMode::Bytecode => Profiler::Bytecode,
         BuckProfileMode::BytecodePairs => Profiler::BytecodePairs,
         BuckProfileMode::Typecheck => Profiler::Typecheck,
+        BuckProfileMode::Coverage => Profiler::Coverage,
     }
 }
 
@@ -125,7 +125,7 @@ fn eprint_command_details(
                 remote_command.action_digest
             )?;
         }
-        Some(Command::OmittedLocalCommand(..)) | None => {
+        Some(Command::OmittedLocalCommand(..)) | Some(Command::LocalActionCacheHit(..)) | None => {
             // Nothing to show in this case.
         }
     };
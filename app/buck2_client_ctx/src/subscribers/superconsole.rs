@@ -785,7 +785,7 @@ fn lines_for_command_details(
                 )]));
             }
         }
-        Some(Command::OmittedLocalCommand(..)) | None => {
+        Some(Command::OmittedLocalCommand(..)) | Some(Command::LocalActionCacheHit(..)) | None => {
             // Nothing to show in this case.
         }
     };
@@ -171,7 +171,7 @@ pub enum DigestAlgorithm {
 }
 
 impl DigestAlgorithm {
-    fn kind(self) -> DigestAlgorithmKind {
+    pub fn kind(self) -> DigestAlgorithmKind {
         match self {
             Self::Sha1 => DigestAlgorithmKind::Sha1,
             Self::Sha256 => DigestAlgorithmKind::Sha256,
@@ -20,8 +20,11 @@ use internment_tweaks::Intern;
 use internment_tweaks::StaticInterner;
 use once_cell::sync::Lazy;
 
-#[derive(Debug, Eq, Hash, PartialEq, Clone, Dupe, Allocative)]
-pub struct LocalExecutorOptions {}
+#[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Dupe, Allocative)]
+pub struct LocalExecutorOptions {
+    /// Run actions in a sandbox which only exposes their inputs and outputs (Linux only).
+    pub use_sandbox: bool,
+}
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Dupe, Display, Allocative)]
 pub struct RemoteExecutorUseCase(Intern<String>);
@@ -76,8 +79,12 @@ pub enum RemoteEnabledExecutor {
 
 #[derive(Debug, Eq, PartialEq, Clone, Hash, Allocative)]
 pub enum Executor {
-    /// This executor only runs local commands.
-    Local(LocalExecutorOptions),
+    /// This executor only runs local commands. The cache upload behavior controls writes to the
+    /// local action cache, if it is enabled.
+    Local {
+        options: LocalExecutorOptions,
+        cache_upload_behavior: CacheUploadBehavior,
+    },
 
     /// This executor interacts with a RE backend. It may use that to read or write to caches, or
     /// to execute commands.
@@ -184,7 +191,10 @@ pub enum HybridExecutionLevel {
 impl CommandExecutorConfig {
     pub fn testing_local() -> Arc<CommandExecutorConfig> {
         Arc::new(CommandExecutorConfig {
-            executor: Executor::Local(LocalExecutorOptions {}),
+            executor: Executor::Local {
+                options: LocalExecutorOptions::default(),
+                cache_upload_behavior: CacheUploadBehavior::Disabled,
+            },
             options: CommandGenerationOptions {
                 path_separator: PathSeparatorKind::system_default(),
                 output_paths_behavior: Default::default(),
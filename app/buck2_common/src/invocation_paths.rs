@@ -44,6 +44,14 @@ pub(crate) fn home_buck_dir() -> anyhow::Result<&'static AbsNormPath> {
     Ok(&Lazy::force(&DIR).as_ref()?)
 }
 
+/// The default location of the local action cache. This is under the user cache dir rather than
+/// buck-out, so that results are kept across `buck2 clean` and shared by all checkouts.
+pub fn local_action_cache_dir() -> anyhow::Result<AbsNormPathBuf> {
+    let cache = dirs::cache_dir().context("Expected a user cache directory to be available")?;
+    let cache = AbsNormPathBuf::new(cache).context("Expected an absolute user cache directory")?;
+    Ok(cache.join(ForwardRelativePath::new("buck2/action_cache")?))
+}
+
 #[derive(Clone, Allocative)]
 pub struct InvocationPaths {
     pub roots: InvocationRoots,
@@ -196,12 +204,31 @@ impl InvocationPaths {
             .join(ForwardRelativePath::unchecked_new("forkserver"))
     }
 
+    /// Where persistent workers started by the local executor write their stderr.
+    pub fn worker_log_dir(&self) -> AbsNormPathBuf {
+        self.buck_out_path()
+            .join(ForwardRelativePath::unchecked_new("workers"))
+    }
+
+    /// Subdirectory of `cache_dir` storing evaluated `.bzl` files
+    pub fn starlark_module_cache_path(&self) -> AbsNormPathBuf {
+        self.cache_dir_path()
+            .join(self.starlark_module_cache_dir_name())
+    }
+
     pub fn materializer_state_dir_name(&self) -> &FileName {
         FileName::unchecked_new("materializer_state")
     }
 
+    pub fn starlark_module_cache_dir_name(&self) -> &FileName {
+        FileName::unchecked_new("starlark_module_cache")
+    }
+
     pub fn valid_cache_dirs(&self) -> Vec<&FileName> {
-        vec![self.materializer_state_dir_name()]
+        vec![
+            self.materializer_state_dir_name(),
+            self.starlark_module_cache_dir_name(),
+        ]
     }
 }
 
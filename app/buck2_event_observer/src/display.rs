@@ -552,7 +552,9 @@ fn failure_reason_for_command_execution(
 
     let locality = match command.command {
         Some(Command::RemoteCommand(..)) => "Remote ",
-        Some(Command::LocalCommand(..)) | Some(Command::OmittedLocalCommand(..)) => "Local ",
+        Some(Command::LocalCommand(..))
+        | Some(Command::OmittedLocalCommand(..))
+        | Some(Command::LocalActionCacheHit(..)) => "Local ",
         None => "",
     };
 
@@ -159,6 +159,18 @@ impl ReState {
                 last.re_get_digest_expirations_finished_successfully,
                 last.re_get_digest_expirations_finished_with_error,
             )?);
+            r.extend(self.render_detailed_items(
+                "bytestream_uploads",
+                last.re_bytestream_uploads_started,
+                last.re_bytestream_uploads_finished_successfully,
+                last.re_bytestream_uploads_finished_with_error,
+            )?);
+            r.extend(self.render_detailed_items(
+                "bytestream_downloads",
+                last.re_bytestream_downloads_started,
+                last.re_bytestream_downloads_finished_successfully,
+                last.re_bytestream_downloads_finished_with_error,
+            )?);
         }
         Ok(r)
     }
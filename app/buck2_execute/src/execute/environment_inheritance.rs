@@ -12,7 +12,7 @@ use std::ffi::OsString;
 use dupe::Dupe;
 use once_cell::sync::OnceCell;
 
-#[derive(Copy, Clone, Dupe, Debug)]
+#[derive(Copy, Clone, Dupe, Debug, PartialEq, Eq, Hash)]
 pub struct EnvironmentInheritance {
     clear: bool,
     values: &'static [(&'static str, OsString)],
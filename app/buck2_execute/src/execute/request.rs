@@ -253,6 +253,10 @@ pub struct CommandExecutionRequest {
     force_full_hybrid_if_capable: bool,
     /// Whether to disable capturing performance counters for this execution.
     disable_miniperf: bool,
+    /// Whether to run in a sandbox when executing locally. Defaults to the executor setting.
+    local_sandbox: Option<bool>,
+    /// A persistent worker which can run this command when executing locally.
+    worker: Option<WorkerSpec>,
 }
 
 impl CommandExecutionRequest {
@@ -276,6 +280,8 @@ impl CommandExecutionRequest {
             allow_cache_upload: false,
             force_full_hybrid_if_capable: false,
             disable_miniperf: false,
+            local_sandbox: None,
+            worker: None,
         }
     }
 
@@ -403,6 +409,50 @@ impl CommandExecutionRequest {
     pub fn disable_miniperf(&self) -> bool {
         self.disable_miniperf
     }
+
+    pub fn with_local_sandbox(mut self, local_sandbox: Option<bool>) -> Self {
+        self.local_sandbox = local_sandbox;
+        self
+    }
+
+    pub fn local_sandbox(&self) -> Option<bool> {
+        self.local_sandbox
+    }
+
+    pub fn with_worker(mut self, worker: Option<WorkerSpec>) -> Self {
+        self.worker = worker;
+        self
+    }
+
+    pub fn worker(&self) -> Option<&WorkerSpec> {
+        self.worker.as_ref()
+    }
+}
+
+/// How requests and responses are encoded when talking to a persistent worker.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Dupe, Display, Allocative)]
+pub enum WorkerProtocol {
+    /// One JSON `WorkRequest` / `WorkResponse` per line.
+    #[display(fmt = "json")]
+    Json,
+    /// Length-delimited protobuf `WorkRequest` / `WorkResponse` messages.
+    #[display(fmt = "proto")]
+    Proto,
+}
+
+/// A persistent worker which can run a command. The arguments of the command start with `exe`,
+/// which is used to start the worker, and the remaining arguments are sent in a request. Executors
+/// which don't support workers run the command as is.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Allocative)]
+pub struct WorkerSpec {
+    /// The worker executable and its startup arguments.
+    pub exe: Vec<String>,
+    pub protocol: WorkerProtocol,
+    /// Whether a single worker can process several requests concurrently.
+    pub multiplex: bool,
+    /// A digest of the artifacts `exe` refers to. Workers are only reused while it is unchanged,
+    /// since a rebuilt tool has the same command line.
+    pub inputs_digest: Option<TrackedFileDigest>,
 }
 
 /// Is an output a file or a directory
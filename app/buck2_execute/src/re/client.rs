@@ -73,6 +73,7 @@ use crate::re::metadata::RemoteExecutionMetadataExt;
 use crate::re::uploader::UploadStats;
 use crate::re::uploader::Uploader;
 
+#[derive(Default)]
 pub struct RemoteExecutionClientOpStats {
     pub started: u32,
     pub finished_successfully: u32,
@@ -101,6 +102,9 @@ pub struct RemoteExecutionClientStats {
     pub materializes: RemoteExecutionClientOpStats,
     pub write_action_results: RemoteExecutionClientOpStats,
     pub get_digest_expirations: RemoteExecutionClientOpStats,
+    /// Blobs too large for batch requests, which we transfer through the ByteStream API.
+    pub bytestream_uploads: RemoteExecutionClientOpStats,
+    pub bytestream_downloads: RemoteExecutionClientOpStats,
 }
 
 #[derive(Clone, Dupe, Allocative)]
@@ -236,6 +240,29 @@ impl RemoteExecutionClient {
         .await
     }
 
+    /// Fail if the RE server doesn't support the digest algorithm buck2 is configured to use,
+    /// as every request would fail otherwise.
+    pub fn check_digest_config(&self, digest_config: DigestConfig) -> anyhow::Result<()> {
+        #[cfg(fbcode_build)]
+        {
+            let _unused = digest_config;
+            Ok(())
+        }
+
+        #[cfg(not(fbcode_build))]
+        {
+            let algorithm = digest_config
+                .cas_digest_config()
+                .preferred_algorithm()
+                .kind();
+            self.data
+                .client
+                .client()
+                .capabilities()
+                .check_digest_function(&algorithm.to_string())
+        }
+    }
+
     fn decorate_error(&self, source: anyhow::Error) -> anyhow::Error {
         source.context(format!(
             "Remote Execution Error ({})",
@@ -443,6 +470,7 @@ impl RemoteExecutionClient {
             .checked_sub(self.data.initial_network_stats.downloaded)
             .and_then(|d| u64::try_from(d).ok())
             .context("Overflow calculating downloaded bytes")?;
+        let (bytestream_uploads, bytestream_downloads) = self.get_bytestream_stats();
         Ok(RemoteExecutionClientStats {
             uploaded,
             downloaded,
@@ -457,8 +485,29 @@ impl RemoteExecutionClient {
             get_digest_expirations: RemoteExecutionClientOpStats::from(
                 &self.data.get_digest_expirations,
             ),
+            bytestream_uploads,
+            bytestream_downloads,
         })
     }
+
+    /// Only the OSS client uses the ByteStream API, so that's the only one that counts transfers.
+    fn get_bytestream_stats(&self) -> (RemoteExecutionClientOpStats, RemoteExecutionClientOpStats) {
+        #[cfg(fbcode_build)]
+        {
+            Default::default()
+        }
+
+        #[cfg(not(fbcode_build))]
+        {
+            let stats = self.data.client.client().get_bytestream_stats();
+            let convert = |stats: remote_execution::TransferStats| RemoteExecutionClientOpStats {
+                started: stats.started,
+                finished_successfully: stats.finished_successfully,
+                finished_with_error: stats.finished_with_error,
+            };
+            (convert(stats.uploads), convert(stats.downloads))
+        }
+    }
 }
 
 #[derive(Allocative)]
@@ -86,11 +86,12 @@ struct RemoteExecutionConfig {
     static_metadata: Arc<RemoteExecutionStaticMetadata>,
     logs_dir_path: Option<String>,
     buck_out_path: String,
+    digest_config: DigestConfig,
 }
 
 impl RemoteExecutionConfig {
     async fn connect_now(&self) -> anyhow::Result<RemoteExecutionClient> {
-        RemoteExecutionClient::new_retry(
+        let client = RemoteExecutionClient::new_retry(
             self.fb,
             self.skip_remote_cache,
             self.connection_retries,
@@ -98,7 +99,10 @@ impl RemoteExecutionConfig {
             self.logs_dir_path.as_deref(),
             &self.buck_out_path,
         )
-        .await
+        .await?;
+        // Not worth retrying: the server won't change its mind.
+        client.check_digest_config(self.digest_config)?;
+        Ok(client)
     }
 }
 
@@ -198,6 +202,7 @@ impl ReConnectionManager {
         static_metadata: Arc<RemoteExecutionStaticMetadata>,
         logs_dir_path: Option<String>,
         buck_out_path: String,
+        digest_config: DigestConfig,
     ) -> Self {
         Self {
             data: RwLock::new(Weak::new()),
@@ -208,6 +213,7 @@ impl ReConnectionManager {
                 static_metadata,
                 logs_dir_path,
                 buck_out_path,
+                digest_config,
             },
         }
     }
@@ -19,6 +19,7 @@ use std::time::SystemTime;
 
 use anyhow::Context as _;
 use async_trait::async_trait;
+use buck2_common::executor_config::LocalExecutorOptions;
 use buck2_common::file_ops::FileDigest;
 use buck2_common::file_ops::FileMetadata;
 use buck2_common::file_ops::TrackedFileDigest;
@@ -30,6 +31,7 @@ use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use buck2_core::fs::paths::file_name::FileNameBuf;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_core::quiet_soft_error;
 use buck2_execute::artifact_value::ArtifactValue;
 use buck2_execute::digest_config::DigestConfig;
@@ -57,6 +59,7 @@ use buck2_execute::execute::request::CommandExecutionOutput;
 use buck2_execute::execute::request::CommandExecutionOutputRef;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::request::WorkerSpec;
 use buck2_execute::execute::result::CommandExecutionMetadata;
 use buck2_execute::execute::result::CommandExecutionResult;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
@@ -83,6 +86,8 @@ use more_futures::cancellation::CancellationContext;
 use thiserror::Error;
 use tracing::info;
 
+use crate::executors::worker::WorkerPool;
+
 #[derive(Debug, Error)]
 enum LocalExecutionError {
     #[error("Args list was empty")]
@@ -90,6 +95,17 @@ enum LocalExecutionError {
 
     #[error("Trying to execute a remote-only action on a local executor")]
     RemoteOnlyAction,
+
+    #[error("Sandboxed local execution requires the forkserver")]
+    SandboxRequiresForkserver,
+}
+
+/// Absolute paths exposed to an action running in a sandbox.
+pub struct SandboxPaths {
+    /// Exposed read-only.
+    pub inputs: Vec<AbsNormPathBuf>,
+    /// Exposed read-write.
+    pub outputs: Vec<AbsNormPathBuf>,
 }
 
 #[derive(Clone)]
@@ -103,6 +119,8 @@ pub struct LocalExecutor {
     forkserver: Option<ForkserverClient>,
     #[allow(unused)]
     knobs: ExecutorGlobalKnobs,
+    options: LocalExecutorOptions,
+    worker_pool: Arc<WorkerPool>,
 }
 
 impl LocalExecutor {
@@ -114,6 +132,8 @@ impl LocalExecutor {
         root: AbsNormPathBuf,
         forkserver: Option<ForkserverClient>,
         knobs: ExecutorGlobalKnobs,
+        options: LocalExecutorOptions,
+        worker_pool: Arc<WorkerPool>,
     ) -> Self {
         Self {
             artifact_fs,
@@ -123,6 +143,8 @@ impl LocalExecutor {
             root,
             forkserver,
             knobs,
+            options,
+            worker_pool,
         }
     }
 
@@ -138,6 +160,7 @@ impl LocalExecutor {
         env_inheritance: Option<&'a EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         disable_miniperf: bool,
+        sandbox: Option<SandboxPaths>,
     ) -> impl futures::future::Future<
         Output = anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>,
     > + Send
@@ -164,18 +187,23 @@ impl LocalExecutor {
                             env_inheritance,
                             liveliness_observer,
                             self.knobs.enable_miniperf && !disable_miniperf,
+                            sandbox,
                         )
                         .await
                     }
 
                     #[cfg(not(unix))]
                     {
-                        let _unused = (forkserver, disable_miniperf);
+                        let _unused = (forkserver, disable_miniperf, sandbox);
                         Err(anyhow::anyhow!("Forkserver is not supported off-UNIX"))
                     }
                 }
 
                 None => {
+                    if sandbox.is_some() {
+                        return Err(LocalExecutionError::SandboxRequiresForkserver.into());
+                    }
+
                     let mut cmd = background_command(exe);
                     cmd.current_dir(working_directory);
                     cmd.args(args);
@@ -201,6 +229,38 @@ impl LocalExecutor {
         }
     }
 
+    async fn exec_worker(
+        &self,
+        worker: &WorkerSpec,
+        request: &CommandExecutionRequest,
+        liveliness_observer: &dyn LivelinessObserver,
+    ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)> {
+        let args = request
+            .args()
+            .strip_prefix(worker.exe.as_slice())
+            .context("Command does not start with the worker command line")?;
+        let inputs = worker_inputs(&self.artifact_fs, request)?;
+        // Same as the environment of other commands, except for the temporary directory, which
+        // the worker pool sets per worker.
+        let daemon_uuid = buck2_events::daemon_id::DAEMON_UUID.to_string();
+        self.worker_pool
+            .exec(
+                worker,
+                &self.root,
+                args,
+                &inputs,
+                request
+                    .env()
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str()))
+                    .chain(std::iter::once(("BUCK2_DAEMON_UUID", daemon_uuid.as_str()))),
+                request.local_environment_inheritance(),
+                request.timeout(),
+                liveliness_observer,
+            )
+            .await
+    }
+
     async fn exec_request(
         &self,
         action_digest: &ActionDigest,
@@ -289,6 +349,15 @@ impl LocalExecutor {
             return manager.error("prepare_output_dirs_failed", e);
         };
 
+        let sandbox = if request.local_sandbox().unwrap_or(self.options.use_sandbox) {
+            match sandbox_paths(&self.artifact_fs, request, scratch_dir.as_ref()) {
+                Ok(sandbox) => Some(sandbox),
+                Err(e) => return manager.error("sandbox_paths_failed", e),
+            }
+        } else {
+            None
+        };
+
         info!(
             "Local execution command line:\n```\n$ {}\n```",
             args.join(" "),
@@ -365,19 +434,45 @@ impl LocalExecutor {
                 let execution_start = Instant::now();
                 let start_time = SystemTime::now();
 
-                let env = iter_env().map(|(k, v)| (k, v.into_os_str()));
-                let r = self
-                    .exec(
-                        &args[0],
-                        &args[1..],
-                        env,
-                        request.working_directory(),
-                        request.timeout(),
-                        request.local_environment_inheritance(),
-                        liveliness_observer,
-                        request.disable_miniperf(),
-                    )
-                    .await;
+                // Workers run in the project root and outside of any sandbox, so commands which
+                // need either run as a new process.
+                let worker_res = match request.worker() {
+                    Some(worker) if sandbox.is_none() && request.working_directory().is_none() => {
+                        match self
+                            .exec_worker(worker, request, &liveliness_observer)
+                            .await
+                        {
+                            Ok(res) => Some(res),
+                            Err(e) => {
+                                tracing::warn!(
+                                    "Failed to run command with a worker, running it directly: {:#}",
+                                    e
+                                );
+                                None
+                            }
+                        }
+                    }
+                    _ => None,
+                };
+
+                let r = match worker_res {
+                    Some(res) => Ok(res),
+                    None => {
+                        let env = iter_env().map(|(k, v)| (k, v.into_os_str()));
+                        self.exec(
+                            &args[0],
+                            &args[1..],
+                            env,
+                            request.working_directory(),
+                            request.timeout(),
+                            request.local_environment_inheritance(),
+                            liveliness_observer,
+                            request.disable_miniperf(),
+                            sandbox,
+                        )
+                        .await
+                    }
+                };
 
                 let execution_time = execution_start.elapsed();
 
@@ -494,31 +589,7 @@ impl LocalExecutor {
             }
         }
 
-        let mut to_declare = vec![];
-        let mut mapped_outputs = IndexMap::with_capacity(entries.len());
-
-        for (output, path) in entries {
-            let value = extract_artifact_value(&builder, &path, digest_config)?;
-            if let Some(value) = value {
-                match output {
-                    CommandExecutionOutput::BuildArtifact { .. } => {
-                        to_declare.push((path, value.dupe()));
-                    }
-                    CommandExecutionOutput::TestPath { .. } => {
-                        // Don't declare those as we don't currently have any form of GC so this
-                        // would take up space for nothing, and most importantly, we will never
-                        // need them to be in materializer state for e.g. matching as nothing
-                        // should depend on them.
-                    }
-                }
-
-                mapped_outputs.insert(output, value);
-            }
-        }
-
-        self.materializer.declare_existing(to_declare).await?;
-
-        Ok(mapped_outputs)
+        declare_output_values(&*self.materializer, &builder, entries, digest_config).await
     }
 
     fn build_entry_from_disk(
@@ -714,6 +785,70 @@ pub async fn materialize_inputs(
     Ok(())
 }
 
+/// The project-relative paths of the inputs of `request`, which are sent to workers.
+fn worker_inputs(
+    artifact_fs: &ArtifactFs,
+    request: &CommandExecutionRequest,
+) -> anyhow::Result<Vec<String>> {
+    let mut inputs = vec![];
+    for input in request.inputs() {
+        match input {
+            CommandExecutionInput::Artifact(group) => {
+                for (artifact, _) in group.iter() {
+                    inputs.push(artifact.resolve_path(artifact_fs)?.to_string());
+                }
+            }
+            CommandExecutionInput::ActionMetadata(metadata) => {
+                let path = artifact_fs
+                    .buck_out_path_resolver()
+                    .resolve_gen(&metadata.path);
+                inputs.push(path.to_string());
+            }
+        }
+    }
+    Ok(inputs)
+}
+
+/// The paths to expose when running `request` in a sandbox: its inputs, the directories its
+/// outputs are created in, and its scratch directory.
+fn sandbox_paths(
+    artifact_fs: &ArtifactFs,
+    request: &CommandExecutionRequest,
+    scratch_dir: Option<&ProjectRelativePathBuf>,
+) -> anyhow::Result<SandboxPaths> {
+    let project_fs = artifact_fs.fs();
+
+    let mut inputs = vec![];
+    for input in request.inputs() {
+        match input {
+            CommandExecutionInput::Artifact(group) => {
+                for (artifact, _) in group.iter() {
+                    inputs.push(project_fs.resolve(&artifact.resolve_path(artifact_fs)?));
+                }
+            }
+            CommandExecutionInput::ActionMetadata(metadata) => {
+                let path = artifact_fs
+                    .buck_out_path_resolver()
+                    .resolve_gen(&metadata.path);
+                inputs.push(project_fs.resolve(&path));
+            }
+        }
+    }
+
+    let mut outputs: Vec<_> = request
+        .outputs()
+        .filter_map(|output| {
+            output
+                .resolve(artifact_fs)
+                .path_to_create()
+                .map(|path| project_fs.resolve(path))
+        })
+        .collect();
+    outputs.extend(scratch_dir.map(|path| project_fs.resolve(path)));
+
+    Ok(SandboxPaths { inputs, outputs })
+}
+
 async fn check_inputs(
     manager: CommandExecutionManagerWithClaim,
     artifact_fs: &ArtifactFs,
@@ -775,6 +910,41 @@ pub async fn materialize_build_outputs_from_previous_run(
     materializer.ensure_materialized(paths).await
 }
 
+/// Compute the values of outputs that were written to disk and inserted into `builder` (which
+/// should otherwise contain the action's inputs), and declare them to the materializer.
+pub(crate) async fn declare_output_values(
+    materializer: &dyn Materializer,
+    builder: &ActionDirectoryBuilder,
+    entries: Vec<(CommandExecutionOutput, ProjectRelativePathBuf)>,
+    digest_config: DigestConfig,
+) -> anyhow::Result<IndexMap<CommandExecutionOutput, ArtifactValue>> {
+    let mut to_declare = vec![];
+    let mut mapped_outputs = IndexMap::with_capacity(entries.len());
+
+    for (output, path) in entries {
+        let value = extract_artifact_value(builder, &path, digest_config)?;
+        if let Some(value) = value {
+            match output {
+                CommandExecutionOutput::BuildArtifact { .. } => {
+                    to_declare.push((path, value.dupe()));
+                }
+                CommandExecutionOutput::TestPath { .. } => {
+                    // Don't declare those as we don't currently have any form of GC so this
+                    // would take up space for nothing, and most importantly, we will never
+                    // need them to be in materializer state for e.g. matching as nothing
+                    // should depend on them.
+                }
+            }
+
+            mapped_outputs.insert(output, value);
+        }
+    }
+
+    materializer.declare_existing(to_declare).await?;
+
+    Ok(mapped_outputs)
+}
+
 /// Create any output dirs requested by the command. Note that this makes no effort to delete
 /// the output paths first. Eventually it should, but right now this happens earlier. This
 /// would be a separate refactor.
@@ -906,6 +1076,7 @@ mod unix {
         env_inheritance: Option<&EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         enable_miniperf: bool,
+        sandbox: Option<SandboxPaths>,
     ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)> {
         let exe = exe.as_ref();
 
@@ -921,6 +1092,18 @@ mod unix {
             env: vec![],
             timeout: command_timeout.try_map(|d| d.try_into())?,
             enable_miniperf,
+            sandbox: sandbox.map(|sandbox| buck2_forkserver_proto::Sandbox {
+                inputs: sandbox
+                    .inputs
+                    .iter()
+                    .map(|path| path.as_os_str().as_bytes().to_vec())
+                    .collect(),
+                outputs: sandbox
+                    .outputs
+                    .iter()
+                    .map(|path| path.as_os_str().as_bytes().to_vec())
+                    .collect(),
+            }),
         };
         apply_local_execution_environment(&mut req, working_directory, env, env_inheritance);
         forkserver
@@ -989,12 +1172,13 @@ mod tests {
     use buck2_core::fs::buck_out_path::BuckOutPathResolver;
     use buck2_core::fs::project::ProjectRoot;
     use buck2_core::fs::project::ProjectRootTemp;
-    use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
     use buck2_execute::execute::blocking::testing::DummyBlockingExecutor;
     use buck2_execute::materialize::nodisk::NoDiskMaterializer;
     use host_sharing::HostSharingStrategy;
 
     use super::*;
+    use crate::executors::worker::DEFAULT_MAX_WORKERS_PER_KEY;
+    use crate::executors::worker::DEFAULT_WORKER_IDLE_TIMEOUT;
 
     #[tokio::test]
     async fn test_gather_output() -> anyhow::Result<()> {
@@ -1107,6 +1291,12 @@ mod tests {
             temp.path().root().to_buf(),
             None,
             ExecutorGlobalKnobs::default(),
+            LocalExecutorOptions::default(),
+            WorkerPool::new(
+                temp.path().root().to_buf(),
+                DEFAULT_MAX_WORKERS_PER_KEY,
+                DEFAULT_WORKER_IDLE_TIMEOUT,
+            ),
         );
 
         Ok((executor, temp.path().root().to_buf(), temp))
@@ -1127,6 +1317,7 @@ mod tests {
                 None,
                 NoopLivelinessObserver::create(),
                 false,
+                None,
             )
             .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
@@ -1163,6 +1354,7 @@ mod tests {
                 Some(&EnvironmentInheritance::empty()),
                 NoopLivelinessObserver::create(),
                 false,
+                None,
             )
             .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
@@ -1170,4 +1362,29 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_exec_cmd_sandbox_requires_forkserver() -> anyhow::Result<()> {
+        let (executor, root, _tmpdir) = test_executor()?;
+
+        let res = executor
+            .exec(
+                "true",
+                std::iter::empty::<&str>(),
+                &HashMap::<String, String>::default(),
+                None,
+                None,
+                None,
+                NoopLivelinessObserver::create(),
+                false,
+                Some(SandboxPaths {
+                    inputs: vec![],
+                    outputs: vec![root],
+                }),
+            )
+            .await;
+        assert!(res.is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,1027 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! An action cache on local disk.
+//!
+//! This is the local counterpart to the RE action cache used by the [`CachingExecutor`]: results
+//! of actions that ran locally are stored keyed by their action digest, and the contents of their
+//! outputs are stored in a content-addressed directory next to them. This lets local-only builds
+//! reuse results after `buck2 clean` or from another checkout. The cache is bounded in size, and
+//! evicts the least recently used entries once it grows past that size.
+//!
+//! The layout on disk is:
+//!
+//! - `ac/<action digest>`: the action result, as JSON.
+//! - `cas/<file digest>`: the contents of output files and std streams.
+//! - `tmp/`: entries being written, which are renamed into place once complete.
+//!
+//! A cache hit is only reported once all the outputs have been copied out of the cache and their
+//! digests checked, so that a missing or corrupt entry runs the action instead of failing it.
+//!
+//! [`CachingExecutor`]: crate::executors::caching::CachingExecutor
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_common::cas_digest::CasDigest;
+use buck2_common::executor_config::CacheUploadBehavior;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileMetadata;
+use buck2_common::file_ops::TrackedFileDigest;
+use buck2_core::directory::Directory;
+use buck2_core::directory::DirectoryEntry;
+use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::file_name::FileName;
+use buck2_core::fs::paths::file_name::FileNameBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_execute::artifact_value::ArtifactValue;
+use buck2_execute::digest_config::DigestConfig;
+use buck2_execute::directory::insert_entry;
+use buck2_execute::directory::new_symlink;
+use buck2_execute::directory::ActionDirectoryBuilder;
+use buck2_execute::directory::ActionDirectoryEntry;
+use buck2_execute::directory::ActionDirectoryMember;
+use buck2_execute::execute::action_digest::ActionDigest;
+use buck2_execute::execute::blocking::BlockingExecutor;
+use buck2_execute::execute::executor_stage_async;
+use buck2_execute::execute::inputs_directory::inputs_directory;
+use buck2_execute::execute::kind::CommandExecutionKind;
+use buck2_execute::execute::manager::CommandExecutionManager;
+use buck2_execute::execute::manager::CommandExecutionManagerExt;
+use buck2_execute::execute::output::CommandStdStreams;
+use buck2_execute::execute::prepared::PreparedCommand;
+use buck2_execute::execute::prepared::PreparedCommandExecutor;
+use buck2_execute::execute::request::CommandExecutionOutput;
+use buck2_execute::execute::request::CommandExecutionRequest;
+use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::result::CommandExecutionMetadata;
+use buck2_execute::execute::result::CommandExecutionResult;
+use buck2_execute::execute::result::CommandExecutionStatus;
+use buck2_execute::materialize::materializer::Materializer;
+use dupe::Dupe;
+use indexmap::IndexMap;
+use more_futures::cancellation::CancellationContext;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::executors::local::create_output_dirs;
+use crate::executors::local::declare_output_values;
+
+const ACTION_CACHE_DIR: &str = "ac";
+const CAS_DIR: &str = "cas";
+const TMP_DIR: &str = "tmp";
+
+/// Entries in `tmp/` older than this were left behind by a daemon which died while writing them.
+const STALE_TMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, thiserror::Error)]
+enum LocalActionCacheError {
+    #[error("Blob `{expected}` in the local action cache is corrupt, its digest is `{actual}`")]
+    CorruptBlob {
+        expected: FileDigest,
+        actual: FileDigest,
+    },
+    #[error("Output `{0}` is missing from the local action cache entry")]
+    MissingOutput(ProjectRelativePathBuf),
+}
+
+/// An action result, as stored in the local action cache.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct CachedActionResult {
+    /// The outputs of the action, keyed by their project-relative path.
+    outputs: BTreeMap<String, CachedEntry>,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CachedEntry {
+    File {
+        digest: String,
+        executable: bool,
+    },
+    Symlink {
+        target: String,
+    },
+    Directory {
+        entries: BTreeMap<String, CachedEntry>,
+    },
+}
+
+/// Where to copy a blob from when storing it.
+enum BlobSource {
+    File(AbsNormPathBuf),
+    Bytes(Vec<u8>),
+}
+
+impl CachedEntry {
+    /// Describe an output of an action that ran locally, collecting the blobs it references from
+    /// `disk_path`. Returns `None` if the output cannot be cached, which is the case for symlinks
+    /// pointing outside the project.
+    fn new(
+        entry: DirectoryEntry<
+            &dyn Directory<ActionDirectoryMember, TrackedFileDigest>,
+            &ActionDirectoryMember,
+        >,
+        disk_path: AbsNormPathBuf,
+        blobs: &mut Vec<(FileDigest, BlobSource)>,
+    ) -> Option<Self> {
+        match entry {
+            DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) => {
+                let digest = f.digest.to_string();
+                blobs.push((f.digest.data().dupe(), BlobSource::File(disk_path)));
+                Some(Self::File {
+                    digest,
+                    executable: f.is_executable,
+                })
+            }
+            DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(s)) => Some(Self::Symlink {
+                target: s.target().to_string(),
+            }),
+            DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(..)) => None,
+            DirectoryEntry::Dir(d) => {
+                let mut entries = BTreeMap::new();
+                for (name, entry) in d.entries() {
+                    entries.insert(
+                        name.as_str().to_owned(),
+                        Self::new(entry, disk_path.join(name), blobs)?,
+                    );
+                }
+                Some(Self::Directory { entries })
+            }
+        }
+    }
+
+    fn visit_digests<'a>(&'a self, f: &mut impl FnMut(&'a str)) {
+        match self {
+            Self::File { digest, .. } => f(digest),
+            Self::Symlink { .. } => {}
+            Self::Directory { entries } => {
+                for entry in entries.values() {
+                    entry.visit_digests(f);
+                }
+            }
+        }
+    }
+
+    fn to_directory_entry(
+        &self,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<ActionDirectoryEntry<ActionDirectoryBuilder>> {
+        Ok(match self {
+            Self::File { digest, executable } => {
+                DirectoryEntry::Leaf(ActionDirectoryMember::File(FileMetadata {
+                    digest: TrackedFileDigest::new(
+                        parse_file_digest(digest, digest_config)?,
+                        digest_config.cas_digest_config(),
+                    ),
+                    is_executable: *executable,
+                }))
+            }
+            Self::Symlink { target } => DirectoryEntry::Leaf(new_symlink(target)?),
+            Self::Directory { entries } => {
+                let mut builder = ActionDirectoryBuilder::empty();
+                for (name, entry) in entries {
+                    builder.insert(
+                        FileNameBuf::try_from(name.clone())?,
+                        entry.to_directory_entry(digest_config)?,
+                    )?;
+                }
+                DirectoryEntry::Dir(builder)
+            }
+        })
+    }
+}
+
+fn parse_file_digest(digest: &str, digest_config: DigestConfig) -> anyhow::Result<FileDigest> {
+    let (digest, _) = FileDigest::parse_digest(digest, digest_config.cas_digest_config())
+        .with_context(|| format!("Invalid digest in local action cache: `{}`", digest))?;
+    Ok(digest)
+}
+
+/// The name of the file storing the entry for `digest`. Digests are displayed as `hash:size`, but
+/// `:` is not allowed in file names on all platforms.
+fn digest_file_name<Kind>(digest: &CasDigest<Kind>) -> String {
+    format!(
+        "{}_{}_{}",
+        digest.raw_digest().algorithm(),
+        digest.raw_digest(),
+        digest.size()
+    )
+}
+
+fn action_key(digest: &ActionDigest) -> String {
+    format!("{}/{}", ACTION_CACHE_DIR, digest_file_name(digest))
+}
+
+fn blob_key(digest: &FileDigest) -> String {
+    format!("{}/{}", CAS_DIR, digest_file_name(digest))
+}
+
+struct LruEntry {
+    size: u64,
+    tick: u64,
+}
+
+/// Tracks the size and recency of the entries in the cache, keyed by their path relative to the
+/// cache root.
+#[derive(Default)]
+struct LruIndex {
+    entries: HashMap<String, LruEntry>,
+    order: BTreeMap<u64, String>,
+    next_tick: u64,
+    total_bytes: u64,
+}
+
+impl LruIndex {
+    /// Record `key` as the most recently used entry.
+    fn insert(&mut self, key: String, size: u64) {
+        self.remove(&key);
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.order.insert(tick, key.clone());
+        self.entries.insert(key, LruEntry { size, tick });
+        self.total_bytes += size;
+    }
+
+    /// Mark `key` as the most recently used entry, if we know about it.
+    fn touch(&mut self, key: &str) -> bool {
+        match self.entries.get(key) {
+            Some(entry) => {
+                let size = entry.size;
+                self.insert(key.to_owned(), size);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.tick);
+            self.total_bytes -= entry.size;
+        }
+    }
+
+    /// Drop least recently used entries until the total size is at most `max_bytes`, and return
+    /// the keys of the entries that were dropped.
+    fn evict(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.total_bytes > max_bytes {
+            let key = match self.order.pop_first() {
+                Some((_, key)) => key,
+                None => break,
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.total_bytes -= entry.size;
+            }
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+/// The on-disk store backing the local action cache. This is shared by all commands for the
+/// lifetime of the daemon.
+pub struct LocalActionCache {
+    root: AbsNormPathBuf,
+    max_bytes: u64,
+    /// Loaded from disk on first use. Recency is tracked in memory, so after a restart entries are
+    /// ordered by when they were written.
+    index: Mutex<Option<LruIndex>>,
+    next_tmp_file: AtomicU64,
+}
+
+impl LocalActionCache {
+    pub fn new(root: AbsNormPathBuf, max_bytes: u64) -> Self {
+        Self {
+            root,
+            max_bytes,
+            index: Mutex::new(None),
+            next_tmp_file: AtomicU64::new(0),
+        }
+    }
+
+    fn path(&self, key: &str) -> anyhow::Result<AbsNormPathBuf> {
+        Ok(self.root.join(ForwardRelativePath::new(key)?))
+    }
+
+    fn with_index<R>(&self, f: impl FnOnce(&mut LruIndex) -> R) -> anyhow::Result<R> {
+        let mut index = self.index.lock();
+        match &mut *index {
+            Some(index) => Ok(f(index)),
+            None => {
+                let mut loaded = self.load_index()?;
+                let res = f(&mut loaded);
+                *index = Some(loaded);
+                Ok(res)
+            }
+        }
+    }
+
+    fn load_index(&self) -> anyhow::Result<LruIndex> {
+        let mut found = Vec::new();
+        for dir in [ACTION_CACHE_DIR, CAS_DIR, TMP_DIR] {
+            fs_util::create_dir_all(self.path(dir)?)?;
+        }
+        self.remove_stale_tmp_entries()?;
+        for dir in [ACTION_CACHE_DIR, CAS_DIR] {
+            for entry in fs_util::read_dir(self.path(dir)?)? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                let name = entry.file_name();
+                let name = name
+                    .to_str()
+                    .with_context(|| format!("Invalid file name: {}", entry.path()))?;
+                found.push((
+                    metadata.modified()?,
+                    format!("{}/{}", dir, name),
+                    metadata.len(),
+                ));
+            }
+        }
+        found.sort();
+
+        let mut index = LruIndex::default();
+        for (_, key, size) in found {
+            index.insert(key, size);
+        }
+        Ok(index)
+    }
+
+    /// Remove what daemons which died while writing to the cache left behind. Recent entries might
+    /// still be written by another daemon sharing this cache, so they are kept.
+    fn remove_stale_tmp_entries(&self) -> anyhow::Result<()> {
+        for entry in fs_util::read_dir(self.path(TMP_DIR)?)? {
+            let entry = entry?;
+            let stale = entry
+                .metadata()?
+                .modified()?
+                .elapsed()
+                .map_or(false, |age| age > STALE_TMP_AGE);
+            if stale {
+                if let Err(e) = fs_util::remove_all(entry.path()) {
+                    tracing::warn!("Error removing stale local action cache entry: {:#}", e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the result for `digest`. Results are only returned if all the blobs they reference are
+    /// still present.
+    fn lookup(
+        &self,
+        digest: &ActionDigest,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<Option<CachedActionResult>> {
+        let key = action_key(digest);
+        let data = match std::fs::read(self.path(&key)?) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Error reading `{}`", key)),
+        };
+        let result: CachedActionResult = serde_json::from_slice(&data)
+            .with_context(|| format!("Invalid action result in `{}`", key))?;
+
+        let mut blobs = vec![result.stdout.as_str(), result.stderr.as_str()];
+        for entry in result.outputs.values() {
+            entry.visit_digests(&mut |d| blobs.push(d));
+        }
+
+        let mut blob_keys = Vec::with_capacity(blobs.len());
+        for blob in blobs {
+            let blob_key = blob_key(&parse_file_digest(blob, digest_config)?);
+            let size = match fs_util::symlink_metadata_if_exists(self.path(&blob_key)?)? {
+                Some(metadata) => metadata.len(),
+                None => return Ok(None),
+            };
+            blob_keys.push((blob_key, size));
+        }
+
+        self.with_index(|index| {
+            // Entries might have been written by another daemon sharing this cache.
+            for (blob_key, size) in blob_keys {
+                if !index.touch(&blob_key) {
+                    index.insert(blob_key, size);
+                }
+            }
+            if !index.touch(&key) {
+                index.insert(key, data.len() as u64);
+            }
+        })?;
+
+        Ok(Some(result))
+    }
+
+    /// A file name which is unique among the daemons sharing this cache.
+    fn tmp_name(&self) -> String {
+        let id = self.next_tmp_file.fetch_add(1, Ordering::Relaxed);
+        format!("{}-{}", std::process::id(), id)
+    }
+
+    fn tmp_path(&self) -> anyhow::Result<AbsNormPathBuf> {
+        self.path(&format!("{}/{}", TMP_DIR, self.tmp_name()))
+    }
+
+    /// Store `result` for `digest`, along with the blobs it references, then evict entries if the
+    /// cache grew too large.
+    fn store(
+        &self,
+        digest: &ActionDigest,
+        result: &CachedActionResult,
+        blobs: Vec<(FileDigest, BlobSource)>,
+    ) -> anyhow::Result<()> {
+        // Make sure the directories exist.
+        self.with_index(|_| ())?;
+
+        for (digest, source) in blobs {
+            let key = blob_key(&digest);
+            if self.with_index(|index| index.touch(&key))? {
+                continue;
+            }
+
+            let path = self.path(&key)?;
+            if !fs_util::try_exists(&path)? {
+                let tmp = self.tmp_path()?;
+                match source {
+                    BlobSource::File(src) => {
+                        fs_util::copy(&src, &tmp)?;
+                    }
+                    BlobSource::Bytes(bytes) => fs_util::write(&tmp, bytes)?,
+                }
+                normalize_permissions(&tmp)?;
+                fs_util::rename(&tmp, &path)?;
+            }
+            self.with_index(|index| index.insert(key, digest.size()))?;
+        }
+
+        let key = action_key(digest);
+        let data = serde_json::to_vec(result)?;
+        let tmp = self.tmp_path()?;
+        fs_util::write(&tmp, &data)?;
+        fs_util::rename(&tmp, self.path(&key)?)?;
+
+        let evicted = self.with_index(|index| {
+            index.insert(key, data.len() as u64);
+            index.evict(self.max_bytes)
+        })?;
+        for key in evicted {
+            fs_util::remove_all(self.path(&key)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write `entry` to `dest`, copying files out of the cache and checking their digests.
+    fn restore(
+        &self,
+        entry: &CachedEntry,
+        dest: &AbsNormPath,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<()> {
+        match entry {
+            CachedEntry::File { digest, executable } => {
+                let expected = parse_file_digest(digest, digest_config)?;
+                fs_util::copy(self.path(&blob_key(&expected))?, dest)?;
+                let actual =
+                    FileDigest::from_file_disk(dest.as_path(), digest_config.cas_digest_config())?;
+                self.check_blob(expected, actual)?;
+                if *executable {
+                    fs_util::set_executable(dest)?;
+                }
+            }
+            CachedEntry::Symlink { target } => fs_util::symlink(target, dest)?,
+            CachedEntry::Directory { entries } => {
+                fs_util::create_dir_all(dest)?;
+                for (name, entry) in entries {
+                    self.restore(entry, &dest.join(FileName::new(name)?), digest_config)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_blob(&self, digest: &str, digest_config: DigestConfig) -> anyhow::Result<Vec<u8>> {
+        let expected = parse_file_digest(digest, digest_config)?;
+        let data = fs_util::read(self.path(&blob_key(&expected))?)?;
+        let actual = FileDigest::from_content(&data, digest_config.cas_digest_config());
+        self.check_blob(expected, actual)?;
+        Ok(data)
+    }
+
+    /// Fail if a blob doesn't have the contents we expect, removing it so that the results using
+    /// it are not found anymore.
+    fn check_blob(&self, expected: FileDigest, actual: FileDigest) -> anyhow::Result<()> {
+        if expected == actual {
+            return Ok(());
+        }
+        let key = blob_key(&expected);
+        self.with_index(|index| index.remove(&key))?;
+        fs_util::remove_all(self.path(&key)?)?;
+        Err(LocalActionCacheError::CorruptBlob { expected, actual }.into())
+    }
+}
+
+/// Blobs are shared by all the outputs with the same contents, so they are stored without the
+/// executable bit, which is set when restoring outputs instead.
+fn normalize_permissions(path: &AbsNormPath) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs_util::set_permissions(path, std::fs::Permissions::from_mode(0o644))?;
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+    }
+
+    Ok(())
+}
+
+/// A PreparedCommandExecutor that will check the local action cache before executing any actions
+/// using the underlying executor, and store the results of actions that ran locally in it.
+pub struct LocalActionCacheExecutor {
+    pub inner: Arc<dyn PreparedCommandExecutor>,
+    pub cache: Arc<LocalActionCache>,
+    pub artifact_fs: ArtifactFs,
+    pub materializer: Arc<dyn Materializer>,
+    pub blocking_executor: Arc<dyn BlockingExecutor>,
+    pub cache_upload_behavior: CacheUploadBehavior,
+}
+
+/// A directory which is removed when dropped, including when the future using it is dropped.
+struct StagingDir(AbsNormPathBuf);
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        let _ignored = fs_util::remove_all(&self.0);
+    }
+}
+
+/// The outputs of a cached result, copied out of the cache and checked, ready to be moved into
+/// place.
+struct StagedOutputs {
+    dir: StagingDir,
+    /// The outputs, their path, and where they were copied to.
+    outputs: Vec<(
+        CommandExecutionOutput,
+        ProjectRelativePathBuf,
+        AbsNormPathBuf,
+    )>,
+    builder: ActionDirectoryBuilder,
+    std_streams: CommandStdStreams,
+}
+
+impl LocalActionCacheExecutor {
+    /// Copy the outputs of `cached` next to where they go, or fail if the cache entry can't be
+    /// used. Nothing is written to the outputs, so this can run before claiming the command.
+    fn stage_outputs(
+        &self,
+        request: &CommandExecutionRequest,
+        cached: &CachedActionResult,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<StagedOutputs> {
+        let project_fs = self.artifact_fs.fs();
+        // In buck-out, so that outputs are moved rather than copied into place.
+        let dir = StagingDir(
+            project_fs.resolve(self.artifact_fs.buck_out_path_resolver().root().join(
+                ForwardRelativePath::new(&format!(
+                    "tmp/.local_action_cache/{}",
+                    self.cache.tmp_name()
+                ))?,
+            )),
+        );
+        fs_util::create_dir_all(&dir.0)?;
+
+        let mut builder = inputs_directory(request.inputs(), &self.artifact_fs)?;
+        let mut outputs = Vec::new();
+        for output in request.outputs() {
+            let path = output.resolve(&self.artifact_fs).into_path();
+            // Serving a result without one of its outputs would leave the action half built.
+            let entry = match cached.outputs.get(path.as_str()) {
+                Some(entry) => entry,
+                None => return Err(LocalActionCacheError::MissingOutput(path).into()),
+            };
+            let staged = dir.0.join(FileName::new(&outputs.len().to_string())?);
+            self.cache.restore(entry, &staged, digest_config)?;
+            insert_entry(
+                &mut builder,
+                &path,
+                entry.to_directory_entry(digest_config)?,
+            )?;
+            outputs.push((output.cloned(), path, staged));
+        }
+
+        let std_streams = CommandStdStreams::Local {
+            stdout: self.cache.read_blob(&cached.stdout, digest_config)?,
+            stderr: self.cache.read_blob(&cached.stderr, digest_config)?,
+        };
+
+        Ok(StagedOutputs {
+            dir,
+            outputs,
+            builder,
+            std_streams,
+        })
+    }
+
+    async fn serve_from_cache(
+        &self,
+        manager: CommandExecutionManager,
+        request: &CommandExecutionRequest,
+        action_digest: &ActionDigest,
+        staged: StagedOutputs,
+        digest_config: DigestConfig,
+        cancellations: &CancellationContext,
+    ) -> CommandExecutionResult {
+        tracing::info!(
+            "Action result is in the local action cache, skipping execution of:\n```\n$ {}\n```\n for action `{}`",
+            request.args().join(" "),
+            action_digest,
+        );
+
+        let start_time = SystemTime::now();
+        let start = Instant::now();
+
+        // Claim the request before writing any outputs. A claim can't be given back, so from here
+        // on errors fail the command rather than running it.
+        let manager = manager.claim().await;
+
+        let res = executor_stage_async(
+            buck2_data::CacheHit {
+                action_digest: action_digest.to_string(),
+            },
+            self.move_outputs(request, staged, digest_config, cancellations),
+        )
+        .await;
+
+        let (outputs, std_streams) = match res {
+            Ok(res) => res,
+            Err(e) => return manager.error("local_action_cache", e),
+        };
+
+        manager.success(
+            CommandExecutionKind::LocalActionCache {
+                digest: action_digest.dupe(),
+            },
+            outputs,
+            std_streams,
+            CommandExecutionMetadata {
+                wall_time: start.elapsed(),
+                re_queue_time: None,
+                execution_time: Duration::ZERO,
+                start_time,
+                execution_stats: None,
+            },
+        )
+    }
+
+    async fn move_outputs(
+        &self,
+        request: &CommandExecutionRequest,
+        staged: StagedOutputs,
+        digest_config: DigestConfig,
+        cancellations: &CancellationContext,
+    ) -> anyhow::Result<(
+        IndexMap<CommandExecutionOutput, ArtifactValue>,
+        CommandStdStreams,
+    )> {
+        create_output_dirs(
+            &self.artifact_fs,
+            request,
+            self.materializer.dupe(),
+            self.blocking_executor.dupe(),
+            cancellations,
+        )
+        .await?;
+
+        let StagedOutputs {
+            dir,
+            outputs,
+            builder,
+            std_streams,
+        } = staged;
+
+        let entries = self
+            .blocking_executor
+            .execute_io_inline(|| {
+                let project_fs = self.artifact_fs.fs();
+                let mut entries = Vec::with_capacity(outputs.len());
+                for (output, path, staged) in outputs {
+                    let dest = project_fs.resolve(&path);
+                    fs_util::remove_all(&dest)?;
+                    fs_util::rename(&staged, &dest)?;
+                    entries.push((output, path));
+                }
+                drop(dir);
+                Ok(entries)
+            })
+            .await?;
+
+        let outputs =
+            declare_output_values(&*self.materializer, &builder, entries, digest_config).await?;
+
+        Ok((outputs, std_streams))
+    }
+
+    /// Store an action result in the local action cache, under the same conditions as uploads to
+    /// the RE action cache: the action must have been successful and must have run locally, and
+    /// cache uploads must be enabled, both for this executor and this particular action.
+    async fn maybe_store(
+        &self,
+        request: &CommandExecutionRequest,
+        action_digest: &ActionDigest,
+        result: &CommandExecutionResult,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<bool> {
+        let max_bytes = match self.cache_upload_behavior {
+            CacheUploadBehavior::Enabled { max_bytes } => max_bytes,
+            CacheUploadBehavior::Disabled => return Ok(false),
+        };
+
+        if !request.allow_cache_upload() {
+            return Ok(false);
+        }
+
+        match &result.report.status {
+            CommandExecutionStatus::Success {
+                execution_kind: CommandExecutionKind::Local { .. },
+            } => {}
+            _ => return Ok(false),
+        }
+
+        if let Some(max_bytes) = max_bytes {
+            if result.calc_output_size_bytes() > max_bytes {
+                return Ok(false);
+            }
+        }
+
+        let (stdout, stderr) = match &result.report.std_streams {
+            CommandStdStreams::Local { stdout, stderr } => (stdout, stderr),
+            _ => return Ok(false),
+        };
+
+        let project_fs = self.artifact_fs.fs();
+        let mut outputs = BTreeMap::new();
+        let mut blobs = Vec::new();
+
+        for (output, value) in result.resolve_outputs(&self.artifact_fs) {
+            let entry = value
+                .entry()
+                .as_ref()
+                .map_dir(|d| d as &dyn Directory<ActionDirectoryMember, TrackedFileDigest>);
+            match CachedEntry::new(entry, project_fs.resolve(output.path()), &mut blobs) {
+                Some(entry) => {
+                    outputs.insert(output.path().to_string(), entry);
+                }
+                None => return Ok(false),
+            }
+        }
+
+        let mut std_stream = |bytes: &[u8]| {
+            let digest = FileDigest::from_content(bytes, digest_config.cas_digest_config());
+            let res = digest.to_string();
+            blobs.push((digest, BlobSource::Bytes(bytes.to_vec())));
+            res
+        };
+
+        let cached = CachedActionResult {
+            outputs,
+            stdout: std_stream(stdout),
+            stderr: std_stream(stderr),
+        };
+
+        self.blocking_executor
+            .execute_io_inline(|| self.cache.store(action_digest, &cached, blobs))
+            .await?;
+
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl PreparedCommandExecutor for LocalActionCacheExecutor {
+    async fn exec_cmd(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        manager: CommandExecutionManager,
+        cancellations: &CancellationContext,
+    ) -> CommandExecutionResult {
+        let action_digest = &command.prepared_action.action;
+
+        // Outputs are staged as part of the lookup, so that a missing or corrupt entry is a miss.
+        let staged = executor_stage_async(
+            buck2_data::CacheQuery {
+                action_digest: action_digest.to_string(),
+            },
+            self.blocking_executor.execute_io_inline(|| {
+                match self.cache.lookup(action_digest, command.digest_config)? {
+                    Some(cached) => Ok(Some(self.stage_outputs(
+                        command.request,
+                        &cached,
+                        command.digest_config,
+                    )?)),
+                    None => Ok(None),
+                }
+            }),
+        )
+        .await;
+
+        match staged {
+            Ok(Some(staged)) => {
+                return self
+                    .serve_from_cache(
+                        manager,
+                        command.request,
+                        action_digest,
+                        staged,
+                        command.digest_config,
+                        cancellations,
+                    )
+                    .await;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                // The cache is only an optimization, so run the action if it's unusable.
+                tracing::warn!(
+                    "Local action cache lookup for `{}` failed: {:#}",
+                    action_digest,
+                    e
+                );
+            }
+        }
+
+        let res = self.inner.exec_cmd(command, manager, cancellations).await;
+
+        match self
+            .maybe_store(command.request, action_digest, &res, command.digest_config)
+            .await
+        {
+            Ok(true) => {
+                tracing::debug!("Local action cache store for `{}` succeeded", action_digest);
+            }
+            Ok(false) => {
+                tracing::debug!(
+                    "Local action cache store for `{}` not attempted",
+                    action_digest
+                );
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Local action cache store for `{}` failed: {:#}",
+                    action_digest,
+                    e
+                );
+            }
+        }
+
+        res
+    }
+
+    fn is_local_execution_possible(&self, executor_preference: ExecutorPreference) -> bool {
+        self.inner.is_local_execution_possible(executor_preference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+
+    #[test]
+    fn test_lru_index_evicts_least_recently_used() {
+        let mut index = LruIndex::default();
+        index.insert("a".to_owned(), 10);
+        index.insert("b".to_owned(), 10);
+        index.insert("c".to_owned(), 10);
+        assert!(index.touch("a"));
+        assert!(!index.touch("d"));
+
+        assert_eq!(vec!["b".to_owned()], index.evict(20));
+        assert_eq!(20, index.total_bytes);
+        assert_eq!(vec!["c".to_owned(), "a".to_owned()], index.evict(0));
+        assert!(index.entries.is_empty());
+    }
+
+    #[test]
+    fn test_store_and_lookup() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let root = temp.path().root();
+        let digest_config = DigestConfig::testing_default();
+
+        let output = root.join(ForwardRelativePath::new("out")?);
+        fs_util::write(&output, "contents")?;
+        let file_digest = FileDigest::from_content(b"contents", digest_config.cas_digest_config());
+        let std_digest = FileDigest::from_content(b"", digest_config.cas_digest_config());
+
+        let result = CachedActionResult {
+            outputs: BTreeMap::from([(
+                "buck-out/out".to_owned(),
+                CachedEntry::File {
+                    digest: file_digest.to_string(),
+                    executable: true,
+                },
+            )]),
+            stdout: std_digest.to_string(),
+            stderr: std_digest.to_string(),
+        };
+
+        let cache = LocalActionCache::new(root.join(ForwardRelativePath::new("cache")?), 1024);
+        let action_digest =
+            ActionDigest::from_content(b"action", digest_config.cas_digest_config());
+        assert_eq!(None, cache.lookup(&action_digest, digest_config)?);
+
+        cache.store(
+            &action_digest,
+            &result,
+            vec![
+                (file_digest, BlobSource::File(output)),
+                (std_digest.dupe(), BlobSource::Bytes(Vec::new())),
+                (std_digest, BlobSource::Bytes(Vec::new())),
+            ],
+        )?;
+        assert_eq!(
+            Some(&result),
+            cache.lookup(&action_digest, digest_config)?.as_ref()
+        );
+
+        let restored = root.join(ForwardRelativePath::new("restored")?);
+        cache.restore(&result.outputs["buck-out/out"], &restored, digest_config)?;
+        assert_eq!("contents", fs_util::read_to_string(&restored)?);
+
+        // A cache with no room evicts everything once it is written to.
+        let cache = LocalActionCache::new(root.join(ForwardRelativePath::new("cache")?), 0);
+        cache.store(&action_digest, &result, Vec::new())?;
+        assert_eq!(None, cache.lookup(&action_digest, digest_config)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_blob() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let root = temp.path().root();
+        let digest_config = DigestConfig::testing_default();
+
+        let file_digest = FileDigest::from_content(b"contents", digest_config.cas_digest_config());
+        let std_digest = FileDigest::from_content(b"", digest_config.cas_digest_config());
+        let result = CachedActionResult {
+            outputs: BTreeMap::from([(
+                "buck-out/out".to_owned(),
+                CachedEntry::File {
+                    digest: file_digest.to_string(),
+                    executable: false,
+                },
+            )]),
+            stdout: std_digest.to_string(),
+            stderr: std_digest.to_string(),
+        };
+
+        let cache = LocalActionCache::new(root.join(ForwardRelativePath::new("cache")?), 1024);
+        let action_digest =
+            ActionDigest::from_content(b"action", digest_config.cas_digest_config());
+        cache.store(
+            &action_digest,
+            &result,
+            vec![
+                (file_digest.dupe(), BlobSource::Bytes(b"contents".to_vec())),
+                (std_digest, BlobSource::Bytes(Vec::new())),
+            ],
+        )?;
+        fs_util::write(cache.path(&blob_key(&file_digest))?, "corrupt!")?;
+
+        // Restoring checks the digest, and removes the corrupt blob so the result isn't found again.
+        let restored = root.join(ForwardRelativePath::new("restored")?);
+        assert!(
+            cache
+                .restore(&result.outputs["buck-out/out"], &restored, digest_config)
+                .is_err()
+        );
+        assert_eq!(None, cache.lookup(&action_digest, digest_config)?);
+
+        Ok(())
+    }
+}
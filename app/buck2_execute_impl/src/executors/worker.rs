@@ -0,0 +1,1002 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Persistent workers for local execution.
+//!
+//! Tools with a high startup cost (e.g. compilers running on the JVM) can run as a persistent
+//! worker: a long-lived process which reads `WorkRequest`s on stdin and writes `WorkResponse`s on
+//! stdout, following the Bazel worker protocol. Workers are kept by the [`WorkerPool`] until they
+//! have been unused for a while, so that later actions using the same worker find it warm.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::process::Stdio;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Weak;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Context as _;
+use buck2_common::liveliness_observer::LivelinessObserver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
+use buck2_execute::execute::request::WorkerProtocol;
+use buck2_execute::execute::request::WorkerSpec;
+use buck2_forkserver::run::GatherOutputStatus;
+use buck2_util::process::background_command;
+use dupe::Dupe;
+use parking_lot::Mutex;
+use prost::Message;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::process::Child;
+use tokio::process::ChildStdin;
+use tokio::process::ChildStdout;
+use tokio::sync::oneshot;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+
+use crate::executors::local::apply_local_execution_environment;
+
+/// Appended to the startup arguments of a worker, as Bazel does.
+const PERSISTENT_WORKER_FLAG: &str = "--persistent_worker";
+
+/// How many singleplex workers run for the same key by default, as in Bazel.
+pub const DEFAULT_MAX_WORKERS_PER_KEY: usize = 4;
+
+/// How long a worker is kept without processing requests by default.
+pub const DEFAULT_WORKER_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Error)]
+enum WorkerError {
+    #[error("Worker command line is empty")]
+    EmptyCommandLine,
+    #[error("Worker `{0}` exited before responding")]
+    Crashed(String),
+    #[error("Invalid response from worker `{0}`: {1}")]
+    InvalidResponse(String, String),
+}
+
+/// Whether `e` means the worker exited, as opposed to e.g. not following the protocol.
+fn is_worker_exit(e: &anyhow::Error) -> bool {
+    e.chain().any(|e| {
+        if let Some(WorkerError::Crashed(_)) = e.downcast_ref::<WorkerError>() {
+            return true;
+        }
+        match e.downcast_ref::<std::io::Error>() {
+            Some(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::UnexpectedEof
+            ),
+            None => false,
+        }
+    })
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkInput {
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    path: String,
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkRequest {
+    #[prost(string, repeated, tag = "1")]
+    #[serde(default)]
+    arguments: Vec<String>,
+    #[prost(message, repeated, tag = "2")]
+    #[serde(default)]
+    inputs: Vec<WorkInput>,
+    #[prost(int32, tag = "3")]
+    #[serde(default)]
+    request_id: i32,
+    /// Asks the worker to stop processing the request with `request_id`.
+    #[prost(bool, tag = "4")]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    cancel: bool,
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkResponse {
+    #[prost(int32, tag = "1")]
+    #[serde(default)]
+    exit_code: i32,
+    #[prost(string, tag = "2")]
+    #[serde(default)]
+    output: String,
+    #[prost(int32, tag = "3")]
+    #[serde(default)]
+    request_id: i32,
+}
+
+fn encode_request(protocol: WorkerProtocol, request: &WorkRequest) -> anyhow::Result<Vec<u8>> {
+    match protocol {
+        WorkerProtocol::Json => {
+            let mut buf = serde_json::to_vec(request)?;
+            buf.push(b'\n');
+            Ok(buf)
+        }
+        WorkerProtocol::Proto => Ok(request.encode_length_delimited_to_vec()),
+    }
+}
+
+/// Read the next response, or `None` if the worker closed its stdout.
+async fn read_response(
+    protocol: WorkerProtocol,
+    reader: &mut BufReader<ChildStdout>,
+) -> anyhow::Result<Option<WorkResponse>> {
+    match protocol {
+        WorkerProtocol::Json => {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).await? == 0 {
+                    return Ok(None);
+                }
+                if !line.trim().is_empty() {
+                    break;
+                }
+            }
+            Ok(Some(
+                serde_json::from_str(&line).context("Invalid JSON WorkResponse")?,
+            ))
+        }
+        WorkerProtocol::Proto => {
+            let mut len = 0u64;
+            for shift in (0..64).step_by(7) {
+                let byte = match reader.read_u8().await {
+                    Ok(byte) => byte,
+                    Err(e) if shift == 0 && e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                        return Ok(None);
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                len |= u64::from(byte & 0x7f) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            let mut buf = vec![0; usize::try_from(len)?];
+            reader.read_exact(&mut buf).await?;
+            Ok(Some(
+                WorkResponse::decode(buf.as_slice()).context("Invalid protobuf WorkResponse")?,
+            ))
+        }
+    }
+}
+
+/// Requests waiting for a response, by request id. `None` once the worker is dead.
+type PendingRequests =
+    Arc<Mutex<Option<HashMap<i32, oneshot::Sender<Result<WorkResponse, WorkerError>>>>>>;
+
+/// Why a worker stopped, which decides whether its log is kept for diagnostics.
+#[derive(Default)]
+struct WorkerExit {
+    /// We killed the worker, e.g. after a timeout.
+    killed: AtomicBool,
+    /// The worker exited or broke the protocol on its own.
+    failed: AtomicBool,
+}
+
+impl WorkerExit {
+    fn fail(&self) {
+        if !self.killed.load(Ordering::Relaxed) {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+struct Worker {
+    exe: String,
+    protocol: WorkerProtocol,
+    child: Mutex<Child>,
+    stdin: tokio::sync::Mutex<ChildStdin>,
+    pending: PendingRequests,
+    exit: Arc<WorkerExit>,
+    next_request_id: AtomicI32,
+    log_path: AbsNormPathBuf,
+    tmp_dir: AbsNormPathBuf,
+}
+
+impl Worker {
+    fn spawn(
+        spec: &WorkerSpec,
+        root: &AbsNormPath,
+        env: &[(String, String)],
+        env_inheritance: Option<&EnvironmentInheritance>,
+        log_path: &AbsNormPath,
+        tmp_dir: &AbsNormPath,
+    ) -> anyhow::Result<Self> {
+        let (exe, startup_args) = spec
+            .exe
+            .split_first()
+            .ok_or(WorkerError::EmptyCommandLine)?;
+
+        let mut cmd = background_command(exe);
+        cmd.args(startup_args);
+        cmd.arg(PERSISTENT_WORKER_FLAG);
+        cmd.current_dir(root);
+        let tmp_vars: &[&str] = if cfg!(windows) {
+            &["TEMP", "TMP"]
+        } else {
+            &["TMPDIR"]
+        };
+        apply_local_execution_environment(
+            &mut cmd,
+            root.as_path(),
+            tmp_vars
+                .iter()
+                .map(|k| (OsStr::new(k), tmp_dir.as_os_str()))
+                .chain(env.iter().map(|(k, v)| (OsStr::new(k), OsStr::new(v)))),
+            env_inheritance,
+        );
+        cmd.stdin(Stdio::piped());
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(
+            File::create(log_path)
+                .with_context(|| format!("Error creating worker log `{}`", log_path))?,
+        );
+
+        let mut cmd = tokio::process::Command::from(cmd);
+        cmd.kill_on_drop(true);
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Failed to spawn worker `{}`", exe))?;
+
+        let stdin = child.stdin.take().context("Worker stdin is missing")?;
+        let stdout = child.stdout.take().context("Worker stdout is missing")?;
+
+        let pending: PendingRequests = Arc::new(Mutex::new(Some(HashMap::new())));
+        let exit = Arc::new(WorkerExit::default());
+        tokio::spawn(Self::read_responses(
+            exe.clone(),
+            spec.protocol,
+            stdout,
+            pending.dupe(),
+            exit.dupe(),
+        ));
+
+        Ok(Self {
+            exe: exe.clone(),
+            protocol: spec.protocol,
+            child: Mutex::new(child),
+            stdin: tokio::sync::Mutex::new(stdin),
+            pending,
+            exit,
+            next_request_id: AtomicI32::new(1),
+            log_path: log_path.to_buf(),
+            tmp_dir: tmp_dir.to_buf(),
+        })
+    }
+
+    /// Dispatch responses to the requests waiting for them, until the worker exits.
+    async fn read_responses(
+        exe: String,
+        protocol: WorkerProtocol,
+        stdout: ChildStdout,
+        pending: PendingRequests,
+        exit: Arc<WorkerExit>,
+    ) {
+        let mut reader = BufReader::new(stdout);
+        loop {
+            match read_response(protocol, &mut reader).await {
+                Ok(Some(response)) => {
+                    let sender = pending
+                        .lock()
+                        .as_mut()
+                        .and_then(|pending| pending.remove(&response.request_id));
+                    if let Some(sender) = sender {
+                        let _ignored = sender.send(Ok(response));
+                    }
+                }
+                Ok(None) => break,
+                Err(e) if is_worker_exit(&e) => break,
+                Err(e) => {
+                    tracing::warn!("Error reading from worker `{}`: {:#}", exe, e);
+                    exit.fail();
+                    // The worker is still running, but we can't tell where its next response
+                    // starts, so it can't be used anymore.
+                    if let Some(senders) = pending.lock().take() {
+                        for sender in senders.into_values() {
+                            let _ignored = sender.send(Err(WorkerError::InvalidResponse(
+                                exe.clone(),
+                                format!("{:#}", e),
+                            )));
+                        }
+                    }
+                    return;
+                }
+            }
+        }
+        exit.fail();
+        // Dropping the senders fails the requests still waiting.
+        *pending.lock() = None;
+    }
+
+    fn is_alive(&self) -> bool {
+        self.pending.lock().is_some()
+    }
+
+    fn kill(&self) {
+        self.exit.killed.store(true, Ordering::Relaxed);
+        *self.pending.lock() = None;
+        let _ignored = self.child.lock().start_kill();
+    }
+
+    async fn send(
+        &self,
+        request_id: i32,
+        arguments: Vec<String>,
+        inputs: Vec<String>,
+    ) -> anyhow::Result<oneshot::Receiver<Result<WorkResponse, WorkerError>>> {
+        let (sender, receiver) = oneshot::channel();
+        match &mut *self.pending.lock() {
+            Some(pending) => {
+                pending.insert(request_id, sender);
+            }
+            None => return Err(WorkerError::Crashed(self.exe.clone()).into()),
+        }
+
+        self.write(&WorkRequest {
+            arguments,
+            inputs: inputs.into_iter().map(|path| WorkInput { path }).collect(),
+            request_id,
+            cancel: false,
+        })
+        .await?;
+        Ok(receiver)
+    }
+
+    /// Stop waiting for the response to `request_id`, and ask the worker to stop processing it.
+    /// Workers which don't support cancellation still respond, and the response is dropped.
+    async fn cancel(&self, request_id: i32) -> anyhow::Result<()> {
+        if let Some(pending) = &mut *self.pending.lock() {
+            pending.remove(&request_id);
+        }
+        self.write(&WorkRequest {
+            arguments: Vec::new(),
+            inputs: Vec::new(),
+            request_id,
+            cancel: true,
+        })
+        .await
+    }
+
+    async fn write(&self, request: &WorkRequest) -> anyhow::Result<()> {
+        let buf = encode_request(self.protocol, request)?;
+        let mut stdin = self.stdin.lock().await;
+        stdin
+            .write_all(&buf)
+            .await
+            .with_context(|| format!("Error writing to worker `{}`", self.exe))?;
+        stdin.flush().await?;
+        Ok(())
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ignored = self.child.get_mut().start_kill();
+        let mut paths = vec![self.tmp_dir.clone()];
+        // The log of a worker which exited on its own is the only trace of why, so keep it.
+        if self.exit.failed.load(Ordering::Relaxed) {
+            tracing::info!(
+                "Worker `{}` exited, its log is kept in `{}`",
+                self.exe,
+                self.log_path
+            );
+        } else {
+            paths.push(self.log_path.clone());
+        }
+        let remove = move || {
+            for path in &paths {
+                if let Err(e) = fs_util::remove_all(path) {
+                    tracing::warn!("Error removing `{}`: {:#}", path, e);
+                }
+            }
+        };
+        // Workers are usually dropped on an executor thread, which shouldn't block on IO.
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(remove);
+            }
+            Err(_) => remove(),
+        }
+    }
+}
+
+/// Workers with the same command line, inputs and environment are interchangeable.
+#[derive(PartialEq, Eq, Hash, Clone)]
+struct WorkerKey {
+    spec: WorkerSpec,
+    env: Vec<(String, String)>,
+    env_inheritance: Option<EnvironmentInheritance>,
+}
+
+/// The workers started for a key.
+struct KeyWorkers {
+    /// A permit is held while a singleplex worker processes a request, or while a worker starts.
+    /// This bounds the number of singleplex workers, and makes sure a single multiplexed worker
+    /// is started.
+    permits: Arc<Semaphore>,
+    /// The workers which can take a request, with the time they were last used. Multiplexed
+    /// workers stay here while they process requests.
+    idle: Vec<(Arc<Worker>, Instant)>,
+}
+
+impl KeyWorkers {
+    fn take_idle(&mut self, multiplex: bool) -> Option<Arc<Worker>> {
+        self.idle.retain(|(w, _)| w.is_alive());
+        if multiplex {
+            let (worker, last_used) = self.idle.first_mut()?;
+            *last_used = Instant::now();
+            Some(worker.dupe())
+        } else {
+            self.idle.pop().map(|(w, _)| w)
+        }
+    }
+}
+
+/// The persistent workers started by the local executor. Multiplexed workers are shared by all the
+/// requests for them, while other workers process a single request at a time and are returned to
+/// the pool when they are done.
+pub struct WorkerPool {
+    log_dir: AbsNormPathBuf,
+    max_workers_per_key: usize,
+    idle_timeout: Duration,
+    workers: Mutex<HashMap<WorkerKey, KeyWorkers>>,
+    next_log_id: AtomicU64,
+}
+
+impl WorkerPool {
+    /// `log_dir` receives the stderr and temporary directories of the workers. The stderr of
+    /// workers which crashed is kept after they are gone.
+    ///
+    /// At most `max_workers_per_key` singleplex workers run with the same key, further requests
+    /// wait for one of them to be done. Workers which processed no request for `idle_timeout` are
+    /// stopped.
+    pub fn new(
+        log_dir: AbsNormPathBuf,
+        max_workers_per_key: usize,
+        idle_timeout: Duration,
+    ) -> Arc<Self> {
+        assert!(
+            max_workers_per_key > 0,
+            "max_workers_per_key must be positive"
+        );
+        let pool = Arc::new(Self {
+            log_dir,
+            max_workers_per_key,
+            idle_timeout,
+            workers: Mutex::new(HashMap::new()),
+            next_log_id: AtomicU64::new(0),
+        });
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(Self::evict_idle_periodically(
+                Arc::downgrade(&pool),
+                idle_timeout,
+            ));
+        }
+        pool
+    }
+
+    async fn evict_idle_periodically(pool: Weak<Self>, idle_timeout: Duration) {
+        loop {
+            tokio::time::sleep(idle_timeout.max(Duration::from_secs(1))).await;
+            match pool.upgrade() {
+                Some(pool) => pool.evict_idle(),
+                None => return,
+            }
+        }
+    }
+
+    /// Stop the workers which processed no request for the idle timeout.
+    fn evict_idle(&self) {
+        let now = Instant::now();
+        let mut evicted = Vec::new();
+        {
+            let mut workers = self.workers.lock();
+            workers.retain(|_, key_workers| {
+                let (keep, evict): (Vec<_>, Vec<_>) = std::mem::take(&mut key_workers.idle)
+                    .into_iter()
+                    .partition(|(w, last_used)| {
+                        // A multiplexed worker is also referenced by the requests it is processing.
+                        w.is_alive()
+                            && (Arc::strong_count(w) > 1
+                                || now.duration_since(*last_used) < self.idle_timeout)
+                    });
+                key_workers.idle = keep;
+                evicted.extend(evict);
+                // The permits are also referenced by the requests using or waiting for a worker,
+                // which must keep sharing them.
+                !key_workers.idle.is_empty() || Arc::strong_count(&key_workers.permits) > 1
+            });
+        }
+        // Stopping workers removes their files, so don't hold the lock meanwhile.
+        drop(evicted);
+    }
+
+    /// Run a command with a worker, which is started if none is available. A worker which exits
+    /// while processing the request is replaced once, other errors are returned immediately.
+    /// Errors mean the worker can't be used, and the command should run as a one-shot process
+    /// instead.
+    pub async fn exec(
+        &self,
+        spec: &WorkerSpec,
+        root: &AbsNormPath,
+        arguments: &[String],
+        inputs: &[String],
+        env: impl IntoIterator<Item = (&str, &str)>,
+        env_inheritance: Option<&EnvironmentInheritance>,
+        timeout: Option<Duration>,
+        liveliness_observer: &dyn LivelinessObserver,
+    ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)> {
+        let key = WorkerKey {
+            spec: spec.clone(),
+            env: env
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+            env_inheritance: env_inheritance.copied(),
+        };
+
+        match self
+            .exec_once(&key, root, arguments, inputs, timeout, liveliness_observer)
+            .await
+        {
+            Err(e) if is_worker_exit(&e) => {
+                tracing::info!("Restarting worker after error: {:#}", e);
+                self.exec_once(&key, root, arguments, inputs, timeout, liveliness_observer)
+                    .await
+            }
+            res => res,
+        }
+    }
+
+    async fn exec_once(
+        &self,
+        key: &WorkerKey,
+        root: &AbsNormPath,
+        arguments: &[String],
+        inputs: &[String],
+        timeout: Option<Duration>,
+        liveliness_observer: &dyn LivelinessObserver,
+    ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)> {
+        let (worker, permit) = self.acquire(key, root).await?;
+
+        // Singleplex workers only ever have one request in flight, which Bazel identifies as 0.
+        let request_id = if key.spec.multiplex {
+            worker.next_request_id.fetch_add(1, Ordering::Relaxed)
+        } else {
+            0
+        };
+
+        let response = async {
+            let receiver = worker
+                .send(request_id, arguments.to_vec(), inputs.to_vec())
+                .await?;
+            let response = receiver
+                .await
+                .map_err(|_| WorkerError::Crashed(worker.exe.clone()))??;
+            anyhow::Ok(response)
+        };
+        let timeout_expired = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => futures::future::pending::<()>().await,
+            }
+        };
+
+        let outcome = tokio::select! {
+            response = response => Ok(response),
+            _ = timeout_expired => Err(GatherOutputStatus::TimedOut(timeout.unwrap_or_default())),
+            _ = liveliness_observer.while_alive() => Err(GatherOutputStatus::Cancelled),
+        };
+
+        match outcome {
+            Ok(Ok(response)) => {
+                self.release(key, worker, permit);
+                Ok((
+                    GatherOutputStatus::Finished {
+                        exit_code: response.exit_code,
+                        execution_stats: None,
+                    },
+                    Vec::new(),
+                    response.output.into_bytes(),
+                ))
+            }
+            Ok(Err(e)) => {
+                worker.exit.failed.store(true, Ordering::Relaxed);
+                self.discard(key, &worker);
+                Err(e)
+            }
+            // A multiplexed worker is processing other requests too, so only cancel this one.
+            Err(status) if key.spec.multiplex => {
+                if let Err(e) = worker.cancel(request_id).await {
+                    tracing::warn!("Error cancelling worker request: {:#}", e);
+                    self.discard(key, &worker);
+                }
+                Ok((status, Vec::new(), Vec::new()))
+            }
+            // A singleplex worker interrupted in the middle of a request can't be reused, so kill
+            // it.
+            Err(status) => {
+                self.discard(key, &worker);
+                Ok((status, Vec::new(), Vec::new()))
+            }
+        }
+    }
+
+    /// A worker for `key`, started if none is available, and the permit to hold while a
+    /// singleplex worker processes the request.
+    async fn acquire(
+        &self,
+        key: &WorkerKey,
+        root: &AbsNormPath,
+    ) -> anyhow::Result<(Arc<Worker>, Option<OwnedSemaphorePermit>)> {
+        let permits = {
+            let mut workers = self.workers.lock();
+            let key_workers = workers.entry(key.clone()).or_insert_with(|| KeyWorkers {
+                permits: Arc::new(Semaphore::new(if key.spec.multiplex {
+                    1
+                } else {
+                    self.max_workers_per_key
+                })),
+                idle: Vec::new(),
+            });
+            // A multiplexed worker takes any number of requests once it is started.
+            if key.spec.multiplex {
+                if let Some(worker) = key_workers.take_idle(true) {
+                    return Ok((worker, None));
+                }
+            }
+            key_workers.permits.dupe()
+        };
+
+        let permit = permits.acquire_owned().await?;
+        // Another request may have released or started a worker while this one waited. The entry
+        // is kept while its permits are referenced.
+        let existing = self
+            .workers
+            .lock()
+            .get_mut(key)
+            .and_then(|w| w.take_idle(key.spec.multiplex));
+        let worker = match existing {
+            Some(worker) => worker,
+            None => {
+                let worker = self.spawn(key, root).await?;
+                if key.spec.multiplex {
+                    if let Some(key_workers) = self.workers.lock().get_mut(key) {
+                        key_workers.idle.push((worker.dupe(), Instant::now()));
+                    }
+                }
+                worker
+            }
+        };
+        Ok((
+            worker,
+            if key.spec.multiplex {
+                None
+            } else {
+                Some(permit)
+            },
+        ))
+    }
+
+    /// Start a worker. This creates files and a process, so it runs on the blocking pool.
+    async fn spawn(&self, key: &WorkerKey, root: &AbsNormPath) -> anyhow::Result<Arc<Worker>> {
+        let log_id = self.next_log_id.fetch_add(1, Ordering::Relaxed);
+        let log_path = self
+            .log_dir
+            .join(ForwardRelativePathBuf::unchecked_new(format!(
+                "worker-{}.log",
+                log_id
+            )));
+        // The scratch directory of an action is only known per request, so every worker gets a
+        // temporary directory of its own instead.
+        let tmp_dir = self
+            .log_dir
+            .join(ForwardRelativePathBuf::unchecked_new(format!(
+                "worker-{}-tmp",
+                log_id
+            )));
+        let key = key.clone();
+        let root = root.to_buf();
+        let worker = tokio::task::spawn_blocking(move || {
+            fs_util::create_dir_all(&tmp_dir)?;
+            Worker::spawn(
+                &key.spec,
+                &root,
+                &key.env,
+                key.env_inheritance.as_ref(),
+                &log_path,
+                &tmp_dir,
+            )
+        })
+        .await??;
+        Ok(Arc::new(worker))
+    }
+
+    /// Done with a worker which can process more requests. The permit is released afterwards, so
+    /// that a request waiting for it finds the worker.
+    fn release(&self, key: &WorkerKey, worker: Arc<Worker>, permit: Option<OwnedSemaphorePermit>) {
+        if worker.is_alive() {
+            if let Some(key_workers) = self.workers.lock().get_mut(key) {
+                if key.spec.multiplex {
+                    for (w, last_used) in &mut key_workers.idle {
+                        if Arc::ptr_eq(w, &worker) {
+                            *last_used = Instant::now();
+                        }
+                    }
+                } else {
+                    key_workers.idle.push((worker, Instant::now()));
+                }
+            }
+        }
+        drop(permit);
+    }
+
+    fn discard(&self, key: &WorkerKey, worker: &Arc<Worker>) {
+        worker.kill();
+        if let Some(key_workers) = self.workers.lock().get_mut(key) {
+            key_workers.idle.retain(|(w, _)| !Arc::ptr_eq(w, worker));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::cas_digest::CasDigestConfig;
+    use buck2_common::file_ops::TrackedFileDigest;
+    use buck2_common::liveliness_observer::NoopLivelinessObserver;
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_request() -> anyhow::Result<()> {
+        let request = WorkRequest {
+            arguments: vec!["a".to_owned()],
+            inputs: vec![WorkInput {
+                path: "in".to_owned(),
+            }],
+            request_id: 3,
+            cancel: false,
+        };
+        assert_eq!(
+            b"{\"arguments\":[\"a\"],\"inputs\":[{\"path\":\"in\"}],\"requestId\":3}\n".as_slice(),
+            encode_request(WorkerProtocol::Json, &request)?
+        );
+        let proto = encode_request(WorkerProtocol::Proto, &request)?;
+        assert_eq!(
+            request,
+            WorkRequest::decode_length_delimited(proto.as_slice())?
+        );
+
+        let cancel = WorkRequest {
+            arguments: Vec::new(),
+            inputs: Vec::new(),
+            request_id: 3,
+            cancel: true,
+        };
+        assert_eq!(
+            b"{\"arguments\":[],\"inputs\":[],\"requestId\":3,\"cancel\":true}\n".as_slice(),
+            encode_request(WorkerProtocol::Json, &cancel)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_worker_exit() {
+        assert!(is_worker_exit(
+            &WorkerError::Crashed("worker".to_owned()).into()
+        ));
+        assert!(is_worker_exit(
+            &anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+                .context("Error writing to worker")
+        ));
+        assert!(!is_worker_exit(
+            &WorkerError::InvalidResponse("worker".to_owned(), "error".to_owned()).into()
+        ));
+        assert!(!is_worker_exit(&WorkerError::EmptyCommandLine.into()));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_worker_pool() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let root = temp.path().root();
+        let log_dir = root.join(ForwardRelativePathBuf::unchecked_new("workers".to_owned()));
+        let pool = WorkerPool::new(
+            log_dir.clone(),
+            DEFAULT_MAX_WORKERS_PER_KEY,
+            DEFAULT_WORKER_IDLE_TIMEOUT,
+        );
+        let spec = counting_worker();
+
+        for expected in ["1", "2"] {
+            assert_eq!(expected, exec(&pool, &spec, root).await?);
+        }
+
+        // A worker whose inputs changed is not reused.
+        let rebuilt = WorkerSpec {
+            inputs_digest: Some(TrackedFileDigest::from_content(
+                b"rebuilt",
+                CasDigestConfig::testing_default(),
+            )),
+            ..spec.clone()
+        };
+        assert_eq!("1", exec(&pool, &rebuilt, root).await?);
+        assert_eq!("3", exec(&pool, &spec, root).await?);
+
+        // Workers remove their logs and temporary directories when they are dropped.
+        drop(pool);
+        assert!(remaining_files(&log_dir).await?.is_empty());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_worker_pool_keeps_crash_log() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let root = temp.path().root();
+        let log_dir = root.join(ForwardRelativePathBuf::unchecked_new("workers".to_owned()));
+        let pool = WorkerPool::new(
+            log_dir.clone(),
+            DEFAULT_MAX_WORKERS_PER_KEY,
+            DEFAULT_WORKER_IDLE_TIMEOUT,
+        );
+        let spec = WorkerSpec {
+            exe: vec![
+                "sh".to_owned(),
+                "-c".to_owned(),
+                "echo crashed >&2; exit 1".to_owned(),
+            ],
+            protocol: WorkerProtocol::Json,
+            multiplex: false,
+            inputs_digest: None,
+        };
+
+        let res = pool
+            .exec(
+                &spec,
+                root,
+                &[],
+                &[],
+                [],
+                None,
+                None,
+                &*NoopLivelinessObserver::create(),
+            )
+            .await;
+        assert!(res.is_err());
+
+        // The worker is restarted once, and both logs are kept.
+        drop(pool);
+        let remaining = remaining_files(&log_dir).await?;
+        assert_eq!(vec!["worker-0.log", "worker-1.log"], remaining);
+        for name in remaining {
+            let log = fs_util::read_to_string(log_dir.join(ForwardRelativePathBuf::new(name)?))?;
+            assert_eq!("crashed\n", log);
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_worker_pool_max_workers_per_key() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let root = temp.path().root();
+        let log_dir = root.join(ForwardRelativePathBuf::unchecked_new("workers".to_owned()));
+        let pool = WorkerPool::new(log_dir, 1, DEFAULT_WORKER_IDLE_TIMEOUT);
+        let spec = counting_worker();
+
+        // The second request waits for the only worker instead of starting another one.
+        let (a, b) =
+            futures::future::join(exec(&pool, &spec, root), exec(&pool, &spec, root)).await;
+        let mut outputs = vec![a?, b?];
+        outputs.sort();
+        assert_eq!(vec!["1", "2"], outputs);
+        assert_eq!(1, pool.next_log_id.load(Ordering::Relaxed));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_worker_pool_evicts_idle() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let root = temp.path().root();
+        let log_dir = root.join(ForwardRelativePathBuf::unchecked_new("workers".to_owned()));
+        let pool = WorkerPool::new(log_dir.clone(), DEFAULT_MAX_WORKERS_PER_KEY, Duration::ZERO);
+        let spec = counting_worker();
+
+        assert_eq!("1", exec(&pool, &spec, root).await?);
+        pool.evict_idle();
+        assert!(remaining_files(&log_dir).await?.is_empty());
+        assert!(pool.workers.lock().is_empty());
+
+        // A new worker is started for the next request.
+        assert_eq!("1", exec(&pool, &spec, root).await?);
+        Ok(())
+    }
+
+    /// Responds to every request with the number of requests it has processed so far.
+    fn counting_worker() -> WorkerSpec {
+        WorkerSpec {
+            exe: vec![
+                "sh".to_owned(),
+                "-c".to_owned(),
+                "n=0; while read -r line; do n=$((n+1)); echo \"{\\\"exitCode\\\":1,\\\"output\\\":\\\"$n\\\"}\"; done".to_owned(),
+                "worker".to_owned(),
+            ],
+            protocol: WorkerProtocol::Json,
+            multiplex: false,
+            inputs_digest: None,
+        }
+    }
+
+    /// Run a request with `arg` as its argument, and return the output of the worker.
+    async fn exec(
+        pool: &WorkerPool,
+        spec: &WorkerSpec,
+        root: &AbsNormPath,
+    ) -> anyhow::Result<String> {
+        let (status, stdout, stderr) = pool
+            .exec(
+                spec,
+                root,
+                &["arg".to_owned()],
+                &[],
+                [("KEY", "VALUE")],
+                None,
+                None,
+                &*NoopLivelinessObserver::create(),
+            )
+            .await?;
+        assert!(matches!(
+            status,
+            GatherOutputStatus::Finished { exit_code: 1, .. }
+        ));
+        assert!(stdout.is_empty());
+        Ok(String::from_utf8(stderr)?)
+    }
+
+    /// Files left in `dir` once the workers have finished cleaning up after themselves.
+    async fn remaining_files(dir: &AbsNormPath) -> anyhow::Result<Vec<String>> {
+        // Cleanup happens on the blocking pool, so wait for the temporary directories to go.
+        for _ in 0..100 {
+            let mut names = fs_util::read_dir(dir)?
+                .map(|e| Ok(e?.file_name().to_string_lossy().into_owned()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            if names.iter().all(|n| n.ends_with(".log")) {
+                names.sort();
+                return Ok(names);
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Err(anyhow::anyhow!("Workers did not clean up `{}`", dir))
+    }
+}
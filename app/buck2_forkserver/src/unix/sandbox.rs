@@ -0,0 +1,597 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Hermetic sandbox for local actions, using Linux user and mount namespaces.
+//!
+//! The command runs in a private mount namespace whose root is an empty tmpfs. Only the system
+//! directories needed to run tools and the paths declared by the action are bind-mounted into it,
+//! at the same absolute paths as outside, so the command line and environment don't change.
+//! Anything else the action reads (e.g. undeclared files in the repo or `buck-out`) is missing,
+//! as it would be on remote execution.
+//!
+//! Every declared path is its own bind mount, except the ones already exposed by the mount of a
+//! parent directory, and the kernel limits the number of mounts in a namespace
+//! (`fs.mount-max`, 100000 by default). Actions with more inputs than that fail with an error
+//! saying so, and must run without the sandbox.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::Context as _;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use nix::errno::Errno;
+use nix::fcntl::open;
+use nix::fcntl::OFlag;
+use nix::mount::mount;
+use nix::mount::umount2;
+use nix::mount::MntFlags;
+use nix::mount::MsFlags;
+use nix::sched::unshare;
+use nix::sched::CloneFlags;
+use nix::sys::stat::fchmodat;
+use nix::sys::stat::FchmodatFlags;
+use nix::sys::stat::Mode;
+use nix::sys::statvfs::statvfs;
+use nix::sys::statvfs::FsFlags;
+use nix::unistd::chdir;
+use nix::unistd::close;
+use nix::unistd::getgid;
+use nix::unistd::getuid;
+use nix::unistd::mkdir;
+use nix::unistd::pivot_root;
+use nix::unistd::write;
+
+/// System directories exposed read-only, when they exist, so that the usual tools can run.
+const SYSTEM_DIRS: &[&str] = &[
+    "/bin", "/etc", "/lib", "/lib32", "/lib64", "/nix", "/opt", "/sbin", "/usr",
+];
+
+/// Directories exposed read-write, as many tools need them.
+const DEVICE_DIRS: &[&str] = &["/dev", "/proc"];
+
+/// Used when `/proc/sys/fs/mount-max` can't be read.
+const DEFAULT_MOUNT_MAX: usize = 100_000;
+
+/// Where the private root of each sandbox is mounted. Every sandbox has its own mount namespace,
+/// so they can all use the same directory.
+pub(crate) struct SandboxContainer {
+    mount_point: AbsNormPathBuf,
+}
+
+impl SandboxContainer {
+    pub(crate) fn new(forkserver_state_dir: &AbsNormPath) -> anyhow::Result<Self> {
+        let mount_point = forkserver_state_dir.join(ForwardRelativePath::unchecked_new("sandbox"));
+        fs_util::create_dir_all(&mount_point)?;
+        Ok(Self { mount_point })
+    }
+
+    /// Make `cmd` run in a sandbox which only exposes the paths in `sandbox`.
+    pub(crate) fn apply(
+        &self,
+        cmd: &mut Command,
+        sandbox: &buck2_forkserver_proto::Sandbox,
+        cwd: Option<&OsStr>,
+    ) -> anyhow::Result<()> {
+        let setup = SandboxSetup::new(&self.mount_point, sandbox, cwd)
+            .context("Error preparing the sandbox")?;
+        // SAFETY: `enter` only makes system calls, it doesn't allocate or take locks, so it is
+        // safe to run between fork and exec in a multithreaded process.
+        unsafe {
+            cmd.pre_exec(move || setup.enter().map_err(io::Error::from));
+        }
+        Ok(())
+    }
+}
+
+struct SandboxMount {
+    source: CString,
+    /// The path of the source in the new root, before it becomes the root.
+    target: CString,
+    writable: bool,
+    /// The mounts under the source, as paths in the new root. The bind exposes them too, but
+    /// remounting it read-only doesn't apply to them, so they are remounted one by one.
+    submounts: Vec<CString>,
+}
+
+/// Everything the child needs to enter the sandbox, computed before forking as nothing can be
+/// allocated after.
+struct SandboxSetup {
+    new_root: CString,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    /// Directories to create in the new root, parents first.
+    dirs: Vec<CString>,
+    /// Empty files to create in the new root, to mount files on.
+    files: Vec<CString>,
+    /// Directory in the new root which everyone can write to.
+    tmp: CString,
+    /// Parents are mounted before their children, so they don't hide them.
+    mounts: Vec<SandboxMount>,
+    cwd: CString,
+}
+
+fn c_path(path: &Path) -> anyhow::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .with_context(|| format!("Path contains a NUL byte: `{}`", path.display()))
+}
+
+impl SandboxSetup {
+    fn new(
+        new_root: &AbsNormPath,
+        sandbox: &buck2_forkserver_proto::Sandbox,
+        cwd: Option<&OsStr>,
+    ) -> anyhow::Result<Self> {
+        // If a path is both an input and an output, it must be writable.
+        let mut paths: BTreeMap<PathBuf, bool> = BTreeMap::new();
+        for dir in SYSTEM_DIRS {
+            paths.insert(PathBuf::from(dir), false);
+        }
+        for dir in DEVICE_DIRS {
+            paths.insert(PathBuf::from(dir), true);
+        }
+        for input in &sandbox.inputs {
+            paths
+                .entry(PathBuf::from(OsStr::from_bytes(input)))
+                .or_insert(false);
+        }
+        for output in &sandbox.outputs {
+            paths.insert(PathBuf::from(OsStr::from_bytes(output)), true);
+        }
+        let mut paths: Vec<(PathBuf, bool)> = paths.into_iter().collect();
+        paths.sort_by_key(|(path, _)| path.components().count());
+
+        let in_root = |path: &Path| -> anyhow::Result<CString> {
+            let relative = path
+                .strip_prefix("/")
+                .with_context(|| format!("Sandbox paths must be absolute: `{}`", path.display()))?;
+            c_path(&new_root.as_path().join(relative))
+        };
+
+        let cwd = cwd.map_or_else(|| PathBuf::from("/"), PathBuf::from);
+        let mut dirs: BTreeSet<PathBuf> = cwd.ancestors().map(Path::to_owned).collect();
+        let tmp = Path::new("/tmp");
+        dirs.insert(tmp.to_owned());
+        let mut files = Vec::new();
+        let mut mounts = Vec::new();
+        let mount_points = mount_points()?;
+        // Paths already exposed, with whether they are writable.
+        let mut mounted: BTreeMap<PathBuf, bool> = BTreeMap::new();
+        for (path, writable) in paths {
+            // Bind mounts are recursive, so a path inside a mounted directory is already visible.
+            if path
+                .ancestors()
+                .skip(1)
+                .any(|x| mounted.get(x) == Some(&writable))
+            {
+                continue;
+            }
+            let metadata = match std::fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Error reading `{}`", path.display()));
+                }
+            };
+            dirs.extend(path.ancestors().skip(1).map(Path::to_owned));
+            if metadata.is_dir() {
+                dirs.insert(path.clone());
+            } else {
+                files.push(in_root(&path)?);
+            }
+            let submounts = if writable {
+                Vec::new()
+            } else {
+                let source = std::fs::canonicalize(&path)
+                    .with_context(|| format!("Error resolving `{}`", path.display()))?;
+                mount_points
+                    .iter()
+                    .filter_map(|x| x.strip_prefix(&source).ok())
+                    .filter(|x| !x.as_os_str().is_empty())
+                    .map(|x| in_root(&path.join(x)))
+                    .collect::<anyhow::Result<_>>()?
+            };
+            mounts.push(SandboxMount {
+                source: c_path(&path)?,
+                target: in_root(&path)?,
+                writable,
+                submounts,
+            });
+            mounted.insert(path, writable);
+        }
+        dirs.remove(Path::new("/"));
+        check_mount_limit(mounts.len())?;
+
+        Ok(Self {
+            new_root: c_path(new_root.as_path())?,
+            uid_map: format!("{0} {0} 1\n", getuid()).into_bytes(),
+            gid_map: format!("{0} {0} 1\n", getgid()).into_bytes(),
+            // `BTreeSet` orders parents before their children.
+            dirs: dirs
+                .iter()
+                .map(|x| in_root(x))
+                .collect::<anyhow::Result<_>>()?,
+            files,
+            tmp: in_root(tmp)?,
+            mounts,
+            cwd: c_path(&cwd)?,
+        })
+    }
+
+    /// Runs in the child, after fork and before exec.
+    fn enter(&self) -> nix::Result<()> {
+        unshare(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNS)?;
+        // Map our user to itself, so that files created in the sandbox have the right owner.
+        write_file(cstr(b"/proc/self/setgroups\0"), b"deny")?;
+        write_file(cstr(b"/proc/self/uid_map\0"), &self.uid_map)?;
+        write_file(cstr(b"/proc/self/gid_map\0"), &self.gid_map)?;
+
+        // Don't let our mounts propagate to the parent namespace.
+        mount(
+            None::<&CStr>,
+            cstr(b"/\0"),
+            None::<&CStr>,
+            MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+            None::<&CStr>,
+        )?;
+        mount(
+            Some(cstr(b"tmpfs\0")),
+            self.new_root.as_c_str(),
+            Some(cstr(b"tmpfs\0")),
+            MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+            None::<&CStr>,
+        )?;
+
+        // Create the mount points while everything is still in the tmpfs.
+        for dir in &self.dirs {
+            match mkdir(dir.as_c_str(), Mode::from_bits_truncate(0o755)) {
+                Ok(()) | Err(Errno::EEXIST) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        fchmodat(
+            None,
+            self.tmp.as_c_str(),
+            Mode::from_bits_truncate(0o1777),
+            FchmodatFlags::FollowSymlink,
+        )?;
+        for file in &self.files {
+            let fd = open(
+                file.as_c_str(),
+                OFlag::O_CREAT | OFlag::O_WRONLY | OFlag::O_CLOEXEC,
+                Mode::from_bits_truncate(0o644),
+            )?;
+            close(fd)?;
+        }
+
+        for m in &self.mounts {
+            mount(
+                Some(m.source.as_c_str()),
+                m.target.as_c_str(),
+                None::<&CStr>,
+                MsFlags::MS_BIND | MsFlags::MS_REC,
+                None::<&CStr>,
+            )?;
+            if !m.writable {
+                for target in std::iter::once(&m.target).chain(&m.submounts) {
+                    remount_read_only(target)?;
+                }
+            }
+        }
+
+        chdir(self.new_root.as_c_str())?;
+        // Stack the new root on top of the old one, then detach the old one.
+        pivot_root(cstr(b".\0"), cstr(b".\0"))?;
+        umount2(cstr(b".\0"), MntFlags::MNT_DETACH)?;
+        chdir(self.cwd.as_c_str())?;
+        Ok(())
+    }
+}
+
+/// Fail before forking if the sandbox needs more mounts than the kernel allows, as the child can
+/// only report a bare `ENOSPC`.
+fn check_mount_limit(mounts: usize) -> anyhow::Result<()> {
+    let mount_max = std::fs::read_to_string("/proc/sys/fs/mount-max")
+        .ok()
+        .and_then(|x| x.trim().parse().ok())
+        .unwrap_or(DEFAULT_MOUNT_MAX);
+    // The new mount namespace starts with a copy of the mounts of ours.
+    let existing = std::fs::read_to_string("/proc/self/mountinfo")
+        .context("Error reading `/proc/self/mountinfo`")?
+        .lines()
+        .count();
+    let available = mount_max.saturating_sub(existing);
+    // Plus the tmpfs for the new root.
+    let needed = mounts + 1;
+    if needed > available {
+        return Err(anyhow::anyhow!(
+            "The sandbox needs {} mounts for the inputs and outputs of the action, but only {} \
+            more are allowed by `fs.mount-max`. Run the action without the sandbox, or raise \
+            the limit",
+            needed,
+            available
+        ));
+    }
+    Ok(())
+}
+
+fn cstr(bytes: &'static [u8]) -> &'static CStr {
+    CStr::from_bytes_with_nul(bytes).unwrap()
+}
+
+fn write_file(path: &CStr, contents: &[u8]) -> nix::Result<()> {
+    let fd = open(path, OFlag::O_WRONLY | OFlag::O_CLOEXEC, Mode::empty())?;
+    let res = write(fd, contents);
+    close(fd)?;
+    res.map(|_| ())
+}
+
+fn remount_read_only(target: &CStr) -> nix::Result<()> {
+    // A remount in a user namespace must keep the flags which are locked on the original mount.
+    let flags = locked_flags(statvfs(target)?.flags());
+    mount(
+        None::<&CStr>,
+        target,
+        None::<&CStr>,
+        MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY | flags,
+        None::<&CStr>,
+    )
+}
+
+/// The mount points of our mount namespace, which the sandbox starts with a copy of.
+fn mount_points() -> anyhow::Result<Vec<PathBuf>> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+        .context("Error reading `/proc/self/mountinfo`")?;
+    mountinfo
+        .lines()
+        .map(|line| {
+            let mount_point = line
+                .split(' ')
+                .nth(4)
+                .with_context(|| format!("Malformed line in `/proc/self/mountinfo`: `{}`", line))?;
+            Ok(PathBuf::from(OsStr::from_bytes(&unescape_mountinfo(
+                mount_point.as_bytes(),
+            ))))
+        })
+        .collect()
+}
+
+/// Paths in `/proc/self/mountinfo` escape spaces, tabs, newlines and backslashes as `\ooo`.
+fn unescape_mountinfo(path: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(path.len());
+    let mut i = 0;
+    while i < path.len() {
+        let octal = path
+            .get(i + 1..i + 4)
+            .and_then(|x| u8::from_str_radix(std::str::from_utf8(x).ok()?, 8).ok());
+        match octal {
+            Some(c) if path[i] == b'\\' => {
+                res.push(c);
+                i += 4;
+            }
+            _ => {
+                res.push(path[i]);
+                i += 1;
+            }
+        }
+    }
+    res
+}
+
+fn locked_flags(flags: FsFlags) -> MsFlags {
+    let mut res = MsFlags::empty();
+    for (fs_flag, ms_flag) in [
+        (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+        (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+        (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+        (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+        (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+    ] {
+        if flags.contains(fs_flag) {
+            res |= ms_flag;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_setup() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let input = tempdir.path().join("input");
+        std::fs::write(&input, "")?;
+        let output = tempdir.path().join("out");
+        std::fs::create_dir(&output)?;
+        let sandbox = buck2_forkserver_proto::Sandbox {
+            inputs: vec![
+                input.as_os_str().as_bytes().to_vec(),
+                b"/does/not/exist".to_vec(),
+            ],
+            outputs: vec![output.as_os_str().as_bytes().to_vec()],
+        };
+
+        let setup = SandboxSetup::new(
+            AbsNormPath::new("/sandbox")?,
+            &sandbox,
+            Some(output.as_os_str()),
+        )?;
+
+        let in_root = |path: &Path| format!("/sandbox{}", path.display());
+        let mounts: Vec<_> = setup
+            .mounts
+            .iter()
+            .map(|m| (m.target.to_str().unwrap().to_owned(), m.writable))
+            .collect();
+        assert!(mounts.contains(&(in_root(&input), false)));
+        assert!(mounts.contains(&(in_root(&output), true)));
+        assert!(!mounts.iter().any(|(x, _)| x.contains("/does/not/exist")));
+        assert!(mounts.contains(&("/sandbox/usr".to_owned(), false)));
+        assert_eq!(
+            vec![in_root(&input)],
+            setup
+                .files
+                .iter()
+                .map(|x| x.to_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        );
+        let dirs: Vec<_> = setup.dirs.iter().map(|x| x.to_str().unwrap()).collect();
+        assert!(dirs.contains(&"/sandbox/tmp"));
+        assert!(dirs.contains(&in_root(tempdir.path()).as_str()));
+        assert!(!dirs.contains(&"/sandbox"));
+        assert_eq!(output.as_os_str().as_bytes(), setup.cwd.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sandbox_setup_nested_paths() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let input = tempdir.path().join("input");
+        std::fs::create_dir(&input)?;
+        std::fs::write(input.join("file"), "")?;
+        let sandbox = buck2_forkserver_proto::Sandbox {
+            inputs: vec![
+                input.as_os_str().as_bytes().to_vec(),
+                input.join("file").as_os_str().as_bytes().to_vec(),
+            ],
+            outputs: vec![],
+        };
+
+        let setup = SandboxSetup::new(AbsNormPath::new("/sandbox")?, &sandbox, None)?;
+
+        // The file is visible through the mount of its directory.
+        let targets: Vec<_> = setup
+            .mounts
+            .iter()
+            .map(|m| m.target.to_str().unwrap().to_owned())
+            .filter(|x| x.contains(tempdir.path().to_str().unwrap()))
+            .collect();
+        assert_eq!(vec![format!("/sandbox{}", input.display())], targets);
+        assert!(setup.files.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unescape_mountinfo() {
+        assert_eq!(b"/a b\\c".to_vec(), unescape_mountinfo(b"/a\\040b\\134c"));
+        assert_eq!(b"/a\\9".to_vec(), unescape_mountinfo(b"/a\\9"));
+    }
+
+    /// Whether we can create a user namespace, which some systems (e.g. containers) don't allow.
+    /// Tests which run a sandbox print that they are skipped when we can't.
+    fn user_namespaces_allowed(test: &str) -> anyhow::Result<bool> {
+        let mut cmd = Command::new("/bin/true");
+        // SAFETY: `unshare` is a system call.
+        unsafe {
+            cmd.pre_exec(|| unshare(CloneFlags::CLONE_NEWUSER).map_err(io::Error::from));
+        }
+        match cmd.status() {
+            Ok(_) => Ok(true),
+            Err(e) if e.raw_os_error() == Some(Errno::EPERM as i32) => {
+                eprintln!("Skipping `{}`: user namespaces are not allowed", test);
+                Ok(false)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Run `script` with `sh` in a sandbox with the given inputs, in the `out` directory, which
+    /// is the only output.
+    fn run_in_sandbox(
+        tempdir: &Path,
+        inputs: &[&Path],
+        script: &str,
+    ) -> anyhow::Result<std::process::ExitStatus> {
+        let state_dir = AbsNormPathBuf::new(tempdir.join("state"))?;
+        let output = tempdir.join("out");
+        std::fs::create_dir_all(&output)?;
+        let sandbox = buck2_forkserver_proto::Sandbox {
+            inputs: inputs
+                .iter()
+                .map(|x| x.as_os_str().as_bytes().to_vec())
+                .collect(),
+            outputs: vec![output.as_os_str().as_bytes().to_vec()],
+        };
+
+        let mut cmd = Command::new("/bin/sh");
+        cmd.arg("-c").arg(script).current_dir(&output);
+        SandboxContainer::new(&state_dir)?.apply(&mut cmd, &sandbox, Some(output.as_os_str()))?;
+        Ok(cmd.status()?)
+    }
+
+    #[test]
+    fn test_sandbox_run() -> anyhow::Result<()> {
+        if !user_namespaces_allowed("test_sandbox_run")? {
+            return Ok(());
+        }
+
+        let tempdir = tempfile::tempdir()?;
+        let input = tempdir.path().join("input");
+        std::fs::write(&input, "input")?;
+        let status = run_in_sandbox(
+            tempdir.path(),
+            &[&input],
+            &format!("cat '{}' > result", input.display()),
+        )?;
+
+        assert!(status.success(), "{}", status);
+        assert_eq!(
+            "input",
+            std::fs::read_to_string(tempdir.path().join("out/result"))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sandbox_hides_undeclared_inputs() -> anyhow::Result<()> {
+        if !user_namespaces_allowed("test_sandbox_hides_undeclared_inputs")? {
+            return Ok(());
+        }
+
+        let tempdir = tempfile::tempdir()?;
+        let input = tempdir.path().join("src/input");
+        std::fs::create_dir(tempdir.path().join("src"))?;
+        std::fs::write(&input, "input")?;
+        // Next to the declared input, and in a directory with no declared inputs.
+        let sibling = tempdir.path().join("src/sibling");
+        std::fs::write(&sibling, "sibling")?;
+        std::fs::create_dir(tempdir.path().join("other"))?;
+        let other = tempdir.path().join("other/file");
+        std::fs::write(&other, "other")?;
+        // Both exist outside of the sandbox.
+        assert!(sibling.exists() && other.exists());
+
+        let status = run_in_sandbox(
+            tempdir.path(),
+            &[&input],
+            &format!(
+                "test -e '{}' && test ! -e '{}' && test ! -e '{}'",
+                input.display(),
+                sibling.display(),
+                other.display()
+            ),
+        )?;
+
+        assert!(status.success(), "{}", status);
+        Ok(())
+    }
+}
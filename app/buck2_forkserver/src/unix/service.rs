@@ -12,6 +12,7 @@ use std::fs::OpenOptions;
 use std::io::Write;
 use std::os::unix::ffi::OsStrExt;
 use std::pin::Pin;
+use std::process::Command;
 use std::sync::Arc;
 
 use anyhow::Context as _;
@@ -47,6 +48,8 @@ use crate::run::stream_command_events;
 use crate::run::timeout_into_cancellation;
 use crate::run::DefaultKillProcess;
 use crate::run::GatherOutputStatus;
+#[cfg(target_os = "linux")]
+use crate::unix::sandbox::SandboxContainer;
 
 // Not quite BoxStream: it has to be Sync (...)
 type RunStream =
@@ -57,6 +60,10 @@ pub struct UnixForkserverService {
 
     /// State for Miniperf.
     miniperf: Option<MiniperfContainer>,
+
+    /// State for sandboxed commands.
+    #[cfg(target_os = "linux")]
+    sandbox: SandboxContainer,
 }
 
 impl UnixForkserverService {
@@ -69,8 +76,32 @@ impl UnixForkserverService {
         Ok(Self {
             log_reload_handle,
             miniperf,
+            #[cfg(target_os = "linux")]
+            sandbox: SandboxContainer::new(state_dir)?,
         })
     }
+
+    #[cfg(target_os = "linux")]
+    fn apply_sandbox(
+        &self,
+        cmd: &mut Command,
+        sandbox: &buck2_forkserver_proto::Sandbox,
+        cwd: Option<&OsStr>,
+    ) -> anyhow::Result<()> {
+        self.sandbox.apply(cmd, sandbox, cwd)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn apply_sandbox(
+        &self,
+        _cmd: &mut Command,
+        _sandbox: &buck2_forkserver_proto::Sandbox,
+        _cwd: Option<&OsStr>,
+    ) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!(
+            "Sandboxed commands are only supported on Linux"
+        ))
+    }
 }
 
 #[async_trait::async_trait]
@@ -109,6 +140,7 @@ impl Forkserver for UnixForkserverService {
                 cwd,
                 timeout,
                 enable_miniperf,
+                sandbox,
             } = msg;
 
             let exe = OsStr::from_bytes(&exe);
@@ -119,6 +151,9 @@ impl Forkserver for UnixForkserverService {
                 .transpose()
                 .context("Invalid timeout")?;
 
+            // Miniperf and its output are not visible in the sandbox.
+            let enable_miniperf = enable_miniperf && sandbox.is_none();
+
             let (mut cmd, miniperf_output) = match (enable_miniperf, &self.miniperf) {
                 (true, Some(miniperf)) => {
                     let mut cmd = background_command(miniperf.miniperf.as_path());
@@ -153,6 +188,10 @@ impl Forkserver for UnixForkserverService {
                 }
             }
 
+            if let Some(sandbox) = &sandbox {
+                self.apply_sandbox(&mut cmd, sandbox, cwd)?;
+            }
+
             let mut cmd = prepare_command(cmd);
             let child = cmd.spawn();
 
@@ -68,6 +68,9 @@ struct LoadedModuleData {
     loaded_modules: LoadedModules,
     #[derivative(Debug = "ignore")]
     env: FrozenModule,
+    /// Key of the module in the [`StarlarkModuleCache`](crate::module_cache::StarlarkModuleCache),
+    /// `None` if the cache is disabled, or was not used for this module or one it loads.
+    cache_key: Option<String>,
 }
 
 impl LoadedModule {
@@ -75,11 +78,21 @@ impl LoadedModule {
         path: OwnedStarlarkModulePath,
         loaded_modules: LoadedModules,
         env: FrozenModule,
+    ) -> Self {
+        Self::new_with_cache_key(path, loaded_modules, env, None)
+    }
+
+    pub fn new_with_cache_key(
+        path: OwnedStarlarkModulePath,
+        loaded_modules: LoadedModules,
+        env: FrozenModule,
+        cache_key: Option<String>,
     ) -> Self {
         Self(Arc::new(LoadedModuleData {
             path,
             loaded_modules,
             env,
+            cache_key,
         }))
     }
 
@@ -98,6 +111,10 @@ impl LoadedModule {
     pub fn env(&self) -> &FrozenModule {
         &self.0.env
     }
+
+    pub fn cache_key(&self) -> Option<&str> {
+        self.0.cache_key.as_deref()
+    }
 }
 
 pub struct InterpreterFileLoader {
@@ -68,6 +68,7 @@ impl StarlarkFileType {
             },
             enable_load_reexport: false,
             enable_top_level_stmt: true,
+            enable_bytes: true,
             ..Dialect::Standard
         };
 
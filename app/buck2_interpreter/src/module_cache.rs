@@ -0,0 +1,474 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! An on-disk cache of evaluated `.bzl` files, so that a new daemon can reload modules instead of
+//! evaluating them again.
+//!
+//! Modules are stored as a [`SerializedModule`], under a key which hashes the buck2 version, the
+//! path and content of the file, and the keys of the modules it loads. The buckconfig values read
+//! during the evaluation are stored with the module and checked again before reloading it, so
+//! changing them evaluates the file again.
+//!
+//! Only modules which [`FrozenModule::serialize`](starlark::environment::FrozenModule::serialize)
+//! supports are cached. Other modules, e.g. the ones defining rules or providers, are evaluated as
+//! usual. Side effects of the evaluation, such as `print` output, are not replayed on reload.
+//!
+//! The cache directory may be shared by several daemons. Each of them bounds its size, evicting
+//! the modules it used least recently.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use allocative::Allocative;
+use anyhow::Context;
+use buck2_common::legacy_configs::view::LegacyBuckConfigView;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use dice::DiceData;
+use dice::DiceDataBuilder;
+use dupe::Dupe;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use starlark::environment::SerializedModule;
+
+/// Suffix of files being written, which are renamed into place once complete.
+const TMP_SUFFIX: &str = ".tmp";
+
+/// Temporary files older than this were left behind by a daemon which died while writing them.
+const STALE_TMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// A buckconfig value read by the evaluation of a module.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BuckConfigRead {
+    /// Whether the value was read from the root cell buckconfig, rather than the buckconfig of
+    /// the cell of the file.
+    pub root: bool,
+    pub section: String,
+    pub key: String,
+    pub value: Option<String>,
+}
+
+/// A module, as stored in the [`StarlarkModuleCache`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedModule {
+    /// The buckconfig values read during evaluation, sorted.
+    pub config_reads: Vec<BuckConfigRead>,
+    pub module: SerializedModule,
+}
+
+/// The size and recency of the modules in the cache, keyed by their content key.
+#[derive(Default)]
+struct CacheIndex {
+    /// Size and last use of each module.
+    entries: HashMap<String, (u64, u64)>,
+    /// Modules by last use, least recently used first.
+    order: BTreeMap<u64, String>,
+    next_tick: u64,
+    total_bytes: u64,
+}
+
+impl CacheIndex {
+    /// Record `key` as the most recently used module.
+    fn insert(&mut self, key: String, size: u64) {
+        self.remove(&key);
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.order.insert(tick, key.clone());
+        self.entries.insert(key, (size, tick));
+        self.total_bytes += size;
+    }
+
+    fn touch(&mut self, key: &str) {
+        if let Some(&(size, _)) = self.entries.get(key) {
+            self.insert(key.to_owned(), size);
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((size, tick)) = self.entries.remove(key) {
+            self.order.remove(&tick);
+            self.total_bytes -= size;
+        }
+    }
+
+    /// Drop least recently used modules until the total size is at most `max_bytes`, and return
+    /// their keys.
+    fn evict(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.total_bytes > max_bytes {
+            let key = match self.order.pop_first() {
+                Some((_, key)) => key,
+                None => break,
+            };
+            if let Some((size, _)) = self.entries.remove(&key) {
+                self.total_bytes -= size;
+            }
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+#[derive(Allocative)]
+pub struct StarlarkModuleCache {
+    dir: AbsNormPathBuf,
+    /// Identifies the buck2 binary: globals, and the format of serialized modules, depend on it.
+    version: String,
+    max_bytes: u64,
+    /// Recency is tracked in memory, so after a restart modules are ordered by when they were
+    /// written.
+    #[allocative(skip)]
+    index: Mutex<CacheIndex>,
+}
+
+impl StarlarkModuleCache {
+    /// Open the cache in `dir`, which holds at most `max_bytes` of modules. Incomplete files left
+    /// by daemons which died while writing them are removed.
+    pub fn new(dir: AbsNormPathBuf, version: String, max_bytes: u64) -> anyhow::Result<Self> {
+        fs_util::create_dir_all(&dir)?;
+        let mut found = Vec::new();
+        for entry in fs_util::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.ends_with(TMP_SUFFIX) {
+                // Recent files might still be written by another daemon sharing the cache.
+                let stale = metadata
+                    .modified()?
+                    .elapsed()
+                    .map_or(false, |age| age > STALE_TMP_AGE);
+                if stale {
+                    if let Err(e) = fs_util::remove_all(entry.path()) {
+                        tracing::warn!("Error removing stale cached module: {:#}", e);
+                    }
+                }
+            } else {
+                found.push((metadata.modified()?, name, metadata.len()));
+            }
+        }
+        found.sort();
+
+        let mut index = CacheIndex::default();
+        for (_, key, size) in found {
+            index.insert(key, size);
+        }
+        let cache = Self {
+            dir,
+            version,
+            max_bytes,
+            index: Mutex::new(index),
+        };
+        cache.evict();
+        Ok(cache)
+    }
+
+    /// Remove the least recently used modules until the cache fits in `max_bytes`.
+    fn evict(&self) {
+        let evicted = self.index.lock().evict(self.max_bytes);
+        for key in evicted {
+            if let Err(e) = self.path(&key).and_then(fs_util::remove_all) {
+                tracing::warn!("Error evicting cached module: {:#}", e);
+            }
+        }
+    }
+
+    /// The key of a `.bzl` file with `content`, loading modules with keys `deps`
+    /// (see [`module_key`](StarlarkModuleCache::module_key)).
+    pub fn content_key<'a>(
+        &self,
+        path: &str,
+        content: &str,
+        deps: impl IntoIterator<Item = &'a str>,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        for x in [self.version.as_str(), path, content]
+            .into_iter()
+            .chain(deps)
+        {
+            // Hash the length too, so different lists of strings never hash the same bytes.
+            hasher.update(x.len().to_le_bytes());
+            hasher.update(x);
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// The key of an evaluated module, which the modules loading it use for their
+    /// [`content_key`](StarlarkModuleCache::content_key).
+    pub fn module_key(content_key: &str, config_reads: &[BuckConfigRead]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(content_key);
+        for read in config_reads {
+            hasher.update([read.root as u8]);
+            for x in [&read.section, &read.key] {
+                hasher.update(x.len().to_le_bytes());
+                hasher.update(x);
+            }
+            match &read.value {
+                None => hasher.update([0]),
+                Some(value) => {
+                    hasher.update([1]);
+                    hasher.update(value.len().to_le_bytes());
+                    hasher.update(value);
+                }
+            }
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    fn path(&self, key: &str) -> anyhow::Result<AbsNormPathBuf> {
+        Ok(self.dir.join(ForwardRelativePath::new(key)?))
+    }
+
+    /// The module stored under `content_key`, if any.
+    pub fn get(&self, content_key: &str) -> anyhow::Result<Option<CachedModule>> {
+        let path = self.path(content_key)?;
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            // Another daemon sharing the cache may have evicted it.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Error reading `{}`", path)),
+        };
+        let module = bincode::deserialize(&data)
+            .with_context(|| format!("Error decoding cached module `{}`", path))?;
+        self.index.lock().touch(content_key);
+        Ok(Some(module))
+    }
+
+    /// Store a module under `content_key`, replacing any previous one.
+    pub fn put(&self, content_key: &str, module: &CachedModule) -> anyhow::Result<()> {
+        let data = bincode::serialize(module)?;
+        // Write to a temporary file first, so concurrent readers never see a partial module.
+        let tmp = self.path(&format!(
+            "{}.{}{}",
+            content_key,
+            std::process::id(),
+            TMP_SUFFIX
+        ))?;
+        let size = data.len() as u64;
+        fs_util::write(&tmp, data)?;
+        fs_util::rename(&tmp, self.path(content_key)?)?;
+        self.index.lock().insert(content_key.to_owned(), size);
+        self.evict();
+        Ok(())
+    }
+}
+
+/// Buckconfig view which records the values read through it, so they can be stored with the
+/// module in the [`StarlarkModuleCache`].
+#[derive(Debug)]
+pub struct RecordingBuckConfigView<'a> {
+    inner: &'a dyn LegacyBuckConfigView,
+    root: bool,
+    reads: &'a RefCell<Vec<BuckConfigRead>>,
+}
+
+impl<'a> RecordingBuckConfigView<'a> {
+    pub fn new(
+        inner: &'a dyn LegacyBuckConfigView,
+        root: bool,
+        reads: &'a RefCell<Vec<BuckConfigRead>>,
+    ) -> Self {
+        Self { inner, root, reads }
+    }
+}
+
+impl LegacyBuckConfigView for RecordingBuckConfigView<'_> {
+    fn get(&self, section: &str, key: &str) -> anyhow::Result<Option<Arc<str>>> {
+        let value = self.inner.get(section, key)?;
+        self.reads.borrow_mut().push(BuckConfigRead {
+            root: self.root,
+            section: section.to_owned(),
+            key: key.to_owned(),
+            value: value.as_deref().map(str::to_owned),
+        });
+        Ok(value)
+    }
+}
+
+pub trait HasStarlarkModuleCache {
+    /// The module cache, if it is enabled.
+    fn get_starlark_module_cache(&self) -> Option<Arc<StarlarkModuleCache>>;
+}
+
+pub trait SetStarlarkModuleCache {
+    fn set_starlark_module_cache(&mut self, cache: Arc<StarlarkModuleCache>);
+}
+
+impl HasStarlarkModuleCache for DiceData {
+    fn get_starlark_module_cache(&self) -> Option<Arc<StarlarkModuleCache>> {
+        self.get::<Arc<StarlarkModuleCache>>()
+            .ok()
+            .map(|c| c.dupe())
+    }
+}
+
+impl SetStarlarkModuleCache for DiceDataBuilder {
+    fn set_starlark_module_cache(&mut self, cache: Arc<StarlarkModuleCache>) {
+        self.set(cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use buck2_common::legacy_configs::testing::legacy_buck_config_from_entries;
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+    use starlark::environment::Globals;
+    use starlark::environment::Module;
+    use starlark::eval::Evaluator;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+    use starlark::values::serialize::ValueSerializer;
+
+    use super::*;
+
+    #[test]
+    fn test_put_get() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let dir = AbsNormPathBuf::new(tempdir.path().to_path_buf())?;
+        let cache = StarlarkModuleCache::new(dir.clone(), "v1".to_owned(), 1024)?;
+
+        let key = cache.content_key("cell//a.bzl", "x = 1", ["dep"]);
+        assert_ne!(key, cache.content_key("cell//a.bzl", "x = 2", ["dep"]));
+        assert_ne!(key, cache.content_key("cell//a.bzl", "x = 1", ["dep2"]));
+        assert_ne!(
+            key,
+            StarlarkModuleCache::new(dir.clone(), "v2".to_owned(), 1024)?.content_key(
+                "cell//a.bzl",
+                "x = 1",
+                ["dep"]
+            )
+        );
+        assert!(cache.get(&key)?.is_none());
+
+        let globals = Globals::standard();
+        let module = Module::new();
+        Evaluator::new(&module).eval_module(
+            AstModule::parse("a.bzl", "x = [1, 'a']".to_owned(), &Dialect::Extended)?,
+            &globals,
+        )?;
+        let module = module.freeze()?;
+        let reads = vec![BuckConfigRead {
+            root: false,
+            section: "s".to_owned(),
+            key: "k".to_owned(),
+            value: Some("v".to_owned()),
+        }];
+        cache.put(
+            &key,
+            &CachedModule {
+                config_reads: reads.clone(),
+                module: module.serialize(ValueSerializer::new(), &[], &globals)?,
+            },
+        )?;
+
+        // Reopening the cache keeps complete modules, and the files other daemons are writing.
+        fs_util::write(dir.join(ForwardRelativePath::new("partial.1.tmp")?), "")?;
+        let cache = StarlarkModuleCache::new(dir.clone(), "v1".to_owned(), 1024)?;
+        assert!(fs_util::try_exists(
+            dir.join(ForwardRelativePath::new("partial.1.tmp")?)
+        )?);
+
+        let cached = cache.get(&key)?.unwrap();
+        assert_eq!(reads, cached.config_reads);
+        let reloaded =
+            cached
+                .module
+                .reload(ValueSerializer::new(), &[], &globals, &Dialect::Extended)?;
+        assert_eq!("[1, \"a\"]", reloaded.get("x")?.value().to_repr());
+        Ok(())
+    }
+
+    #[test]
+    fn test_eviction() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let dir = AbsNormPathBuf::new(tempdir.path().to_path_buf())?;
+        let module = || CachedModule {
+            config_reads: Vec::new(),
+            module: Module::new()
+                .freeze()
+                .unwrap()
+                .serialize(ValueSerializer::new(), &[], &Globals::standard())
+                .unwrap(),
+        };
+        let size = bincode::serialize(&module())?.len() as u64;
+
+        // Room for two modules, the least recently used one is evicted for the third.
+        let cache = StarlarkModuleCache::new(dir.clone(), "v1".to_owned(), 2 * size)?;
+        cache.put("a", &module())?;
+        cache.put("b", &module())?;
+        assert!(cache.get("a")?.is_some());
+        cache.put("c", &module())?;
+        assert!(cache.get("a")?.is_some());
+        assert!(cache.get("b")?.is_none());
+        assert!(cache.get("c")?.is_some());
+
+        // Reopening a cache with less room evicts the oldest modules.
+        let cache = StarlarkModuleCache::new(dir, "v1".to_owned(), size)?;
+        assert_eq!(
+            1,
+            ["a", "c"]
+                .iter()
+                .filter(|k| cache.get(k).unwrap().is_some())
+                .count()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_module_key() {
+        let read = |value: Option<&str>| BuckConfigRead {
+            root: true,
+            section: "s".to_owned(),
+            key: "k".to_owned(),
+            value: value.map(str::to_owned),
+        };
+        let key = StarlarkModuleCache::module_key("c", &[read(Some("v"))]);
+        assert_ne!(key, StarlarkModuleCache::module_key("c", &[read(None)]));
+        assert_ne!(key, StarlarkModuleCache::module_key("c", &[read(Some(""))]));
+        assert_ne!(key, StarlarkModuleCache::module_key("c", &[]));
+    }
+
+    #[test]
+    fn test_recording_view() -> anyhow::Result<()> {
+        let config = legacy_buck_config_from_entries([("s", "k", "v")])?;
+        let reads = RefCell::new(Vec::new());
+        let view = RecordingBuckConfigView::new(&config, true, &reads);
+        let view: &dyn LegacyBuckConfigView = &view;
+        assert_eq!(Some("v"), view.get("s", "k")?.as_deref());
+        assert_eq!(None, view.get("s", "missing")?);
+        assert_eq!(
+            vec![
+                BuckConfigRead {
+                    root: true,
+                    section: "s".to_owned(),
+                    key: "k".to_owned(),
+                    value: Some("v".to_owned()),
+                },
+                BuckConfigRead {
+                    root: true,
+                    section: "s".to_owned(),
+                    key: "missing".to_owned(),
+                    value: None,
+                },
+            ],
+            reads.into_inner()
+        );
+        Ok(())
+    }
+}
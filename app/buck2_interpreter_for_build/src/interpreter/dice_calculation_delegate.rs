@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use std::cell::RefCell;
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -21,6 +22,7 @@ use buck2_common::error_report::CreateErrorReport;
 use buck2_common::file_ops::FileOps;
 use buck2_common::legacy_configs::dice::HasLegacyConfigs;
 use buck2_common::legacy_configs::dice::LegacyBuckConfigOnDice;
+use buck2_common::legacy_configs::view::LegacyBuckConfigView;
 use buck2_common::package_boundary::HasPackageBoundaryExceptions;
 use buck2_common::package_listing::dice::HasPackageListingResolver;
 use buck2_common::package_listing::listing::PackageListing;
@@ -33,11 +35,18 @@ use buck2_core::cells::name::CellName;
 use buck2_core::package::PackageLabel;
 use buck2_events::dispatch::span;
 use buck2_events::dispatch::span_async;
+use buck2_execute::execute::blocking::HasBlockingExecutor;
+use buck2_interpreter::dice::starlark_debug::HasStarlarkDebugger;
 use buck2_interpreter::dice::starlark_profiler::GetStarlarkProfilerInstrumentation;
 use buck2_interpreter::dice::starlark_provider::with_starlark_eval_provider;
 use buck2_interpreter::file_loader::LoadedModule;
+use buck2_interpreter::file_loader::LoadedModules;
 use buck2_interpreter::file_loader::ModuleDeps;
 use buck2_interpreter::import_paths::HasImportPaths;
+use buck2_interpreter::module_cache::CachedModule;
+use buck2_interpreter::module_cache::HasStarlarkModuleCache;
+use buck2_interpreter::module_cache::RecordingBuckConfigView;
+use buck2_interpreter::module_cache::StarlarkModuleCache;
 use buck2_interpreter::path::OwnedStarlarkModulePath;
 use buck2_interpreter::path::PackageFilePath;
 use buck2_interpreter::path::StarlarkModulePath;
@@ -56,6 +65,7 @@ use starlark::syntax::AstModule;
 
 use crate::interpreter::cycles::LoadCycleDescriptor;
 use crate::interpreter::dice_calculation_delegate::keys::EvalImportKey;
+use crate::interpreter::eval_budget::StarlarkEvalBudget;
 use crate::interpreter::global_interpreter_state::HasGlobalInterpreterState;
 use crate::interpreter::interpreter_for_cell::InterpreterForCell;
 use crate::interpreter::interpreter_for_cell::ParseResult;
@@ -109,10 +119,15 @@ impl<'c> HasCalculationDelegate<'c> for DiceComputations {
 
                 let implicit_import_paths = ctx.import_paths_for_cell(self.1).await?;
 
+                let eval_budget = StarlarkEvalBudget::from_buckconfig(
+                    &ctx.get_legacy_root_config_on_dice().await?,
+                )?;
+
                 Ok(Arc::new(InterpreterForCell::new(
                     cell.cell_alias_resolver().dupe(),
                     global_state.dupe(),
                     implicit_import_paths,
+                    eval_budget,
                 )?))
             }
 
@@ -270,19 +285,75 @@ impl<'c> DiceCalculationDelegate<'c> {
         let buckconfig = self.get_legacy_buck_config_for_starlark().await?;
         let root_buckconfig = self.ctx.get_legacy_root_config_on_dice().await?;
 
-        with_starlark_eval_provider(
+        // Profiling and debugging need the module to actually be evaluated.
+        let cache = match (
+            &starlark_profiler_instrumentation,
+            self.ctx.get_starlark_debugger_handle(),
+        ) {
+            (None, None) => self.ctx.global_data().get_starlark_module_cache(),
+            _ => None,
+        };
+        let cache = cache.and_then(|cache| {
+            let deps = loaded_modules
+                .map
+                .values()
+                .map(|module| module.cache_key())
+                .collect::<Option<Vec<_>>>()?;
+            let content_key = cache.content_key(&starlark_file.to_string(), ast.source(), deps);
+            Some((cache, content_key))
+        });
+
+        if let Some((cache, content_key)) = &cache {
+            let cached = self
+                .ctx
+                .get_blocking_executor()
+                .execute_io_inline(|| cache.get(content_key))
+                .await;
+            let reloaded = cached.and_then(|cached| match cached {
+                Some(cached) => self.reload_cached_module(
+                    cached,
+                    content_key,
+                    starlark_file,
+                    &loaded_modules,
+                    &buckconfig,
+                    &root_buckconfig,
+                ),
+                None => Ok(None),
+            });
+            match reloaded {
+                Ok(Some(module)) => return Ok(module),
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::debug!("Error reloading cached `{}`: {:#}", starlark_file, e);
+                }
+            }
+        }
+
+        let (module, to_store) = with_starlark_eval_provider(
             self.ctx,
             &mut StarlarkProfilerOrInstrumentation::maybe_instrumentation(
                 starlark_profiler_instrumentation,
             ),
             format!("load:{}", &starlark_file),
             move |provider| {
+                let config_reads = RefCell::new(Vec::new());
+                let recording_buckconfig =
+                    RecordingBuckConfigView::new(&buckconfig, false, &config_reads);
+                let recording_root_buckconfig =
+                    RecordingBuckConfigView::new(&root_buckconfig, true, &config_reads);
+                let (buckconfig, root_buckconfig): (
+                    &dyn LegacyBuckConfigView,
+                    &dyn LegacyBuckConfigView,
+                ) = match cache {
+                    Some(_) => (&recording_buckconfig, &recording_root_buckconfig),
+                    None => (&buckconfig, &root_buckconfig),
+                };
                 let evaluation = self
                     .configs
                     .eval_module(
                         starlark_file,
-                        &buckconfig,
-                        &root_buckconfig,
+                        buckconfig,
+                        root_buckconfig,
                         ast,
                         loaded_modules.clone(),
                         provider,
@@ -291,14 +362,93 @@ impl<'c> DiceCalculationDelegate<'c> {
                         DiceCalculationDelegateError::EvalModuleError(starlark_file.to_string())
                     })?;
 
-                Ok(LoadedModule::new(
-                    OwnedStarlarkModulePath::new(starlark_file),
-                    loaded_modules,
-                    evaluation,
+                let mut to_store = None;
+                let cache_key = cache.map(|(cache, content_key)| {
+                    let mut config_reads = config_reads.into_inner();
+                    config_reads.sort();
+                    config_reads.dedup();
+                    let cache_key = StarlarkModuleCache::module_key(&content_key, &config_reads);
+                    // Modules which can't be serialized are evaluated every time, but still
+                    // have a key, so the modules loading them can be cached.
+                    match self
+                        .configs
+                        .serialize_module(starlark_file, &loaded_modules, &evaluation)
+                    {
+                        Ok(module) => {
+                            to_store = Some((
+                                cache,
+                                content_key,
+                                CachedModule {
+                                    config_reads,
+                                    module,
+                                },
+                            ))
+                        }
+                        Err(e) => tracing::debug!("Not caching `{}`: {:#}", starlark_file, e),
+                    }
+                    cache_key
+                });
+
+                Ok((
+                    LoadedModule::new_with_cache_key(
+                        OwnedStarlarkModulePath::new(starlark_file),
+                        loaded_modules,
+                        evaluation,
+                        cache_key,
+                    ),
+                    to_store,
                 ))
             },
         )
-        .await
+        .await?;
+
+        if let Some((cache, content_key, cached)) = to_store {
+            let stored = self
+                .ctx
+                .get_blocking_executor()
+                .execute_io_inline(|| cache.put(&content_key, &cached))
+                .await;
+            if let Err(e) = stored {
+                tracing::debug!("Not caching `{}`: {:#}", starlark_file, e);
+            }
+        }
+
+        Ok(module)
+    }
+
+    /// The module `cached` under `content_key` in the module cache, unless the buckconfig values
+    /// read by its evaluation changed since.
+    fn reload_cached_module(
+        &self,
+        cached: CachedModule,
+        content_key: &str,
+        starlark_file: StarlarkModulePath<'_>,
+        loaded_modules: &LoadedModules,
+        buckconfig: &dyn LegacyBuckConfigView,
+        root_buckconfig: &dyn LegacyBuckConfigView,
+    ) -> anyhow::Result<Option<LoadedModule>> {
+        for read in &cached.config_reads {
+            let config = if read.root {
+                root_buckconfig
+            } else {
+                buckconfig
+            };
+            if config.get(&read.section, &read.key)?.as_deref() != read.value.as_deref() {
+                return Ok(None);
+            }
+        }
+        let env = self
+            .configs
+            .reload_module(starlark_file, loaded_modules, &cached.module)?;
+        Ok(Some(LoadedModule::new_with_cache_key(
+            OwnedStarlarkModulePath::new(starlark_file),
+            loaded_modules.clone(),
+            env,
+            Some(StarlarkModuleCache::module_key(
+                content_key,
+                &cached.config_reads,
+            )),
+        )))
     }
 
     /// Eval parent `PACKAGE` file for given `PACKAGE` file.
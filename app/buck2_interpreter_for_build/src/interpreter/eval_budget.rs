@@ -0,0 +1,127 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::time::Duration;
+
+use buck2_common::legacy_configs::view::LegacyBuckConfigView;
+use dupe::Dupe;
+use starlark::eval::Evaluator;
+
+/// Limits on a single Starlark evaluation (a build file, a `PACKAGE` file,
+/// a `.bzl` file or a BXL invocation), configured in the root buckconfig:
+///
+/// ```ini
+/// [buck2]
+/// starlark_max_instructions = 100000000
+/// starlark_max_heap_bytes = 4000000000
+/// starlark_timeout_ms = 600000
+/// bxl_timeout_ms = 3600000
+/// ```
+///
+/// BXL invocations block on builds and analysis while they run, so
+/// `starlark_timeout_ms` does not apply to them; they use `bxl_timeout_ms`
+/// instead. Like the other timeouts it is only checked while Starlark code
+/// executes: a build or analysis the invocation waits on is not interrupted,
+/// and the invocation only fails once Starlark runs again past the deadline.
+///
+/// All limits are unset by default.
+#[derive(Debug, Default, Clone, Copy, Dupe, Eq, PartialEq)]
+pub struct StarlarkEvalBudget {
+    pub max_instructions: Option<u64>,
+    pub max_heap_bytes: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+impl StarlarkEvalBudget {
+    pub fn from_buckconfig(root_buckconfig: &dyn LegacyBuckConfigView) -> anyhow::Result<Self> {
+        Self::from_buckconfig_with_timeout(root_buckconfig, "starlark_timeout_ms")
+    }
+
+    /// Limits for a BXL invocation.
+    pub fn from_buckconfig_for_bxl(
+        root_buckconfig: &dyn LegacyBuckConfigView,
+    ) -> anyhow::Result<Self> {
+        Self::from_buckconfig_with_timeout(root_buckconfig, "bxl_timeout_ms")
+    }
+
+    fn from_buckconfig_with_timeout(
+        root_buckconfig: &dyn LegacyBuckConfigView,
+        timeout_key: &str,
+    ) -> anyhow::Result<Self> {
+        Ok(StarlarkEvalBudget {
+            max_instructions: root_buckconfig.parse("buck2", "starlark_max_instructions")?,
+            max_heap_bytes: root_buckconfig.parse("buck2", "starlark_max_heap_bytes")?,
+            timeout: root_buckconfig
+                .parse("buck2", timeout_key)?
+                .map(Duration::from_millis),
+        })
+    }
+
+    /// Configure the evaluator. Must be called right before evaluation starts,
+    /// because the timeout is measured from this call.
+    pub fn apply(&self, eval: &mut Evaluator) {
+        if let Some(max_instructions) = self.max_instructions {
+            eval.set_max_instructions(max_instructions);
+        }
+        if let Some(max_heap_bytes) = self.max_heap_bytes {
+            eval.set_max_heap_bytes(max_heap_bytes);
+        }
+        if let Some(timeout) = self.timeout {
+            eval.set_timeout(timeout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use buck2_common::legacy_configs::testing::legacy_buck_config_from_entries;
+    use buck2_common::legacy_configs::view::LegacyBuckConfigView;
+
+    use crate::interpreter::eval_budget::StarlarkEvalBudget;
+
+    #[test]
+    fn test_from_buckconfig() -> anyhow::Result<()> {
+        let config = legacy_buck_config_from_entries([
+            ("buck2", "starlark_max_instructions", "1000"),
+            ("buck2", "starlark_timeout_ms", "20"),
+        ])?;
+        let budget = StarlarkEvalBudget::from_buckconfig(&config as &dyn LegacyBuckConfigView)?;
+        assert_eq!(
+            StarlarkEvalBudget {
+                max_instructions: Some(1000),
+                max_heap_bytes: None,
+                timeout: Some(Duration::from_millis(20)),
+            },
+            budget
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_buckconfig_for_bxl() -> anyhow::Result<()> {
+        let config = legacy_buck_config_from_entries([
+            ("buck2", "starlark_max_instructions", "1000"),
+            ("buck2", "starlark_timeout_ms", "20"),
+            ("buck2", "bxl_timeout_ms", "3000"),
+        ])?;
+        let budget =
+            StarlarkEvalBudget::from_buckconfig_for_bxl(&config as &dyn LegacyBuckConfigView)?;
+        assert_eq!(
+            StarlarkEvalBudget {
+                max_instructions: Some(1000),
+                max_heap_bytes: None,
+                timeout: Some(Duration::from_millis(3000)),
+            },
+            budget
+        );
+        Ok(())
+    }
+}
@@ -44,8 +44,10 @@ use gazebo::prelude::*;
 use starlark::codemap::FileSpan;
 use starlark::environment::FrozenModule;
 use starlark::environment::Module;
+use starlark::environment::SerializedModule;
 use starlark::syntax::AstModule;
 use starlark::values::list::ListRef;
+use starlark::values::serialize::ValueSerializer;
 use starlark::values::OwnedFrozenValue;
 use starlark::values::ValueLike;
 use starlark_map::small_map::SmallMap;
@@ -54,6 +56,7 @@ use thiserror::Error;
 use super::print_handler::EventDispatcherPrintHandler;
 use crate::interpreter::build_context::BuildContext;
 use crate::interpreter::build_context::PerFileTypeContext;
+use crate::interpreter::eval_budget::StarlarkEvalBudget;
 use crate::interpreter::global_interpreter_state::GlobalInterpreterState;
 use crate::interpreter::module_internals::ModuleInternals;
 use crate::super_package::data::SuperPackage;
@@ -121,6 +124,9 @@ pub(crate) struct InterpreterForCell {
     cell_names: CellAliasResolver,
     /// Log GC.
     verbose_gc: bool,
+    /// Limits on each evaluation, from the root buckconfig.
+    #[allocative(skip)]
+    eval_budget: StarlarkEvalBudget,
     /// When true, rule function creates a node with no attributes.
     /// (Which won't work correctly, but useful for profiling of starlark).
     ignore_attrs_for_profiling: bool,
@@ -254,11 +260,13 @@ impl InterpreterForCell {
         cell_names: CellAliasResolver,
         global_state: Arc<GlobalInterpreterState>,
         implicit_import_paths: Arc<ImplicitImportPaths>,
+        eval_budget: StarlarkEvalBudget,
     ) -> anyhow::Result<Self> {
         Ok(Self {
             global_state,
             cell_names,
             verbose_gc: Self::verbose_gc()?,
+            eval_budget,
             ignore_attrs_for_profiling: Self::is_ignore_attrs_for_profiling()?,
             implicit_import_paths,
         })
@@ -475,6 +483,7 @@ impl InterpreterForCell {
             if self.verbose_gc {
                 eval.verbose_gc();
             }
+            self.eval_budget.apply(&mut eval);
             match eval.eval_module(ast, globals) {
                 Ok(_) => {
                     eval_provider
@@ -516,6 +525,59 @@ impl InterpreterForCell {
         env.freeze()
     }
 
+    /// Serialize a module evaluated by [`eval_module`](InterpreterForCell::eval_module), for the
+    /// module cache. Fails if the module has values which can't be serialized.
+    pub(crate) fn serialize_module(
+        &self,
+        starlark_path: StarlarkModulePath<'_>,
+        loaded_modules: &LoadedModules,
+        env: &FrozenModule,
+    ) -> anyhow::Result<SerializedModule> {
+        let ids = Self::loaded_module_ids(loaded_modules);
+        env.serialize(
+            ValueSerializer::new(),
+            &Self::loads(&ids, loaded_modules),
+            self.global_state
+                .globals_for_file_type(StarlarkPath::from(starlark_path).file_type()),
+        )
+    }
+
+    /// Turn a module serialized by [`serialize_module`](InterpreterForCell::serialize_module) back
+    /// into the module `eval_module` would return.
+    pub(crate) fn reload_module(
+        &self,
+        starlark_path: StarlarkModulePath<'_>,
+        loaded_modules: &LoadedModules,
+        module: &SerializedModule,
+    ) -> anyhow::Result<FrozenModule> {
+        let ids = Self::loaded_module_ids(loaded_modules);
+        let file_type = StarlarkPath::from(starlark_path).file_type();
+        module.reload(
+            ValueSerializer::new(),
+            &Self::loads(&ids, loaded_modules),
+            self.global_state.globals_for_file_type(file_type),
+            &file_type.dialect(self.global_state.disable_starlark_types),
+        )
+    }
+
+    fn loaded_module_ids(loaded_modules: &LoadedModules) -> Vec<String> {
+        loaded_modules
+            .map
+            .keys()
+            .map(|path| path.to_string())
+            .collect()
+    }
+
+    fn loads<'a>(
+        ids: &'a [String],
+        loaded_modules: &'a LoadedModules,
+    ) -> Vec<(&'a str, &'a FrozenModule)> {
+        ids.iter()
+            .map(String::as_str)
+            .zip(loaded_modules.map.values().map(|module| module.env()))
+            .collect()
+    }
+
     pub(crate) fn eval_package_file(
         self: &Arc<Self>,
         package_file_path: &PackageFilePath,
@@ -14,6 +14,7 @@ pub mod configuror;
 pub mod context;
 pub mod cycles;
 pub mod dice_calculation_delegate;
+pub mod eval_budget;
 pub mod functions;
 pub mod global_interpreter_state;
 pub mod interpreter_for_cell;
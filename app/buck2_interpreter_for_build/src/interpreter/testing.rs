@@ -43,6 +43,7 @@ use starlark::values::Value;
 
 use crate::interpreter::configuror::AdditionalGlobalsFn;
 use crate::interpreter::configuror::BuildInterpreterConfiguror;
+use crate::interpreter::eval_budget::StarlarkEvalBudget;
 use crate::interpreter::global_interpreter_state::GlobalInterpreterState;
 use crate::interpreter::interpreter_for_cell::InterpreterForCell;
 use crate::interpreter::interpreter_for_cell::ParseResult;
@@ -191,6 +192,9 @@ impl Tester {
             BuildFileCell::new(self.cell_alias_resolver.resolve_self()),
             &self.cell_alias_resolver,
         )?;
+        let eval_budget = StarlarkEvalBudget::from_buckconfig(
+            self.configs.get(self.cell_resolver.root_cell()).unwrap(),
+        )?;
         let additional_globals = self.additional_globals.clone();
         Ok(Arc::new(InterpreterForCell::new(
             self.cell_alias_resolver.dupe(),
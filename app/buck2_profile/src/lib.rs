@@ -34,6 +34,7 @@ pub fn starlark_profiler_configuration_from_request(
         Profiler::Bytecode => ProfileMode::Bytecode,
         Profiler::BytecodePairs => ProfileMode::BytecodePairs,
         Profiler::Typecheck => ProfileMode::Typecheck,
+        Profiler::Coverage => ProfileMode::Coverage,
     };
 
     match req.profile_opts.as_ref().expect("Missing profile opts") {
@@ -90,6 +91,10 @@ pub fn get_profile_response(
                 .context("Failed to write profile")?;
             fs_util::write(output.join("flame.svg"), &svg).context("Failed to write profile")?;
         }
+        Profiler::Coverage if output.extension().map_or(false, |x| x == "xml") => {
+            let profile = profile_data.profile_data.gen_cobertura()?;
+            fs_util::write(&output, profile).context("Failed to write profile")?;
+        }
         _ => {
             let profile = profile_data.profile_data.gen()?;
             fs_util::write(&output, profile).context("Failed to write profile")?;
@@ -135,6 +135,9 @@ pub struct Buck2OssReConfiguration {
     pub cas_address: Option<String>,
     pub engine_address: Option<String>,
     pub action_cache_address: Option<String>,
+    /// The instance name to pass in all requests to RE. Servers that host multiple instances use
+    /// this to select between them. If none is set, the empty instance name is used.
+    pub instance_name: Option<String>,
     /// Whether to use TLS to interact with remote execution.
     pub tls: bool,
     /// Path to a CA certificates bundle. This must be PEM-encoded. If none is set, a default
@@ -155,6 +158,9 @@ pub struct Buck2OssReConfiguration {
     /// This can contain environment variables using shell interpolation syntax (i.e. $VAR). They
     /// will be substituted before using the value.
     pub http_headers: Vec<HttpHeader>,
+    /// Maximum number of blobs to transfer concurrently through the ByteStream API (which we use
+    /// for blobs that are too large for batch requests). Must be greater than 0.
+    pub bytestream_concurrency: Option<usize>,
 }
 
 #[derive(Clone, Debug, Default, Allocative)]
@@ -188,6 +194,7 @@ impl Buck2OssReConfiguration {
             engine_address: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "engine_address")?,
             action_cache_address: legacy_config
                 .parse(BUCK2_RE_CLIENT_CFG_SECTION, "action_cache_address")?,
+            instance_name: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "instance_name")?,
             tls: legacy_config
                 .parse(BUCK2_RE_CLIENT_CFG_SECTION, "tls")?
                 .unwrap_or(true),
@@ -196,6 +203,18 @@ impl Buck2OssReConfiguration {
             http_headers: legacy_config
                 .parse_list(BUCK2_RE_CLIENT_CFG_SECTION, "http_headers")?
                 .unwrap_or_default(), // Empty list is as good None.
+            bytestream_concurrency: match legacy_config
+                .parse(BUCK2_RE_CLIENT_CFG_SECTION, "bytestream_concurrency")?
+            {
+                // No transfer could ever start.
+                Some(0) => {
+                    return Err(anyhow::anyhow!(
+                        "`{}.bytestream_concurrency` must be greater than 0",
+                        BUCK2_RE_CLIENT_CFG_SECTION
+                    ));
+                }
+                n => n,
+            },
         })
     }
 }
@@ -77,6 +77,8 @@ use buck2_execute::re::client::RemoteExecutionClient;
 use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute::re::manager::ReConnectionManager;
 use buck2_execute::re::manager::ReConnectionObserver;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCache;
+use buck2_execute_impl::executors::worker::WorkerPool;
 use buck2_execute_impl::low_pass_filter::LowPassFilter;
 use buck2_forkserver::client::ForkserverClient;
 use buck2_interpreter::dice::starlark_debug::SetStarlarkDebugger;
@@ -147,6 +149,10 @@ pub struct BaseServerCommandContext {
     pub materializer: Arc<dyn Materializer>,
     /// Forkserver connection, if any was started
     pub forkserver: Option<ForkserverClient>,
+    /// Persistent workers used by the local executor.
+    pub worker_pool: Arc<WorkerPool>,
+    /// The local action cache, if enabled.
+    pub local_action_cache: Option<Arc<LocalActionCache>>,
     /// The event dispatcher for this command context.
     pub events: EventDispatcher,
     /// Removes this command from the set of active commands when dropped.
@@ -362,6 +368,8 @@ impl ServerCommandContext {
         let build_signals = self.build_signals.dupe();
 
         let forkserver = self.base_context.forkserver.dupe();
+        let worker_pool = self.base_context.worker_pool.dupe();
+        let local_action_cache = self.base_context.local_action_cache.dupe();
 
         let upload_all_actions = self
             .build_options
@@ -382,6 +390,8 @@ impl ServerCommandContext {
             re_connection,
             build_signals,
             forkserver,
+            worker_pool,
+            local_action_cache,
             upload_all_actions,
             no_remote_cache,
             create_unhashed_symlink_lock,
@@ -471,6 +481,8 @@ struct DiceCommandDataProvider {
     re_connection: Arc<ReConnectionHandle>,
     build_signals: BuildSignalSender,
     forkserver: Option<ForkserverClient>,
+    worker_pool: Arc<WorkerPool>,
+    local_action_cache: Option<Arc<LocalActionCache>>,
     upload_all_actions: bool,
     run_action_knobs: RunActionKnobs,
     no_remote_cache: bool,
@@ -552,6 +564,8 @@ impl DiceDataProvider for DiceCommandDataProvider {
             executor_global_knobs,
             self.upload_all_actions,
             self.forkserver.dupe(),
+            self.worker_pool.dupe(),
+            self.local_action_cache.dupe(),
             self.no_remote_cache,
             ctx.global_data()
                 .get_io_provider()
@@ -37,7 +37,10 @@ use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute_impl::executors::caching::CachingExecutor;
 use buck2_execute_impl::executors::hybrid::HybridExecutor;
 use buck2_execute_impl::executors::local::LocalExecutor;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCache;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCacheExecutor;
 use buck2_execute_impl::executors::re::ReExecutor;
+use buck2_execute_impl::executors::worker::WorkerPool;
 use buck2_execute_impl::low_pass_filter::LowPassFilter;
 use buck2_forkserver::client::ForkserverClient;
 use dupe::Dupe;
@@ -71,6 +74,8 @@ pub struct CommandExecutorFactory {
     pub executor_global_knobs: ExecutorGlobalKnobs,
     pub upload_all_actions: bool,
     pub forkserver: Option<ForkserverClient>,
+    pub worker_pool: Arc<WorkerPool>,
+    pub local_action_cache: Option<Arc<LocalActionCache>>,
     pub no_remote_cache: bool,
     project_root: ProjectRoot,
 }
@@ -86,6 +91,8 @@ impl CommandExecutorFactory {
         executor_global_knobs: ExecutorGlobalKnobs,
         upload_all_actions: bool,
         forkserver: Option<ForkserverClient>,
+        worker_pool: Arc<WorkerPool>,
+        local_action_cache: Option<Arc<LocalActionCache>>,
         no_remote_cache: bool,
         project_root: ProjectRoot,
     ) -> Self {
@@ -99,6 +106,8 @@ impl CommandExecutorFactory {
             executor_global_knobs,
             upload_all_actions,
             forkserver,
+            worker_pool,
+            local_action_cache,
             no_remote_cache,
             project_root,
         }
@@ -111,7 +120,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
         artifact_fs: &ArtifactFs,
         executor_config: &CommandExecutorConfig,
     ) -> anyhow::Result<CommandExecutorResponse> {
-        let local_executor_new = |_options| {
+        let local_executor_new = |options: &LocalExecutorOptions| {
             LocalExecutor::new(
                 artifact_fs.clone(),
                 self.materializer.dupe(),
@@ -120,6 +129,8 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 self.project_root.root().to_owned(),
                 self.forkserver.dupe(),
                 self.executor_global_knobs.dupe(),
+                options.dupe(),
+                self.worker_pool.dupe(),
             )
         };
 
@@ -137,7 +148,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
             }
 
             return Ok(CommandExecutorResponse {
-                executor: Arc::new(local_executor_new(&LocalExecutorOptions {})),
+                executor: Arc::new(local_executor_new(&LocalExecutorOptions::default())),
                 platform: Default::default(),
             });
         }
@@ -163,13 +174,44 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 }
             };
 
+        // NOTE: While we now have a legit flag for this, we keep the env var. This has been used
+        // in remediating prod incidents in the past, and this is the kind of thing that can easily
+        // become tribal knowledge. Keeping this does not hurt us.
+        static DISABLE_CACHING: EnvHelper<bool> = EnvHelper::new("BUCK2_TEST_DISABLE_CACHING");
+
+        let disable_caching = DISABLE_CACHING
+            .get_copied()?
+            .unwrap_or(self.no_remote_cache);
+
+        let local_action_cache_new = |inner: Arc<dyn PreparedCommandExecutor>,
+                                      cache_upload_behavior: CacheUploadBehavior|
+         -> Arc<dyn PreparedCommandExecutor> {
+            match &self.local_action_cache {
+                Some(cache) if !disable_caching => Arc::new(LocalActionCacheExecutor {
+                    inner,
+                    cache: cache.dupe(),
+                    artifact_fs: artifact_fs.clone(),
+                    materializer: self.materializer.dupe(),
+                    blocking_executor: self.blocking_executor.dupe(),
+                    cache_upload_behavior,
+                }),
+                _ => inner,
+            }
+        };
+
         let response = match &executor_config.executor {
-            Executor::Local(local) => {
+            Executor::Local {
+                options,
+                cache_upload_behavior,
+            } => {
                 if self.strategy.ban_local() {
                     None
                 } else {
                     Some(CommandExecutorResponse {
-                        executor: Arc::new(local_executor_new(local)),
+                        executor: local_action_cache_new(
+                            Arc::new(local_executor_new(options)),
+                            *cache_upload_behavior,
+                        ),
                         platform: Default::default(),
                     })
                 }
@@ -202,16 +244,6 @@ impl HasCommandExecutor for CommandExecutorFactory {
                     _ => None,
                 };
 
-                // NOTE: While we now have a legit flag for this, we keep the env var. This has been used
-                // in remediating prod incidents in the past, and this is the kind of thing that can easily
-                // become tribal knowledge. Keeping this does not hurt us.
-                static DISABLE_CACHING: EnvHelper<bool> =
-                    EnvHelper::new("BUCK2_TEST_DISABLE_CACHING");
-
-                let disable_caching = DISABLE_CACHING
-                    .get_copied()?
-                    .unwrap_or(self.no_remote_cache);
-
                 let executor = if disable_caching || !remote_cache_enabled {
                     inner_executor
                 } else {
@@ -229,6 +261,9 @@ impl HasCommandExecutor for CommandExecutorFactory {
                     })
                 };
 
+                let executor = executor
+                    .map(|executor| local_action_cache_new(executor, *cache_upload_behavior));
+
                 let platform = RE::Platform {
                     properties: re_properties
                         .iter()
@@ -295,11 +330,15 @@ impl ExecutionStrategyExt for ExecutionStrategy {
 /// This is used when execution platforms are not configured.
 pub fn get_default_executor_config(host_platform: HostPlatformOverride) -> CommandExecutorConfig {
     let executor = if buck2_core::is_open_source() {
-        Executor::Local(LocalExecutorOptions {})
+        Executor::Local {
+            options: LocalExecutorOptions::default(),
+            // Only used by the local action cache, if it is enabled.
+            cache_upload_behavior: CacheUploadBehavior::Enabled { max_bytes: None },
+        }
     } else {
         Executor::RemoteEnabled {
             executor: RemoteEnabledExecutor::Hybrid {
-                local: LocalExecutorOptions {},
+                local: LocalExecutorOptions::default(),
                 remote: RemoteExecutorOptions::default(),
                 level: HybridExecutionLevel::Limited,
             },
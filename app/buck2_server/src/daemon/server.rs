@@ -48,6 +48,7 @@ use buck2_events::EventSource;
 use buck2_execute::digest_config::DigestConfig;
 use buck2_execute_impl::materializers::sqlite::MaterializerStateIdentity;
 use buck2_interpreter::dice::starlark_profiler::StarlarkProfilerConfiguration;
+use buck2_interpreter::module_cache::StarlarkModuleCache;
 use buck2_profile::starlark_profiler_configuration_from_request;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::partial_result_dispatcher::NoPartialResult;
@@ -145,11 +146,13 @@ impl BuckdServerInitPreferences {
         io: Arc<dyn IoProvider>,
         digest_config: DigestConfig,
         root_config: &LegacyBuckConfig,
+        starlark_module_cache: Option<Arc<StarlarkModuleCache>>,
     ) -> anyhow::Result<Arc<Dice>> {
         configure_dice_for_buck(
             io,
             digest_config,
             Some(root_config),
+            starlark_module_cache,
             self.detect_cycles,
             self.which_dice,
         )
@@ -324,7 +327,9 @@ impl BuckdServer {
         let (shutdown_channel, shutdown_receiver): (UnboundedSender<()>, _) = mpsc::unbounded();
         let (command_channel, command_receiver): (UnboundedSender<()>, _) = mpsc::unbounded();
 
-        let daemon_state = Arc::new(DaemonState::new(fb, paths, init_ctx).await);
+        let daemon_state = Arc::new(
+            DaemonState::new(fb, paths, init_ctx, base_daemon_constraints.version.clone()).await,
+        );
 
         let auth_token = process_info.auth_token.clone();
         let api_server = BuckdServer(Arc::new(BuckdServerData {
@@ -9,6 +9,7 @@
 
 use std::collections::HashMap;
 use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
@@ -20,13 +21,16 @@ use buck2_cli_proto::unstable_dice_dump_request::DiceDumpFormat;
 use buck2_common::cas_digest::DigestAlgorithm;
 use buck2_common::cas_digest::DigestAlgorithmKind;
 use buck2_common::ignores::ignore_set::IgnoreSet;
+use buck2_common::invocation_paths::local_action_cache_dir;
 use buck2_common::invocation_paths::InvocationPaths;
 use buck2_common::io::IoProvider;
 use buck2_common::legacy_configs::cells::BuckConfigBasedCells;
+use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_common::result::SharedResult;
 use buck2_common::result::ToSharedResultExt;
 use buck2_core::cells::name::CellName;
 use buck2_core::facebook_only;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use buck2_core::fs::project::ProjectRoot;
 use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_core::quiet_soft_error;
@@ -43,6 +47,10 @@ use buck2_execute::execute::blocking::BuckBlockingExecutor;
 use buck2_execute::materialize::materializer::MaterializationMethod;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::manager::ReConnectionManager;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCache;
+use buck2_execute_impl::executors::worker::WorkerPool;
+use buck2_execute_impl::executors::worker::DEFAULT_MAX_WORKERS_PER_KEY;
+use buck2_execute_impl::executors::worker::DEFAULT_WORKER_IDLE_TIMEOUT;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializer;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializerConfigs;
 use buck2_execute_impl::materializers::deferred::TtlRefreshConfiguration;
@@ -51,6 +59,7 @@ use buck2_execute_impl::materializers::sqlite::MaterializerState;
 use buck2_execute_impl::materializers::sqlite::MaterializerStateIdentity;
 use buck2_execute_impl::materializers::sqlite::MaterializerStateSqliteDb;
 use buck2_forkserver::client::ForkserverClient;
+use buck2_interpreter::module_cache::StarlarkModuleCache;
 use buck2_re_configuration::RemoteExecutionStaticMetadata;
 use buck2_re_configuration::RemoteExecutionStaticMetadataImpl;
 use buck2_server_ctx::concurrency::ConcurrencyHandler;
@@ -120,6 +129,14 @@ pub struct DaemonStateData {
 
     pub(crate) forkserver: Option<ForkserverClient>,
 
+    /// Persistent workers, kept across commands so they stay warm.
+    #[allocative(skip)]
+    pub(crate) worker_pool: Arc<WorkerPool>,
+
+    /// The local action cache, if enabled via `buck2.local_action_cache`.
+    #[allocative(skip)]
+    pub(crate) local_action_cache: Option<Arc<LocalActionCache>>,
+
     #[allocative(skip)]
     pub scribe_sink: Option<Arc<dyn EventSink>>,
 
@@ -167,8 +184,9 @@ impl DaemonState {
         fb: fbinit::FacebookInit,
         paths: InvocationPaths,
         init_ctx: BuckdServerInitPreferences,
+        version: String,
     ) -> Self {
-        let data = Self::init_data(fb, &paths, init_ctx)
+        let data = Self::init_data(fb, &paths, init_ctx, version)
             .await
             .context("Error initializing DaemonStateData");
         if let Ok(data) = &data {
@@ -188,6 +206,7 @@ impl DaemonState {
         fb: fbinit::FacebookInit,
         paths: &InvocationPaths,
         init_ctx: BuckdServerInitPreferences,
+        version: String,
     ) -> anyhow::Result<Arc<DaemonStateData>> {
         let fs = paths.project_root().clone();
 
@@ -337,6 +356,7 @@ impl DaemonState {
             static_metadata,
             Some(paths.re_logs_dir().to_string()),
             paths.buck_out_dir().to_string(),
+            digest_config,
         ));
         let materializer = Self::create_materializer(
             fb,
@@ -356,8 +376,17 @@ impl DaemonState {
         let forkserver =
             maybe_launch_forkserver(root_config, &paths.forkserver_state_dir()).await?;
 
+        let worker_pool =
+            Self::init_worker_pool(root_config, paths).context("failed to init worker pool")?;
+
+        let local_action_cache = Self::init_local_action_cache(root_config)
+            .context("failed to init local action cache")?;
+
+        let starlark_module_cache = Self::init_starlark_module_cache(root_config, paths, version)
+            .context("failed to init starlark module cache")?;
+
         let dice = init_ctx
-            .construct_dice(io.dupe(), digest_config, root_config)
+            .construct_dice(io.dupe(), digest_config, root_config, starlark_module_cache)
             .await?;
 
         // TODO(cjhopman): We want to use Expr::True here, but we need to workaround
@@ -444,6 +473,8 @@ impl DaemonState {
             blocking_executor,
             materializer,
             forkserver,
+            worker_pool,
+            local_action_cache,
             scribe_sink,
             hash_all_commands,
             disk_state_options,
@@ -530,6 +561,84 @@ impl DaemonState {
         }
     }
 
+    fn init_worker_pool(
+        root_config: &LegacyBuckConfig,
+        paths: &InvocationPaths,
+    ) -> anyhow::Result<Arc<WorkerPool>> {
+        let max_workers_per_key: usize = root_config
+            .parse("buck2", "worker_max_per_key")?
+            .unwrap_or(DEFAULT_MAX_WORKERS_PER_KEY);
+        if max_workers_per_key == 0 {
+            return Err(anyhow::anyhow!(
+                "`buck2.worker_max_per_key` must be greater than 0"
+            ));
+        }
+        let idle_timeout = match root_config.parse("buck2", "worker_idle_timeout_secs")? {
+            Some(secs) => Duration::from_secs(secs),
+            None => DEFAULT_WORKER_IDLE_TIMEOUT,
+        };
+
+        Ok(WorkerPool::new(
+            paths.worker_log_dir(),
+            max_workers_per_key,
+            idle_timeout,
+        ))
+    }
+
+    fn init_local_action_cache(
+        root_config: &LegacyBuckConfig,
+    ) -> anyhow::Result<Option<Arc<LocalActionCache>>> {
+        // 10GiB by default.
+        const DEFAULT_MAX_MEBIBYTES: u64 = 10 * 1024;
+
+        if !root_config
+            .parse("buck2", "local_action_cache")?
+            .unwrap_or(false)
+        {
+            return Ok(None);
+        }
+
+        let dir = match root_config.get("buck2", "local_action_cache_dir") {
+            Some(dir) => AbsNormPathBuf::new(PathBuf::from(dir))
+                .context("`buck2.local_action_cache_dir` must be an absolute path")?,
+            None => local_action_cache_dir()?,
+        };
+        let max_mebibytes: u64 = root_config
+            .parse("buck2", "local_action_cache_max_mebibytes")?
+            .unwrap_or(DEFAULT_MAX_MEBIBYTES);
+
+        Ok(Some(Arc::new(LocalActionCache::new(
+            dir,
+            max_mebibytes * 1024 * 1024,
+        ))))
+    }
+
+    fn init_starlark_module_cache(
+        root_config: &LegacyBuckConfig,
+        paths: &InvocationPaths,
+        version: String,
+    ) -> anyhow::Result<Option<Arc<StarlarkModuleCache>>> {
+        // 1GiB by default.
+        const DEFAULT_MAX_MEBIBYTES: u64 = 1024;
+
+        if !root_config
+            .parse("buck2", "starlark_module_cache")?
+            .unwrap_or(false)
+        {
+            return Ok(None);
+        }
+
+        let max_mebibytes: u64 = root_config
+            .parse("buck2", "starlark_module_cache_max_mebibytes")?
+            .unwrap_or(DEFAULT_MAX_MEBIBYTES);
+
+        Ok(Some(Arc::new(StarlarkModuleCache::new(
+            paths.starlark_module_cache_path(),
+            version,
+            max_mebibytes * 1024 * 1024,
+        )?)))
+    }
+
     pub fn init_scribe_sink(
         fb: FacebookInit,
         buffer_size: usize,
@@ -629,6 +738,8 @@ impl DaemonState {
             file_watcher: data.file_watcher.dupe(),
             events: dispatcher,
             forkserver: data.forkserver.dupe(),
+            worker_pool: data.worker_pool.dupe(),
+            local_action_cache: data.local_action_cache.dupe(),
             hash_all_commands: data.hash_all_commands,
             _drop_guard: drop_guard,
             daemon_start_time: data.start_time,
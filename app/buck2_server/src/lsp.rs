@@ -12,6 +12,8 @@ use std::future::Future;
 use std::io;
 use std::io::ErrorKind;
 use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use buck2_cli_proto::*;
 use buck2_common::dice::cells::HasCellResolver;
@@ -22,6 +24,7 @@ use buck2_common::result::SharedResult;
 use buck2_core::bzl::ImportPath;
 use buck2_core::cells::build_file_cell::BuildFileCell;
 use buck2_core::cells::cell_path::CellPathRef;
+use buck2_core::cells::paths::CellRelativePath;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::abs_path::AbsPath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
@@ -60,6 +63,7 @@ use starlark::docs::Doc;
 use starlark::docs::Location;
 use starlark::errors::EvalMessage;
 use starlark::lsp::server::server_with_connection;
+use starlark::lsp::server::LoadCompletion;
 use starlark::lsp::server::LspContext;
 use starlark::lsp::server::LspEvalResult;
 use starlark::lsp::server::LspUrl;
@@ -148,6 +152,8 @@ struct DocsCache {
     global_urls: HashMap<String, LspUrl>,
     /// Mapping of starlark: urls to a synthesized starlark representation.
     native_starlark_files: HashMap<LspUrl, String>,
+    /// Documentation for all of the global symbols, used for hover and completion.
+    environment: Arc<Vec<Doc>>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -250,6 +256,7 @@ impl DocsCache {
         Ok(Self {
             global_urls,
             native_starlark_files,
+            environment: Arc::new(builtin_symbols.to_vec()),
         })
     }
 
@@ -262,6 +269,10 @@ impl DocsCache {
     fn url_for_symbol(&self, symbol: &str) -> Option<&LspUrl> {
         self.global_urls.get(symbol)
     }
+
+    fn environment(&self) -> &Arc<Vec<Doc>> {
+        &self.environment
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -437,6 +448,52 @@ impl BuckLspContext {
         }
     }
 
+    /// Find the directory to list when completing the path of a `load()`, and the part of
+    /// `current_value` that the names in that directory are appended to. Handles both
+    /// `cell//package:path` style paths, and paths relative to the current file.
+    async fn load_completion_dir<'a>(
+        &self,
+        current_value: &'a str,
+        current_file: &Path,
+    ) -> anyhow::Result<(&'a str, PathBuf)> {
+        match current_value.split_once("//") {
+            Some((alias, rest)) => {
+                let prefix_len = match rest.rfind(&['/', ':'][..]) {
+                    Some(i) => alias.len() + 2 + i + 1,
+                    None => alias.len() + 2,
+                };
+                let dir = current_value[alias.len() + 2..prefix_len]
+                    .trim_end_matches(&['/', ':'][..])
+                    .replace(':', "/");
+                let alias = alias.strip_prefix('@').unwrap_or(alias);
+                let current_cell = self.import_path(current_file).await?.borrow().cell();
+                let relative_path = self
+                    .with_dice_ctx(|dice_ctx| async move {
+                        let cell_resolver = dice_ctx.get_cell_resolver().await?;
+                        let cell = cell_resolver
+                            .get(current_cell)?
+                            .cell_alias_resolver()
+                            .resolve(alias)?;
+                        cell_resolver.resolve_path(CellPathRef::new(
+                            cell,
+                            CellRelativePath::new(ForwardRelativePath::new(&dir)?),
+                        ))
+                    })
+                    .await?;
+                let abs_path = self.fs.resolve(&relative_path);
+                let abs_path: &Path = abs_path.as_ref();
+                Ok((&current_value[..prefix_len], abs_path.to_owned()))
+            }
+            None => {
+                // `:file.bzl` and `dir/file.bzl` are both relative to the current file.
+                let prefix_len = current_value.rfind(&['/', ':'][..]).map_or(0, |i| i + 1);
+                let dir = current_value[..prefix_len].trim_start_matches(':');
+                let current_dir = current_file.parent().unwrap_or(current_file);
+                Ok((&current_value[..prefix_len], current_dir.join(dir)))
+            }
+        }
+    }
+
     async fn parse_file_with_contents(
         &self,
         uri: &LspUrl,
@@ -710,6 +767,75 @@ impl LspContext for BuckLspContext {
                 Ok(docs_cache.url_for_symbol(symbol).cloned())
             }))
     }
+
+    fn get_environment(&self, _uri: &LspUrl) -> Arc<Vec<Doc>> {
+        let dispatcher = self.server_ctx.events().dupe();
+        let environment = self
+            .runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                let docs_cache = self
+                    .with_dice_ctx(|dice_ctx| async {
+                        self.docs_cache_manager.get_cache(dice_ctx).await
+                    })
+                    .await?;
+                anyhow::Ok(docs_cache.environment().dupe())
+            }));
+        // Failing to load the docs just means there is less to show, so is not an error.
+        environment.unwrap_or_default()
+    }
+
+    fn get_load_completions(
+        &self,
+        current_value: &str,
+        current_file: &LspUrl,
+    ) -> anyhow::Result<Vec<LoadCompletion>> {
+        let current_file = match current_file {
+            LspUrl::File(current_file) => current_file,
+            _ => {
+                return Err(ResolveLoadError::WrongScheme(
+                    "file://".to_owned(),
+                    current_file.clone(),
+                )
+                .into());
+            }
+        };
+
+        let dispatcher = self.server_ctx.events().dupe();
+        let (prefix, dir) = self
+            .runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                self.load_completion_dir(current_value, current_file).await
+            }))?;
+        // Until there is a `:`, we are still completing the package.
+        let in_package = current_value.contains("//") && !current_value.contains(':');
+
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut completions = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            if &path == current_file {
+                continue;
+            }
+            let is_directory = entry.file_type()?.is_dir();
+            let is_starlark = matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("bzl" | "bxl")
+            );
+            if is_directory || (is_starlark && !in_package) {
+                completions.push(LoadCompletion {
+                    value: format!("{}{}", prefix, entry.file_name().to_string_lossy()),
+                    is_directory,
+                });
+            }
+        }
+        completions.sort_by(|a, b| a.value.cmp(&b.value));
+        Ok(completions)
+    }
 }
 
 pub(crate) async fn run_lsp_server_command(
@@ -151,6 +151,16 @@ impl SnapshotCollector {
                 stats.get_digest_expirations.finished_successfully;
             snapshot.re_get_digest_expirations_finished_with_error =
                 stats.get_digest_expirations.finished_with_error;
+            snapshot.re_bytestream_uploads_started = stats.bytestream_uploads.started;
+            snapshot.re_bytestream_uploads_finished_successfully =
+                stats.bytestream_uploads.finished_successfully;
+            snapshot.re_bytestream_uploads_finished_with_error =
+                stats.bytestream_uploads.finished_with_error;
+            snapshot.re_bytestream_downloads_started = stats.bytestream_downloads.started;
+            snapshot.re_bytestream_downloads_finished_successfully =
+                stats.bytestream_downloads.finished_successfully;
+            snapshot.re_bytestream_downloads_finished_with_error =
+                stats.bytestream_downloads.finished_with_error;
 
             Ok(())
         }
@@ -48,7 +48,7 @@ pub(crate) trait DebugServer {
     ) -> anyhow::Result<()>;
 
     /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Attach>
-    fn attach(&mut self, x: dap::AttachRequestArguments) -> anyhow::Result<()>;
+    fn attach(&mut self, x: AttachArguments) -> anyhow::Result<()>;
 
     /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Threads>
     fn threads(&mut self) -> anyhow::Result<dap::ThreadsResponseBody>;
@@ -83,6 +83,12 @@ pub(crate) trait DebugServer {
     /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_StepOut>
     fn step_out(&mut self, x: dap::StepOutArguments) -> anyhow::Result<()>;
 
+    /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_StepBack>
+    fn step_back(&mut self, x: dap::StepBackArguments) -> anyhow::Result<()>;
+
+    /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_ReverseContinue>
+    fn reverse_continue(&mut self, x: dap::ReverseContinueArguments) -> anyhow::Result<()>;
+
     /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Evaluate>
     fn evaluate(&mut self, x: dap::EvaluateArguments) -> anyhow::Result<dap::EvaluateResponseBody>;
 
@@ -102,6 +108,15 @@ pub(crate) struct ContinueArguments {
     pub(crate) single_thread: Option<bool>,
 }
 
+/// DAP AttachRequestArguments, with the implementation specific attributes we support.
+#[derive(Debug, Eq, PartialEq, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AttachArguments {
+    /// Record the statements executed, so the client can step backwards.
+    #[serde(default)]
+    pub(crate) record: bool,
+}
+
 /// Create a dap Event with the given body. The user is responsible for updating the `seq` field.
 pub(crate) fn dap_event<T: Serialize>(event: &str, body: Option<&T>) -> dap::Event {
     dap::Event {
@@ -179,6 +194,8 @@ pub(crate) fn dispatch(
         "next" => ret_none(r, server.next(arg(r)?)),
         "stepIn" => ret_none(r, server.step_in(arg(r)?)),
         "stepOut" => ret_none(r, server.step_out(arg(r)?)),
+        "stepBack" => ret_none(r, server.step_back(arg(r)?)),
+        "reverseContinue" => ret_none(r, server.reverse_continue(arg(r)?)),
         _ => Err(anyhow::anyhow!(
             "Buck2 debugserver didn't recognize command: {}",
             r.command
@@ -48,6 +48,7 @@ use crate::starlark_debug::controller::BuckStarlarkDebugController;
 use crate::starlark_debug::dap_api::dap_event;
 use crate::starlark_debug::dap_api::dispatch;
 use crate::starlark_debug::dap_api::err_response;
+use crate::starlark_debug::dap_api::AttachArguments;
 use crate::starlark_debug::dap_api::ContinueArguments;
 use crate::starlark_debug::dap_api::DebugServer;
 use crate::starlark_debug::error::StarlarkDebuggerError;
@@ -69,6 +70,7 @@ fn capabilities() -> serde_json::Value {
         "supports_set_variable": true,
         "supports_step_in_targets_request": true,
         "supports_conditional_breakpoints": true,
+        "supports_step_back": true,
 
         // This is different from starlark's `dap_capabilities`. The buck starlark debugger treats
         // each ongoing starlark Evaluation as a separate thread and handles requests appropriately.
@@ -281,6 +283,10 @@ struct ServerState {
     /// 100s of ids)
     free_pseudo_threads: BTreeSet<u32>,
     next_pseudo_thread: u32,
+
+    /// Whether evaluations record the statements they execute, so the client can step back.
+    /// Set by the client when it attaches.
+    record: bool,
 }
 
 static TOP_FRAME_LOCALS_ID: i64 = 2000;
@@ -327,7 +333,11 @@ impl DebugServer for ServerState {
         Err(StarlarkDebuggerError::Unimplemented.into())
     }
 
-    fn attach(&mut self, _x: dap::AttachRequestArguments) -> anyhow::Result<()> {
+    fn attach(&mut self, x: AttachArguments) -> anyhow::Result<()> {
+        self.record = x.record;
+        for hook_state in self.current_hooks.values() {
+            hook_state.adapter.set_recording(self.record)?;
+        }
         Ok(())
     }
 
@@ -450,6 +460,18 @@ impl DebugServer for ServerState {
         Ok(())
     }
 
+    fn step_back(&mut self, x: dap::StepBackArguments) -> anyhow::Result<()> {
+        let hook = self.find_hook_by_pseudo_thread(x.thread_id)?;
+        hook.adapter.step_back()?;
+        Ok(())
+    }
+
+    fn reverse_continue(&mut self, x: dap::ReverseContinueArguments) -> anyhow::Result<()> {
+        let hook = self.find_hook_by_pseudo_thread(x.thread_id)?;
+        hook.adapter.reverse_continue()?;
+        Ok(())
+    }
+
     fn evaluate(&mut self, x: dap::EvaluateArguments) -> anyhow::Result<dap::EvaluateResponseBody> {
         let frame_id = match x.frame_id {
             Some(v) => v,
@@ -483,6 +505,7 @@ impl ServerState {
             next_pseudo_thread: 0,
             next_hook_id: HookId(0),
             set_breakpoints: HashMap::new(),
+            record: false,
         }
     }
 
@@ -630,6 +653,7 @@ impl ServerState {
         for (source, breakpoints) in &self.set_breakpoints {
             hook_state.adapter.set_breakpoints(source, breakpoints)?;
         }
+        hook_state.adapter.set_recording(self.record)?;
         self.current_hooks.insert(hook_id, hook_state);
 
         self.to_client.send(ToClientMessage::Event(dap_event(
@@ -18,6 +18,8 @@ use buck2_common::dice::data::HasIoProvider;
 use buck2_common::dice::file_ops::HasFileOps;
 use buck2_common::io::IoProvider;
 use buck2_core::cells::CellResolver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::project::ProjectRoot;
 use buck2_interpreter::path::StarlarkPath;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::ctx::ServerCommandDiceContext;
@@ -25,6 +27,7 @@ use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
 use starlark::codemap::FileSpan;
 use starlark::errors::Diagnostic;
 use starlark::errors::Lint;
+use starlark::errors::LintFix;
 use starlark::syntax::AstModule;
 
 use crate::util::globals::CachedGlobals;
@@ -38,25 +41,47 @@ pub struct StarlarkLintCommand {
     #[clap(flatten)]
     common_opts: StarlarkCommandCommonOptions,
 
+    #[clap(
+        long,
+        help = "Apply the automatic fixes for lints, then report the lints that remain."
+    )]
+    fix: bool,
+
     #[clap(value_name = "PATH", required = true)]
     paths: Vec<PathArg>,
 }
 
+/// Lint a file. If `fix` is given, the fixes are written back to the file (which is
+/// resolved against that project root) and the lints in the fixed file are returned.
 async fn lint_file(
     path: &StarlarkPath<'_>,
     cell_resolver: &CellResolver,
     io: &dyn IoProvider,
     cached_globals: &mut CachedGlobals<'_>,
+    fix: Option<&ProjectRoot>,
 ) -> anyhow::Result<Vec<Lint>> {
     let dialect = path.file_type().dialect(false);
     let proj_path = cell_resolver.resolve_path(path.path().as_ref().as_ref())?;
     let path_str = proj_path.to_string();
     let content = io
-        .read_file_if_exists(proj_path)
+        .read_file_if_exists(proj_path.clone())
         .await?
         .with_context(|| format!("File not found: `{}`", path_str))?;
     match AstModule::parse(&path_str, content.clone(), &dialect) {
-        Ok(ast) => Ok(ast.lint(Some(&*cached_globals.get_names(path).await?))),
+        Ok(ast) => {
+            let globals = cached_globals.get_names(path).await?;
+            let lints = ast.lint(Some(&*globals));
+            if let Some(project_root) = fix {
+                let (fixed, count) =
+                    LintFix::apply_all(&content, lints.iter().filter_map(|x| x.fix.as_ref()));
+                if count > 0 {
+                    fs_util::write(project_root.resolve(&proj_path), &fixed)?;
+                    let ast = AstModule::parse(&path_str, fixed, &dialect)?;
+                    return Ok(ast.lint(Some(&*globals)));
+                }
+            }
+            Ok(lints)
+        }
         Err(err) => {
             // There was a parse error, so we don't want to fail, we want to give a nice error message
             // Do the best we can - it is probably a `Diagnostic`, which gives us more precise info.
@@ -70,6 +95,7 @@ async fn lint_file(
                 serious: true,
                 problem: format!("{:#}", message),
                 original: "".to_owned(),
+                fix: None,
             }])
         }
     }
@@ -90,14 +116,20 @@ impl StarlarkOpaqueSubcommand for StarlarkLintCommand {
                 let io = ctx.global_data().get_io_provider();
                 let mut cached_globals = CachedGlobals::new(&ctx);
 
+                let fix = self.fix.then(|| server_ctx.project_root());
                 let mut stdout = stdout.as_writer();
                 let mut lint_count = 0;
                 let files =
                     starlark_files(&self.paths, server_ctx, &cell_resolver, &fs, &*io).await?;
                 for file in &files {
-                    let lints =
-                        lint_file(&file.borrow(), &cell_resolver, &*io, &mut cached_globals)
-                            .await?;
+                    let lints = lint_file(
+                        &file.borrow(),
+                        &cell_resolver,
+                        &*io,
+                        &mut cached_globals,
+                        fix,
+                    )
+                    .await?;
                     lint_count += lints.len();
                     for lint in lints {
                         writeln!(stdout, "{}", lint)?;
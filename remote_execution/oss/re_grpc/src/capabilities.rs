@@ -0,0 +1,222 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
+use re_grpc_proto::build::bazel::remote::execution::v2::digest_function;
+use re_grpc_proto::build::bazel::remote::execution::v2::ServerCapabilities;
+use thiserror::Error;
+
+/// The batch limit we assume when the server does not set one. There is still a limit on the size
+/// of gRPC messages, and servers usually use the default of 4MiB. We leave some room for the rest
+/// of the message.
+const DEFAULT_MAX_BATCH_TOTAL_SIZE_BYTES: u64 = (4 << 20) - (64 << 10);
+
+#[derive(Error, Debug)]
+enum RECapabilitiesError {
+    #[error(
+        "RE server does not support the `{configured}` digest function buck2 is configured to \
+        use, it supports: {supported}. Set `buck2.digest_algorithms` to one of them"
+    )]
+    UnsupportedDigestFunction {
+        configured: String,
+        supported: String,
+    },
+}
+
+/// What the server told us it supports when we connected (via `GetCapabilities`), reduced to the
+/// parts we act on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RECapabilities {
+    /// The digest functions the server supports. Empty if it did not say.
+    pub digest_functions: Vec<digest_function::Value>,
+    /// Maximum total size of the blobs in a single batch request. `None` if the server does not
+    /// set a limit.
+    pub max_batch_total_size_bytes: Option<u64>,
+    /// The compressor to use for blobs we send in `BatchUpdateBlobs`.
+    pub batch_update_compressor: compressor::Value,
+    /// The compressor we accept for blobs we receive from `BatchReadBlobs`.
+    pub batch_read_compressor: compressor::Value,
+}
+
+impl Default for RECapabilities {
+    /// What we assume when the server does not implement `GetCapabilities`.
+    fn default() -> Self {
+        Self {
+            digest_functions: Vec::new(),
+            max_batch_total_size_bytes: None,
+            batch_update_compressor: compressor::Value::Identity,
+            batch_read_compressor: compressor::Value::Identity,
+        }
+    }
+}
+
+impl RECapabilities {
+    /// The maximum total size of blobs to send or receive in a single batch request. Larger blobs
+    /// need to go through the ByteStream API.
+    pub fn max_batch_size(&self) -> u64 {
+        self.max_batch_total_size_bytes
+            .unwrap_or(DEFAULT_MAX_BATCH_TOTAL_SIZE_BYTES)
+    }
+
+    pub fn from_server(capabilities: ServerCapabilities) -> Self {
+        let cache = capabilities.cache_capabilities.unwrap_or_default();
+
+        let mut digest_functions: Vec<digest_function::Value> = Vec::new();
+        for f in cache.digest_functions.into_iter().chain(
+            capabilities
+                .execution_capabilities
+                .map(|execution| execution.digest_function),
+        ) {
+            match digest_function::Value::from_i32(f) {
+                Some(digest_function::Value::Unknown) | None => {}
+                Some(f) => {
+                    if !digest_functions.contains(&f) {
+                        digest_functions.push(f);
+                    }
+                }
+            }
+        }
+
+        let max_batch_total_size_bytes = if cache.max_batch_total_size_bytes > 0 {
+            Some(cache.max_batch_total_size_bytes as u64)
+        } else {
+            None
+        };
+
+        // We only ever use zstd: the spec recommends it over deflate, and the server must always
+        // support identity.
+        let pick_compressor = |supported: &[i32]| {
+            if supported.contains(&(compressor::Value::Zstd as i32)) {
+                compressor::Value::Zstd
+            } else {
+                compressor::Value::Identity
+            }
+        };
+
+        Self {
+            digest_functions,
+            max_batch_total_size_bytes,
+            batch_update_compressor: pick_compressor(&cache.supported_batch_update_compressors),
+            batch_read_compressor: pick_compressor(&cache.supported_compressors),
+        }
+    }
+
+    /// Check that the server supports the digest function buck2 is configured to use, named as in
+    /// the `DigestFunction` enum (e.g. `SHA256`).
+    pub fn check_digest_function(&self, configured: &str) -> anyhow::Result<()> {
+        let Some(expected) = digest_function::Value::from_str_name(configured) else {
+            // The protocol version we use has no name for it, so servers can't advertise it.
+            tracing::warn!(
+                "Cannot check that the RE server supports the `{}` digest function",
+                configured
+            );
+            return Ok(());
+        };
+        if self.digest_functions.is_empty() || self.digest_functions.contains(&expected) {
+            return Ok(());
+        }
+        Err(RECapabilitiesError::UnsupportedDigestFunction {
+            configured: configured.to_owned(),
+            supported: self
+                .digest_functions
+                .iter()
+                .map(|f| f.as_str_name())
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use re_grpc_proto::build::bazel::remote::execution::v2::CacheCapabilities;
+    use re_grpc_proto::build::bazel::remote::execution::v2::ExecutionCapabilities;
+
+    use super::*;
+
+    #[test]
+    fn test_from_server_empty() {
+        assert_eq!(
+            RECapabilities::from_server(ServerCapabilities::default()),
+            RECapabilities::default()
+        );
+    }
+
+    #[test]
+    fn test_from_server() {
+        let capabilities = RECapabilities::from_server(ServerCapabilities {
+            cache_capabilities: Some(CacheCapabilities {
+                digest_functions: vec![
+                    digest_function::Value::Md5 as i32,
+                    digest_function::Value::Sha1 as i32,
+                    digest_function::Value::Sha256 as i32,
+                ],
+                max_batch_total_size_bytes: 4 << 20,
+                supported_compressors: vec![
+                    compressor::Value::Deflate as i32,
+                    compressor::Value::Zstd as i32,
+                ],
+                supported_batch_update_compressors: vec![compressor::Value::Deflate as i32],
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            capabilities,
+            RECapabilities {
+                digest_functions: vec![
+                    digest_function::Value::Md5,
+                    digest_function::Value::Sha1,
+                    digest_function::Value::Sha256,
+                ],
+                max_batch_total_size_bytes: Some(4 << 20),
+                batch_update_compressor: compressor::Value::Identity,
+                batch_read_compressor: compressor::Value::Zstd,
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_server_execution_digest_function() {
+        let capabilities = RECapabilities::from_server(ServerCapabilities {
+            execution_capabilities: Some(ExecutionCapabilities {
+                digest_function: digest_function::Value::Sha1 as i32,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            capabilities.digest_functions,
+            vec![digest_function::Value::Sha1]
+        );
+    }
+
+    #[test]
+    fn test_check_digest_function() {
+        let capabilities = RECapabilities {
+            digest_functions: vec![digest_function::Value::Sha256],
+            ..Default::default()
+        };
+        assert!(capabilities.check_digest_function("SHA256").is_ok());
+        let err = capabilities.check_digest_function("SHA1").unwrap_err();
+        assert!(err.to_string().contains("`SHA1`"), "{}", err);
+        assert!(err.to_string().contains("SHA256"), "{}", err);
+        // Not something the server can advertise, so we can't tell.
+        assert!(capabilities.check_digest_function("BLAKE3").is_ok());
+        // The server didn't say what it supports.
+        assert!(
+            RECapabilities::default()
+                .check_digest_function("SHA1")
+                .is_ok()
+        );
+    }
+}
@@ -8,7 +8,13 @@
  */
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::env::VarError;
+use std::io;
+use std::io::SeekFrom;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -19,6 +25,7 @@ use buck2_re_configuration::HttpHeader;
 use dupe::Dupe;
 use futures::future::Future;
 use futures::stream::BoxStream;
+use futures::stream::Stream;
 use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
 use gazebo::prelude::*;
@@ -26,6 +33,7 @@ use once_cell::sync::Lazy;
 use prost::Message;
 use re_grpc_proto::build::bazel::remote::execution::v2::action_cache_client::ActionCacheClient;
 use re_grpc_proto::build::bazel::remote::execution::v2::batch_update_blobs_request::Request;
+use re_grpc_proto::build::bazel::remote::execution::v2::capabilities_client::CapabilitiesClient;
 use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
 use re_grpc_proto::build::bazel::remote::execution::v2::content_addressable_storage_client::ContentAddressableStorageClient;
 use re_grpc_proto::build::bazel::remote::execution::v2::execution_client::ExecutionClient;
@@ -39,13 +47,23 @@ use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteOperationMetadata
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteRequest as GExecuteRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteResponse as GExecuteResponse;
 use re_grpc_proto::build::bazel::remote::execution::v2::GetActionResultRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetCapabilitiesRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::ResultsCachePolicy;
+use re_grpc_proto::google::bytestream::byte_stream_client::ByteStreamClient;
+use re_grpc_proto::google::bytestream::QueryWriteStatusRequest;
+use re_grpc_proto::google::bytestream::ReadRequest;
+use re_grpc_proto::google::bytestream::ReadResponse;
+use re_grpc_proto::google::bytestream::WriteRequest;
 use re_grpc_proto::google::longrunning::operation::Result as OpResult;
 use re_grpc_proto::google::rpc::Code;
 use re_grpc_proto::google::rpc::Status;
 use regex::Regex;
 use tokio::fs::OpenOptions;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncSeekExt;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
+use tokio::sync::SemaphorePermit;
 use tonic::codegen::InterceptedService;
 use tonic::metadata;
 use tonic::metadata::MetadataKey;
@@ -56,13 +74,25 @@ use tonic::transport::Certificate;
 use tonic::transport::Channel;
 use tonic::transport::Identity;
 use tonic::transport::Uri;
+use tonic::Streaming;
 
+use crate::capabilities::*;
 use crate::error::*;
 use crate::metadata::*;
 use crate::request::*;
 use crate::response::*;
 
-const INSTANCE_NAME: &str = "";
+/// Size of the chunks we send when writing blobs through the ByteStream API.
+const BYTESTREAM_CHUNK_SIZE: usize = 1 << 20;
+
+/// How many times we attempt a ByteStream transfer. Each attempt resumes where the previous one
+/// left off.
+const BYTESTREAM_ATTEMPTS: usize = 3;
+
+const DEFAULT_BYTESTREAM_CONCURRENCY: usize = 16;
+
+/// How many BatchUpdateBlobs requests we have in flight at once.
+const BATCH_UPLOAD_CONCURRENCY: usize = 16;
 
 fn tdigest_to(tdigest: TDigest) -> Digest {
     Digest {
@@ -79,6 +109,183 @@ fn tdigest_from(digest: Digest) -> TDigest {
     }
 }
 
+fn compress(compressor: compressor::Value, data: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    match compressor {
+        compressor::Value::Identity => Ok(data),
+        // Level 0 means zstd's default level.
+        compressor::Value::Zstd => Ok(zstd::bulk::compress(&data, 0)?),
+        compressor => Err(anyhow::anyhow!("Unsupported compressor: {:?}", compressor)),
+    }
+}
+
+fn decompress(compressor: i32, data: Vec<u8>, digest: &TDigest) -> anyhow::Result<Vec<u8>> {
+    match compressor::Value::from_i32(compressor) {
+        Some(compressor::Value::Identity) => Ok(data),
+        Some(compressor::Value::Zstd) => {
+            zstd::bulk::decompress(&data, digest.size_in_bytes as usize)
+                .with_context(|| format!("Error decompressing digest `{}`", digest))
+        }
+        _ => Err(anyhow::anyhow!(
+            "Unsupported compressor `{}` for digest `{}`",
+            compressor,
+            digest
+        )),
+    }
+}
+
+/// Split `items` into batches whose total size does not exceed `max_batch_size`. An item that
+/// exceeds the limit on its own gets a batch of its own.
+fn split_into_batches<T>(
+    items: Vec<T>,
+    max_batch_size: u64,
+    size: impl Fn(&T) -> u64,
+) -> Vec<Vec<T>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_size = 0u64;
+
+    for item in items {
+        let item_size = size(&item);
+        if !batch.is_empty() && batch_size.saturating_add(item_size) > max_batch_size {
+            batches.push(std::mem::take(&mut batch));
+            batch_size = 0;
+        }
+        batch_size = batch_size.saturating_add(item_size);
+        batch.push(item);
+    }
+
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+fn bytestream_resource_name(instance_name: &str, path: String) -> String {
+    if instance_name.is_empty() {
+        path
+    } else {
+        format!("{}/{}", instance_name, path)
+    }
+}
+
+fn bytestream_read_resource_name(instance_name: &str, digest: &TDigest) -> String {
+    bytestream_resource_name(
+        instance_name,
+        format!("blobs/{}/{}", digest.hash, digest.size_in_bytes),
+    )
+}
+
+fn bytestream_write_resource_name(
+    instance_name: &str,
+    upload_id: &str,
+    digest: &TDigest,
+) -> String {
+    bytestream_resource_name(
+        instance_name,
+        format!(
+            "uploads/{}/blobs/{}/{}",
+            upload_id, digest.hash, digest.size_in_bytes
+        ),
+    )
+}
+
+/// Where we read a blob that we write through the ByteStream API from.
+#[derive(Clone, Dupe)]
+enum BlobSource {
+    Memory(Arc<Vec<u8>>),
+    File(Arc<str>),
+}
+
+/// Reads a `BlobSource` one chunk at a time.
+struct BlobReader {
+    source: BlobSource,
+    file: Option<tokio::fs::File>,
+}
+
+impl BlobReader {
+    /// Read the bytes from `start` to `end`. Chunks are read in order, so we only need to seek
+    /// when we open the file.
+    async fn read(&mut self, start: usize, end: usize) -> io::Result<Vec<u8>> {
+        match &self.source {
+            BlobSource::Memory(data) => data.get(start..end).map(<[u8]>::to_vec).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("Blob is only {} bytes long", data.len()),
+                )
+            }),
+            BlobSource::File(path) => {
+                let file = match &mut self.file {
+                    Some(file) => file,
+                    None => {
+                        let mut file = tokio::fs::File::open(&**path).await?;
+                        file.seek(SeekFrom::Start(start as u64)).await?;
+                        self.file.insert(file)
+                    }
+                };
+                let mut data = vec![0; end - start];
+                file.read_exact(&mut data).await?;
+                Ok(data)
+            }
+        }
+    }
+}
+
+/// The requests to send to write a blob of `size` bytes through the ByteStream API, starting at
+/// `offset`. We always send at least one request, since the last one needs to set `finish_write`.
+/// If reading the blob fails, we stop early and leave the error in `read_error`.
+fn bytestream_write_requests(
+    resource_name: String,
+    source: BlobSource,
+    size: usize,
+    offset: usize,
+    chunk_size: usize,
+    read_error: Arc<Mutex<Option<io::Error>>>,
+) -> impl Stream<Item = WriteRequest> + Send + 'static {
+    let offset = std::cmp::min(offset, size);
+    let reader = BlobReader { source, file: None };
+
+    futures::stream::unfold((reader, Some(offset)), move |(mut reader, start)| {
+        let resource_name = resource_name.clone();
+        let read_error = read_error.dupe();
+        async move {
+            let start = start?;
+            let end = std::cmp::min(start + chunk_size, size);
+
+            let data = match reader.read(start, end).await {
+                Ok(data) => data,
+                Err(e) => {
+                    *read_error.lock().unwrap() = Some(e);
+                    return None;
+                }
+            };
+
+            let request = WriteRequest {
+                // Only required in the first request.
+                resource_name: if start == offset {
+                    resource_name
+                } else {
+                    String::new()
+                },
+                write_offset: start as i64,
+                finish_write: end == size,
+                data,
+            };
+
+            let next = if end == size { None } else { Some(end) };
+            Some((request, (reader, next)))
+        }
+    })
+}
+
+/// Whether a failed ByteStream transfer is worth resuming.
+fn is_retryable(status: &tonic::Status) -> bool {
+    matches!(
+        status.code(),
+        tonic::Code::Unavailable | tonic::Code::DeadlineExceeded | tonic::Code::Aborted
+    )
+}
+
 fn tstatus_ok() -> TStatus {
     TStatus {
         code: TCode::OK,
@@ -219,9 +426,19 @@ impl REClientBuilder {
 
         let interceptor = InjectHeadersInterceptor::new(&opts.http_headers)?;
 
+        let cas = cas.context("Error creating CAS client")?;
+
+        let instance_name = opts.instance_name.clone().unwrap_or_default();
+
+        let capabilities = get_capabilities(
+            CapabilitiesClient::with_interceptor(cas.clone(), interceptor.dupe()),
+            &instance_name,
+        )
+        .await;
+
         let grpc_clients = GRPCClients {
             cas_client: ContentAddressableStorageClient::with_interceptor(
-                cas.context("Error creating CAS client")?,
+                cas.clone(),
                 interceptor.dupe(),
             ),
             execution_client: ExecutionClient::with_interceptor(
@@ -232,12 +449,64 @@ impl REClientBuilder {
                 action_cache.context("Error creating ActionCache client")?,
                 interceptor.dupe(),
             ),
+            bytestream_client: ByteStreamClient::with_interceptor(cas, interceptor.dupe()),
         };
 
-        Ok(REClient::new(grpc_clients))
+        Ok(REClient::new(
+            grpc_clients,
+            instance_name,
+            capabilities,
+            opts.bytestream_concurrency
+                .unwrap_or(DEFAULT_BYTESTREAM_CONCURRENCY),
+        ))
     }
 }
 
+async fn get_capabilities(
+    mut client: CapabilitiesClient<InterceptedService<Channel, InjectHeadersInterceptor>>,
+    instance_name: &str,
+) -> RECapabilities {
+    let res = client
+        .get_capabilities(GetCapabilitiesRequest {
+            instance_name: instance_name.to_owned(),
+        })
+        .await;
+
+    let capabilities = match res {
+        Ok(res) => {
+            let res = res.into_inner();
+
+            let capabilities = RECapabilities::from_server(res.clone());
+            if capabilities.digest_functions.is_empty() {
+                tracing::warn!(
+                    "RE server did not advertise a known digest function: {:?}",
+                    res.cache_capabilities
+                        .map(|c| c.digest_functions)
+                        .unwrap_or_default()
+                );
+            }
+            capabilities
+        }
+        Err(status) if status.code() == tonic::Code::Unimplemented => {
+            tracing::warn!("RE server does not implement GetCapabilities, using defaults");
+            RECapabilities::default()
+        }
+        // The capabilities only let us use the server better, so don't fail to connect without
+        // them. If the server is really unusable, the first request will say so.
+        Err(status) => {
+            tracing::warn!(
+                "Error getting RE server capabilities, using defaults: {}",
+                status
+            );
+            RECapabilities::default()
+        }
+    };
+
+    tracing::info!("RE capabilities: {:?}", capabilities);
+
+    capabilities
+}
+
 #[derive(Clone, Dupe)]
 struct InjectHeadersInterceptor {
     headers: Arc<Vec<(MetadataKey<metadata::Ascii>, MetadataValue<metadata::Ascii>)>>,
@@ -288,18 +557,88 @@ pub struct GRPCClients {
         ContentAddressableStorageClient<InterceptedService<Channel, InjectHeadersInterceptor>>,
     execution_client: ExecutionClient<InterceptedService<Channel, InjectHeadersInterceptor>>,
     action_cache_client: ActionCacheClient<InterceptedService<Channel, InjectHeadersInterceptor>>,
+    bytestream_client: ByteStreamClient<InterceptedService<Channel, InjectHeadersInterceptor>>,
 }
 
 #[derive(Default)]
 pub struct REState {
-    // TODO(aloiscochard): Update those values
-    network_uploaded: i64,   // in bytes
-    network_downloaded: i64, // in bytes
+    network_uploaded: AtomicI64,   // in bytes
+    network_downloaded: AtomicI64, // in bytes
+    bytestream_uploads: TransferCounters,
+    bytestream_downloads: TransferCounters,
+}
+
+#[derive(Default)]
+struct TransferCounters {
+    started: AtomicU32,
+    finished_successfully: AtomicU32,
+    finished_with_error: AtomicU32,
+}
+
+impl TransferCounters {
+    fn start(&self) -> TransferGuard<'_> {
+        self.started.fetch_add(1, Ordering::Relaxed);
+        TransferGuard {
+            counters: self,
+            succeeded: false,
+        }
+    }
+
+    fn get(&self) -> TransferStats {
+        TransferStats {
+            started: self.started.load(Ordering::Relaxed),
+            finished_successfully: self.finished_successfully.load(Ordering::Relaxed),
+            finished_with_error: self.finished_with_error.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counts a transfer as failed when dropped, unless it was marked as succeeded.
+struct TransferGuard<'a> {
+    counters: &'a TransferCounters,
+    succeeded: bool,
+}
+
+impl TransferGuard<'_> {
+    fn succeeded(mut self) {
+        self.succeeded = true;
+    }
+}
+
+impl Drop for TransferGuard<'_> {
+    fn drop(&mut self) {
+        let counter = if self.succeeded {
+            &self.counters.finished_successfully
+        } else {
+            &self.counters.finished_with_error
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TransferStats {
+    pub started: u32,
+    pub finished_successfully: u32,
+    pub finished_with_error: u32,
+}
+
+/// Blobs we transferred through the ByteStream API, i.e. those too large for batch requests.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ByteStreamStatistics {
+    pub uploads: TransferStats,
+    pub downloads: TransferStats,
 }
 
 pub struct REClient {
     grpc_clients: GRPCClients,
-    state: Mutex<REState>,
+    instance_name: String,
+    capabilities: RECapabilities,
+    /// Limits how many blobs we transfer through the ByteStream API at once.
+    bytestream_semaphore: Semaphore,
+    /// Limits how many batches of blobs we upload at once.
+    batch_upload_semaphore: Semaphore,
+    state: Arc<REState>,
 }
 
 impl Drop for REClient {
@@ -310,13 +649,27 @@ impl Drop for REClient {
 }
 
 impl REClient {
-    pub fn new(grpc_clients: GRPCClients) -> Self {
+    pub fn new(
+        grpc_clients: GRPCClients,
+        instance_name: String,
+        capabilities: RECapabilities,
+        bytestream_concurrency: usize,
+    ) -> Self {
         REClient {
             grpc_clients,
-            state: Mutex::new(REState::default()),
+            instance_name,
+            capabilities,
+            bytestream_semaphore: Semaphore::new(bytestream_concurrency),
+            batch_upload_semaphore: Semaphore::new(BATCH_UPLOAD_CONCURRENCY),
+            state: Arc::new(REState::default()),
         }
     }
 
+    /// What the server told us it supports when we connected.
+    pub fn capabilities(&self) -> &RECapabilities {
+        &self.capabilities
+    }
+
     pub async fn get_action_result(
         &self,
         metadata: RemoteExecutionMetadata,
@@ -327,7 +680,7 @@ impl REClient {
         let res = client
             .get_action_result(with_internal_metadata(
                 GetActionResultRequest {
-                    instance_name: INSTANCE_NAME.into(),
+                    instance_name: self.instance_name.clone(),
                     action_digest: Some(tdigest_to(request.digest)),
                     ..Default::default()
                 },
@@ -362,7 +715,7 @@ impl REClient {
         let action_digest = tdigest_to(execute_request.action_digest.clone());
 
         let request = GExecuteRequest {
-            instance_name: INSTANCE_NAME.into(),
+            instance_name: self.instance_name.clone(),
             skip_cache_lookup: false,
             execution_policy: None,
             results_cache_policy: Some(ResultsCachePolicy { priority: 0 }),
@@ -471,34 +824,70 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: UploadRequest,
     ) -> anyhow::Result<UploadResponse> {
-        let mut client = self.grpc_clients.cas_client.clone();
+        let compressor = self.capabilities.batch_update_compressor;
+        let max_batch_size = self.capabilities.max_batch_size();
+
+        let batch_request = |digest: TDigest, data: Vec<u8>| {
+            anyhow::Ok(Request {
+                digest: Some(tdigest_to(digest)),
+                data: compress(compressor, data)?,
+                compressor: compressor as i32,
+            })
+        };
 
-        let files_with_digest: Vec<Request> = request
-            .files_with_digest
-            .unwrap_or_default()
-            .into_try_map(|x| {
-                anyhow::Ok(Request {
-                    digest: Some(tdigest_to(x.digest)),
-                    // FIXME: This could do a lot of blocking reads
-                    data: fs_util::read(&x.name)?,
-                    compressor: compressor::Value::Identity as i32,
-                })
-            })?;
+        let mut batched = Vec::new();
+        let mut streamed = Vec::new();
+
+        for blob in request.inlined_blobs_with_digest.unwrap_or_default() {
+            if blob.digest.size_in_bytes as u64 > max_batch_size {
+                streamed.push((blob.digest, BlobSource::Memory(Arc::new(blob.blob))));
+            } else {
+                batched.push(batch_request(blob.digest, blob.blob)?);
+            }
+        }
+
+        for file in request.files_with_digest.unwrap_or_default() {
+            if file.digest.size_in_bytes as u64 > max_batch_size {
+                // We read those as we send them, so they don't need to fit in memory.
+                streamed.push((file.digest, BlobSource::File(file.name.into())));
+            } else {
+                // FIXME: This could do a lot of blocking reads
+                let data = fs_util::read(&file.name)?;
+                batched.push(batch_request(file.digest, data)?);
+            }
+        }
+
+        let batches = split_into_batches(batched, max_batch_size, |r| r.data.len() as u64);
+
+        futures::future::try_join(
+            futures::future::try_join_all(
+                batches
+                    .into_iter()
+                    .map(|requests| self.upload_batch(metadata.clone(), requests)),
+            ),
+            futures::future::try_join_all(
+                streamed.into_iter().map(|(digest, source)| {
+                    self.bytestream_write(metadata.clone(), digest, source)
+                }),
+            ),
+        )
+        .await?;
+
+        // TODO(aloiscochard): Add something interesting in UploadResponse?
+        Ok(UploadResponse {})
+    }
+
+    async fn upload_batch(
+        &self,
+        metadata: RemoteExecutionMetadata,
+        requests: Vec<Request>,
+    ) -> anyhow::Result<()> {
+        let _permit = self.batch_upload_semaphore.acquire().await?;
+        let mut client = self.grpc_clients.cas_client.clone();
 
         let re_request = BatchUpdateBlobsRequest {
-            instance_name: INSTANCE_NAME.into(),
-            requests: [
-                request
-                    .inlined_blobs_with_digest
-                    .unwrap_or_default()
-                    .into_map(|x| Request {
-                        digest: Some(tdigest_to(x.digest)),
-                        data: x.blob,
-                        compressor: compressor::Value::Identity as i32,
-                    }),
-                files_with_digest,
-            ]
-            .concat(),
+            instance_name: self.instance_name.clone(),
+            requests,
         };
 
         let blob_hashes = re_request
@@ -506,9 +895,17 @@ impl REClient {
             .iter()
             .map(|x| x.digest.as_ref().unwrap().hash.clone())
             .collect::<Vec<String>>();
+        let size = re_request
+            .requests
+            .iter()
+            .map(|x| x.data.len() as i64)
+            .sum::<i64>();
         let response = client
             .batch_update_blobs(with_internal_metadata(re_request, metadata))
             .await?;
+        self.state
+            .network_uploaded
+            .fetch_add(size, Ordering::Relaxed);
 
         let failures: Vec<String> = response
             .get_ref()
@@ -532,13 +929,202 @@ impl REClient {
 
         if failures.is_empty() {
             tracing::debug!("uploaded: {:?}", blob_hashes);
-            // TODO(aloiscochard): Add something interesting in UploadResponse?
-            Ok(UploadResponse {})
+            Ok(())
         } else {
             Err(anyhow::anyhow!("Batch upload failed: {:?}", failures))
         }
     }
 
+    async fn bytestream_write(
+        &self,
+        metadata: RemoteExecutionMetadata,
+        digest: TDigest,
+        source: BlobSource,
+    ) -> anyhow::Result<()> {
+        let _permit = self.bytestream_semaphore.acquire().await?;
+        let transfer = self.state.bytestream_uploads.start();
+
+        let resource_name = bytestream_write_resource_name(
+            &self.instance_name,
+            &uuid::Uuid::new_v4().to_string(),
+            &digest,
+        );
+        let size = digest.size_in_bytes.try_into()?;
+
+        let mut offset = 0;
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            let res = self
+                .bytestream_write_from(
+                    metadata.clone(),
+                    &resource_name,
+                    source.dupe(),
+                    size,
+                    offset,
+                )
+                .await
+                .with_context(|| format!("Error reading `{}` for ByteStream write", digest))?;
+
+            match res {
+                Ok(committed_size) => {
+                    // The server may also return early if it already has the blob, but it will
+                    // then tell us the full size as well.
+                    if committed_size != digest.size_in_bytes {
+                        return Err(anyhow::anyhow!(
+                            "ByteStream write of `{}` committed {} bytes",
+                            digest,
+                            committed_size
+                        ));
+                    }
+                    tracing::debug!("uploaded: {} (ByteStream)", digest.hash);
+                    transfer.succeeded();
+                    return Ok(());
+                }
+                Err(status) if attempt < BYTESTREAM_ATTEMPTS && is_retryable(&status) => {
+                    tracing::debug!(
+                        "ByteStream write of `{}` failed, resuming: {}",
+                        digest,
+                        status
+                    );
+                    offset = self
+                        .bytestream_committed_size(metadata.clone(), &resource_name)
+                        .await
+                        .with_context(|| {
+                            format!("Error resuming ByteStream write of `{}`", digest)
+                        })?;
+                }
+                Err(status) => {
+                    return Err(anyhow::Error::from(status)
+                        .context(format!("Error in ByteStream write of `{}`", digest)));
+                }
+            }
+        }
+    }
+
+    /// Write the blob from `offset` onwards, and return how many bytes the server committed. The
+    /// outer error means we failed to read the blob, which isn't worth resuming.
+    async fn bytestream_write_from(
+        &self,
+        metadata: RemoteExecutionMetadata,
+        resource_name: &str,
+        source: BlobSource,
+        size: usize,
+        offset: usize,
+    ) -> anyhow::Result<Result<i64, tonic::Status>> {
+        let mut client = self.grpc_clients.bytestream_client.clone();
+
+        let state = self.state.dupe();
+        let read_error = Arc::new(Mutex::new(None));
+        let requests = bytestream_write_requests(
+            resource_name.to_owned(),
+            source,
+            size,
+            offset,
+            BYTESTREAM_CHUNK_SIZE,
+            read_error.dupe(),
+        )
+        .inspect(move |r| {
+            state
+                .network_uploaded
+                .fetch_add(r.data.len() as i64, Ordering::Relaxed);
+        });
+
+        let res = client
+            .write(with_internal_metadata(requests, metadata))
+            .await;
+
+        // If we couldn't read the blob, the server only saw a truncated write, so whatever it
+        // told us isn't interesting.
+        if let Some(e) = read_error.lock().unwrap().take() {
+            return Err(e.into());
+        }
+
+        Ok(res.map(|res| res.into_inner().committed_size))
+    }
+
+    async fn bytestream_committed_size(
+        &self,
+        metadata: RemoteExecutionMetadata,
+        resource_name: &str,
+    ) -> anyhow::Result<usize> {
+        let mut client = self.grpc_clients.bytestream_client.clone();
+
+        let res = client
+            .query_write_status(with_internal_metadata(
+                QueryWriteStatusRequest {
+                    resource_name: resource_name.to_owned(),
+                },
+                metadata,
+            ))
+            .await;
+
+        match res {
+            Ok(res) => Ok(res.into_inner().committed_size.try_into()?),
+            // Either nothing reached the server, or it can't tell us how much did. Either way, we
+            // have to start over.
+            Err(status)
+                if status.code() == tonic::Code::NotFound
+                    || status.code() == tonic::Code::Unimplemented =>
+            {
+                Ok(0)
+            }
+            Err(status) => Err(status.into()),
+        }
+    }
+
+    /// Read a blob through the ByteStream API one chunk at a time, so that it can be written out
+    /// as it arrives rather than held in memory.
+    fn bytestream_read(
+        &self,
+        metadata: RemoteExecutionMetadata,
+        digest: TDigest,
+    ) -> BoxStream<'_, anyhow::Result<Vec<u8>>> {
+        let start = async move {
+            let permit = self.bytestream_semaphore.acquire().await?;
+            anyhow::Ok(ByteStreamRead {
+                client: self,
+                metadata,
+                resource_name: bytestream_read_resource_name(&self.instance_name, &digest),
+                digest,
+                _permit: permit,
+                transfer: self.state.bytestream_downloads.start(),
+                stream: None,
+                offset: 0,
+                attempt: 0,
+            })
+        };
+
+        futures::stream::once(start)
+            .map_ok(|read| futures::stream::try_unfold(read, ByteStreamRead::next))
+            .try_flatten()
+            .boxed()
+    }
+
+    async fn bytestream_read_from(
+        &self,
+        metadata: RemoteExecutionMetadata,
+        resource_name: &str,
+        offset: i64,
+    ) -> Result<Streaming<ReadResponse>, tonic::Status> {
+        let mut client = self.grpc_clients.bytestream_client.clone();
+
+        let res = client
+            .read(with_internal_metadata(
+                ReadRequest {
+                    resource_name: resource_name.to_owned(),
+                    read_offset: offset,
+                    read_limit: 0,
+                },
+                metadata,
+            ))
+            .await?;
+
+        Ok(res.into_inner())
+    }
+
     pub async fn upload_blob(
         &self,
         _blob: Vec<u8>,
@@ -553,13 +1139,27 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: DownloadRequest,
     ) -> anyhow::Result<DownloadResponse> {
-        download_impl(request, |re_request| async {
-            let mut client = self.grpc_clients.cas_client.clone();
-            Ok(client
-                .batch_read_blobs(with_internal_metadata(re_request, metadata))
-                .await?
-                .into_inner())
-        })
+        download_impl(
+            &self.instance_name,
+            &self.capabilities,
+            request,
+            |re_request| {
+                let metadata = metadata.clone();
+                async move {
+                    let mut client = self.grpc_clients.cas_client.clone();
+                    let response = client
+                        .batch_read_blobs(with_internal_metadata(re_request, metadata))
+                        .await?
+                        .into_inner();
+                    self.state.network_downloaded.fetch_add(
+                        response.responses.iter().map(|r| r.data.len() as i64).sum(),
+                        Ordering::Relaxed,
+                    );
+                    Ok(response)
+                }
+            },
+            |digest| self.bytestream_read(metadata.clone(), digest),
+        )
         .await
     }
 
@@ -599,19 +1199,112 @@ impl REClient {
     }
 
     pub fn get_network_stats(&self) -> anyhow::Result<NetworkStatisticsResponse> {
-        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
         Ok(NetworkStatisticsResponse {
-            downloaded: state.network_downloaded,
-            uploaded: state.network_uploaded,
+            downloaded: self.state.network_downloaded.load(Ordering::Relaxed),
+            uploaded: self.state.network_uploaded.load(Ordering::Relaxed),
             _dot_dot_default: (),
         })
     }
 
+    pub fn get_bytestream_stats(&self) -> ByteStreamStatistics {
+        ByteStreamStatistics {
+            uploads: self.state.bytestream_uploads.get(),
+            downloads: self.state.bytestream_downloads.get(),
+        }
+    }
+
     pub fn get_experiment_name(&self) -> anyhow::Result<Option<String>> {
         Ok(None)
     }
 }
 
+/// A ByteStream read in progress. If the stream breaks, we resume from what we already returned.
+struct ByteStreamRead<'a> {
+    client: &'a REClient,
+    metadata: RemoteExecutionMetadata,
+    resource_name: String,
+    digest: TDigest,
+    _permit: SemaphorePermit<'a>,
+    transfer: TransferGuard<'a>,
+    stream: Option<Streaming<ReadResponse>>,
+    offset: i64,
+    attempt: usize,
+}
+
+impl ByteStreamRead<'_> {
+    async fn next(mut self) -> anyhow::Result<Option<(Vec<u8>, Self)>> {
+        loop {
+            let mut stream = match self.stream.take() {
+                Some(stream) => stream,
+                None => {
+                    self.attempt += 1;
+                    let res = self
+                        .client
+                        .bytestream_read_from(
+                            self.metadata.clone(),
+                            &self.resource_name,
+                            self.offset,
+                        )
+                        .await;
+                    match res {
+                        Ok(stream) => stream,
+                        Err(status) => {
+                            self.resume(status)?;
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            match stream.message().await {
+                Ok(Some(res)) => {
+                    self.client
+                        .state
+                        .network_downloaded
+                        .fetch_add(res.data.len() as i64, Ordering::Relaxed);
+                    self.offset += res.data.len() as i64;
+                    if self.offset > self.digest.size_in_bytes {
+                        return Err(anyhow::anyhow!(
+                            "ByteStream read of `{}` returned more than {} bytes",
+                            self.digest,
+                            self.digest.size_in_bytes
+                        ));
+                    }
+                    self.stream = Some(stream);
+                    return Ok(Some((res.data, self)));
+                }
+                Ok(None) => {
+                    if self.offset != self.digest.size_in_bytes {
+                        return Err(anyhow::anyhow!(
+                            "ByteStream read of `{}` returned {} bytes",
+                            self.digest,
+                            self.offset
+                        ));
+                    }
+                    self.transfer.succeeded();
+                    return Ok(None);
+                }
+                Err(status) => self.resume(status)?,
+            }
+        }
+    }
+
+    /// Check whether we should resume after `status`.
+    fn resume(&self, status: tonic::Status) -> anyhow::Result<()> {
+        if self.attempt < BYTESTREAM_ATTEMPTS && is_retryable(&status) {
+            tracing::debug!(
+                "ByteStream read of `{}` failed, resuming: {}",
+                self.digest,
+                status
+            );
+            Ok(())
+        } else {
+            Err(anyhow::Error::from(status)
+                .context(format!("Error in ByteStream read of `{}`", self.digest)))
+        }
+    }
+}
+
 fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionResult2> {
     let execution_metadata = action_result
         .execution_metadata
@@ -697,38 +1390,97 @@ fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionR
     Ok(action_result)
 }
 
-async fn download_impl<F, Fut>(request: DownloadRequest, f: F) -> anyhow::Result<DownloadResponse>
+async fn download_impl<F, Fut, G, GStream>(
+    instance_name: &str,
+    capabilities: &RECapabilities,
+    request: DownloadRequest,
+    f: F,
+    read_blob: G,
+) -> anyhow::Result<DownloadResponse>
 where
-    F: FnOnce(BatchReadBlobsRequest) -> Fut,
+    F: Fn(BatchReadBlobsRequest) -> Fut,
     Fut: Future<Output = anyhow::Result<BatchReadBlobsResponse>>,
+    G: Fn(TDigest) -> GStream,
+    GStream: Stream<Item = anyhow::Result<Vec<u8>>>,
 {
     let inlined_digests = request.inlined_digests.unwrap_or_default();
     let file_digests = request.file_digests.unwrap_or_default();
 
-    let re_request = BatchReadBlobsRequest {
-        instance_name: INSTANCE_NAME.into(),
-        digests: file_digests
-            .iter()
-            .map(|req| &req.named_digest.digest)
-            .chain(inlined_digests.iter())
-            .map(|d| tdigest_to(d.clone()))
-            .filter(|d| d.size_bytes > 0)
-            .collect(),
-        acceptable_compressors: vec![compressor::Value::Identity as i32],
-    };
+    let mut acceptable_compressors = vec![compressor::Value::Identity as i32];
+    if capabilities.batch_read_compressor != compressor::Value::Identity {
+        acceptable_compressors.push(capabilities.batch_read_compressor as i32);
+    }
+
+    let max_batch_size = capabilities.max_batch_size();
+    let is_streamed = |digest: &TDigest| digest.size_in_bytes as u64 > max_batch_size;
+
+    let batched = file_digests
+        .iter()
+        .map(|req| &req.named_digest.digest)
+        .chain(inlined_digests.iter())
+        .filter(|d| d.size_in_bytes > 0 && !is_streamed(d))
+        .map(|d| tdigest_to(d.clone()))
+        .collect();
+
+    let batches = split_into_batches(batched, max_batch_size, |d| d.size_bytes as u64);
+
+    // Blobs that are too large for a batch are streamed. Files get them written as they arrive,
+    // and we only download each blob once, copying it to any other file that wants it.
+    let streamed_inlined = inlined_digests
+        .iter()
+        .filter(|d| is_streamed(d))
+        .collect::<HashSet<_>>();
+
+    let mut streamed_files = HashMap::<_, Vec<_>>::new();
+    for req in &file_digests {
+        if is_streamed(&req.named_digest.digest) {
+            streamed_files
+                .entry(&req.named_digest.digest)
+                .or_default()
+                .push(req);
+        }
+    }
 
-    let response = f(re_request).await?;
+    let read_blob = &read_blob;
 
-    let response = response
-        .responses
+    let (responses, streamed, _) = futures::future::try_join3(
+        futures::future::try_join_all(batches.into_iter().map(|digests| {
+            f(BatchReadBlobsRequest {
+                instance_name: instance_name.to_owned(),
+                digests,
+                acceptable_compressors: acceptable_compressors.clone(),
+            })
+        })),
+        futures::future::try_join_all(streamed_inlined.into_iter().map(|digest| async move {
+            let data = read_blob(digest.clone()).try_concat().await?;
+            anyhow::Ok((digest.clone(), data))
+        })),
+        futures::future::try_join_all(streamed_files.into_iter().map(
+            |(digest, reqs)| async move {
+                let (first, rest) = reqs.split_first().context("No files for digest")?;
+                write_file(first, read_blob(digest.clone())).await?;
+                for req in rest {
+                    copy_file(first, req).await?;
+                }
+                anyhow::Ok(())
+            },
+        )),
+    )
+    .await?;
+
+    let mut response = responses
         .into_iter()
+        .flat_map(|response| response.responses)
         .map(|r| {
             check_status(r.status.unwrap_or_default())?;
             let digest = tdigest_from(r.digest.context("Response digest not found.")?);
-            anyhow::Ok((digest, r.data))
+            let data = decompress(r.compressor, r.data, &digest)?;
+            anyhow::Ok((digest, data))
         })
         .collect::<Result<HashMap<_, _>, _>>()?;
 
+    response.extend(streamed);
+
     let get = |digest: &TDigest| -> anyhow::Result<Vec<u8>> {
         if digest.size_in_bytes == 0 {
             return Ok(Vec::new());
@@ -750,37 +1502,13 @@ where
         })
     })?;
 
-    let writes = file_digests.iter().map(|req| async {
-        let data = get(&req.named_digest.digest)?;
-
-        let mut opts = OpenOptions::new();
-        opts.read(true).write(true).create_new(true);
-        #[cfg(unix)]
-        {
-            if req.is_executable {
-                opts.mode(0o755);
-            } else {
-                opts.mode(0o644);
-            }
-        }
-
-        async {
-            let mut file = opts
-                .open(&req.named_digest.name)
-                .await
-                .context("Error opening")?;
-            file.write_all(&data).await.context("Error writing")?;
-            file.flush().await.context("Error flushing")?;
-            anyhow::Ok(())
-        }
-        .await
-        .with_context(|| {
-            format!(
-                "Error writing digest `{}` to `{}`",
-                req.named_digest.digest, req.named_digest.name,
-            )
-        })
-    });
+    let writes = file_digests
+        .iter()
+        .filter(|req| !is_streamed(&req.named_digest.digest))
+        .map(|req| async {
+            let data = get(&req.named_digest.digest);
+            write_file(req, futures::stream::once(futures::future::ready(data))).await
+        });
 
     futures::future::try_join_all(writes).await?;
 
@@ -790,6 +1518,69 @@ where
     })
 }
 
+async fn create_file(req: &NamedDigestWithPermissions) -> io::Result<tokio::fs::File> {
+    let mut opts = OpenOptions::new();
+    opts.read(true).write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        if req.is_executable {
+            opts.mode(0o755);
+        } else {
+            opts.mode(0o644);
+        }
+    }
+    opts.open(&req.named_digest.name).await
+}
+
+/// Create the file for `req`, and write `data` to it as it arrives.
+async fn write_file(
+    req: &NamedDigestWithPermissions,
+    data: impl Stream<Item = anyhow::Result<Vec<u8>>>,
+) -> anyhow::Result<()> {
+    futures::pin_mut!(data);
+
+    async {
+        let mut file = create_file(req).await.context("Error opening")?;
+        while let Some(chunk) = data.try_next().await? {
+            file.write_all(&chunk).await.context("Error writing")?;
+        }
+        file.flush().await.context("Error flushing")?;
+        anyhow::Ok(())
+    }
+    .await
+    .with_context(|| {
+        format!(
+            "Error writing digest `{}` to `{}`",
+            req.named_digest.digest, req.named_digest.name,
+        )
+    })
+}
+
+/// Create the file for `req` with the contents of the file we already wrote for `from`.
+async fn copy_file(
+    from: &NamedDigestWithPermissions,
+    req: &NamedDigestWithPermissions,
+) -> anyhow::Result<()> {
+    async {
+        let mut source = tokio::fs::File::open(&from.named_digest.name)
+            .await
+            .context("Error opening source")?;
+        let mut file = create_file(req).await.context("Error opening")?;
+        tokio::io::copy(&mut source, &mut file)
+            .await
+            .context("Error copying")?;
+        file.flush().await.context("Error flushing")?;
+        anyhow::Ok(())
+    }
+    .await
+    .with_context(|| {
+        format!(
+            "Error copying digest `{}` from `{}` to `{}`",
+            req.named_digest.digest, from.named_digest.name, req.named_digest.name,
+        )
+    })
+}
+
 fn with_internal_metadata<T>(t: T, metadata: RemoteExecutionMetadata) -> tonic::Request<T> {
     // This is pretty ugly, but the protobuf spec that defines this is internal, so considering
     // field numbers need to be stable anyway (= low risk), and this is not used in prod (= low
@@ -858,6 +1649,14 @@ mod tests {
     use crate::NamedDigest;
     use crate::NamedDigestWithPermissions;
 
+    fn no_bytestream_reads(digest: TDigest) -> BoxStream<'static, anyhow::Result<Vec<u8>>> {
+        futures::stream::once(futures::future::ready(Err(anyhow::anyhow!(
+            "Unexpected ByteStream read of `{}`",
+            digest
+        ))))
+        .boxed()
+    }
+
     #[tokio::test]
     async fn test_download_named() -> anyhow::Result<()> {
         let work = tempfile::tempdir()?;
@@ -920,12 +1719,18 @@ mod tests {
             ],
         };
 
-        download_impl(req, |req| async move {
-            assert_eq!(req.digests.len(), 2);
-            assert_eq!(req.digests[0], tdigest_to(digest1.clone()));
-            assert_eq!(req.digests[1], tdigest_to(digest2.clone()));
-            Ok(res)
-        })
+        download_impl(
+            "",
+            &RECapabilities::default(),
+            req,
+            |req| {
+                assert_eq!(req.digests.len(), 2);
+                assert_eq!(req.digests[0], tdigest_to(digest1.clone()));
+                assert_eq!(req.digests[1], tdigest_to(digest2.clone()));
+                futures::future::ready(Ok(res.clone()))
+            },
+            no_bytestream_reads,
+        )
         .await?;
 
         assert_eq!(tokio::fs::read(&path1).await?, vec![1, 2, 3]);
@@ -982,12 +1787,18 @@ mod tests {
             ],
         };
 
-        let res = download_impl(req, |req| async move {
-            assert_eq!(req.digests.len(), 2);
-            assert_eq!(req.digests[0], tdigest_to(digest1.clone()));
-            assert_eq!(req.digests[1], tdigest_to(digest2.clone()));
-            Ok(res)
-        })
+        let res = download_impl(
+            "",
+            &RECapabilities::default(),
+            req,
+            |req| {
+                assert_eq!(req.digests.len(), 2);
+                assert_eq!(req.digests[0], tdigest_to(digest1.clone()));
+                assert_eq!(req.digests[1], tdigest_to(digest2.clone()));
+                futures::future::ready(Ok(res.clone()))
+            },
+            no_bytestream_reads,
+        )
         .await?;
 
         let inlined_blobs = res.inlined_blobs.unwrap();
@@ -1016,12 +1827,14 @@ mod tests {
             ..Default::default()
         };
 
-        let res = BatchReadBlobsResponse { responses: vec![] };
-
-        let res = download_impl(req, |req| async move {
-            assert_eq!(req.digests.len(), 0);
-            Ok(res)
-        })
+        // Nothing to fetch, so we shouldn't be making a request at all.
+        let res = download_impl(
+            "",
+            &RECapabilities::default(),
+            req,
+            |_req| futures::future::ready(Err(anyhow::anyhow!("Unexpected request"))),
+            no_bytestream_reads,
+        )
         .await?;
 
         let inlined_blobs = res.inlined_blobs.unwrap();
@@ -1034,6 +1847,354 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_download_batched_and_compressed() -> anyhow::Result<()> {
+        let digest1 = &TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+
+        let digest2 = &TDigest {
+            hash: "bb".to_owned(),
+            size_in_bytes: 4,
+            ..Default::default()
+        };
+
+        let req = DownloadRequest {
+            inlined_digests: Some(vec![digest1.clone(), digest2.clone()]),
+            ..Default::default()
+        };
+
+        let capabilities = RECapabilities {
+            max_batch_total_size_bytes: Some(5),
+            batch_read_compressor: compressor::Value::Zstd,
+            ..Default::default()
+        };
+
+        let res = download_impl(
+            "instance",
+            &capabilities,
+            req,
+            |req| {
+                assert_eq!(req.instance_name, "instance");
+                assert_eq!(
+                    req.acceptable_compressors,
+                    vec![
+                        compressor::Value::Identity as i32,
+                        compressor::Value::Zstd as i32
+                    ]
+                );
+                // The two digests don't fit in a single batch.
+                assert_eq!(req.digests.len(), 1);
+
+                let digest = tdigest_from(req.digests[0].clone());
+                let response = if digest == *digest1 {
+                    batch_read_blobs_response::Response {
+                        digest: Some(tdigest_to(digest)),
+                        data: vec![1, 2, 3],
+                        compressor: compressor::Value::Identity as i32,
+                        ..Default::default()
+                    }
+                } else {
+                    batch_read_blobs_response::Response {
+                        digest: Some(tdigest_to(digest)),
+                        data: compress(compressor::Value::Zstd, vec![4, 5, 6, 7]).unwrap(),
+                        compressor: compressor::Value::Zstd as i32,
+                        ..Default::default()
+                    }
+                };
+
+                futures::future::ready(Ok(BatchReadBlobsResponse {
+                    responses: vec![response],
+                }))
+            },
+            no_bytestream_reads,
+        )
+        .await?;
+
+        let inlined_blobs = res.inlined_blobs.unwrap();
+
+        assert_eq!(inlined_blobs.len(), 2);
+
+        assert_eq!(inlined_blobs[0].digest, *digest1);
+        assert_eq!(inlined_blobs[0].blob, vec![1, 2, 3]);
+
+        assert_eq!(inlined_blobs[1].digest, *digest2);
+        assert_eq!(inlined_blobs[1].blob, vec![4, 5, 6, 7]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_bytestream() -> anyhow::Result<()> {
+        let digest1 = &TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+
+        let digest2 = &TDigest {
+            hash: "bb".to_owned(),
+            size_in_bytes: 6,
+            ..Default::default()
+        };
+
+        let req = DownloadRequest {
+            inlined_digests: Some(vec![digest1.clone(), digest2.clone()]),
+            ..Default::default()
+        };
+
+        let capabilities = RECapabilities {
+            max_batch_total_size_bytes: Some(5),
+            ..Default::default()
+        };
+
+        let res = download_impl(
+            "",
+            &capabilities,
+            req,
+            |req| {
+                assert_eq!(req.digests, vec![tdigest_to(digest1.clone())]);
+                futures::future::ready(Ok(BatchReadBlobsResponse {
+                    responses: vec![batch_read_blobs_response::Response {
+                        digest: Some(tdigest_to(digest1.clone())),
+                        data: vec![1, 2, 3],
+                        ..Default::default()
+                    }],
+                }))
+            },
+            |digest| {
+                // This one is too large for a batch.
+                assert_eq!(digest, *digest2);
+                futures::stream::iter(vec![Ok(vec![4, 5, 6]), Ok(vec![7, 8, 9])])
+            },
+        )
+        .await?;
+
+        let inlined_blobs = res.inlined_blobs.unwrap();
+
+        assert_eq!(inlined_blobs.len(), 2);
+        assert_eq!(inlined_blobs[0].blob, vec![1, 2, 3]);
+        assert_eq!(inlined_blobs[1].blob, vec![4, 5, 6, 7, 8, 9]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_bytestream_files() -> anyhow::Result<()> {
+        let work = tempfile::tempdir()?;
+
+        let path1 = work.path().join("path1");
+        let path1 = path1.to_str().context("tempdir is not utf8")?;
+
+        let path2 = work.path().join("path2");
+        let path2 = path2.to_str().context("tempdir is not utf8")?;
+
+        let digest = TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 6,
+            ..Default::default()
+        };
+
+        // Both files want the same blob, which is too large for a batch.
+        let req = DownloadRequest {
+            file_digests: Some(vec![
+                NamedDigestWithPermissions {
+                    named_digest: NamedDigest {
+                        name: path1.to_owned(),
+                        digest: digest.clone(),
+                        ..Default::default()
+                    },
+                    is_executable: false,
+                    ..Default::default()
+                },
+                NamedDigestWithPermissions {
+                    named_digest: NamedDigest {
+                        name: path2.to_owned(),
+                        digest: digest.clone(),
+                        ..Default::default()
+                    },
+                    is_executable: true,
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let capabilities = RECapabilities {
+            max_batch_total_size_bytes: Some(5),
+            ..Default::default()
+        };
+
+        let reads = AtomicU32::new(0);
+
+        download_impl(
+            "",
+            &capabilities,
+            req,
+            |_req| futures::future::ready(Err(anyhow::anyhow!("Unexpected request"))),
+            |d| {
+                assert_eq!(d, digest);
+                reads.fetch_add(1, Ordering::Relaxed);
+                futures::stream::iter(vec![Ok(vec![1, 2, 3]), Ok(vec![4, 5, 6])])
+            },
+        )
+        .await?;
+
+        assert_eq!(reads.load(Ordering::Relaxed), 1);
+        assert_eq!(tokio::fs::read(&path1).await?, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(tokio::fs::read(&path2).await?, vec![1, 2, 3, 4, 5, 6]);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                tokio::fs::metadata(&path1).await?.permissions().mode() & 0o111,
+                0o000
+            );
+            assert_eq!(
+                tokio::fs::metadata(&path2).await?.permissions().mode() & 0o111,
+                0o111
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn write_requests(source: BlobSource, offset: usize) -> Vec<WriteRequest> {
+        let read_error = Arc::new(Mutex::new(None));
+        let requests =
+            bytestream_write_requests("name".to_owned(), source, 10, offset, 4, read_error.dupe())
+                .collect::<Vec<_>>()
+                .await;
+        assert!(read_error.lock().unwrap().is_none());
+        requests
+    }
+
+    #[tokio::test]
+    async fn test_bytestream_write_requests() -> anyhow::Result<()> {
+        let data = (0..10).collect::<Vec<u8>>();
+
+        let work = tempfile::tempdir()?;
+        let path = work.path().join("blob");
+        std::fs::write(&path, &data)?;
+        let path = path.to_str().context("tempdir is not utf8")?;
+
+        for source in [
+            BlobSource::Memory(Arc::new(data)),
+            BlobSource::File(path.into()),
+        ] {
+            assert_eq!(
+                write_requests(source.dupe(), 0).await,
+                vec![
+                    WriteRequest {
+                        resource_name: "name".to_owned(),
+                        write_offset: 0,
+                        finish_write: false,
+                        data: vec![0, 1, 2, 3],
+                    },
+                    WriteRequest {
+                        resource_name: "".to_owned(),
+                        write_offset: 4,
+                        finish_write: false,
+                        data: vec![4, 5, 6, 7],
+                    },
+                    WriteRequest {
+                        resource_name: "".to_owned(),
+                        write_offset: 8,
+                        finish_write: true,
+                        data: vec![8, 9],
+                    },
+                ]
+            );
+
+            // Resuming.
+            assert_eq!(
+                write_requests(source.dupe(), 6).await,
+                vec![WriteRequest {
+                    resource_name: "name".to_owned(),
+                    write_offset: 6,
+                    finish_write: true,
+                    data: vec![6, 7, 8, 9],
+                }]
+            );
+
+            // Everything was already committed, but we still need to finish the write.
+            assert_eq!(
+                write_requests(source, 10).await,
+                vec![WriteRequest {
+                    resource_name: "name".to_owned(),
+                    write_offset: 10,
+                    finish_write: true,
+                    data: vec![],
+                }]
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bytestream_write_requests_short_file() -> anyhow::Result<()> {
+        let work = tempfile::tempdir()?;
+        let path = work.path().join("blob");
+        std::fs::write(&path, [0, 1, 2, 3, 4, 5])?;
+        let path = path.to_str().context("tempdir is not utf8")?;
+
+        // The file is shorter than we expect, so we stop after the first chunk.
+        let read_error = Arc::new(Mutex::new(None));
+        let requests = bytestream_write_requests(
+            "name".to_owned(),
+            BlobSource::File(path.into()),
+            10,
+            0,
+            4,
+            read_error.dupe(),
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            read_error.lock().unwrap().as_ref().map(|e| e.kind()),
+            Some(io::ErrorKind::UnexpectedEof)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytestream_resource_names() {
+        let digest = TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+
+        assert_eq!(bytestream_read_resource_name("", &digest), "blobs/aa/3");
+        assert_eq!(
+            bytestream_read_resource_name("instance", &digest),
+            "instance/blobs/aa/3"
+        );
+        assert_eq!(
+            bytestream_write_resource_name("instance", "id", &digest),
+            "instance/uploads/id/blobs/aa/3"
+        );
+    }
+
+    #[test]
+    fn test_split_into_batches() {
+        let split = |items: Vec<u64>, max| split_into_batches(items, max, |x| *x);
+
+        assert_eq!(split(vec![], 10), Vec::<Vec<u64>>::new());
+        assert_eq!(split(vec![1, 2, 3], u64::MAX), vec![vec![1, 2, 3]]);
+        assert_eq!(
+            split(vec![4, 5, 6, 20, 1], 10),
+            vec![vec![4, 5], vec![6], vec![20], vec![1]]
+        );
+    }
+
     #[test]
     fn test_substitute_env_vars() {
         let getter = |s: &str| match s {
@@ -12,6 +12,7 @@
 #![cfg_attr(feature = "gazebo_lint", allow(deprecated))] // :(
 #![cfg_attr(feature = "gazebo_lint", plugin(gazebo_lint))]
 
+mod capabilities;
 mod client;
 mod digest;
 mod error;
@@ -19,6 +20,7 @@ mod grpc;
 mod metadata;
 mod request;
 mod response;
+pub use capabilities::*;
 pub use client::*;
 pub use digest::*;
 pub use error::*;
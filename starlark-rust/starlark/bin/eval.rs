@@ -22,7 +22,10 @@ use std::io;
 use std::iter;
 use std::path::Path;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
 
+use dupe::Dupe;
 use itertools::Either;
 use lsp_types::Diagnostic;
 use lsp_types::Url;
@@ -30,11 +33,15 @@ use starlark::docs::get_registered_starlark_docs;
 use starlark::docs::render_docs_as_code;
 use starlark::docs::Doc;
 use starlark::docs::DocItem;
+use starlark::docs::DocTest;
+use starlark::docs::Identifier;
 use starlark::environment::FrozenModule;
 use starlark::environment::Globals;
 use starlark::environment::Module;
 use starlark::errors::EvalMessage;
+use starlark::errors::LintFix;
 use starlark::eval::Evaluator;
+use starlark::lsp::server::LoadCompletion;
 use starlark::lsp::server::LspContext;
 use starlark::lsp::server::LspEvalResult;
 use starlark::lsp::server::LspUrl;
@@ -63,9 +70,11 @@ pub(crate) struct Context {
     pub(crate) mode: ContextMode,
     pub(crate) print_non_none: bool,
     pub(crate) prelude: Vec<FrozenModule>,
-    pub(crate) module: Option<Module>,
+    pub(crate) module: Option<Rc<Module>>,
     pub(crate) builtin_docs: HashMap<LspUrl, String>,
     pub(crate) builtin_symbols: HashMap<String, LspUrl>,
+    /// Documentation for the global symbols, used for hover and completion.
+    pub(crate) environment: Arc<Vec<Doc>>,
 }
 
 /// The outcome of evaluating (checking, parsing or running) given starlark code.
@@ -111,15 +120,32 @@ impl Context {
             .collect::<anyhow::Result<_>>()?;
 
         let module = if module {
-            Some(Self::new_module(&prelude))
+            Some(Rc::new(Self::new_module(&prelude)))
         } else {
             None
         };
         let mut builtins: HashMap<LspUrl, Vec<Doc>> = HashMap::new();
         let mut builtin_symbols: HashMap<String, LspUrl> = HashMap::new();
+        let mut environment: Vec<Doc> = globals
+            .member_documentation()
+            .into_iter()
+            .filter_map(|(name, item)| {
+                Some(Doc {
+                    id: Identifier {
+                        name,
+                        location: None,
+                    },
+                    item: item?,
+                    custom_attrs: HashMap::new(),
+                })
+            })
+            .collect();
         for doc in get_registered_starlark_docs() {
             let uri = Self::url_for_doc(&doc);
             builtin_symbols.insert(doc.id.name.clone(), uri.clone());
+            if !environment.iter().any(|d| d.id.name == doc.id.name) {
+                environment.push(doc.clone());
+            }
             builtins.entry(uri).or_default().push(doc);
         }
         let builtin_docs = builtins
@@ -134,6 +160,7 @@ impl Context {
             module,
             builtin_docs,
             builtin_symbols,
+            environment: Arc::new(environment),
         })
     }
 
@@ -228,7 +255,7 @@ impl Context {
 
     fn run(&self, file: &str, ast: AstModule) -> EvalResult<impl Iterator<Item = EvalMessage>> {
         let new_module;
-        let module = match self.module.as_ref() {
+        let module = match self.module.as_deref() {
             Some(module) => module,
             None => {
                 new_module = Self::new_module(&self.prelude);
@@ -252,8 +279,38 @@ impl Context {
         )
     }
 
-    fn check(&self, module: &AstModule) -> impl Iterator<Item = EvalMessage> {
-        let globals = if self.prelude.is_empty() {
+    /// Run the `>>>` examples in the docstrings of `file`, with the symbols it defines available.
+    pub(crate) fn doctest_file(&self, file: &Path) -> impl Iterator<Item = EvalMessage> {
+        let failures = match self.doctest(file) {
+            Ok(failures) => failures,
+            Err(e) => vec![e],
+        };
+        let file = file.to_owned();
+        failures
+            .into_iter()
+            .map(move |e| EvalMessage::from_anyhow(&file, &e))
+    }
+
+    fn doctest(&self, file: &Path) -> anyhow::Result<Vec<anyhow::Error>> {
+        let ast = AstModule::parse_file(file, &dialect())?;
+        let tests = DocTest::extract(&ast);
+        let globals = globals();
+        let module = Self::new_module(&self.prelude);
+        {
+            let mut eval = Evaluator::new(&module);
+            eval.eval_module(ast, &globals)?;
+        }
+        let mut modules = self.prelude.clone();
+        modules.push(module.freeze()?);
+        Ok(tests
+            .iter()
+            .flat_map(|test| test.run(&globals, &modules, &dialect()))
+            .collect())
+    }
+
+    /// The names that are defined for the linter, if they are known.
+    fn lint_globals(&self) -> Option<HashSet<String>> {
+        if self.prelude.is_empty() {
             None
         } else {
             let mut globals = HashSet::new();
@@ -268,13 +325,32 @@ impl Context {
             }
 
             Some(globals)
-        };
+        }
+    }
 
+    fn check(&self, module: &AstModule) -> impl Iterator<Item = EvalMessage> {
         module
-            .lint(globals.as_ref())
+            .lint(self.lint_globals().as_ref())
             .into_iter()
             .map(EvalMessage::from)
     }
+
+    /// Apply the automatic lint fixes to `file` in place, returning how many were applied.
+    pub(crate) fn fix_file(&self, file: &Path) -> anyhow::Result<usize> {
+        let content = fs::read_to_string(file)?;
+        let ast = match AstModule::parse(&file.to_string_lossy(), content.clone(), &dialect()) {
+            Ok(ast) => ast,
+            // Checking the file will report the error.
+            Err(_) => return Ok(0),
+        };
+        let lints = ast.lint(self.lint_globals().as_ref());
+        let (fixed, count) =
+            LintFix::apply_all(&content, lints.iter().filter_map(|x| x.fix.as_ref()));
+        if count > 0 {
+            fs::write(file, fixed)?;
+        }
+        Ok(count)
+    }
 }
 
 impl LspContext for Context {
@@ -345,6 +421,53 @@ impl LspContext for Context {
     ) -> anyhow::Result<Option<LspUrl>> {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
+
+    fn get_environment(&self, _uri: &LspUrl) -> Arc<Vec<Doc>> {
+        self.environment.dupe()
+    }
+
+    fn get_load_completions(
+        &self,
+        current_value: &str,
+        current_file: &LspUrl,
+    ) -> anyhow::Result<Vec<LoadCompletion>> {
+        // Everything up to and including the last `/` is the directory to list.
+        let prefix = match current_value.rfind('/') {
+            Some(i) => &current_value[..=i],
+            None => "",
+        };
+        let dir = match self.resolve_load(prefix, current_file)? {
+            LspUrl::File(dir) => dir,
+            _ => return Ok(Vec::new()),
+        };
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut completions = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            if LspUrl::File(path.clone()) == *current_file {
+                continue;
+            }
+            let is_directory = entry.file_type()?.is_dir();
+            let is_starlark = matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("star" | "bzl")
+            );
+            if is_directory || is_starlark {
+                completions.push(LoadCompletion {
+                    value: format!("{}{}", prefix, entry.file_name().to_string_lossy()),
+                    is_directory,
+                });
+            }
+        }
+        completions.sort_by(|a, b| a.value.cmp(&b.value));
+        Ok(completions)
+    }
 }
 
 pub(crate) fn globals() -> Globals {
@@ -26,6 +26,8 @@ use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fmt;
 use std::fmt::Display;
+use std::fs;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -35,8 +37,11 @@ use dupe::Dupe;
 use eval::Context;
 use itertools::Either;
 use itertools::Itertools;
+use starlark::docs::docs_json_schema;
 use starlark::docs::get_registered_starlark_docs;
 use starlark::docs::render_docs_as_code;
+use starlark::docs::render_docs_as_html;
+use starlark::docs::render_docs_as_json;
 use starlark::docs::Doc;
 use starlark::docs::Identifier;
 use starlark::docs::MarkdownFlavor;
@@ -45,7 +50,9 @@ use starlark::environment::Globals;
 use starlark::errors::EvalMessage;
 use starlark::errors::EvalSeverity;
 use starlark::lsp;
-use starlark::read_line::ReadLine;
+use starlark::syntax::AstModule;
+use starlark::syntax::ModuleGraph;
+use starlark::syntax::ModuleGraphLoader;
 use walkdir::WalkDir;
 
 use crate::eval::ContextMode;
@@ -53,6 +60,7 @@ use crate::types::LintMessage;
 
 mod dap;
 mod eval;
+mod repl;
 mod types;
 
 #[derive(Debug, Parser)]
@@ -66,6 +74,10 @@ struct Args {
             "check",
             "json",
             "docs",
+            "format",
+            "fix",
+            "doctest",
+            "graph",
             "evaluate",
             "files",
         ],
@@ -81,6 +93,10 @@ struct Args {
             "check",
             "json",
             "docs",
+            "format",
+            "fix",
+            "doctest",
+            "graph",
             "extension",
             "prelude",
             "evaluate",
@@ -103,6 +119,13 @@ struct Args {
     )]
     json: bool,
 
+    #[arg(
+        long = "fix",
+        help = "Apply the automatic fixes for lints, then report the lints that remain.",
+        requires = "check"
+    )]
+    fix: bool,
+
     #[arg(
         long = "docs",
         help = "Generate documentation output.",
@@ -110,6 +133,38 @@ struct Args {
     )]
     docs: Option<ArgsDoc>,
 
+    #[arg(
+        long = "docs-output",
+        value_name = "DIR",
+        help = "Directory to write documentation files to, required for `--docs=html`.",
+        requires = "docs"
+    )]
+    docs_output: Option<PathBuf>,
+
+    #[arg(
+        long = "format",
+        help = "Rewrite the files in canonical format.",
+        conflicts_with_all = &["lsp", "dap", "check", "json", "docs", "evaluate"],
+        requires = "files",
+    )]
+    format: bool,
+
+    #[arg(
+        long = "doctest",
+        help = "Run the `>>>` examples in the docstrings of the files.",
+        conflicts_with_all = &["lsp", "dap", "check", "docs", "format", "evaluate"],
+        requires = "files",
+    )]
+    doctest: bool,
+
+    #[arg(
+        long = "graph",
+        help = "Print the dependency graph of the files and the modules they load, as DOT, or JSON with `--json`.",
+        conflicts_with_all = &["lsp", "dap", "check", "docs", "format", "doctest", "evaluate"],
+        requires = "files",
+    )]
+    graph: bool,
+
     #[arg(
         long = "extension",
         help = "File extension when searching directories."
@@ -144,6 +199,9 @@ enum ArgsDoc {
     Lsp,
     Markdown,
     Code,
+    Json,
+    JsonSchema,
+    Html,
 }
 
 // Treat directories as things to recursively walk for .<extension> files,
@@ -217,17 +275,33 @@ fn drain(xs: impl Iterator<Item = EvalMessage>, json: bool, stats: &mut Stats) {
     }
 }
 
-fn interactive(ctx: &Context) -> anyhow::Result<()> {
-    let mut rl = ReadLine::new("STARLARK_RUST_HISTFILE")?;
-    loop {
-        match rl.read_line("$> ")? {
-            Some(line) => {
-                let mut stats = Stats::default();
-                drain(ctx.expression(line).messages, false, &mut stats);
-            }
-            // User pressed EOF - disconnected terminal, or similar
-            None => return Ok(()),
-        }
+/// Format `file` in place, returning whether it changed.
+fn format_file(file: &Path) -> anyhow::Result<bool> {
+    let content = fs::read_to_string(file)?;
+    let formatted =
+        AstModule::parse(&file.to_string_lossy(), content.clone(), &eval::dialect())?.format();
+    if formatted == content {
+        return Ok(false);
+    }
+    fs::write(file, formatted)?;
+    Ok(true)
+}
+
+/// Modules are named by their path, and loads are relative to the directory of the loading file.
+struct GraphLoader;
+
+impl ModuleGraphLoader for GraphLoader {
+    fn resolve_load(&self, path: &str, current: &str) -> anyhow::Result<String> {
+        let path = Path::new(path);
+        let resolved = match Path::new(current).parent() {
+            Some(dir) if !path.is_absolute() => dir.join(path),
+            _ => path.to_owned(),
+        };
+        Ok(resolved.to_string_lossy().into_owned())
+    }
+
+    fn parse(&self, module: &str) -> anyhow::Result<AstModule> {
+        AstModule::parse_file(Path::new(module), &eval::dialect())
     }
 }
 
@@ -293,9 +367,39 @@ fn main() -> anyhow::Result<()> {
                     )
                 }
                 ArgsDoc::Code => println!("{}", render_docs_as_code(&builtin)),
+                ArgsDoc::Json => println!("{}", render_docs_as_json(&builtin)),
+                ArgsDoc::JsonSchema => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&docs_json_schema()).unwrap()
+                ),
+                ArgsDoc::Html => {
+                    let Some(dir) = &args.docs_output else {
+                        return Err(anyhow::anyhow!("`--docs=html` requires `--docs-output`"));
+                    };
+                    fs::create_dir_all(dir)?;
+                    for file in render_docs_as_html(&builtin) {
+                        fs::write(dir.join(&file.path), file.contents)?;
+                    }
+                }
             };
+        } else if args.format {
+            for file in expand_dirs(ext, args.files) {
+                if format_file(&file)? {
+                    println!("Formatted {}", file.display());
+                }
+            }
+        } else if args.graph {
+            let roots: Vec<String> = expand_dirs(ext, args.files)
+                .map(|x| x.to_string_lossy().into_owned())
+                .collect();
+            let graph = ModuleGraph::build(&roots, &GraphLoader)?;
+            if args.json {
+                println!("{}", graph.to_json());
+            } else {
+                print!("{}", graph.to_dot());
+            }
         } else if is_interactive {
-            interactive(&ctx)?;
+            repl::repl(&ctx)?;
         } else {
             let mut stats = Stats::default();
             for e in args.evaluate.clone() {
@@ -305,7 +409,17 @@ fn main() -> anyhow::Result<()> {
 
             for file in expand_dirs(ext, args.files.clone()) {
                 stats.increment_file();
-                drain(ctx.file(&file).messages, args.json, &mut stats);
+                if args.fix {
+                    let fixed = ctx.fix_file(&file)?;
+                    if fixed > 0 && !args.json {
+                        println!("Fixed {} lints in {}", fixed, file.display());
+                    }
+                }
+                if args.doctest {
+                    drain(ctx.doctest_file(&file), args.json, &mut stats);
+                } else {
+                    drain(ctx.file(&file).messages, args.json, &mut stats);
+                }
             }
 
             if !args.json {
@@ -0,0 +1,386 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The interactive mode of the `starlark` binary.
+
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+
+use dupe::Dupe;
+use starlark::docs::get_registered_starlark_docs;
+use starlark::docs::Doc;
+use starlark::docs::DocItem;
+use starlark::docs::DocProperty;
+use starlark::docs::DocType;
+use starlark::docs::Identifier;
+use starlark::docs::MarkdownFlavor;
+use starlark::docs::RenderMarkdown;
+use starlark::environment::Globals;
+use starlark::environment::LibraryExtension;
+use starlark::environment::Module;
+use starlark::errors::Diagnostic;
+use starlark::read_line::ReadLine;
+use starlark::syntax::AstModule;
+use starlark::typing::OracleDocs;
+use starlark::typing::OracleStandard;
+use starlark::typing::Ty;
+use starlark::typing::TypingOracle;
+use starlark::values::Value;
+
+use crate::drain;
+use crate::eval::dialect;
+use crate::eval::globals;
+use crate::eval::Context;
+use crate::Stats;
+
+#[derive(Debug, thiserror::Error)]
+enum ReplError {
+    #[error("Unknown command `:{0}`, use `:help` to list the commands")]
+    UnknownCommand(String),
+    #[error("`:{0}` expects an argument, use `:help` for details")]
+    MissingArgument(&'static str),
+    #[error("The interactive mode requires a module")]
+    NoModule,
+}
+
+const COMMANDS: &[(&str, &str)] = &[
+    (
+        "type",
+        ":type EXPR    Show the type of an expression, without evaluating it",
+    ),
+    ("doc", ":doc NAME     Show the documentation of a value"),
+    (
+        "load",
+        ":load FILE    Evaluate a file, keeping its definitions",
+    ),
+    ("help", ":help         Show this message"),
+];
+
+/// Used to find the type of an expression, by assigning it to a variable
+/// which the typechecker reports on.
+const TYPE_RESULT: &str = "repl_type_result";
+
+/// History is kept in `$STARLARK_RUST_HISTFILE`, or `~/.starlark_rust_history` if that isn't set.
+fn history_file() -> Option<String> {
+    env::var("STARLARK_RUST_HISTFILE").ok().or_else(|| {
+        let home = env::var("HOME").ok()?;
+        Some(
+            Path::new(&home)
+                .join(".starlark_rust_history")
+                .to_string_lossy()
+                .into_owned(),
+        )
+    })
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The byte offset after the last character in `s` which doesn't satisfy `f`.
+fn start_of_suffix(s: &str, f: impl Fn(char) -> bool) -> usize {
+    s.char_indices()
+        .rev()
+        .find(|(_, c)| !f(*c))
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+/// Whether `code` is inside a triple-quoted string, in which case `e` is the error from parsing it.
+fn in_triple_quoted_string(code: &str, e: &anyhow::Error) -> bool {
+    e.to_string().contains("unfinished string literal")
+        && (code.matches("\"\"\"").count() % 2 == 1 || code.matches("'''").count() % 2 == 1)
+}
+
+/// Whether parsing `code` failed with error `e` because the input ended too early,
+/// e.g. inside brackets.
+fn ended_early(code: &str, e: &anyhow::Error) -> bool {
+    let message = e.to_string();
+    if !message.contains("unexpected end of file") && !message.contains("unexpected new line") {
+        return false;
+    }
+    let last_line = code.trim_end().lines().count().saturating_sub(1);
+    e.downcast_ref::<Diagnostic>()
+        .and_then(|d| d.span.as_ref())
+        .map_or(false, |span| span.resolve_span().begin_line >= last_line)
+}
+
+/// Whether `code` needs more lines: it is inside brackets or a triple-quoted string,
+/// or starts a block statement, which is only finished by an empty line.
+fn needs_more_input(code: &str) -> bool {
+    let err = AstModule::parse("repl", code.to_owned(), &dialect()).err();
+    if let Some(e) = &err {
+        // Empty lines are part of the string.
+        if in_triple_quoted_string(code, e) {
+            return true;
+        }
+    }
+    if code.contains('\n')
+        && code
+            .split('\n')
+            .last()
+            .map_or(true, |x| x.trim().is_empty())
+    {
+        return false;
+    }
+    let first = code.trim_start();
+    let keyword = &first[..first.len() - first.trim_start_matches(is_ident_char).len()];
+    if matches!(keyword, "def" | "if" | "for" | "while") {
+        return true;
+    }
+    err.map_or(false, |e| ended_early(code, &e))
+}
+
+/// The value of a possibly dotted name, like `x` or `x.y`, in the module.
+/// Unlike evaluating the name, this never runs any Starlark code.
+fn lookup<'v>(module: &'v Module, name: &str) -> Option<Value<'v>> {
+    let mut parts = name.split('.');
+    let mut value = module.get(parts.next()?)?;
+    for attr in parts {
+        value = value.get_attr(attr, module.heap()).ok()??;
+    }
+    Some(value)
+}
+
+/// A typing oracle which knows about the variables in the module and the standard library.
+fn oracle(module: &Module) -> Vec<Box<dyn TypingOracle>> {
+    let mut docs = OracleDocs::default();
+    for name in module.variable_names() {
+        if let Some(value) = module.get(name.as_str()) {
+            let item = value.documentation().unwrap_or_else(|| {
+                DocItem::Property(DocProperty {
+                    docs: None,
+                    typ: Some(DocType {
+                        raw_type: value.get_type().to_owned(),
+                    }),
+                })
+            });
+            docs.add_doc(&Doc {
+                id: Identifier {
+                    name: name.as_str().to_owned(),
+                    location: None,
+                },
+                item,
+                custom_attrs: HashMap::new(),
+            });
+        }
+    }
+    vec![
+        Box::new(docs),
+        Box::new(OracleStandard::new(LibraryExtension::all())),
+    ]
+}
+
+/// The type of an expression, according to the typechecker.
+fn type_of(module: &Module, expr: &str) -> anyhow::Result<Ty> {
+    let ast = AstModule::parse("repl", format!("{} = ({})", TYPE_RESULT, expr), &dialect())?;
+    let (errors, _, interface, _) = ast.typecheck(&oracle(module), &HashMap::new());
+    if let Some(e) = errors.into_iter().next() {
+        return Err(e);
+    }
+    Ok(interface.get(TYPE_RESULT).cloned().unwrap_or(Ty::Any))
+}
+
+/// The attributes of the object before the `.` at the end of `before`.
+fn attributes(module: &Module, before: &str) -> Vec<String> {
+    // For a dotted name, look at the value, that is accurate.
+    let name = &before[start_of_suffix(before, |c| is_ident_char(c) || c == '.')..];
+    if let Some(value) = lookup(module, name) {
+        return value.dir_attr();
+    }
+
+    // Otherwise ask the typechecker, e.g. for `"x".` or `f().`, then look up the type in the docs.
+    let expr = &before[start_of_suffix(before, |c| !c.is_whitespace() && !"(,=[{".contains(c))..];
+    let type_name = match type_of(module, expr) {
+        Ok(Ty::Name(x)) => x.as_str().to_owned(),
+        Ok(Ty::List(_)) => "list".to_owned(),
+        Ok(Ty::Dict(_)) => "dict".to_owned(),
+        Ok(Ty::Tuple(_)) => "tuple".to_owned(),
+        _ => return Vec::new(),
+    };
+    get_registered_starlark_docs()
+        .into_iter()
+        .find(|doc| doc.id.name == type_name)
+        .map_or_else(Vec::new, |doc| match doc.item {
+            DocItem::Object(x) => x.members.keys().cloned().collect(),
+            _ => Vec::new(),
+        })
+}
+
+/// Tab completion of commands, globals, and attributes after a `.`.
+fn complete(module: &Module, globals: &Globals, line: &str, pos: usize) -> (usize, Vec<String>) {
+    let line = &line[..pos];
+    let start = start_of_suffix(line, is_ident_char);
+    let (before, word) = line.split_at(start);
+    let mut candidates: Vec<String> = if before == ":" {
+        COMMANDS.iter().map(|(x, _)| (*x).to_owned()).collect()
+    } else if let Some(before) = before.strip_suffix('.') {
+        attributes(module, before)
+    } else {
+        module
+            .variable_names()
+            .into_iter()
+            .chain(globals.names())
+            .map(|x| x.as_str().to_owned())
+            .collect()
+    };
+    candidates.retain(|x| x.starts_with(word));
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+fn run_command(ctx: &Context, module: &Module, command: &str) -> anyhow::Result<()> {
+    let (command, arg) = command.split_once(' ').unwrap_or((command, ""));
+    let arg = arg.trim();
+    let name = COMMANDS
+        .iter()
+        .map(|(x, _)| *x)
+        .find(|x| *x == command)
+        .ok_or_else(|| ReplError::UnknownCommand(command.to_owned()))?;
+    if arg.is_empty() && name != "help" {
+        return Err(ReplError::MissingArgument(name).into());
+    }
+    match name {
+        "type" => println!("{}", type_of(module, arg)?),
+        "doc" => match lookup(module, arg).and_then(|value| value.documentation()) {
+            Some(item) => {
+                let doc = Doc {
+                    id: Identifier {
+                        name: arg.to_owned(),
+                        location: None,
+                    },
+                    item,
+                    custom_attrs: HashMap::new(),
+                };
+                println!("{}", doc.render_markdown(MarkdownFlavor::DocFile));
+            }
+            None => println!("No documentation for `{}`", arg),
+        },
+        "load" => {
+            let mut stats = Stats::default();
+            drain(ctx.file(Path::new(arg)).messages, false, &mut stats);
+        }
+        _ => {
+            for (_, help) in COMMANDS {
+                println!("{}", help);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read lines and evaluate them in the context's module, so definitions persist between lines.
+pub(crate) fn repl(ctx: &Context) -> anyhow::Result<()> {
+    let module = ctx.module.clone().ok_or(ReplError::NoModule)?;
+    let mut rl = ReadLine::with_history_file(history_file())?;
+    rl.set_completer({
+        let module = module.dupe();
+        let globals = globals();
+        move |line, pos| complete(&module, &globals, line, pos)
+    });
+    loop {
+        let Some(mut code) = rl.read_line("$> ")? else {
+            // User pressed EOF - disconnected terminal, or similar
+            return Ok(());
+        };
+        if let Some(command) = code.trim().strip_prefix(':') {
+            if let Err(e) = run_command(ctx, &module, command) {
+                eprintln!("{:#}", e);
+            }
+            continue;
+        }
+        while needs_more_input(&code) {
+            match rl.read_line("... ")? {
+                Some(line) => {
+                    code.push('\n');
+                    code.push_str(&line);
+                }
+                None => break,
+            }
+        }
+        let mut stats = Stats::default();
+        drain(ctx.expression(code).messages, false, &mut stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starlark::eval::Evaluator;
+
+    use super::*;
+
+    #[test]
+    fn test_block_continuation() {
+        assert!(needs_more_input("def f():"));
+        assert!(needs_more_input("def f():\n    return 1"));
+        assert!(needs_more_input("if True:\n    x = 1\nelse:"));
+        assert!(!needs_more_input("def f():\n    return 1\n"));
+        assert!(!needs_more_input("x = 1"));
+    }
+
+    #[test]
+    fn test_unfinished_brackets() {
+        assert!(needs_more_input("x = [1,"));
+        assert!(needs_more_input("f(1,\n2"));
+        assert!(needs_more_input("x = {\"a\": (1,"));
+        assert!(!needs_more_input("x = [1,\n2]"));
+        // A syntax error which more input can't fix.
+        assert!(!needs_more_input("x = ]"));
+    }
+
+    #[test]
+    fn test_triple_quoted_string() {
+        assert!(needs_more_input("s = \"\"\"abc"));
+        assert!(needs_more_input("s = \'\'\'abc"));
+        // Empty lines don't finish a string.
+        assert!(needs_more_input("s = \"\"\"abc\n"));
+        assert!(!needs_more_input("s = \"\"\"abc\n\ndef\"\"\""));
+        assert!(!needs_more_input("s = \"abc"));
+    }
+
+    #[test]
+    fn test_attribute_completion() -> anyhow::Result<()> {
+        let module = Module::new();
+        let globals = globals();
+        Evaluator::new(&module).eval_module(
+            AstModule::parse(
+                "repl",
+                "s = struct(alpha = 1, beta = struct(gamma = 2))".to_owned(),
+                &dialect(),
+            )?,
+            &globals,
+        )?;
+        assert_eq!(
+            (2, vec!["alpha".to_owned(), "beta".to_owned()]),
+            complete(&module, &globals, "s.", 2)
+        );
+        assert_eq!(
+            (2, vec!["alpha".to_owned()]),
+            complete(&module, &globals, "s.al", 4)
+        );
+        assert_eq!(
+            (9, vec!["gamma".to_owned()]),
+            complete(&module, &globals, "f(s.beta.", 9)
+        );
+        assert_eq!(
+            (1, vec!["type".to_owned()]),
+            complete(&module, &globals, ":ty", 3)
+        );
+        Ok(())
+    }
+}
@@ -302,6 +302,12 @@ fn stmt(x: &AstStmt, res: &mut Vec<Bind>) {
             stmt(body, res);
             flow(res)
         }
+        Stmt::While(cond, body) => {
+            expr(cond, res);
+            flow(res);
+            stmt(body, res);
+            flow(res)
+        }
         Stmt::Load(load) => {
             for x in &load.args {
                 res.push(Bind::Set(
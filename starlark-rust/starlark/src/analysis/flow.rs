@@ -260,6 +260,7 @@ fn redundant(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) {
                 let (_over, body) = &**over_body;
                 check(true, codemap, body, res)
             }
+            Stmt::While(_, body) => check(true, codemap, body, res),
             Stmt::Def(DefP { body, .. }) => check(false, codemap, body, res),
             _ => {}
         }
@@ -0,0 +1,568 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Symbol-level dependency graph of a set of modules, following their `load()` statements.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::collections::SmallMap;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::Expr;
+use crate::syntax::AstModule;
+
+/// Finds and parses the modules of a [`ModuleGraph`].
+pub trait ModuleGraphLoader {
+    /// Resolve the path of a `load()` in the module `current` to the name of the loaded module.
+    fn resolve_load(&self, path: &str, current: &str) -> anyhow::Result<String>;
+
+    /// Parse the module with the given name, as returned by [`resolve_load`](ModuleGraphLoader::resolve_load).
+    fn parse(&self, module: &str) -> anyhow::Result<AstModule>;
+}
+
+/// A symbol loaded by one module from another.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoadedSymbol {
+    /// The name exported by the loaded module.
+    pub name: String,
+    /// The name it is bound to in the loading module.
+    pub local: String,
+    /// Number of times the symbol is referenced in the loading module.
+    pub uses: usize,
+}
+
+/// A `load()` of one module by another.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModuleGraphEdge {
+    /// The loading module.
+    pub from: String,
+    /// The loaded module.
+    pub to: String,
+    /// The symbols loaded.
+    pub symbols: Vec<LoadedSymbol>,
+}
+
+/// A module in a [`ModuleGraph`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ModuleGraphNode {
+    /// Whether the module is one of the roots the graph was built from.
+    pub root: bool,
+    /// The symbols exported by the module, see [`AstModule::exported_symbols`].
+    pub exports: Vec<String>,
+    /// Number of distinct modules which load this one.
+    pub fan_in: usize,
+    /// Why the module couldn't be parsed, in which case it has no exports or loads.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The modules reachable from a set of roots, and the symbols they load from each other.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ModuleGraph {
+    /// The modules, in the order they were discovered.
+    pub modules: SmallMap<String, ModuleGraphNode>,
+    /// The `load()` edges, in the order they were discovered.
+    pub edges: Vec<ModuleGraphEdge>,
+}
+
+/// Summary of the problems found in a [`ModuleGraph`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ModuleGraphReport {
+    /// Exported symbols (as module and name) which no module in the graph loads.
+    pub unused_exports: Vec<(String, String)>,
+    /// Groups of modules which load each other, directly or indirectly.
+    pub cycles: Vec<Vec<String>>,
+    /// The number of modules loading each module, largest first.
+    pub fan_in: Vec<(String, usize)>,
+}
+
+/// Quote a string for the DOT language, where only `"` and `\` need escaping.
+/// Line breaks are written as `\n`, which Graphviz renders as a line break in labels.
+fn dot_string(x: &str) -> String {
+    let mut res = String::with_capacity(x.len() + 2);
+    res.push('"');
+    dot_escape(x, &mut res);
+    res.push('"');
+    res
+}
+
+fn dot_escape(x: &str, res: &mut String) {
+    for c in x.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => {}
+            c => res.push(c),
+        }
+    }
+}
+
+/// Count the references to each of `names` in the module.
+/// Shadowing by local variables is ignored, so the counts are an upper bound.
+fn count_uses(ast: &AstModule, names: &HashSet<&str>) -> HashMap<String, usize> {
+    fn visit(x: &AstExpr, names: &HashSet<&str>, res: &mut HashMap<String, usize>) {
+        if let Expr::Identifier(name, _) = &x.node {
+            if names.contains(name.node.as_str()) {
+                *res.entry(name.node.clone()).or_default() += 1;
+            }
+        }
+        x.visit_expr(|x| visit(x, names, res));
+    }
+
+    let mut res = HashMap::new();
+    ast.statement.visit_expr(|x| visit(x, names, &mut res));
+    res
+}
+
+impl ModuleGraph {
+    /// Build the graph of the modules reachable from `roots` by following `load()` statements.
+    ///
+    /// Modules which fail to parse are recorded with an error rather than failing the whole graph,
+    /// but failing to resolve a `load()` is an error.
+    pub fn build(roots: &[String], loader: &dyn ModuleGraphLoader) -> anyhow::Result<ModuleGraph> {
+        let mut graph = ModuleGraph::default();
+        let mut todo: VecDeque<String> = VecDeque::new();
+        for root in roots {
+            if !graph.modules.contains_key(root) {
+                graph.modules.insert(
+                    root.clone(),
+                    ModuleGraphNode {
+                        root: true,
+                        ..ModuleGraphNode::default()
+                    },
+                );
+                todo.push_back(root.clone());
+            }
+        }
+        while let Some(module) = todo.pop_front() {
+            let ast = match loader.parse(&module) {
+                Ok(ast) => ast,
+                Err(e) => {
+                    graph.modules.get_mut(&module).unwrap().error = Some(format!("{:#}", e));
+                    continue;
+                }
+            };
+            graph.modules.get_mut(&module).unwrap().exports = ast
+                .exported_symbols()
+                .into_iter()
+                .map(|(_, name)| name.to_owned())
+                .collect();
+
+            let loads = ast.loads();
+            let locals: HashSet<&str> = loads
+                .iter()
+                .flat_map(|x| x.symbols.keys().copied())
+                .collect();
+            let uses = count_uses(&ast, &locals);
+            for load in loads {
+                let to = loader.resolve_load(load.module_id, &module)?;
+                if !graph.modules.contains_key(&to) {
+                    graph.modules.insert(to.clone(), ModuleGraphNode::default());
+                    todo.push_back(to.clone());
+                }
+                graph.edges.push(ModuleGraphEdge {
+                    from: module.clone(),
+                    to,
+                    symbols: load
+                        .symbols
+                        .iter()
+                        .map(|(local, name)| LoadedSymbol {
+                            name: (*name).to_owned(),
+                            local: (*local).to_owned(),
+                            uses: uses.get(*local).copied().unwrap_or_default(),
+                        })
+                        .collect(),
+                });
+            }
+        }
+
+        let mut loaded_by: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for edge in &graph.edges {
+            loaded_by
+                .entry(edge.to.as_str())
+                .or_default()
+                .insert(edge.from.as_str());
+        }
+        let fan_in: Vec<(String, usize)> = loaded_by
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v.len()))
+            .collect();
+        for (module, count) in fan_in {
+            graph.modules.get_mut(&module).unwrap().fan_in = count;
+        }
+        Ok(graph)
+    }
+
+    /// Groups of modules which load each other, using Tarjan's strongly connected components
+    /// algorithm. Each group is in discovery order, and includes a module which loads itself.
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        struct Tarjan<'a> {
+            graph: &'a ModuleGraph,
+            successors: Vec<Vec<usize>>,
+            index: Vec<Option<usize>>,
+            lowlink: Vec<usize>,
+            on_stack: Vec<bool>,
+            stack: Vec<usize>,
+            next: usize,
+            res: Vec<Vec<String>>,
+        }
+
+        impl Tarjan<'_> {
+            fn visit(&mut self, v: usize) {
+                self.index[v] = Some(self.next);
+                self.lowlink[v] = self.next;
+                self.next += 1;
+                self.stack.push(v);
+                self.on_stack[v] = true;
+                for i in 0..self.successors[v].len() {
+                    let w = self.successors[v][i];
+                    match self.index[w] {
+                        None => {
+                            self.visit(w);
+                            self.lowlink[v] = self.lowlink[v].min(self.lowlink[w]);
+                        }
+                        Some(index) if self.on_stack[w] => {
+                            self.lowlink[v] = self.lowlink[v].min(index);
+                        }
+                        Some(_) => {}
+                    }
+                }
+                if Some(self.lowlink[v]) == self.index[v] {
+                    let mut component = Vec::new();
+                    loop {
+                        let w = self.stack.pop().unwrap();
+                        self.on_stack[w] = false;
+                        component.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 || self.successors[v].contains(&v) {
+                        component.sort_unstable();
+                        self.res.push(
+                            component
+                                .into_iter()
+                                .map(|x| self.graph.modules.get_index(x).unwrap().0.clone())
+                                .collect(),
+                        );
+                    }
+                }
+            }
+        }
+
+        let n = self.modules.len();
+        let mut successors = vec![Vec::new(); n];
+        for edge in &self.edges {
+            let from = self.modules.get_index_of(&edge.from).unwrap();
+            let to = self.modules.get_index_of(&edge.to).unwrap();
+            successors[from].push(to);
+        }
+        let mut tarjan = Tarjan {
+            graph: self,
+            successors,
+            index: vec![None; n],
+            lowlink: vec![0; n],
+            on_stack: vec![false; n],
+            stack: Vec::new(),
+            next: 0,
+            res: Vec::new(),
+        };
+        for v in 0..n {
+            if tarjan.index[v].is_none() {
+                tarjan.visit(v);
+            }
+        }
+        tarjan.res.sort();
+        tarjan.res
+    }
+
+    /// Exported symbols, as module and name, which no module in the graph loads.
+    /// The roots are loaded from outside the graph, so their exports are never reported,
+    /// and modules which failed to parse have no exports.
+    pub fn unused_exports(&self) -> Vec<(String, String)> {
+        let loaded: HashSet<(&str, &str)> = self
+            .edges
+            .iter()
+            .flat_map(|e| e.symbols.iter().map(|s| (e.to.as_str(), s.name.as_str())))
+            .collect();
+        self.modules
+            .iter()
+            .filter(|(_, node)| !node.root)
+            .flat_map(|(module, node)| node.exports.iter().map(move |name| (module, name)))
+            .filter(|(module, name)| !loaded.contains(&(module.as_str(), name.as_str())))
+            .map(|(module, name)| (module.clone(), name.clone()))
+            .collect()
+    }
+
+    /// The unused exports, cycles and fan-in of the graph.
+    pub fn report(&self) -> ModuleGraphReport {
+        let mut fan_in: Vec<(String, usize)> = self
+            .modules
+            .iter()
+            .filter(|(_, node)| node.fan_in > 0)
+            .map(|(module, node)| (module.clone(), node.fan_in))
+            .collect();
+        fan_in.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ModuleGraphReport {
+            unused_exports: self.unused_exports(),
+            cycles: self.cycles(),
+            fan_in,
+        }
+    }
+
+    /// Render the graph in the Graphviz DOT language. Edges are labelled with the symbols loaded,
+    /// nodes with their fan-in, and edges within a cycle are coloured red.
+    pub fn to_dot(&self) -> String {
+        let in_cycle: HashMap<String, usize> = self
+            .cycles()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, xs)| xs.into_iter().map(move |x| (x, i)))
+            .collect();
+        let mut res = String::new();
+        writeln!(res, "digraph {{").unwrap();
+        for (module, node) in &self.modules {
+            // Escape the module name only, so the `\n`s we add remain line breaks.
+            let mut label = String::from("\"");
+            dot_escape(module, &mut label);
+            write!(label, "\\nfan-in: {}", node.fan_in).unwrap();
+            if node.error.is_some() {
+                label.push_str("\\n(error)");
+            }
+            label.push('"');
+            writeln!(res, "  {} [label={}];", dot_string(module), label).unwrap();
+        }
+        for edge in &self.edges {
+            let label = edge
+                .symbols
+                .iter()
+                .map(|x| x.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let cycle = match (in_cycle.get(&edge.from), in_cycle.get(&edge.to)) {
+                (Some(a), Some(b)) if a == b => ", color=red",
+                _ => "",
+            };
+            writeln!(
+                res,
+                "  {} -> {} [label={}{}];",
+                dot_string(&edge.from),
+                dot_string(&edge.to),
+                dot_string(&label),
+                cycle
+            )
+            .unwrap();
+        }
+        for (module, name) in self.unused_exports() {
+            writeln!(
+                res,
+                "  // unused export: {} {}",
+                dot_string(&module),
+                dot_string(&name)
+            )
+            .unwrap();
+        }
+        writeln!(res, "}}").unwrap();
+        res
+    }
+
+    /// Render the graph and its report as JSON.
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            modules: &'a SmallMap<String, ModuleGraphNode>,
+            edges: &'a [ModuleGraphEdge],
+            report: ModuleGraphReport,
+        }
+
+        serde_json::to_string_pretty(&Json {
+            modules: &self.modules,
+            edges: &self.edges,
+            report: self.report(),
+        })
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::Dialect;
+
+    struct Loader(HashMap<&'static str, &'static str>);
+
+    impl ModuleGraphLoader for Loader {
+        fn resolve_load(&self, path: &str, _current: &str) -> anyhow::Result<String> {
+            Ok(path.trim_start_matches(':').to_owned())
+        }
+
+        fn parse(&self, module: &str) -> anyhow::Result<AstModule> {
+            let content = self
+                .0
+                .get(module)
+                .ok_or_else(|| anyhow::anyhow!("No module `{}`", module))?;
+            AstModule::parse(module, (*content).to_owned(), &Dialect::Extended)
+        }
+    }
+
+    fn graph(modules: &[(&'static str, &'static str)]) -> ModuleGraph {
+        let loader = Loader(modules.iter().copied().collect());
+        ModuleGraph::build(&[modules[0].0.to_owned()], &loader).unwrap()
+    }
+
+    #[test]
+    fn test_module_graph() {
+        let graph = graph(&[
+            (
+                "root.bzl",
+                r#"
+load(":a.bzl", "f", g = "g")
+load(":b.bzl", "h")
+f(g(h), g)
+"#,
+            ),
+            (
+                "a.bzl",
+                r#"
+load(":b.bzl", "h")
+def f(x): return h
+def g(x): return x
+unused = 1
+_private = 2
+"#,
+            ),
+            ("b.bzl", "def h(): pass\n"),
+        ]);
+        assert_eq!(
+            vec!["root.bzl", "a.bzl", "b.bzl"],
+            graph.modules.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(3, graph.edges.len());
+        let uses: Vec<_> = graph.edges[0]
+            .symbols
+            .iter()
+            .map(|x| (x.name.as_str(), x.uses))
+            .collect();
+        assert_eq!(vec![("f", 1), ("g", 2)], uses);
+
+        let report = graph.report();
+        assert_eq!(
+            vec![("a.bzl".to_owned(), "unused".to_owned())],
+            report.unused_exports
+        );
+        assert!(report.cycles.is_empty());
+        assert_eq!(
+            vec![("b.bzl".to_owned(), 2), ("a.bzl".to_owned(), 1)],
+            report.fan_in
+        );
+
+        let dot = graph.to_dot();
+        assert!(
+            dot.contains(r#"  "root.bzl" -> "a.bzl" [label="f, g"];"#),
+            "{}",
+            dot
+        );
+        assert!(
+            dot.contains(r#"// unused export: "a.bzl" "unused""#),
+            "{}",
+            dot
+        );
+    }
+
+    #[test]
+    fn test_module_graph_cycles() {
+        let graph = graph(&[
+            ("a.bzl", "load(':b.bzl', 'y')\nx = y\n"),
+            ("b.bzl", "load(':c.bzl', 'z')\ny = z\n"),
+            ("c.bzl", "load(':b.bzl', 'y')\nz = 1\n"),
+        ]);
+        assert_eq!(
+            vec![vec!["b.bzl".to_owned(), "c.bzl".to_owned()]],
+            graph.cycles()
+        );
+        assert!(
+            graph
+                .to_dot()
+                .contains(r#""c.bzl" -> "b.bzl" [label="y", color=red];"#)
+        );
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json()).unwrap();
+        assert_eq!(
+            serde_json::json!([["b.bzl", "c.bzl"]]),
+            json["report"]["cycles"]
+        );
+    }
+
+    #[test]
+    fn test_module_graph_parse_error() {
+        let graph = graph(&[("a.bzl", "load(':missing.bzl', 'y')\nx = y\n")]);
+        assert!(
+            graph
+                .modules
+                .get("missing.bzl")
+                .unwrap()
+                .error
+                .as_ref()
+                .unwrap()
+                .contains("No module")
+        );
+        // The exports of the root are used from outside the graph.
+        assert!(graph.unused_exports().is_empty());
+    }
+
+    #[test]
+    fn test_module_graph_unused_exports_of_loaded_roots() {
+        let loader = Loader(
+            [
+                ("a.bzl", "load(':b.bzl', 'y')\nx = y\n"),
+                ("b.bzl", "y = 1\nz = 2\n"),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let graph = ModuleGraph::build(&["a.bzl".to_owned(), "b.bzl".to_owned()], &loader).unwrap();
+        assert!(graph.unused_exports().is_empty());
+        let graph = ModuleGraph::build(&["a.bzl".to_owned()], &loader).unwrap();
+        assert_eq!(
+            vec![("b.bzl".to_owned(), "z".to_owned())],
+            graph.unused_exports()
+        );
+    }
+
+    #[test]
+    fn test_module_graph_dot_escaping() {
+        let graph = graph(&[
+            ("a\"\\.bzl", "load(':b\\\\n.bzl', 'y')\nx = y\n"),
+            ("b\\n.bzl", "y = 1\n"),
+        ]);
+        let dot = graph.to_dot();
+        assert!(
+            dot.contains(r#"  "a\"\\.bzl" [label="a\"\\.bzl\nfan-in: 0"];"#),
+            "{}",
+            dot
+        );
+        assert!(
+            dot.contains(r#"  "a\"\\.bzl" -> "b\\n.bzl" [label="y"];"#),
+            "{}",
+            dot
+        );
+    }
+}
@@ -98,14 +98,20 @@ fn match_bad_type_equality(
             if (*op == BinOp::Equal || *op == BinOp::NotEqual) && is_type_call(lhs) =>
         {
             if let Some(replacement) = lookup_type(rhs, types) {
-                res.push(LintT::new(
-                    codemap,
-                    x.span,
-                    Incompatibility::IncompatibleTypeCheck(
-                        x.to_string(),
-                        format!("{}{}type({})", lhs.node, op, replacement),
-                    ),
-                ))
+                let fixed = format!(
+                    "{}{}type({})",
+                    codemap.source_span(lhs.span),
+                    op,
+                    replacement
+                );
+                res.push(
+                    LintT::new(
+                        codemap,
+                        x.span,
+                        Incompatibility::IncompatibleTypeCheck(x.to_string(), fixed.clone()),
+                    )
+                    .with_fix(format!("Replace with `{}`", fixed), vec![(x.span, fixed)]),
+                )
             }
         }
         _ => {}
@@ -206,6 +212,7 @@ pub(crate) fn lint(module: &AstModule) -> Vec<LintT<Incompatibility>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::analysis::types::LintFix;
     use crate::slice_vec_ext::SliceExt;
     use crate::syntax::Dialect;
 
@@ -234,6 +241,19 @@ def foo():
         );
     }
 
+    #[test]
+    fn test_lint_incompatible_fix() {
+        let program = "x = type(y) == str or type(z[0]) != list\n";
+        let mut res = Vec::new();
+        bad_type_equality(&module(program), &mut res);
+        let (fixed, count) = LintFix::apply_all(program, res.iter().filter_map(|x| x.fix.as_ref()));
+        assert_eq!(count, 2);
+        assert_eq!(
+            fixed,
+            "x = type(y) == type(\"\") or type(z[0]) != type([])\n"
+        );
+    }
+
     #[test]
     fn test_lint_duplicate_top_level_assign() {
         let m = module(
@@ -17,9 +17,16 @@
 
 use std::collections::HashSet;
 
+pub use graph::LoadedSymbol;
+pub use graph::ModuleGraph;
+pub use graph::ModuleGraphEdge;
+pub use graph::ModuleGraphLoader;
+pub use graph::ModuleGraphNode;
+pub use graph::ModuleGraphReport;
 pub use types::EvalMessage;
 pub use types::EvalSeverity;
 pub use types::Lint;
+pub use types::LintFix;
 
 use crate::analysis::types::LintT;
 use crate::syntax::AstModule;
@@ -30,9 +37,11 @@ mod dubious;
 mod exported;
 mod find_call_name;
 mod flow;
+mod graph;
 mod incompatible;
 mod names;
 mod performance;
+pub(crate) mod references;
 mod types;
 mod underscore;
 
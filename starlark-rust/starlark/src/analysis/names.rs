@@ -24,10 +24,13 @@ use crate::analysis::bind;
 use crate::analysis::bind::Assigner;
 use crate::analysis::bind::Bind;
 use crate::analysis::bind::Scope;
+use crate::analysis::types::LintFix;
 use crate::analysis::types::LintT;
 use crate::analysis::types::LintWarning;
 use crate::codemap::CodeMap;
+use crate::codemap::Pos;
 use crate::codemap::Span;
+use crate::syntax::ast::Stmt;
 use crate::syntax::AstModule;
 
 #[derive(Error, Debug)]
@@ -89,9 +92,95 @@ pub(crate) fn lint(
     if let Some(globals) = globals {
         undefined_variable(&module.codemap, &scope, globals, &mut res);
     }
+    fix_unused_loads(module, &mut res);
     res
 }
 
+/// Extend `span` to the whole lines it is on, provided there is nothing else on them.
+fn whole_lines(codemap: &CodeMap, span: Span) -> Option<Span> {
+    let source = codemap.source();
+    let begin = span.begin().get() as usize;
+    let end = span.end().get() as usize;
+    let line_begin = source[..begin].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[end..]
+        .find('\n')
+        .map_or(source.len(), |i| end + i + 1);
+    if source[line_begin..begin].trim().is_empty() && source[end..line_end].trim().is_empty() {
+        Some(Span::new(
+            Pos::new(line_begin as u32),
+            Pos::new(line_end as u32),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Attach fixes to the unused load warnings. The fixes for a single `load` are computed
+/// together, so that applying all of them removes the statement if nothing it loads is used.
+fn fix_unused_loads(module: &AstModule, res: &mut [LintT<NameWarning>]) {
+    let unused: HashSet<Span> = res
+        .iter()
+        .filter(|x| matches!(x.problem, NameWarning::UnusedLoad(..)))
+        .map(|x| x.location.span)
+        .collect();
+    if unused.is_empty() {
+        return;
+    }
+
+    // From the span of the local name to the edit removing it.
+    let mut edits: HashMap<Span, Span> = HashMap::new();
+    for stmt in module.top_level_statements() {
+        let load = match &**stmt {
+            Stmt::Load(load) => load,
+            _ => continue,
+        };
+        let spans: Vec<Span> = load
+            .args
+            .iter()
+            .map(|(local, their)| local.span.merge(their.span))
+            .collect();
+        let is_unused: Vec<bool> = load
+            .args
+            .iter()
+            .map(|(local, _)| unused.contains(&local.span))
+            .collect();
+        match is_unused.iter().rposition(|x| !x) {
+            None => {
+                if let Some(remove) = whole_lines(&module.codemap, stmt.span) {
+                    for (local, _) in &load.args {
+                        edits.insert(local.span, remove);
+                    }
+                }
+            }
+            Some(last_used) => {
+                for (i, (local, _)) in load.args.iter().enumerate() {
+                    if !is_unused[i] {
+                        continue;
+                    }
+                    // Take the comma after the symbol, or before it if nothing used follows.
+                    let remove = if i < last_used {
+                        Span::new(spans[i].begin(), spans[i + 1].begin())
+                    } else {
+                        Span::new(spans[i - 1].end(), spans[i].end())
+                    };
+                    edits.insert(local.span, remove);
+                }
+            }
+        }
+    }
+
+    for x in res {
+        if let NameWarning::UnusedLoad(name) = &x.problem {
+            if let Some(remove) = edits.get(&x.location.span) {
+                x.fix = Some(LintFix {
+                    description: format!("Remove unused `load` of `{}`", name),
+                    edits: vec![(*remove, String::new())],
+                });
+            }
+        }
+    }
+}
+
 fn undefined_variable(
     codemap: &CodeMap,
     scope: &Scope,
@@ -361,4 +450,27 @@ def bar(ctx):
         res.sort();
         assert_eq!(res, &["no1", "no2"])
     }
+
+    #[test]
+    fn test_lint_unused_load_fix() {
+        let program = r#"
+load("a.star", "x", "no1")
+load("b.star", "no2", y = "b_y")
+load("c.star", "no3", "z", "no4", "no5")
+load("d.star", "no6", "no7")
+print(x, y, z)
+"#;
+        let res = lint(&module(program), None);
+        let (fixed, count) = LintFix::apply_all(program, res.iter().filter_map(|x| x.fix.as_ref()));
+        assert_eq!(count, 7);
+        assert_eq!(
+            fixed,
+            r#"
+load("a.star", "x")
+load("b.star", y = "b_y")
+load("c.star", "z")
+print(x, y, z)
+"#
+        );
+    }
 }
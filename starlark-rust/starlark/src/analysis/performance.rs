@@ -58,11 +58,21 @@ fn match_dict_copy(codemap: &CodeMap, x: &AstExpr, res: &mut Vec<LintT<Performan
     match &**x {
         Expr::Call(fun, args) if args.len() == 1 => match (&***fun, &*args[0]) {
             (Expr::Identifier(f, _), Argument::KwArgs(arg)) if f.node == "dict" => {
-                res.push(LintT::new(
-                    codemap,
-                    x.span,
-                    Performance::DictWithoutStarStar(x.to_string(), format!("dict({})", arg.node)),
-                ))
+                let arg_source = codemap.source_span(arg.span);
+                res.push(
+                    LintT::new(
+                        codemap,
+                        x.span,
+                        Performance::DictWithoutStarStar(
+                            x.to_string(),
+                            format!("dict({})", arg.node),
+                        ),
+                    )
+                    .with_fix(
+                        format!("Replace with `dict({})`", arg_source),
+                        vec![(args[0].span, arg_source.to_owned())],
+                    ),
+                )
             }
             _ => {}
         },
@@ -85,18 +95,33 @@ fn match_inefficient_bool_check(codemap: &CodeMap, x: &AstExpr, res: &mut Vec<Li
                             Performance::EagerAndInefficientBoolCheck(f.node.clone()),
                         )),
                     // any(list(_get_some_dict()))
-                    Expr::Call(any_call, _) => match &***any_call {
+                    Expr::Call(any_call, any_args) => match &***any_call {
                         Expr::Identifier(any_id, _)
                             if any_id.node == "dict" || any_id.node == "list" =>
                         {
-                            res.push(LintT::new(
+                            let lint = LintT::new(
                                 codemap,
                                 x.span,
                                 Performance::InefficientBoolCheck(
                                     x.to_string(),
                                     any_id.node.clone(),
                                 ),
-                            ))
+                            );
+                            // Iterating over `list(xs)` is the same as iterating over `xs`,
+                            // but `dict(xs)` iterates over the keys, so can't be dropped.
+                            res.push(match any_args.as_slice() {
+                                [inner] if any_id.node == "list" => match &inner.node {
+                                    Argument::Positional(inner) => {
+                                        let inner_source = codemap.source_span(inner.span);
+                                        lint.with_fix(
+                                            format!("Replace with `{}({})`", f.node, inner_source),
+                                            vec![(arg.span, inner_source.to_owned())],
+                                        )
+                                    }
+                                    _ => lint,
+                                },
+                                _ => lint,
+                            })
                         }
                         _ => {}
                     },
@@ -129,6 +154,7 @@ pub(crate) fn lint(module: &AstModule) -> Vec<LintT<Performance>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::analysis::types::LintFix;
     use crate::slice_vec_ext::SliceExt;
     use crate::syntax::Dialect;
 
@@ -185,4 +211,23 @@ def foo(items):
             ]
         );
     }
+
+    #[test]
+    fn test_lint_fixes() {
+        let program = r#"
+def foo(xs, **kwargs):
+    return (dict(**kwargs), any(list(xs)), all(dict(xs)), any([x for x in xs]))
+"#;
+        let mut res = Vec::new();
+        check_call_expr(&module(program), &mut res);
+        let (fixed, count) = LintFix::apply_all(program, res.iter().filter_map(|x| x.fix.as_ref()));
+        assert_eq!(count, 2);
+        assert_eq!(
+            fixed,
+            r#"
+def foo(xs, **kwargs):
+    return (dict(kwargs), any(xs), all(dict(xs)), any([x for x in xs]))
+"#
+        );
+    }
 }
@@ -0,0 +1,478 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Find all of the places a symbol is used within a module, for find-references and rename.
+
+use crate::analysis::bind::scope;
+use crate::analysis::bind::Assigner;
+use crate::analysis::bind::Bind;
+use crate::analysis::bind::Scope;
+use crate::analysis::definition::LspModule;
+use crate::codemap::ResolvedSpan;
+use crate::codemap::Span;
+use crate::syntax::ast::StmtP;
+
+/// Where the symbol that a name refers to is bound.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum SymbolBinding {
+    /// Bound in this module, by an assignment, `def`, parameter, etc. `exported` is set for
+    /// public top level symbols, which other modules may load.
+    Local { span: Span, exported: bool },
+    /// Bound by a `load()`. `span` is the local name in the `load()`, `path` and `name` are
+    /// the module and the name of the symbol within that module.
+    Loaded {
+        span: Span,
+        path: String,
+        name: String,
+    },
+    /// Not bound anywhere in this module, e.g. a builtin.
+    Global,
+}
+
+/// The symbol at a given position in a module.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct SymbolAtPosition {
+    /// The name the symbol has within this module.
+    pub(crate) name: String,
+    /// The location of the name that contained the position.
+    pub(crate) source: ResolvedSpan,
+    pub(crate) binding: SymbolBinding,
+}
+
+/// How a symbol is referred to at some location.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum ReferenceKind {
+    /// The name of the symbol where it is first bound, e.g. the name of a `def`.
+    Definition,
+    /// The name of the symbol, where it is used or bound again.
+    Identifier,
+    /// The string naming the symbol in a `load()`, including the quotes.
+    LoadedName { quote: char },
+    /// A use of the symbol under a different name, because it was loaded with an alias
+    /// (e.g. `load("foo.star", bar = "baz")`).
+    Alias,
+}
+
+/// A single place a symbol is referred to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct Reference {
+    pub(crate) span: ResolvedSpan,
+    pub(crate) kind: ReferenceKind,
+}
+
+/// The place a name is bound, as seen from one of the scopes that can access it.
+#[derive(Debug, Clone, Copy)]
+struct Binding<'a> {
+    assigner: &'a Assigner,
+    span: Span,
+    top_level: bool,
+}
+
+/// Call `f` for every binding or use of a name in the module, along with where that name
+/// is bound (if it is bound at all).
+fn for_each_name<'a>(scope: &'a Scope, f: &mut dyn FnMut(&'a str, Span, Option<Binding<'a>>)) {
+    fn resolve<'a>(stack: &[&'a Scope], name: &str) -> Option<Binding<'a>> {
+        stack.iter().enumerate().rev().find_map(|(depth, scope)| {
+            scope.bound.get(name).map(|(assigner, span)| Binding {
+                assigner,
+                span: *span,
+                top_level: depth == 0,
+            })
+        })
+    }
+
+    fn walk<'a>(
+        scope: &'a Scope,
+        stack: &mut Vec<&'a Scope>,
+        f: &mut dyn FnMut(&'a str, Span, Option<Binding<'a>>),
+    ) {
+        stack.push(scope);
+        for bind in &scope.inner {
+            match bind {
+                Bind::Set(_, x) => f(&x.0, x.span, resolve(stack, &x.0)),
+                Bind::Get(x) => f(&x.node, x.span, resolve(stack, &x.node)),
+                Bind::GetDotted(x) => f(
+                    &x.variable.node,
+                    x.variable.span,
+                    resolve(stack, &x.variable.node),
+                ),
+                Bind::Scope(inner) => walk(inner, stack, f),
+                Bind::Flow => {}
+            }
+        }
+        stack.pop();
+    }
+
+    walk(scope, &mut Vec::new(), f)
+}
+
+impl LspModule {
+    fn symbol_binding(name: &str, binding: Option<Binding>) -> SymbolBinding {
+        match binding {
+            None => SymbolBinding::Global,
+            Some(Binding {
+                assigner: Assigner::Load { path, name },
+                span,
+                ..
+            }) => SymbolBinding::Loaded {
+                span,
+                path: path.node.clone(),
+                name: name.node.clone(),
+            },
+            Some(Binding {
+                assigner,
+                span,
+                top_level,
+            }) => SymbolBinding::Local {
+                span,
+                exported: top_level && *assigner == Assigner::Assign && !name.starts_with('_'),
+            },
+        }
+    }
+
+    /// Find the symbol at `line`/`col` (zero based), and where it is bound. Unlike
+    /// [`LspModule::find_definition`] this also finds symbols where they are bound,
+    /// e.g. the name of a `def`, or the names in a `load()`.
+    pub(crate) fn find_symbol_at(&self, line: u32, col: u32) -> Option<SymbolAtPosition> {
+        let line_span = self.ast.codemap.line_span_opt(line as usize)?;
+        let pos = std::cmp::min(line_span.begin() + col, line_span.end());
+
+        // The symbol names in a `load()` are strings, so are not part of the bindings.
+        for stmt in self.ast.top_level_statements() {
+            if let StmtP::Load(load) = &stmt.node {
+                for (local, their) in &load.args {
+                    let source = if local.span.contains(pos) {
+                        local.span
+                    } else if their.span.contains(pos) {
+                        their.span
+                    } else {
+                        continue;
+                    };
+                    return Some(SymbolAtPosition {
+                        name: local.0.clone(),
+                        source: self.ast.codemap.resolve_span(source),
+                        binding: SymbolBinding::Loaded {
+                            span: local.span,
+                            path: load.module.node.clone(),
+                            name: their.node.clone(),
+                        },
+                    });
+                }
+            }
+        }
+
+        let scope = scope(&self.ast);
+        let mut found = None;
+        for_each_name(&scope, &mut |name, span, binding| {
+            if found.is_none() && span.contains(pos) {
+                found = Some(SymbolAtPosition {
+                    name: name.to_owned(),
+                    source: self.ast.codemap.resolve_span(span),
+                    binding: Self::symbol_binding(name, binding),
+                });
+            }
+        });
+        found
+    }
+
+    /// Find every binding and use of `name` in this module that refers to `binding`.
+    /// For symbols loaded without an alias this includes the string in the `load()`.
+    pub(crate) fn find_references(&self, name: &str, binding: &SymbolBinding) -> Vec<Reference> {
+        let binding_span = match binding {
+            SymbolBinding::Local { span, .. } | SymbolBinding::Loaded { span, .. } => Some(*span),
+            SymbolBinding::Global => None,
+        };
+        let scope = scope(&self.ast);
+        let mut spans = Vec::new();
+        for_each_name(&scope, &mut |n, span, b| {
+            if n == name && b.map(|b| b.span) == binding_span {
+                spans.push(span);
+            }
+        });
+        // `x += 1` both uses and binds `x`, at the same location.
+        spans.sort_by_key(|span| (span.begin(), span.end()));
+        spans.dedup();
+        spans
+            .into_iter()
+            .map(|span| {
+                let kind = if Some(span) == binding_span {
+                    ReferenceKind::Definition
+                } else {
+                    ReferenceKind::Identifier
+                };
+                self.reference(span, kind)
+            })
+            .collect()
+    }
+
+    /// Find every reference to the public top level symbol `name` of this module.
+    pub(crate) fn find_references_to_exported(&self, name: &str) -> Vec<Reference> {
+        match self.find_exported_symbol_span(name) {
+            Some(span) => self.find_references(
+                name,
+                &SymbolBinding::Local {
+                    span,
+                    exported: true,
+                },
+            ),
+            None => Vec::new(),
+        }
+    }
+
+    /// Find every reference to the symbol `name` which is loaded from another module.
+    /// `is_module` is called with the paths in `load()` statements, and should return
+    /// whether they refer to the module that defines `name`.
+    pub(crate) fn find_references_to_loaded(
+        &self,
+        name: &str,
+        is_module: &mut dyn FnMut(&str) -> bool,
+    ) -> Vec<Reference> {
+        let mut references = Vec::new();
+        for stmt in self.ast.top_level_statements() {
+            let load = match &stmt.node {
+                StmtP::Load(load) => load,
+                _ => continue,
+            };
+            for (local, their) in &load.args {
+                if their.node != name || !is_module(&load.module.node) {
+                    continue;
+                }
+                let quote = self
+                    .ast
+                    .codemap
+                    .source_span(their.span)
+                    .chars()
+                    .next()
+                    .unwrap_or('"');
+                references.push(self.reference(their.span, ReferenceKind::LoadedName { quote }));
+
+                let binding = SymbolBinding::Loaded {
+                    span: local.span,
+                    path: load.module.node.clone(),
+                    name: their.node.clone(),
+                };
+                let aliased = local.span != their.span;
+                for reference in self.find_references(&local.0, &binding) {
+                    if aliased {
+                        references.push(Reference {
+                            kind: ReferenceKind::Alias,
+                            ..reference
+                        });
+                    } else if reference.span != self.ast.codemap.resolve_span(their.span) {
+                        // The local name and the string in the `load()` are the same.
+                        references.push(reference);
+                    }
+                }
+            }
+        }
+        references
+    }
+
+    /// The paths of all of the modules loaded by this module.
+    pub(crate) fn loaded_paths(&self) -> Vec<&str> {
+        self.ast
+            .top_level_statements()
+            .into_iter()
+            .filter_map(|stmt| match &stmt.node {
+                StmtP::Load(load) => Some(load.module.node.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn find_exported_symbol_span(&self, name: &str) -> Option<Span> {
+        let scope = scope(&self.ast);
+        match scope.bound.get(name) {
+            Some((Assigner::Assign, span)) if !name.starts_with('_') => Some(*span),
+            _ => None,
+        }
+    }
+
+    fn reference(&self, span: Span, kind: ReferenceKind) -> Reference {
+        Reference {
+            span: self.ast.codemap.resolve_span(span),
+            kind,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use textwrap::dedent;
+
+    use crate::analysis::definition::helpers::FixtureWithRanges;
+    use crate::analysis::references::Reference;
+    use crate::analysis::references::ReferenceKind;
+    use crate::analysis::references::SymbolBinding;
+
+    fn spans(references: &[Reference]) -> Vec<(crate::codemap::ResolvedSpan, ReferenceKind)> {
+        references.iter().map(|r| (r.span, r.kind)).collect()
+    }
+
+    #[test]
+    fn finds_local_references() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            <x1>x</x1> = 1
+            def <f1>f</f1>(<a1>a</a1>):
+                <x2>x</x2>
+                <a2>a</a2>.foo
+                def g(x):
+                    return x
+                return [<a3>a</a3> for b in []]
+            <f2>f</f2>(<x3>x</x3>)
+            <x4>x</x4> += 1
+            "#,
+        );
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+
+        let x = module
+            .find_symbol_at(parsed.begin_line("x3"), parsed.begin_column("x3"))
+            .unwrap();
+        assert_eq!("x", x.name);
+        assert!(matches!(
+            x.binding,
+            SymbolBinding::Local { exported: true, .. }
+        ));
+        assert_eq!(
+            vec![
+                (parsed.span("x1"), ReferenceKind::Definition),
+                (parsed.span("x2"), ReferenceKind::Identifier),
+                (parsed.span("x3"), ReferenceKind::Identifier),
+                (parsed.span("x4"), ReferenceKind::Identifier),
+            ],
+            spans(&module.find_references(&x.name, &x.binding))
+        );
+        assert_eq!(
+            module.find_references(&x.name, &x.binding),
+            module.find_references_to_exported("x")
+        );
+
+        let a = module
+            .find_symbol_at(parsed.begin_line("a1"), parsed.begin_column("a1"))
+            .unwrap();
+        assert!(matches!(
+            a.binding,
+            SymbolBinding::Local {
+                exported: false,
+                ..
+            }
+        ));
+        assert_eq!(
+            vec![
+                (parsed.span("a1"), ReferenceKind::Definition),
+                (parsed.span("a2"), ReferenceKind::Identifier),
+                (parsed.span("a3"), ReferenceKind::Identifier),
+            ],
+            spans(&module.find_references(&a.name, &a.binding))
+        );
+
+        let f = module
+            .find_symbol_at(parsed.begin_line("f1"), parsed.begin_column("f1"))
+            .unwrap();
+        assert_eq!(
+            vec![
+                (parsed.span("f1"), ReferenceKind::Definition),
+                (parsed.span("f2"), ReferenceKind::Identifier),
+            ],
+            spans(&module.find_references(&f.name, &f.binding))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn finds_global_references() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            <p1>print</p1>(1)
+            def f(print):
+                print(2)
+            <p2>print</p2>(3)
+            "#,
+        );
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+
+        let print = module
+            .find_symbol_at(parsed.begin_line("p2"), parsed.begin_column("p2"))
+            .unwrap();
+        assert_eq!(SymbolBinding::Global, print.binding);
+        assert_eq!(
+            vec![
+                (parsed.span("p1"), ReferenceKind::Identifier),
+                (parsed.span("p2"), ReferenceKind::Identifier),
+            ],
+            spans(&module.find_references(&print.name, &print.binding))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn finds_loaded_references() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            load("bar.star", <foo1>"foo"</foo1>, <baz1>baz</baz1> = <baz_name>"qux"</baz_name>)
+            load("other.star", "qux")
+            <foo2>foo</foo2>(<baz2>baz</baz2>, qux)
+            "#,
+        );
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+
+        let foo = module
+            .find_symbol_at(parsed.begin_line("foo2"), parsed.begin_column("foo2"))
+            .unwrap();
+        assert_eq!("foo", foo.name);
+        assert!(matches!(
+            &foo.binding,
+            SymbolBinding::Loaded { path, name, .. } if path == "bar.star" && name == "foo"
+        ));
+        // The string in the `load()` refers to the same symbol.
+        assert_eq!(
+            foo.binding,
+            module
+                .find_symbol_at(parsed.begin_line("foo1"), parsed.begin_column("foo1") + 1)
+                .unwrap()
+                .binding
+        );
+
+        assert_eq!(
+            vec![
+                (
+                    parsed.span("foo1"),
+                    ReferenceKind::LoadedName { quote: '"' }
+                ),
+                (parsed.span("foo2"), ReferenceKind::Identifier),
+            ],
+            spans(&module.find_references_to_loaded("foo", &mut |p| p == "bar.star"))
+        );
+        assert_eq!(
+            vec![
+                (
+                    parsed.span("baz_name"),
+                    ReferenceKind::LoadedName { quote: '"' }
+                ),
+                (parsed.span("baz1"), ReferenceKind::Alias),
+                (parsed.span("baz2"), ReferenceKind::Alias),
+            ],
+            spans(&module.find_references_to_loaded("qux", &mut |p| p == "bar.star"))
+        );
+        assert_eq!(vec!["bar.star", "other.star"], module.loaded_paths());
+        Ok(())
+    }
+}
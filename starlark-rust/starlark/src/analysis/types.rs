@@ -42,6 +42,59 @@ pub(crate) struct LintT<T> {
     pub location: FileSpan,
     pub original: String,
     pub problem: T,
+    pub fix: Option<LintFix>,
+}
+
+/// A machine-applicable change that resolves a [`Lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFix {
+    /// What the fix does, e.g. ``Remove unused `load` of `foo` ``.
+    pub description: String,
+    /// Replacement text for spans in the file of the lint. The spans do not overlap.
+    pub(crate) edits: Vec<(Span, String)>,
+}
+
+impl LintFix {
+    /// Apply `fixes` to `source`, which must be the text the lints were produced from.
+    /// A fix that overlaps an earlier one is skipped (edits identical to ones already made
+    /// are fine), so rerunning the linter on the result may find more to fix.
+    /// Returns the new text and the number of fixes applied.
+    pub fn apply_all<'a>(
+        source: &str,
+        fixes: impl IntoIterator<Item = &'a LintFix>,
+    ) -> (String, usize) {
+        let mut edits: Vec<(Span, &str)> = Vec::new();
+        let mut applied = 0;
+        for fix in fixes {
+            let overlaps = fix.edits.iter().any(|(span, text)| {
+                edits.iter().any(|(old, old_text)| {
+                    (old, *old_text) != (span, text.as_str())
+                        && old.begin() < span.end()
+                        && span.begin() < old.end()
+                })
+            });
+            if overlaps {
+                continue;
+            }
+            for (span, text) in &fix.edits {
+                if !edits.contains(&(*span, text.as_str())) {
+                    edits.push((*span, text.as_str()));
+                }
+            }
+            applied += 1;
+        }
+
+        edits.sort_by_key(|(span, _)| span.begin());
+        let mut res = String::with_capacity(source.len());
+        let mut pos = 0;
+        for (span, text) in edits {
+            res.push_str(&source[pos..span.begin().get() as usize]);
+            res.push_str(text);
+            pos = span.end().get() as usize;
+        }
+        res.push_str(&source[pos..]);
+        (res, applied)
+    }
 }
 
 /// A lint produced by [`AstModule::lint`](crate::syntax::AstModule::lint).
@@ -58,6 +111,8 @@ pub struct Lint {
     pub problem: String,
     /// The source code at [`location`](Lint::location).
     pub original: String,
+    /// An automatic fix for the problem, if there is an obvious one.
+    pub fix: Option<LintFix>,
 }
 
 impl Display for Lint {
@@ -79,9 +134,16 @@ impl<T: LintWarning> LintT<T> {
             original: location.file.source_span(span).to_owned(),
             location,
             problem,
+            fix: None,
         }
     }
 
+    /// Attach a fix replacing each span in `edits` with its string.
+    pub(crate) fn with_fix(mut self, description: String, edits: Vec<(Span, String)>) -> Self {
+        self.fix = Some(LintFix { description, edits });
+        self
+    }
+
     pub(crate) fn erase(self) -> Lint {
         Lint {
             location: self.location,
@@ -89,6 +151,7 @@ impl<T: LintWarning> LintT<T> {
             serious: self.problem.is_serious(),
             problem: self.problem.to_string(),
             original: self.original,
+            fix: self.fix,
         }
     }
 }
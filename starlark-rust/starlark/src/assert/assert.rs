@@ -34,6 +34,7 @@ use crate::codemap::CodeMap;
 use crate::codemap::FileSpanRef;
 use crate::codemap::Pos;
 use crate::codemap::Span;
+use crate::docs::DocTest;
 use crate::environment::FrozenModule;
 use crate::environment::Globals;
 use crate::environment::GlobalsBuilder;
@@ -168,11 +169,6 @@ pub(crate) fn test_functions(builder: &mut GlobalsBuilder) {
         Ok(AllocStruct::EMPTY)
     }
 
-    // Approximate version of a method used by the Go test suite
-    fn set<'v>(xs: Value<'v>) -> anyhow::Result<Value<'v>> {
-        Ok(xs)
-    }
-
     fn assert_eq<'v>(a: Value<'v>, b: Value<'v>) -> anyhow::Result<NoneType> {
         assert_equals(a, b)
     }
@@ -503,6 +499,42 @@ impl<'a> Assert<'a> {
         })
     }
 
+    /// A program whose docstrings contain `>>>` examples, all of which must produce
+    /// the output that follows them. The examples can use the symbols the program defines.
+    /// See [`DocTest`].
+    ///
+    /// ```
+    /// # use starlark::assert::Assert;
+    /// Assert::new().doctests(r#"
+    /// def double(x):
+    ///     """Double a value.
+    ///
+    ///     >>> double(3)
+    ///     6
+    ///     """
+    ///     return x * 2
+    /// "#);
+    /// ```
+    pub fn doctests(&self, program: &str) {
+        let tests = DocTest::extract(&self.parse_ast(program));
+        let module = self.pass_module(program);
+        let failures: Vec<_> = tests
+            .iter()
+            .flat_map(|test| test.run(&self.globals, &[module.dupe()], &self.dialect))
+            .collect();
+        if !failures.is_empty() {
+            for failure in &failures {
+                Diagnostic::eprint(failure);
+            }
+            panic!(
+                "starlark::assert::doctests, {} examples failed!\nCode:\n{}\nFirst error: {}",
+                failures.len(),
+                program,
+                failures[0]
+            );
+        }
+    }
+
     /// A program that must evaluate to `True`.
     ///
     /// ```
@@ -691,6 +723,11 @@ pub fn fails(program: &str, msgs: &[&str]) -> anyhow::Error {
     Assert::new().fails(program, msgs)
 }
 
+/// See [`Assert::doctests`].
+pub fn doctests(program: &str) {
+    Assert::new().doctests(program)
+}
+
 /// See [`Assert::is_true`].
 pub fn is_true(program: &str) {
     Assert::new().is_true(program)
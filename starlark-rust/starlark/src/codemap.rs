@@ -47,6 +47,11 @@ impl Pos {
     pub const fn new(x: u32) -> Self {
         Self(x)
     }
+
+    /// Get the byte offset of the position in the file.
+    pub(crate) fn get(self) -> u32 {
+        self.0
+    }
 }
 
 impl Add<u32> for Pos {
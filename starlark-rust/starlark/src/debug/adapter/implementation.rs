@@ -16,7 +16,11 @@
  */
 
 use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
 use std::fmt::Debug;
+use std::fmt::Write;
+use std::mem;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc::channel;
@@ -25,20 +29,27 @@ use std::sync::mpsc::Sender;
 use std::sync::Arc;
 use std::sync::Mutex;
 
-use debugserver_types::*;
+use debugserver_types::EvaluateResponseBody;
+use debugserver_types::SetBreakpointsArguments;
+use debugserver_types::SetBreakpointsResponseBody;
+use debugserver_types::Source;
+use debugserver_types::StackFrame;
+use debugserver_types::StackTraceArguments;
+use debugserver_types::StackTraceResponseBody;
 use dupe::Dupe;
 
 use crate::codemap::FileSpan;
 use crate::codemap::FileSpanRef;
 use crate::codemap::Span;
 use crate::debug::adapter::Breakpoint;
+use crate::debug::adapter::DapAdapterError;
 use crate::debug::adapter::ResolvedBreakpoints;
+use crate::debug::adapter::Variable as AdapterVariable;
 use crate::debug::DapAdapter;
 use crate::debug::DapAdapterClient;
 use crate::debug::DapAdapterEvalHook;
 use crate::debug::ScopesInfo;
 use crate::debug::StepKind;
-use crate::debug::Variable;
 use crate::debug::VariablesInfo;
 use crate::eval::BeforeStmtFuncDyn;
 use crate::eval::Evaluator;
@@ -55,6 +66,7 @@ pub(crate) fn prepare_dap_adapter(
         client,
         breakpoints: Arc::new(Mutex::new(BreakpointConfig::new())),
         disable_breakpoints: Arc::new(0usize.into()),
+        recording: Mutex::new(Recording::default()),
     });
 
     (
@@ -101,6 +113,7 @@ impl<'a> BeforeStmtFuncDyn<'a> for DapAdapterEvalHookImpl {
         let stop = if self.state.disable_breakpoints.load(Ordering::SeqCst) > 0 {
             false
         } else {
+            self.state.recording.lock().unwrap().record(span_loc, eval);
             let breaks = self.state.breakpoints.lock().unwrap();
             let breakpoint = breaks.at(span_loc);
             match breakpoint {
@@ -133,6 +146,10 @@ impl<'a> BeforeStmtFuncDyn<'a> for DapAdapterEvalHookImpl {
                 let msg = self.receiver.recv();
                 match msg.map(|msg| msg(span_loc, eval)) {
                     Ok(Next::Continue) => break,
+                    Ok(Next::Stopped) => {
+                        self.state.client.event_stopped();
+                        continue;
+                    }
                     Ok(Next::Step(kind)) => {
                         self.step = Some((kind, eval.call_stack_count()));
                         break;
@@ -211,6 +228,183 @@ impl BreakpointConfig {
     }
 }
 
+/// Maximum size of the statements kept by a recording, older statements are discarded.
+const MAX_RECORDED_BYTES: usize = 64 * 1024 * 1024;
+
+/// Longest value kept by a recording, longer values are truncated.
+const MAX_RECORDED_VALUE_LEN: usize = 1000;
+
+/// Writer which keeps the first `MAX_RECORDED_VALUE_LEN` bytes, and fails after that,
+/// so formatting a large value stops early instead of building its whole repr.
+struct TruncatingWriter {
+    repr: String,
+    truncated: bool,
+}
+
+impl fmt::Write for TruncatingWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let remaining = MAX_RECORDED_VALUE_LEN - self.repr.len();
+        if s.len() <= remaining {
+            self.repr.push_str(s);
+            return Ok(());
+        }
+        let mut end = remaining;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.repr.push_str(&s[..end]);
+        self.truncated = true;
+        Err(fmt::Error)
+    }
+}
+
+/// The repr of a value, truncated to about `MAX_RECORDED_VALUE_LEN` bytes.
+fn truncated_repr(value: Value) -> String {
+    let mut writer = TruncatingWriter {
+        repr: String::new(),
+        truncated: false,
+    };
+    // Fails once the repr is truncated.
+    let _ignored = write!(writer, "{}", value);
+    if writer.truncated {
+        writer.repr.push_str("...");
+    }
+    writer.repr
+}
+
+/// A statement executed while recording, with the state of the evaluator before it ran.
+#[derive(Debug, Clone)]
+struct RecordedStep {
+    span: FileSpan,
+    /// The call stack, outermost first, as the function name and the location of the call.
+    frames: Vec<(String, Option<FileSpan>)>,
+    locals: Vec<AdapterVariable>,
+}
+
+impl RecordedStep {
+    /// Approximate memory used by the step.
+    fn bytes(&self) -> usize {
+        mem::size_of::<Self>()
+            + self
+                .frames
+                .iter()
+                .map(|(name, _)| mem::size_of::<(String, Option<FileSpan>)>() + name.len())
+                .sum::<usize>()
+            + self
+                .locals
+                .iter()
+                .map(|x| {
+                    mem::size_of::<AdapterVariable>() + x.name.len() + x.value.len() + x.type_.len()
+                })
+                .sum::<usize>()
+    }
+}
+
+/// History of the statements executed, used to implement stepping backwards.
+///
+/// The evaluator can't run backwards, so when going back in time we show the recorded state
+/// instead of the live one, and evaluation stays paused at the latest statement until we get
+/// back to it.
+#[derive(Debug, Default)]
+struct Recording {
+    enabled: bool,
+    /// The last entry is the statement the evaluator is paused at.
+    steps: VecDeque<RecordedStep>,
+    /// Index in `steps` being shown, or `None` if we are showing the live evaluator.
+    position: Option<usize>,
+    /// Sum of the `bytes` of `steps`.
+    bytes: usize,
+}
+
+impl Recording {
+    fn record(&mut self, span_loc: FileSpanRef, eval: &Evaluator) {
+        if !self.enabled {
+            return;
+        }
+        self.push(RecordedStep {
+            span: span_loc.to_file_span(),
+            frames: eval
+                .call_stack()
+                .into_frames()
+                .into_iter()
+                .map(|x| (x.name, x.location))
+                .collect(),
+            locals: eval
+                .local_variables()
+                .into_iter()
+                .map(|(name, value)| AdapterVariable {
+                    name,
+                    value: truncated_repr(value),
+                    type_: value.get_type().to_owned(),
+                })
+                .collect(),
+        });
+    }
+
+    fn push(&mut self, step: RecordedStep) {
+        self.bytes += step.bytes();
+        self.steps.push_back(step);
+        // Always keep the live statement.
+        while self.bytes > MAX_RECORDED_BYTES && self.steps.len() > 1 {
+            let step = self.steps.pop_front().unwrap();
+            self.bytes -= step.bytes();
+            self.position = self.position.map(|i| i.saturating_sub(1));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.steps.clear();
+        self.position = None;
+        self.bytes = 0;
+    }
+
+    /// The recorded step being shown, if we are not at the live evaluator.
+    fn current(&self) -> Option<&RecordedStep> {
+        self.position.map(|i| &self.steps[i])
+    }
+
+    /// Move to the previous step for which `stop` is true, or the first recorded step.
+    /// Returns `false` if there is no earlier step.
+    fn back(&mut self, stop: impl Fn(&RecordedStep) -> bool) -> bool {
+        let from = match self.position {
+            Some(i) => i,
+            None => self.steps.len().saturating_sub(1),
+        };
+        if from == 0 {
+            return false;
+        }
+        let to = (0..from).rev().find(|i| stop(&self.steps[*i])).unwrap_or(0);
+        self.position = Some(to);
+        true
+    }
+
+    /// Move forward through the history to the next step for which `stop` is true.
+    /// Returns `false` if there is none, and we are now back at the live evaluator.
+    fn forward(&mut self, stop: impl Fn(&RecordedStep) -> bool) -> bool {
+        let Some(from) = self.position else {
+            return false;
+        };
+        // The last step is the live statement, which doesn't need to be shown from the recording.
+        let last = self.steps.len() - 1;
+        self.position = (from + 1..last).find(|i| stop(&self.steps[*i]));
+        self.position.is_some() || stop(&self.steps[last])
+    }
+}
+
+fn recorded_stack_trace(step: &RecordedStep) -> StackTraceResponseBody {
+    let mut next = Some(step.span.dupe());
+    let mut res = Vec::with_capacity(step.frames.len() + 1);
+    for (i, (name, location)) in step.frames.iter().rev().enumerate() {
+        res.push(convert_frame(i, name.clone(), next));
+        next = location.dupe();
+    }
+    res.push(convert_frame(10000, "Root".to_owned(), next));
+    StackTraceResponseBody {
+        total_frames: Some(res.len() as i64),
+        stack_frames: res,
+    }
+}
+
 #[derive(Debug)]
 struct SharedAdapterState {
     client: Box<dyn DapAdapterClient>,
@@ -219,12 +413,16 @@ struct SharedAdapterState {
     breakpoints: Arc<Mutex<BreakpointConfig>>,
     // Set while we are doing evaluate calls (>= 1 means disable)
     disable_breakpoints: Arc<AtomicUsize>,
+    // Statements executed, when recording is enabled.
+    recording: Mutex<Recording>,
 }
 
 #[derive(Debug, Clone, Copy, Dupe)]
 enum Next {
     Continue,
     RemainPaused,
+    /// Remain paused, and tell the client we stopped again, after moving through the recording.
+    Stopped,
     Step(StepKind),
 }
 
@@ -268,6 +466,10 @@ impl DapAdapter for DapAdapterImpl {
     }
 
     fn top_frame(&self) -> anyhow::Result<Option<StackFrame>> {
+        if let Some(step) = self.state.recording.lock().unwrap().current() {
+            let name = step.frames.last().map_or("".to_owned(), |x| x.0.clone());
+            return Ok(Some(convert_frame(0, name, Some(step.span.dupe()))));
+        }
         self.with_ctx(Box::new(|span, eval| {
             let frame = eval.call_stack_top_frame();
             let name = frame.map_or("".to_owned(), |v| v.name);
@@ -279,6 +481,9 @@ impl DapAdapter for DapAdapterImpl {
         // Our model of a Frame and the debugger model are a bit different.
         // We record the location of the call, but DAP wants the location we are at.
         // We also have them in the wrong order
+        if let Some(step) = self.state.recording.lock().unwrap().current() {
+            return Ok(recorded_stack_trace(step));
+        }
         self.with_ctx(Box::new(|span, eval| {
             let frames = eval.call_stack().into_frames();
             let mut next = Some(span.to_file_span());
@@ -296,6 +501,11 @@ impl DapAdapter for DapAdapterImpl {
     }
 
     fn scopes(&self) -> anyhow::Result<ScopesInfo> {
+        if let Some(step) = self.state.recording.lock().unwrap().current() {
+            return Ok(ScopesInfo {
+                num_locals: step.locals.len(),
+            });
+        }
         self.with_ctx(Box::new(|_, eval| {
             let vars = eval.local_variables();
             Ok(ScopesInfo {
@@ -305,12 +515,17 @@ impl DapAdapter for DapAdapterImpl {
     }
 
     fn variables(&self) -> anyhow::Result<VariablesInfo> {
+        if let Some(step) = self.state.recording.lock().unwrap().current() {
+            return Ok(VariablesInfo {
+                locals: step.locals.clone(),
+            });
+        }
         self.with_ctx(Box::new(|_, eval| {
             let vars = eval.local_variables();
             Ok(VariablesInfo {
                 locals: vars
                     .into_iter()
-                    .map(|(name, value)| Variable {
+                    .map(|(name, value)| AdapterVariable {
                         name,
                         value: value.to_string(),
                         type_: value.get_type().to_owned(),
@@ -321,16 +536,89 @@ impl DapAdapter for DapAdapterImpl {
     }
 
     fn continue_(&self) -> anyhow::Result<()> {
-        self.inject_next(Next::Continue);
+        let breakpoints = self.state.breakpoints.lock().unwrap();
+        let stopped = self
+            .state
+            .recording
+            .lock()
+            .unwrap()
+            .forward(|step| breakpoints.at(step.span.as_ref()).is_some());
+        drop(breakpoints);
+        self.inject_next(if stopped {
+            Next::Stopped
+        } else {
+            Next::Continue
+        });
         Ok(())
     }
 
     fn step(&self, kind: StepKind) -> anyhow::Result<()> {
-        self.inject_next(Next::Step(kind));
+        let mut recording = self.state.recording.lock().unwrap();
+        let depth = recording.current().map_or(0, |x| x.frames.len());
+        let stopped = recording.forward(|step| match kind {
+            StepKind::Into => true,
+            StepKind::Over => step.frames.len() <= depth,
+            StepKind::Out => step.frames.len() < depth,
+        });
+        drop(recording);
+        self.inject_next(if stopped {
+            Next::Stopped
+        } else {
+            Next::Step(kind)
+        });
+        Ok(())
+    }
+
+    fn set_recording(&self, enabled: bool) -> anyhow::Result<()> {
+        let mut recording = self.state.recording.lock().unwrap();
+        recording.enabled = enabled;
+        if !enabled {
+            recording.clear();
+        }
+        Ok(())
+    }
+
+    fn step_back(&self) -> anyhow::Result<()> {
+        let moved = self.state.recording.lock().unwrap().back(|_| true);
+        if !moved {
+            return Err(DapAdapterError::NoRecordedHistory.into());
+        }
+        self.inject_next(Next::Stopped);
+        Ok(())
+    }
+
+    fn reverse_continue(&self) -> anyhow::Result<()> {
+        let breakpoints = self.state.breakpoints.lock().unwrap();
+        let moved = self
+            .state
+            .recording
+            .lock()
+            .unwrap()
+            .back(|step| breakpoints.at(step.span.as_ref()).is_some());
+        drop(breakpoints);
+        if !moved {
+            return Err(DapAdapterError::NoRecordedHistory.into());
+        }
+        self.inject_next(Next::Stopped);
         Ok(())
     }
 
     fn evaluate(&self, expr: &str) -> anyhow::Result<EvaluateResponseBody> {
+        if let Some(step) = self.state.recording.lock().unwrap().current() {
+            // We can't evaluate code in the past, but we can show the recorded variables.
+            let result = match step.locals.iter().find(|x| x.name == expr.trim()) {
+                Some(x) => x.value.clone(),
+                None => DapAdapterError::NotRecorded(expr.to_owned()).to_string(),
+            };
+            return Ok(EvaluateResponseBody {
+                indexed_variables: None,
+                named_variables: None,
+                presentation_hint: None,
+                result,
+                type_: None,
+                variables_reference: 0.0,
+            });
+        }
         let state = self.state.dupe();
         let expression = expr.to_owned();
         self.with_ctx(Box::new(move |_, eval| {
@@ -44,6 +44,7 @@ pub struct ScopesInfo {
 }
 
 /// Information about a variable.
+#[derive(Debug, Clone)]
 pub struct Variable {
     /// Name of the variable.
     pub name: String,
@@ -89,6 +90,18 @@ pub struct VariablesInfo {
     pub locals: Vec<Variable>,
 }
 
+#[derive(Debug, thiserror::Error)]
+enum DapAdapterError {
+    #[error("No earlier statement has been recorded, recording must be enabled to step back")]
+    NoRecordedHistory,
+    #[error(
+        "Only the variables recorded at this statement can be shown, `{0}` is not one of them"
+    )]
+    NotRecorded(String),
+    #[error("The debug adapter does not support `{0}`")]
+    Unsupported(&'static str),
+}
+
 /// The DapAdapter accepts DAP requests and updates the hooks in the running evaluator.
 pub trait DapAdapter: Debug + Send + 'static {
     /// Sets multiple breakpoints for a file (and clears existing ones).
@@ -129,6 +142,30 @@ pub trait DapAdapter: Debug + Send + 'static {
     /// <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_StepIn>
     /// <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_StepOut>
     fn step(&self, kind: StepKind) -> anyhow::Result<()>;
+
+    /// Enables or disables recording of the statements executed, which is required to step backwards.
+    /// Disabling the recording discards the history.
+    ///
+    /// A recording keeps up to 64MiB of frames and variables, after which the oldest statements
+    /// are discarded, and values are truncated to their first 1000 bytes.
+    fn set_recording(&self, _enabled: bool) -> anyhow::Result<()> {
+        Err(DapAdapterError::Unsupported("setRecording").into())
+    }
+
+    /// Goes back to the previous recorded statement.
+    ///
+    /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_StepBack>
+    fn step_back(&self) -> anyhow::Result<()> {
+        Err(DapAdapterError::Unsupported("stepBack").into())
+    }
+
+    /// Goes back to the previous recorded statement with a breakpoint, or the first recorded statement.
+    ///
+    /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_ReverseContinue>
+    fn reverse_continue(&self) -> anyhow::Result<()> {
+        Err(DapAdapterError::Unsupported("reverseContinue").into())
+    }
+
     /// Evaluates in expression in the context of the top-most frame.
     ///
     /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Evaluate>
@@ -169,6 +206,9 @@ pub trait DapAdapterEvalHook: Debug + Send + 'static {
 }
 
 /// The DAP capabilities that the adapter supports.
+///
+/// Stepping back is not included, as it needs the server to enable recording with
+/// [`DapAdapter::set_recording`] and to handle the `stepBack` and `reverseContinue` requests.
 pub fn dap_capabilities() -> Capabilities {
     Capabilities {
         supports_configuration_done_request: Some(true),
@@ -351,4 +351,99 @@ print(x)
             Ok(())
         })
     }
+
+    #[test]
+    fn test_step_back() -> anyhow::Result<()> {
+        let controller = BreakpointController::new();
+        let (adapter, eval_hook) = prepare_dap_adapter(controller.get_client());
+        let file_contents = "
+def adjust(y):
+    y[0] += 1
+    y[1] += 1 # line 4
+    y[2] += 1
+x = [1, 2, 3]
+adjust(x)
+adjust(x) # line 8
+print(x)
+        ";
+        std::thread::scope(|s| {
+            let ast = AstModule::parse("test.bzl", file_contents.to_owned(), &Dialect::Extended)?;
+            let breakpoints =
+                resolve_breakpoints(&breakpoints_args("test.bzl", &[(4, None), (8, None)]), &ast)?;
+            adapter.set_breakpoints("test.bzl", &breakpoints)?;
+            adapter.set_recording(true)?;
+            let eval_result =
+                s.spawn(move || -> anyhow::Result<_> { eval_with_hook(ast, eval_hook) });
+            controller.wait_for_eval_stopped(1, TIMEOUT);
+            assert_eq!("[2, 2, 3]", adapter.evaluate("y")?.result);
+            adapter.continue_()?;
+            controller.wait_for_eval_stopped(2, TIMEOUT);
+            assert_eq!("[2, 3, 4]", adapter.evaluate("x")?.result);
+
+            // stepping back takes us to the end of the first call
+            adapter.step_back()?;
+            controller.wait_for_eval_stopped(3, TIMEOUT);
+            assert_eq!("[2, 3, 3]", adapter.evaluate("y")?.result);
+            let stack_trace = adapter.stack_trace(StackTraceArguments {
+                thread_id: 0,
+                start_frame: None,
+                levels: None,
+                format: None,
+            })?;
+            assert_eq!(2, stack_trace.stack_frames.len());
+
+            // reverse continue goes back to the breakpoint on line 4, where `x` isn't a local
+            adapter.reverse_continue()?;
+            controller.wait_for_eval_stopped(4, TIMEOUT);
+            assert_eq!("[2, 2, 3]", adapter.evaluate("y")?.result);
+            assert!(adapter.evaluate("x")?.result.contains("not one of them"));
+
+            // continue replays the history up to where evaluation is paused
+            adapter.continue_()?;
+            controller.wait_for_eval_stopped(5, TIMEOUT);
+            assert_eq!("[2, 3, 4]", adapter.evaluate("x")?.result);
+
+            adapter.set_recording(false)?;
+            adapter.continue_()?;
+            controller.wait_for_eval_stopped(6, TIMEOUT);
+            assert_eq!("[3, 3, 4]", adapter.evaluate("y")?.result);
+            assert!(adapter.step_back().is_err());
+            adapter.continue_()?;
+            join_timeout(eval_result, TIMEOUT)?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_step_back_truncates_values() -> anyhow::Result<()> {
+        let controller = BreakpointController::new();
+        let (adapter, eval_hook) = prepare_dap_adapter(controller.get_client());
+        let file_contents = "
+x = list(range(100000))
+y = 1
+print(x) # line 4
+        ";
+        std::thread::scope(|s| {
+            let ast = AstModule::parse("test.bzl", file_contents.to_owned(), &Dialect::Extended)?;
+            let breakpoints =
+                resolve_breakpoints(&breakpoints_args("test.bzl", &[(4, None)]), &ast)?;
+            adapter.set_breakpoints("test.bzl", &breakpoints)?;
+            adapter.set_recording(true)?;
+            let eval_result =
+                s.spawn(move || -> anyhow::Result<_> { eval_with_hook(ast, eval_hook) });
+            controller.wait_for_eval_stopped(1, TIMEOUT);
+            adapter.step_back()?;
+            controller.wait_for_eval_stopped(2, TIMEOUT);
+            // values are recorded with a truncated repr
+            let x = adapter.evaluate("x")?.result;
+            assert!(x.starts_with("[0, 1, 2, "), "{}", x);
+            assert!(x.ends_with("..."), "{}", x);
+            assert!(x.len() < 2000, "{}", x.len());
+            adapter.continue_()?;
+            controller.wait_for_eval_stopped(3, TIMEOUT);
+            adapter.continue_()?;
+            join_timeout(eval_result, TIMEOUT)?;
+            Ok(())
+        })
+    }
 }
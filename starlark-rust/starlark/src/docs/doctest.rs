@@ -0,0 +1,390 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Run the `>>>` examples in Starlark docstrings, in the style of Python's `doctest`.
+
+use std::cell::RefCell;
+
+use crate::codemap::CodeMap;
+use crate::codemap::FileSpan;
+use crate::codemap::Span;
+use crate::docs::DocString;
+use crate::environment::FrozenModule;
+use crate::environment::Globals;
+use crate::environment::Module;
+use crate::errors::Diagnostic;
+use crate::eval::Evaluator;
+use crate::stdlib::PrintHandler;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::Stmt;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+#[derive(Debug, thiserror::Error)]
+enum DocTestError {
+    #[error("Doctest in `{0}` failed\nExpected:\n{1}\nGot:\n{2}")]
+    Mismatch(String, String, String),
+    #[error("Doctest in `{0}` failed with an error: {1}")]
+    Error(String, anyhow::Error),
+}
+
+/// The `>>>` examples in one docstring.
+///
+/// The examples are run in order in the same module, so later examples can use
+/// the variables assigned by earlier ones.
+#[derive(Debug, Clone)]
+pub struct DocTest {
+    /// The function the docstring belongs to, or the file name for the module docstring.
+    pub name: String,
+    /// The examples, in the order they appear in the docstring.
+    pub examples: Vec<DocTestExample>,
+}
+
+/// A single example: a `>>>` line, any `...` continuation lines, and the expected output.
+#[derive(Debug, Clone)]
+pub struct DocTestExample {
+    /// The code to evaluate, with the prompts removed.
+    pub source: String,
+    /// The expected output: anything printed, followed by the `repr()` of the result if it is not `None`.
+    pub expected: String,
+    /// The location of the example in the file.
+    pub span: FileSpan,
+}
+
+#[derive(Default)]
+struct CapturePrintHandler(RefCell<String>);
+
+impl PrintHandler for CapturePrintHandler {
+    fn println(&self, text: &str) -> anyhow::Result<()> {
+        let mut output = self.0.borrow_mut();
+        output.push_str(text);
+        output.push('\n');
+        Ok(())
+    }
+}
+
+/// Trailing whitespace is ignored, and `<BLANKLINE>` stands for an empty line, like in Python.
+fn normalize_output(output: &str) -> String {
+    let lines: Vec<&str> = output
+        .lines()
+        .map(|line| match line.trim_end() {
+            "<BLANKLINE>" => "",
+            line => line,
+        })
+        .collect();
+    lines.join("\n").trim_end().to_owned()
+}
+
+/// If `line` is `prompt` after some indentation, return the indentation and the code that follows.
+fn strip_prompt<'a>(line: &'a str, prompt: &str) -> Option<(&'a str, &'a str)> {
+    let code = line.trim_start();
+    let indent = &line[..line.len() - code.len()];
+    let code = code.strip_prefix(prompt)?;
+    if code.is_empty() {
+        Some((indent, code))
+    } else {
+        Some((indent, code.strip_prefix(' ')?))
+    }
+}
+
+/// An example found in a raw docstring, with the indices of its first and last lines.
+struct RawExample {
+    source: String,
+    expected: String,
+    first_line: usize,
+    last_line: usize,
+}
+
+fn parse_examples(docstring: &str) -> Vec<RawExample> {
+    let lines: Vec<&str> = docstring.lines().collect();
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let Some((indent, code)) = strip_prompt(lines[i], ">>>") else {
+            i += 1;
+            continue;
+        };
+        let first_line = i;
+        let mut source = code.to_owned();
+        i += 1;
+        while let Some((_, code)) = lines
+            .get(i)
+            .and_then(|line| strip_prompt(line, "..."))
+            .filter(|(x, _)| *x == indent)
+        {
+            source.push('\n');
+            source.push_str(code);
+            i += 1;
+        }
+        let mut expected = Vec::new();
+        while let Some(line) = lines.get(i) {
+            if line.trim().is_empty() || strip_prompt(line, ">>>").is_some() {
+                break;
+            }
+            expected.push(
+                line.strip_prefix(indent)
+                    .unwrap_or_else(|| line.trim_start()),
+            );
+            i += 1;
+        }
+        res.push(RawExample {
+            source,
+            expected: normalize_output(&expected.join("\n")),
+            first_line,
+            last_line: i - 1,
+        });
+    }
+    res
+}
+
+/// The span of the lines of an example, falling back to the whole docstring
+/// if escapes in the literal mean the lines don't match up with the source.
+fn example_span(codemap: &CodeMap, literal: Span, example: &RawExample) -> Span {
+    let line = codemap.find_line(literal.begin());
+    let first = line + example.first_line;
+    let last = line + example.last_line;
+    let (Some(first_span), Some(last_span)) =
+        (codemap.line_span_opt(first), codemap.line_span_opt(last))
+    else {
+        return literal;
+    };
+    let prompt = codemap.source_line(first).find(">>>").unwrap_or(0);
+    let span = Span::new(
+        first_span.begin() + prompt as u32,
+        last_span.begin() + codemap.source_line(last).len() as u32,
+    );
+    if literal.contains(span.begin()) && literal.contains(span.end()) {
+        span
+    } else {
+        literal
+    }
+}
+
+impl DocTest {
+    /// Find the examples in the module docstring and the docstrings of all the functions in a module.
+    /// Docstrings without examples are skipped.
+    pub fn extract(module: &AstModule) -> Vec<DocTest> {
+        fn docstring(module: &AstModule, name: &str, body: &AstStmt, res: &mut Vec<DocTest>) {
+            if let Some(literal) = DocString::find_raw_starlark_docstring(body) {
+                let examples: Vec<_> = parse_examples(&literal.node)
+                    .into_iter()
+                    .map(|example| DocTestExample {
+                        span: module.file_span(example_span(
+                            &module.codemap,
+                            literal.span,
+                            &example,
+                        )),
+                        source: example.source,
+                        expected: example.expected,
+                    })
+                    .collect();
+                if !examples.is_empty() {
+                    res.push(DocTest {
+                        name: name.to_owned(),
+                        examples,
+                    });
+                }
+            }
+        }
+
+        fn defs(module: &AstModule, stmt: &AstStmt, res: &mut Vec<DocTest>) {
+            if let Stmt::Def(def) = &stmt.node {
+                docstring(module, &def.name.0, &def.body, res);
+            }
+            stmt.visit_stmt(|x| defs(module, x, res));
+        }
+
+        let mut res = Vec::new();
+        docstring(
+            module,
+            module.codemap.filename(),
+            &module.statement,
+            &mut res,
+        );
+        defs(module, &module.statement, &mut res);
+        res
+    }
+
+    /// Run the examples in a fresh module which has all the symbols of `modules` available,
+    /// including private ones, usually the module the docstring came from. Returns an error for
+    /// each failing example, with the span of the example.
+    pub fn run(
+        &self,
+        globals: &Globals,
+        modules: &[FrozenModule],
+        dialect: &Dialect,
+    ) -> Vec<anyhow::Error> {
+        let module = Module::new();
+        for m in modules {
+            module.import_all_symbols(m);
+        }
+        self.examples
+            .iter()
+            .enumerate()
+            .filter_map(|(i, example)| {
+                example
+                    .run(&self.name, i, &module, globals, dialect)
+                    .err()
+                    .map(|e| Diagnostic::new(e, example.span.span, &example.span.file))
+            })
+            .collect()
+    }
+}
+
+impl DocTestExample {
+    fn run(
+        &self,
+        name: &str,
+        index: usize,
+        module: &Module,
+        globals: &Globals,
+        dialect: &Dialect,
+    ) -> Result<(), DocTestError> {
+        let output = CapturePrintHandler::default();
+        let result = {
+            let mut eval = Evaluator::new(module);
+            eval.set_print_handler(&output);
+            // Positions in the example don't match the file, so name it like Python does.
+            AstModule::parse(
+                &format!("<doctest {}[{}]>", name, index),
+                self.source.clone(),
+                dialect,
+            )
+            .and_then(|ast| eval.eval_module(ast, globals))
+            .map(|v| if v.is_none() { None } else { Some(v.to_repr()) })
+        };
+        let mut output = output.0.into_inner();
+        match result {
+            Ok(repr) => output.extend(repr),
+            Err(e) => return Err(DocTestError::Error(name.to_owned(), e)),
+        }
+        let output = normalize_output(&output);
+        if output == self.expected {
+            Ok(())
+        } else {
+            Err(DocTestError::Mismatch(
+                name.to_owned(),
+                self.expected.clone(),
+                output,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+    use crate::docs::DocTest;
+    use crate::environment::Globals;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    const PROGRAM: &str = r#"
+"""Module docs.
+
+>>> double(2)
+4
+"""
+
+def double(x):
+    """Double a value.
+
+    >>> double(3)
+    6
+    >>> xs = [double(x) for x in range(3)]
+    >>> for x in xs:
+    ...     print(x)
+    0
+    2
+    4
+    >>> double("ab")
+    "abab"
+    """
+    return x * 2
+
+def no_examples():
+    """Just docs."""
+    pass
+"#;
+
+    #[test]
+    fn test_extract() {
+        let ast = AstModule::parse("test.bzl", PROGRAM.to_owned(), &Dialect::Extended).unwrap();
+        let tests = DocTest::extract(&ast);
+        assert_eq!(
+            vec!["test.bzl", "double"],
+            tests.iter().map(|t| t.name.as_str()).collect::<Vec<_>>()
+        );
+        let examples = &tests[1].examples;
+        assert_eq!(4, examples.len());
+        assert_eq!("for x in xs:\n    print(x)", examples[2].source);
+        assert_eq!("0\n2\n4", examples[2].expected);
+        assert_eq!("", examples[1].expected);
+        assert_eq!("test.bzl:11:5-12:6", examples[0].span.resolve().to_string());
+    }
+
+    #[test]
+    fn test_run() {
+        assert::doctests(PROGRAM);
+
+        let program = PROGRAM.replace("    6\n", "    7\n");
+        let ast = AstModule::parse("test.bzl", program.clone(), &Dialect::Extended).unwrap();
+        let module = assert::pass_module(&program);
+        let failures =
+            DocTest::extract(&ast)[1].run(&Globals::extended(), &[module], &Dialect::Extended);
+        assert_eq!(1, failures.len());
+        let failure = failures[0].to_string();
+        assert!(failure.contains("Expected:\n7\nGot:\n6"), "{}", failure);
+        assert!(failure.contains("test.bzl:11:5"), "{}", failure);
+    }
+
+    #[test]
+    fn test_run_private() {
+        assert::doctests(
+            r#"
+def _half(x):
+    """
+    >>> _half(4)
+    2
+    """
+    return x // 2
+"#,
+        );
+    }
+
+    #[test]
+    fn test_error_location() {
+        let program = r#"
+def f():
+    """
+    >>> 1
+    1
+    >>> len(
+    ...   undefined)
+    """
+    pass
+"#;
+        let ast = AstModule::parse("test.bzl", program.to_owned(), &Dialect::Extended).unwrap();
+        let module = assert::pass_module(program);
+        let failures =
+            DocTest::extract(&ast)[0].run(&Globals::extended(), &[module], &Dialect::Extended);
+        assert_eq!(1, failures.len());
+        let failure = format!("{:#}", failures[0]);
+        assert!(failure.contains("<doctest f[1]>:2:3"), "{}", failure);
+        assert!(failure.contains("test.bzl:6:5"), "{}", failure);
+    }
+}
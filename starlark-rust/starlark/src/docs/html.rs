@@ -0,0 +1,659 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Render documentation as a self-contained set of cross-linked HTML pages.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use itertools::Itertools;
+use serde::Serialize;
+use starlark_map::small_map::SmallMap;
+
+use crate::docs::Doc;
+use crate::docs::DocFunction;
+use crate::docs::DocItem;
+use crate::docs::DocMember;
+use crate::docs::DocParam;
+use crate::docs::DocProperty;
+use crate::docs::DocString;
+use crate::docs::DocType;
+
+/// A file produced by [`render_docs_as_html`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlFile {
+    /// Path relative to the output directory, using `/` as a separator.
+    pub path: String,
+    /// The contents of the file.
+    pub contents: String,
+}
+
+/// An entry in the search index, pointing at a symbol.
+#[derive(Serialize)]
+struct SearchEntry {
+    name: String,
+    kind: &'static str,
+    href: String,
+    summary: String,
+}
+
+/// Functions with more parameters than this have their prototype split over multiple lines,
+/// matching the markdown output.
+const MAX_ARGS_BEFORE_MULTILINE: usize = 3;
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; max-width: 60em; margin: 0 auto; padding: 1em; line-height: 1.5; }
+nav { margin-bottom: 1em; }
+pre { background: #f5f5f5; padding: 0.5em; overflow-x: auto; }
+code { font-family: monospace; }
+section { border-top: 1px solid #ddd; }
+.kind { color: #777; font-size: small; }
+#results li, .index li { list-style: none; }
+"#;
+
+const SEARCH_SCRIPT: &str = r#"
+const search = document.getElementById("search");
+const results = document.getElementById("results");
+search.addEventListener("input", () => {
+  const query = search.value.toLowerCase();
+  results.innerHTML = "";
+  if (query === "") return;
+  for (const entry of SEARCH_INDEX.filter(x => x.name.toLowerCase().includes(query)).slice(0, 50)) {
+    const li = document.createElement("li");
+    const a = document.createElement("a");
+    a.href = entry.href;
+    a.textContent = entry.name;
+    const kind = document.createElement("span");
+    kind.className = "kind";
+    kind.textContent = " " + entry.kind + " ";
+    li.append(a, kind, entry.summary);
+    results.append(li);
+  }
+});
+"#;
+
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Turn a symbol name into something usable as a file name or anchor.
+fn slug(name: &str) -> String {
+    let res: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if res.is_empty() || res.starts_with('.') {
+        format!("_{}", res)
+    } else {
+        res
+    }
+}
+
+fn summary(docs: &Option<DocString>) -> String {
+    docs.as_ref().map(|d| d.summary.clone()).unwrap_or_default()
+}
+
+fn kind(item: &DocItem) -> &'static str {
+    match item {
+        DocItem::Module(_) => "module",
+        DocItem::Object(_) => "object",
+        DocItem::Function(_) => "function",
+        DocItem::Property(_) => "property",
+    }
+}
+
+fn members(item: &DocItem) -> Option<(&Option<DocString>, &SmallMap<String, DocMember>)> {
+    match item {
+        DocItem::Module(m) => Some((&m.docs, &m.members)),
+        DocItem::Object(o) => Some((&o.docs, &o.members)),
+        DocItem::Function(_) | DocItem::Property(_) => None,
+    }
+}
+
+struct Renderer {
+    /// The page for each doc, in the same order as the docs.
+    pages: Vec<String>,
+    /// Names that can be linked to from types and code, and where they link to.
+    links: HashMap<String, String>,
+}
+
+impl Renderer {
+    fn new(docs: &[Doc]) -> Self {
+        let mut used = HashSet::new();
+        used.insert("index.html".to_owned());
+        let pages = docs
+            .iter()
+            .map(|doc| {
+                let base = slug(&doc.id.name);
+                let mut page = format!("{}.html", base);
+                let mut i = 1;
+                while !used.insert(page.clone()) {
+                    i += 1;
+                    page = format!("{}-{}.html", base, i);
+                }
+                page
+            })
+            .collect::<Vec<_>>();
+
+        let mut links = HashMap::new();
+        for (doc, page) in docs.iter().zip(&pages) {
+            links
+                .entry(doc.id.name.clone())
+                .or_insert_with(|| page.clone());
+        }
+        // Members of modules are usually global functions, so are worth linking to,
+        // but we don't link the members of objects, since they are only accessed as methods.
+        for (doc, page) in docs.iter().zip(&pages) {
+            if let DocItem::Module(m) = &doc.item {
+                for name in m.members.keys() {
+                    links
+                        .entry(name.clone())
+                        .or_insert_with(|| format!("{}#{}", page, slug(name)));
+                }
+            }
+        }
+        Self { pages, links }
+    }
+
+    /// Escape some code, linking any identifiers that we have documentation for.
+    fn code(&self, code: &str) -> String {
+        let mut res = String::new();
+        let mut rest = code;
+        while let Some(c) = rest.chars().next() {
+            let len = if c.is_ascii_alphanumeric() || c == '_' {
+                rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len())
+            } else {
+                c.len_utf8()
+            };
+            let (word, after) = rest.split_at(len);
+            match self.links.get(word) {
+                Some(href) => write!(res, "<a href=\"{}\">{}</a>", href, escape(word)).unwrap(),
+                None => res.push_str(&escape(word)),
+            }
+            rest = after;
+        }
+        res
+    }
+
+    fn typ(&self, typ: &Option<DocType>) -> String {
+        match typ {
+            Some(t) if !t.raw_type.is_empty() => self.code(&t.raw_type),
+            _ => escape("\"\""),
+        }
+    }
+
+    /// Text with backticks for code, which is linked.
+    fn inline(&self, text: &str) -> String {
+        text.split('`')
+            .enumerate()
+            .map(|(i, x)| {
+                if i % 2 == 0 {
+                    escape(x)
+                } else {
+                    format!("<code>{}</code>", self.code(x))
+                }
+            })
+            .join("")
+    }
+
+    /// Render the subset of markdown used in docstrings: paragraphs, bullet lists and code blocks.
+    fn markdown(&self, text: &str) -> String {
+        let mut res = String::new();
+        let mut paragraph: Vec<&str> = Vec::new();
+        let mut list: Vec<String> = Vec::new();
+        let mut code: Option<Vec<&str>> = None;
+
+        fn flush(
+            r: &Renderer,
+            res: &mut String,
+            paragraph: &mut Vec<&str>,
+            list: &mut Vec<String>,
+        ) {
+            if !paragraph.is_empty() {
+                writeln!(res, "<p>{}</p>", r.inline(&paragraph.join("\n"))).unwrap();
+                paragraph.clear();
+            }
+            if !list.is_empty() {
+                res.push_str("<ul>\n");
+                for x in list.iter() {
+                    writeln!(res, "<li>{}</li>", r.inline(x)).unwrap();
+                }
+                res.push_str("</ul>\n");
+                list.clear();
+            }
+        }
+
+        for line in text.lines() {
+            let trimmed = line.trim_start();
+            if let Some(lines) = &mut code {
+                if trimmed.starts_with("```") {
+                    writeln!(res, "<pre><code>{}</code></pre>", escape(&lines.join("\n"))).unwrap();
+                    code = None;
+                } else {
+                    lines.push(line);
+                }
+            } else if trimmed.starts_with("```") {
+                flush(self, &mut res, &mut paragraph, &mut list);
+                code = Some(Vec::new());
+            } else if trimmed.is_empty() {
+                flush(self, &mut res, &mut paragraph, &mut list);
+            } else if let Some(item) = trimmed
+                .strip_prefix("* ")
+                .or_else(|| trimmed.strip_prefix("- "))
+            {
+                if !paragraph.is_empty() {
+                    flush(self, &mut res, &mut paragraph, &mut Vec::new());
+                }
+                list.push(item.to_owned());
+            } else if let Some(last) = list.last_mut().filter(|_| line.starts_with(' ')) {
+                // Continuation of the previous list item
+                last.push('\n');
+                last.push_str(trimmed);
+            } else {
+                if !list.is_empty() {
+                    flush(self, &mut res, &mut Vec::new(), &mut list);
+                }
+                paragraph.push(line);
+            }
+        }
+        if let Some(lines) = code {
+            // An unterminated code block, just render what we have
+            writeln!(res, "<pre><code>{}</code></pre>", escape(&lines.join("\n"))).unwrap();
+        }
+        flush(self, &mut res, &mut paragraph, &mut list);
+        res
+    }
+
+    fn doc_string(&self, docs: &Option<DocString>) -> String {
+        match docs {
+            None => String::new(),
+            Some(d) => match &d.details {
+                None => self.markdown(&d.summary),
+                Some(details) => format!("{}{}", self.markdown(&d.summary), self.markdown(details)),
+            },
+        }
+    }
+
+    fn prototype(&self, name: &str, f: &DocFunction) -> String {
+        let mut params = f.params.iter().map(|p| match p {
+            DocParam::Arg {
+                name,
+                typ,
+                default_value,
+                ..
+            } => {
+                let mut res = escape(name);
+                if typ.is_some() {
+                    write!(res, ": {}", self.typ(typ)).unwrap();
+                }
+                if let Some(v) = default_value {
+                    write!(res, " = {}", escape(v)).unwrap();
+                }
+                res
+            }
+            DocParam::NoArgs => "*".to_owned(),
+            DocParam::Args { name, typ, .. } | DocParam::Kwargs { name, typ, .. } => {
+                if typ.is_some() {
+                    format!("{}: {}", escape(name), self.typ(typ))
+                } else {
+                    escape(name)
+                }
+            }
+        });
+        let params = if MAX_ARGS_BEFORE_MULTILINE < f.params.len() {
+            format!("(\n    {}\n)", params.join(",\n    "))
+        } else {
+            format!("({})", params.join(", "))
+        };
+        let ret = if f.ret.typ.is_some() {
+            format!(" -&gt; {}", self.typ(&f.ret.typ))
+        } else {
+            String::new()
+        };
+        format!(
+            "<pre><code>def {}{}{}</code></pre>\n",
+            escape(name),
+            params,
+            ret
+        )
+    }
+
+    fn function(&self, name: &str, f: &DocFunction) -> String {
+        let mut res = self.prototype(name, f);
+        res.push_str(&self.doc_string(&f.docs));
+        let params: Vec<_> = f
+            .params
+            .iter()
+            .filter_map(|p| match p {
+                DocParam::Arg { name, docs, .. }
+                | DocParam::Args { name, docs, .. }
+                | DocParam::Kwargs { name, docs, .. } => Some((name, docs.as_ref()?)),
+                DocParam::NoArgs => None,
+            })
+            .collect();
+        if !params.is_empty() {
+            res.push_str("<h4>Parameters</h4>\n<ul>\n");
+            for (name, docs) in params {
+                writeln!(
+                    res,
+                    "<li><code>{}</code>: {}</li>",
+                    escape(name),
+                    self.doc_string(&Some(docs.clone()))
+                )
+                .unwrap();
+            }
+            res.push_str("</ul>\n");
+        }
+        if f.ret.docs.is_some() {
+            write!(res, "<h4>Returns</h4>\n{}", self.doc_string(&f.ret.docs)).unwrap();
+        }
+        res
+    }
+
+    fn property(&self, name: &str, p: &DocProperty) -> String {
+        format!(
+            "<pre><code>{}: {}</code></pre>\n{}",
+            escape(name),
+            self.typ(&p.typ),
+            self.doc_string(&p.docs)
+        )
+    }
+
+    fn page(title: &str, body: &str) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+            escape(title),
+            STYLE,
+            body
+        )
+    }
+
+    fn doc_page(&self, doc: &Doc) -> String {
+        let name = &doc.id.name;
+        let mut body = String::from("<nav><a href=\"index.html\">Index</a></nav>\n");
+        match &doc.item {
+            DocItem::Function(f) => write!(
+                body,
+                "<h1>{}</h1>\n{}",
+                escape(name),
+                self.function(name, f)
+            )
+            .unwrap(),
+            DocItem::Property(p) => write!(
+                body,
+                "<h1>{}</h1>\n{}",
+                escape(name),
+                self.property(name, p)
+            )
+            .unwrap(),
+            DocItem::Module(_) | DocItem::Object(_) => {
+                let (docs, members) = members(&doc.item).unwrap();
+                let object = matches!(doc.item, DocItem::Object(_));
+                if object {
+                    writeln!(body, "<h1><code>{}</code> type</h1>", escape(name)).unwrap();
+                } else {
+                    writeln!(body, "<h1>{}</h1>", escape(name)).unwrap();
+                }
+                body.push_str(&self.doc_string(docs));
+                for (member, item) in members.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+                    let full = if object {
+                        format!("{}.{}", name, member)
+                    } else {
+                        member.clone()
+                    };
+                    let rendered = match item {
+                        DocMember::Function(f) => self.function(&full, f),
+                        DocMember::Property(p) => self.property(&full, p),
+                    };
+                    writeln!(
+                        body,
+                        "<section id=\"{}\">\n<h2>{}</h2>\n{}</section>",
+                        slug(member),
+                        escape(&full),
+                        rendered
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        Self::page(name, &body)
+    }
+
+    fn search_index(&self, docs: &[Doc]) -> Vec<SearchEntry> {
+        let mut res = Vec::new();
+        for (doc, page) in docs.iter().zip(&self.pages) {
+            let (docs, members) = match members(&doc.item) {
+                Some((docs, members)) => (docs.clone(), Some(members)),
+                None => match &doc.item {
+                    DocItem::Function(f) => (f.docs.clone(), None),
+                    DocItem::Property(p) => (p.docs.clone(), None),
+                    _ => unreachable!(),
+                },
+            };
+            res.push(SearchEntry {
+                name: doc.id.name.clone(),
+                kind: kind(&doc.item),
+                href: page.clone(),
+                summary: summary(&docs),
+            });
+            let object = matches!(doc.item, DocItem::Object(_));
+            for (member, item) in members.into_iter().flatten() {
+                let (kind, docs) = match item {
+                    DocMember::Function(f) => ("function", &f.docs),
+                    DocMember::Property(p) => ("property", &p.docs),
+                };
+                res.push(SearchEntry {
+                    name: if object {
+                        format!("{}.{}", doc.id.name, member)
+                    } else {
+                        member.clone()
+                    },
+                    kind,
+                    href: format!("{}#{}", page, slug(member)),
+                    summary: summary(docs),
+                });
+            }
+        }
+        res
+    }
+
+    fn index_page(&self, docs: &[Doc], search_index: &str) -> String {
+        let mut body = String::from(
+            "<h1>Documentation</h1>\n<input id=\"search\" type=\"search\" placeholder=\"Search\" autofocus>\n<ul id=\"results\"></ul>\n",
+        );
+        for (title, k) in [
+            ("Modules", "module"),
+            ("Types", "object"),
+            ("Functions", "function"),
+            ("Properties", "property"),
+        ] {
+            let items: Vec<_> = docs
+                .iter()
+                .zip(&self.pages)
+                .filter(|(doc, _)| kind(&doc.item) == k)
+                .sorted_by(|a, b| a.0.id.name.cmp(&b.0.id.name))
+                .collect();
+            if items.is_empty() {
+                continue;
+            }
+            writeln!(body, "<h2>{}</h2>\n<ul class=\"index\">", title).unwrap();
+            for (doc, page) in items {
+                let docs = match &doc.item {
+                    DocItem::Module(m) => &m.docs,
+                    DocItem::Object(o) => &o.docs,
+                    DocItem::Function(f) => &f.docs,
+                    DocItem::Property(p) => &p.docs,
+                };
+                writeln!(
+                    body,
+                    "<li><a href=\"{}\">{}</a> {}</li>",
+                    page,
+                    escape(&doc.id.name),
+                    self.inline(&summary(docs))
+                )
+                .unwrap();
+            }
+            body.push_str("</ul>\n");
+        }
+        // Make sure the index can't close the script tag early
+        writeln!(
+            body,
+            "<script>\nconst SEARCH_INDEX = {};\n{}</script>",
+            search_index.replace("</", "<\\/"),
+            SEARCH_SCRIPT
+        )
+        .unwrap();
+        Self::page("Documentation", &body)
+    }
+}
+
+/// Render a series of [`Doc`] objects as a set of HTML pages.
+///
+/// Produces an `index.html` with a list of all symbols and a search box,
+/// one page per [`Doc`], and `search-index.json` with the data used by the search box.
+/// Types and code referring to documented symbols link to their documentation.
+/// The pages don't depend on any external resources.
+pub fn render_docs_as_html(docs: &[Doc]) -> Vec<HtmlFile> {
+    let renderer = Renderer::new(docs);
+    let search_index = serde_json::to_string(&renderer.search_index(docs)).unwrap();
+
+    let mut res = Vec::with_capacity(docs.len() + 2);
+    res.push(HtmlFile {
+        path: "index.html".to_owned(),
+        contents: renderer.index_page(docs, &search_index),
+    });
+    for (doc, page) in docs.iter().zip(&renderer.pages) {
+        res.push(HtmlFile {
+            path: page.clone(),
+            contents: renderer.doc_page(doc),
+        });
+    }
+    res.push(HtmlFile {
+        path: "search-index.json".to_owned(),
+        contents: search_index,
+    });
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::docs::html::render_docs_as_html;
+    use crate::docs::Doc;
+    use crate::docs::DocFunction;
+    use crate::docs::DocItem;
+    use crate::docs::DocMember;
+    use crate::docs::DocObject;
+    use crate::docs::DocParam;
+    use crate::docs::DocReturn;
+    use crate::docs::DocString;
+    use crate::docs::DocType;
+    use crate::docs::Identifier;
+
+    fn doc(name: &str, item: DocItem) -> Doc {
+        Doc {
+            id: Identifier {
+                name: name.to_owned(),
+                location: None,
+            },
+            item,
+            custom_attrs: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_html() {
+        let artifact = doc(
+            "Artifact",
+            DocItem::Object(DocObject {
+                docs: Some(DocString {
+                    summary: "A file <on disk>".to_owned(),
+                    details: None,
+                }),
+                members: [(
+                    "basename".to_owned(),
+                    DocMember::Function(DocFunction::default()),
+                )]
+                .into_iter()
+                .collect(),
+            }),
+        );
+        let copy = doc(
+            "copy",
+            DocItem::Function(DocFunction {
+                docs: Some(DocString {
+                    summary: "Copy an `Artifact`".to_owned(),
+                    details: Some("* First\n* Second\n\n```\nx < y\n```".to_owned()),
+                }),
+                params: vec![DocParam::Arg {
+                    name: "src".to_owned(),
+                    docs: None,
+                    typ: Some(DocType {
+                        raw_type: "\"Artifact\"".to_owned(),
+                    }),
+                    default_value: None,
+                }],
+                ret: DocReturn::default(),
+            }),
+        );
+        let files = render_docs_as_html(&[artifact, copy]);
+        let paths: Vec<_> = files.iter().map(|x| x.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "index.html",
+                "Artifact.html",
+                "copy.html",
+                "search-index.json"
+            ]
+        );
+
+        let index = &files[0].contents;
+        assert!(index.contains(r#"<a href="Artifact.html">Artifact</a> A file &lt;on disk&gt;"#));
+        assert!(index.contains(r#"{"name":"Artifact.basename","kind":"function","href":"Artifact.html#basename","summary":""}"#));
+
+        let copy = &files[2].contents;
+        assert!(copy.contains(
+            r#"<pre><code>def copy(src: &quot;<a href="Artifact.html">Artifact</a>&quot;)</code></pre>"#
+        ));
+        assert!(
+            copy.contains(r#"<p>Copy an <code><a href="Artifact.html">Artifact</a></code></p>"#)
+        );
+        assert!(copy.contains("<ul>\n<li>First</li>\n<li>Second</li>\n</ul>"));
+        assert!(copy.contains("<pre><code>x &lt; y</code></pre>"));
+    }
+}
@@ -0,0 +1,272 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Export documentation as JSON, in a versioned format described by a JSON schema.
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::docs::Doc;
+
+/// The version of the JSON produced by [`render_docs_as_json`].
+/// Only incremented when the format changes in a way that would break existing readers.
+pub const DOCS_JSON_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+enum DocsJsonError {
+    #[error(
+        "Unsupported documentation JSON version {0}, expected {}",
+        DOCS_JSON_VERSION
+    )]
+    UnsupportedVersion(u32),
+}
+
+/// The top-level object of the documentation JSON, as described by [`docs_json_schema`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocsJson {
+    /// The format version, see [`DOCS_JSON_VERSION`].
+    pub version: u32,
+    /// The documentation for each symbol, in the order it was given.
+    pub docs: Vec<Doc>,
+}
+
+/// Render a series of [`Doc`] objects as a JSON document matching [`docs_json_schema`].
+pub fn render_docs_as_json(docs: &[Doc]) -> String {
+    // Serializing the underlying types can't fail, they have no non-string keys
+    serde_json::to_string_pretty(&json!({
+        "version": DOCS_JSON_VERSION,
+        "docs": docs,
+    }))
+    .unwrap()
+}
+
+/// Parse the output of [`render_docs_as_json`], failing if it was produced with a different version.
+pub fn parse_docs_json(json: &str) -> anyhow::Result<Vec<Doc>> {
+    let res: DocsJson = serde_json::from_str(json)?;
+    if res.version != DOCS_JSON_VERSION {
+        return Err(DocsJsonError::UnsupportedVersion(res.version).into());
+    }
+    Ok(res.docs)
+}
+
+/// The JSON schema (draft 2020-12) of the output of [`render_docs_as_json`].
+///
+/// Rules are documented as functions with a parameter per attribute,
+/// and providers as objects with a property per field.
+pub fn docs_json_schema() -> serde_json::Value {
+    fn nullable(schema: serde_json::Value) -> serde_json::Value {
+        json!({ "oneOf": [{ "type": "null" }, schema] })
+    }
+
+    fn tagged(kind: &str, def: &str) -> serde_json::Value {
+        json!({
+            "allOf": [
+                {
+                    "type": "object",
+                    "properties": { "kind": { "const": kind } },
+                    "required": ["kind"],
+                },
+                { "$ref": format!("#/$defs/{def}") },
+            ]
+        })
+    }
+
+    fn param(kind: &str, default_value: bool) -> serde_json::Value {
+        let mut properties = json!({
+            "kind": { "const": kind },
+            "name": { "type": "string" },
+            "docs": nullable(json!({ "$ref": "#/$defs/DocString" })),
+            "type": nullable(json!({ "$ref": "#/$defs/DocType" })),
+        });
+        if default_value {
+            properties["default_value"] = nullable(json!({
+                "type": "string",
+                "description": "The `repr()` of the default value, if the parameter has one.",
+            }));
+        }
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": ["kind", "name"],
+        })
+    }
+
+    let docs_and_type = json!({
+        "type": "object",
+        "properties": {
+            "docs": nullable(json!({ "$ref": "#/$defs/DocString" })),
+            "type": nullable(json!({ "$ref": "#/$defs/DocType" })),
+        },
+    });
+    let members = json!({
+        "type": "object",
+        "properties": {
+            "docs": nullable(json!({ "$ref": "#/$defs/DocString" })),
+            "members": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/$defs/DocMember" },
+            },
+        },
+        "required": ["members"],
+    });
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Starlark documentation",
+        "type": "object",
+        "properties": {
+            "version": { "const": DOCS_JSON_VERSION },
+            "docs": { "type": "array", "items": { "$ref": "#/$defs/Doc" } },
+        },
+        "required": ["version", "docs"],
+        "$defs": {
+            "Doc": {
+                "type": "object",
+                "properties": {
+                    "id": { "$ref": "#/$defs/Identifier" },
+                    "item": { "$ref": "#/$defs/DocItem" },
+                    "custom_attrs": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" },
+                    },
+                },
+                "required": ["id", "item", "custom_attrs"],
+            },
+            "Identifier": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "location": nullable(json!({ "$ref": "#/$defs/Location" })),
+                },
+                "required": ["name"],
+            },
+            "Location": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "position": nullable(json!({ "$ref": "#/$defs/Pos" })),
+                },
+                "required": ["path"],
+            },
+            "Pos": {
+                "type": "object",
+                "properties": {
+                    "line": { "type": "integer", "minimum": 0 },
+                    "column": { "type": "integer", "minimum": 0 },
+                },
+                "required": ["line", "column"],
+            },
+            "DocString": {
+                "type": "object",
+                "properties": {
+                    "summary": { "type": "string" },
+                    "details": nullable(json!({ "type": "string" })),
+                },
+                "required": ["summary"],
+            },
+            "DocType": {
+                "type": "object",
+                "properties": { "raw_type": { "type": "string" } },
+                "required": ["raw_type"],
+            },
+            "DocItem": {
+                "oneOf": [
+                    tagged("module", "DocModule"),
+                    tagged("object", "DocObject"),
+                    tagged("function", "DocFunction"),
+                    tagged("property", "DocProperty"),
+                ],
+            },
+            "DocMember": {
+                "oneOf": [
+                    tagged("function", "DocFunction"),
+                    tagged("property", "DocProperty"),
+                ],
+            },
+            "DocModule": members,
+            "DocObject": members,
+            "DocFunction": {
+                "type": "object",
+                "properties": {
+                    "docs": nullable(json!({ "$ref": "#/$defs/DocString" })),
+                    "params": { "type": "array", "items": { "$ref": "#/$defs/DocParam" } },
+                    "ret": { "$ref": "#/$defs/DocReturn" },
+                },
+                "required": ["params", "ret"],
+            },
+            "DocParam": {
+                "oneOf": [
+                    param("arg", true),
+                    {
+                        "type": "object",
+                        "properties": { "kind": { "const": "no_args" } },
+                        "required": ["kind"],
+                    },
+                    param("args", false),
+                    param("kwargs", false),
+                ],
+            },
+            "DocReturn": docs_and_type,
+            "DocProperty": docs_and_type,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::docs::json::docs_json_schema;
+    use crate::docs::json::parse_docs_json;
+    use crate::docs::json::render_docs_as_json;
+    use crate::docs::Doc;
+    use crate::docs::Identifier;
+    use crate::environment::Globals;
+
+    #[test]
+    fn test_json_round_trip() {
+        let docs = vec![Doc {
+            id: Identifier {
+                name: "globals".to_owned(),
+                location: None,
+            },
+            item: Globals::extended().documentation(),
+            custom_attrs: HashMap::new(),
+        }];
+        let json = render_docs_as_json(&docs);
+        assert!(json.contains(r#""version": 1"#), "{}", json);
+        assert_eq!(docs, parse_docs_json(&json).unwrap());
+
+        let old = json.replace(r#""version": 1"#, r#""version": 0"#);
+        assert_eq!(
+            parse_docs_json(&old).unwrap_err().to_string(),
+            "Unsupported documentation JSON version 0, expected 1"
+        );
+    }
+
+    #[test]
+    fn test_json_schema_defs() {
+        // Every reference in the schema should point at a definition
+        let schema = docs_json_schema();
+        let text = schema.to_string();
+        for reference in text.split(r##""$ref":"#/$defs/"##).skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(schema["$defs"].get(name).is_some(), "Missing {}", name);
+        }
+    }
+}
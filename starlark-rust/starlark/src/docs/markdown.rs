@@ -89,15 +89,19 @@ fn render_doc_string(opts: DSOpts, string: &Option<DocString>) -> Option<String>
 }
 
 fn render_property(name: &str, property: &DocProperty) -> String {
+    format!("## {name}\n\n{}", render_property_body(name, property))
+}
+
+/// The prototype and documentation of a property, without a header.
+fn render_property_body(name: &str, property: &DocProperty) -> String {
     let prototype = render_code_block(&format!(
         "{name}: {}",
         TypeRenderer::Type(&property.typ).render_markdown(MarkdownFlavor::DocFile)
     ));
-    let header = format!("## {name}\n\n{prototype}");
     let summary = render_doc_string(DSOpts::Summary, &property.docs);
     let details = render_doc_string(DSOpts::Details, &property.docs);
 
-    let mut body = header;
+    let mut body = prototype;
     if let Some(summary) = summary {
         body.push_str("\n\n");
         body.push_str(&summary);
@@ -144,6 +148,11 @@ fn render_function_parameters(params: &[DocParam]) -> Option<String> {
 }
 
 fn render_function(name: &str, function: &DocFunction) -> String {
+    format!("## {name}\n\n{}", render_function_body(name, function))
+}
+
+/// The prototype and documentation of a function, without a header.
+fn render_function_body(name: &str, function: &DocFunction) -> String {
     let prototype = render_code_block(
         &(TypeRenderer::Function {
             function_name: name,
@@ -151,14 +160,13 @@ fn render_function(name: &str, function: &DocFunction) -> String {
         }
         .render_markdown(MarkdownFlavor::DocFile)),
     );
-    let header = format!("## {name}\n\n{prototype}");
     let summary = render_doc_string(DSOpts::Summary, &function.docs);
     let details = render_doc_string(DSOpts::Details, &function.docs);
 
     let parameter_docs = render_function_parameters(&function.params);
     let return_docs = render_doc_string(DSOpts::Combined, &function.ret.docs);
 
-    let mut body = header;
+    let mut body = prototype;
     if let Some(summary) = summary {
         body.push_str("\n\n");
         body.push_str(&summary);
@@ -231,11 +239,27 @@ fn render_doc_item(name: &str, item: &DocItem) -> String {
     }
 }
 
+/// Render a short summary of an item, e.g. for hovering over a symbol in an editor.
+fn render_doc_item_summary(name: &str, item: &DocItem) -> String {
+    match &item {
+        DocItem::Module(DocModule { docs, .. }) | DocItem::Object(DocObject { docs, .. }) => {
+            let mut body = render_code_block(name);
+            if let Some(docs) = render_doc_string(DSOpts::Combined, docs) {
+                body.push_str("\n\n");
+                body.push_str(&docs);
+            }
+            body
+        }
+        DocItem::Function(f) => render_function_body(name, f),
+        DocItem::Property(p) => render_property_body(name, p),
+    }
+}
+
 impl RenderMarkdown for Doc {
     fn render_markdown_opt(&self, flavor: MarkdownFlavor) -> Option<String> {
         match flavor {
             MarkdownFlavor::DocFile => Some(render_doc_item(&self.id.name, &self.item)),
-            MarkdownFlavor::LspSummary => None,
+            MarkdownFlavor::LspSummary => Some(render_doc_item_summary(&self.id.name, &self.item)),
         }
     }
 }
@@ -283,7 +307,7 @@ impl<'a> RenderMarkdown for TypeRenderer<'a> {
         }
 
         match flavor {
-            MarkdownFlavor::DocFile => match self {
+            MarkdownFlavor::DocFile | MarkdownFlavor::LspSummary => match self {
                 TypeRenderer::Type(t) => Some(raw_type(t)),
                 TypeRenderer::Function { function_name, f } => {
                     let mut params = f.params.iter().map(|p| match p {
@@ -321,7 +345,6 @@ impl<'a> RenderMarkdown for TypeRenderer<'a> {
                     }
                 }
             },
-            MarkdownFlavor::LspSummary => None,
         }
     }
 }
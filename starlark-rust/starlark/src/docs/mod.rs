@@ -20,13 +20,25 @@
 // TODO(nga): document it
 #![allow(missing_docs)]
 
+mod doctest;
+mod html;
+mod json;
 mod markdown;
 
 use std::collections::HashMap;
 
 use allocative::Allocative;
+pub use doctest::DocTest;
+pub use doctest::DocTestExample;
 use dupe::Dupe;
+pub use html::render_docs_as_html;
+pub use html::HtmlFile;
 use itertools::Itertools;
+pub use json::docs_json_schema;
+pub use json::parse_docs_json;
+pub use json::render_docs_as_json;
+pub use json::DocsJson;
+pub use json::DOCS_JSON_VERSION;
 pub use markdown::MarkdownFlavor;
 pub use markdown::RenderMarkdown;
 use once_cell::sync::Lazy;
@@ -42,6 +54,7 @@ use crate::codemap::Spanned;
 use crate::syntax::ast::AstLiteral;
 use crate::syntax::ast::AstPayload;
 use crate::syntax::ast::AstStmtP;
+use crate::syntax::ast::AstString;
 use crate::syntax::ast::ExprP;
 use crate::syntax::ast::StmtP;
 use crate::values::StarlarkValue;
@@ -161,6 +174,13 @@ impl DocString {
     pub(crate) fn extract_raw_starlark_docstring<P: AstPayload>(
         body: &AstStmtP<P>,
     ) -> Option<String> {
+        Self::find_raw_starlark_docstring(body).map(|s| s.node.to_owned())
+    }
+
+    /// Like `extract_raw_starlark_docstring`, but returns the string literal with its span.
+    pub(crate) fn find_raw_starlark_docstring<P: AstPayload>(
+        body: &AstStmtP<P>,
+    ) -> Option<&AstString> {
         if let StmtP::Statements(stmts) = &body.node {
             if let Some(Spanned {
                 node:
@@ -171,7 +191,7 @@ impl DocString {
                 ..
             }) = stmts.first()
             {
-                return Some(s.node.to_owned());
+                return Some(s);
             }
         };
         None
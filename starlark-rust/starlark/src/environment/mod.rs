@@ -24,11 +24,13 @@
 
 mod globals;
 mod module_dump;
+mod module_serialize;
 mod modules;
 pub(crate) mod names;
 pub(crate) mod slots;
 
 pub use globals::*;
+pub use module_serialize::*;
 pub use modules::*;
 use thiserror::Error;
 
@@ -0,0 +1,770 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Serialization of frozen modules, so an embedder can cache evaluated modules and
+//! [`reload`](SerializedModule::reload) them without evaluating them again.
+//!
+//! Values are encoded with a [`ValueSerializer`]. Symbols of the loaded modules and globals
+//! are stored as references to them.
+//!
+//! Functions created by the module (`def` and `lambda`, including nested ones) are stored as
+//! the values their definition evaluated (parameter defaults and types, return type) and the
+//! values they captured. The source of the module is stored with them: on reload it is compiled
+//! again, without running it, to get the bodies and docstrings of the functions. Functions
+//! created by other modules can only be serialized if these modules export them.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ptr;
+use std::rc::Rc;
+
+use dupe::Dupe;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::collections::SmallMap;
+use crate::environment::FrozenModule;
+use crate::environment::FrozenModuleData;
+use crate::environment::Globals;
+use crate::environment::Module;
+use crate::eval::compiler::def::FrozenDef;
+use crate::eval::Evaluator;
+use crate::syntax::ast::Visibility;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+use crate::values::layout::pointer::RawPointer;
+use crate::values::layout::value_captured::value_captured_get;
+use crate::values::layout::value_captured::FrozenValueCaptured;
+use crate::values::serialize::CustomSerializer;
+use crate::values::serialize::SerializedValue;
+use crate::values::serialize::ValueSerializer;
+use crate::values::FrozenHeap;
+use crate::values::FrozenRef;
+use crate::values::FrozenValue;
+use crate::values::Value;
+use crate::values::ValueLike;
+
+/// Version of the encoding, bumped on incompatible changes.
+const FORMAT_VERSION: u32 = 2;
+
+/// Names of the custom serializers used for references. These names can't be used by
+/// custom serializers of the embedder.
+const LOAD_TYPE: &str = "starlark.load";
+const GLOBAL_TYPE: &str = "starlark.global";
+const DEF_TYPE: &str = "starlark.def";
+
+#[derive(Debug, Error)]
+enum ModuleSerializeError {
+    #[error("Modules with an extra value can't be serialized")]
+    ExtraValue,
+    #[error("Serialized module has version `{0}`, expected `{}`", FORMAT_VERSION)]
+    Version(u32),
+    #[error("Serialized module refers to module `{0}`, which was not provided")]
+    UnknownLoad(String),
+    #[error("Serialized module refers to global `{0}`, which is not defined")]
+    UnknownGlobal(String),
+    #[error("Invalid reference in serialized module")]
+    InvalidReference,
+    #[error("Function `{0}` was created by another module, which doesn't export it")]
+    ForeignDef(String),
+    #[error("Function `{0}` is not in `{1}`, the file of the other functions of the module")]
+    OtherFile(String, String),
+    #[error("Serialized module has a function at {0}..{1}, which is not in its source")]
+    UnknownDef(u32, u32),
+}
+
+/// A [`FrozenModule`] in a form which can be written with any [`serde`] format, or with
+/// [`to_bytes`](SerializedModule::to_bytes), and turned back into an equivalent module with
+/// [`reload`](SerializedModule::reload).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SerializedModule {
+    version: u32,
+    docstring: Option<String>,
+    /// File name and source of the module, to compile its functions again.
+    /// `None` if the module has no functions.
+    source: Option<(String, String)>,
+    /// Functions created by the module, dependencies first.
+    functions: Vec<SerializedDef>,
+    variables: Vec<SerializedVariable>,
+}
+
+/// A function created by the module.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SerializedDef {
+    /// Span of the signature, which identifies the `def` or `lambda` in the source.
+    signature: (u32, u32),
+    /// The values of the parameter types, defaults and return type, see
+    /// [`FrozenDef::evaluated_values`].
+    values: Vec<SerializedValue>,
+    /// Values of the captured variables, `None` for variables not assigned yet.
+    captured: Vec<Option<SerializedValue>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SerializedVariable {
+    name: String,
+    public: bool,
+    value: VariableValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum VariableValue {
+    /// The name is declared, but was not assigned during evaluation.
+    Unset,
+    /// Any other value.
+    Value(SerializedValue),
+}
+
+/// Symbols of the loaded modules, as the module name and symbol name.
+struct LoadRefs {
+    by_ptr: HashMap<RawPointer, (String, String)>,
+    modules: SmallMap<String, FrozenModule>,
+}
+
+impl LoadRefs {
+    fn new(loads: &[(&str, &FrozenModule)]) -> Self {
+        let mut by_ptr = HashMap::new();
+        for (id, module) in loads {
+            for (name, value) in module.all_items() {
+                by_ptr
+                    .entry(value.ptr_value())
+                    .or_insert_with(|| ((*id).to_owned(), name.as_str().to_owned()));
+            }
+        }
+        LoadRefs {
+            by_ptr,
+            modules: loads
+                .iter()
+                .map(|(id, module)| ((*id).to_owned(), (*module).clone()))
+                .collect(),
+        }
+    }
+
+    fn encode(&self, value: Value) -> Option<SerializedValue> {
+        let (module, name) = self.by_ptr.get(&value.ptr_value())?;
+        Some(SerializedValue::Tuple(vec![
+            SerializedValue::String(module.clone()),
+            SerializedValue::String(name.clone()),
+        ]))
+    }
+
+    fn reference(&self, value: Value) -> Option<SerializedValue> {
+        Some(SerializedValue::Custom {
+            typ: LOAD_TYPE.to_owned(),
+            value: Box::new(self.encode(value)?),
+        })
+    }
+}
+
+impl CustomSerializer for LoadRefs {
+    fn to_serialized(
+        &self,
+        value: Value,
+        _serializer: &ValueSerializer,
+    ) -> Option<anyhow::Result<SerializedValue>> {
+        self.encode(value).map(Ok)
+    }
+
+    fn from_serialized(
+        &self,
+        value: &SerializedValue,
+        _serializer: &ValueSerializer,
+        heap: &FrozenHeap,
+    ) -> anyhow::Result<FrozenValue> {
+        let (module, name) = match value {
+            SerializedValue::Tuple(xs) if xs.len() == 2 => {
+                (unpack_string(&xs[0])?, unpack_string(&xs[1])?)
+            }
+            _ => return Err(ModuleSerializeError::InvalidReference.into()),
+        };
+        let module = self
+            .modules
+            .get(module)
+            .ok_or_else(|| ModuleSerializeError::UnknownLoad(module.to_owned()))?;
+        let (value, _) = module.get_any_visibility(name)?;
+        // Safe because the heap keeps a reference to the loaded module.
+        Ok(unsafe { value.owned_frozen_value(heap) })
+    }
+}
+
+/// Values of the globals, by name.
+struct GlobalRefs {
+    by_ptr: HashMap<RawPointer, String>,
+    globals: Globals,
+}
+
+impl GlobalRefs {
+    fn new(globals: &Globals) -> Self {
+        let mut by_ptr = HashMap::new();
+        for name in globals.names() {
+            if let Some(value) = globals.get_frozen(name.as_str()) {
+                by_ptr
+                    .entry(value.ptr_value())
+                    .or_insert_with(|| name.as_str().to_owned());
+            }
+        }
+        GlobalRefs {
+            by_ptr,
+            globals: globals.dupe(),
+        }
+    }
+
+    fn encode(&self, value: Value) -> Option<SerializedValue> {
+        let name = self.by_ptr.get(&value.ptr_value())?;
+        Some(SerializedValue::String(name.clone()))
+    }
+
+    fn reference(&self, value: Value) -> Option<SerializedValue> {
+        Some(SerializedValue::Custom {
+            typ: GLOBAL_TYPE.to_owned(),
+            value: Box::new(self.encode(value)?),
+        })
+    }
+}
+
+impl CustomSerializer for GlobalRefs {
+    fn to_serialized(
+        &self,
+        value: Value,
+        _serializer: &ValueSerializer,
+    ) -> Option<anyhow::Result<SerializedValue>> {
+        self.encode(value).map(Ok)
+    }
+
+    fn from_serialized(
+        &self,
+        value: &SerializedValue,
+        _serializer: &ValueSerializer,
+        heap: &FrozenHeap,
+    ) -> anyhow::Result<FrozenValue> {
+        let name = unpack_string(value)?;
+        let value = self
+            .globals
+            .get_frozen(name)
+            .ok_or_else(|| ModuleSerializeError::UnknownGlobal(name.to_owned()))?;
+        heap.add_reference(self.globals.heap());
+        Ok(value)
+    }
+}
+
+/// Functions created by the module being serialized, stored in [`SerializedModule::functions`]
+/// and encoded as their index there.
+struct DefRefs {
+    module: FrozenRef<'static, FrozenModuleData>,
+    by_ptr: RefCell<HashMap<RawPointer, usize>>,
+    functions: RefCell<Vec<SerializedDef>>,
+    source: RefCell<Option<(String, String)>>,
+}
+
+impl DefRefs {
+    fn encode(
+        &self,
+        value: Value,
+        def: &FrozenDef,
+        serializer: &ValueSerializer,
+    ) -> anyhow::Result<SerializedValue> {
+        if let Some(index) = self.by_ptr.borrow().get(&value.ptr_value()) {
+            return Ok(SerializedValue::Int(*index as i32));
+        }
+        let name = def.def_info.name.as_str();
+        let ours = def
+            .module
+            .load_relaxed()
+            .map_or(false, |m| ptr::eq(m.as_ref(), self.module.as_ref()));
+        if !ours {
+            return Err(ModuleSerializeError::ForeignDef(name.to_owned()).into());
+        }
+
+        let file = def.def_info.codemap;
+        match &*self.source.borrow() {
+            Some((filename, _)) if filename != file.filename() => {
+                return Err(
+                    ModuleSerializeError::OtherFile(name.to_owned(), filename.clone()).into(),
+                );
+            }
+            _ => {}
+        }
+
+        // Values are encoded first, so the functions they contain come before this one.
+        let encoded = (|| {
+            let values = def
+                .evaluated_values()
+                .into_iter()
+                .map(|v| serializer.to_serialized(v))
+                .collect::<anyhow::Result<_>>()?;
+            let captured = def
+                .captured
+                .iter()
+                .map(|c| {
+                    value_captured_get(c.to_value())
+                        .map(|v| serializer.to_serialized_value(v))
+                        .transpose()
+                })
+                .collect::<anyhow::Result<_>>()?;
+            anyhow::Ok((values, captured))
+        })();
+        let (values, captured) =
+            encoded.map_err(|e| e.context(format!("Serializing function `{}`", name)))?;
+
+        let span = def.def_info.signature_span.span();
+        let mut functions = self.functions.borrow_mut();
+        functions.push(SerializedDef {
+            signature: (span.begin().get(), span.end().get()),
+            values,
+            captured,
+        });
+        let index = functions.len() - 1;
+        self.by_ptr.borrow_mut().insert(value.ptr_value(), index);
+        self.source
+            .borrow_mut()
+            .get_or_insert_with(|| (file.filename().to_owned(), file.source().to_owned()));
+        Ok(SerializedValue::Int(index as i32))
+    }
+}
+
+impl CustomSerializer for Rc<DefRefs> {
+    fn to_serialized(
+        &self,
+        value: Value,
+        serializer: &ValueSerializer,
+    ) -> Option<anyhow::Result<SerializedValue>> {
+        let def = value.downcast_ref::<FrozenDef>()?;
+        Some(self.encode(value, def, serializer))
+    }
+
+    fn from_serialized(
+        &self,
+        _value: &SerializedValue,
+        _serializer: &ValueSerializer,
+        _heap: &FrozenHeap,
+    ) -> anyhow::Result<FrozenValue> {
+        Err(ModuleSerializeError::InvalidReference.into())
+    }
+}
+
+/// Functions rebuilt so far when reloading a module, by their index in
+/// [`SerializedModule::functions`].
+struct ReloadedDefs(Rc<RefCell<Vec<FrozenValue>>>);
+
+impl CustomSerializer for ReloadedDefs {
+    fn to_serialized(
+        &self,
+        _value: Value,
+        _serializer: &ValueSerializer,
+    ) -> Option<anyhow::Result<SerializedValue>> {
+        None
+    }
+
+    fn from_serialized(
+        &self,
+        value: &SerializedValue,
+        _serializer: &ValueSerializer,
+        _heap: &FrozenHeap,
+    ) -> anyhow::Result<FrozenValue> {
+        // Functions only refer to the ones before them, so the reference is already rebuilt.
+        let index = match value {
+            SerializedValue::Int(x) => usize::try_from(*x).ok(),
+            _ => None,
+        };
+        index
+            .and_then(|i| self.0.borrow().get(i).copied())
+            .ok_or_else(|| ModuleSerializeError::InvalidReference.into())
+    }
+}
+
+fn unpack_string(x: &SerializedValue) -> anyhow::Result<&str> {
+    match x {
+        SerializedValue::String(x) => Ok(x),
+        _ => Err(ModuleSerializeError::InvalidReference.into()),
+    }
+}
+
+impl FrozenModule {
+    /// Encode the module, with values encoded by `serializer`.
+    ///
+    /// `loads` are the modules this module loaded, with names identifying them, and `globals` are
+    /// the globals the module was evaluated with. The same modules, names and globals must be
+    /// passed to [`reload`](SerializedModule::reload).
+    pub fn serialize(
+        &self,
+        mut serializer: ValueSerializer,
+        loads: &[(&str, &FrozenModule)],
+        globals: &Globals,
+    ) -> anyhow::Result<SerializedModule> {
+        if self.extra_value().is_some() {
+            return Err(ModuleSerializeError::ExtraValue.into());
+        }
+        let module = self.module_data();
+
+        let loads = LoadRefs::new(loads);
+        let globals = GlobalRefs::new(globals);
+
+        let mut variables = Vec::new();
+        for (name, slot) in module.names.all_symbols() {
+            let (_, vis) = module.names.get_name(name.as_str()).unwrap();
+            let value = module.get_slot(slot);
+            // Prefer references to loaded symbols and globals to copies of them.
+            let reference = value.and_then(|v| {
+                loads
+                    .reference(v.to_value())
+                    .or_else(|| globals.reference(v.to_value()))
+            });
+            variables.push((name, vis, value, reference));
+        }
+
+        let defs = Rc::new(DefRefs {
+            module,
+            by_ptr: RefCell::new(HashMap::new()),
+            functions: RefCell::new(Vec::new()),
+            source: RefCell::new(None),
+        });
+        serializer.add_custom(LOAD_TYPE, loads);
+        serializer.add_custom(GLOBAL_TYPE, globals);
+        // After the references, so functions exported by loaded modules are referenced.
+        serializer.add_custom(DEF_TYPE, defs.dupe());
+
+        let variables = variables
+            .into_iter()
+            .map(|(name, vis, value, reference)| {
+                let value = match (value, reference) {
+                    (None, _) => VariableValue::Unset,
+                    (Some(_), Some(reference)) => VariableValue::Value(reference),
+                    (Some(value), None) => {
+                        let value = serializer.to_serialized(value).map_err(|e| {
+                            e.context(format!("Serializing variable `{}`", name.as_str()))
+                        })?;
+                        VariableValue::Value(value)
+                    }
+                };
+                Ok(SerializedVariable {
+                    name: name.as_str().to_owned(),
+                    public: vis == Visibility::Public,
+                    value,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        drop(serializer);
+        let defs = Rc::try_unwrap(defs)
+            .ok()
+            .expect("serializer dropped, so no other reference");
+        Ok(SerializedModule {
+            version: FORMAT_VERSION,
+            docstring: module.docstring().map(str::to_owned),
+            source: defs.source.into_inner(),
+            functions: defs.functions.into_inner(),
+            variables,
+        })
+    }
+}
+
+impl SerializedModule {
+    /// Rebuild the module. The arguments must match the ones passed to
+    /// [`serialize`](FrozenModule::serialize), except the values of loaded modules can
+    /// come from another evaluation of the same files. `dialect` must be the dialect the
+    /// module was parsed with, to compile its functions again.
+    pub fn reload(
+        &self,
+        mut serializer: ValueSerializer,
+        loads: &[(&str, &FrozenModule)],
+        globals: &Globals,
+        dialect: &Dialect,
+    ) -> anyhow::Result<FrozenModule> {
+        if self.version != FORMAT_VERSION {
+            return Err(ModuleSerializeError::Version(self.version).into());
+        }
+        let reloaded = Rc::new(RefCell::new(Vec::new()));
+        serializer.add_custom(LOAD_TYPE, LoadRefs::new(loads));
+        serializer.add_custom(GLOBAL_TYPE, GlobalRefs::new(globals));
+        serializer.add_custom(DEF_TYPE, ReloadedDefs(reloaded.dupe()));
+
+        let module = Module::new();
+        let mut frozen_defs = Vec::new();
+        if let Some((filename, source)) = &self.source {
+            // Compiling declares the names of the module in the same order as evaluating it did.
+            let ast = AstModule::parse(filename, source.clone(), dialect)?;
+            let compiled: HashMap<_, _> = Evaluator::new(&module)
+                .compile_module_defs(ast, globals)?
+                .into_iter()
+                .map(|def| {
+                    let span = def.info.signature_span.span();
+                    ((span.begin().get(), span.end().get()), def)
+                })
+                .collect();
+            for function in &self.functions {
+                let (begin, end) = function.signature;
+                let def = compiled
+                    .get(&function.signature)
+                    .ok_or(ModuleSerializeError::UnknownDef(begin, end))?;
+                let values = function
+                    .values
+                    .iter()
+                    .map(|x| serializer.from_serialized(x, module.frozen_heap()))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let captured = function
+                    .captured
+                    .iter()
+                    .map(|x| {
+                        let x = x
+                            .as_ref()
+                            .map(|x| serializer.from_serialized(x, module.frozen_heap()))
+                            .transpose()?;
+                        Ok(module
+                            .frozen_heap()
+                            .alloc_simple(FrozenValueCaptured::new(x)))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let def = FrozenDef::from_evaluated_values(def, &values, captured, module.heap())?;
+                let value = module.frozen_heap().alloc_simple(def);
+                frozen_defs.push(value.downcast_frozen_ref::<FrozenDef>().unwrap());
+                reloaded.borrow_mut().push(value);
+            }
+        }
+
+        for variable in &self.variables {
+            let name = module.frozen_heap().alloc_str_intern(&variable.name);
+            let vis = match variable.public {
+                true => Visibility::Public,
+                false => Visibility::Private,
+            };
+            let slot = module.names().add_name_visibility(name, vis);
+            module.slots().ensure_slot(slot);
+            if let VariableValue::Value(x) = &variable.value {
+                let value = serializer.from_serialized(x, module.frozen_heap())?;
+                module.slots().set_slot(slot, value.to_value());
+            }
+        }
+        if let Some(docstring) = &self.docstring {
+            module.set_docstring(docstring.clone());
+        }
+        Ok(module.freeze()?.post_freeze_defs(&frozen_defs))
+    }
+
+    /// Encode as CBOR.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut res = Vec::new();
+        ciborium::ser::into_writer(self, &mut res)?;
+        Ok(res)
+    }
+
+    /// Decode the encoding returned by [`to_bytes`](SerializedModule::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let res: Self = ciborium::de::from_reader(bytes)?;
+        if res.version != FORMAT_VERSION {
+            return Err(ModuleSerializeError::Version(res.version).into());
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::environment::FrozenModule;
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::environment::SerializedModule;
+    use crate::eval::compiler::def::FrozenDef;
+    use crate::eval::Evaluator;
+    use crate::eval::ReturnFileLoader;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+    use crate::values::serialize::ValueSerializer;
+    use crate::values::Heap;
+    use crate::values::ValueLike;
+
+    fn eval(
+        name: &str,
+        program: &str,
+        loads: &[(&str, &FrozenModule)],
+        globals: &Globals,
+    ) -> anyhow::Result<FrozenModule> {
+        let modules: HashMap<&str, &FrozenModule> = loads.iter().copied().collect();
+        let loader = ReturnFileLoader { modules: &modules };
+        let ast = AstModule::parse(name, program.to_owned(), &Dialect::Extended)?;
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            eval.set_loader(&loader);
+            eval.eval_module(ast, globals)?;
+        }
+        module.freeze()
+    }
+
+    fn round_trip(
+        module: &FrozenModule,
+        loads: &[(&str, &FrozenModule)],
+        globals: &Globals,
+    ) -> FrozenModule {
+        let serialized = module
+            .serialize(ValueSerializer::new(), loads, globals)
+            .unwrap();
+        let bytes = serialized.to_bytes().unwrap();
+        SerializedModule::from_bytes(&bytes)
+            .unwrap()
+            .reload(ValueSerializer::new(), loads, globals, &Dialect::Extended)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let globals = Globals::extended();
+        let lib = eval(
+            "lib.bzl",
+            "def helper(x):\n    return x + 1\ndef bad():\n    return 1 // 0\n",
+            &[],
+            &globals,
+        )
+        .unwrap();
+        let program = r#"
+"""Module docs."""
+load("lib.bzl", "helper", "bad")
+X = [1, ("a", None), {"k": 2.5}]
+_private = struct(f = helper, n = 3)
+RULES = struct(helper = helper, xs = [helper, len])
+alias = helper
+out = helper(3)
+my_len = len
+fails = bad
+"#;
+        let module = eval("m.bzl", program, &[("lib.bzl", &lib)], &globals).unwrap();
+        let reloaded = round_trip(&module, &[("lib.bzl", &lib)], &globals);
+
+        assert_eq!(Some("Module docs."), reloaded.module_data().docstring());
+        assert_eq!(
+            module.names().map(|x| x.as_str()).collect::<Vec<_>>(),
+            reloaded.names().map(|x| x.as_str()).collect::<Vec<_>>()
+        );
+
+        let check = r#"
+load("m.bzl", "RULES", "alias", "X", "out", "my_len")
+result = [RULES.helper(1), RULES.xs[1]([1, 2]), alias(2), X, out, my_len([1])]
+"#;
+        let run = |m: &FrozenModule| {
+            let check = eval("check.bzl", check, &[("m.bzl", m)], &globals).unwrap();
+            check.get("result").unwrap().value().to_repr()
+        };
+        assert_eq!(
+            "[2, 2, 3, [1, (\"a\", None), {\"k\": 2.5}], 4, 1]",
+            run(&module)
+        );
+        assert_eq!(run(&module), run(&reloaded));
+
+        // Re-exported functions are the functions of the loaded module.
+        assert!(
+            reloaded
+                .get("fails")
+                .unwrap()
+                .value()
+                .ptr_eq(lib.get("bad").unwrap().value())
+        );
+    }
+
+    #[test]
+    fn test_defs() {
+        let globals = Globals::extended();
+        let program = r#"
+def add(x, y = 10, *args, z: int.type = 1, **kwargs) -> int.type:
+    """Adds things."""
+    return x + y + z + len(args) + len(kwargs)
+def make_adder(n):
+    base = [n]
+    def adder(x):
+        return x + base[0]
+    return adder
+add5 = make_adder(5)
+double = lambda x: x * 2
+DEFAULT = [1]
+def default_list(x = DEFAULT):
+    return x
+RULES = struct(add = add, add5 = add5)
+"#;
+        let module = eval("m.bzl", program, &[], &globals).unwrap();
+        let reloaded = round_trip(&module, &[], &globals);
+
+        let check = r#"
+load("m.bzl", "add", "add5", "double", "default_list", "make_adder", "RULES", "DEFAULT")
+result = [
+    add(1), add(1, 2, 3, z = 4, w = 5), add5(1), double(3), default_list(),
+    default_list() == DEFAULT, make_adder(2)(1), RULES.add5(2), RULES.add(0, 0),
+]
+"#;
+        let run = |m: &FrozenModule| {
+            let check = eval("check.bzl", check, &[("m.bzl", m)], &globals).unwrap();
+            check.get("result").unwrap().value().to_repr()
+        };
+        assert_eq!("[12, 9, 6, 6, [1], True, 3, 7, 1]", run(&module));
+        assert_eq!(run(&module), run(&reloaded));
+
+        // The same function is rebuilt once.
+        let add = reloaded.get("add").unwrap();
+        let rules = reloaded.get("RULES").unwrap();
+        let heap = Heap::new();
+        let rules_add = rules.value().get_attr("add", &heap).unwrap().unwrap();
+        assert!(rules_add.ptr_eq(add.value()));
+
+        let add = add.value().downcast_ref::<FrozenDef>().unwrap();
+        assert_eq!(
+            Some("Adds things."),
+            add.def_info.docstring.as_deref()
+        );
+
+        // Parameter types are still checked.
+        let err = eval(
+            "bad.bzl",
+            "load(\"m.bzl\", \"add\")\nadd(1, z = \"s\")",
+            &[("m.bzl", &reloaded)],
+            &globals,
+        )
+        .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("does not match the type annotation `int`"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let globals = Globals::extended();
+        let lib = eval(
+            "lib.bzl",
+            "def make():\n    return lambda: 1\n",
+            &[],
+            &globals,
+        )
+        .unwrap();
+        let module = eval(
+            "m.bzl",
+            "load(\"lib.bzl\", \"make\")\nf = make()",
+            &[("lib.bzl", &lib)],
+            &globals,
+        )
+        .unwrap();
+        let err = module
+            .serialize(ValueSerializer::new(), &[("lib.bzl", &lib)], &globals)
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("was created by another module"),
+            "{:#}",
+            err
+        );
+    }
+}
@@ -43,6 +43,7 @@ use crate::environment::slots::ModuleSlotId;
 use crate::environment::slots::MutableSlots;
 use crate::environment::EnvironmentError;
 use crate::errors::did_you_mean::did_you_mean;
+use crate::eval::compiler::def::FrozenDef;
 use crate::eval::runtime::profile::heap::RetainedHeapProfileMode;
 use crate::eval::ProfileData;
 use crate::syntax::ast::Visibility;
@@ -201,6 +202,29 @@ impl FrozenModule {
         self.module.all_items()
     }
 
+    pub(crate) fn module_data(&self) -> FrozenRef<'static, FrozenModuleData> {
+        self.module
+    }
+
+    /// Finish functions allocated directly on the frozen heap of the module before it was frozen,
+    /// which [`Module::freeze`] doesn't see, like it finishes the functions it freezes.
+    pub(crate) fn post_freeze_defs(self, defs: &[FrozenRef<'static, FrozenDef>]) -> FrozenModule {
+        if defs.is_empty() {
+            return self;
+        }
+        // The optimized bytecode goes to a new heap, which keeps the module alive.
+        let frozen_heap = FrozenHeap::new();
+        frozen_heap.add_reference(&self.heap);
+        let heap = Heap::new();
+        for def in defs {
+            def.post_freeze(self.module, &heap, &frozen_heap);
+        }
+        FrozenModule {
+            heap: frozen_heap.into_ref(),
+            ..self
+        }
+    }
+
     /// The documentation for the module, and all of its top level values
     ///
     /// Returns `(<module documentation>, { <symbol> : <that symbol's documentation> })`
@@ -283,6 +307,11 @@ impl FrozenModuleData {
         None
     }
 
+    /// The raw docstring of the module.
+    pub(crate) fn docstring(&self) -> Option<&str> {
+        self.docstring.as_deref()
+    }
+
     pub(crate) fn documentation(&self) -> Option<DocString> {
         self.docstring
             .as_ref()
@@ -329,6 +358,16 @@ impl Module {
         &self.names
     }
 
+    /// Names of the variables declared in the module, including private ones,
+    /// whether they are assigned or not.
+    pub fn variable_names(&self) -> Vec<FrozenStringValue> {
+        self.names
+            .all_names_and_visibilities()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
     pub(crate) fn slots<'v>(&'v self) -> &'v MutableSlots<'v> {
         // Not true because of variance, but mostly true. Don't export further.
         unsafe { transmute!(&'v MutableSlots<'static>, &'v MutableSlots<'v>, &self.slots) }
@@ -454,6 +493,15 @@ impl Module {
         }
     }
 
+    /// Import all the symbols from a module, including the private ones, as if the code was
+    /// running in that module.
+    pub(crate) fn import_all_symbols(&self, module: &FrozenModule) {
+        self.frozen_heap.add_reference(&module.heap);
+        for (k, value) in module.all_items() {
+            self.set_private(k, Value::new_frozen(value))
+        }
+    }
+
     pub(crate) fn load_symbol<'v>(
         &'v self,
         module: &FrozenModule,
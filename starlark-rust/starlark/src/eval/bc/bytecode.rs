@@ -139,6 +139,19 @@ fn step<'v, 'b>(
 /// Execute the code block, either a module, a function body or a loop body.
 // Do not inline this function because it is called from two places: function and loop.
 pub(crate) fn run_block<'v>(
+    eval: &mut Evaluator<'v, '_>,
+    ip: BcPtrAddr,
+) -> Result<Value<'v>, EvalException> {
+    // Select the loop once per block, so evaluation without limits
+    // does not pay for budget accounting on every instruction.
+    if eval.budget.is_active() {
+        run_block_impl::<true>(eval, ip)
+    } else {
+        run_block_impl::<false>(eval, ip)
+    }
+}
+
+fn run_block_impl<'v, const BUDGET: bool>(
     eval: &mut Evaluator<'v, '_>,
     mut ip: BcPtrAddr,
 ) -> Result<Value<'v>, EvalException> {
@@ -146,6 +159,17 @@ pub(crate) fn run_block<'v>(
     let frame = eval.current_frame;
 
     loop {
+        if BUDGET {
+            // Cheap budget accounting: expensive checks are done only when fuel is exhausted.
+            if eval.budget.fuel == 0 {
+                let heap = eval.heap();
+                if let Err(e) = eval.budget.refuel(heap) {
+                    return Err(Bc::wrap_error_for_instr_ptr(ip, e, eval));
+                }
+            }
+            eval.budget.fuel -= 1;
+        }
+
         // Note most functions called from here must be carefully annotated
         // as `#[inline(always)]` otherwise LLVM considers them too large to inline.
         //
@@ -126,7 +126,7 @@ impl ExprCompiled {
             ExprCompiled::Local(local) => bc.mark_definitely_assigned(*local),
             ExprCompiled::LocalCaptured(_) => {}
             ExprCompiled::Module(_) => {}
-            ExprCompiled::Tuple(xs) | ExprCompiled::List(xs) => {
+            ExprCompiled::Tuple(xs) | ExprCompiled::List(xs) | ExprCompiled::FString(_, xs) => {
                 for x in xs {
                     x.mark_definitely_assigned_after(bc);
                 }
@@ -328,6 +328,11 @@ impl IrSpanned<ExprCompiled> {
                 }
             }
             ExprCompiled::Dict(ref xs) => Self::write_dict(span, xs, target, bc),
+            ExprCompiled::FString(format, xs) => {
+                write_exprs(xs, bc, |xs, bc| {
+                    bc.write_instr::<InstrFString>(span, (*format, xs, target));
+                });
+            }
             ExprCompiled::Compr(ref compr) => compr.write_bc(span, target, bc),
             ExprCompiled::Slice(l_start_stop_step) => {
                 let (l, start, stop, step) = &**l_start_stop_step;
@@ -52,6 +52,24 @@ pub(crate) fn write_if_then(
     wr(c, maybe_not, t, |_| unreachable!(), bc);
 }
 
+/// Write the condition of a `while` loop.
+///
+/// Generated code falls through if the condition is true,
+/// the returned addresses need to be patched with the loop exit.
+pub(crate) fn write_while_cond(c: &IrSpanned<ExprCompiled>, bc: &mut BcWriter) -> Vec<PatchAddr> {
+    if let Some(value) = c.as_value() {
+        if value.to_value().to_bool() {
+            // `while True:` only exits with `break` or `return`.
+            return Vec::new();
+        }
+    }
+    let mut then_addrs = Vec::new();
+    let mut else_addrs = Vec::new();
+    write_cond(c, MaybeNot::Id, &mut then_addrs, &mut else_addrs, bc);
+    bc.patch_addrs(then_addrs);
+    else_addrs
+}
+
 /// Common code for writing if-then or if-then-else expression or statement.
 fn write_if_else_impl<T, F>(
     cond: &IrSpanned<ExprCompiled>,
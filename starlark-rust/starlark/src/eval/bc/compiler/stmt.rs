@@ -18,6 +18,7 @@
 use crate::eval::bc::bytecode::Bc;
 use crate::eval::bc::compiler::if_compiler::write_if_else;
 use crate::eval::bc::compiler::if_compiler::write_if_then;
+use crate::eval::bc::compiler::if_compiler::write_while_cond;
 use crate::eval::bc::instr_impl::InstrBeforeStmt;
 use crate::eval::bc::instr_impl::InstrCheckType;
 use crate::eval::bc::instr_impl::InstrPossibleGc;
@@ -119,6 +120,11 @@ impl StmtCompiled {
                 let (_var, over, _body) = &**var_over_body;
                 over.mark_definitely_assigned_after(bc);
             }
+            StmtCompiled::While(cond_body) => {
+                // The condition is evaluated at least once.
+                let (cond, _body) = &**cond_body;
+                cond.mark_definitely_assigned_after(bc);
+            }
             StmtCompiled::Break => {}
             StmtCompiled::Continue => {}
         }
@@ -253,6 +259,14 @@ impl IrSpanned<StmtCompiled> {
                 let (assign, over, body) = &**assign_over_body;
                 write_for(over, assign, span, bc, |bc| body.write_bc(compiler, bc));
             }
+            StmtCompiled::While(cond_body) => {
+                let (cond, body) = &**cond_body;
+                bc.write_while(
+                    span,
+                    |bc| write_while_cond(cond, bc),
+                    |bc| body.write_bc(compiler, bc),
+                );
+            }
             StmtCompiled::Break => {
                 bc.write_break(span);
             }
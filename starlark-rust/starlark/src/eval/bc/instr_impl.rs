@@ -76,6 +76,7 @@ use crate::values::dict::Dict;
 use crate::values::int::PointerI32;
 use crate::values::layout::value_not_special::FrozenValueNotSpecial;
 use crate::values::string::dot_format::format_one;
+use crate::values::string::dot_format::FStringFormat;
 use crate::values::string::interpolation::percent_s_one;
 use crate::values::types::known_methods::KnownMethod;
 use crate::values::types::list::value::ListData;
@@ -762,6 +763,8 @@ pub(crate) struct InstrPercentSOneImpl;
 pub(crate) type InstrPercentSOne = InstrNoFlow<InstrPercentSOneImpl>;
 pub(crate) struct InstrFormatOneImpl;
 pub(crate) type InstrFormatOne = InstrNoFlow<InstrFormatOneImpl>;
+pub(crate) struct InstrFStringImpl;
+pub(crate) type InstrFString = InstrNoFlow<InstrFStringImpl>;
 
 impl InstrNoFlowImpl for InstrPercentSOneImpl {
     type Arg = (FrozenStringValue, BcSlotIn, FrozenStringValue, BcSlotOut);
@@ -797,8 +800,26 @@ impl InstrNoFlowImpl for InstrFormatOneImpl {
     }
 }
 
+impl InstrNoFlowImpl for InstrFStringImpl {
+    type Arg = (FrozenRef<'static, FStringFormat>, BcSlotInRange, BcSlotOut);
+
+    #[inline(always)]
+    fn run_with_args<'v>(
+        eval: &mut Evaluator<'v, '_>,
+        frame: BcFramePtr<'v>,
+        _ip: BcPtrAddr,
+        (format, args, target): &(FrozenRef<'static, FStringFormat>, BcSlotInRange, BcSlotOut),
+    ) -> anyhow::Result<()> {
+        let args = frame.get_bc_slot_range(*args);
+        let heap = eval.heap();
+        let r = format.format(args, &mut eval.string_pool, heap)?;
+        frame.set_bc_slot(*target, r.to_value());
+        Ok(())
+    }
+}
+
 pub(crate) trait InstrCompareImpl: 'static {
-    fn eval_compare(ordering: Ordering) -> bool;
+    fn eval_compare(ordering: Option<Ordering>) -> bool;
 }
 
 pub(crate) struct InstrCompare<I: InstrCompareImpl>(marker::PhantomData<I>);
@@ -806,7 +827,7 @@ pub(crate) struct InstrCompare<I: InstrCompareImpl>(marker::PhantomData<I>);
 impl<I: InstrCompareImpl> InstrBinOpImpl for InstrCompare<I> {
     #[inline(always)]
     fn eval<'v>(v0: Value<'v>, v1: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
-        Ok(Value::new_bool(I::eval_compare(v0.compare(v1)?)))
+        Ok(Value::new_bool(I::eval_compare(v0.partial_compare(v1)?)))
     }
 }
 
@@ -822,29 +843,29 @@ pub(crate) type InstrGreaterOrEqual = InstrBinOp<InstrCompare<InstrGreaterOrEqua
 
 impl InstrCompareImpl for InstrLessImpl {
     #[inline(always)]
-    fn eval_compare(ordering: Ordering) -> bool {
-        ordering == Ordering::Less
+    fn eval_compare(ordering: Option<Ordering>) -> bool {
+        ordering == Some(Ordering::Less)
     }
 }
 
 impl InstrCompareImpl for InstrGreaterImpl {
     #[inline(always)]
-    fn eval_compare(ordering: Ordering) -> bool {
-        ordering == Ordering::Greater
+    fn eval_compare(ordering: Option<Ordering>) -> bool {
+        ordering == Some(Ordering::Greater)
     }
 }
 
 impl InstrCompareImpl for InstrLessOrEqualImpl {
     #[inline(always)]
-    fn eval_compare(ordering: Ordering) -> bool {
-        ordering != Ordering::Greater
+    fn eval_compare(ordering: Option<Ordering>) -> bool {
+        matches!(ordering, Some(Ordering::Less | Ordering::Equal))
     }
 }
 
 impl InstrCompareImpl for InstrGreaterOrEqualImpl {
     #[inline(always)]
-    fn eval_compare(ordering: Ordering) -> bool {
-        ordering != Ordering::Less
+    fn eval_compare(ordering: Option<Ordering>) -> bool {
+        matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
     }
 }
 
@@ -1138,6 +1159,8 @@ impl InstrNoFlowImpl for InstrCheckTypeImpl {
 }
 
 pub(crate) struct InstrBr;
+/// Backward branch, used to jump to the condition of a `while` loop.
+pub(crate) struct InstrBrBack;
 pub(crate) struct InstrIfBr;
 pub(crate) struct InstrIfNotBr;
 
@@ -1155,6 +1178,20 @@ impl BcInstr for InstrBr {
     }
 }
 
+impl BcInstr for InstrBrBack {
+    type Arg = BcAddrOffsetNeg;
+
+    #[inline(always)]
+    fn run<'v, 'b>(
+        _eval: &mut Evaluator<'v, '_>,
+        _frame: BcFramePtr<'v>,
+        ip: BcPtrAddr<'b>,
+        target: &BcAddrOffsetNeg,
+    ) -> InstrControl<'v, 'b> {
+        InstrControl::Next(ip.add_rel_neg(*target))
+    }
+}
+
 impl BcInstr for InstrIfBr {
     type Arg = (BcSlotIn, BcAddrOffset);
 
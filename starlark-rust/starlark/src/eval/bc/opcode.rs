@@ -69,6 +69,7 @@ pub(crate) enum BcOpcode {
     Percent,
     PercentSOne,
     FormatOne,
+    FString,
     Divide,
     FloorDivide,
     BitAnd,
@@ -92,6 +93,7 @@ pub(crate) enum BcOpcode {
     ComprDictInsert,
     CheckType,
     Br,
+    BrBack,
     IfBr,
     IfNotBr,
     Iter,
@@ -18,6 +18,7 @@
 //! Bytecode writer.
 
 use std::cmp;
+use std::mem;
 
 use crate::cast::transmute;
 use crate::eval::bc::addr::BcAddr;
@@ -27,6 +28,7 @@ use crate::eval::bc::definitely_assigned::BcDefinitelyAssigned;
 use crate::eval::bc::for_loop::LoopDepth;
 use crate::eval::bc::instr::BcInstr;
 use crate::eval::bc::instr_impl::InstrBr;
+use crate::eval::bc::instr_impl::InstrBrBack;
 use crate::eval::bc::instr_impl::InstrBreak;
 use crate::eval::bc::instr_impl::InstrConst;
 use crate::eval::bc::instr_impl::InstrContinue;
@@ -70,6 +72,29 @@ struct BcWriterForLoop {
     end_addrs_to_patch: Vec<PatchAddr>,
 }
 
+/// While loop during bytecode write.
+struct BcWriterWhileLoop {
+    /// Address of the first instruction of the loop condition.
+    cond_addr: BcAddr,
+    /// Addresses to patch with the address of the instruction after the loop.
+    end_addrs_to_patch: Vec<PatchAddr>,
+}
+
+/// Loop during bytecode write.
+enum BcWriterLoop {
+    For(BcWriterForLoop),
+    While(BcWriterWhileLoop),
+}
+
+impl BcWriterLoop {
+    fn end_addrs_to_patch(&mut self) -> &mut Vec<PatchAddr> {
+        match self {
+            BcWriterLoop::For(for_loop) => &mut for_loop.end_addrs_to_patch,
+            BcWriterLoop::While(while_loop) => &mut while_loop.end_addrs_to_patch,
+        }
+    }
+}
+
 /// Write bytecode here.
 pub(crate) struct BcWriter<'f> {
     /// Insert bytecode profiling instructions.
@@ -89,9 +114,9 @@ pub(crate) struct BcWriter<'f> {
     definitely_assigned: BcDefinitelyAssigned,
     /// Max observed stack size.
     max_stack_size: u32,
-    /// Current loop depth.
-    for_loops: Vec<BcWriterForLoop>,
-    /// Max observed loop depth.
+    /// Loops enclosing the current instruction, innermost last.
+    loops: Vec<BcWriterLoop>,
+    /// Max observed `for` loop depth.
     max_loop_depth: LoopDepth,
 
     /// Allocate various objects here.
@@ -123,7 +148,7 @@ impl<'f> BcWriter<'f> {
             definitely_assigned,
             max_stack_size: 0,
             heap,
-            for_loops: Vec::new(),
+            loops: Vec::new(),
             max_loop_depth: LoopDepth(0),
         }
     }
@@ -141,7 +166,7 @@ impl<'f> BcWriter<'f> {
             definitely_assigned,
             max_stack_size,
             heap,
-            for_loops,
+            loops,
             max_loop_depth,
         } = self;
         let _ = has_before_instr;
@@ -149,7 +174,7 @@ impl<'f> BcWriter<'f> {
         let _ = heap;
         let _ = definitely_assigned;
         assert_eq!(stack_size, 0);
-        assert!(for_loops.is_empty());
+        assert!(loops.is_empty());
         // Drop lifetime.
         let local_names = unsafe {
             transmute!(
@@ -363,33 +388,53 @@ impl<'f> BcWriter<'f> {
         }
     }
 
+    /// Enclosing `for` loops, outermost first.
+    fn for_loops(&self) -> impl DoubleEndedIterator<Item = &BcWriterForLoop> {
+        self.loops.iter().filter_map(|l| match l {
+            BcWriterLoop::For(for_loop) => Some(for_loop),
+            BcWriterLoop::While(_) => None,
+        })
+    }
+
+    /// Depth of `for` loops, which is the number of iterators to track at runtime.
+    fn for_loop_depth(&self) -> u32 {
+        self.for_loops().count() as u32
+    }
+
     pub(crate) fn write_continue(&mut self, span: FrameSpan) {
-        let loop_depth = LoopDepth(self.for_loops.len().checked_sub(1).unwrap() as u32);
-        let for_loop = self.for_loops.last().unwrap();
-        let jump_back = self.ip().offset_from(for_loop.inner_addr).neg();
-        let var = for_loop.var;
-        let (addr, arg) = self.write_instr_ret_arg::<InstrContinue>(
-            span,
-            (
-                for_loop.iter,
-                loop_depth,
-                var,
-                jump_back,
-                BcAddrOffset::FORWARD,
-            ),
-        );
-        let end_patch = self.instrs.addr_to_patch(addr, unsafe { &(*arg).4 });
-        let for_loop = self.for_loops.last_mut().unwrap();
-        for_loop.end_addrs_to_patch.push(end_patch);
+        match self.loops.last().unwrap() {
+            BcWriterLoop::For(for_loop) => {
+                let iter = for_loop.iter;
+                let var = for_loop.var;
+                let jump_back = self.ip().offset_from(for_loop.inner_addr).neg();
+                let loop_depth = LoopDepth(self.for_loop_depth().checked_sub(1).unwrap());
+                let (addr, arg) = self.write_instr_ret_arg::<InstrContinue>(
+                    span,
+                    (iter, loop_depth, var, jump_back, BcAddrOffset::FORWARD),
+                );
+                let end_patch = self.instrs.addr_to_patch(addr, unsafe { &(*arg).4 });
+                let for_loop = self.loops.last_mut().unwrap();
+                for_loop.end_addrs_to_patch().push(end_patch);
+            }
+            BcWriterLoop::While(while_loop) => {
+                let jump_back = self.ip().offset_from(while_loop.cond_addr).neg();
+                self.write_instr::<InstrBrBack>(span, jump_back);
+            }
+        }
     }
 
     pub(crate) fn write_break(&mut self, span: FrameSpan) {
-        let for_loop = self.for_loops.last().unwrap();
-        let (addr, arg) =
-            self.write_instr_ret_arg::<InstrBreak>(span, (for_loop.iter, BcAddrOffset::FORWARD));
-        let end_patch = self.instrs.addr_to_patch(addr, unsafe { &(*arg).1 });
-        let for_loop = self.for_loops.last_mut().unwrap();
-        for_loop.end_addrs_to_patch.push(end_patch);
+        let end_patch = match self.loops.last().unwrap() {
+            BcWriterLoop::For(for_loop) => {
+                let iter = for_loop.iter;
+                let (addr, arg) =
+                    self.write_instr_ret_arg::<InstrBreak>(span, (iter, BcAddrOffset::FORWARD));
+                self.instrs.addr_to_patch(addr, unsafe { &(*arg).1 })
+            }
+            BcWriterLoop::While(_) => self.write_br(span),
+        };
+        let innermost = self.loops.last_mut().unwrap();
+        innermost.end_addrs_to_patch().push(end_patch);
     }
 
     /// Write for loop.
@@ -406,36 +451,61 @@ impl<'f> BcWriter<'f> {
             // by the caller. But it is safer to do it here anyway.
             let definitely_assigned = bc.save_definitely_assigned();
 
-            let loop_depth = LoopDepth(bc.for_loops.len() as u32);
+            let loop_depth = LoopDepth(bc.for_loop_depth());
             let (addr, arg) = bc.write_instr_ret_arg::<InstrIter>(
                 span,
                 (over, loop_depth, iter.to_out(), var, BcAddrOffset::FORWARD),
             );
             let end_patch = bc.instrs.addr_to_patch(addr, unsafe { &(*arg).4 });
-            bc.for_loops.push(BcWriterForLoop {
+            bc.loops.push(BcWriterLoop::For(BcWriterForLoop {
                 inner_addr: bc.ip(),
                 end_addrs_to_patch: vec![end_patch],
                 var,
                 iter: iter.to_in(),
-            });
-            bc.max_loop_depth = cmp::max(bc.max_loop_depth, LoopDepth(bc.for_loops.len() as u32));
+            }));
+            bc.max_loop_depth = cmp::max(bc.max_loop_depth, LoopDepth(bc.for_loop_depth()));
             body(bc);
             bc.write_continue(span);
-            let for_loop = bc.for_loops.pop().unwrap();
-            for addr_to_patch in for_loop.end_addrs_to_patch {
-                bc.patch_addr(addr_to_patch);
-            }
+            let mut for_loop = bc.loops.pop().unwrap();
+            bc.patch_addrs(mem::take(for_loop.end_addrs_to_patch()));
 
             bc.restore_definitely_assigned(definitely_assigned);
         })
     }
 
+    /// Write while loop.
+    ///
+    /// `cond` writes the loop condition. The generated code must fall through
+    /// when the condition is true, and `cond` returns the addresses to patch
+    /// with the loop exit, which are taken when the condition is false.
+    pub(crate) fn write_while(
+        &mut self,
+        span: FrameSpan,
+        cond: impl FnOnce(&mut BcWriter) -> Vec<PatchAddr>,
+        body: impl FnOnce(&mut BcWriter),
+    ) {
+        let definitely_assigned = self.save_definitely_assigned();
+
+        let cond_addr = self.ip();
+        let end_addrs_to_patch = cond(self);
+        self.loops.push(BcWriterLoop::While(BcWriterWhileLoop {
+            cond_addr,
+            end_addrs_to_patch,
+        }));
+        body(self);
+        self.write_continue(span);
+        let mut while_loop = self.loops.pop().unwrap();
+        self.patch_addrs(mem::take(while_loop.end_addrs_to_patch()));
+
+        self.restore_definitely_assigned(definitely_assigned);
+    }
+
     /// Write instructions to stop all current iterations.
     /// This is done before `return`.
     pub(crate) fn write_iter_stop(&mut self, span: FrameSpan) {
         // We can stop iteration in any order, but let's for consistency stop them in reverse order.
-        for depth in (0..self.for_loops.len()).rev() {
-            let iter = self.for_loops[depth].iter;
+        let iters: Vec<BcSlotIn> = self.for_loops().rev().map(|l| l.iter).collect();
+        for iter in iters {
             self.write_instr::<InstrIterStop>(span, iter);
         }
     }
@@ -68,6 +68,7 @@ use crate::eval::runtime::arguments::ResolvedArgName;
 use crate::eval::runtime::evaluator::Evaluator;
 use crate::eval::runtime::frame_span::FrameSpan;
 use crate::eval::runtime::frozen_file_span::FrozenFileSpan;
+use crate::eval::runtime::params::ParameterKind;
 use crate::eval::runtime::params::ParametersSpec;
 use crate::eval::runtime::slots::LocalSlotId;
 use crate::eval::runtime::slots::LocalSlotIdCapturedOrNot;
@@ -97,6 +98,8 @@ use crate::values::ValueLike;
 enum DefError {
     #[error("Function has no type, while function was compiled with return type (internal error)")]
     CheckReturnTypeNoType,
+    #[error("Function `{0}` expects {1} values to rebuild it, got {2}")]
+    WrongValueCount(String, usize, usize),
 }
 
 /// Store frozen `StmtCompiled`.
@@ -486,12 +489,16 @@ impl Compiler<'_, '_, '_> {
             globals: self.globals,
         });
 
-        ExprCompiled::Def(DefCompiled {
+        let def = DefCompiled {
             function_name,
             params,
             return_type,
             info,
-        })
+        };
+        if let Some(defs) = &mut self.record_defs {
+            defs.push(def.clone());
+        }
+        ExprCompiled::Def(def)
     }
 }
 
@@ -506,7 +513,7 @@ pub(crate) struct DefGen<V> {
     parameter_captures: FrozenRef<'static, [LocalSlotId]>,
     // The types of the parameters.
     // (Sparse indexed array, (0, argm T) implies parameter 0 named arg must have type T).
-    parameter_types: Vec<(LocalSlotId, String, V, TypeCompiled)>,
+    pub(crate) parameter_types: Vec<(LocalSlotId, String, V, TypeCompiled)>,
     pub(crate) return_type: Option<(V, TypeCompiled)>, // The return type annotation for the function
     /// Data created during function compilation but before function instantiation.
     /// `DefInfo` can be shared by multiple `def` instances, for example,
@@ -515,14 +522,14 @@ pub(crate) struct DefGen<V> {
     /// Any variables captured from the outer scope (nested def/lambda).
     /// Values are either [`Value`] or [`FrozenValue`] pointing respectively to
     /// [`ValueCaptured`] or [`FrozenValueCaptured`].
-    captured: Vec<V>,
+    pub(crate) captured: Vec<V>,
     // Important to ignore these field as it probably references DefGen in a cycle
     #[derivative(Debug = "ignore")]
     /// A reference to the module where the function is defined after the module has been frozen.
     /// When the module is not frozen yet, this field contains `None`, and function's module
     /// can be accessed from evaluator's module.
     #[allocative(skip)]
-    module: AtomicFrozenRefOption<FrozenModuleData>,
+    pub(crate) module: AtomicFrozenRefOption<FrozenModuleData>,
     /// This field is only used in `FrozenDef`. It is populated in `post_freeze`.
     #[derivative(Debug = "ignore")]
     #[allocative(skip)]
@@ -800,6 +807,97 @@ where
 }
 
 impl FrozenDef {
+    /// The values the `def` or `lambda` evaluated to create this function: for each parameter its
+    /// type and its default value, then the return type.
+    pub(crate) fn evaluated_values(&self) -> Vec<FrozenValue> {
+        let mut values = Vec::new();
+        for (i, (_, kind)) in self.parameters.iter_params().enumerate() {
+            if let Some((_, _, ty, _)) = self
+                .parameter_types
+                .iter()
+                .find(|(slot, ..)| slot.0 as usize == i)
+            {
+                values.push(*ty);
+            }
+            if let ParameterKind::Defaulted(value) = kind {
+                values.push(*value);
+            }
+        }
+        if let Some((ty, _)) = &self.return_type {
+            values.push(*ty);
+        }
+        values
+    }
+
+    /// Create the function `def` evaluates to, from the values returned by
+    /// [`evaluated_values`](FrozenDef::evaluated_values) and the captured values, without
+    /// running any code. Used to reload serialized modules, the function must be
+    /// [`post_freeze`](FrozenDef::post_freeze)d once its module is frozen.
+    pub(crate) fn from_evaluated_values(
+        def: &DefCompiled,
+        values: &[FrozenValue],
+        captured: Vec<FrozenValue>,
+        heap: &Heap,
+    ) -> anyhow::Result<FrozenDef> {
+        let expected = def.params.count_exprs() as usize + def.return_type.iter().len();
+        if values.len() != expected || captured.len() != def.info.parent.len() {
+            return Err(DefError::WrongValueCount(
+                def.function_name.clone(),
+                expected + def.info.parent.len(),
+                values.len() + captured.len(),
+            )
+            .into());
+        }
+
+        // Same as instantiating the `def` in `InstrDefImpl`, without checking the defaults, which
+        // was done when the function was created.
+        let mut values = values.iter().copied();
+        let mut parameters =
+            ParametersSpec::with_capacity(def.function_name.clone(), def.params.params.len());
+        parameters.no_more_positional_only_args();
+        let mut parameter_types = Vec::new();
+        for (i, x) in def.params.params.iter().enumerate() {
+            if i as u32 == def.params.num_positional && !x.is_star_or_star_star() {
+                parameters.no_more_positional_args();
+            }
+            if let (name, Some(_)) = x.name_ty() {
+                let ty = values.next().unwrap();
+                parameter_types.push((
+                    LocalSlotId(i as u32),
+                    name.name.clone(),
+                    ty,
+                    TypeCompiled::new(ty.to_value(), heap)?,
+                ));
+            }
+            match &x.node {
+                ParameterCompiled::Normal(n, _) => parameters.required(&n.name),
+                ParameterCompiled::WithDefaultValue(n, _, _) => {
+                    parameters.defaulted(&n.name, values.next().unwrap())
+                }
+                ParameterCompiled::Args(_, _) => parameters.args(),
+                ParameterCompiled::KwArgs(_, _) => parameters.kwargs(),
+            }
+        }
+        let return_type = match &def.return_type {
+            None => None,
+            Some(_) => {
+                let ty = values.next().unwrap();
+                Some((ty, TypeCompiled::new(ty.to_value(), heap)?))
+            }
+        };
+
+        Ok(FrozenDef {
+            parameters: parameters.finish(),
+            parameter_captures: def.info.parameter_captures,
+            parameter_types,
+            return_type,
+            def_info: def.info,
+            captured,
+            module: AtomicFrozenRefOption::new(None),
+            optimized_on_freeze_stmt: StmtCompiledCell::new(),
+        })
+    }
+
     pub(crate) fn post_freeze(
         &self,
         module: FrozenRef<FrozenModuleData>,
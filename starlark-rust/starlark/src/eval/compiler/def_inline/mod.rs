@@ -125,7 +125,7 @@ impl IsSafeToInlineExpr {
                 let _: &Builtin1 = un_op;
                 self.is_safe_to_inline_expr(arg)
             }
-            ExprCompiled::Tuple(xs) | ExprCompiled::List(xs) => {
+            ExprCompiled::Tuple(xs) | ExprCompiled::List(xs) | ExprCompiled::FString(_, xs) => {
                 xs.iter().all(|x| self.is_safe_to_inline_expr(x))
             }
             ExprCompiled::Dict(xs) => xs
@@ -285,6 +285,16 @@ impl<'s, 'v, 'a, 'e> InlineDefCallSite<'s, 'v, 'a, 'e> {
                     node: ExprCompiled::tuple(xs, self.ctx.frozen_heap()),
                 }
             }
+            ExprCompiled::FString(format, xs) => {
+                let xs = xs
+                    .iter()
+                    .map(|x| self.inline(x))
+                    .collect::<Result<Vec<_>, CannotInline>>()?;
+                IrSpanned {
+                    span,
+                    node: ExprCompiled::fstring(*format, xs, self.ctx),
+                }
+            }
             ExprCompiled::Dict(xs) => {
                 let xs = xs
                     .iter()
@@ -24,6 +24,7 @@ use starlark_derive::VisitSpanMut;
 use thiserror::Error;
 
 use crate::codemap::Spanned;
+use crate::collections::string_pool::StringPool;
 use crate::collections::symbol_map::Symbol;
 use crate::environment::slots::ModuleSlotId;
 use crate::errors::did_you_mean::did_you_mean;
@@ -55,6 +56,7 @@ use crate::syntax::ast::AstPayload;
 use crate::syntax::ast::AstString;
 use crate::syntax::ast::BinOp;
 use crate::syntax::ast::ExprP;
+use crate::syntax::ast::FStringP;
 use crate::syntax::ast::LambdaP;
 use crate::syntax::ast::StmtP;
 use crate::syntax::lexer::TokenInt;
@@ -65,16 +67,19 @@ use crate::values::list::ListRef;
 use crate::values::string::interpolation::parse_percent_s_one;
 use crate::values::types::bigint::StarlarkBigInt;
 use crate::values::types::bool::StarlarkBool;
+use crate::values::types::bytes::StarlarkBytes;
 use crate::values::types::dict::Dict;
 use crate::values::types::float::StarlarkFloat;
 use crate::values::types::list::value::FrozenListData;
 use crate::values::types::list::value::ListData;
 use crate::values::types::range::Range;
 use crate::values::types::string::dot_format::format_one;
+use crate::values::types::string::dot_format::FStringFormat;
 use crate::values::types::string::interpolation::percent_s_one;
 use crate::values::types::tuple::value::Tuple;
 use crate::values::types::unbound::MaybeUnboundValue;
 use crate::values::FrozenHeap;
+use crate::values::FrozenRef;
 use crate::values::FrozenStringValue;
 use crate::values::FrozenValue;
 use crate::values::FrozenValueTyped;
@@ -110,12 +115,12 @@ pub(crate) enum CompareOp {
 }
 
 impl CompareOp {
-    fn apply(self, x: Ordering) -> bool {
+    fn apply(self, x: Option<Ordering>) -> bool {
         match self {
-            CompareOp::Less => x == Ordering::Less,
-            CompareOp::Greater => x == Ordering::Greater,
-            CompareOp::LessOrEqual => x != Ordering::Greater,
-            CompareOp::GreaterOrEqual => x != Ordering::Less,
+            CompareOp::Less => x == Some(Ordering::Less),
+            CompareOp::Greater => x == Some(Ordering::Greater),
+            CompareOp::LessOrEqual => matches!(x, Some(Ordering::Less | Ordering::Equal)),
+            CompareOp::GreaterOrEqual => matches!(x, Some(Ordering::Greater | Ordering::Equal)),
         }
     }
 }
@@ -202,7 +207,7 @@ impl Builtin2 {
     fn eval<'v>(self, a: Value<'v>, b: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         match self {
             Builtin2::Equals => a.equals(b).map(Value::new_bool),
-            Builtin2::Compare(cmp) => a.compare(b).map(|c| Value::new_bool(cmp.apply(c))),
+            Builtin2::Compare(cmp) => a.partial_compare(b).map(|c| Value::new_bool(cmp.apply(c))),
             Builtin2::In => b.is_in(a).map(Value::new_bool),
             Builtin2::Sub => a.sub(b, heap),
             Builtin2::Add => a.add(b, heap),
@@ -272,6 +277,11 @@ pub(crate) enum ExprCompiled {
     ),
     Call(Box<IrSpanned<CallCompiled>>),
     Def(DefCompiled),
+    /// `f"..."`, formatting the values of the expressions.
+    FString(
+        FrozenRef<'static, FStringFormat>,
+        Vec<IrSpanned<ExprCompiled>>,
+    ),
 }
 
 impl ExprCompiled {
@@ -510,6 +520,9 @@ impl IrSpanned<ExprCompiled> {
             }
             d @ ExprCompiled::Def(..) => (*d).clone(),
             ExprCompiled::Call(ref call) => call.optimize(ctx),
+            ExprCompiled::FString(format, xs) => {
+                ExprCompiled::fstring(*format, xs.map(|e| e.optimize(ctx)), ctx)
+            }
         };
         IrSpanned { node: expr, span }
     }
@@ -650,6 +663,32 @@ impl ExprCompiled {
         ExprCompiled::Builtin1(Builtin1::FormatOne(before, after), Box::new(arg))
     }
 
+    pub(crate) fn fstring(
+        format: FrozenRef<'static, FStringFormat>,
+        mut args: Vec<IrSpanned<ExprCompiled>>,
+        ctx: &mut OptCtx,
+    ) -> ExprCompiled {
+        if let (Some((before, after)), 1) = (format.as_format_one(), args.len()) {
+            let before = ctx.frozen_heap().alloc_str(before);
+            let after = ctx.frozen_heap().alloc_str(after);
+            return ExprCompiled::format_one(before, args.pop().unwrap(), after, ctx);
+        }
+
+        let values: Option<Vec<Value>> = args
+            .iter()
+            .map(|x| Some(x.as_value()?.to_value()))
+            .collect();
+        if let Some(values) = values {
+            // If formatting fails, let it fail in runtime.
+            if let Ok(value) = format.format(&values, &mut StringPool::default(), ctx.heap()) {
+                let value = ctx.frozen_heap().alloc_str(value.as_str());
+                return ExprCompiled::Value(value.to_frozen_value());
+            }
+        }
+
+        ExprCompiled::FString(format, args)
+    }
+
     fn add(l: IrSpanned<ExprCompiled>, r: IrSpanned<ExprCompiled>) -> ExprCompiled {
         let span = l.span.merge(&r.span);
         if let (Some(l), Some(r)) = (l.as_short_list_of_consts(), r.as_short_list_of_consts()) {
@@ -998,6 +1037,7 @@ impl AstLiteral {
             },
             AstLiteral::Float(f) => heap.alloc(f.node),
             AstLiteral::String(x) => heap.alloc(x.node.as_str()),
+            AstLiteral::Bytes(x) => heap.alloc(StarlarkBytes::new(x.node.as_slice())),
         }
     }
 }
@@ -1355,6 +1395,17 @@ impl<'v, 'a, 'e> Compiler<'v, 'a, 'e> {
                 let val = x.compile(self.eval.module_env.frozen_heap());
                 ExprCompiled::Value(val)
             }
+            ExprP::FString(fstring) => {
+                let FStringP {
+                    format,
+                    expressions,
+                } = *fstring;
+                let format = FStringFormat::parse(&format)
+                    .expect("f-string format is checked by the parser");
+                let format = self.eval.module_env.frozen_heap().alloc_any(format);
+                let args = expressions.into_map(|x| self.expr(x));
+                ExprCompiled::fstring(format, args, &mut self.opt_ctx())
+            }
         };
         IrSpanned { node: expr, span }
     }
@@ -36,6 +36,7 @@ use std::fmt::Debug;
 use crate::codemap::CodeMap;
 use crate::environment::Globals;
 use crate::errors::Diagnostic;
+use crate::eval::compiler::def::DefCompiled;
 use crate::eval::compiler::scope::ScopeData;
 use crate::eval::compiler::scope::ScopeId;
 use crate::eval::compiler::scope::ScopeNames;
@@ -88,6 +89,8 @@ pub(crate) struct Compiler<'v, 'a, 'e> {
     pub(crate) has_before_stmt: bool,
     pub(crate) bc_profile: bool,
     pub(crate) check_types: bool,
+    /// When set, every `def` and `lambda` compiled is recorded here.
+    pub(crate) record_defs: Option<Vec<DefCompiled>>,
 }
 
 impl Compiler<'_, '_, '_> {
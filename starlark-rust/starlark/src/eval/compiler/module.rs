@@ -115,6 +115,28 @@ impl<'v> Compiler<'v, '_, '_> {
         }
     }
 
+    fn compile_top_level_stmt(&mut self, stmt: CstStmt) {
+        match stmt.node {
+            StmtP::Statements(stmts) => {
+                for stmt in stmts {
+                    self.compile_top_level_stmt(stmt);
+                }
+            }
+            StmtP::Load(..) => {}
+            _ => {
+                self.module_top_level_stmt(stmt);
+            }
+        }
+    }
+
+    /// Compile the module without running it, which is enough to record its functions.
+    pub(crate) fn compile_module(&mut self, stmt: CstStmt) {
+        self.enter_scope(ScopeId::module());
+        self.compile_top_level_stmt(stmt);
+        self.exit_scope();
+        assert!(self.locals.is_empty());
+    }
+
     pub(crate) fn eval_module(
         &mut self,
         stmt: CstStmt,
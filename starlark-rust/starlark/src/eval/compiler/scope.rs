@@ -658,6 +658,9 @@ impl Stmt {
                 Assign::collect_defines_lvalue(dest, InLoop::Yes, scope_data, frozen_heap, result);
                 StmtP::collect_defines(body, InLoop::Yes, scope_data, frozen_heap, result, dialect);
             }
+            StmtP::While(_, body) => {
+                StmtP::collect_defines(body, InLoop::Yes, scope_data, frozen_heap, result, dialect);
+            }
             StmtP::Def(DefP { name, .. }) => AssignIdent::collect_assign_ident(
                 name,
                 in_loop,
@@ -96,6 +96,7 @@ pub(crate) enum StmtCompiled {
             StmtsCompiled,
         )>,
     ),
+    While(Box<(IrSpanned<ExprCompiled>, StmtsCompiled)>),
     Break,
     Continue,
 }
@@ -172,6 +173,12 @@ impl IrSpanned<StmtCompiled> {
                 let body = body.optimize(ctx);
                 StmtsCompiled::for_stmt(span, var, over, body)
             }
+            StmtCompiled::While(cond_body) => {
+                let (cond, body) = &**cond_body;
+                let cond = cond.optimize(ctx);
+                let body = body.optimize(ctx);
+                StmtsCompiled::while_stmt(span, cond, body)
+            }
             s @ (StmtCompiled::PossibleGc | StmtCompiled::Break | StmtCompiled::Continue) => {
                 StmtsCompiled::one(IrSpanned {
                     span,
@@ -349,6 +356,21 @@ impl StmtsCompiled {
             node: StmtCompiled::For(Box::new((var, over, body))),
         })
     }
+
+    fn while_stmt(
+        span: FrameSpan,
+        cond: IrSpanned<ExprCompiled>,
+        body: StmtsCompiled,
+    ) -> StmtsCompiled {
+        let cond = ExprCompiledBool::new(cond);
+        if let ExprCompiledBool::Const(false) = cond.node {
+            return StmtsCompiled::empty();
+        }
+        StmtsCompiled::one(IrSpanned {
+            span,
+            node: StmtCompiled::While(Box::new((cond.into_expr(), body))),
+        })
+    }
 }
 
 #[derive(Debug, Error)]
@@ -773,6 +795,12 @@ impl Compiler<'_, '_, '_> {
                 let st = self.stmt(body, false);
                 StmtsCompiled::for_stmt(span, var, over, st)
             }
+            StmtP::While(cond, body) => {
+                let cond = self.expr(cond);
+                // Unlike `for`, a `while` loop holds no iterator, so it's safe to GC in the body
+                let st = self.stmt(*body, allow_gc);
+                StmtsCompiled::while_stmt(span, cond, st)
+            }
             StmtP::Return(None) => StmtsCompiled::one(IrSpanned {
                 node: StmtCompiled::Return(IrSpanned {
                     span,
@@ -27,7 +27,11 @@ use std::time::Instant;
 
 use dupe::Dupe;
 pub use runtime::arguments::Arguments;
+pub use runtime::async_call::AsyncCall;
+pub use runtime::async_call::AsyncCallPool;
+pub use runtime::async_call::AsyncHandle;
 pub use runtime::before_stmt::BeforeStmtFuncDyn;
+pub use runtime::budget::EvalBudgetExceeded;
 pub use runtime::call_stack::CallStack;
 pub use runtime::evaluator::Evaluator;
 pub use runtime::file_loader::FileLoader;
@@ -41,6 +45,7 @@ pub use runtime::profile::ProfileMode;
 use crate::collections::symbol_map::Symbol;
 use crate::docs::DocString;
 use crate::environment::Globals;
+use crate::eval::compiler::def::DefCompiled;
 use crate::eval::compiler::def::DefInfo;
 use crate::eval::compiler::scope::CompilerAstMap;
 use crate::eval::compiler::scope::Scope;
@@ -59,6 +64,28 @@ impl<'v, 'a> Evaluator<'v, 'a> {
     /// Evaluate an [`AstModule`] with this [`Evaluator`], modifying the in-scope
     /// [`Module`](crate::environment::Module) as appropriate.
     pub fn eval_module(&mut self, ast: AstModule, globals: &Globals) -> anyhow::Result<Value<'v>> {
+        self.eval_or_compile_module(ast, globals, false)
+            .map(|(value, _)| value)
+    }
+
+    /// Compile an [`AstModule`] into the in-scope module without running it, and return the
+    /// `def` and `lambda` expressions it contains. This is used to rebuild the functions of
+    /// a [`SerializedModule`](crate::environment::SerializedModule).
+    pub(crate) fn compile_module_defs(
+        &mut self,
+        ast: AstModule,
+        globals: &Globals,
+    ) -> anyhow::Result<Vec<DefCompiled>> {
+        self.eval_or_compile_module(ast, globals, true)
+            .map(|(_, defs)| defs)
+    }
+
+    fn eval_or_compile_module(
+        &mut self,
+        ast: AstModule,
+        globals: &Globals,
+        compile_only: bool,
+    ) -> anyhow::Result<(Value<'v>, Vec<DefCompiled>)> {
         let start = Instant::now();
 
         let AstModule {
@@ -67,6 +94,11 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             dialect,
         } = ast;
 
+        if unlikely(self.typecheck_profile.enabled) {
+            self.typecheck_profile
+                .add_module(&codemap, &dialect, globals);
+        }
+
         let codemap = self
             .module_env
             .frozen_heap()
@@ -140,9 +172,20 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             bc_profile: self.bc_profile.enabled(),
             eval: self,
             check_types: dialect.enable_types == DialectTypes::Enable,
+            record_defs: if compile_only { Some(Vec::new()) } else { None },
         };
 
-        let res = compiler.eval_module(statement, local_names);
+        let res = if compile_only {
+            compiler.compile_module(statement);
+            Ok((
+                Value::new_none(),
+                compiler.record_defs.take().unwrap_or_default(),
+            ))
+        } else {
+            compiler
+                .eval_module(statement, local_names)
+                .map(|value| (value, Vec::new()))
+        };
 
         // Clean up the world, putting everything back
         self.call_stack.pop();
@@ -0,0 +1,422 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Calling Starlark functions from async Rust, with native functions which await futures.
+//!
+//! The interpreter keeps its state on the Rust stack, so evaluation can only be paused by
+//! pausing the thread running it. Evaluations run on the threads of an [`AsyncCallPool`].
+//! When a native function awaits a future, the evaluation thread sends the future to the async
+//! task and blocks until the task has awaited it and sent the result back, then evaluation
+//! continues where it stopped. Only the evaluation thread is blocked, never an executor thread.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::mem;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+use std::thread;
+use std::time::Duration;
+
+use dupe::Dupe;
+use once_cell::sync::Lazy;
+
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::values::OwnedFrozenValue;
+use crate::values::Value;
+
+/// Stack size of the evaluation threads. Spawned threads get 2MiB by default, which deep
+/// Starlark recursion exhausts, so use the usual size of a main thread instead.
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Maximum number of threads of [`AsyncCallPool::global`].
+const GLOBAL_MAX_THREADS: usize = 64;
+
+/// Evaluation threads exit after being idle for this long.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, thiserror::Error)]
+enum AsyncCallError {
+    #[error("`await_future` can only be used in evaluations run by `AsyncCallPool`")]
+    NotAsync,
+    #[error("`AsyncCall` was dropped while waiting for a future")]
+    Cancelled,
+    #[error("Thread evaluating `AsyncCall` panicked")]
+    Panicked,
+}
+
+type AnyResult = Box<dyn Any + Send>;
+
+type PendingFuture = Pin<Box<dyn Future<Output = AnyResult> + Send>>;
+
+/// Sent by the evaluation thread to the async task: await the future, and send its result back.
+struct AwaitRequest(PendingFuture, mpsc::Sender<AnyResult>);
+
+#[derive(Default)]
+struct MailboxData {
+    requests: VecDeque<AwaitRequest>,
+    waker: Option<Waker>,
+    /// The evaluation finished, so no more requests will arrive.
+    closed: bool,
+    /// The async task was dropped, so requests are dropped instead of being awaited.
+    abandoned: bool,
+}
+
+/// Requests from the evaluation thread, which the async task can wait for.
+#[derive(Default)]
+struct Mailbox(Mutex<MailboxData>);
+
+impl Mailbox {
+    fn send(&self, request: AwaitRequest) {
+        let mut data = self.0.lock().unwrap();
+        if data.abandoned {
+            // Dropping the request makes the evaluation thread stop waiting.
+            return;
+        }
+        data.requests.push_back(request);
+        if let Some(waker) = data.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn close(&self) {
+        let mut data = self.0.lock().unwrap();
+        data.closed = true;
+        if let Some(waker) = data.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn abandon(&self) {
+        let mut data = self.0.lock().unwrap();
+        data.abandoned = true;
+        let requests = mem::take(&mut data.requests);
+        drop(data);
+        drop(requests);
+    }
+
+    /// The next request, `None` once the evaluation finished.
+    fn recv(&self) -> impl Future<Output = Option<AwaitRequest>> + '_ {
+        std::future::poll_fn(|cx: &mut Context| {
+            let mut data = self.0.lock().unwrap();
+            match data.requests.pop_front() {
+                Some(request) => Poll::Ready(Some(request)),
+                None if data.closed => Poll::Ready(None),
+                None => {
+                    data.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+}
+
+/// Closes the mailbox when the evaluation finishes, even if it panics.
+struct CloseOnDrop(Arc<Mailbox>);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// Cancels the evaluation when the task running it completes or is dropped.
+struct AbandonOnDrop(AsyncHandle);
+
+impl Drop for AbandonOnDrop {
+    fn drop(&mut self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+        self.0.mailbox.abandon();
+    }
+}
+
+/// Connection of an evaluation thread to the async task running it,
+/// given to the function run by [`AsyncCallPool::run`].
+#[derive(Clone, Dupe)]
+pub struct AsyncHandle {
+    mailbox: Arc<Mailbox>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl AsyncHandle {
+    /// Wait for a future on the async task. Must be called on the evaluation thread.
+    ///
+    /// The evaluation thread blocks until the task has awaited the future, and fails once
+    /// the task is dropped.
+    pub fn await_future<T, F>(&self, make_future: impl FnOnce() -> F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = T> + Send + 'static,
+    {
+        let future = make_future();
+        let (sender, receiver) = mpsc::channel();
+        self.mailbox.send(AwaitRequest(
+            Box::pin(async move { Box::new(future.await) as AnyResult }),
+            sender,
+        ));
+        let result = receiver.recv().map_err(|_| AsyncCallError::Cancelled)?;
+        Ok(*result
+            .downcast::<T>()
+            .expect("result has the type of the future"))
+    }
+
+    /// Set once the async task is dropped, see [`Evaluator::set_cancellation`].
+    pub fn cancelled(&self) -> &Arc<AtomicBool> {
+        &self.cancelled
+    }
+}
+
+impl<'v, 'a> Evaluator<'v, 'a> {
+    /// Let native functions of this evaluator await futures on the async task running it,
+    /// with [`await_future`](Evaluator::await_future), and cancel the evaluation once the task
+    /// is dropped. The evaluator must be used on the thread running the function passed to
+    /// [`AsyncCallPool::run`].
+    pub fn set_async_handle(&mut self, handle: AsyncHandle) {
+        self.set_cancellation(handle.cancelled.dupe());
+        self.async_handle = Some(handle);
+    }
+
+    /// Wait for a future in a native function, see [`set_async_handle`](Evaluator::set_async_handle).
+    ///
+    /// Evaluation is paused until the task running it has awaited the future,
+    /// then continues with its result. Native functions are never evaluated twice.
+    pub fn await_future<T, F>(&mut self, make_future: impl FnOnce() -> F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = T> + Send + 'static,
+    {
+        self.async_handle
+            .as_ref()
+            .ok_or(AsyncCallError::NotAsync)?
+            .await_future(make_future)
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct PoolData {
+    jobs: VecDeque<Job>,
+    threads: usize,
+    /// Threads waiting for a job.
+    idle: usize,
+}
+
+struct PoolShared {
+    data: Mutex<PoolData>,
+    job_added: Condvar,
+    max_threads: usize,
+}
+
+impl PoolShared {
+    /// Body of an evaluation thread.
+    fn work(&self) {
+        let mut data = self.data.lock().unwrap();
+        loop {
+            if let Some(job) = data.jobs.pop_front() {
+                drop(data);
+                // The job reports its panic to its task, the thread can run the next jobs.
+                let _ignored = panic::catch_unwind(AssertUnwindSafe(job));
+                data = self.data.lock().unwrap();
+                continue;
+            }
+            data.idle += 1;
+            let (new_data, timeout) = self.job_added.wait_timeout(data, IDLE_TIMEOUT).unwrap();
+            data = new_data;
+            data.idle -= 1;
+            if timeout.timed_out() && data.jobs.is_empty() {
+                data.threads -= 1;
+                return;
+            }
+        }
+    }
+}
+
+/// Threads evaluating Starlark for async tasks.
+///
+/// The interpreter keeps its state on the Rust stack, so an evaluation waiting for a future
+/// blocks its thread until the task running it has awaited the future. Each evaluation in
+/// progress therefore occupies a thread with an 8 MiB stack, of which only the used part is
+/// committed. Threads are started when all of them are busy, up to the maximum, and exit after
+/// being idle for a minute. Further evaluations wait for a free thread, so an evaluation must
+/// not await another evaluation on the same pool, which waits forever once the pool is full.
+#[derive(Clone, Dupe)]
+pub struct AsyncCallPool(Arc<PoolShared>);
+
+impl AsyncCallPool {
+    /// Create a pool with at most `max_threads` evaluation threads.
+    pub fn new(max_threads: usize) -> AsyncCallPool {
+        assert!(max_threads > 0, "`AsyncCallPool` needs at least one thread");
+        AsyncCallPool(Arc::new(PoolShared {
+            data: Mutex::new(PoolData::default()),
+            job_added: Condvar::new(),
+            max_threads,
+        }))
+    }
+
+    /// The pool used by [`AsyncCall::call`], with at most 64 threads.
+    pub fn global() -> &'static AsyncCallPool {
+        static GLOBAL: Lazy<AsyncCallPool> = Lazy::new(|| AsyncCallPool::new(GLOBAL_MAX_THREADS));
+        &GLOBAL
+    }
+
+    fn execute(&self, job: Job) -> anyhow::Result<()> {
+        let mut data = self.0.data.lock().unwrap();
+        data.jobs.push_back(job);
+        if data.jobs.len() > data.idle && data.threads < self.0.max_threads {
+            let pool = self.0.dupe();
+            let spawned = thread::Builder::new()
+                .name("starlark-async-call".to_owned())
+                .stack_size(STACK_SIZE)
+                .spawn(move || pool.work());
+            match spawned {
+                Ok(_) => data.threads += 1,
+                Err(e) if data.threads == 0 => {
+                    data.jobs.pop_back();
+                    return Err(e.into());
+                }
+                // One of the existing threads runs the job later.
+                Err(_) => {}
+            }
+        }
+        self.0.job_added.notify_one();
+        Ok(())
+    }
+
+    /// Run `f` on a thread of the pool, and await the futures it passes to
+    /// [`AsyncHandle::await_future`], e.g. through an evaluator set up with
+    /// [`Evaluator::set_async_handle`].
+    ///
+    /// Dropping the returned future makes pending `await_future` calls fail, sets
+    /// [`AsyncHandle::cancelled`], and skips `f` if it didn't start yet.
+    pub async fn run<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(AsyncHandle) -> anyhow::Result<T> + Send + 'static,
+    {
+        let handle = AsyncHandle {
+            mailbox: Arc::new(Mailbox::default()),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        let _abandon = AbandonOnDrop(handle.dupe());
+        let result = Arc::new(Mutex::new(None));
+        self.execute(Box::new({
+            let handle = handle.dupe();
+            let result = result.dupe();
+            move || {
+                let _close = CloseOnDrop(handle.mailbox.dupe());
+                if !handle.cancelled.load(Ordering::Relaxed) {
+                    *result.lock().unwrap() = Some(f(handle));
+                }
+            }
+        }))?;
+        while let Some(AwaitRequest(future, sender)) = handle.mailbox.recv().await {
+            // The evaluation thread only stops waiting if this task was dropped.
+            let _ignored = sender.send(future.await);
+        }
+        let result = result.lock().unwrap().take();
+        result.unwrap_or_else(|| Err(AsyncCallError::Panicked.into()))
+    }
+}
+
+type CallFn =
+    Box<dyn for<'v, 'a> FnOnce(&mut Evaluator<'v, 'a>) -> anyhow::Result<Value<'v>> + Send>;
+
+/// A call of a Starlark function from async code, where native functions
+/// can wait for futures with [`Evaluator::await_future`] without blocking an executor thread.
+///
+/// The call is evaluated in a fresh [`Module`] on a thread of an [`AsyncCallPool`], and the
+/// result is frozen so it can outlive it. Dropping the future returned by
+/// [`call`](AsyncCall::call) makes pending `await_future` calls fail and cancels the evaluation
+/// (see [`Evaluator::set_cancellation`]), which frees the thread.
+pub struct AsyncCall {
+    call: CallFn,
+    setup: Box<dyn Fn(&mut Evaluator) + Send>,
+}
+
+impl AsyncCall {
+    /// Create a call of `function` with the given arguments.
+    pub fn new(
+        function: OwnedFrozenValue,
+        positional: Vec<OwnedFrozenValue>,
+        named: Vec<(String, OwnedFrozenValue)>,
+    ) -> Self {
+        Self::new_with(move |eval| {
+            let heap = eval.frozen_heap();
+            let function = function.owned_value(heap);
+            let positional: Vec<Value> = positional.iter().map(|x| x.owned_value(heap)).collect();
+            let named: Vec<(&str, Value)> = named
+                .iter()
+                .map(|(name, x)| (name.as_str(), x.owned_value(heap)))
+                .collect();
+            eval.eval_function(function, &positional, &named)
+        })
+    }
+
+    /// Create a call evaluated by `call` on the evaluation thread, e.g. to allocate arguments
+    /// which are not frozen, or to evaluate a module and call one of its functions.
+    pub fn new_with<F>(call: F) -> Self
+    where
+        F: for<'v, 'a> FnOnce(&mut Evaluator<'v, 'a>) -> anyhow::Result<Value<'v>> + Send + 'static,
+    {
+        AsyncCall {
+            call: Box::new(call),
+            setup: Box::new(|_| ()),
+        }
+    }
+
+    /// Configure the [`Evaluator`] before the evaluation of the call,
+    /// e.g. to set a print handler or the instruction limit.
+    pub fn setup_eval(&mut self, setup: impl Fn(&mut Evaluator) + Send + 'static) {
+        self.setup = Box::new(setup);
+    }
+
+    /// Evaluate the call. Runs on the evaluation thread.
+    fn eval(self, handle: AsyncHandle) -> anyhow::Result<OwnedFrozenValue> {
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            (self.setup)(&mut eval);
+            eval.set_async_handle(handle);
+            let result = (self.call)(&mut eval)?;
+            module.set("_", result);
+        }
+        module.freeze()?.get("_")
+    }
+
+    /// Evaluate the call on [`AsyncCallPool::global`], awaiting the futures requested by
+    /// native functions.
+    pub async fn call(self) -> anyhow::Result<OwnedFrozenValue> {
+        self.call_in(AsyncCallPool::global()).await
+    }
+
+    /// Evaluate the call on the given pool.
+    pub async fn call_in(self, pool: &AsyncCallPool) -> anyhow::Result<OwnedFrozenValue> {
+        pool.run(move |handle| self.eval(handle)).await
+    }
+}
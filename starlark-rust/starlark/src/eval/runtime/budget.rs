@@ -0,0 +1,334 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Limits on the amount of work an evaluation may perform.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::errors::Diagnostic;
+use crate::values::Heap;
+
+/// How many instructions to execute between checks of heap size and elapsed time.
+const CHECK_INTERVAL: u64 = 10_000;
+
+/// Error produced when evaluation exceeds one of the limits configured
+/// with [`Evaluator::set_max_instructions`](crate::eval::Evaluator::set_max_instructions),
+/// [`Evaluator::set_max_heap_bytes`](crate::eval::Evaluator::set_max_heap_bytes) or
+/// [`Evaluator::set_timeout`](crate::eval::Evaluator::set_timeout), or is cancelled with
+/// [`Evaluator::set_cancellation`](crate::eval::Evaluator::set_cancellation).
+///
+/// The error is reported with the Starlark call stack at the point evaluation was aborted.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum EvalBudgetExceeded {
+    /// Too many bytecode instructions were executed.
+    #[error("Starlark evaluation exceeded the limit of {0} instructions")]
+    Instructions(u64),
+    /// The heap grew beyond the configured size.
+    #[error(
+        "Starlark evaluation exceeded the heap limit of {limit} bytes ({allocated} bytes allocated)"
+    )]
+    HeapBytes {
+        /// Configured limit.
+        limit: usize,
+        /// Heap size when the limit was detected.
+        allocated: usize,
+    },
+    /// Evaluation took longer than allowed.
+    #[error("Starlark evaluation exceeded the time limit of {0:?}")]
+    Timeout(Duration),
+    /// The cancellation flag was set.
+    #[error("Starlark evaluation was cancelled")]
+    Cancelled,
+}
+
+impl EvalBudgetExceeded {
+    /// Find this error in an error returned from evaluation,
+    /// which is usually wrapped in a [`Diagnostic`](crate::errors::Diagnostic).
+    pub fn find(err: &anyhow::Error) -> Option<&EvalBudgetExceeded> {
+        match err.downcast_ref::<Diagnostic>() {
+            Some(diag) => diag.message.downcast_ref(),
+            None => err.downcast_ref(),
+        }
+    }
+}
+
+/// Budget state of an evaluator.
+///
+/// When any limit is set, the interpreter loop decrements `fuel` for every instruction,
+/// and only when it reaches zero we do the (relatively) expensive checks.
+/// Without limits the interpreter does no accounting at all.
+pub(crate) struct EvalBudget {
+    /// Instructions which can be executed before the next call to `refuel`.
+    pub(crate) fuel: u64,
+    /// Fuel issued on the last `refuel`.
+    issued: u64,
+    /// Instructions executed before the last `refuel`.
+    executed: u64,
+    max_instructions: Option<u64>,
+    max_heap_bytes: Option<usize>,
+    timeout: Option<(Instant, Duration)>,
+    cancelled: Option<Arc<AtomicBool>>,
+}
+
+impl Default for EvalBudget {
+    fn default() -> Self {
+        EvalBudget {
+            fuel: u64::MAX,
+            issued: u64::MAX,
+            executed: 0,
+            max_instructions: None,
+            max_heap_bytes: None,
+            timeout: None,
+            cancelled: None,
+        }
+    }
+}
+
+impl EvalBudget {
+    pub(crate) fn set_max_instructions(&mut self, max: u64) {
+        self.max_instructions = Some(max);
+        self.reissue();
+    }
+
+    pub(crate) fn set_max_heap_bytes(&mut self, max: usize) {
+        self.max_heap_bytes = Some(max);
+        self.reissue();
+    }
+
+    pub(crate) fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some((Instant::now(), timeout));
+        self.reissue();
+    }
+
+    pub(crate) fn set_cancellation(&mut self, cancelled: Arc<AtomicBool>) {
+        self.cancelled = Some(cancelled);
+        self.reissue();
+    }
+
+    /// Whether any limit is set, so the interpreter needs to account instructions.
+    #[inline(always)]
+    pub(crate) fn is_active(&self) -> bool {
+        self.max_instructions.is_some()
+            || self.max_heap_bytes.is_some()
+            || self.timeout.is_some()
+            || self.cancelled.is_some()
+    }
+
+    /// Number of instructions executed so far.
+    pub(crate) fn instructions_executed(&self) -> u64 {
+        self.executed + (self.issued - self.fuel)
+    }
+
+    /// Account for the consumed fuel and issue new fuel according to the limits.
+    fn reissue(&mut self) {
+        self.executed = self.instructions_executed();
+        let mut fuel = if self.max_heap_bytes.is_some()
+            || self.timeout.is_some()
+            || self.cancelled.is_some()
+        {
+            CHECK_INTERVAL
+        } else {
+            u64::MAX
+        };
+        if let Some(max) = self.max_instructions {
+            fuel = fuel.min(max.saturating_sub(self.executed));
+        }
+        self.issued = fuel;
+        self.fuel = fuel;
+    }
+
+    /// Called by the interpreter when `fuel` is zero.
+    /// On success, `fuel` is guaranteed to be non-zero.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn refuel(&mut self, heap: &Heap) -> anyhow::Result<()> {
+        self.reissue();
+        if let Some(cancelled) = &self.cancelled {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err(EvalBudgetExceeded::Cancelled.into());
+            }
+        }
+        if let Some(limit) = self.max_heap_bytes {
+            let allocated = heap.allocated_bytes();
+            if allocated > limit {
+                return Err(EvalBudgetExceeded::HeapBytes { limit, allocated }.into());
+            }
+        }
+        if let Some((start, timeout)) = self.timeout {
+            if start.elapsed() > timeout {
+                return Err(EvalBudgetExceeded::Timeout(timeout).into());
+            }
+        }
+        if self.fuel == 0 {
+            // Only possible when instruction limit is reached.
+            let limit = self.max_instructions.unwrap_or_default();
+            return Err(EvalBudgetExceeded::Instructions(limit).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use dupe::Dupe;
+
+    use crate::assert::Assert;
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::eval::runtime::budget::EvalBudgetExceeded;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn eval_with(program: &str, configure: impl Fn(&mut Evaluator)) -> anyhow::Result<()> {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        configure(&mut eval);
+        let ast = AstModule::parse("budget.star", program.to_owned(), &Dialect::Extended)?;
+        eval.eval_module(ast, &Globals::standard())?;
+        Ok(())
+    }
+
+    const LOOP_FOREVER: &str = r#"
+def f():
+    x = []
+    for i in range(1000000000):
+        x.append(str(i))
+f()
+"#;
+
+    #[test]
+    fn test_no_limits() {
+        Assert::new().pass("x = [i for i in range(100000)]");
+    }
+
+    #[test]
+    fn test_instructions_exceeded() {
+        let err = eval_with(LOOP_FOREVER, |eval| eval.set_max_instructions(10_000)).unwrap_err();
+        assert!(matches!(
+            EvalBudgetExceeded::find(&err),
+            Some(EvalBudgetExceeded::Instructions(10_000))
+        ));
+        // Error must be reported with the call stack, at the statement running out of budget.
+        let err = format!("{:#}", err);
+        assert!(err.contains("budget.star:6, in <module>"), "{}", err);
+        assert!(err.contains("budget.star:5:18"), "{}", err);
+    }
+
+    #[test]
+    fn test_cancelled() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        eval_with("x = [i for i in range(100000)]", |eval| {
+            eval.set_cancellation(cancelled.dupe())
+        })
+        .unwrap();
+        cancelled.store(true, Ordering::Relaxed);
+        let err =
+            eval_with(LOOP_FOREVER, |eval| eval.set_cancellation(cancelled.dupe())).unwrap_err();
+        assert!(matches!(
+            EvalBudgetExceeded::find(&err),
+            Some(EvalBudgetExceeded::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_instructions_zero() {
+        let err = eval_with("x = 1", |eval| eval.set_max_instructions(0)).unwrap_err();
+        assert!(EvalBudgetExceeded::find(&err).is_some());
+    }
+
+    #[test]
+    fn test_instructions_within_limit() {
+        eval_with("x = [i for i in range(10)]", |eval| {
+            eval.set_max_instructions(1_000)
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_instructions_executed() {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.set_max_instructions(u64::MAX);
+        eval.eval_module(
+            AstModule::parse(
+                "a.star",
+                "x = [i for i in range(10)]".to_owned(),
+                &Dialect::Extended,
+            )
+            .unwrap(),
+            &Globals::standard(),
+        )
+        .unwrap();
+        assert!(eval.instructions_executed() > 10);
+    }
+
+    #[test]
+    fn test_instructions_not_counted_without_limits() {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.eval_module(
+            AstModule::parse(
+                "a.star",
+                "x = [i for i in range(10)]".to_owned(),
+                &Dialect::Extended,
+            )
+            .unwrap(),
+            &Globals::standard(),
+        )
+        .unwrap();
+        assert_eq!(eval.instructions_executed(), 0);
+    }
+
+    #[test]
+    fn test_heap_exceeded() {
+        let err = eval_with(LOOP_FOREVER, |eval| {
+            eval.disable_gc();
+            eval.set_max_heap_bytes(1_000_000);
+        })
+        .unwrap_err();
+        assert!(matches!(
+            EvalBudgetExceeded::find(&err),
+            Some(EvalBudgetExceeded::HeapBytes {
+                limit: 1_000_000,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let err = eval_with(LOOP_FOREVER, |eval| {
+            eval.set_timeout(Duration::from_millis(10))
+        })
+        .unwrap_err();
+        assert!(matches!(
+            EvalBudgetExceeded::find(&err),
+            Some(EvalBudgetExceeded::Timeout(_))
+        ));
+    }
+}
@@ -24,6 +24,7 @@
 // * When an exception happens, decorate it with the call stack on the way back
 //   up, in eval_call.
 
+use std::cmp;
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
@@ -50,7 +51,14 @@ struct CheapFrame<'v> {
     span: Option<FrozenRef<'static, FrameSpan>>,
 }
 
-impl CheapFrame<'_> {
+impl<'v> CheapFrame<'v> {
+    fn empty() -> CheapFrame<'v> {
+        CheapFrame {
+            function: Value::new_none(),
+            span: None,
+        }
+    }
+
     fn location(&self) -> Option<FileSpan> {
         self.span.map(|span| span.span.to_file_span())
     }
@@ -91,17 +99,18 @@ enum CallStackError {
 #[derive(Debug)]
 pub(crate) struct CheapCallStack<'v> {
     count: usize,
-    stack: [CheapFrame<'v>; MAX_CALLSTACK_RECURSION],
+    /// Allocated once with `max_size` frames, so `push` doesn't need to grow it.
+    stack: Vec<CheapFrame<'v>>,
+    /// Maximum number of frames, see [`DEFAULT_MAX_CALLSTACK_SIZE`].
+    max_size: usize,
 }
 
 impl<'v> Default for CheapCallStack<'v> {
     fn default() -> Self {
         Self {
             count: 0,
-            stack: [CheapFrame {
-                function: Value::new_none(),
-                span: None,
-            }; MAX_CALLSTACK_RECURSION],
+            stack: vec![CheapFrame::empty(); DEFAULT_MAX_CALLSTACK_SIZE],
+            max_size: DEFAULT_MAX_CALLSTACK_SIZE,
         }
     }
 }
@@ -117,8 +126,8 @@ impl<'v> Default for CheapCallStack<'v> {
 // * [tokio default stack size is 2MB][1]
 // [1] https://docs.rs/tokio/0.2.1/tokio/runtime/struct.Builder.html#method.thread_stack_size
 // TODO(nga): count loops in call stack size.
-// TODO(nga): make it configurable.
-const MAX_CALLSTACK_RECURSION: usize = 50;
+// The limit can be changed with `Evaluator::set_max_callstack_size`.
+const DEFAULT_MAX_CALLSTACK_SIZE: usize = 50;
 
 unsafe impl<'v> Trace<'v> for CheapCallStack<'v> {
     fn trace(&mut self, tracer: &Tracer<'v>) {
@@ -143,7 +152,7 @@ impl<'v> CheapCallStack<'v> {
         function: Value<'v>,
         span: Option<FrozenRef<'static, FrameSpan>>,
     ) -> anyhow::Result<()> {
-        if unlikely(self.count >= MAX_CALLSTACK_RECURSION) {
+        if unlikely(self.count >= self.max_size) {
             return Err(CallStackError::Overflow.into());
         }
         self.stack[self.count] = CheapFrame { function, span };
@@ -151,6 +160,14 @@ impl<'v> CheapCallStack<'v> {
         Ok(())
     }
 
+    /// Set the maximum number of frames, including the frame of the module itself.
+    pub(crate) fn set_max_size(&mut self, max_size: usize) {
+        // Keep the frames already pushed, `push` fails until they are popped.
+        self.stack
+            .resize(cmp::max(max_size, self.count), CheapFrame::empty());
+        self.max_size = max_size;
+    }
+
     /// Remove the top element from the stack. Called after `push`.
     pub(crate) fn pop(&mut self) {
         debug_assert!(self.count >= 1);
@@ -167,7 +184,7 @@ impl<'v> CheapCallStack<'v> {
     /// either there the stack is empty, or the top of the stack lacks location
     /// information (e.g. called from Rust).
     pub(crate) fn top_frame(&self) -> Option<Frame> {
-        Some(self.stack.last().as_ref()?.to_frame())
+        Some(self.stack[..self.count].last()?.to_frame())
     }
 
     /// The location at the top of the stack. May be `None` if
@@ -19,6 +19,9 @@ use std::collections::HashSet;
 use std::mem;
 use std::mem::MaybeUninit;
 use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
 
 use dupe::Dupe;
 use thiserror::Error;
@@ -41,8 +44,10 @@ use crate::eval::compiler::def::CopySlotFromParent;
 use crate::eval::compiler::def::Def;
 use crate::eval::compiler::def::DefInfo;
 use crate::eval::compiler::def::FrozenDef;
+use crate::eval::runtime::async_call::AsyncHandle;
 use crate::eval::runtime::before_stmt::BeforeStmt;
 use crate::eval::runtime::before_stmt::BeforeStmtFunc;
+use crate::eval::runtime::budget::EvalBudget;
 use crate::eval::runtime::call_stack::CheapCallStack;
 use crate::eval::runtime::frame_span::FrameSpan;
 use crate::eval::runtime::inlined_frame::InlinedFrames;
@@ -92,10 +97,6 @@ pub(crate) enum EvaluatorError {
     ProfileOrInstrumentationAlreadyEnabled,
     #[error("Top frame is not def (internal error)")]
     TopFrameNotDef,
-    #[error(
-        "Coverage profile generation not implemented (but can be obtained with `.coverage()` function)"
-    )]
-    CoverageNotImplemented,
     #[error("Coverage not enabled")]
     CoverageNotEnabled,
 }
@@ -131,6 +132,8 @@ pub struct Evaluator<'v, 'a> {
     pub(crate) verbose_gc: bool,
     // Size of the heap when we should next perform a GC.
     pub(crate) next_gc_level: usize,
+    // Limits on instructions, heap size and time, checked by the interpreter loop.
+    pub(crate) budget: EvalBudget,
     // Profiling or instrumentation enabled.
     pub(crate) profile_or_instrumentation_mode: ProfileOrInstrumentationMode,
     // Extra functions to run on each statement, usually empty
@@ -154,6 +157,8 @@ pub struct Evaluator<'v, 'a> {
         Option<Box<dyn Fn() -> anyhow::Result<Box<dyn BreakpointConsole>>>>,
     /// Use in implementation of `print` function.
     pub(crate) print_handler: &'a (dyn PrintHandler + 'a),
+    // Where native functions send the futures they await, see `set_async_handle`.
+    pub(crate) async_handle: Option<AsyncHandle>,
     // The Starlark-level call-stack of functions.
     // Must go last because it's quite a big structure
     pub(crate) call_stack: CheapCallStack<'v>,
@@ -179,6 +184,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             loader: None,
             extra: None,
             next_gc_level: GC_THRESHOLD,
+            budget: EvalBudget::default(),
             disable_gc: false,
             alloca: Alloca::new(),
             profile_or_instrumentation_mode: ProfileOrInstrumentationMode::None,
@@ -193,6 +199,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             string_pool: StringPool::default(),
             breakpoint_handler: None,
             print_handler: &StderrPrintHandler,
+            async_handle: None,
             verbose_gc: false,
         }
     }
@@ -209,6 +216,61 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.verbose_gc = true;
     }
 
+    /// Abort evaluation with [`EvalBudgetExceeded`](crate::eval::EvalBudgetExceeded)
+    /// after executing this many bytecode instructions.
+    pub fn set_max_instructions(&mut self, max: u64) {
+        self.budget.set_max_instructions(max);
+    }
+
+    /// Abort evaluation with [`EvalBudgetExceeded`](crate::eval::EvalBudgetExceeded)
+    /// when the heap grows larger than this many bytes.
+    ///
+    /// The heap size is checked periodically, so it may exceed the limit slightly
+    /// before evaluation is aborted.
+    pub fn set_max_heap_bytes(&mut self, max: usize) {
+        self.budget.set_max_heap_bytes(max);
+    }
+
+    /// Abort evaluation with [`EvalBudgetExceeded`](crate::eval::EvalBudgetExceeded)
+    /// when more than `timeout` has elapsed since this function was called.
+    ///
+    /// Time is only checked while executing Starlark code,
+    /// so a long-running native function is not interrupted.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.budget.set_timeout(timeout);
+    }
+
+    /// Abort evaluation with [`EvalBudgetExceeded`](crate::eval::EvalBudgetExceeded)
+    /// once `cancelled` is set, e.g. by another thread.
+    ///
+    /// The flag is checked periodically while executing Starlark code, like the heap size.
+    pub fn set_cancellation(&mut self, cancelled: Arc<AtomicBool>) {
+        self.budget.set_cancellation(cancelled);
+    }
+
+    /// Limit the depth of the Starlark call stack, which bounds recursion.
+    /// Calls beyond this depth fail with a "Starlark call stack overflow" error.
+    /// The default is 50, and the module itself counts as one frame.
+    ///
+    /// Each Starlark frame also uses native stack, so deep recursion may require
+    /// running the evaluator on a thread with a larger stack.
+    pub fn set_max_callstack_size(&mut self, max: usize) {
+        self.call_stack.set_max_size(max);
+    }
+
+    /// Number of bytecode instructions executed by this evaluator so far.
+    ///
+    /// Instructions are only counted while a limit is set with
+    /// [`set_max_instructions`](Evaluator::set_max_instructions),
+    /// [`set_max_heap_bytes`](Evaluator::set_max_heap_bytes),
+    /// [`set_timeout`](Evaluator::set_timeout) or
+    /// [`set_cancellation`](Evaluator::set_cancellation),
+    /// so evaluation without limits runs at full speed.
+    /// When none of them is set, this is zero.
+    pub fn instructions_executed(&self) -> u64 {
+        self.budget.instructions_executed()
+    }
+
     /// Set the [`FileLoader`] used to resolve `load()` statements.
     /// A list of all load statements can be obtained through
     /// [`AstModule::loads`](crate::syntax::AstModule::loads).
@@ -332,7 +394,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
                 Err(EvaluatorError::RetainedMemoryProfilingCannotBeObtainedFromEvaluator.into())
             }
             ProfileMode::Statement => self.stmt_profile.gen(),
-            ProfileMode::Coverage => Err(EvaluatorError::CoverageNotImplemented.into()),
+            ProfileMode::Coverage => self.stmt_profile.gen_coverage(),
             ProfileMode::Bytecode => self.bc_profile.gen_bc_profile(),
             ProfileMode::BytecodePairs => self.bc_profile.gen_bc_pairs_profile(),
             ProfileMode::TimeFlame => self.flame_profile.gen(),
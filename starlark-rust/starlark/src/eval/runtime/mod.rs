@@ -16,7 +16,9 @@
  */
 
 pub(crate) mod arguments;
+pub(crate) mod async_call;
 pub(crate) mod before_stmt;
+pub(crate) mod budget;
 pub(crate) mod call_stack;
 pub(crate) mod evaluator;
 pub(crate) mod file_loader;
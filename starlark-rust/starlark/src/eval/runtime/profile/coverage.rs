@@ -0,0 +1,414 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Line and function coverage, written as LCOV or Cobertura XML.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::codemap::CodeMap;
+use crate::codemap::Pos;
+use crate::codemap::Span;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::Stmt;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+/// Coverage of a single `def`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FunctionCoverage {
+    /// Last line of the function, 1-based.
+    end_line: u32,
+    /// Number of calls.
+    hits: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct FileCoverage {
+    /// Execution count of every 1-based line which starts a statement.
+    lines: BTreeMap<u32, u64>,
+    /// Functions by the line of their `def` and their name.
+    /// Nested functions are named `outer.inner`.
+    functions: BTreeMap<(u32, String), FunctionCoverage>,
+}
+
+impl FileCoverage {
+    fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|x| **x > 0).count()
+    }
+
+    fn merge(&mut self, other: &FileCoverage) {
+        for (line, hits) in &other.lines {
+            *self.lines.entry(*line).or_default() += hits;
+        }
+        for (key, function) in &other.functions {
+            self.functions
+                .entry(key.clone())
+                .and_modify(|x| x.hits += function.hits)
+                .or_insert_with(|| function.clone());
+        }
+    }
+}
+
+/// Coverage of any number of files, which can be aggregated over many evaluations.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct CoverageData {
+    files: BTreeMap<String, FileCoverage>,
+}
+
+/// Statements which are compiled without a `before_stmt` hook,
+/// so would always appear to be unexecuted.
+fn is_invisible(x: &AstStmt) -> bool {
+    match &**x {
+        Stmt::Statements(_) | Stmt::Pass | Stmt::Load(_) => true,
+        // Docstrings and other constants are optimized away.
+        Stmt::Expression(e) => matches!(&**e, Expr::Literal(_)),
+        _ => false,
+    }
+}
+
+/// The visible statements of a block, looking inside nested `Statements`.
+fn block(x: &AstStmt) -> Vec<&AstStmt> {
+    fn f<'a>(x: &'a AstStmt, res: &mut Vec<&'a AstStmt>) {
+        match &**x {
+            Stmt::Statements(xs) => xs.iter().for_each(|x| f(x, res)),
+            _ if is_invisible(x) => {}
+            _ => res.push(x),
+        }
+    }
+
+    let mut res = Vec::new();
+    f(x, &mut res);
+    res
+}
+
+fn line(codemap: &CodeMap, pos: Pos) -> u32 {
+    codemap.find_line(pos) as u32 + 1
+}
+
+impl CoverageData {
+    /// Add coverage of a file, given the number of times each statement in it was executed.
+    pub(crate) fn add_file(&mut self, codemap: &CodeMap, hits: &HashMap<Span, u64>) {
+        let mut file = FileCoverage::default();
+        for (span, count) in hits {
+            let hits = file.lines.entry(line(codemap, span.begin())).or_default();
+            *hits = (*hits).max(*count);
+        }
+
+        // The profile only knows about statements which ran, so find the others in the AST.
+        // Every file in the profile parsed once already, so the most permissive dialect works.
+        if let Ok(ast) = AstModule::parse(
+            codemap.filename(),
+            codemap.source().to_owned(),
+            &Dialect::Extended,
+        ) {
+            fn walk(codemap: &CodeMap, x: &AstStmt, prefix: &str, file: &mut FileCoverage) {
+                if !is_invisible(x) {
+                    file.lines.entry(line(codemap, x.span.begin())).or_default();
+                }
+                match &**x {
+                    Stmt::Def(def) => {
+                        let name = format!("{}{}", prefix, def.name.0);
+                        // A call always runs the first statement of the body.
+                        let hits = block(&def.body).first().map_or(0, |first| {
+                            file.lines
+                                .get(&line(codemap, first.span.begin()))
+                                .copied()
+                                .unwrap_or_default()
+                        });
+                        let end = Pos::new(x.span.end().get().saturating_sub(1));
+                        file.functions.insert(
+                            (line(codemap, x.span.begin()), name.clone()),
+                            FunctionCoverage {
+                                end_line: line(codemap, end),
+                                hits,
+                            },
+                        );
+                        walk(codemap, &def.body, &format!("{}.", name), file);
+                    }
+                    _ => x.visit_stmt(|x| walk(codemap, x, prefix, file)),
+                }
+            }
+            walk(codemap, &ast.statement, "", &mut file);
+        }
+
+        self.files
+            .entry(codemap.filename().to_owned())
+            .or_default()
+            .merge(&file);
+    }
+
+    pub(crate) fn merge<'a>(data: impl IntoIterator<Item = &'a CoverageData>) -> CoverageData {
+        let mut res = CoverageData::default();
+        for x in data {
+            for (name, file) in &x.files {
+                res.files.entry(name.clone()).or_default().merge(file);
+            }
+        }
+        res
+    }
+
+    /// Write in the LCOV tracefile format, as understood by `genhtml`.
+    pub(crate) fn gen_lcov(&self) -> String {
+        let mut res = String::new();
+        for (name, file) in &self.files {
+            writeln!(res, "TN:").unwrap();
+            writeln!(res, "SF:{}", name).unwrap();
+            for (line, name) in file.functions.keys() {
+                writeln!(res, "FN:{},{}", line, name).unwrap();
+            }
+            for ((_, name), function) in &file.functions {
+                writeln!(res, "FNDA:{},{}", function.hits, name).unwrap();
+            }
+            writeln!(res, "FNF:{}", file.functions.len()).unwrap();
+            let functions_hit = file.functions.values().filter(|x| x.hits > 0).count();
+            writeln!(res, "FNH:{}", functions_hit).unwrap();
+            for (line, hits) in &file.lines {
+                writeln!(res, "DA:{},{}", line, hits).unwrap();
+            }
+            writeln!(res, "LF:{}", file.lines.len()).unwrap();
+            writeln!(res, "LH:{}", file.lines_hit()).unwrap();
+            writeln!(res, "end_of_record").unwrap();
+        }
+        res
+    }
+
+    /// Write in the Cobertura XML format. Each directory is a package, each file a class,
+    /// and each `def` a method. Starlark has no branch coverage, so the branch rates are zero.
+    pub(crate) fn gen_cobertura(&self) -> String {
+        fn rate(hit: usize, total: usize) -> String {
+            if total == 0 {
+                "1".to_owned()
+            } else {
+                format!("{:.4}", hit as f64 / total as f64)
+            }
+        }
+
+        fn write_lines<'a>(
+            res: &mut String,
+            indent: &str,
+            lines: impl Iterator<Item = (&'a u32, &'a u64)>,
+        ) {
+            writeln!(res, "{}<lines>", indent).unwrap();
+            for (line, hits) in lines {
+                writeln!(
+                    res,
+                    r#"{}  <line number="{}" hits="{}" branch="false"/>"#,
+                    indent, line, hits
+                )
+                .unwrap();
+            }
+            writeln!(res, "{}</lines>", indent).unwrap();
+        }
+
+        let mut packages: BTreeMap<String, Vec<(&String, &FileCoverage)>> = BTreeMap::new();
+        for (name, file) in &self.files {
+            let package = Path::new(name)
+                .parent()
+                .map_or_else(String::new, |x| x.to_string_lossy().into_owned());
+            packages.entry(package).or_default().push((name, file));
+        }
+
+        let lines_valid: usize = self.files.values().map(|x| x.lines.len()).sum();
+        let lines_covered: usize = self.files.values().map(|x| x.lines_hit()).sum();
+
+        let mut res = String::new();
+        writeln!(res, r#"<?xml version="1.0" ?>"#).unwrap();
+        writeln!(
+            res,
+            r#"<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">"#
+        )
+        .unwrap();
+        writeln!(
+            res,
+            r#"<coverage line-rate="{}" branch-rate="0" lines-covered="{}" lines-valid="{}" branches-covered="0" branches-valid="0" complexity="0" version="0" timestamp="0">"#,
+            rate(lines_covered, lines_valid),
+            lines_covered,
+            lines_valid
+        )
+        .unwrap();
+        writeln!(res, "  <packages>").unwrap();
+        for (package, files) in packages {
+            let valid: usize = files.iter().map(|(_, x)| x.lines.len()).sum();
+            let covered: usize = files.iter().map(|(_, x)| x.lines_hit()).sum();
+            writeln!(
+                res,
+                r#"    <package name="{}" line-rate="{}" branch-rate="0" complexity="0">"#,
+                xml_escape(&package),
+                rate(covered, valid)
+            )
+            .unwrap();
+            writeln!(res, "      <classes>").unwrap();
+            for (name, file) in files {
+                writeln!(
+                    res,
+                    r#"        <class name="{}" filename="{}" line-rate="{}" branch-rate="0" complexity="0">"#,
+                    xml_escape(name),
+                    xml_escape(name),
+                    rate(file.lines_hit(), file.lines.len())
+                )
+                .unwrap();
+                writeln!(res, "          <methods>").unwrap();
+                for ((line, function_name), function) in &file.functions {
+                    let lines = || file.lines.range(*line..=function.end_line);
+                    writeln!(
+                        res,
+                        r#"            <method name="{}" signature="" line-rate="{}" branch-rate="0" complexity="0">"#,
+                        xml_escape(function_name),
+                        rate(lines().filter(|x| *x.1 > 0).count(), lines().count())
+                    )
+                    .unwrap();
+                    write_lines(&mut res, "              ", lines());
+                    writeln!(res, "            </method>").unwrap();
+                }
+                writeln!(res, "          </methods>").unwrap();
+                write_lines(&mut res, "          ", file.lines.iter());
+                writeln!(res, "        </class>").unwrap();
+            }
+            writeln!(res, "      </classes>").unwrap();
+            writeln!(res, "    </package>").unwrap();
+        }
+        writeln!(res, "  </packages>").unwrap();
+        writeln!(res, "</coverage>").unwrap();
+        res
+    }
+}
+
+fn xml_escape(x: &str) -> String {
+    let mut res = String::with_capacity(x.len());
+    for c in x.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert::test_functions;
+    use crate::environment::GlobalsBuilder;
+    use crate::environment::Module;
+    use crate::eval::Evaluator;
+    use crate::eval::ProfileData;
+    use crate::eval::ProfileMode;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    const PROGRAM: &str = r#"
+def f(x):
+    """Docstring."""
+    if x:
+        y = 1
+    else:
+        y = noop(2)
+    return y
+
+def g():
+    pass
+
+f(True)
+f(True)
+"#;
+
+    fn coverage(program: &str) -> ProfileData {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_profile(&ProfileMode::Coverage).unwrap();
+        let ast = AstModule::parse("dir/cov.star", program.to_owned(), &Dialect::Extended).unwrap();
+        let mut globals = GlobalsBuilder::standard();
+        test_functions(&mut globals);
+        eval.eval_module(ast, &globals.build()).unwrap();
+        eval.gen_profile().unwrap()
+    }
+
+    #[test]
+    fn test_lcov() {
+        assert_eq!(
+            coverage(PROGRAM).gen().unwrap(),
+            r#"TN:
+SF:dir/cov.star
+FN:2,f
+FN:10,g
+FNDA:2,f
+FNDA:0,g
+FNF:2
+FNH:1
+DA:2,1
+DA:4,2
+DA:5,2
+DA:7,0
+DA:8,2
+DA:10,1
+DA:13,1
+DA:14,1
+LF:8
+LH:7
+end_of_record
+"#
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = ProfileData::merge([
+            &coverage(PROGRAM),
+            &coverage(&PROGRAM.replace("f(True)\nf(True)", "f(False)")),
+        ])
+        .unwrap()
+        .gen()
+        .unwrap();
+        assert!(merged.contains("FNDA:3,f\n"), "{}", merged);
+        assert!(merged.contains("DA:5,2\nDA:7,1\n"), "{}", merged);
+        assert!(merged.contains("LH:8\n"), "{}", merged);
+    }
+
+    #[test]
+    fn test_cobertura() {
+        let xml = coverage(PROGRAM).gen_cobertura().unwrap();
+        assert!(
+            xml.contains(
+                r#"<coverage line-rate="0.8750" branch-rate="0" lines-covered="7" lines-valid="8""#
+            ),
+            "{}",
+            xml
+        );
+        assert!(
+            xml.contains(r#"<package name="dir" line-rate="0.8750""#),
+            "{}",
+            xml
+        );
+        assert!(
+            xml.contains(r#"<method name="f" signature="" line-rate="0.8000""#),
+            "{}",
+            xml
+        );
+        assert!(
+            xml.contains(r#"<line number="7" hits="0" branch="false"/>"#),
+            "{}",
+            xml
+        );
+    }
+}
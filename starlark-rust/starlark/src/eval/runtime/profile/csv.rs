@@ -140,6 +140,12 @@ impl CsvValue for i32 {
     }
 }
 
+impl CsvValue for isize {
+    fn format_for_csv(&self) -> String {
+        self.to_string()
+    }
+}
+
 impl CsvValue for u128 {
     fn format_for_csv(&self) -> String {
         self.to_string()
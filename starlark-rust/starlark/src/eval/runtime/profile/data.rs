@@ -23,6 +23,7 @@ use dupe::Dupe;
 
 use crate::eval::runtime::profile::bc::BcPairsProfileData;
 use crate::eval::runtime::profile::bc::BcProfileData;
+use crate::eval::runtime::profile::coverage::CoverageData;
 use crate::eval::runtime::profile::flamegraph::FlameGraphData;
 use crate::eval::ProfileMode;
 use crate::slice_vec_ext::SliceExt;
@@ -38,6 +39,8 @@ enum ProfileDataError {
     DifferentProfileModes,
     #[error("Merge of profile data for profile mode `{0}` is not implemented")]
     MergeNotImplemented(ProfileMode),
+    #[error("Cobertura output is only available for coverage profiles")]
+    CoberturaNotCoverage,
 }
 
 #[derive(Clone, Debug)]
@@ -47,6 +50,7 @@ pub(crate) enum ProfileDataImpl {
     AggregateHeapProfileInfo(Box<AggregateHeapProfileInfo>),
     /// Flame graph data is in milliseconds.
     TimeFlameProfile(FlameGraphData),
+    Coverage(Box<CoverageData>),
     Other(String),
 }
 
@@ -87,12 +91,30 @@ impl ProfileData {
             (ProfileDataImpl::TimeFlameProfile(_), _) => {
                 Err(ProfileDataError::ProfileDataNotConsistent.into())
             }
+            (ProfileDataImpl::Coverage(data), ProfileMode::Coverage) => Ok(data.gen_lcov()),
+            (ProfileDataImpl::Coverage(_), _) => {
+                Err(ProfileDataError::ProfileDataNotConsistent.into())
+            }
+        }
+    }
+
+    /// Generate Cobertura XML for a coverage profile ([`gen`](ProfileData::gen) produces LCOV).
+    pub fn gen_cobertura(&self) -> anyhow::Result<String> {
+        match &self.profile {
+            ProfileDataImpl::Coverage(data) => Ok(data.gen_cobertura()),
+            _ => Err(ProfileDataError::CoberturaNotCoverage.into()),
         }
     }
 
     /// Write to a file.
     pub fn write(&self, path: &Path) -> anyhow::Result<()> {
-        fs::write(path, self.gen()?).with_context(|| {
+        let profile = match &self.profile {
+            ProfileDataImpl::Coverage(_) if path.extension().map_or(false, |x| x == "xml") => {
+                self.gen_cobertura()?
+            }
+            _ => self.gen()?,
+        };
+        fs::write(path, profile).with_context(|| {
             format!(
                 "write profile `{}` data to `{}`",
                 self.profile_mode,
@@ -152,6 +174,13 @@ impl ProfileData {
                 let profile = FlameGraphData::merge(profiles);
                 ProfileDataImpl::TimeFlameProfile(profile)
             }
+            ProfileMode::Coverage => {
+                let profiles = profiles.try_map(|p| match &p.profile {
+                    ProfileDataImpl::Coverage(data) => Ok(&**data),
+                    _ => Err(ProfileDataError::ProfileDataNotConsistent),
+                })?;
+                ProfileDataImpl::Coverage(Box::new(CoverageData::merge(profiles)))
+            }
             profile_mode => {
                 return Err(ProfileDataError::MergeNotImplemented(profile_mode.dupe()).into());
             }
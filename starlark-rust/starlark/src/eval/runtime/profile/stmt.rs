@@ -29,8 +29,10 @@ use crate::codemap::FileSpan;
 use crate::codemap::FileSpanRef;
 use crate::codemap::ResolvedFileSpan;
 use crate::codemap::Span;
+use crate::eval::runtime::profile::coverage::CoverageData;
 use crate::eval::runtime::profile::csv::CsvWriter;
 use crate::eval::runtime::profile::data::ProfileData;
+use crate::eval::runtime::profile::data::ProfileDataImpl;
 use crate::eval::runtime::small_duration::SmallDuration;
 use crate::eval::ProfileMode;
 
@@ -165,6 +167,29 @@ impl StmtProfileData {
             })
             .collect()
     }
+
+    fn coverage_data(&self) -> CoverageData {
+        let mut hits: HashMap<CodeMapId, HashMap<Span, u64>> = HashMap::new();
+        for ((file, span), (count, _)) in &self.stmts {
+            if *file != CodeMapId::EMPTY {
+                hits.entry(*file).or_default().insert(*span, *count as u64);
+            }
+        }
+        // The statement running last has not been counted yet.
+        if self.last_span.0 != CodeMapId::EMPTY {
+            *hits
+                .entry(self.last_span.0)
+                .or_default()
+                .entry(self.last_span.1)
+                .or_default() += 1;
+        }
+
+        let mut data = CoverageData::default();
+        for (id, codemap) in &self.files {
+            data.add_file(codemap, hits.get(id).unwrap_or(&HashMap::new()));
+        }
+        data
+    }
 }
 
 impl StmtProfile {
@@ -194,6 +219,16 @@ impl StmtProfile {
         }
     }
 
+    pub(crate) fn gen_coverage(&self) -> anyhow::Result<ProfileData> {
+        match &self.0 {
+            Some(data) => Ok(ProfileData {
+                profile_mode: ProfileMode::Coverage,
+                profile: ProfileDataImpl::Coverage(Box::new(data.coverage_data())),
+            }),
+            None => Err(StmtProfileError::NotEnabled.into()),
+        }
+    }
+
     pub(crate) fn coverage(&self) -> anyhow::Result<HashSet<ResolvedFileSpan>> {
         Ok(self
             .0
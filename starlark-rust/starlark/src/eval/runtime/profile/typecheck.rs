@@ -17,13 +17,23 @@
 
 //! Runtime typecheck profile.
 
+use std::collections::HashMap;
 use std::time::Duration;
 
+use crate::codemap::CodeMap;
 use crate::collections::SmallMap;
+use crate::environment::Globals;
 use crate::eval::runtime::profile::csv::CsvWriter;
 use crate::eval::runtime::profile::data::ProfileData;
 use crate::eval::runtime::small_duration::SmallDuration;
 use crate::eval::ProfileMode;
+use crate::stdlib::LibraryExtension;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+use crate::typing::typecheck::ExpressionCounts;
+use crate::typing::OracleDocs;
+use crate::typing::OracleStandard;
+use crate::typing::TypingOracle;
 use crate::values::FrozenStringValue;
 
 #[derive(Debug, thiserror::Error)]
@@ -37,6 +47,9 @@ pub(crate) struct TypecheckProfile {
     pub(crate) enabled: bool,
     // TODO(nga): we don't need ordered map here.
     by_function: SmallMap<FrozenStringValue, SmallDuration>,
+    /// Expressions the static typechecker could type, for all modules evaluated.
+    expressions: ExpressionCounts,
+    expressions_by_function: HashMap<String, ExpressionCounts>,
 }
 
 impl TypecheckProfile {
@@ -48,20 +61,58 @@ impl TypecheckProfile {
             .or_default() += time;
     }
 
+    /// Statically typecheck a module about to be evaluated,
+    /// so we can report how many expressions have a type other than `Any`.
+    pub(crate) fn add_module(&mut self, codemap: &CodeMap, dialect: &Dialect, globals: &Globals) {
+        // We have to reparse, since evaluation consumes the AST
+        let ast = match AstModule::parse(codemap.filename(), codemap.source().to_owned(), dialect) {
+            Ok(ast) => ast,
+            Err(_) => return,
+        };
+        let oracle: Vec<Box<dyn TypingOracle>> = vec![
+            Box::new(OracleStandard::new(LibraryExtension::all())),
+            Box::new(OracleDocs::new_object(&globals.documentation())),
+        ];
+        let (_, typemap, _, _) = ast.typecheck(&oracle, &HashMap::new());
+        self.expressions.add(typemap.expressions);
+        for (name, count) in typemap.expressions_by_function {
+            self.expressions_by_function
+                .entry(name)
+                .or_default()
+                .add(count);
+        }
+    }
+
     fn gen_csv(&self) -> String {
         let total_time = self.by_function.values().sum::<SmallDuration>();
 
-        let mut w = CsvWriter::new(["Function", "Time (s)"]);
+        let mut w = CsvWriter::new([
+            "Function",
+            "Time (s)",
+            "Typed expressions",
+            "Any expressions",
+        ]);
         w.write_display("TOTAL");
         w.write_value(total_time);
+        w.write_value(self.expressions.typed);
+        w.write_value(self.expressions.any);
         w.finish_row();
 
-        let mut by_function = Vec::from_iter(&self.by_function);
-        by_function.sort_by_key(|(name, t)| (u64::MAX - t.nanos, *name));
+        let mut by_function: HashMap<&str, (SmallDuration, ExpressionCounts)> = HashMap::new();
+        for (name, t) in &self.by_function {
+            by_function.entry(name.as_str()).or_default().0 += *t;
+        }
+        for (name, count) in &self.expressions_by_function {
+            by_function.entry(name.as_str()).or_default().1.add(*count);
+        }
+        let mut by_function = Vec::from_iter(by_function);
+        by_function.sort_by_key(|(name, (t, _))| (u64::MAX - t.nanos, *name));
 
-        for (name, t) in by_function {
-            w.write_display(name.as_str());
+        for (name, (t, count)) in by_function {
+            w.write_display(name);
             w.write_value(t);
+            w.write_value(count.typed);
+            w.write_value(count.any);
             w.finish_row();
         }
 
@@ -105,10 +156,15 @@ g()
 
         let csv = eval.typecheck_profile.gen_csv();
         let lines: Vec<&str> = csv.lines().collect();
-        assert_eq!("Function,Time (s)", lines[0]);
+        assert_eq!(
+            "Function,Time (s),Typed expressions,Any expressions",
+            lines[0]
+        );
         assert!(lines[1].starts_with("\"TOTAL\","), "{:?}", lines[1]);
         assert!(lines[2].starts_with("\"f\","), "{:?}", lines[2]);
-        assert_eq!(3, lines.len());
+        // `g` has no runtime typechecks, but is still statically typechecked
+        assert!(lines[3].starts_with("\"g\",0.000,"), "{:?}", lines[3]);
+        assert_eq!(4, lines.len());
 
         Ok(())
     }
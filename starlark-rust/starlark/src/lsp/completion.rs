@@ -0,0 +1,151 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Work out what kind of completion is wanted at the cursor.
+//!
+//! This works on the raw text rather than the AST, because while the user is typing
+//! (e.g. `foo.`) the file usually does not parse.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// What is being completed, determined from the text before the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CompletionContext {
+    /// The path of a `load()` statement, e.g. `load("foo/b`.
+    LoadPath {
+        /// The text between the opening quote and the cursor.
+        current_value: String,
+    },
+    /// The name of a symbol in a `load()` statement, e.g. `load("foo.star", "b`.
+    LoadSymbol {
+        /// The path of the module being loaded, as written.
+        path: String,
+    },
+    /// A member of a value, e.g. `foo.b`.
+    Member {
+        /// The expression to the left of the last `.`, split into identifiers.
+        segments: Vec<String>,
+    },
+    /// An identifier, or nothing at all yet.
+    Identifier,
+    /// Somewhere completion makes no sense, e.g. inside a string literal or a comment.
+    None,
+}
+
+impl CompletionContext {
+    /// Determine the completion context from all the text in the file before the cursor.
+    pub(crate) fn from_prefix(prefix: &str) -> CompletionContext {
+        static LOAD_PATH_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r#"load\(\s*["']([^"'\n]*)$"#).unwrap());
+        static LOAD_SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(
+                r#"load\(\s*["']([^"'\n]+)["']\s*,(?:\s*(?:\w+\s*=\s*)?["'][^"'\n]*["']\s*,)*\s*(?:\w+\s*=\s*)?["'][^"'\n]*$"#,
+            )
+            .unwrap()
+        });
+        static MEMBER_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"(?:^|[^\w.])((?:[A-Za-z_]\w*\.)+)\w*$").unwrap());
+
+        if let Some(captures) = LOAD_PATH_RE.captures(prefix) {
+            return CompletionContext::LoadPath {
+                current_value: captures[1].to_owned(),
+            };
+        }
+        if let Some(captures) = LOAD_SYMBOL_RE.captures(prefix) {
+            return CompletionContext::LoadSymbol {
+                path: captures[1].to_owned(),
+            };
+        }
+
+        let line = prefix.rsplit('\n').next().unwrap_or_default();
+        if Self::in_string_or_comment(line) {
+            return CompletionContext::None;
+        }
+        match MEMBER_RE.captures(line) {
+            Some(captures) => CompletionContext::Member {
+                segments: captures[1]
+                    .trim_end_matches('.')
+                    .split('.')
+                    .map(str::to_owned)
+                    .collect(),
+            },
+            None => CompletionContext::Identifier,
+        }
+    }
+
+    /// Whether the end of `line` is inside a single line string literal or a comment.
+    fn in_string_or_comment(line: &str) -> bool {
+        let mut quote = None;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (None, '#') => return true,
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(_), '\\') => {
+                    chars.next();
+                }
+                (Some(q), c) if q == c => quote = None,
+                _ => {}
+            }
+        }
+        quote.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::completion::CompletionContext;
+
+    #[test]
+    fn test_completion_context() {
+        assert_eq!(
+            CompletionContext::LoadPath {
+                current_value: "foo/b".to_owned()
+            },
+            CompletionContext::from_prefix("x = 1\nload(\"foo/b")
+        );
+        assert_eq!(
+            CompletionContext::LoadSymbol {
+                path: "foo.star".to_owned()
+            },
+            CompletionContext::from_prefix("load(\"foo.star\", \"a\", b = \"c\",\n    \"d")
+        );
+        assert_eq!(
+            CompletionContext::Member {
+                segments: vec!["foo".to_owned(), "bar".to_owned()]
+            },
+            CompletionContext::from_prefix("x = foo.bar.ba")
+        );
+        assert_eq!(
+            CompletionContext::Identifier,
+            CompletionContext::from_prefix("load(\"foo.star\", \"a\")\nx = fo")
+        );
+        assert_eq!(
+            CompletionContext::None,
+            CompletionContext::from_prefix("x = \"foo.b")
+        );
+        assert_eq!(
+            CompletionContext::None,
+            CompletionContext::from_prefix("x = 1 # foo.b")
+        );
+        assert_eq!(
+            CompletionContext::Identifier,
+            CompletionContext::from_prefix("x = 1.")
+        );
+    }
+}
@@ -0,0 +1,285 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Documentation extracted directly from the AST, without evaluating the module.
+
+use crate::docs::DocFunction;
+use crate::docs::DocItem;
+use crate::docs::DocParam;
+use crate::docs::DocProperty;
+use crate::docs::DocString;
+use crate::docs::DocStringKind;
+use crate::docs::DocType;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AstAssign;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::AstString;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::ExprP;
+use crate::syntax::ast::ParameterP;
+use crate::syntax::ast::StmtP;
+use crate::syntax::AstModule;
+
+fn doc_type(typ: &Option<Box<AstExpr>>) -> Option<DocType> {
+    typ.as_ref().map(|t| DocType {
+        raw_type: t.node.to_string(),
+    })
+}
+
+/// Documentation for a `def`, using the same parameter naming as
+/// [`ParametersSpec::documentation`](crate::eval::ParametersSpec::documentation).
+pub(crate) fn get_doc_item_for_def(def: &DefP<AstNoPayload>) -> DocFunction {
+    let params = def
+        .params
+        .iter()
+        .map(|param| match &param.node {
+            ParameterP::Normal(name, typ) => DocParam::Arg {
+                name: name.0.clone(),
+                docs: None,
+                typ: doc_type(typ),
+                default_value: None,
+            },
+            ParameterP::WithDefaultValue(name, typ, default) => DocParam::Arg {
+                name: name.0.clone(),
+                docs: None,
+                typ: doc_type(typ),
+                default_value: Some(default.node.to_string()),
+            },
+            ParameterP::NoArgs => DocParam::NoArgs,
+            ParameterP::Args(name, typ) => DocParam::Args {
+                name: format!("*{}", name.0),
+                docs: None,
+                typ: doc_type(typ),
+            },
+            ParameterP::KwArgs(name, typ) => DocParam::Kwargs {
+                name: format!("**{}", name.0),
+                docs: None,
+                typ: doc_type(typ),
+            },
+        })
+        .collect();
+    DocFunction::from_docstring(
+        DocStringKind::Starlark,
+        params,
+        doc_type(&def.return_type),
+        DocString::extract_raw_starlark_docstring(&def.body).as_deref(),
+    )
+}
+
+/// Documentation for an assigned variable. There is only something to document
+/// if the assignment has a type annotation.
+pub(crate) fn get_doc_item_for_assign(typ: &Option<AstExpr>) -> Option<DocProperty> {
+    typ.as_ref().map(|t| DocProperty {
+        docs: None,
+        typ: Some(DocType {
+            raw_type: t.node.to_string(),
+        }),
+    })
+}
+
+/// If `expr` is a call to `struct` or `record`, the names and values of its fields.
+pub(crate) fn get_struct_fields(expr: &AstExpr) -> Option<Vec<(&AstString, &AstExpr)>> {
+    match &expr.node {
+        ExprP::Call(function, args) => match &function.node {
+            ExprP::Identifier(function, _)
+                if function.node == "struct" || function.node == "record" =>
+            {
+                Some(
+                    args.iter()
+                        .filter_map(|arg| match &arg.node {
+                            ArgumentP::Named(name, value) => Some((name, value)),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Find the top level statement that defines `name`, if any.
+pub(crate) fn find_top_level_definition<'a>(ast: &'a AstModule, name: &str) -> Option<&'a AstStmt> {
+    fn assigns(lhs: &AstAssign, name: &str) -> bool {
+        let mut found = false;
+        lhs.visit_lvalue(|x| found |= x.0 == name);
+        found
+    }
+
+    ast.top_level_statements()
+        .into_iter()
+        .find(|stmt| match &stmt.node {
+            StmtP::Def(def) => def.name.0 == name,
+            StmtP::Assign(lhs, _) => assigns(lhs, name),
+            _ => false,
+        })
+}
+
+/// Documentation for the top level symbol `name` in a module, if it is defined there.
+pub(crate) fn get_doc_item_for_symbol(ast: &AstModule, name: &str) -> Option<DocItem> {
+    match &find_top_level_definition(ast, name)?.node {
+        StmtP::Def(def) => Some(DocItem::Function(get_doc_item_for_def(def))),
+        StmtP::Assign(_, ty_rhs) => get_doc_item_for_assign(&ty_rhs.0).map(DocItem::Property),
+        _ => None,
+    }
+}
+
+/// If `name` is bound by a `load()` statement, the module path and the name of the symbol
+/// in that module.
+pub(crate) fn find_loaded_symbol<'a>(ast: &'a AstModule, name: &str) -> Option<(&'a str, &'a str)> {
+    ast.top_level_statements()
+        .into_iter()
+        .find_map(|stmt| match &stmt.node {
+            StmtP::Load(load) => load.args.iter().find_map(|(local, their)| {
+                if local.0 == name {
+                    Some((load.module.node.as_str(), their.node.as_str()))
+                } else {
+                    None
+                }
+            }),
+            _ => None,
+        })
+}
+
+/// The fields of a top level `name = struct(...)` in a module.
+pub(crate) fn get_struct_fields_for_symbol<'a>(
+    ast: &'a AstModule,
+    name: &str,
+) -> Option<Vec<(&'a AstString, &'a AstExpr)>> {
+    match &find_top_level_definition(ast, name)?.node {
+        StmtP::Assign(_, ty_rhs) => get_struct_fields(&ty_rhs.1),
+        _ => None,
+    }
+}
+
+/// Documentation for `name.member`, where `name` is a top level `struct(...)` whose
+/// `member` field refers to a top level function or variable in the same module.
+pub(crate) fn get_doc_item_for_member(
+    ast: &AstModule,
+    name: &str,
+    member: &str,
+) -> Option<DocItem> {
+    let (_, value) = get_struct_fields_for_symbol(ast, name)?
+        .into_iter()
+        .find(|(field, _)| field.node == member)?;
+    match &value.node {
+        ExprP::Identifier(value, _) => get_doc_item_for_symbol(ast, &value.node),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::docs::DocFunction;
+    use crate::docs::DocItem;
+    use crate::docs::DocParam;
+    use crate::docs::DocReturn;
+    use crate::docs::DocString;
+    use crate::docs::DocStringKind;
+    use crate::docs::DocType;
+    use crate::lsp::docs::get_doc_item_for_member;
+    use crate::lsp::docs::get_doc_item_for_symbol;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn module(program: &str) -> AstModule {
+        AstModule::parse("foo.star", program.to_owned(), &Dialect::Extended).unwrap()
+    }
+
+    #[test]
+    fn test_def_docs() {
+        let ast = module(
+            r#"
+def f(a, b: int = 1, *args, **kwargs) -> str:
+    """Summary of f.
+
+    Args:
+        a: The first argument.
+    """
+    pass
+"#,
+        );
+        let expected = DocItem::Function(DocFunction {
+            docs: DocString::from_docstring(DocStringKind::Starlark, "Summary of f."),
+            params: vec![
+                DocParam::Arg {
+                    name: "a".to_owned(),
+                    docs: DocString::from_docstring(DocStringKind::Starlark, "The first argument."),
+                    typ: None,
+                    default_value: None,
+                },
+                DocParam::Arg {
+                    name: "b".to_owned(),
+                    docs: None,
+                    typ: Some(DocType {
+                        raw_type: "int".to_owned(),
+                    }),
+                    default_value: Some("1".to_owned()),
+                },
+                DocParam::Args {
+                    name: "*args".to_owned(),
+                    docs: None,
+                    typ: None,
+                },
+                DocParam::Kwargs {
+                    name: "**kwargs".to_owned(),
+                    docs: None,
+                    typ: None,
+                },
+            ],
+            ret: DocReturn {
+                docs: None,
+                typ: Some(DocType {
+                    raw_type: "str".to_owned(),
+                }),
+            },
+        });
+        assert_eq!(Some(expected), get_doc_item_for_symbol(&ast, "f"));
+        assert_eq!(None, get_doc_item_for_symbol(&ast, "g"));
+    }
+
+    #[test]
+    fn test_assign_docs() {
+        let ast = module("x: int = 1\ny, z = 1, 2\n");
+        assert!(matches!(
+            get_doc_item_for_symbol(&ast, "x"),
+            Some(DocItem::Property(_))
+        ));
+        assert_eq!(None, get_doc_item_for_symbol(&ast, "y"));
+    }
+
+    #[test]
+    fn test_member_docs() {
+        let ast = module(
+            r#"
+def _impl():
+    """Implementation."""
+    pass
+foo = struct(bar = _impl, baz = 1)
+"#,
+        );
+        assert!(matches!(
+            get_doc_item_for_member(&ast, "foo", "bar"),
+            Some(DocItem::Function(DocFunction { docs: Some(_), .. }))
+        ));
+        assert_eq!(None, get_doc_item_for_member(&ast, "foo", "baz"));
+        assert_eq!(None, get_doc_item_for_member(&ast, "foo", "qux"));
+    }
+}
@@ -0,0 +1,234 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The outline of a module, as returned by `textDocument/documentSymbol`.
+
+use lsp_types::DocumentSymbol;
+use lsp_types::SymbolKind;
+
+use crate::codemap::CodeMap;
+use crate::codemap::Span;
+use crate::lsp::docs::get_doc_item_for_def;
+use crate::lsp::docs::get_struct_fields;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::ExprP;
+use crate::syntax::ast::StmtP;
+use crate::syntax::AstModule;
+
+fn make_symbol(
+    codemap: &CodeMap,
+    name: String,
+    detail: Option<String>,
+    kind: SymbolKind,
+    span: Span,
+    selection_span: Span,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    #[allow(deprecated)] // `deprecated` is deprecated in favour of `tags`, but still required.
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: codemap.resolve_span(span).into(),
+        selection_range: codemap.resolve_span(selection_span).into(),
+        children: if children.is_empty() {
+            None
+        } else {
+            Some(children)
+        },
+    }
+}
+
+/// The fields of a `struct(...)` or `record(...)` call.
+fn struct_fields(codemap: &CodeMap, expr: &AstExpr) -> Vec<DocumentSymbol> {
+    get_struct_fields(expr)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| {
+            make_symbol(
+                codemap,
+                name.node.clone(),
+                None,
+                SymbolKind::FIELD,
+                name.span.merge(value.span),
+                name.span,
+                Vec::new(),
+            )
+        })
+        .collect()
+}
+
+/// A call like `rule_name(name = "target", ...)`, which is how build files declare targets.
+fn target_symbol(codemap: &CodeMap, expr: &AstExpr) -> Option<DocumentSymbol> {
+    let (function, args) = match &expr.node {
+        ExprP::Call(function, args) => match &function.node {
+            ExprP::Identifier(function, _) => (function, args),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    args.iter().find_map(|arg| match &arg.node {
+        ArgumentP::Named(name, value) if name.node == "name" => match &value.node {
+            ExprP::Literal(AstLiteral::String(target)) => Some(make_symbol(
+                codemap,
+                target.node.clone(),
+                Some(function.node.clone()),
+                SymbolKind::CONSTRUCTOR,
+                expr.span,
+                target.span,
+                Vec::new(),
+            )),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Get the symbols defined in a module: loads, top level assignments, functions (including
+/// nested functions) and targets declared by calling rules.
+pub(crate) fn get_document_symbols(ast: &AstModule) -> Vec<DocumentSymbol> {
+    fn walk(codemap: &CodeMap, ast: &AstStmt, top_level: bool, symbols: &mut Vec<DocumentSymbol>) {
+        match &ast.node {
+            StmtP::Def(def) => {
+                let mut children = Vec::new();
+                walk(codemap, &def.body, false, &mut children);
+                let detail = get_doc_item_for_def(def).docs.map(|docs| docs.summary);
+                symbols.push(make_symbol(
+                    codemap,
+                    def.name.0.clone(),
+                    detail,
+                    SymbolKind::FUNCTION,
+                    ast.span,
+                    def.name.span,
+                    children,
+                ));
+            }
+            StmtP::Assign(dest, ty_rhs) if top_level => {
+                let fields = struct_fields(codemap, &ty_rhs.1);
+                let kind = if fields.is_empty() {
+                    SymbolKind::VARIABLE
+                } else {
+                    SymbolKind::STRUCT
+                };
+                dest.visit_lvalue(|x| {
+                    symbols.push(make_symbol(
+                        codemap,
+                        x.0.clone(),
+                        None,
+                        kind,
+                        ast.span,
+                        x.span,
+                        fields.clone(),
+                    ))
+                });
+            }
+            StmtP::Load(load) if top_level => {
+                let children = load
+                    .args
+                    .iter()
+                    .map(|(name, _)| {
+                        make_symbol(
+                            codemap,
+                            name.0.clone(),
+                            None,
+                            SymbolKind::VARIABLE,
+                            name.span,
+                            name.span,
+                            Vec::new(),
+                        )
+                    })
+                    .collect();
+                symbols.push(make_symbol(
+                    codemap,
+                    load.module.node.clone(),
+                    None,
+                    SymbolKind::MODULE,
+                    ast.span,
+                    load.module.span,
+                    children,
+                ));
+            }
+            StmtP::Expression(expr) if top_level => {
+                symbols.extend(target_symbol(codemap, expr));
+            }
+            _ => ast.visit_stmt(|x| walk(codemap, x, top_level, symbols)),
+        }
+    }
+
+    let mut symbols = Vec::new();
+    walk(&ast.codemap, &ast.statement, true, &mut symbols);
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::SymbolKind;
+
+    use crate::lsp::document_symbols::get_document_symbols;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    #[test]
+    fn test_document_symbols() {
+        let ast = AstModule::parse(
+            "foo.star",
+            r#"load("foo.star", "bar")
+x = struct(a = 1, b = 2)
+def f():
+    """Summary of f."""
+    y = 1
+    def g():
+        pass
+my_rule(name = "target", srcs = [])
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let symbols = get_document_symbols(&ast);
+
+        let summary: Vec<_> = symbols
+            .iter()
+            .map(|s| {
+                (
+                    s.name.as_str(),
+                    s.kind,
+                    s.detail.as_deref(),
+                    s.children
+                        .iter()
+                        .flatten()
+                        .map(|c| c.name.as_str())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("foo.star", SymbolKind::MODULE, None, vec!["bar"]),
+                ("x", SymbolKind::STRUCT, None, vec!["a", "b"]),
+                ("f", SymbolKind::FUNCTION, Some("Summary of f."), vec!["g"]),
+                ("target", SymbolKind::CONSTRUCTOR, Some("my_rule"), vec![]),
+            ],
+            summary
+        );
+    }
+}
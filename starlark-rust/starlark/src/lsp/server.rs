@@ -18,6 +18,7 @@
 //! Based on the reference lsp-server example at <https://github.com/rust-analyzer/lsp-server/blob/master/examples/goto_def.rs>.
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::path::Path;
 use std::path::PathBuf;
@@ -40,26 +41,62 @@ use lsp_types::notification::DidCloseTextDocument;
 use lsp_types::notification::DidOpenTextDocument;
 use lsp_types::notification::LogMessage;
 use lsp_types::notification::PublishDiagnostics;
+use lsp_types::request::CodeActionRequest;
+use lsp_types::request::Completion;
+use lsp_types::request::DocumentSymbolRequest;
+use lsp_types::request::Formatting;
 use lsp_types::request::GotoDefinition;
+use lsp_types::request::HoverRequest;
+use lsp_types::request::References;
+use lsp_types::request::Rename;
+use lsp_types::CodeAction;
+use lsp_types::CodeActionKind;
+use lsp_types::CodeActionOrCommand;
+use lsp_types::CodeActionParams;
+use lsp_types::CodeActionProviderCapability;
+use lsp_types::CodeActionResponse;
+use lsp_types::CompletionItem;
+use lsp_types::CompletionItemKind;
+use lsp_types::CompletionOptions;
+use lsp_types::CompletionParams;
+use lsp_types::CompletionResponse;
+use lsp_types::CompletionTextEdit;
 use lsp_types::DefinitionOptions;
 use lsp_types::Diagnostic;
 use lsp_types::DidChangeTextDocumentParams;
 use lsp_types::DidCloseTextDocumentParams;
 use lsp_types::DidOpenTextDocumentParams;
+use lsp_types::DocumentFormattingParams;
+use lsp_types::DocumentSymbolParams;
+use lsp_types::DocumentSymbolResponse;
+use lsp_types::Documentation;
 use lsp_types::GotoDefinitionParams;
 use lsp_types::GotoDefinitionResponse;
+use lsp_types::Hover;
+use lsp_types::HoverContents;
+use lsp_types::HoverParams;
+use lsp_types::HoverProviderCapability;
 use lsp_types::InitializeParams;
+use lsp_types::Location;
 use lsp_types::LocationLink;
 use lsp_types::LogMessageParams;
+use lsp_types::MarkupContent;
+use lsp_types::MarkupKind;
 use lsp_types::MessageType;
+use lsp_types::NumberOrString;
 use lsp_types::OneOf;
+use lsp_types::Position;
 use lsp_types::PublishDiagnosticsParams;
 use lsp_types::Range;
+use lsp_types::ReferenceParams;
+use lsp_types::RenameParams;
 use lsp_types::ServerCapabilities;
 use lsp_types::TextDocumentSyncCapability;
 use lsp_types::TextDocumentSyncKind;
+use lsp_types::TextEdit;
 use lsp_types::Url;
 use lsp_types::WorkDoneProgressOptions;
+use lsp_types::WorkspaceEdit;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Deserializer;
@@ -70,8 +107,28 @@ use crate::analysis::definition::Definition;
 use crate::analysis::definition::DottedDefinition;
 use crate::analysis::definition::IdentifierDefinition;
 use crate::analysis::definition::LspModule;
+use crate::analysis::references::Reference;
+use crate::analysis::references::ReferenceKind;
+use crate::analysis::references::SymbolBinding;
 use crate::codemap::ResolvedSpan;
+use crate::docs::Doc;
+use crate::docs::DocItem;
+use crate::docs::DocMember;
+use crate::docs::DocModule;
+use crate::docs::DocObject;
+use crate::docs::Identifier;
+use crate::docs::MarkdownFlavor;
+use crate::docs::RenderMarkdown;
+use crate::lsp::completion::CompletionContext;
+use crate::lsp::docs::find_loaded_symbol;
+use crate::lsp::docs::get_doc_item_for_member;
+use crate::lsp::docs::get_doc_item_for_symbol;
+use crate::lsp::docs::get_struct_fields_for_symbol;
+use crate::lsp::document_symbols::get_document_symbols;
 use crate::lsp::server::LoadContentsError::WrongScheme;
+use crate::lsp::symbols::find_symbols_at_location;
+use crate::syntax::ast::ExprP;
+use crate::syntax::lexer::Token;
 use crate::syntax::AstModule;
 
 /// The request to get the file contents for a starlark: URI
@@ -232,6 +289,16 @@ pub struct LspEvalResult {
     pub ast: Option<AstModule>,
 }
 
+/// A possible completion of the path in a `load()` statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadCompletion {
+    /// The full path to put between the quotes of the `load()` statement.
+    pub value: String,
+    /// Whether this is a directory containing files that can be loaded, rather than
+    /// a file that can be loaded itself.
+    pub is_directory: bool,
+}
+
 /// Settings that the LspContext can provide to change what capabilities the server enables
 /// or disables.
 #[derive(Dupe, Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -293,6 +360,25 @@ pub trait LspContext {
         current_file: &LspUrl,
         symbol: &str,
     ) -> anyhow::Result<Option<LspUrl>>;
+
+    /// Get the documentation for the global symbols available in a given file.
+    ///
+    /// This is used to show documentation when hovering over a global symbol, and to
+    /// offer global symbols (and the members of global objects) as completions.
+    /// The server asks once per open file and reuses the result until the file is
+    /// reopened, so this may be expensive, but should return shared docs rather than
+    /// a fresh copy.
+    fn get_environment(&self, uri: &LspUrl) -> Arc<Vec<Doc>>;
+
+    /// Get the possible completions for a partially typed path in a `load()` statement.
+    ///
+    /// `current_value` is the text between the opening quote and the cursor.
+    /// `current_file` is the file containing the `load()` statement.
+    fn get_load_completions(
+        &self,
+        current_value: &str,
+        current_file: &LspUrl,
+    ) -> anyhow::Result<Vec<LoadCompletion>>;
 }
 
 /// Errors when [`LspContext::resolve_load()`] cannot resolve a given path.
@@ -311,12 +397,113 @@ pub(crate) enum LoadContentsError {
     WrongScheme(String, LspUrl),
 }
 
+/// Errors when renaming a symbol.
+#[derive(thiserror::Error, Debug)]
+enum RenameError {
+    /// The new name cannot be used as a variable name.
+    #[error("`{}` is not a valid identifier", .0)]
+    InvalidName(String),
+    /// The symbol is not defined in any starlark file, e.g. it is a builtin.
+    #[error("`{}` is not defined in a starlark file, so cannot be renamed", .0)]
+    NotDefinedInFile(String),
+    /// Renaming the symbol would make it private, so other files could no longer load it.
+    #[error("Renaming `{}` to `{}` would make it private, so it could not be loaded", .0, .1)]
+    WouldBecomePrivate(String, String),
+}
+
+/// The symbol that find-references and rename operate on.
+enum ReferenceTarget {
+    /// A symbol that can only be referred to from one module: a local variable, a private
+    /// top level symbol, or a global.
+    Local {
+        uri: LspUrl,
+        ast: Arc<LspModule>,
+        name: String,
+        binding: SymbolBinding,
+    },
+    /// A public top level symbol of the module at `uri`, which other modules may load.
+    Exported { uri: LspUrl, name: String },
+}
+
 struct Backend<T: LspContext> {
     connection: Connection,
     context: T,
     /// The `AstModule` from the last time that a file was opened / changed and parsed successfully.
     /// Entries are evicted when the file is closed.
     last_valid_parse: RwLock<HashMap<LspUrl, Arc<LspModule>>>,
+    /// The current contents of open files, whether they parse or not. Used for completion,
+    /// which is usually requested while the file is being edited and does not parse.
+    /// Entries are evicted when the file is closed.
+    document_contents: RwLock<HashMap<LspUrl, String>>,
+    /// The global environment of files, from [`LspContext::get_environment`].
+    /// Entries are evicted when the file is opened or closed.
+    environments: RwLock<HashMap<LspUrl, Arc<Vec<Doc>>>>,
+}
+
+/// The text of `contents` before `position`. Positions past the end of a line are
+/// treated as the end of that line.
+fn text_before_position(contents: &str, position: Position) -> &str {
+    let mut offset = 0;
+    for (i, line) in contents.split_inclusive('\n').enumerate() {
+        if i == position.line as usize {
+            let line = line.trim_end_matches(&['\r', '\n'][..]);
+            let col = line
+                .char_indices()
+                .nth(position.character as usize)
+                .map_or(line.len(), |(col, _)| col);
+            return &contents[..offset + col];
+        }
+        offset += line.len();
+    }
+    contents
+}
+
+/// Render documentation for display in the client.
+fn render_doc(name: &str, item: DocItem) -> MarkupContent {
+    let doc = Doc {
+        id: Identifier {
+            name: name.to_owned(),
+            location: None,
+        },
+        item,
+        custom_attrs: HashMap::new(),
+    };
+    MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: doc.render_markdown(MarkdownFlavor::LspSummary),
+    }
+}
+
+fn completion_item(
+    label: &str,
+    doc_name: &str,
+    kind: CompletionItemKind,
+    detail: Option<String>,
+    doc: Option<DocItem>,
+) -> CompletionItem {
+    CompletionItem {
+        label: label.to_owned(),
+        kind: Some(kind),
+        detail,
+        documentation: doc.map(|doc| Documentation::MarkupContent(render_doc(doc_name, doc))),
+        ..CompletionItem::default()
+    }
+}
+
+fn completion_item_kind(doc: &Option<DocItem>) -> CompletionItemKind {
+    match doc {
+        Some(DocItem::Function(_)) => CompletionItemKind::FUNCTION,
+        Some(DocItem::Object(_)) => CompletionItemKind::CLASS,
+        Some(DocItem::Module(_)) => CompletionItemKind::MODULE,
+        Some(DocItem::Property(_)) | None => CompletionItemKind::VARIABLE,
+    }
+}
+
+fn doc_member_to_item(member: DocMember) -> DocItem {
+    match member {
+        DocMember::Property(p) => DocItem::Property(p),
+        DocMember::Function(f) => DocItem::Function(f),
+    }
 }
 
 /// The logic implementations of stuff
@@ -332,6 +519,16 @@ impl<T: LspContext> Backend<T> {
         ServerCapabilities {
             text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
             definition_provider,
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            completion_provider: Some(CompletionOptions {
+                trigger_characters: Some([".", "\"", "'", "/", ":"].map(str::to_owned).to_vec()),
+                ..CompletionOptions::default()
+            }),
+            document_symbol_provider: Some(OneOf::Left(true)),
+            references_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Left(true)),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
             ..ServerCapabilities::default()
         }
     }
@@ -341,6 +538,31 @@ impl<T: LspContext> Backend<T> {
         last_valid_parse.get(uri).duped()
     }
 
+    /// Like [`Self::get_ast`], but only if the file has not been edited since its last valid
+    /// parse, so that spans into the AST still match the text the client has.
+    fn get_current_ast(&self, uri: &LspUrl) -> Option<Arc<LspModule>> {
+        let ast = self.get_ast(uri)?;
+        let contents = self.document_contents.read().unwrap().get(uri).cloned();
+        if contents.as_deref() == Some(ast.ast.codemap.source()) {
+            Some(ast)
+        } else {
+            None
+        }
+    }
+
+    /// The global environment of a file, asking the context only the first time.
+    fn get_environment(&self, uri: &LspUrl) -> Arc<Vec<Doc>> {
+        if let Some(environment) = self.environments.read().unwrap().get(uri) {
+            return environment.dupe();
+        }
+        let environment = self.context.get_environment(uri);
+        self.environments
+            .write()
+            .unwrap()
+            .insert(uri.clone(), environment.dupe());
+        environment
+    }
+
     fn get_ast_or_load_from_disk(&self, uri: &LspUrl) -> anyhow::Result<Option<Arc<LspModule>>> {
         let module = match self.get_ast(uri) {
             Some(result) => Some(result),
@@ -353,7 +575,11 @@ impl<T: LspContext> Backend<T> {
     }
 
     fn validate(&self, uri: Url, version: Option<i64>, text: String) -> anyhow::Result<()> {
-        let uri = uri.try_into()?;
+        let uri: LspUrl = uri.try_into()?;
+        {
+            let mut document_contents = self.document_contents.write().unwrap();
+            document_contents.insert(uri.clone(), text.clone());
+        }
         let eval_result = self.context.parse_file_with_contents(&uri, text);
         if let Some(ast) = eval_result.ast {
             let module = Arc::new(LspModule::new(ast));
@@ -365,6 +591,9 @@ impl<T: LspContext> Backend<T> {
     }
 
     fn did_open(&self, params: DidOpenTextDocumentParams) -> anyhow::Result<()> {
+        // Reopening a file is the way to pick up changes to the global environment.
+        let uri = params.text_document.uri.clone().try_into()?;
+        self.environments.write().unwrap().remove(&uri);
         self.validate(
             params.text_document.uri,
             Some(params.text_document.version as i64),
@@ -384,8 +613,13 @@ impl<T: LspContext> Backend<T> {
 
     fn did_close(&self, params: DidCloseTextDocumentParams) -> anyhow::Result<()> {
         {
+            let uri = params.text_document.uri.clone().try_into()?;
             let mut last_valid_parse = self.last_valid_parse.write().unwrap();
-            last_valid_parse.remove(&params.text_document.uri.clone().try_into()?);
+            last_valid_parse.remove(&uri);
+            let mut document_contents = self.document_contents.write().unwrap();
+            document_contents.remove(&uri);
+            let mut environments = self.environments.write().unwrap();
+            environments.remove(&uri);
         }
         self.publish_diagnostics(params.text_document.uri, Vec::new(), None);
         Ok(())
@@ -401,6 +635,49 @@ impl<T: LspContext> Backend<T> {
         self.send_response(new_response(id, self.find_definition(params)));
     }
 
+    /// Show the signature and documentation of the symbol at the current cursor.
+    ///
+    /// NOTE: Like [`Self::goto_definition`], this uses the last valid parse of the file.
+    fn hover(&self, id: RequestId, params: HoverParams) {
+        self.send_response(new_response(id, self.hover_info(params)));
+    }
+
+    /// Offer completions at the current cursor. The kind of completion (load paths,
+    /// loadable symbols, members or identifiers) is based on the text before the cursor.
+    fn completion(&self, id: RequestId, params: CompletionParams) {
+        self.send_response(new_response(id, self.completion_options(params)));
+    }
+
+    /// List the symbols defined in a file, based on its last valid parse.
+    fn document_symbols(&self, id: RequestId, params: DocumentSymbolParams) {
+        self.send_response(new_response(id, self.find_document_symbols(params)));
+    }
+
+    /// Find all references to the symbol at the current cursor, in any file which is open,
+    /// or which can be reached through `load()` from an open file.
+    ///
+    /// NOTE: Like [`Self::goto_definition`], this uses the last valid parse of open files.
+    fn references(&self, id: RequestId, params: ReferenceParams) {
+        self.send_response(new_response(id, self.find_references(params)));
+    }
+
+    /// Rename the symbol at the current cursor, and all references to it found as in
+    /// [`Self::references`], including the names in `load()` statements.
+    fn rename(&self, id: RequestId, params: RenameParams) {
+        self.send_response(new_response(id, self.rename_symbol(params)));
+    }
+
+    /// Format a whole file. Nothing is done while the file does not parse.
+    fn formatting(&self, id: RequestId, params: DocumentFormattingParams) {
+        self.send_response(new_response(id, self.format_document(params)));
+    }
+
+    /// Offer the automatic fixes for the lints in the request's diagnostics.
+    /// Nothing is offered while the file does not parse.
+    fn code_action(&self, id: RequestId, params: CodeActionParams) {
+        self.send_response(new_response(id, self.find_code_actions(params)));
+    }
+
     /// Get the file contents of a starlark: URI.
     fn get_starlark_file_contents(&self, id: RequestId, params: StarlarkFileContentsParams) {
         let response: anyhow::Result<_> = match params.uri {
@@ -587,6 +864,590 @@ impl<T: LspContext> Backend<T> {
         };
         Ok(GotoDefinitionResponse::Link(response))
     }
+
+    /// Find the documentation for `name` (or `name.member`), given where `name` is defined.
+    fn get_doc_item(
+        &self,
+        definition: IdentifierDefinition,
+        name: &str,
+        member: Option<&str>,
+        ast: &LspModule,
+        uri: &LspUrl,
+        position: Position,
+    ) -> anyhow::Result<Option<DocItem>> {
+        let ret = match definition {
+            IdentifierDefinition::Location { .. } => match member {
+                Some(member) => get_doc_item_for_member(&ast.ast, name, member),
+                None => find_symbols_at_location(&ast.ast, position.line, position.character)
+                    .remove(name)
+                    .and_then(|symbol| symbol.doc),
+            },
+            IdentifierDefinition::LoadedLocation {
+                path,
+                name: loaded_name,
+                ..
+            } => {
+                let load_uri = self.resolve_load_path(&path, uri)?;
+                self.get_doc_item_from_file(&load_uri, &loaded_name, member)?
+            }
+            IdentifierDefinition::Unresolved { .. } => {
+                let global = self
+                    .get_environment(uri)
+                    .iter()
+                    .find(|doc| doc.id.name == name)
+                    .cloned();
+                match (global, member) {
+                    (Some(doc), None) => Some(doc.item),
+                    (Some(doc), Some(member)) => match doc.item {
+                        DocItem::Object(DocObject { mut members, .. })
+                        | DocItem::Module(DocModule { mut members, .. }) => {
+                            members.remove(member).map(doc_member_to_item)
+                        }
+                        _ => None,
+                    },
+                    // Globals which are defined in starlark files, e.g. a prelude.
+                    (None, _) => match self.context.get_url_for_global_symbol(uri, name)? {
+                        Some(url @ LspUrl::File(_)) => {
+                            self.get_doc_item_from_file(&url, name, member)?
+                        }
+                        _ => None,
+                    },
+                }
+            }
+            IdentifierDefinition::LoadPath { .. }
+            | IdentifierDefinition::StringLiteral { .. }
+            | IdentifierDefinition::NotFound => None,
+        };
+        Ok(ret)
+    }
+
+    /// Find the documentation for a top level symbol (or one of its members) in another file.
+    fn get_doc_item_from_file(
+        &self,
+        uri: &LspUrl,
+        name: &str,
+        member: Option<&str>,
+    ) -> anyhow::Result<Option<DocItem>> {
+        Ok(self
+            .get_ast_or_load_from_disk(uri)?
+            .and_then(|ast| match member {
+                Some(member) => get_doc_item_for_member(&ast.ast, name, member),
+                None => get_doc_item_for_symbol(&ast.ast, name),
+            }))
+    }
+
+    fn hover_info(&self, params: HoverParams) -> anyhow::Result<Option<Hover>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .try_into()?;
+        let position = params.text_document_position_params.position;
+
+        let ast = match self.get_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let definition = ast.find_definition(position.line, position.character);
+        let source = match definition.source() {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+        let (name, doc) = match definition {
+            Definition::Identifier(definition) => {
+                let name: String = ast
+                    .ast
+                    .codemap
+                    .source_line(source.begin_line)
+                    .chars()
+                    .skip(source.begin_column)
+                    .take(source.end_column.saturating_sub(source.begin_column))
+                    .collect();
+                let doc = self.get_doc_item(definition, &name, None, &ast, &uri, position)?;
+                (name, doc)
+            }
+            Definition::Dotted(DottedDefinition {
+                root_definition_location,
+                segments,
+                ..
+            }) => match segments.as_slice() {
+                [name] => {
+                    let doc = self.get_doc_item(
+                        root_definition_location,
+                        name,
+                        None,
+                        &ast,
+                        &uri,
+                        position,
+                    )?;
+                    (name.clone(), doc)
+                }
+                [name, member] => {
+                    let doc = self.get_doc_item(
+                        root_definition_location,
+                        name,
+                        Some(member),
+                        &ast,
+                        &uri,
+                        position,
+                    )?;
+                    (format!("{name}.{member}"), doc)
+                }
+                _ => return Ok(None),
+            },
+        };
+
+        Ok(doc.map(|doc| Hover {
+            contents: HoverContents::Markup(render_doc(&name, doc)),
+            range: Some(source.into()),
+        }))
+    }
+
+    fn completion_options(&self, params: CompletionParams) -> anyhow::Result<CompletionResponse> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        let contents = self
+            .document_contents
+            .read()
+            .unwrap()
+            .get(&uri)
+            .cloned()
+            .unwrap_or_default();
+        let prefix = text_before_position(&contents, params.text_document_position.position);
+        // The position of the cursor, clamped to the contents of the line.
+        let position = Position::new(
+            params.text_document_position.position.line,
+            prefix
+                .rsplit('\n')
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .count() as u32,
+        );
+
+        let items = match CompletionContext::from_prefix(prefix) {
+            CompletionContext::LoadPath { current_value } => {
+                self.load_path_completions(&uri, position, &current_value)?
+            }
+            CompletionContext::LoadSymbol { path } => self.load_symbol_completions(&uri, &path)?,
+            CompletionContext::Member { segments } => self.member_completions(&uri, &segments)?,
+            CompletionContext::Identifier => self.identifier_completions(&uri, position),
+            CompletionContext::None => Vec::new(),
+        };
+        Ok(CompletionResponse::Array(items))
+    }
+
+    fn load_path_completions(
+        &self,
+        uri: &LspUrl,
+        position: Position,
+        current_value: &str,
+    ) -> anyhow::Result<Vec<CompletionItem>> {
+        // Replace everything typed so far inside the quotes, as paths contain characters
+        // which editors do not usually consider to be part of a word.
+        let range = Range::new(
+            Position::new(
+                position.line,
+                position.character - current_value.chars().count() as u32,
+            ),
+            position,
+        );
+        Ok(self
+            .context
+            .get_load_completions(current_value, uri)?
+            .into_iter()
+            .map(|completion| CompletionItem {
+                label: completion.value.clone(),
+                kind: Some(if completion.is_directory {
+                    CompletionItemKind::FOLDER
+                } else {
+                    CompletionItemKind::FILE
+                }),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range,
+                    new_text: completion.value,
+                })),
+                ..CompletionItem::default()
+            })
+            .collect())
+    }
+
+    fn load_symbol_completions(
+        &self,
+        uri: &LspUrl,
+        path: &str,
+    ) -> anyhow::Result<Vec<CompletionItem>> {
+        // The user may still be typing the path, so failing to resolve it is not an error.
+        let ast = match self.resolve_load_path(path, uri) {
+            Ok(load_uri) => self.get_ast_or_load_from_disk(&load_uri)?,
+            Err(_) => None,
+        };
+        Ok(match ast {
+            Some(ast) => ast
+                .ast
+                .exported_symbols()
+                .into_iter()
+                .map(|(_, name)| {
+                    let doc = get_doc_item_for_symbol(&ast.ast, name);
+                    completion_item(name, name, completion_item_kind(&doc), None, doc)
+                })
+                .collect(),
+            None => Vec::new(),
+        })
+    }
+
+    fn member_completions(
+        &self,
+        uri: &LspUrl,
+        segments: &[String],
+    ) -> anyhow::Result<Vec<CompletionItem>> {
+        // Only direct members of a named value are supported.
+        let name = match segments {
+            [name] => name.as_str(),
+            _ => return Ok(Vec::new()),
+        };
+
+        fn struct_completions(ast: &AstModule, name: &str) -> Option<Vec<CompletionItem>> {
+            let fields = get_struct_fields_for_symbol(ast, name)?;
+            Some(
+                fields
+                    .into_iter()
+                    .map(|(field, value)| {
+                        let doc = match &value.node {
+                            ExprP::Identifier(value, _) => {
+                                get_doc_item_for_symbol(ast, &value.node)
+                            }
+                            _ => None,
+                        };
+                        let kind = match doc {
+                            Some(DocItem::Function(_)) => CompletionItemKind::METHOD,
+                            _ => CompletionItemKind::FIELD,
+                        };
+                        completion_item(
+                            &field.node,
+                            &format!("{}.{}", name, field.node),
+                            kind,
+                            None,
+                            doc,
+                        )
+                    })
+                    .collect(),
+            )
+        }
+
+        if let Some(ast) = self.get_ast(uri) {
+            if let Some(items) = struct_completions(&ast.ast, name) {
+                return Ok(items);
+            }
+            if let Some((path, loaded_name)) = find_loaded_symbol(&ast.ast, name) {
+                let loaded = match self.resolve_load_path(path, uri) {
+                    Ok(load_uri) => self.get_ast_or_load_from_disk(&load_uri)?,
+                    Err(_) => None,
+                };
+                return Ok(loaded
+                    .and_then(|loaded| struct_completions(&loaded.ast, loaded_name))
+                    .unwrap_or_default());
+            }
+        }
+
+        let global = self
+            .get_environment(uri)
+            .iter()
+            .find(|doc| doc.id.name == name)
+            .cloned();
+        Ok(match global.map(|doc| doc.item) {
+            Some(DocItem::Object(DocObject { members, .. }))
+            | Some(DocItem::Module(DocModule { members, .. })) => members
+                .into_iter()
+                .map(|(member, doc)| {
+                    let kind = match doc {
+                        DocMember::Function(_) => CompletionItemKind::METHOD,
+                        DocMember::Property(_) => CompletionItemKind::PROPERTY,
+                    };
+                    completion_item(
+                        &member,
+                        &format!("{}.{}", name, member),
+                        kind,
+                        None,
+                        Some(doc_member_to_item(doc)),
+                    )
+                })
+                .collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    fn identifier_completions(&self, uri: &LspUrl, position: Position) -> Vec<CompletionItem> {
+        let mut items = Vec::new();
+        let mut names = HashSet::new();
+        if let Some(ast) = self.get_ast(uri) {
+            for (name, symbol) in
+                find_symbols_at_location(&ast.ast, position.line, position.character)
+            {
+                items.push(completion_item(
+                    &name,
+                    &name,
+                    symbol.kind.into(),
+                    symbol.detail,
+                    symbol.doc,
+                ));
+                names.insert(name);
+            }
+        }
+        for doc in self.get_environment(uri).iter() {
+            // Locals shadow globals.
+            if names.insert(doc.id.name.clone()) {
+                let doc_item = Some(doc.item.clone());
+                items.push(completion_item(
+                    &doc.id.name,
+                    &doc.id.name,
+                    completion_item_kind(&doc_item),
+                    None,
+                    doc_item,
+                ));
+            }
+        }
+        items
+    }
+
+    /// Work out which symbol is at `position`, and where it is defined.
+    fn reference_target(
+        &self,
+        uri: &LspUrl,
+        position: Position,
+    ) -> anyhow::Result<Option<ReferenceTarget>> {
+        let ast = match self.get_ast(uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let symbol = match ast.find_symbol_at(position.line, position.character) {
+            Some(symbol) => symbol,
+            None => return Ok(None),
+        };
+        let target = match &symbol.binding {
+            SymbolBinding::Local { exported: true, .. } => ReferenceTarget::Exported {
+                uri: uri.clone(),
+                name: symbol.name,
+            },
+            SymbolBinding::Loaded { path, name, .. } => ReferenceTarget::Exported {
+                uri: self.resolve_load_path(path, uri)?,
+                name: name.clone(),
+            },
+            SymbolBinding::Local { .. } | SymbolBinding::Global => ReferenceTarget::Local {
+                uri: uri.clone(),
+                ast,
+                name: symbol.name,
+                binding: symbol.binding,
+            },
+        };
+        Ok(Some(target))
+    }
+
+    /// The modules that might refer to a symbol defined in `uri`: `uri` itself, every open
+    /// file, and every file that can be reached from those through `load()`.
+    fn modules_to_search(&self, uri: &LspUrl) -> Vec<(LspUrl, Arc<LspModule>)> {
+        let mut seen: HashSet<LspUrl> = self
+            .last_valid_parse
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        seen.insert(uri.clone());
+        let mut pending: Vec<LspUrl> = seen.iter().cloned().collect();
+
+        let mut modules = Vec::new();
+        while let Some(uri) = pending.pop() {
+            // Files that cannot be loaded or parsed cannot refer to anything.
+            let ast = match self.get_ast_or_load_from_disk(&uri) {
+                Ok(Some(ast)) => ast,
+                _ => continue,
+            };
+            for path in ast.loaded_paths() {
+                if let Ok(loaded) = self.resolve_load_path(path, &uri) {
+                    if seen.insert(loaded.clone()) {
+                        pending.push(loaded);
+                    }
+                }
+            }
+            modules.push((uri, ast));
+        }
+        modules
+    }
+
+    /// Find all references to `target`, grouped by the module they are in.
+    fn find_target_references(&self, target: &ReferenceTarget) -> Vec<(LspUrl, Vec<Reference>)> {
+        match target {
+            ReferenceTarget::Local {
+                uri,
+                ast,
+                name,
+                binding,
+            } => vec![(uri.clone(), ast.find_references(name, binding))],
+            ReferenceTarget::Exported { uri, name } => self
+                .modules_to_search(uri)
+                .into_iter()
+                .map(|(module_uri, ast)| {
+                    let references = if module_uri == *uri {
+                        ast.find_references_to_exported(name)
+                    } else {
+                        ast.find_references_to_loaded(name, &mut |path| {
+                            self.resolve_load_path(path, &module_uri)
+                                .map_or(false, |loaded| loaded == *uri)
+                        })
+                    };
+                    (module_uri, references)
+                })
+                .filter(|(_, references)| !references.is_empty())
+                .collect(),
+        }
+    }
+
+    fn find_references(&self, params: ReferenceParams) -> anyhow::Result<Vec<Location>> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        let target = match self.reference_target(&uri, params.text_document_position.position)? {
+            Some(target) => target,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut locations = Vec::new();
+        for (uri, references) in self.find_target_references(&target) {
+            let url: Url = uri.try_into()?;
+            for reference in references {
+                if reference.kind == ReferenceKind::Definition
+                    && !params.context.include_declaration
+                {
+                    continue;
+                }
+                locations.push(Location::new(url.clone(), reference.span.into()));
+            }
+        }
+        locations.sort_by_key(|l| {
+            (
+                l.uri.to_string(),
+                l.range.start.line,
+                l.range.start.character,
+            )
+        });
+        Ok(locations)
+    }
+
+    fn rename_symbol(&self, params: RenameParams) -> anyhow::Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        let new_name = params.new_name;
+        if !Token::is_identifier(&new_name) {
+            return Err(RenameError::InvalidName(new_name).into());
+        }
+        let target = match self.reference_target(&uri, params.text_document_position.position)? {
+            Some(target) => target,
+            None => return Ok(None),
+        };
+        match &target {
+            ReferenceTarget::Local {
+                name,
+                binding: SymbolBinding::Global,
+                ..
+            } => return Err(RenameError::NotDefinedInFile(name.clone()).into()),
+            ReferenceTarget::Exported { name, .. } if new_name.starts_with('_') => {
+                return Err(RenameError::WouldBecomePrivate(name.clone(), new_name).into());
+            }
+            _ => {}
+        }
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for (uri, references) in self.find_target_references(&target) {
+            let edits = references
+                .into_iter()
+                .filter_map(|reference| {
+                    let new_text = match reference.kind {
+                        ReferenceKind::Definition | ReferenceKind::Identifier => new_name.clone(),
+                        ReferenceKind::LoadedName { quote } => {
+                            format!("{quote}{new_name}{quote}")
+                        }
+                        // The local alias can stay as it is.
+                        ReferenceKind::Alias => return None,
+                    };
+                    Some(TextEdit::new(reference.span.into(), new_text))
+                })
+                .collect();
+            changes.insert(uri.try_into()?, edits);
+        }
+        Ok(Some(WorkspaceEdit::new(changes)))
+    }
+
+    fn format_document(
+        &self,
+        params: DocumentFormattingParams,
+    ) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri.try_into()?;
+        let ast = match self.get_current_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let source = ast.ast.codemap.source();
+        let formatted = ast.ast.format();
+        if formatted == source {
+            return Ok(Some(Vec::new()));
+        }
+        let range = ast.ast.codemap.resolve_span(ast.ast.codemap.full_span());
+        Ok(Some(vec![TextEdit::new(range.into(), formatted)]))
+    }
+
+    fn find_code_actions(
+        &self,
+        params: CodeActionParams,
+    ) -> anyhow::Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri.clone().try_into()?;
+        let ast = match self.get_current_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let codemap = &ast.ast.codemap;
+        let actions = ast
+            .ast
+            .lint(None)
+            .into_iter()
+            .filter_map(|lint| {
+                let fix = lint.fix?;
+                // Only fix the lints the client is asking about.
+                let range: Range = lint.location.resolve_span().into();
+                let code = Some(NumberOrString::String(lint.short_name));
+                let diagnostic = params
+                    .context
+                    .diagnostics
+                    .iter()
+                    .find(|d| d.range == range && d.code == code)?;
+                let edits = fix
+                    .edits
+                    .into_iter()
+                    .map(|(span, text)| TextEdit::new(codemap.resolve_span(span).into(), text))
+                    .collect();
+                Some(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: fix.description,
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit::new(HashMap::from([(
+                        params.text_document.uri.clone(),
+                        edits,
+                    )]))),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                }))
+            })
+            .collect();
+        Ok(Some(actions))
+    }
+
+    fn find_document_symbols(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> anyhow::Result<DocumentSymbolResponse> {
+        let uri = params.text_document.uri.try_into()?;
+        let symbols = match self.get_ast(&uri) {
+            Some(ast) => get_document_symbols(&ast.ast),
+            None => Vec::new(),
+        };
+        Ok(DocumentSymbolResponse::Nested(symbols))
+    }
 }
 
 /// The library style pieces
@@ -620,10 +1481,22 @@ impl<T: LspContext> Backend<T> {
         for msg in &self.connection.receiver {
             match msg {
                 Message::Request(req) => {
-                    // TODO(nmj): Also implement DocumentSymbols so that some logic can
-                    //            be handled client side.
                     if let Some(params) = as_request::<GotoDefinition>(&req) {
                         self.goto_definition(req.id, params);
+                    } else if let Some(params) = as_request::<HoverRequest>(&req) {
+                        self.hover(req.id, params);
+                    } else if let Some(params) = as_request::<Completion>(&req) {
+                        self.completion(req.id, params);
+                    } else if let Some(params) = as_request::<DocumentSymbolRequest>(&req) {
+                        self.document_symbols(req.id, params);
+                    } else if let Some(params) = as_request::<References>(&req) {
+                        self.references(req.id, params);
+                    } else if let Some(params) = as_request::<Rename>(&req) {
+                        self.rename(req.id, params);
+                    } else if let Some(params) = as_request::<Formatting>(&req) {
+                        self.formatting(req.id, params);
+                    } else if let Some(params) = as_request::<CodeActionRequest>(&req) {
+                        self.code_action(req.id, params);
                     } else if let Some(params) = as_request::<StarlarkFileContentsRequest>(&req) {
                         self.get_starlark_file_contents(req.id, params);
                     } else if self.connection.handle_shutdown(&req)? {
@@ -689,6 +1562,8 @@ pub fn server_with_connection<T: LspContext>(
         connection,
         context,
         last_valid_parse: RwLock::default(),
+        document_contents: RwLock::default(),
+        environments: RwLock::default(),
     }
     .main_loop(initialization_params)?;
 
@@ -768,21 +1643,55 @@ where
 //            some paths. Revisit later.
 #[cfg(all(test, not(windows)))]
 mod test {
+    use std::collections::HashMap;
     use std::path::Path;
     use std::path::PathBuf;
 
     use anyhow::Context;
     use lsp_server::Request;
     use lsp_server::RequestId;
+    use lsp_types::request::CodeActionRequest;
+    use lsp_types::request::Completion;
+    use lsp_types::request::DocumentSymbolRequest;
+    use lsp_types::request::Formatting;
     use lsp_types::request::GotoDefinition;
+    use lsp_types::request::HoverRequest;
+    use lsp_types::request::References;
+    use lsp_types::request::Rename;
+    use lsp_types::CodeAction;
+    use lsp_types::CodeActionContext;
+    use lsp_types::CodeActionKind;
+    use lsp_types::CodeActionOrCommand;
+    use lsp_types::CodeActionParams;
+    use lsp_types::CodeActionResponse;
+    use lsp_types::CompletionItem;
+    use lsp_types::CompletionItemKind;
+    use lsp_types::CompletionParams;
+    use lsp_types::CompletionResponse;
+    use lsp_types::CompletionTextEdit;
+    use lsp_types::Diagnostic;
+    use lsp_types::DocumentFormattingParams;
+    use lsp_types::DocumentSymbolParams;
+    use lsp_types::DocumentSymbolResponse;
+    use lsp_types::FormattingOptions;
     use lsp_types::GotoDefinitionParams;
     use lsp_types::GotoDefinitionResponse;
+    use lsp_types::Hover;
+    use lsp_types::HoverContents;
+    use lsp_types::HoverParams;
+    use lsp_types::Location;
     use lsp_types::LocationLink;
+    use lsp_types::NumberOrString;
     use lsp_types::Position;
     use lsp_types::Range;
+    use lsp_types::ReferenceContext;
+    use lsp_types::ReferenceParams;
+    use lsp_types::RenameParams;
     use lsp_types::TextDocumentIdentifier;
     use lsp_types::TextDocumentPositionParams;
+    use lsp_types::TextEdit;
     use lsp_types::Url;
+    use lsp_types::WorkspaceEdit;
     use textwrap::dedent;
 
     use crate::analysis::definition::helpers::FixtureWithRanges;
@@ -823,6 +1732,159 @@ mod test {
         }
     }
 
+    /// Get the markdown shown when hovering at the given position, if any.
+    fn hover(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+    ) -> anyhow::Result<Option<(String, Option<Range>)>> {
+        let req = server.new_request::<HoverRequest>(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(req)?;
+        match server.get_response::<Option<Hover>>(request_id)? {
+            Some(Hover {
+                contents: HoverContents::Markup(markup),
+                range,
+            }) => Ok(Some((markup.value, range))),
+            Some(hover) => Err(anyhow::anyhow!("Unexpected hover contents: {:?}", hover)),
+            None => Ok(None),
+        }
+    }
+
+    fn completion(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+    ) -> anyhow::Result<Vec<CompletionItem>> {
+        let req = server.new_request::<Completion>(CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        });
+        let request_id = server.send_request(req)?;
+        match server.get_response::<CompletionResponse>(request_id)? {
+            CompletionResponse::Array(items) => Ok(items),
+            response => Err(anyhow::anyhow!(
+                "Unexpected completion response: {:?}",
+                response
+            )),
+        }
+    }
+
+    fn references(
+        server: &mut TestServer,
+        uri: Url,
+        position: Position,
+        include_declaration: bool,
+    ) -> anyhow::Result<Vec<Location>> {
+        let req = server.new_request::<References>(ReferenceParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext {
+                include_declaration,
+            },
+        });
+        let request_id = server.send_request(req)?;
+        server.get_response::<Vec<Location>>(request_id)
+    }
+
+    fn rename(
+        server: &mut TestServer,
+        uri: Url,
+        position: Position,
+        new_name: &str,
+    ) -> anyhow::Result<Option<WorkspaceEdit>> {
+        let req = server.new_request::<Rename>(RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            new_name: new_name.to_owned(),
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(req)?;
+        server.get_response::<Option<WorkspaceEdit>>(request_id)
+    }
+
+    /// Three modules, where `bar.star` and `baz.star` both load `exported` from `foo.star`.
+    /// `foo.star` and `bar.star` are open, `baz.star` is only reachable through `bar.star`.
+    fn setup_reference_modules(
+        server: &mut TestServer,
+    ) -> anyhow::Result<(
+        (Url, FixtureWithRanges),
+        (Url, FixtureWithRanges),
+        (Url, FixtureWithRanges),
+    )> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+        let baz_uri = temp_file_uri("baz.star");
+
+        let foo = FixtureWithRanges::from_fixture(
+            foo_uri.path(),
+            &dedent(
+                r#"
+                def <foo_def>exported</foo_def>():
+                    pass
+
+                <foo_use>exported</foo_use>()
+                "#,
+            ),
+        )?;
+        let bar = FixtureWithRanges::from_fixture(
+            bar_uri.path(),
+            &dedent(
+                r#"
+                load("{baz}", "baz")
+                load("{foo}", <bar_load>"exported"</bar_load>)
+                <bar_use>exported</bar_use>(baz)
+                "#,
+            )
+            .replace("{baz}", baz_uri.path())
+            .replace("{foo}", foo_uri.path()),
+        )?;
+        let baz = FixtureWithRanges::from_fixture(
+            baz_uri.path(),
+            &dedent(
+                r#"
+                load("{foo}", <baz_alias>alias</baz_alias> = <baz_load>'exported'</baz_load>)
+                baz = <baz_use>alias</baz_use>
+                "#,
+            )
+            .replace("{foo}", foo_uri.path()),
+        )?;
+
+        server.set_file_contents(PathBuf::from(baz_uri.path()), baz.program())?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        server.open_file(bar_uri.clone(), bar.program())?;
+        Ok(((foo_uri, foo), (bar_uri, bar), (baz_uri, baz)))
+    }
+
+    fn position(fixture: &FixtureWithRanges, identifier: &str) -> Position {
+        Position::new(
+            fixture.begin_line(identifier),
+            fixture.begin_column(identifier),
+        )
+    }
+
+    fn completion_labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.label.as_str()).collect()
+    }
+
     fn expected_location_link(
         uri: Url,
         source_line: u32,
@@ -1809,4 +2871,432 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn advertises_hover_completion_and_document_symbols() -> anyhow::Result<()> {
+        let server = TestServer::new()?;
+        let capabilities = server.initialization_result().unwrap().capabilities;
+        assert!(capabilities.hover_provider.is_some());
+        assert!(capabilities.completion_provider.is_some());
+        assert!(capabilities.document_symbol_provider.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn hovers_local_function() -> anyhow::Result<()> {
+        let uri = temp_file_uri("file.star");
+        let contents = dedent(
+            r#"
+            def foo(a, b = 1):
+                """Summary of foo.
+
+                Args:
+                    a: The a argument.
+                """
+                return a + b
+
+            foo(1)
+            "#,
+        )
+        .trim()
+        .to_owned();
+
+        let mut server = TestServer::new()?;
+        server.open_file(uri.clone(), contents)?;
+
+        let (markdown, range) = hover(&mut server, uri.clone(), 8, 1)?.unwrap();
+        assert!(
+            markdown.starts_with("```python\ndef foo(a, b = 1)\n```\n\nSummary of foo."),
+            "{}",
+            markdown
+        );
+        assert!(markdown.contains("* `a`: The a argument."), "{}", markdown);
+        assert_eq!(
+            Some(Range::new(Position::new(8, 0), Position::new(8, 3))),
+            range
+        );
+
+        // Nothing to show for a literal.
+        assert_eq!(None, hover(&mut server, uri, 8, 4)?);
+        Ok(())
+    }
+
+    #[test]
+    fn hovers_loaded_global_and_member_symbols() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+
+        let foo_contents = dedent(
+            r#"
+            load("{load}", "baz")
+            def _impl():
+                """Implementation."""
+                pass
+            s = struct(impl = _impl)
+            baz()
+            native_function1()
+            s.impl()
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+        let bar_contents = "def baz():\n    \"\"\"Docs for baz.\"\"\"\n    pass\n";
+
+        let mut server = TestServer::new()?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar_contents.to_owned())?;
+        server.open_file(foo_uri.clone(), foo_contents)?;
+
+        let (markdown, _) = hover(&mut server, foo_uri.clone(), 5, 0)?.unwrap();
+        assert_eq!("```python\ndef baz()\n```\n\nDocs for baz.", markdown);
+
+        let (markdown, _) = hover(&mut server, foo_uri.clone(), 6, 0)?.unwrap();
+        assert_eq!("```python\ndef native_function1()\n```", markdown);
+
+        let (markdown, _) = hover(&mut server, foo_uri, 7, 3)?.unwrap();
+        assert_eq!("```python\ndef s.impl()\n```\n\nImplementation.", markdown);
+        Ok(())
+    }
+
+    #[test]
+    fn completes_identifiers_and_members() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+
+        let foo_contents = dedent(
+            r#"
+            load("{load}", "baz")
+            x = struct(field1 = 1, field2 = baz)
+            def f(param):
+                return param
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo_contents.clone())?;
+
+        let items = completion(&mut server, foo_uri.clone(), 3, 4)?;
+        let labels = completion_labels(&items);
+        for expected in [
+            "baz",
+            "x",
+            "f",
+            "param",
+            "native_function1",
+            "native_function2",
+            "prelude_function",
+        ] {
+            assert!(labels.contains(&expected), "{:?}", labels);
+        }
+        let f = items.iter().find(|item| item.label == "f").unwrap();
+        assert_eq!(Some(CompletionItemKind::FUNCTION), f.kind);
+
+        // Parameters are not visible outside the function. The file does not
+        // parse after this change, so the last valid parse is used.
+        server.change_file(foo_uri.clone(), format!("{}\nx.", foo_contents))?;
+        let labels = completion_labels(&completion(&mut server, foo_uri.clone(), 4, 0)?)
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        assert!(!labels.contains(&"param".to_owned()), "{:?}", labels);
+
+        let items = completion(&mut server, foo_uri.clone(), 4, 2)?;
+        assert_eq!(vec!["field1", "field2"], completion_labels(&items));
+
+        // No completions inside strings.
+        server.change_file(foo_uri.clone(), format!("{}\ny = \"x.", foo_contents))?;
+        assert!(completion(&mut server, foo_uri, 4, 7)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn completes_load_paths_and_symbols() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("dir/bar.star");
+        let baz_uri = temp_file_uri("dir/sub/baz.star");
+
+        let mut server = TestServer::new()?;
+        server.set_file_contents(
+            PathBuf::from(bar_uri.path()),
+            "def bar():\n    pass\ndef _private():\n    pass\n".to_owned(),
+        )?;
+        server.set_file_contents(PathBuf::from(baz_uri.path()), "baz = 1\n".to_owned())?;
+        server.open_file(foo_uri.clone(), "x = 1\n".to_owned())?;
+
+        server.change_file(foo_uri.clone(), "load(\"dir/".to_owned())?;
+        let items = completion(&mut server, foo_uri.clone(), 0, 10)?;
+        assert_eq!(vec!["dir/bar.star", "dir/sub"], completion_labels(&items));
+        assert_eq!(Some(CompletionItemKind::FILE), items[0].kind);
+        assert_eq!(Some(CompletionItemKind::FOLDER), items[1].kind);
+        match &items[0].text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => assert_eq!(
+                Range::new(Position::new(0, 6), Position::new(0, 10)),
+                edit.range
+            ),
+            edit => panic!("Unexpected text edit: {:?}", edit),
+        }
+
+        server.change_file(foo_uri.clone(), "load(\"dir/bar.star\", \"".to_owned())?;
+        let items = completion(&mut server, foo_uri, 0, 22)?;
+        assert_eq!(vec!["bar"], completion_labels(&items));
+        Ok(())
+    }
+
+    #[test]
+    fn returns_document_symbols() -> anyhow::Result<()> {
+        let uri = temp_file_uri("file.star");
+        let contents = "x = 1\ndef foo():\n    pass\nfoo()\n";
+
+        let mut server = TestServer::new()?;
+        server.open_file(uri.clone(), contents.to_owned())?;
+
+        let req = server.new_request::<DocumentSymbolRequest>(DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let request_id = server.send_request(req)?;
+        match server.get_response::<DocumentSymbolResponse>(request_id)? {
+            DocumentSymbolResponse::Nested(symbols) => {
+                assert_eq!(
+                    vec!["x", "foo"],
+                    symbols.iter().map(|s| s.name.as_str()).collect::<Vec<_>>()
+                );
+                assert_eq!(
+                    Range::new(Position::new(1, 4), Position::new(1, 7)),
+                    symbols[1].selection_range
+                );
+            }
+            response => panic!("Unexpected response: {:?}", response),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn advertises_references_and_rename() -> anyhow::Result<()> {
+        let server = TestServer::new()?;
+        let capabilities = server.initialization_result().unwrap().capabilities;
+        assert!(capabilities.references_provider.is_some());
+        assert!(capabilities.rename_provider.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn finds_references_across_files() -> anyhow::Result<()> {
+        let mut server = TestServer::new()?;
+        let ((foo_uri, foo), (bar_uri, bar), (baz_uri, baz)) =
+            setup_reference_modules(&mut server)?;
+
+        let expected = vec![
+            Location::new(bar_uri.clone(), bar.span("bar_load").into()),
+            Location::new(bar_uri.clone(), bar.span("bar_use").into()),
+            Location::new(baz_uri.clone(), baz.span("baz_alias").into()),
+            Location::new(baz_uri.clone(), baz.span("baz_load").into()),
+            Location::new(baz_uri, baz.span("baz_use").into()),
+            Location::new(foo_uri.clone(), foo.span("foo_def").into()),
+            Location::new(foo_uri.clone(), foo.span("foo_use").into()),
+        ];
+        // The results are the same wherever the symbol is referenced from.
+        assert_eq!(
+            expected,
+            references(
+                &mut server,
+                foo_uri.clone(),
+                position(&foo, "foo_use"),
+                true
+            )?
+        );
+        assert_eq!(
+            expected,
+            references(
+                &mut server,
+                bar_uri.clone(),
+                position(&bar, "bar_use"),
+                true
+            )?
+        );
+        assert_eq!(
+            expected,
+            references(&mut server, bar_uri, position(&bar, "bar_load"), true)?
+        );
+
+        let without_declaration =
+            references(&mut server, foo_uri, position(&foo, "foo_def"), false)?;
+        assert_eq!(expected.len() - 1, without_declaration.len());
+        assert!(!without_declaration.contains(&expected[5]));
+        Ok(())
+    }
+
+    #[test]
+    fn finds_references_to_locals() -> anyhow::Result<()> {
+        let uri = temp_file_uri("file.star");
+        let fixture = FixtureWithRanges::from_fixture(
+            uri.path(),
+            &dedent(
+                r#"
+                def f(<x1>x</x1>):
+                    return <x2>x</x2>
+                x = 1
+                f(x)
+                "#,
+            ),
+        )?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(uri.clone(), fixture.program())?;
+
+        assert_eq!(
+            vec![
+                Location::new(uri.clone(), fixture.span("x1").into()),
+                Location::new(uri.clone(), fixture.span("x2").into()),
+            ],
+            references(&mut server, uri, position(&fixture, "x2"), true)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn renames_across_files() -> anyhow::Result<()> {
+        let mut server = TestServer::new()?;
+        let ((foo_uri, foo), (bar_uri, bar), (baz_uri, baz)) =
+            setup_reference_modules(&mut server)?;
+
+        let edit = rename(
+            &mut server,
+            bar_uri.clone(),
+            position(&bar, "bar_use"),
+            "renamed",
+        )?
+        .unwrap();
+        let expected = WorkspaceEdit::new(
+            vec![
+                (
+                    foo_uri,
+                    vec![
+                        TextEdit::new(foo.span("foo_def").into(), "renamed".to_owned()),
+                        TextEdit::new(foo.span("foo_use").into(), "renamed".to_owned()),
+                    ],
+                ),
+                (
+                    bar_uri,
+                    vec![
+                        TextEdit::new(bar.span("bar_load").into(), "\"renamed\"".to_owned()),
+                        TextEdit::new(bar.span("bar_use").into(), "renamed".to_owned()),
+                    ],
+                ),
+                (
+                    // The alias is left alone.
+                    baz_uri,
+                    vec![TextEdit::new(
+                        baz.span("baz_load").into(),
+                        "'renamed'".to_owned(),
+                    )],
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(expected, edit);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_renames() -> anyhow::Result<()> {
+        let mut server = TestServer::new()?;
+        let ((foo_uri, foo), _, _) = setup_reference_modules(&mut server)?;
+
+        let position = position(&foo, "foo_use");
+        assert!(rename(&mut server, foo_uri.clone(), position, "not valid").is_err());
+        assert!(rename(&mut server, foo_uri.clone(), position, "def").is_err());
+        // Other files would not be able to load a private symbol.
+        assert!(rename(&mut server, foo_uri, position, "_private").is_err());
+
+        let uri = temp_file_uri("file.star");
+        server.open_file(uri.clone(), "print(1)\n".to_owned())?;
+        assert!(rename(&mut server, uri, Position::new(0, 1), "renamed").is_err());
+        Ok(())
+    }
+
+    fn format(server: &mut TestServer, uri: Url) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let req = server.new_request::<Formatting>(DocumentFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            options: FormattingOptions::default(),
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(req)?;
+        server.get_response::<Option<Vec<TextEdit>>>(request_id)
+    }
+
+    #[test]
+    fn formats_documents() -> anyhow::Result<()> {
+        let uri = temp_file_uri("file.star");
+        let mut server = TestServer::new()?;
+        let capabilities = server.initialization_result().unwrap().capabilities;
+        assert!(capabilities.document_formatting_provider.is_some());
+
+        server.open_file(uri.clone(), "x=[1,2]\ndef f( a ):\n  return a\n".to_owned())?;
+        let expected = vec![TextEdit::new(
+            Range::new(Position::new(0, 0), Position::new(3, 0)),
+            "x = [1, 2]\ndef f(a):\n    return a\n".to_owned(),
+        )];
+        assert_eq!(Some(expected), format(&mut server, uri.clone())?);
+
+        server.change_file(uri.clone(), "x = [1, 2]\n".to_owned())?;
+        assert_eq!(Some(Vec::new()), format(&mut server, uri.clone())?);
+
+        // Formatting the last valid parse would throw away the edits since.
+        server.change_file(uri.clone(), "x = [1,\n".to_owned())?;
+        assert_eq!(None, format(&mut server, uri)?);
+        Ok(())
+    }
+
+    #[test]
+    fn code_actions_fix_lints() -> anyhow::Result<()> {
+        let uri = temp_file_uri("file.star");
+        let mut server = TestServer::new()?;
+        let capabilities = server.initialization_result().unwrap().capabilities;
+        assert!(capabilities.code_action_provider.is_some());
+
+        server.open_file(uri.clone(), "x = 1\n".to_owned())?;
+        server.change_file(uri.clone(), "load(\"a.star\", \"y\")\nx = 1\n".to_owned())?;
+
+        let diagnostic = Diagnostic {
+            range: Range::new(Position::new(0, 15), Position::new(0, 18)),
+            code: Some(NumberOrString::String("unused-load".to_owned())),
+            message: "Unused `load` of `y`".to_owned(),
+            ..Default::default()
+        };
+        let req = server.new_request::<CodeActionRequest>(CodeActionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            range: diagnostic.range,
+            context: CodeActionContext {
+                diagnostics: vec![diagnostic.clone()],
+                only: None,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let request_id = server.send_request(req)?;
+        let response = server.get_response::<Option<CodeActionResponse>>(request_id)?;
+
+        let expected = vec![CodeActionOrCommand::CodeAction(CodeAction {
+            title: "Remove unused `load` of `y`".to_owned(),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![diagnostic]),
+            edit: Some(WorkspaceEdit::new(HashMap::from([(
+                uri,
+                vec![TextEdit::new(
+                    Range::new(Position::new(0, 0), Position::new(1, 0)),
+                    String::new(),
+                )],
+            )]))),
+            is_preferred: Some(true),
+            ..Default::default()
+        })];
+        assert_eq!(Some(expected), response);
+        Ok(())
+    }
 }
@@ -0,0 +1,205 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Find which symbols are in scope at a particular point.
+
+use lsp_types::CompletionItemKind;
+
+use crate::codemap::Pos;
+use crate::collections::SmallMap;
+use crate::docs::DocItem;
+use crate::lsp::docs::get_doc_item_for_assign;
+use crate::lsp::docs::get_doc_item_for_def;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::ParameterP;
+use crate::syntax::ast::StmtP;
+use crate::syntax::AstModule;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SymbolKind {
+    Function,
+    Variable,
+}
+
+impl From<SymbolKind> for CompletionItemKind {
+    fn from(kind: SymbolKind) -> Self {
+        match kind {
+            SymbolKind::Function => CompletionItemKind::FUNCTION,
+            SymbolKind::Variable => CompletionItemKind::VARIABLE,
+        }
+    }
+}
+
+/// A symbol which is in scope at some point in a module.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Symbol {
+    pub(crate) name: String,
+    pub(crate) kind: SymbolKind,
+    /// Short description of where the symbol comes from, e.g. the module it was loaded from.
+    pub(crate) detail: Option<String>,
+    /// Documentation extracted from the AST, if the symbol is defined in this module.
+    pub(crate) doc: Option<DocItem>,
+}
+
+/// Find all the symbols which are visible at `line`/`col` (zero based): top level symbols
+/// of the module, and parameters and locals of the functions enclosing the position.
+pub(crate) fn find_symbols_at_location(
+    ast: &AstModule,
+    line: u32,
+    col: u32,
+) -> SmallMap<String, Symbol> {
+    fn walk(ast: &AstStmt, cursor: Option<Pos>, symbols: &mut SmallMap<String, Symbol>) {
+        match &ast.node {
+            StmtP::Assign(dest, ty_rhs) => {
+                let doc = get_doc_item_for_assign(&ty_rhs.0).map(DocItem::Property);
+                dest.visit_lvalue(|x| {
+                    symbols.insert(
+                        x.0.clone(),
+                        Symbol {
+                            name: x.0.clone(),
+                            kind: SymbolKind::Variable,
+                            detail: None,
+                            doc: doc.clone(),
+                        },
+                    );
+                });
+            }
+            StmtP::AssignModify(dest, _, _) => dest.visit_lvalue(|x| {
+                symbols.entry(x.0.clone()).or_insert_with(|| Symbol {
+                    name: x.0.clone(),
+                    kind: SymbolKind::Variable,
+                    detail: None,
+                    doc: None,
+                });
+            }),
+            StmtP::For(dest, over_body) => {
+                dest.visit_lvalue(|x| {
+                    symbols.insert(
+                        x.0.clone(),
+                        Symbol {
+                            name: x.0.clone(),
+                            kind: SymbolKind::Variable,
+                            detail: None,
+                            doc: None,
+                        },
+                    );
+                });
+                walk(&over_body.1, cursor, symbols);
+            }
+            StmtP::Def(def) => {
+                symbols.insert(
+                    def.name.0.clone(),
+                    Symbol {
+                        name: def.name.0.clone(),
+                        kind: SymbolKind::Function,
+                        detail: None,
+                        doc: Some(DocItem::Function(get_doc_item_for_def(def))),
+                    },
+                );
+                // Only the function the cursor is in contributes its locals.
+                if cursor.map_or(false, |cursor| ast.span.contains(cursor)) {
+                    for param in &def.params {
+                        if let ParameterP::Normal(name, _)
+                        | ParameterP::WithDefaultValue(name, _, _)
+                        | ParameterP::Args(name, _)
+                        | ParameterP::KwArgs(name, _) = &param.node
+                        {
+                            symbols.insert(
+                                name.0.clone(),
+                                Symbol {
+                                    name: name.0.clone(),
+                                    kind: SymbolKind::Variable,
+                                    detail: Some(format!("Parameter of `{}`", def.name.0)),
+                                    doc: None,
+                                },
+                            );
+                        }
+                    }
+                    walk(&def.body, cursor, symbols);
+                }
+            }
+            StmtP::Load(load) => {
+                for (name, _) in &load.args {
+                    symbols.insert(
+                        name.0.clone(),
+                        Symbol {
+                            name: name.0.clone(),
+                            kind: SymbolKind::Variable,
+                            detail: Some(format!("Loaded from {}", load.module.node)),
+                            doc: None,
+                        },
+                    );
+                }
+            }
+            _ => ast.visit_stmt(|x| walk(x, cursor, symbols)),
+        }
+    }
+
+    let cursor = ast
+        .codemap
+        .line_span_opt(line as usize)
+        .map(|line_span| std::cmp::min(line_span.begin() + col, line_span.end()));
+    let mut symbols = SmallMap::new();
+    walk(&ast.statement, cursor, &mut symbols);
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::symbols::find_symbols_at_location;
+    use crate::lsp::symbols::SymbolKind;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    #[test]
+    fn test_symbols_at_location() {
+        let ast = AstModule::parse(
+            "foo.star",
+            r#"load("foo.star", "exported")
+x = 1
+def f(a, *args, **kwargs):
+    y = 2
+    return y
+def g(b):
+    z = 3
+    return z
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+
+        // Inside `f`.
+        let symbols = find_symbols_at_location(&ast, 4, 4);
+        assert_eq!(
+            vec!["exported", "x", "f", "a", "args", "kwargs", "y", "g"],
+            symbols.keys().map(|s| s.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(SymbolKind::Function, symbols.get("f").unwrap().kind);
+        assert_eq!(
+            Some("Loaded from foo.star"),
+            symbols.get("exported").unwrap().detail.as_deref()
+        );
+
+        // At the top level.
+        let symbols = find_symbols_at_location(&ast, 1, 0);
+        assert_eq!(
+            vec!["exported", "x", "f", "g"],
+            symbols.keys().map(|s| s.as_str()).collect::<Vec<_>>()
+        );
+    }
+}
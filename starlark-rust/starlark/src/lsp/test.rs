@@ -16,6 +16,7 @@
  */
 
 use std::collections::hash_map::Entry;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
@@ -66,6 +67,7 @@ use crate::docs::Location;
 use crate::errors::EvalMessage;
 use crate::lsp::server::new_notification;
 use crate::lsp::server::server_with_connection;
+use crate::lsp::server::LoadCompletion;
 use crate::lsp::server::LspContext;
 use crate::lsp::server::LspEvalResult;
 use crate::lsp::server::LspServerSettings;
@@ -120,6 +122,7 @@ struct TestServerContext {
     dirs: Arc<RwLock<HashSet<PathBuf>>>,
     builtin_docs: Arc<HashMap<LspUrl, String>>,
     builtin_symbols: Arc<HashMap<String, LspUrl>>,
+    environment: Arc<Vec<Doc>>,
 }
 
 impl LspContext for TestServerContext {
@@ -227,6 +230,49 @@ impl LspContext for TestServerContext {
     ) -> anyhow::Result<Option<LspUrl>> {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
+
+    fn get_environment(&self, _uri: &LspUrl) -> Arc<Vec<Doc>> {
+        self.environment.dupe()
+    }
+
+    fn get_load_completions(
+        &self,
+        current_value: &str,
+        current_file: &LspUrl,
+    ) -> anyhow::Result<Vec<LoadCompletion>> {
+        // Everything up to and including the last `/` is the directory to list.
+        let prefix = match current_value.rfind('/') {
+            Some(i) => &current_value[..=i],
+            None => "",
+        };
+        let dir = match self.resolve_load(prefix, current_file)? {
+            LspUrl::File(dir) => dir,
+            _ => return Ok(Vec::new()),
+        };
+        // Map from name within `dir` to whether it is a directory.
+        let mut entries = BTreeMap::new();
+        for path in self.file_contents.read().unwrap().keys() {
+            if LspUrl::File(path.clone()) == *current_file {
+                continue;
+            }
+            if let Ok(rest) = path.strip_prefix(&dir) {
+                let mut components = rest.components();
+                if let Some(first) = components.next() {
+                    let is_directory = components.next().is_some();
+                    entries
+                        .entry(first.as_os_str().to_string_lossy().into_owned())
+                        .or_insert(is_directory);
+                }
+            }
+        }
+        Ok(entries
+            .into_iter()
+            .map(|(name, is_directory)| LoadCompletion {
+                value: format!("{}{}", prefix, name),
+                is_directory,
+            })
+            .collect())
+    }
 }
 
 /// A server for use in testing that provides helpers for sending requests, correlating
@@ -357,16 +403,19 @@ impl TestServer {
         let builtin = Self::testing_builtins(&std::env::current_dir()?)?;
         let mut builtin_docs = HashMap::with_capacity(builtin.len());
         let mut builtin_symbols = HashMap::new();
+        let mut environment = Vec::new();
 
         for (u, ds) in builtin {
             builtin_docs.insert(u.clone(), render_docs_as_code(&ds));
             for d in ds {
-                builtin_symbols.insert(d.id.name, u.clone());
+                builtin_symbols.insert(d.id.name.clone(), u.clone());
+                environment.push(d);
             }
         }
 
         let builtin_docs = Arc::new(builtin_docs);
         let builtin_symbols = Arc::new(builtin_symbols);
+        let environment = Arc::new(environment);
 
         let prelude_file_contents = builtin_docs
             .iter()
@@ -382,6 +431,7 @@ impl TestServer {
             dirs: dirs.dupe(),
             builtin_docs: builtin_docs.dupe(),
             builtin_symbols,
+            environment,
         };
 
         let server_thread = std::thread::spawn(|| {
@@ -21,33 +21,86 @@
 use std::env;
 use std::io;
 
+use rustyline::completion::Completer;
 use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
 use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::Context;
 use rustyline::Editor;
+use rustyline::Helper;
+
+/// Given the line and the cursor position, return the position where the completed
+/// text starts, and the candidates to replace it with.
+type ReadLineCompleter = Box<dyn Fn(&str, usize) -> (usize, Vec<String>)>;
+
+struct ReadLineHelper {
+    completer: Option<ReadLineCompleter>,
+}
+
+impl Completer for ReadLineHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        match &self.completer {
+            Some(completer) => Ok(completer(line, pos)),
+            None => Ok((pos, Vec::new())),
+        }
+    }
+}
+
+impl Hinter for ReadLineHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReadLineHelper {}
+
+impl Validator for ReadLineHelper {}
+
+impl Helper for ReadLineHelper {}
 
 /// Wrapper for the readline library, whichever we are using at the moment.
 pub struct ReadLine {
-    editor: Editor<(), DefaultHistory>,
+    editor: Editor<ReadLineHelper, DefaultHistory>,
     histfile: Option<String>,
 }
 
 impl ReadLine {
     pub fn new(histfile_env: &str) -> anyhow::Result<ReadLine> {
+        Self::with_history_file(env::var(histfile_env).ok())
+    }
+
+    /// Like `new`, but with the history file given directly, `None` to not keep history.
+    pub fn with_history_file(histfile: Option<String>) -> anyhow::Result<ReadLine> {
         let mut editor = Editor::new()?;
-        let histfile = if let Ok(histfile) = env::var(histfile_env) {
-            if let Err(e) = editor.load_history(&histfile) {
+        editor.set_helper(Some(ReadLineHelper { completer: None }));
+        if let Some(histfile) = &histfile {
+            if let Err(e) = editor.load_history(histfile) {
                 match e {
                     ReadlineError::Io(e) if e.kind() == io::ErrorKind::NotFound => {}
                     e => eprintln!("Failed to load history from `{}`: {}", histfile, e),
                 }
             }
-            Some(histfile)
-        } else {
-            None
-        };
+        }
         Ok(ReadLine { editor, histfile })
     }
 
+    /// Complete the text at the cursor when tab is pressed.
+    pub fn set_completer(
+        &mut self,
+        completer: impl Fn(&str, usize) -> (usize, Vec<String>) + 'static,
+    ) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.completer = Some(Box::new(completer));
+        }
+    }
+
     /// Read line. Return `None` on EOF or interrupt.
     pub fn read_line(&mut self, prompt: &str) -> anyhow::Result<Option<String>> {
         match self.editor.readline(prompt) {
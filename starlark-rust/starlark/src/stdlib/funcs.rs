@@ -23,6 +23,7 @@ use std::cmp::Ordering;
 use std::fmt::Display;
 use std::num::NonZeroI32;
 
+use either::Either;
 use starlark_derive::starlark_module;
 
 use crate as starlark;
@@ -31,6 +32,7 @@ use crate::environment::GlobalsBuilder;
 use crate::eval::Arguments;
 use crate::eval::Evaluator;
 use crate::values::bool::BOOL_TYPE;
+use crate::values::bytes::StarlarkBytes;
 use crate::values::dict::Dict;
 use crate::values::dict::DictRef;
 use crate::values::float::StarlarkFloat;
@@ -248,6 +250,50 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
         }
     }
 
+    /// [bytes](
+    /// https://github.com/bazelbuild/starlark/blob/master/spec.md#bytes
+    /// ): convert a value to bytes.
+    ///
+    /// `bytes(x)` converts its argument to bytes.
+    ///
+    /// If x is bytes, the result is x.
+    /// If x is a string, the result is its UTF-8 encoding.
+    /// If x is an iterable of ints, each of which must be in the range 0-255,
+    /// the result contains those values.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// bytes("hello") == b"hello"
+    /// bytes([104, 105]) == b"hi"
+    /// bytes(b"hi") == b"hi"
+    /// # "#);
+    /// ```
+    #[starlark(type = StarlarkBytes::TYPE, speculative_exec_safe)]
+    fn bytes<'v>(
+        #[starlark(require = pos, type = "[str.type, \"bytes\", iter(int.type)]")] x: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        if StarlarkBytes::from_value(x).is_some() {
+            return Ok(x);
+        }
+        if let Some(s) = x.unpack_str() {
+            return Ok(heap.alloc(StarlarkBytes::new(s.as_bytes())));
+        }
+        let mut res = Vec::new();
+        for v in x.iterate(heap)? {
+            match v.unpack_int().and_then(|i| u8::try_from(i).ok()) {
+                Some(b) => res.push(b),
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "bytes() expects ints in the range 0-255, got `{}`",
+                        v.to_repr()
+                    ));
+                }
+            }
+        }
+        Ok(heap.alloc(StarlarkBytes::new(res)))
+    }
+
     /// [chr](
     /// https://github.com/google/skylark/blob/a0e5de7e63b47e716cca7226662a4c95d47bf873/doc/spec.md#bool
     /// ): returns a string encoding a codepoint.
@@ -541,13 +587,21 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
-    fn hash(#[starlark(require = pos)] a: &str) -> anyhow::Result<i32> {
+    fn hash(#[starlark(require = pos)] a: Either<&str, &StarlarkBytes>) -> anyhow::Result<i32> {
         // From the starlark spec:
         // > the hash function for strings is the same as that implemented by java.lang.String.hashCode,
         // > a simple polynomial accumulator over the UTF-16 transcoding of the string:
         // > `s[0]*31^(n-1) + s[1]*31^(n-2) + ... + s[n-1]`
         // As per spec the function should only support string and bytes types.
-        // We don't have support for bytes, so parameter is forced to be a string.
+        // Bytes use the same accumulator over the bytes, so agree with ASCII strings.
+        let a = match a {
+            Either::Left(a) => a,
+            Either::Right(b) => {
+                return Ok(b.iter().fold(0i32, |hash: i32, b: &u8| {
+                    31i32.wrapping_mul(hash).wrapping_add(*b as i32)
+                }));
+            }
+        };
 
         // Most strings are ASCII strings, try them first.
         #[allow(clippy::never_loop)]
@@ -1047,6 +1101,8 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// If x is a string, the result is x (without quotation).
     /// All other strings, such as elements of a list of strings, are
     /// double-quoted.
+    /// If x is bytes, the result is their UTF-8 decoding, with any invalid
+    /// sequences replaced by U+FFFD.
     ///
     /// ```
     /// # starlark::assert::all_true(r#"
@@ -1063,6 +1119,8 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
         if let Some(a) = StringValue::new(a) {
             // Special case that can avoid reallocating, but is equivalent.
             Ok(a)
+        } else if let Some(b) = StarlarkBytes::from_value(a) {
+            Ok(eval.heap().alloc_str(&b.to_str_lossy()))
         } else {
             let mut s = eval.string_pool.alloc();
             a.collect_repr(&mut s);
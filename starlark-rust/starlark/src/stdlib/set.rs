@@ -0,0 +1,360 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `set()` constructor and methods for the `set` type.
+
+use std::mem;
+
+use starlark_derive::starlark_module;
+
+use crate as starlark;
+use crate::collections::SmallSet;
+use crate::environment::GlobalsBuilder;
+use crate::environment::MethodsBuilder;
+use crate::values::none::NoneType;
+use crate::values::set::Set;
+use crate::values::set::SetMut;
+use crate::values::set::SetRef;
+use crate::values::Heap;
+use crate::values::Value;
+
+/// Collect the values of an iterable into a new [`Set`].
+/// Fails if the value is not iterable, or any element is not hashable.
+fn set_from_iterable<'v>(xs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Set<'v>> {
+    if let Some(xs) = SetRef::from_value(xs) {
+        return Ok((*xs).clone());
+    }
+    let it = xs.iterate(heap)?;
+    let mut content = SmallSet::with_capacity(it.size_hint().0);
+    for x in it {
+        content.insert_hashed(x.get_hashed()?);
+    }
+    Ok(Set::new(content))
+}
+
+#[starlark_module]
+pub(crate) fn global(builder: &mut GlobalsBuilder) {
+    /// Create a new set, a collection of unique hashable values
+    /// which iterates in insertion order.
+    ///
+    /// `set()` returns a new empty set.
+    /// `set(xs)` returns a new set containing the elements of the iterable `xs`,
+    /// with duplicates removed.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set() == set([])
+    /// set([1, 2, 1]) == set([1, 2])
+    /// list(set([3, 1, 3, 2])) == [3, 1, 2]
+    /// len(set(("a", "b", "a"))) == 2
+    /// # "#);
+    /// ```
+    #[starlark(type = Set::TYPE, speculative_exec_safe)]
+    fn set<'v>(
+        #[starlark(require = pos, type = "iter(\"\")")] a: Option<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        match a {
+            None => Ok(Set::default()),
+            Some(a) => set_from_iterable(a, heap),
+        }
+    }
+}
+
+#[starlark_module]
+pub(crate) fn set_methods(registry: &mut MethodsBuilder) {
+    /// `S.add(x)` adds the value `x` to the set `S`, if not already present.
+    ///
+    /// `add` fails if `x` is unhashable, or the set is frozen or has active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.add(3)
+    /// x.add(1)
+    /// x == set([1, 2, 3])
+    /// # "#);
+    /// ```
+    fn add<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let mut this = SetMut::from_value(this)?;
+        this.insert_hashed(value.get_hashed()?);
+        Ok(NoneType)
+    }
+
+    /// `S.clear()` removes all the values of the set `S`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.clear()
+    /// x == set()
+    /// # "#);
+    /// ```
+    fn clear(this: Value) -> anyhow::Result<NoneType> {
+        let mut this = SetMut::from_value(this)?;
+        this.clear();
+        Ok(NoneType)
+    }
+
+    /// `S.difference(xs)` returns a new set containing the values of `S`
+    /// which are not in the iterable `xs`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// set([1, 2, 3]).difference([2, 4]) == set([1, 3])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn difference<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.difference(&set_from_iterable(other, heap)?))
+    }
+
+    /// `S.discard(x)` removes the value `x` from the set `S`, if present.
+    ///
+    /// `discard` fails if `x` is unhashable, or the set is frozen or has active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.discard(2)
+    /// x.discard(3)
+    /// x == set([1])
+    /// # "#);
+    /// ```
+    fn discard<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let mut this = SetMut::from_value(this)?;
+        this.remove_hashed(value.get_hashed()?);
+        Ok(NoneType)
+    }
+
+    /// `S.intersection(xs)` returns a new set containing the values of `S`
+    /// which are also in the iterable `xs`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// set([1, 2, 3]).intersection([3, 2, 4]) == set([2, 3])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn intersection<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.intersection(&set_from_iterable(other, heap)?))
+    }
+
+    /// `S.isdisjoint(xs)` returns `True` if `S` has no values in common with the iterable `xs`.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2]).isdisjoint([3, 4])
+    /// not set([1, 2]).isdisjoint([2, 3])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn isdisjoint<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(this.is_disjoint(&set_from_iterable(other, heap)?))
+    }
+
+    /// `S.issubset(xs)` returns `True` if every value of `S` is in the iterable `xs`.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2]).issubset([1, 2, 3])
+    /// not set([1, 4]).issubset([1, 2, 3])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn issubset<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(this.is_subset(&set_from_iterable(other, heap)?))
+    }
+
+    /// `S.issuperset(xs)` returns `True` if every value of the iterable `xs` is in `S`.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2, 3]).issuperset([1, 2])
+    /// not set([1, 2, 3]).issuperset([1, 4])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn issuperset<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(set_from_iterable(other, heap)?.is_subset(&this))
+    }
+
+    /// `S.pop()` removes and returns the first value of the set `S`.
+    ///
+    /// `pop` fails if the set is empty, frozen or has active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([3, 1])
+    /// # (
+    /// x.pop() == 3
+    /// # and
+    /// x == set([1])
+    /// # )"#);
+    /// ```
+    fn pop<'v>(this: Value<'v>) -> anyhow::Result<Value<'v>> {
+        let mut this = SetMut::from_value(this)?;
+        match this.pop_first() {
+            Some(x) => Ok(x),
+            None => Err(anyhow::anyhow!("Cannot .pop() on an empty set")),
+        }
+    }
+
+    /// `S.remove(x)` removes the value `x` from the set `S`.
+    ///
+    /// `remove` fails if `x` is not in the set, `x` is unhashable,
+    /// or the set is frozen or has active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.remove(2)
+    /// x == set([1])
+    /// # "#);
+    /// ```
+    ///
+    /// ```
+    /// # starlark::assert::fail(r#"
+    /// set([1]).remove(2)   # error: not found
+    /// # "#, "not found");
+    /// ```
+    fn remove<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let mut me = SetMut::from_value(this)?;
+        if me.remove_hashed(value.get_hashed()?) {
+            Ok(NoneType)
+        } else {
+            mem::drop(me);
+            Err(anyhow::anyhow!(
+                "Value `{}` not found in set `{}`",
+                value.to_repr(),
+                this.to_repr()
+            ))
+        }
+    }
+
+    /// `S.symmetric_difference(xs)` returns a new set containing the values
+    /// which are in exactly one of `S` and the iterable `xs`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// set([1, 2, 3]).symmetric_difference([3, 4]) == set([1, 2, 4])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn symmetric_difference<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.symmetric_difference(&set_from_iterable(other, heap)?))
+    }
+
+    /// `S.union(xs)` returns a new set containing the values of `S`
+    /// followed by the values of the iterable `xs` not already in `S`.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2]).union([2, 3]) == set([1, 2, 3])
+    /// list(set([2, 1]).union([3, 1])) == [2, 1, 3]
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn union<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.union(&set_from_iterable(other, heap)?))
+    }
+
+    /// `S.update(xs)` adds all the values of the iterable `xs` to the set `S`.
+    ///
+    /// `update` fails if any value is unhashable, or the set is frozen or has active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1])
+    /// x.update([2, 1, 3])
+    /// x == set([1, 2, 3])
+    /// # "#);
+    /// ```
+    fn update<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<NoneType> {
+        if other.ptr_eq(this) {
+            // `x.update(x)` does nothing, but would fail trying to iterate `x`
+            // while it is borrowed for mutation.
+            return Ok(NoneType);
+        }
+        // Collect first, so that iterating `other` cannot observe `this` mid-mutation.
+        let other = set_from_iterable(other, heap)?;
+        let mut this = SetMut::from_value(this)?;
+        for x in other.iter_hashed() {
+            this.insert_hashed(x);
+        }
+        Ok(NoneType)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    #[test]
+    fn test_error_codes() {
+        assert::fail("x = set([1]); x.remove(2)", "not found");
+        assert::fail("x = set(); x.pop()", "empty");
+    }
+
+    #[test]
+    fn test_set_not_in_standard() {
+        let mut a = assert::Assert::new();
+        a.globals(crate::environment::Globals::standard());
+        a.fail("set([1])", "Variable `set` not found");
+    }
+}
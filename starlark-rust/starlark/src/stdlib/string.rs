@@ -27,6 +27,7 @@ use crate::eval::Arguments;
 use crate::eval::Evaluator;
 use crate::slice_vec_ext::SliceExt;
 use crate::stdlib::string::fast_string::convert_str_indices;
+use crate::values::bytes::StarlarkBytes;
 use crate::values::none::NoneOr;
 use crate::values::string::dot_format;
 use crate::values::string::fast_string;
@@ -247,6 +248,21 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
         }
     }
 
+    /// string.encode: returns the UTF-8 encoding of a string as bytes.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// "hello".encode() == b"hello"
+    /// len("café".encode()) == 5
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn encode(this: &str) -> anyhow::Result<StarlarkBytes> {
+        Ok(StarlarkBytes::new(this.as_bytes()))
+    }
+
     /// [string.endswith](
     /// https://github.com/google/skylark/blob/3705afa472e466b8b061cce44b47c9ddc6db696d/doc/spec.md#string·endswith
     /// ): determine if a string ends with a given suffix.
@@ -21,6 +21,7 @@ use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::fmt::Write;
 use std::mem;
 
 use allocative::Allocative;
@@ -34,6 +35,7 @@ use crate::codemap::Span;
 use crate::codemap::Spanned;
 use crate::syntax::lexer::TokenInt;
 use crate::syntax::Dialect;
+use crate::values::bytes::StarlarkBytes;
 
 /// Payload types attached to AST nodes.
 pub(crate) trait AstPayload: Debug {
@@ -61,6 +63,7 @@ pub(crate) type Argument = ArgumentP<AstNoPayload>;
 pub(crate) type Parameter = ParameterP<AstNoPayload>;
 pub(crate) type Load = LoadP<AstNoPayload>;
 pub(crate) type Stmt = StmtP<AstNoPayload>;
+pub(crate) type FString = FStringP<AstNoPayload>;
 
 // Boxed types used for storing information from the parsing will be used
 // especially for the location of the AST item
@@ -80,6 +83,7 @@ pub(crate) type AstString = Spanned<String>;
 pub(crate) type AstParameter = AstParameterP<AstNoPayload>;
 pub(crate) type AstInt = Spanned<TokenInt>;
 pub(crate) type AstFloat = Spanned<f64>;
+pub(crate) type AstBytes = Spanned<Vec<u8>>;
 pub(crate) type AstStmt = AstStmtP<AstNoPayload>;
 
 // We don't care _that_ much about the size of these structures,
@@ -169,6 +173,17 @@ pub(crate) enum AstLiteral {
     Int(AstInt),
     Float(AstFloat),
     String(AstString),
+    Bytes(AstBytes),
+}
+
+/// `f"..."` literal.
+#[derive(Debug)]
+pub(crate) struct FStringP<P: AstPayload> {
+    /// The format string, with a `{}` for each expression, which may contain
+    /// a conversion and a format spec, e.g. `"x = {!r:>10}"`.
+    pub(crate) format: AstString,
+    /// The expressions to format, in order, which are currently always identifiers.
+    pub(crate) expressions: Vec<AstExprP<P>>,
 }
 
 #[derive(Debug)]
@@ -220,6 +235,7 @@ pub(crate) enum ExprP<P: AstPayload> {
         Box<ForClauseP<P>>,
         Vec<ClauseP<P>>,
     ),
+    FString(Box<FStringP<P>>),
 }
 
 /// In some places e.g. AssignModify, the Tuple case is not allowed.
@@ -338,6 +354,7 @@ pub(crate) enum StmtP<P: AstPayload> {
     If(AstExprP<P>, Box<AstStmtP<P>>),
     IfElse(AstExprP<P>, Box<(AstStmtP<P>, AstStmtP<P>)>),
     For(AstAssignP<P>, Box<(AstExprP<P>, AstStmtP<P>)>),
+    While(AstExprP<P>, Box<AstStmtP<P>>),
     Def(DefP<P>),
     // The Visibility of a Load is implicit from the Dialect, not written by a user
     Load(LoadP<P>),
@@ -461,6 +478,9 @@ impl Display for AstLiteral {
             AstLiteral::Int(i) => write!(f, "{}", &i.node),
             AstLiteral::Float(n) => write!(f, "{}", &n.node),
             AstLiteral::String(s) => fmt_string_literal(f, &s.node),
+            AstLiteral::Bytes(b) => {
+                write!(f, "{}", StarlarkBytes::new(b.node.as_slice()))
+            }
         }
     }
 }
@@ -552,10 +572,33 @@ impl Display for Expr {
                 f.write_str("}}")
             }
             Expr::Literal(x) => write!(f, "{}", x),
+            Expr::FString(x) => write!(f, "{}", x),
         }
     }
 }
 
+impl Display for FString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Put the expressions back inside the braces.
+        let mut source = String::with_capacity(self.format.len());
+        let mut expressions = self.expressions.iter();
+        let mut chars = self.format.chars().peekable();
+        while let Some(c) = chars.next() {
+            source.push(c);
+            if c == '{' {
+                if chars.peek() == Some(&'{') {
+                    chars.next();
+                    source.push('{');
+                } else if let Some(e) = expressions.next() {
+                    write!(source, "{}", e.node)?;
+                }
+            }
+        }
+        f.write_str("f")?;
+        fmt_string_literal(f, &source)
+    }
+}
+
 impl Display for Assign {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
@@ -666,6 +709,10 @@ impl Stmt {
                 writeln!(f, "{}for {} in {}:", tab, bind.node, coll.node)?;
                 suite.node.fmt_with_tab(f, tab + "  ")
             }
+            Stmt::While(cond, suite) => {
+                writeln!(f, "{}while {}:", tab, cond.node)?;
+                suite.node.fmt_with_tab(f, tab + "  ")
+            }
             Stmt::Def(DefP {
                 name,
                 params,
@@ -37,6 +37,12 @@ pub(crate) enum DialectError {
     KeywordOnlyArguments,
     #[error("type annotations are not allowed in this dialect")]
     Types,
+    #[error("bytes literals are not allowed in this dialect")]
+    Bytes,
+    #[error("`while` is not allowed in this dialect")]
+    While,
+    #[error("f-strings are not allowed in this dialect")]
+    FString,
 }
 
 /// How to handle type annotations in Starlark.
@@ -75,6 +81,15 @@ pub struct Dialect {
     /// Are `for`, `if` and other statements allowed at the top level.
     /// Only enabled in [`Extended`](Dialect::Extended).
     pub enable_top_level_stmt: bool,
+    /// Are `b"..."` bytes literals permitted.
+    /// Only enabled in [`Extended`](Dialect::Extended).
+    pub enable_bytes: bool,
+    /// Are `while` loops permitted, an optional feature of the Starlark language standard.
+    /// Only enabled in [`Extended`](Dialect::Extended).
+    pub enable_while: bool,
+    /// Are `f"..."` string literals permitted, formatting the identifiers in `{}`.
+    /// Only enabled in [`Extended`](Dialect::Extended).
+    pub enable_f_strings: bool,
     /// Like `#[non_exhaustive]`, but allows struct expression.
     ///
     /// [Explanation](https://github.com/rust-lang/rust-clippy/issues/6559).
@@ -102,6 +117,9 @@ impl Dialect {
         enable_types: DialectTypes::Disable,
         enable_load_reexport: true, // But they plan to change it
         enable_top_level_stmt: false,
+        enable_bytes: false,
+        enable_while: false,
+        enable_f_strings: false,
         _non_exhaustive: (),
     };
 
@@ -114,6 +132,9 @@ impl Dialect {
         enable_types: DialectTypes::Enable,
         enable_load_reexport: true,
         enable_top_level_stmt: true,
+        enable_bytes: true,
+        enable_while: true,
+        enable_f_strings: true,
         _non_exhaustive: (),
     };
 }
@@ -174,6 +195,42 @@ impl Dialect {
         }
     }
 
+    pub(crate) fn check_bytes<T>(
+        &self,
+        codemap: &CodeMap,
+        x: Spanned<T>,
+    ) -> anyhow::Result<Spanned<T>> {
+        if self.enable_bytes {
+            Ok(x)
+        } else {
+            err(codemap, x.span, DialectError::Bytes)
+        }
+    }
+
+    pub(crate) fn check_fstring<T>(
+        &self,
+        codemap: &CodeMap,
+        x: Spanned<T>,
+    ) -> anyhow::Result<Spanned<T>> {
+        if self.enable_f_strings {
+            Ok(x)
+        } else {
+            err(codemap, x.span, DialectError::FString)
+        }
+    }
+
+    pub(crate) fn check_while<T>(
+        &self,
+        codemap: &CodeMap,
+        x: Spanned<T>,
+    ) -> anyhow::Result<Spanned<T>> {
+        if self.enable_while {
+            Ok(x)
+        } else {
+            err(codemap, x.span, DialectError::While)
+        }
+    }
+
     pub(crate) fn load_visibility(&self) -> Visibility {
         if self.enable_load_reexport {
             Visibility::Public
@@ -0,0 +1,1017 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Print an [`AstModule`] back out as canonical Starlark source.
+//!
+//! The AST does not record comments, blank lines or where lines were broken, so those
+//! are recovered from the tokens of the original source. Comments are whatever lies
+//! between two tokens. A bracketed list of items (arguments, parameters, list, dict and
+//! tuple elements, `load` symbols) is written one item per line if the original had a
+//! line break inside it, contains a comment, or would not fit on one line. Otherwise
+//! everything is written on one line.
+
+use std::iter;
+use std::mem;
+
+use dupe::Dupe;
+
+use crate::codemap::CodeMap;
+use crate::codemap::Pos;
+use crate::codemap::Span;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AssignP;
+use crate::syntax::ast::AstArgument;
+use crate::syntax::ast::AstAssign;
+use crate::syntax::ast::AstAssignIdent;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstParameter;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::AstString;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::Clause;
+use crate::syntax::ast::ClauseP;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::ExprP;
+use crate::syntax::ast::ForClause;
+use crate::syntax::ast::LambdaP;
+use crate::syntax::ast::Load;
+use crate::syntax::ast::ParameterP;
+use crate::syntax::ast::StmtP;
+use crate::syntax::lexer::Lexer;
+use crate::syntax::lexer::Token;
+use crate::syntax::AstModule;
+
+/// One level of indentation, for blocks and for items of a list split over several lines.
+const INDENT: &str = "    ";
+
+/// A list of items which would make a line wider than this is split over several lines.
+const MAX_WIDTH: usize = 100;
+
+// How tightly each kind of expression binds, loosest first, following the grammar.
+const PREC_TEST: u8 = 0;
+const PREC_IF: u8 = 1;
+const PREC_OR: u8 = 2;
+const PREC_AND: u8 = 3;
+const PREC_NOT: u8 = 4;
+const PREC_COMPARE: u8 = 5;
+const PREC_BIT_OR: u8 = 6;
+const PREC_BIT_XOR: u8 = 7;
+const PREC_BIT_AND: u8 = 8;
+const PREC_SHIFT: u8 = 9;
+const PREC_ARITH: u8 = 10;
+const PREC_PRODUCT: u8 = 11;
+const PREC_UNARY: u8 = 12;
+const PREC_PRIMARY: u8 = 13;
+
+fn bin_op_prec(op: BinOp) -> u8 {
+    match op {
+        BinOp::Or => PREC_OR,
+        BinOp::And => PREC_AND,
+        BinOp::Equal
+        | BinOp::NotEqual
+        | BinOp::Less
+        | BinOp::Greater
+        | BinOp::LessOrEqual
+        | BinOp::GreaterOrEqual
+        | BinOp::In
+        | BinOp::NotIn => PREC_COMPARE,
+        BinOp::BitOr => PREC_BIT_OR,
+        BinOp::BitXor => PREC_BIT_XOR,
+        BinOp::BitAnd => PREC_BIT_AND,
+        BinOp::LeftShift | BinOp::RightShift => PREC_SHIFT,
+        BinOp::Add | BinOp::Subtract => PREC_ARITH,
+        BinOp::Multiply | BinOp::Percent | BinOp::Divide | BinOp::FloorDivide => PREC_PRODUCT,
+    }
+}
+
+fn expr_prec(x: &AstExpr) -> u8 {
+    match &x.node {
+        ExprP::Lambda(_) => PREC_TEST,
+        ExprP::If(_) => PREC_IF,
+        ExprP::Op(_, op, _) => bin_op_prec(*op),
+        ExprP::Not(_) => PREC_NOT,
+        ExprP::Minus(_) | ExprP::Plus(_) | ExprP::BitNot(_) => PREC_UNARY,
+        _ => PREC_PRIMARY,
+    }
+}
+
+fn offset(pos: Pos) -> usize {
+    pos.get() as usize
+}
+
+/// The width of the first line of `s`.
+fn first_line_width(s: &str) -> usize {
+    s.lines().next().map_or(0, |line| line.chars().count())
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Comment {
+    begin: usize,
+    end: usize,
+    /// Whether there is nothing but whitespace before the comment on its line.
+    own_line: bool,
+}
+
+/// The comments in `source[begin..end]`, which must not contain any tokens.
+fn comments_between(source: &str, begin: usize, end: usize, res: &mut Vec<Comment>) {
+    let mut pos = begin;
+    while let Some(i) = source[pos..end].find('#') {
+        let begin = pos + i;
+        let end = source[begin..end].find('\n').map_or(end, |i| begin + i);
+        let line_begin = source[..begin].rfind('\n').map_or(0, |i| i + 1);
+        res.push(Comment {
+            begin,
+            end,
+            own_line: source[line_begin..begin].trim().is_empty(),
+        });
+        pos = end;
+    }
+}
+
+/// A `load` statement has its module and symbols as items in one bracketed list.
+enum LoadItem<'a> {
+    Module(&'a AstString),
+    Symbol(&'a AstAssignIdent, &'a AstString),
+}
+
+struct Printer<'a> {
+    codemap: &'a CodeMap,
+    source: &'a str,
+    /// The tokens of the source, apart from newlines, indents and dedents.
+    tokens: Vec<(usize, Token, usize)>,
+    comments: Vec<Comment>,
+    written: Vec<bool>,
+    /// No comment before this one is waiting to be written.
+    next_comment: usize,
+    out: String,
+    indent: usize,
+    /// Write everything on one line, used to find out how wide that would be.
+    flat: bool,
+    /// The end of the source of the last thing written.
+    last: usize,
+    /// Nothing has been written in the current block or list yet.
+    block_start: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn new(ast: &'a AstModule) -> Self {
+        let source = ast.codemap.source();
+        // The module parsed, so lexing it again cannot fail.
+        let tokens: Vec<_> = Lexer::new(source, &ast.dialect, ast.codemap.dupe())
+            .filter_map(|lexeme| lexeme.ok())
+            .filter(|(_, token, _)| {
+                !matches!(token, Token::Newline | Token::Indent | Token::Dedent)
+            })
+            .collect();
+        let mut comments = Vec::new();
+        let gap_begins = iter::once(0).chain(tokens.iter().map(|(_, _, end)| *end));
+        let gap_ends = tokens
+            .iter()
+            .map(|(begin, _, _)| *begin)
+            .chain(iter::once(source.len()));
+        for (begin, end) in gap_begins.zip(gap_ends) {
+            comments_between(source, begin, end, &mut comments);
+        }
+        Printer {
+            codemap: &ast.codemap,
+            source,
+            tokens,
+            written: vec![false; comments.len()],
+            comments,
+            next_comment: 0,
+            out: String::new(),
+            indent: 0,
+            flat: false,
+            last: 0,
+            block_start: true,
+        }
+    }
+
+    fn line(&self, pos: usize) -> usize {
+        self.codemap.find_line(Pos::new(pos as u32))
+    }
+
+    fn column(&self, pos: usize) -> usize {
+        pos - self.source[..pos].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    /// The first token which starts at or after `pos`.
+    fn token_after(&self, pos: usize) -> Option<&(usize, Token, usize)> {
+        self.tokens
+            .get(self.tokens.partition_point(|(begin, _, _)| *begin < pos))
+    }
+
+    /// The last token which ends at or before `pos`.
+    fn token_before(&self, pos: usize) -> Option<&(usize, Token, usize)> {
+        let i = self.tokens.partition_point(|(_, _, end)| *end <= pos);
+        i.checked_sub(1).map(|i| &self.tokens[i])
+    }
+
+    /// The end of the last token of `span`. The spans of statements with a block can
+    /// include the blank lines after the block.
+    fn content_end(&self, span: Span) -> usize {
+        let end = offset(span.end());
+        self.token_before(end).map_or(end, |(_, _, end)| *end)
+    }
+
+    /// Where whatever follows the statement at `span` starts.
+    fn next_token_begin(&self, span: Span) -> usize {
+        self.token_after(self.content_end(span))
+            .map_or(self.source.len(), |(begin, _, _)| *begin)
+    }
+
+    /// The end of the last `token` which ends at or before `pos`.
+    fn end_of_token_before(&self, pos: usize, token: &Token) -> usize {
+        let i = self.tokens.partition_point(|(_, _, end)| *end <= pos);
+        self.tokens[..i]
+            .iter()
+            .rev()
+            .find(|(_, t, _)| t == token)
+            .map_or(pos, |(_, _, end)| *end)
+    }
+
+    /// The end of the first `token` which starts at or after `pos`.
+    fn end_of_token_after(&self, pos: usize, token: &Token) -> usize {
+        let i = self.tokens.partition_point(|(begin, _, _)| *begin < pos);
+        self.tokens[i..]
+            .iter()
+            .find(|(_, t, _)| t == token)
+            .map_or(pos, |(_, _, end)| *end)
+    }
+
+    /// Whether the source has a line break between two tokens in `begin..end`.
+    fn breaks_line(&self, begin: usize, end: usize) -> bool {
+        let first = self.tokens.partition_point(|(b, _, _)| *b < begin);
+        let last = self.tokens.partition_point(|(_, _, e)| *e <= end);
+        first < last
+            && self.tokens[first..last]
+                .windows(2)
+                .any(|w| self.source[w[0].2..w[1].0].contains('\n'))
+    }
+
+    fn has_comments(&self, begin: usize, end: usize) -> bool {
+        let i = self.comments.partition_point(|c| c.begin < begin);
+        self.comments.get(i).map_or(false, |c| c.begin < end)
+    }
+
+    fn has_blank_line(&self, begin: usize, end: usize) -> bool {
+        if begin >= end {
+            return false;
+        }
+        let lines: Vec<&str> = self.source[begin..end].split('\n').collect();
+        lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|l| l.trim().is_empty())
+    }
+
+    fn out_column(&self) -> usize {
+        let line_begin = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line_begin..].chars().count()
+    }
+
+    /// Start a new line, first adding a blank line if there was one in the source
+    /// between the last thing written and `pos`.
+    fn start_line(&mut self, pos: Option<usize>) {
+        if let Some(pos) = pos {
+            if !self.block_start && self.has_blank_line(self.last, pos) {
+                self.out.push('\n');
+            }
+        }
+        self.block_start = false;
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.out.push('\n');
+    }
+
+    fn write_comment(&mut self, i: usize) {
+        let source = self.source;
+        let comment = self.comments[i];
+        self.start_line(Some(comment.begin));
+        self.out
+            .push_str(source[comment.begin..comment.end].trim_end());
+        self.end_line();
+        self.written[i] = true;
+        self.last = comment.end;
+    }
+
+    /// Write the comments before `pos` which have not been written yet, each on its own line.
+    fn write_comments_before(&mut self, pos: usize) {
+        while self.next_comment < self.comments.len()
+            && self.comments[self.next_comment].begin < pos
+        {
+            if !self.written[self.next_comment] {
+                self.write_comment(self.next_comment);
+            }
+            self.next_comment += 1;
+        }
+    }
+
+    /// Write the comments which follow the last statement of a block, are before `limit`,
+    /// and are indented more than the statement the block belongs to.
+    fn write_comments_ending_block(&mut self, limit: usize, owner_column: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment).copied() {
+            if !self.written[self.next_comment] {
+                if comment.begin >= limit
+                    || !comment.own_line
+                    || self.column(comment.begin) <= owner_column
+                {
+                    return;
+                }
+                self.write_comment(self.next_comment);
+            }
+            self.next_comment += 1;
+        }
+    }
+
+    /// Write the comment which follows `end` on the same line, if there is one before `limit`.
+    fn write_trailing_comment(&mut self, end: usize, limit: usize) {
+        let source = self.source;
+        let i = self.comments.partition_point(|c| c.begin < end);
+        if let Some(comment) = self.comments.get(i).copied() {
+            if !self.written[i]
+                && comment.begin < limit
+                && self.line(comment.begin) == self.line(end)
+            {
+                self.out.push_str("  ");
+                self.out
+                    .push_str(source[comment.begin..comment.end].trim_end());
+                self.written[i] = true;
+                self.last = comment.end;
+            }
+        }
+    }
+
+    /// Write everything `f` writes on one line, and return it rather than writing it.
+    fn render_flat(&mut self, f: impl FnOnce(&mut Self)) -> String {
+        let start = self.out.len();
+        let flat = mem::replace(&mut self.flat, true);
+        f(self);
+        self.flat = flat;
+        self.out.split_off(start)
+    }
+
+    fn stmt(&mut self, x: &AstStmt) {
+        if let StmtP::Statements(xs) = &x.node {
+            for x in xs {
+                self.stmt(x);
+            }
+            return;
+        }
+
+        let begin = offset(x.span.begin());
+        self.write_comments_before(begin);
+        self.start_line(Some(begin));
+        match &x.node {
+            StmtP::Statements(_) => unreachable!("handled above"),
+            StmtP::Break => self.out.push_str("break"),
+            StmtP::Continue => self.out.push_str("continue"),
+            StmtP::Pass => self.out.push_str("pass"),
+            StmtP::Return(value) => {
+                self.out.push_str("return");
+                if let Some(value) = value {
+                    self.out.push(' ');
+                    self.expr(value, PREC_TEST);
+                }
+            }
+            StmtP::Expression(e) => self.expr(e, PREC_TEST),
+            StmtP::Assign(lhs, ty_rhs) => {
+                let (ty, rhs) = &**ty_rhs;
+                self.assign_target(lhs, true);
+                if let Some(ty) = ty {
+                    self.out.push_str(": ");
+                    self.expr(ty, PREC_TEST);
+                }
+                self.out.push_str(" = ");
+                self.expr(rhs, PREC_TEST);
+            }
+            StmtP::AssignModify(lhs, op, rhs) => {
+                self.assign_target(lhs, true);
+                self.out.push_str(&op.to_string());
+                self.expr(rhs, PREC_TEST);
+            }
+            StmtP::Load(load) => self.load(x.span, load),
+            StmtP::If(cond, body) => return self.if_stmt("if", begin, x.span, cond, body, None),
+            StmtP::IfElse(cond, bodies) => {
+                let (then_body, else_body) = &**bodies;
+                return self.if_stmt("if", begin, x.span, cond, then_body, Some(else_body));
+            }
+            StmtP::For(var, over_body) => {
+                let (over, body) = &**over_body;
+                self.out.push_str("for ");
+                self.assign_target(var, true);
+                self.out.push_str(" in ");
+                self.expr(over, PREC_TEST);
+                self.header_end(body);
+                return self.block(body, self.column(begin), self.next_token_begin(x.span));
+            }
+            StmtP::While(cond, body) => {
+                self.out.push_str("while ");
+                self.expr(cond, PREC_TEST);
+                self.header_end(body);
+                return self.block(body, self.column(begin), self.next_token_begin(x.span));
+            }
+            StmtP::Def(def) => {
+                self.def(def);
+                self.header_end(&def.body);
+                return self.block(&def.body, self.column(begin), self.next_token_begin(x.span));
+            }
+        }
+        let end = self.content_end(x.span);
+        self.last = end;
+        self.write_trailing_comment(end, usize::MAX);
+        self.end_line();
+    }
+
+    /// Finish the line of a statement which has a block `body`, with its `:` and comment.
+    fn header_end(&mut self, body: &AstStmt) {
+        let body_begin = offset(body.span.begin());
+        let colon = self.end_of_token_before(body_begin, &Token::Colon);
+        self.out.push(':');
+        self.last = colon;
+        self.write_trailing_comment(colon, body_begin);
+        self.end_line();
+    }
+
+    /// Write the block `body`, followed by the comments before `limit` which belong to it.
+    fn block(&mut self, body: &AstStmt, owner_column: usize, limit: usize) {
+        self.indent += 1;
+        self.block_start = true;
+        self.stmt(body);
+        self.write_comments_ending_block(limit, owner_column);
+        self.indent -= 1;
+    }
+
+    /// Write an `if` or `elif` whose keyword starts at `begin`, and any `elif` and `else`
+    /// branches after it. `span` is the whole `if` statement.
+    fn if_stmt(
+        &mut self,
+        keyword: &str,
+        begin: usize,
+        span: Span,
+        cond: &AstExpr,
+        then_body: &AstStmt,
+        else_body: Option<&AstStmt>,
+    ) {
+        self.out.push_str(keyword);
+        self.out.push(' ');
+        self.expr(cond, PREC_TEST);
+        self.header_end(then_body);
+        let else_body = match else_body {
+            None => return self.block(then_body, self.column(begin), self.next_token_begin(span)),
+            Some(else_body) => else_body,
+        };
+
+        let (else_begin, else_token) = match self.token_after(self.content_end(then_body.span)) {
+            Some((begin, token, _)) => (*begin, token.clone()),
+            None => unreachable!("`else` or `elif` must follow the `if` block"),
+        };
+        self.block(then_body, self.column(begin), else_begin);
+        self.write_comments_before(else_begin);
+        self.start_line(Some(else_begin));
+        match (else_token, &else_body.node) {
+            (Token::Elif, StmtP::If(cond, body)) => {
+                self.if_stmt("elif", else_begin, span, cond, body, None)
+            }
+            (Token::Elif, StmtP::IfElse(cond, bodies)) => {
+                self.if_stmt("elif", else_begin, span, cond, &bodies.0, Some(&bodies.1))
+            }
+            _ => {
+                self.out.push_str("else:");
+                let colon = self.end_of_token_after(else_begin, &Token::Colon);
+                self.last = colon;
+                self.write_trailing_comment(colon, offset(else_body.span.begin()));
+                self.end_line();
+                self.block(
+                    else_body,
+                    self.column(else_begin),
+                    self.next_token_begin(span),
+                );
+            }
+        }
+    }
+
+    /// Write the signature of a `def`, up to the `:`.
+    fn def(&mut self, def: &DefP<AstNoPayload>) {
+        self.out.push_str("def ");
+        self.out.push_str(&def.name.0);
+        let name_end = offset(def.name.span.end());
+        let params_begin = self
+            .token_after(name_end)
+            .map_or(name_end, |(begin, _, _)| *begin);
+        let params_end = match &def.return_type {
+            Some(return_type) => offset(return_type.span.begin()),
+            None => self.end_of_token_before(offset(def.body.span.begin()), &Token::Colon),
+        };
+        let params_end = self.end_of_token_before(params_end, &Token::ClosingRound);
+        self.bracketed(
+            ("(", ")"),
+            (params_begin, params_end),
+            &def.params,
+            |x| x.span,
+            |p, x| p.param(x),
+            false,
+        );
+        if let Some(return_type) = &def.return_type {
+            self.out.push_str(" -> ");
+            self.expr(return_type, PREC_TEST);
+        }
+    }
+
+    fn load(&mut self, span: Span, load: &Load) {
+        self.out.push_str("load");
+        let items: Vec<LoadItem> = iter::once(LoadItem::Module(&load.module))
+            .chain(
+                load.args
+                    .iter()
+                    .map(|(local, their)| LoadItem::Symbol(local, their)),
+            )
+            .collect();
+        self.bracketed(
+            ("(", ")"),
+            (offset(span.begin()), self.content_end(span)),
+            &items,
+            |x| match x {
+                LoadItem::Module(module) => module.span,
+                LoadItem::Symbol(local, their) => local.span.merge(their.span),
+            },
+            |p, x| match x {
+                LoadItem::Module(module) => p.string(module.span),
+                LoadItem::Symbol(local, their) => {
+                    if local.0 != their.node {
+                        p.out.push_str(&local.0);
+                        p.out.push_str(" = ");
+                    }
+                    p.string(their.span)
+                }
+            },
+            false,
+        );
+    }
+
+    /// Write a comma separated list of items between `open` and `close`, either on one line
+    /// or one item per line. `region` is the part of the source with the brackets.
+    fn bracketed<T>(
+        &mut self,
+        (open, close): (&str, &str),
+        region: (usize, usize),
+        items: &[T],
+        item_span: impl Fn(&T) -> Span,
+        write_item: impl Fn(&mut Self, &T),
+        is_tuple: bool,
+    ) {
+        let (begin, end) = region;
+        if !self.flat && (self.has_comments(begin, end) || self.breaks_line(begin, end)) {
+            return self.bracketed_split((open, close), region, items, item_span, write_item);
+        }
+        let column = self.out_column();
+        let flat = self.render_flat(|p| {
+            p.out.push_str(open);
+            for (i, item) in items.iter().enumerate() {
+                if i != 0 {
+                    p.out.push_str(", ");
+                }
+                write_item(p, item);
+            }
+            if is_tuple && items.len() == 1 {
+                p.out.push(',');
+            }
+            p.out.push_str(close);
+        });
+        if !self.flat && !items.is_empty() && column + first_line_width(&flat) > MAX_WIDTH {
+            self.bracketed_split((open, close), region, items, item_span, write_item);
+        } else {
+            self.out.push_str(&flat);
+        }
+    }
+
+    fn bracketed_split<T>(
+        &mut self,
+        (open, close): (&str, &str),
+        region: (usize, usize),
+        items: &[T],
+        item_span: impl Fn(&T) -> Span,
+        write_item: impl Fn(&mut Self, &T),
+    ) {
+        self.out.push_str(open);
+        self.end_line();
+        self.indent += 1;
+        self.block_start = true;
+        self.last = region.0;
+        for item in items {
+            self.split_line(item_span(item), region.1, |p| {
+                write_item(p, item);
+                p.out.push(',');
+            });
+        }
+        self.write_comments_before(region.1);
+        self.indent -= 1;
+        self.start_line(None);
+        self.out.push_str(close);
+    }
+
+    /// Write a string literal as it was written, except that simple single quoted strings
+    /// get double quotes.
+    fn string(&mut self, span: Span) {
+        let source = self.source;
+        let text = &source[offset(span.begin())..offset(span.end())];
+        match text.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+            Some(body) if !text.starts_with("'''") && !body.contains(|c| c == '"' || c == '\\') => {
+                self.out.push('"');
+                self.out.push_str(body);
+                self.out.push('"');
+            }
+            _ => self.out.push_str(text),
+        }
+    }
+
+    /// Write `x`, in parentheses if it binds less tightly than `prec`.
+    fn expr(&mut self, x: &AstExpr, prec: u8) {
+        let parens = expr_prec(x) < prec;
+        if parens {
+            self.out.push('(');
+        }
+        self.expr_inner(x);
+        if parens {
+            self.out.push(')');
+        }
+    }
+
+    fn exprs(&mut self, xs: &[AstExpr], prec: u8) {
+        for (i, x) in xs.iter().enumerate() {
+            if i != 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(x, prec);
+        }
+    }
+
+    fn expr_inner(&mut self, x: &AstExpr) {
+        match &x.node {
+            ExprP::Tuple(xs) => self.tuple(x.span, xs),
+            ExprP::Dot(object, field) => {
+                // `1.x` would be lexed as the float `1.` followed by `x`.
+                if let ExprP::Literal(AstLiteral::Int(_)) = &object.node {
+                    self.out.push('(');
+                    self.expr(object, PREC_TEST);
+                    self.out.push(')');
+                } else {
+                    self.expr(object, PREC_PRIMARY);
+                }
+                self.out.push('.');
+                self.out.push_str(&field.node);
+            }
+            ExprP::Call(function, args) => {
+                self.expr(function, PREC_PRIMARY);
+                self.bracketed(
+                    ("(", ")"),
+                    (offset(function.span.end()), offset(x.span.end())),
+                    args,
+                    |x| x.span,
+                    |p, x| p.argument(x),
+                    false,
+                );
+            }
+            ExprP::ArrayIndirection(array_index) => {
+                let (array, index) = &**array_index;
+                self.expr(array, PREC_PRIMARY);
+                self.out.push('[');
+                self.expr(index, PREC_TEST);
+                self.out.push(']');
+            }
+            ExprP::Slice(array, start, stop, stride) => {
+                self.expr(array, PREC_PRIMARY);
+                self.out.push('[');
+                if let Some(start) = start {
+                    self.expr(start, PREC_TEST);
+                }
+                self.out.push(':');
+                if let Some(stop) = stop {
+                    self.expr(stop, PREC_TEST);
+                }
+                if let Some(stride) = stride {
+                    self.out.push(':');
+                    self.expr(stride, PREC_TEST);
+                }
+                self.out.push(']');
+            }
+            ExprP::Identifier(name, _) => self.out.push_str(&name.node),
+            ExprP::Lambda(LambdaP { params, body, .. }) => {
+                self.out.push_str("lambda");
+                for (i, param) in params.iter().enumerate() {
+                    self.out.push_str(if i == 0 { " " } else { ", " });
+                    self.param(param);
+                }
+                self.out.push_str(": ");
+                self.expr(body, PREC_TEST);
+            }
+            ExprP::Literal(AstLiteral::String(_)) => self.string(x.span),
+            ExprP::Literal(AstLiteral::Int(_) | AstLiteral::Float(_) | AstLiteral::Bytes(_))
+            | ExprP::FString(_) => {
+                let source = self.source;
+                self.out
+                    .push_str(&source[offset(x.span.begin())..offset(x.span.end())]);
+            }
+            ExprP::Not(e) => {
+                self.out.push_str("not ");
+                self.expr(e, PREC_NOT);
+            }
+            ExprP::Minus(e) => {
+                self.out.push('-');
+                self.expr(e, PREC_UNARY);
+            }
+            ExprP::Plus(e) => {
+                self.out.push('+');
+                self.expr(e, PREC_UNARY);
+            }
+            ExprP::BitNot(e) => {
+                self.out.push('~');
+                self.expr(e, PREC_UNARY);
+            }
+            ExprP::Op(lhs, op, rhs) => {
+                let prec = bin_op_prec(*op);
+                // Comparisons do not chain, the others associate to the left.
+                let lhs_prec = if prec == PREC_COMPARE { prec + 1 } else { prec };
+                self.expr(lhs, lhs_prec);
+                self.out.push_str(&op.to_string());
+                self.expr(rhs, prec + 1);
+            }
+            ExprP::If(cond_then_else) => {
+                let (cond, then_value, else_value) = &**cond_then_else;
+                self.expr(then_value, PREC_OR);
+                self.out.push_str(" if ");
+                self.expr(cond, PREC_OR);
+                self.out.push_str(" else ");
+                self.expr(else_value, PREC_TEST);
+            }
+            ExprP::List(xs) => self.bracketed(
+                ("[", "]"),
+                (offset(x.span.begin()), offset(x.span.end())),
+                xs,
+                |x| x.span,
+                |p, x| p.expr(x, PREC_TEST),
+                false,
+            ),
+            ExprP::Dict(xs) => self.bracketed(
+                ("{", "}"),
+                (offset(x.span.begin()), offset(x.span.end())),
+                xs,
+                |(k, v)| k.span.merge(v.span),
+                |p, (k, v)| {
+                    p.expr(k, PREC_TEST);
+                    p.out.push_str(": ");
+                    p.expr(v, PREC_TEST);
+                },
+                false,
+            ),
+            ExprP::ListComprehension(e, first, clauses) => self.comprehension(
+                ("[", "]"),
+                (offset(x.span.begin()), offset(x.span.end())),
+                &[&**e],
+                first,
+                clauses,
+            ),
+            ExprP::DictComprehension(k_v, first, clauses) => {
+                let (k, v) = &**k_v;
+                self.comprehension(
+                    ("{", "}"),
+                    (offset(x.span.begin()), offset(x.span.end())),
+                    &[k, v],
+                    first,
+                    clauses,
+                )
+            }
+        }
+    }
+
+    /// Tuples keep their parentheses, or lack of them, as the grammar only allows them
+    /// without parentheses where they are unambiguous.
+    fn tuple(&mut self, span: Span, xs: &[AstExpr]) {
+        if xs.is_empty() {
+            return self.out.push_str("()");
+        }
+        // The span of a tuple in parentheses does not include them.
+        let open = self.token_before(offset(span.begin()));
+        let close = self.token_after(offset(span.end()));
+        match (open, close) {
+            (Some((begin, Token::OpeningRound, _)), Some((_, Token::ClosingRound, end))) => {
+                let region = (*begin, *end);
+                self.bracketed(
+                    ("(", ")"),
+                    region,
+                    xs,
+                    |x| x.span,
+                    |p, x| p.expr(x, PREC_TEST),
+                    true,
+                )
+            }
+            _ => {
+                self.exprs(xs, PREC_TEST);
+                if xs.len() == 1 {
+                    self.out.push(',');
+                }
+            }
+        }
+    }
+
+    /// Write a comprehension on one line, or with its result and each of its clauses on a line
+    /// of their own, when the source breaks it or one line would be too wide, like
+    /// [`Self::bracketed`]. `result` is the element, or the key and the value.
+    fn comprehension(
+        &mut self,
+        (open, close): (&str, &str),
+        region: (usize, usize),
+        result: &[&AstExpr],
+        first: &ForClause,
+        clauses: &[Clause],
+    ) {
+        let (begin, end) = region;
+        if self.flat || !(self.has_comments(begin, end) || self.breaks_line(begin, end)) {
+            let column = self.out_column();
+            let flat = self.render_flat(|p| {
+                p.out.push_str(open);
+                p.comprehension_result(result);
+                p.out.push(' ');
+                p.for_clause(first);
+                for clause in clauses {
+                    p.out.push(' ');
+                    p.clause(clause);
+                }
+                p.out.push_str(close);
+            });
+            if self.flat || column + first_line_width(&flat) <= MAX_WIDTH {
+                return self.out.push_str(&flat);
+            }
+        }
+
+        self.out.push_str(open);
+        self.end_line();
+        self.indent += 1;
+        self.block_start = true;
+        self.last = begin;
+        let result_span = result[0].span.merge(result[result.len() - 1].span);
+        self.split_line(result_span, end, |p| p.comprehension_result(result));
+        self.split_line(first.var.span.merge(first.over.span), end, |p| {
+            p.for_clause(first)
+        });
+        for clause in clauses {
+            let span = match clause {
+                ClauseP::For(x) => x.var.span.merge(x.over.span),
+                ClauseP::If(cond) => cond.span,
+            };
+            self.split_line(span, end, |p| p.clause(clause));
+        }
+        self.write_comments_before(end);
+        self.indent -= 1;
+        self.start_line(None);
+        self.out.push_str(close);
+    }
+
+    /// Write what `f` writes on a line of its own, with the comments before it and the comment
+    /// which follows it. `span` is its source, and `limit` is the end of the enclosing brackets.
+    fn split_line(&mut self, span: Span, limit: usize, f: impl FnOnce(&mut Self)) {
+        let (begin, end) = (offset(span.begin()), offset(span.end()));
+        self.write_comments_before(begin);
+        self.start_line(Some(begin));
+        f(self);
+        self.last = end;
+        self.write_trailing_comment(end, limit);
+        self.end_line();
+    }
+
+    fn comprehension_result(&mut self, result: &[&AstExpr]) {
+        for (i, x) in result.iter().enumerate() {
+            if i != 0 {
+                self.out.push_str(": ");
+            }
+            self.expr(x, PREC_TEST);
+        }
+    }
+
+    fn for_clause(&mut self, x: &ForClause) {
+        self.out.push_str("for ");
+        self.assign_target(&x.var, true);
+        self.out.push_str(" in ");
+        self.expr(&x.over, PREC_OR);
+    }
+
+    fn clause(&mut self, x: &Clause) {
+        match x {
+            ClauseP::For(x) => self.for_clause(x),
+            ClauseP::If(cond) => {
+                self.out.push_str("if ");
+                self.expr(cond, PREC_OR);
+            }
+        }
+    }
+
+    /// Write the target of an assignment or `for`. Only nested tuples need parentheses.
+    fn assign_target(&mut self, x: &AstAssign, top: bool) {
+        match &x.node {
+            AssignP::Tuple(xs) => {
+                let parens = !top || xs.is_empty();
+                if parens {
+                    self.out.push('(');
+                }
+                for (i, x) in xs.iter().enumerate() {
+                    if i != 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.assign_target(x, false);
+                }
+                if xs.len() == 1 {
+                    self.out.push(',');
+                }
+                if parens {
+                    self.out.push(')');
+                }
+            }
+            AssignP::ArrayIndirection(array_index) => {
+                let (array, index) = &**array_index;
+                self.expr(array, PREC_PRIMARY);
+                self.out.push('[');
+                self.expr(index, PREC_TEST);
+                self.out.push(']');
+            }
+            AssignP::Dot(object, field) => {
+                self.expr(object, PREC_PRIMARY);
+                self.out.push('.');
+                self.out.push_str(&field.node);
+            }
+            AssignP::Identifier(name) => self.out.push_str(&name.0),
+        }
+    }
+
+    fn argument(&mut self, x: &AstArgument) {
+        match &x.node {
+            ArgumentP::Positional(e) => self.expr(e, PREC_TEST),
+            ArgumentP::Named(name, e) => {
+                self.out.push_str(&name.node);
+                self.out.push_str(" = ");
+                self.expr(e, PREC_TEST);
+            }
+            ArgumentP::Args(e) => {
+                self.out.push('*');
+                self.expr(e, PREC_TEST);
+            }
+            ArgumentP::KwArgs(e) => {
+                self.out.push_str("**");
+                self.expr(e, PREC_TEST);
+            }
+        }
+    }
+
+    fn param(&mut self, x: &AstParameter) {
+        let (prefix, name, ty, default) = match &x.node {
+            ParameterP::Normal(name, ty) => ("", name, ty, None),
+            ParameterP::WithDefaultValue(name, ty, default) => ("", name, ty, Some(default)),
+            ParameterP::NoArgs => return self.out.push('*'),
+            ParameterP::Args(name, ty) => ("*", name, ty, None),
+            ParameterP::KwArgs(name, ty) => ("**", name, ty, None),
+        };
+        self.out.push_str(prefix);
+        self.out.push_str(&name.0);
+        if let Some(ty) = ty {
+            self.out.push_str(": ");
+            self.expr(ty, PREC_TEST);
+        }
+        if let Some(default) = default {
+            self.out.push_str(" = ");
+            self.expr(default, PREC_TEST);
+        }
+    }
+}
+
+impl AstModule {
+    /// Print the module as canonical Starlark source.
+    ///
+    /// Indentation, spacing, parentheses and line breaks are normalized, while comments,
+    /// single blank lines between statements, the quotes of strings with escapes and the
+    /// spelling of numbers are kept. Formatting the result again gives the same text.
+    pub fn format(&self) -> String {
+        let mut printer = Printer::new(self);
+        printer.stmt(&self.statement);
+        printer.write_comments_before(usize::MAX);
+        printer.out
+    }
+}
@@ -0,0 +1,203 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::assert;
+
+/// Check that `program` formats to `expected`, which is already formatted.
+fn check(program: &str, expected: &str) {
+    assert_eq!(expected, assert::parse_ast(program).format());
+    assert_eq!(expected, assert::parse_ast(expected).format());
+}
+
+#[test]
+fn test_format_empty() {
+    check("", "");
+    check("\n\n", "");
+    check("# Just a comment\n", "# Just a comment\n");
+}
+
+#[test]
+fn test_format_expressions() {
+    check(
+        r#"x=(1+2)*3
+y = (a and b) or not (c == d)
+z = -(1 + 2)
+w = a - (b - c)
+t = 1,
+u = (1,)
+v = f(lambda x:x+1, *args, **kwargs)
+s = 'single'
+r = 'it\'s'
+q = [x*2 for x in y if x]
+p = a if b else c
+o = {'k' : v}[k][1:-1]
+"#,
+        r#"x = (1 + 2) * 3
+y = a and b or not c == d
+z = -(1 + 2)
+w = a - (b - c)
+t = 1,
+u = (1,)
+v = f(lambda x: x + 1, *args, **kwargs)
+s = "single"
+r = 'it\'s'
+q = [x * 2 for x in y if x]
+p = a if b else c
+o = {"k": v}[k][1:-1]
+"#,
+    );
+}
+
+#[test]
+fn test_format_statements() {
+    check(
+        r#"def f(a,b=1,*args,c:int=2,**kwargs)->str:
+  for x, y in z: a += x; b -= y
+  while a > 0: a -= 1
+  if a: return
+  elif b: pass
+  else:
+    return a,b
+load('foo.star','x',y='y',z='w')
+"#,
+        r#"def f(a, b = 1, *args, c: int = 2, **kwargs) -> str:
+    for x, y in z:
+        a += x
+        b -= y
+    while a > 0:
+        a -= 1
+    if a:
+        return
+    elif b:
+        pass
+    else:
+        return a, b
+load("foo.star", "x", "y", z = "w")
+"#,
+    );
+}
+
+#[test]
+fn test_format_comments() {
+    check(
+        r#"# Header comment.
+
+load("foo.star", "a")  # Loads a.
+
+
+
+def f(x):  # Trailing on def.
+  # Leading in body.
+  if x:
+    return 1  # One.
+  elif x == 2:
+    return 2
+    # End of elif.
+  else:
+    pass
+
+  # End of f.
+
+# Before y.
+y = f(1)
+"#,
+        r#"# Header comment.
+
+load("foo.star", "a")  # Loads a.
+
+def f(x):  # Trailing on def.
+    # Leading in body.
+    if x:
+        return 1  # One.
+    elif x == 2:
+        return 2
+        # End of elif.
+    else:
+        pass
+
+    # End of f.
+
+# Before y.
+y = f(1)
+"#,
+    );
+}
+
+#[test]
+fn test_format_line_breaks() {
+    check(
+        r#"foo(name = "x", srcs = ["a.c",
+    "b.c"], deps = [])
+bar(
+  1,  # One.
+  # Two.
+  2
+)
+a_rather_long_function_name(argument_number_one = 1, argument_number_two = 2, argument_number_three = 3)
+"#,
+        r#"foo(
+    name = "x",
+    srcs = [
+        "a.c",
+        "b.c",
+    ],
+    deps = [],
+)
+bar(
+    1,  # One.
+    # Two.
+    2,
+)
+a_rather_long_function_name(
+    argument_number_one = 1,
+    argument_number_two = 2,
+    argument_number_three = 3,
+)
+"#,
+    );
+}
+
+#[test]
+fn test_format_comprehensions() {
+    check(
+        r#"short = [x for x in xs if x]
+names = [a_rather_long_function_name(target) for target in all_the_configured_targets if target.is_enabled]
+by_name = {target.name: target for target in all_the_configured_targets for dep in target.deps if dep.visible}
+kept = [x
+  for x in xs  # All of them.
+  if x]
+"#,
+        r#"short = [x for x in xs if x]
+names = [
+    a_rather_long_function_name(target)
+    for target in all_the_configured_targets
+    if target.is_enabled
+]
+by_name = {
+    target.name: target
+    for target in all_the_configured_targets
+    for dep in target.deps
+    if dep.visible
+}
+kept = [
+    x
+    for x in xs  # All of them.
+    if x
+]
+"#,
+    );
+}
@@ -151,6 +151,14 @@ fn test_ifelse() {
     );
 }
 
+#[test]
+fn test_while() {
+    assert_eq!(
+        assert::parse("def d():\n  while x > 0:\n    x -= 1\n    if x: break"),
+        "def d():\n  while (x > 0):\n    x -= 1\n    if x:\n      break\n"
+    );
+}
+
 #[test]
 fn test_kwargs_passing() {
     assert_eq!(
@@ -33,6 +33,7 @@ use crate::errors::Diagnostic;
 use crate::syntax::cursors::CursorBytes;
 use crate::syntax::cursors::CursorChars;
 use crate::syntax::dialect::Dialect;
+use crate::values::bytes::StarlarkBytes;
 
 #[derive(Error, Debug)]
 pub(crate) enum LexemeError {
@@ -269,6 +270,28 @@ impl<'a> Lexer<'a> {
         Ok(())
     }
 
+    // We have seen a '\' character in a bytes literal. The escapes are the same as for strings,
+    // except `\x` and octal escapes denote a single byte, rather than a character.
+    fn escape_bytes(it: &mut CursorChars, res: &mut Vec<u8>) -> Result<(), ()> {
+        match it.next() {
+            Some('x') => res.push(Self::escape_char(it, 2, 2, 16)? as u8),
+            Some(c @ '0'..='7') => {
+                it.unnext(c);
+                let c = Self::escape_char(it, 1, 3, 8)?;
+                res.push(u8::try_from(c as u32).map_err(|_| ())?);
+            }
+            c => {
+                if let Some(c) = c {
+                    it.unnext(c);
+                }
+                let mut s = String::new();
+                Self::escape(it, &mut s)?;
+                res.extend_from_slice(s.as_bytes());
+            }
+        }
+        Ok(())
+    }
+
     // String parsing is a hot-spot, so parameterise by a `stop` function which gets
     // specialised for each variant
     fn string(&mut self, triple: bool, raw: bool, mut stop: impl FnMut(char) -> bool) -> Lexeme {
@@ -378,6 +401,81 @@ impl<'a> Lexer<'a> {
         )
     }
 
+    // An f-string is lexed like a string, the replacement fields are parsed by the grammar.
+    fn fstring(&mut self, triple: bool, raw: bool, stop: impl FnMut(char) -> bool) -> Lexeme {
+        let content_start_offset = self.lexer.span().len() + if triple { 2 } else { 0 };
+        self.string(triple, raw, stop)
+            .map(|(l, token, r)| match token {
+                Token::String(content) => (
+                    l,
+                    Token::FString(TokenFString {
+                        content,
+                        content_start_offset,
+                    }),
+                    r,
+                ),
+                _ => unreachable!("string() only produces strings"),
+            })
+    }
+
+    // Bytes literals are rare, so unlike `string` there is no fast path.
+    fn bytes(&mut self, triple: bool, raw: bool, mut stop: impl FnMut(char) -> bool) -> Lexeme {
+        // Before the opening `b`
+        let string_start = self.lexer.span().start;
+        // After the first quote character
+        let mut string_end = self.lexer.span().end;
+
+        let mut it = CursorChars::new_offset(self.lexer.remainder(), if triple { 2 } else { 0 });
+        let mut res = Vec::new();
+        while let Some(c) = it.next() {
+            if stop(c) {
+                self.lexer.bump(it.pos());
+                if triple {
+                    res.truncate(res.len() - 2);
+                }
+                return Ok((string_start, Token::Bytes(res), string_end + it.pos()));
+            }
+            match c {
+                '\n' if !triple => {
+                    string_end -= 1;
+                    break;
+                }
+                '\r' => {}
+                '\\' => {
+                    if raw {
+                        match it.next() {
+                            Some(c) => {
+                                if c != '\'' && c != '"' {
+                                    res.push(b'\\');
+                                }
+                                res.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                            }
+                            _ => break,
+                        }
+                    } else {
+                        let pos = it.pos();
+                        if Self::escape_bytes(&mut it, &mut res).is_err() {
+                            return self.err_span(
+                                LexemeError::InvalidEscapeSequence(
+                                    self.lexer.remainder()[pos..it.pos()].to_owned(),
+                                ),
+                                string_end + pos - 1,
+                                string_end + it.pos(),
+                            );
+                        }
+                    }
+                }
+                c => res.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+
+        self.err_span(
+            LexemeError::UnfinishedStringLiteral,
+            string_start,
+            string_end + it.pos(),
+        )
+    }
+
     fn int(&self, s: &str, radix: u32) -> Lexeme {
         let span = self.lexer.span();
         match i32::from_str_radix(s, radix) {
@@ -488,6 +586,74 @@ impl<'a> Lexer<'a> {
                                 Some(self.string(false, raw, |c| c == '\''))
                             }
                         }
+                        Token::RawBytesDoubleQuote => {
+                            let raw = self.lexer.span().len() == 3;
+                            if self.lexer.remainder().starts_with("\"\"") {
+                                let mut qs = 0;
+                                Some(self.bytes(true, raw, |c| {
+                                    if c == '\"' {
+                                        qs += 1;
+                                        qs == 3
+                                    } else {
+                                        qs = 0;
+                                        false
+                                    }
+                                }))
+                            } else {
+                                Some(self.bytes(false, raw, |c| c == '\"'))
+                            }
+                        }
+                        Token::RawBytesSingleQuote => {
+                            let raw = self.lexer.span().len() == 3;
+                            if self.lexer.remainder().starts_with("''") {
+                                let mut qs = 0;
+                                Some(self.bytes(true, raw, |c| {
+                                    if c == '\'' {
+                                        qs += 1;
+                                        qs == 3
+                                    } else {
+                                        qs = 0;
+                                        false
+                                    }
+                                }))
+                            } else {
+                                Some(self.bytes(false, raw, |c| c == '\''))
+                            }
+                        }
+                        Token::RawFStringDoubleQuote => {
+                            let raw = self.lexer.span().len() == 3;
+                            if self.lexer.remainder().starts_with("\"\"") {
+                                let mut qs = 0;
+                                Some(self.fstring(true, raw, |c| {
+                                    if c == '\"' {
+                                        qs += 1;
+                                        qs == 3
+                                    } else {
+                                        qs = 0;
+                                        false
+                                    }
+                                }))
+                            } else {
+                                Some(self.fstring(false, raw, |c| c == '\"'))
+                            }
+                        }
+                        Token::RawFStringSingleQuote => {
+                            let raw = self.lexer.span().len() == 3;
+                            if self.lexer.remainder().starts_with("''") {
+                                let mut qs = 0;
+                                Some(self.fstring(true, raw, |c| {
+                                    if c == '\'' {
+                                        qs += 1;
+                                        qs == 3
+                                    } else {
+                                        qs = 0;
+                                        false
+                                    }
+                                }))
+                            } else {
+                                Some(self.fstring(false, raw, |c| c == '\''))
+                            }
+                        }
                         Token::OpeningCurly | Token::OpeningRound | Token::OpeningSquare => {
                             self.parens += 1;
                             self.wrap(token)
@@ -510,6 +676,15 @@ pub enum TokenInt {
     BigInt(BigInt),
 }
 
+/// An f-string literal, `f"..."`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenFString {
+    /// The contents of the literal, with escapes processed.
+    pub content: String,
+    /// Offset of the contents from the start of the token, the length of `f"` or `rf"""`.
+    pub content_start_offset: usize,
+}
+
 /// All token that can be generated by the lexer
 #[derive(Logos, Debug, Clone, PartialEq)]
 pub enum Token {
@@ -537,10 +712,20 @@ pub enum Token {
     #[token("\"")]
     #[token("r\"")]
     RawDoubleQuote,
-
-    #[regex(
-        "as|import|is|class|nonlocal|del|raise|except|try|finally|while|from|with|global|yield"
-    )]
+    #[token("b'")]
+    #[token("rb'")]
+    RawBytesSingleQuote,
+    #[token("b\"")]
+    #[token("rb\"")]
+    RawBytesDoubleQuote,
+    #[token("f'")]
+    #[token("rf'")]
+    RawFStringSingleQuote,
+    #[token("f\"")]
+    #[token("rf\"")]
+    RawFStringDoubleQuote,
+
+    #[regex("as|import|is|class|nonlocal|del|raise|except|try|finally|from|with|global|yield")]
     Reserved, // One of the reserved keywords
 
     #[regex(
@@ -564,7 +749,9 @@ pub enum Token {
     #[regex("\\.[0-9]+([eE][-+]?[0-9]+)?", |lex| lex.slice().parse::<f64>())]
     Float(f64), // A float literal (3.14, .3, 1e6, 0.)
 
-    String(String), // A string literal
+    String(String),        // A string literal
+    Bytes(Vec<u8>),        // A bytes literal
+    FString(TokenFString), // An f-string literal
 
     // Keywords
     #[token("and")]
@@ -597,6 +784,8 @@ pub enum Token {
     Return,
     #[token("lambda")]
     Lambda,
+    #[token("while")]
+    While,
     // Symbols
     #[token(",")]
     Comma,
@@ -687,6 +876,15 @@ pub enum Token {
 }
 
 impl Token {
+    /// Whether `name` can be used as a variable name, i.e. it is an identifier and not a keyword.
+    pub(crate) fn is_identifier(name: &str) -> bool {
+        let mut tokens = Token::lexer(name);
+        matches!(
+            (tokens.next(), tokens.next()),
+            (Some(Token::Identifier(_)), None)
+        )
+    }
+
     /// Used for testing
     pub(crate) fn unlex(&self) -> String {
         match self {
@@ -699,6 +897,8 @@ impl Token {
                 // Reuse the StarlarkValue implementation since it's close to hand.
                 serde_json::to_string(x).unwrap()
             }
+            Token::Bytes(x) => StarlarkBytes::new(x.as_slice()).to_string(),
+            Token::FString(x) => format!("f{}", serde_json::to_string(&x.content).unwrap()),
             _ => {
                 let s = self.to_string();
                 // Out display is often: keyword 'lambda'
@@ -737,6 +937,7 @@ impl Display for Token {
             Token::Elif => write!(f, "keyword 'elif'"),
             Token::Return => write!(f, "keyword 'return'"),
             Token::Lambda => write!(f, "keyword 'lambda'"),
+            Token::While => write!(f, "keyword 'while'"),
             Token::Comma => write!(f, "symbol ','"),
             Token::Semicolon => write!(f, "symbol ';'"),
             Token::Colon => write!(f, "symbol ':'"),
@@ -788,8 +989,14 @@ impl Display for Token {
             Token::RawBinInt => write!(f, "binary integer literal"),
             Token::Float(n) => write!(f, "float literal '{}'", n),
             Token::String(s) => write!(f, "string literal '{}'", s),
+            Token::Bytes(b) => write!(f, "bytes literal '{}'", String::from_utf8_lossy(b)),
             Token::RawSingleQuote => write!(f, "starting '"),
             Token::RawDoubleQuote => write!(f, "starting \""),
+            Token::RawBytesSingleQuote => write!(f, "starting b'"),
+            Token::RawBytesDoubleQuote => write!(f, "starting b\""),
+            Token::FString(s) => write!(f, "f-string literal '{}'", s.content),
+            Token::RawFStringSingleQuote => write!(f, "starting f'"),
+            Token::RawFStringDoubleQuote => write!(f, "starting f\""),
             Token::Tabs => Ok(()),
         }
     }
@@ -16,6 +16,7 @@
  */
 
 use crate::assert;
+use crate::syntax::lexer::Token;
 use crate::syntax::lexer::Token::*;
 
 #[test]
@@ -60,9 +61,9 @@ fn test_symbols() {
 fn test_keywords() {
     assert_eq!(
         assert::lex(
-            "and else load break for not not  in continue if or def in pass elif return lambda"
+            "and else load break for not not  in continue if or def in pass elif return lambda while"
         ),
-        "and else load break for not not in continue if or def in pass elif return lambda \n"
+        "and else load break for not not in continue if or def in pass elif return lambda while \n"
     );
 }
 
@@ -78,7 +79,7 @@ fn test_number_collated_with_keywords_or_identifier() {
 #[test]
 fn test_reserved() {
     let reserved =
-        "as import is class nonlocal del raise except try finally while from with global yield"
+        "as import is class nonlocal del raise except try finally from with global yield"
             .split_whitespace();
     for x in reserved {
         assert::parse_fail(&format!("!{}! = 1", x));
@@ -103,6 +104,17 @@ fn test_identifier() {
     )
 }
 
+#[test]
+fn test_is_identifier() {
+    assert!(Token::is_identifier("a"));
+    assert!(Token::is_identifier("_CAPS_0123"));
+    assert!(!Token::is_identifier(""));
+    assert!(!Token::is_identifier("0a"));
+    assert!(!Token::is_identifier("a b"));
+    assert!(!Token::is_identifier("def"));
+    assert!(!Token::is_identifier("class"));
+}
+
 #[test]
 fn test_string_lit() {
     assert_eq!(
@@ -144,6 +156,17 @@ fn test_string_escape() {
     assert::parse_fail("test 'more !\\x0!");
 }
 
+#[test]
+fn test_bytes_lit() {
+    assert_eq!(
+        assert::lex("b'abc' rb'\\n' b\"\\xff\\377\" b'\\'' b'''a\nb'''"),
+        "b\"abc\" b\"\\\\n\" b\"\\xff\\xff\" b\"'\" b\"a\\nb\" \n"
+    );
+    // Octal escapes in bytes must fit in a byte
+    assert::parse_fail("b'!\\400!'");
+    assert::parse_fail("!b'unfinished!\n'");
+}
+
 #[test]
 fn test_simple_example() {
     assert_eq!(
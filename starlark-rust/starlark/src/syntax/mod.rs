@@ -22,6 +22,15 @@ pub use dialect::Dialect;
 pub use dialect::DialectTypes;
 pub use parser::AstLoad;
 
+pub use crate::analysis::LoadedSymbol;
+pub use crate::analysis::ModuleGraph;
+pub use crate::analysis::ModuleGraphEdge;
+pub use crate::analysis::ModuleGraphLoader;
+pub use crate::analysis::ModuleGraphNode;
+pub use crate::analysis::ModuleGraphReport;
+
+#[cfg(test)]
+mod format_tests;
 #[cfg(test)]
 mod grammar_tests;
 #[cfg(test)]
@@ -32,6 +41,7 @@ mod testcases;
 pub(crate) mod ast;
 pub(crate) mod cursors;
 mod dialect;
+mod format;
 pub(crate) mod lexer;
 pub(crate) mod payload_map;
 pub(crate) mod validate;
@@ -185,6 +185,11 @@ impl AstModule {
         loads
     }
 
+    /// The source code of the module.
+    pub fn source(&self) -> &str {
+        self.codemap.source()
+    }
+
     /// Look up a [`Span`] contained in this module to a [`FileSpan`].
     pub(crate) fn file_span(&self, x: Span) -> FileSpan {
         self.codemap.file_span(x)
@@ -26,6 +26,7 @@ use crate::syntax::ast::AstPayload;
 use crate::syntax::ast::ClauseP;
 use crate::syntax::ast::DefP;
 use crate::syntax::ast::ExprP;
+use crate::syntax::ast::FStringP;
 use crate::syntax::ast::ForClauseP;
 use crate::syntax::ast::LambdaP;
 use crate::syntax::ast::LoadP;
@@ -99,6 +100,9 @@ impl<A: AstPayload> StmtP<A> {
                     Box::new((coll.into_map_payload(f), body.into_map_payload(f))),
                 )
             }
+            StmtP::While(cond, body) => {
+                StmtP::While(cond.into_map_payload(f), Box::new(body.into_map_payload(f)))
+            }
             StmtP::Def(DefP {
                 name,
                 params,
@@ -187,6 +191,16 @@ impl<A: AstPayload> ExprP<A> {
                     cs.into_map(|c| c.into_map_payload(f)),
                 )
             }
+            ExprP::FString(fstring) => {
+                let FStringP {
+                    format,
+                    expressions,
+                } = *fstring;
+                ExprP::FString(Box::new(FStringP {
+                    format,
+                    expressions: expressions.into_map(|e| e.into_map_payload(f)),
+                }))
+            }
         }
     }
 }
@@ -215,3 +215,24 @@ fn parsing_testcases() {
         assert::parse(content);
     }
 }
+
+#[test]
+fn formatting_testcases() {
+    for (name, content) in TESTCASE_FILES {
+        let ast = assert::parse_ast(content);
+        let formatted = ast.format();
+        let reparsed = assert::parse_ast(&formatted);
+        assert_eq!(
+            ast.statement.to_string(),
+            reparsed.statement.to_string(),
+            "Formatting changed the meaning of {}",
+            name
+        );
+        assert_eq!(
+            formatted,
+            reparsed.format(),
+            "Formatting {} is not idempotent",
+            name
+        );
+    }
+}
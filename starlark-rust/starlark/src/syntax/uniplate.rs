@@ -86,6 +86,10 @@ impl<P: AstPayload> StmtP<P> {
                 f(Visit::Expr(over));
                 f(Visit::Stmt(body));
             }
+            StmtP::While(condition, body) => {
+                f(Visit::Expr(condition));
+                f(Visit::Stmt(body));
+            }
             // Nothing else contains nested statements
             StmtP::Break => {}
             StmtP::Continue => {}
@@ -140,6 +144,10 @@ impl<P: AstPayload> StmtP<P> {
                 f(VisitMut::Expr(over));
                 f(VisitMut::Stmt(body));
             }
+            StmtP::While(condition, body) => {
+                f(VisitMut::Expr(condition));
+                f(VisitMut::Stmt(body));
+            }
             // Nothing else contains nested statements
             StmtP::Break => {}
             StmtP::Continue => {}
@@ -319,6 +327,7 @@ impl<P: AstPayload> ExprP<P> {
                 f(&x.0);
                 f(&x.1);
             }
+            ExprP::FString(fstring) => fstring.expressions.iter().for_each(|x| f(x)),
         }
     }
 
@@ -381,6 +390,7 @@ impl<P: AstPayload> ExprP<P> {
                 f(&mut x.0);
                 f(&mut x.1);
             }
+            ExprP::FString(fstring) => fstring.expressions.iter_mut().for_each(|x| f(x)),
         }
     }
 }
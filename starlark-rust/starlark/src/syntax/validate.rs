@@ -17,6 +17,7 @@
 
 //! AST for parsed starlark files.
 
+use std::cmp;
 use std::collections::HashSet;
 
 use thiserror::Error;
@@ -39,17 +40,22 @@ use crate::syntax::ast::AstStmt;
 use crate::syntax::ast::AstString;
 use crate::syntax::ast::DefP;
 use crate::syntax::ast::Expr;
+use crate::syntax::ast::FString;
 use crate::syntax::ast::LambdaP;
 use crate::syntax::ast::Parameter;
 use crate::syntax::ast::Stmt;
+use crate::syntax::ast::ToAst;
 use crate::syntax::dialect::DialectError;
+use crate::syntax::lexer::Token;
+use crate::syntax::lexer::TokenFString;
 use crate::syntax::Dialect;
+use crate::values::string::dot_format::FStringFormat;
 
 #[derive(Error, Debug)]
 enum ValidateError {
-    #[error("`break` cannot be used outside of a `for` loop")]
+    #[error("`break` cannot be used outside of a `for` or `while` loop")]
     BreakOutsideLoop,
-    #[error("`continue` cannot be used outside of a `for` loop")]
+    #[error("`continue` cannot be used outside of a `for` or `while` loop")]
     ContinueOutsideLoop,
     #[error("`return` cannot be used outside of a `def` function")]
     ReturnOutsideDef,
@@ -59,6 +65,8 @@ enum ValidateError {
     NoTopLevelIf,
     #[error("`for` cannot be used outside `def` in this dialect")]
     NoTopLevelFor,
+    #[error("`while` cannot be used outside `def` in this dialect")]
+    NoTopLevelWhile,
     #[error("left-hand-side of assignment must take the form `a`, `a.b` or `a[b]`")]
     InvalidLhs,
     #[error("left-hand-side of modifying assignment cannot be a list or tuple")]
@@ -67,6 +75,12 @@ enum ValidateError {
     TypeAnnotationOnAssignOp,
     #[error("type annotations not allowed on multiple assignments")]
     TypeAnnotationOnTupleAssign,
+    #[error("Unmatched '{{' in f-string")]
+    FStringUnmatchedOpen,
+    #[error("Standalone '}}' in f-string")]
+    FStringStandaloneClose,
+    #[error("Only identifiers can be used inside an f-string, got `{0}`")]
+    FStringNotIdentifier(String),
 }
 
 #[derive(Eq, PartialEq, PartialOrd, Ord)]
@@ -92,6 +106,72 @@ enum ArgumentDefinitionOrderError {
 }
 
 impl Expr {
+    /// Split an f-string into a format string and the identifiers in its replacement fields.
+    pub(crate) fn check_fstring(
+        fstring: Spanned<TokenFString>,
+        codemap: &CodeMap,
+    ) -> anyhow::Result<Expr> {
+        let Spanned {
+            node:
+                TokenFString {
+                    content,
+                    content_start_offset,
+                },
+            span,
+        } = fstring;
+        let err = |msg: ValidateError| Err(Diagnostic::new(msg, span, codemap));
+
+        let mut format = String::with_capacity(content.len());
+        let mut expressions = Vec::new();
+        let mut rem = content.as_str();
+        while let Some(i) = rem.find(['{', '}']) {
+            let (text, brace) = rem.split_at(i);
+            format.push_str(text);
+            if brace.starts_with("{{") || brace.starts_with("}}") {
+                format.push_str(&brace[..2]);
+                rem = &brace[2..];
+                continue;
+            }
+            if brace.starts_with('}') {
+                return err(ValidateError::FStringStandaloneClose);
+            }
+            let end = brace[1..].find(['{', '}']).map(|end| end + 1);
+            let Some(end) = end.filter(|&end| brace[end..].starts_with('}')) else {
+                return err(ValidateError::FStringUnmatchedOpen);
+            };
+            let field = &brace[1..end];
+            let name_len = field.find(['!', ':']).unwrap_or(field.len());
+            let (name, conv_spec) = field.split_at(name_len);
+            if !Token::is_identifier(name) {
+                return err(ValidateError::FStringNotIdentifier(name.to_owned()));
+            }
+            // Escapes make the contents shorter than the source, so the span is approximate.
+            let begin = span.begin().get() as usize
+                + content_start_offset
+                + (content.len() - brace.len())
+                + 1;
+            let begin = cmp::min(begin, span.end().get() as usize);
+            let name_end = cmp::min(begin + name.len(), span.end().get() as usize);
+            expressions.push(
+                Expr::Identifier(name.to_owned().ast(begin, name_end), ()).ast(begin, name_end),
+            );
+            format.push('{');
+            format.push_str(conv_spec);
+            format.push('}');
+            rem = &brace[end + 1..];
+        }
+        format.push_str(rem);
+
+        // Check the conversions and format specs now, rather than when compiling.
+        if let Err(e) = FStringFormat::parse(&format) {
+            return Err(Diagnostic::new(e, span, codemap));
+        }
+        Ok(Expr::FString(Box::new(FString {
+            format: Spanned { node: format, span },
+            expressions,
+        })))
+    }
+
     /// We want to check a function call is well-formed.
     /// Our eventual plan is to follow the Python invariants, but for now, we are closer
     /// to the Starlark invariants.
@@ -338,10 +418,10 @@ impl Stmt {
         stmt: &AstStmt,
         dialect: &Dialect,
     ) -> anyhow::Result<()> {
-        // Inside a for, we allow continue/break, unless we go beneath a def.
+        // Inside a for or while, we allow continue/break, unless we go beneath a def.
         // Inside a def, we allow return.
         // All load's must occur at the top-level.
-        // At the top-level we only allow for/if when the dialect permits it.
+        // At the top-level we only allow for/while/if when the dialect permits it.
         fn f(
             codemap: &CodeMap,
             dialect: &Dialect,
@@ -362,6 +442,13 @@ impl Stmt {
                         f(codemap, dialect, body, false, true, inside_def)
                     }
                 }
+                Stmt::While(_, body) => {
+                    if top_level && !dialect.enable_top_level_stmt {
+                        err(ValidateError::NoTopLevelWhile.into())
+                    } else {
+                        f(codemap, dialect, body, false, true, inside_def)
+                    }
+                }
                 Stmt::If(..) | Stmt::IfElse(..) => {
                     if top_level && !dialect.enable_top_level_stmt {
                         err(ValidateError::NoTopLevelIf.into())
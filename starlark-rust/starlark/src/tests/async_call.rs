@@ -0,0 +1,276 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::future::Future;
+use std::pin::pin;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Wake;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use starlark_derive::starlark_module;
+
+use crate as starlark;
+use crate::assert::Assert;
+use crate::environment::Globals;
+use crate::environment::GlobalsBuilder;
+use crate::eval::AsyncCall;
+use crate::eval::AsyncCallPool;
+use crate::eval::Evaluator;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+use crate::values::OwnedFrozenValue;
+
+/// Future which is pending the first time it is polled.
+struct YieldOnce<T>(Option<T>, bool);
+
+impl<T: Unpin> Future for YieldOnce<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        if self.1 {
+            Poll::Ready(self.0.take().unwrap())
+        } else {
+            self.1 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Arc::new(NoopWaker).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(x) = future.as_mut().poll(&mut cx) {
+            return x;
+        }
+    }
+}
+
+/// Number of calls of `count`, only used by `test_no_replay`.
+static COUNT_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of calls of `spin`, only used by `test_drop_cancels`.
+static SPIN_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set by `wait_forever`, only used by `test_drop_while_awaiting`.
+static WAITING: AtomicBool = AtomicBool::new(false);
+
+#[starlark_module]
+fn fetch_globals(builder: &mut GlobalsBuilder) {
+    fn fetch(key: &str, eval: &mut Evaluator) -> anyhow::Result<String> {
+        let key = key.to_owned();
+        eval.await_future(|| YieldOnce(Some(format!("value of {}", key)), false))
+    }
+
+    fn count() -> anyhow::Result<i32> {
+        Ok(COUNT_CALLS.fetch_add(1, Ordering::SeqCst) as i32)
+    }
+
+    fn spin() -> anyhow::Result<i32> {
+        Ok(SPIN_CALLS.fetch_add(1, Ordering::SeqCst) as i32)
+    }
+
+    fn wait_forever(eval: &mut Evaluator) -> anyhow::Result<i32> {
+        WAITING.store(true, Ordering::SeqCst);
+        eval.await_future(std::future::pending::<i32>)
+    }
+}
+
+fn function(code: &str) -> OwnedFrozenValue {
+    let mut a = Assert::new();
+    a.globals_add(fetch_globals);
+    a.pass_module(code).get("f").unwrap()
+}
+
+#[test]
+fn test_async_call() {
+    let f = function(
+        r#"
+def f(x, y = "b"):
+    return [fetch(x), fetch(y)]
+"#,
+    );
+    let mut call = AsyncCall::new(
+        f,
+        vec![OwnedFrozenValue::alloc("a")],
+        vec![("y".to_owned(), OwnedFrozenValue::alloc("c"))],
+    );
+    call.setup_eval(|eval| eval.set_max_instructions(1000));
+    let res = block_on(async move {
+        let res = call.call().await.unwrap();
+        res.value().to_repr()
+    });
+    assert_eq!(r#"["value of a", "value of c"]"#, res);
+}
+
+#[test]
+fn test_no_replay() {
+    // Each `fetch` continues the evaluation where it stopped, so `count` runs once per iteration.
+    let f = function(
+        r#"
+def f():
+    res = []
+    for i in range(100):
+        res.append((count(), fetch(str(i))))
+    return res[0], res[99], count()
+"#,
+    );
+    let res = block_on(AsyncCall::new(f, Vec::new(), Vec::new()).call()).unwrap();
+    assert_eq!(
+        r#"((0, "value of 0"), (99, "value of 99"), 100)"#,
+        res.value().to_repr()
+    );
+}
+
+#[test]
+fn test_drop_cancels() {
+    let f = function(
+        r#"
+def f():
+    for i in range(1000000000):
+        spin()
+"#,
+    );
+    let waker = Arc::new(NoopWaker).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(AsyncCall::new(f, Vec::new(), Vec::new()).call());
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    while SPIN_CALLS.load(Ordering::SeqCst) == 0 {
+        thread::yield_now();
+    }
+    drop(future);
+
+    // The evaluation thread stops, even though it never awaits a future.
+    let deadline = Instant::now() + Duration::from_secs(60);
+    loop {
+        let before = SPIN_CALLS.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(100));
+        if SPIN_CALLS.load(Ordering::SeqCst) == before {
+            break;
+        }
+        assert!(Instant::now() < deadline, "evaluation was not cancelled");
+    }
+}
+
+#[test]
+fn test_drop_while_awaiting() {
+    let f = function(
+        r#"
+def f():
+    return wait_forever()
+"#,
+    );
+    let pool = AsyncCallPool::new(1);
+    let waker = Arc::new(NoopWaker).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(AsyncCall::new(f, Vec::new(), Vec::new()).call_in(&pool));
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    while !WAITING.load(Ordering::SeqCst) {
+        thread::yield_now();
+    }
+    // The future is dropped before it was given the request to await.
+    drop(future);
+
+    // The only thread of the pool is free again.
+    assert_eq!(1, block_on(pool.run(|_| Ok(1))).unwrap());
+}
+
+#[test]
+fn test_pool_is_bounded() {
+    let pool = AsyncCallPool::new(2);
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+    let callers: Vec<_> = (0..6)
+        .map(|_| {
+            let pool = pool.clone();
+            let running = running.clone();
+            let max_running = max_running.clone();
+            thread::spawn(move || {
+                block_on(pool.run(move |_| {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(50));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                }))
+            })
+        })
+        .collect();
+    for caller in callers {
+        caller.join().unwrap().unwrap();
+    }
+    assert_eq!(2, max_running.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_async_call_new_with() {
+    // The function and its arguments are created by the evaluation thread, so they are not frozen.
+    let call = AsyncCall::new_with(|eval| {
+        let mut globals = GlobalsBuilder::extended();
+        fetch_globals(&mut globals);
+        let globals: Globals = globals.build();
+        let ast = AstModule::parse(
+            "f.bzl",
+            "def f(xs):\n    return [fetch(x) for x in xs]".to_owned(),
+            &Dialect::Extended,
+        )?;
+        eval.eval_module(ast, &globals)?;
+        let f = eval.module().get("f").unwrap();
+        let xs = eval.heap().alloc(vec!["a", "b"]);
+        eval.eval_function(f, &[xs], &[])
+    });
+    let res = block_on(call.call()).unwrap();
+    assert_eq!(r#"["value of a", "value of b"]"#, res.value().to_repr());
+}
+
+#[test]
+fn test_async_call_error() {
+    let f = function(
+        r#"
+def f():
+    return fetch("a") + 1
+"#,
+    );
+    let err = block_on(AsyncCall::new(f, Vec::new(), Vec::new()).call()).unwrap_err();
+    assert!(err.to_string().contains("not supported"), "{}", err);
+}
+
+#[test]
+fn test_await_future_outside_async_call() {
+    let mut a = Assert::new();
+    a.globals_add(fetch_globals);
+    a.fail(
+        "fetch('a')",
+        "`await_future` can only be used in evaluations run by `AsyncCallPool`",
+    );
+}
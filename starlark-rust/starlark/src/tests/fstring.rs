@@ -0,0 +1,99 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::assert;
+use crate::assert::Assert;
+use crate::syntax::Dialect;
+
+#[test]
+fn test_fstring() {
+    assert::pass(
+        r#"
+x = 1
+name = "world"
+assert_eq(f"a{x}b", "a1b")
+assert_eq(f"hello {name}!", "hello world!")
+assert_eq(f"{x}{name}", "1world")
+assert_eq(f"{{x}}", "{x}")
+assert_eq(f"", "")
+assert_eq(f'{name}', "world")
+assert_eq(f"""{x}
+{name}""", "1\nworld")
+assert_eq(rf"\n{x}", "\\n1")
+"#,
+    );
+}
+
+#[test]
+fn test_fstring_conversion_and_spec() {
+    assert::pass(
+        r#"
+s = "x"
+n = 42
+f = 3.14159
+assert_eq(f"{s!r}", '"x"')
+assert_eq(f"{n:5}", "   42")
+assert_eq(f"{n:<5}|", "42   |")
+assert_eq(f"{n:x}", "2a")
+assert_eq(f"{n:#06b}", "0b101010")
+assert_eq(f"{f:.2f}", "3.14")
+assert_eq(f"{s:*^5}", "**x**")
+assert_eq(f"{s!r:>5}", '  "x"')
+"#,
+    );
+}
+
+#[test]
+fn test_fstring_spec_too_large() {
+    assert::fail(
+        "n = 1\nf'{n:10000000000}'",
+        "Width or precision 10000000000 is larger than the maximum",
+    );
+    assert::fail(
+        "f = 1.0\nf'{f:.1000000000f}'",
+        "Width or precision 1000000000 is larger than the maximum",
+    );
+}
+
+#[test]
+fn test_fstring_in_def() {
+    assert::pass(
+        r#"
+def greet(who, n):
+    return f"{who} x{n:03}"
+
+assert_eq(greet("bob", 7), "bob x007")
+assert_eq([f"<{x}>" for x in [1, 2]], ["<1>", "<2>"])
+"#,
+    );
+}
+
+#[test]
+fn test_fstring_errors() {
+    assert::fail(
+        "x = 1\nf'{x + 1}'",
+        "Only identifiers can be used inside an f-string",
+    );
+    assert::fail("x = 1\nf'{x'", "Unmatched '{'");
+    assert::fail("x = 1\nf'x}'", "Standalone '}'");
+    assert::fail("f'{undefined}'", "Variable `undefined` not found");
+    assert::fail("x = 's'\nf'{x:d}'", "Unknown format code");
+
+    let mut a = Assert::new();
+    a.dialect(&Dialect::Standard);
+    a.fail("x = 1\nf'{x}'", "f-strings are not allowed in this dialect");
+}
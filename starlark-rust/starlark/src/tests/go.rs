@@ -56,8 +56,7 @@ fn test_go() {
         test_case!("builtin.star"),
         &[
             "[] not in {123: \"\"}", // We disagree, see test_not_in_unhashable
-            // Set, unsupported
-            "set(",
+            // We have more set methods than Go
             "(myset)",
             "(myset,",
             // Has fields, unsupported
@@ -117,6 +116,8 @@ fn test_go() {
             "frozen list",        // Our freeze does nothing
             "called recursively", // We allow recursion
             "hf",                 // We don't support hasfield
+            // We evaluate `"".count` once at compile time, so the set has a single element
+            "(closures)",
         ],
     ));
     // Skip int.star, a lot of bit mask stuff, floats and int's outside our range
@@ -134,8 +135,16 @@ fn test_go() {
     );
     // Skip module.star, we don't support modules
     // Skip paths.star, a path support library, not tests
-    // Skip recursion.star, we don't support `while` loops, which is what this mostly tests
-    // Skip set.star, we don't support set
+    // Skip recursion.star, it mostly tests `while` loops, which are covered in `while_loop.rs`
+    assert.conformance(&ignore_bad_lines(
+        test_case!("set.star"),
+        &[
+            "x < y", // Our sets compare by inclusion, like Python
+            // Our assert.true doesn't take a message
+            "assert.true(type([elem",
+            "assert.true(list([elem",
+        ],
+    ));
     // Skip string.star, our String's are fundamentally different
     assert.conformance(&ignore_bad_lines(
         test_case!("tuple.star"),
@@ -15,6 +15,7 @@
  * limitations under the License.
  */
 
+mod async_call;
 mod basic;
 mod bc;
 mod before_stmt;
@@ -25,9 +26,11 @@ mod derive;
 mod docs;
 mod for_loop;
 mod freeze_access_value;
+mod fstring;
 mod go;
 mod interop;
 mod opt;
 mod runtime;
 mod type_annot;
 mod uncategorized;
+mod while_loop;
@@ -0,0 +1,188 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::assert;
+use crate::assert::Assert;
+use crate::syntax::Dialect;
+
+#[test]
+fn test_while() {
+    assert::pass(
+        r#"
+def count(n):
+    i = 0
+    xs = []
+    while i < n:
+        xs.append(i)
+        i += 1
+    return xs
+
+assert_eq(count(3), [0, 1, 2])
+assert_eq(count(0), [])
+"#,
+    );
+}
+
+#[test]
+fn test_while_break_continue() {
+    assert::pass(
+        r#"
+def odd_until(n):
+    i = 0
+    xs = []
+    while True:
+        i += 1
+        if i > n:
+            break
+        if i % 2 == 0:
+            continue
+        xs.append(i)
+    return xs
+
+assert_eq(odd_until(6), [1, 3, 5])
+"#,
+    );
+}
+
+#[test]
+fn test_while_nested_in_for() {
+    assert::pass(
+        r#"
+def f(xs):
+    res = []
+    for x in xs:
+        while x > 0:
+            if x == 2:
+                break
+            res.append(x)
+            x -= 1
+        if x == 5:
+            continue
+        res.append("end")
+    return res
+
+assert_eq(f([3, 1]), [3, "end", 1, "end"])
+
+def first_big(xss):
+    for xs in xss:
+        i = 0
+        while i < len(xs):
+            if xs[i] > 10:
+                # Must stop the iteration of the outer `for` loop.
+                return xs[i]
+            i += 1
+    return None
+
+l = [[1, 2], [3, 30]]
+assert_eq(first_big(l), 30)
+l.append([])
+"#,
+    );
+}
+
+#[test]
+fn test_for_nested_in_while() {
+    assert::pass(
+        r#"
+def f():
+    res = []
+    n = 0
+    while n < 3:
+        n += 1
+        for x in range(10):
+            if x == n:
+                break
+            res.append(x)
+    return res
+
+assert_eq(f(), [0, 0, 1, 0, 1, 2])
+"#,
+    );
+}
+
+#[test]
+fn test_while_top_level() {
+    assert::pass(
+        r#"
+x = []
+while len(x) < 1000:
+    x.append(str(len(x)))
+assert_eq(len(x), 1000)
+"#,
+    );
+}
+
+#[test]
+fn test_while_dialect() {
+    let mut a = Assert::new();
+    a.dialect(&Dialect::Standard);
+    a.fail(
+        "def f():\n  while True:\n    pass",
+        "`while` is not allowed in this dialect",
+    );
+
+    let mut a = Assert::new();
+    a.dialect_set(|d| d.enable_top_level_stmt = false);
+    a.fail(
+        "while False:\n  pass",
+        "`while` cannot be used outside `def`",
+    );
+}
+
+#[test]
+fn test_break_outside_while() {
+    assert::fail(
+        "def f():\n  while True:\n    pass\n  break",
+        "`break` cannot be used outside of a `for` or `while` loop",
+    );
+    assert::fail(
+        "def f():\n  while True:\n    def g():\n      continue",
+        "`continue` cannot be used outside of a `for` or `while` loop",
+    );
+}
+
+#[test]
+fn test_while_instruction_limit() {
+    let mut a = Assert::new();
+    a.setup_eval(|eval| eval.set_max_instructions(10_000));
+    a.fail(
+        "def f():\n  while True:\n    pass\nf()",
+        "exceeded the limit of 10000 instructions",
+    );
+}
+
+#[test]
+fn test_max_callstack_size() {
+    let program = r#"
+def depth(n):
+    if n == 0:
+        return 0
+    return 1 + depth(n - 1)
+"#;
+    assert::fail(
+        &format!("{}depth(70)", program),
+        "Starlark call stack overflow",
+    );
+
+    let mut a = Assert::new();
+    a.setup_eval(|eval| eval.set_max_callstack_size(100));
+    a.pass(&format!("{}assert_eq(depth(70), 70)", program));
+    a.fail(
+        &format!("{}depth(100)", program),
+        "Starlark call stack overflow",
+    );
+}
@@ -15,7 +15,10 @@
  * limitations under the License.
  */
 
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::iter;
 use std::sync::Arc;
 
 use dupe::Dupe;
@@ -28,8 +31,11 @@ use crate::eval::compiler::scope::CstExpr;
 use crate::eval::compiler::scope::CstPayload;
 use crate::eval::compiler::scope::CstStmt;
 use crate::eval::compiler::scope::ResolvedIdent;
+use crate::syntax::ast::ArgumentP;
 use crate::syntax::ast::AssignOp;
 use crate::syntax::ast::AssignP;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::BinOp;
 use crate::syntax::ast::ClauseP;
 use crate::syntax::ast::DefP;
 use crate::syntax::ast::ExprP;
@@ -68,6 +74,15 @@ impl<'a> BindExpr<'a> {
     }
 }
 
+/// Something we know about the type of a variable, because of a condition that was checked.
+#[derive(Clone, Debug)]
+pub(crate) enum Narrow {
+    /// The variable has this type, e.g. after `type(x) == "string"`.
+    Is(Ty),
+    /// The variable doesn't have this type, e.g. after `x != None`.
+    IsNot(Ty),
+}
+
 #[derive(Default)]
 pub(crate) struct Bindings<'a> {
     pub(crate) expressions: HashMap<BindingId, Vec<BindExpr<'a>>>,
@@ -76,6 +91,10 @@ pub(crate) struct Bindings<'a> {
     pub(crate) check: Vec<&'a CstExpr>,
     pub(crate) check_type: Vec<(Span, Option<&'a CstExpr>, Ty)>,
     pub(crate) approximations: Vec<Approximation>,
+    /// For identifiers which occur after a condition, what we know about them.
+    pub(crate) narrowings: HashMap<Span, Vec<Narrow>>,
+    /// The name and span of every function definition.
+    pub(crate) functions: Vec<(String, Span)>,
 }
 
 /// Interface representing the types of all bindings in a module.
@@ -101,6 +120,137 @@ impl Interface {
 
 pub type Loads = HashMap<String, Interface>;
 
+/// Does control flow never reach the end of this statement.
+fn terminates(x: &CstStmt) -> bool {
+    match &**x {
+        StmtP::Return(_) | StmtP::Break | StmtP::Continue => true,
+        StmtP::Statements(xs) => xs.last().map_or(false, terminates),
+        StmtP::IfElse(_, then_else) => terminates(&then_else.0) && terminates(&then_else.1),
+        StmtP::Expression(x) => match &**x {
+            ExprP::Call(f, _) => matches!(&***f, ExprP::Identifier(name, _) if &**name == "fail"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// What we learn about variables if `cond` evaluates to `positive`.
+fn narrowings(
+    cond: &CstExpr,
+    positive: bool,
+    approximations: &mut Vec<Approximation>,
+    res: &mut Vec<(BindingId, Narrow)>,
+) {
+    fn ident(x: &CstExpr) -> Option<BindingId> {
+        match &**x {
+            ExprP::Identifier(_, Some(ResolvedIdent::Slot((_, id)))) => Some(*id),
+            _ => None,
+        }
+    }
+
+    // Match `type(x)`
+    fn type_of(x: &CstExpr) -> Option<BindingId> {
+        match &**x {
+            ExprP::Call(f, args) if args.len() == 1 => match (&***f, &*args[0]) {
+                (ExprP::Identifier(name, _), ArgumentP::Positional(x)) if &**name == "type" => {
+                    ident(x)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    let narrow = |is: bool, ty: Ty| {
+        if is {
+            Narrow::Is(ty)
+        } else {
+            Narrow::IsNot(ty)
+        }
+    };
+
+    match &**cond {
+        ExprP::Not(x) => narrowings(x, !positive, approximations, res),
+        ExprP::Op(a, BinOp::And, b) if positive => {
+            narrowings(a, true, approximations, res);
+            narrowings(b, true, approximations, res);
+        }
+        ExprP::Op(a, BinOp::Or, b) if !positive => {
+            narrowings(a, false, approximations, res);
+            narrowings(b, false, approximations, res);
+        }
+        ExprP::Op(a, op @ (BinOp::Equal | BinOp::NotEqual), b) => {
+            let is = (*op == BinOp::Equal) == positive;
+            for (x, y) in [(a, b), (b, a)] {
+                if let (Some(id), ExprP::Identifier(name, _)) = (ident(x), &***y) {
+                    if &**name == "None" {
+                        res.push((id, narrow(is, Ty::None)));
+                    }
+                }
+                if let (Some(id), ExprP::Literal(AstLiteral::String(name))) = (type_of(x), &***y) {
+                    res.push((id, narrow(is, Ty::name(name.as_str()))));
+                }
+            }
+        }
+        ExprP::Call(f, args) if args.len() == 2 => {
+            if let (
+                ExprP::Identifier(name, _),
+                ArgumentP::Positional(x),
+                ArgumentP::Positional(ty),
+            ) = (&***f, &*args[0], &*args[1])
+            {
+                if &**name == "isinstance" {
+                    if let Some(id) = ident(x) {
+                        res.push((id, narrow(positive, Ty::from_expr(ty, approximations))));
+                    }
+                }
+            }
+        }
+        // A truthy value can't be `None`, but a falsy one might be anything
+        ExprP::Identifier(..) if positive => {
+            if let Some(id) = ident(cond) {
+                res.push((id, Narrow::IsNot(Ty::None)));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Add what we learn from `cond` being `positive` to `narrow`,
+/// dropping anything about variables that are assigned in `scope`.
+fn extend_narrow<'a>(
+    narrow: &[(BindingId, Narrow)],
+    cond: &CstExpr,
+    positive: bool,
+    scope: impl IntoIterator<Item = &'a CstStmt>,
+    bindings: &mut Bindings,
+) -> Vec<(BindingId, Narrow)> {
+    fn assigned(x: &CstStmt, res: &mut HashSet<BindingId>) {
+        match &**x {
+            StmtP::Assign(lhs, _) | StmtP::AssignModify(lhs, _, _) | StmtP::For(lhs, _) => lhs
+                .visit_lvalue(|x| {
+                    res.insert(x.1.unwrap());
+                }),
+            StmtP::Def(DefP { name, .. }) => {
+                res.insert(name.1.unwrap());
+            }
+            _ => {}
+        }
+        x.visit_stmt(|x| assigned(x, res));
+    }
+
+    let mut res = narrow.to_vec();
+    narrowings(cond, positive, &mut bindings.approximations, &mut res);
+    let mut ids = HashSet::new();
+    for x in scope {
+        assigned(x, &mut ids);
+    }
+    if !ids.is_empty() {
+        res.retain(|(id, _)| !ids.contains(id));
+    }
+    res
+}
+
 impl<'a> Bindings<'a> {
     /// Collect all the assignments to variables
     pub(crate) fn collect(x: &'a CstStmt, loads: &'_ Loads) -> Self {
@@ -146,6 +296,7 @@ impl<'a> Bindings<'a> {
         fn visit<'a>(
             x: Visit<'a, CstPayload>,
             return_type: &Ty,
+            narrow: &[(BindingId, Narrow)],
             loads: &Loads,
             bindings: &mut Bindings<'a>,
         ) {
@@ -160,7 +311,9 @@ impl<'a> Bindings<'a> {
                             if let AssignP::Identifier(id) = &**lhs {
                                 // FIXME: This could be duplicated if you declare the type of a variable twice,
                                 // we would only see the second one.
-                                bindings.types.insert(id.1.unwrap(), ty2);
+                                bindings
+                                    .types
+                                    .insert(id.1.unwrap(), ty2.instantiate(&BTreeMap::new()));
                             }
                         }
                         assign(lhs, BindExpr::Expr(&ty_rhs.1), bindings)
@@ -223,7 +376,10 @@ impl<'a> Bindings<'a> {
                                 }
                             };
                             if let Some((name, ty)) = name_ty {
-                                bindings.types.insert(name.1.unwrap(), ty);
+                                // Within the function, type variables could be anything
+                                bindings
+                                    .types
+                                    .insert(name.1.unwrap(), ty.instantiate(&BTreeMap::new()));
                                 bindings.descriptions.insert(name.1.unwrap(), name);
                             }
                         }
@@ -231,7 +387,9 @@ impl<'a> Bindings<'a> {
                         bindings
                             .types
                             .insert(name.1.unwrap(), Ty::function(params2, ret_ty.clone()));
-                        x.visit_children(|x| visit(x, &ret_ty, loads, bindings));
+                        bindings.functions.push((name.0.clone(), x.span));
+                        // What we learnt outside doesn't hold by the time the function is called
+                        x.visit_children(|x| visit(x, &ret_ty, &[], loads, bindings));
                         // We do our own visit_children, with a different return type
                         return;
                     }
@@ -278,11 +436,82 @@ impl<'a> Bindings<'a> {
 
                         bindings.check.push(x)
                     }
-                    StmtP::If(x, _) => bindings.check.push(x),
-                    StmtP::IfElse(x, _) => bindings.check.push(x),
+                    StmtP::Statements(xs) => {
+                        let mut narrow = narrow.to_vec();
+                        for (i, x) in xs.iter().enumerate() {
+                            visit(Visit::Stmt(x), return_type, &narrow, loads, bindings);
+                            // After `if x == None: return` we know `x` isn't `None` for the rest of the block
+                            let learnt = match &**x {
+                                StmtP::If(cond, body) if terminates(body) => Some((cond, false)),
+                                StmtP::IfElse(cond, then_else) => {
+                                    match (terminates(&then_else.0), terminates(&then_else.1)) {
+                                        (true, false) => Some((cond, false)),
+                                        (false, true) => Some((cond, true)),
+                                        _ => None,
+                                    }
+                                }
+                                _ => None,
+                            };
+                            if let Some((cond, positive)) = learnt {
+                                narrow = extend_narrow(&narrow, cond, positive, &xs[i..], bindings);
+                            }
+                        }
+                        return;
+                    }
+                    StmtP::If(cond, body) => {
+                        bindings.check.push(cond);
+                        visit(Visit::Expr(cond), return_type, narrow, loads, bindings);
+                        let narrow = extend_narrow(narrow, cond, true, [&**body], bindings);
+                        visit(Visit::Stmt(body), return_type, &narrow, loads, bindings);
+                        return;
+                    }
+                    StmtP::IfElse(cond, then_else) => {
+                        bindings.check.push(cond);
+                        visit(Visit::Expr(cond), return_type, narrow, loads, bindings);
+                        for (body, positive) in [(&then_else.0, true), (&then_else.1, false)] {
+                            let narrow = extend_narrow(narrow, cond, positive, [body], bindings);
+                            visit(Visit::Stmt(body), return_type, &narrow, loads, bindings);
+                        }
+                        return;
+                    }
+                    StmtP::While(cond, body) => {
+                        bindings.check.push(cond);
+                        visit(Visit::Expr(cond), return_type, narrow, loads, bindings);
+                        let narrow = extend_narrow(narrow, cond, true, [&**body], bindings);
+                        visit(Visit::Stmt(body), return_type, &narrow, loads, bindings);
+                        return;
+                    }
                     _ => {}
                 },
                 Visit::Expr(x) => match &**x {
+                    ExprP::Identifier(_, Some(ResolvedIdent::Slot((_, id)))) => {
+                        let ns: Vec<_> = narrow
+                            .iter()
+                            .filter(|(n, _)| n == id)
+                            .map(|(_, n)| n.clone())
+                            .collect();
+                        if !ns.is_empty() {
+                            bindings.narrowings.insert(x.span, ns);
+                        }
+                    }
+                    ExprP::If(c_t_f) => {
+                        let (c, t, f) = &**c_t_f;
+                        visit(Visit::Expr(c), return_type, narrow, loads, bindings);
+                        for (e, positive) in [(t, true), (f, false)] {
+                            let narrow =
+                                extend_narrow(narrow, c, positive, iter::empty(), bindings);
+                            visit(Visit::Expr(e), return_type, &narrow, loads, bindings);
+                        }
+                        return;
+                    }
+                    ExprP::Op(lhs, op @ (BinOp::And | BinOp::Or), rhs) => {
+                        // The right-hand side is only evaluated if the left-hand side was true for `and`
+                        visit(Visit::Expr(lhs), return_type, narrow, loads, bindings);
+                        let narrow =
+                            extend_narrow(narrow, lhs, *op == BinOp::And, iter::empty(), bindings);
+                        visit(Visit::Expr(rhs), return_type, &narrow, loads, bindings);
+                        return;
+                    }
                     ExprP::ListComprehension(_, for1, clauses)
                     | ExprP::DictComprehension(_, for1, clauses) => {
                         fn get_for_clause(
@@ -302,15 +531,56 @@ impl<'a> Bindings<'a> {
                                 bindings,
                             )
                         }
+
+                        // Each `if` clause tells us something about the later clauses and the result
+                        let mut narrow = narrow.to_vec();
+                        visit(
+                            Visit::Expr(&for1.over),
+                            return_type,
+                            &narrow,
+                            loads,
+                            bindings,
+                        );
+                        for clause in clauses {
+                            match clause {
+                                ClauseP::For(x) => {
+                                    visit(
+                                        Visit::Expr(&x.over),
+                                        return_type,
+                                        &narrow,
+                                        loads,
+                                        bindings,
+                                    );
+                                    x.var
+                                        .visit_lvalue(|x| narrow.retain(|(n, _)| Some(*n) != x.1));
+                                }
+                                ClauseP::If(x) => {
+                                    visit(Visit::Expr(x), return_type, &narrow, loads, bindings);
+                                    narrow =
+                                        extend_narrow(&narrow, x, true, iter::empty(), bindings);
+                                }
+                            }
+                        }
+                        match &**x {
+                            ExprP::ListComprehension(x, _, _) => {
+                                visit(Visit::Expr(x), return_type, &narrow, loads, bindings)
+                            }
+                            ExprP::DictComprehension(k_v, _, _) => {
+                                visit(Visit::Expr(&k_v.0), return_type, &narrow, loads, bindings);
+                                visit(Visit::Expr(&k_v.1), return_type, &narrow, loads, bindings);
+                            }
+                            _ => unreachable!(),
+                        }
+                        return;
                     }
                     _ => {}
                 },
             }
-            x.visit_children(|x| visit(x, return_type, loads, bindings))
+            x.visit_children(|x| visit(x, return_type, narrow, loads, bindings))
         }
 
         let mut res = Bindings::default();
-        visit(Visit::Stmt(x), &Ty::Any, loads, &mut res);
+        visit(Visit::Stmt(x), &Ty::Any, &[], loads, &mut res);
         res
     }
 }
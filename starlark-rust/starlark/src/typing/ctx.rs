@@ -16,6 +16,7 @@
  */
 
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt::Debug;
 
@@ -39,6 +40,7 @@ use crate::syntax::ast::ClauseP;
 use crate::syntax::ast::ExprP;
 use crate::syntax::ast::ForClauseP;
 use crate::typing::bindings::BindExpr;
+use crate::typing::bindings::Narrow;
 use crate::typing::oracle::traits::TypingOracle;
 use crate::typing::ty::Approximation;
 use crate::typing::ty::Arg;
@@ -87,6 +89,9 @@ pub(crate) struct TypingContext<'a> {
     pub(crate) errors: RefCell<Vec<TypingError>>,
     pub(crate) approximoations: RefCell<Vec<Approximation>>,
     pub(crate) types: HashMap<BindingId, Ty>,
+    pub(crate) narrowings: &'a HashMap<Span, Vec<Narrow>>,
+    /// For every expression we computed the type of, was that type `Any`.
+    pub(crate) expressions: RefCell<HashMap<Span, bool>>,
 }
 
 impl TypingContext<'_> {
@@ -106,7 +111,25 @@ impl TypingContext<'_> {
         self.codemap.file_span(span).resolve()
     }
 
-    fn validate_args(&self, params: &[Param], args: &[Arg], span: Span) {
+    fn narrow(&self, ty: Ty, narrow: &Narrow) -> Ty {
+        match narrow {
+            Narrow::Is(require) if ty.is_any() => require.clone(),
+            Narrow::Is(require) => Ty::unions(
+                ty.into_iter_union()
+                    .filter(|x| x.intersects(require, Some(self)))
+                    .collect(),
+            ),
+            Narrow::IsNot(require) if require.is_any() => ty,
+            Narrow::IsNot(require) => Ty::unions(
+                ty.into_iter_union()
+                    .filter(|x| !x.is_instance_of(require))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Check the arguments match the parameters, returning what each type variable was bound to.
+    fn validate_args(&self, params: &[Param], args: &[Arg], span: Span) -> BTreeMap<String, Ty> {
         // Want to figure out which arguments go in which positions
         let mut param_args: Vec<Vec<&Ty>> = vec![vec![]; params.len()];
         // The next index a positional parameter might fill
@@ -121,7 +144,7 @@ impl TypingContext<'_> {
                             self.add_error(TypingError::TooManyPositionalArguments {
                                 loc: self.resolve(span),
                             });
-                            return;
+                            return BTreeMap::new();
                         }
                         Some(param) => {
                             let found_index = param_pos;
@@ -161,6 +184,7 @@ impl TypingContext<'_> {
             }
         }
 
+        let mut vars = BTreeMap::new();
         for (param, args) in std::iter::zip(params, param_args) {
             if !param.allows_many() && args.len() > 1 {
                 panic!("bad")
@@ -177,13 +201,15 @@ impl TypingContext<'_> {
             }
             match param.mode {
                 ParamMode::PosOnly | ParamMode::PosOrName(_) | ParamMode::NameOnly(_) => {
-                    self.validate_type(args[0], &param.ty, span)
+                    self.validate_type(args[0], &param.ty, span);
+                    param.ty.unify(args[0], &mut vars);
                 }
                 ParamMode::Args => {
                     for ty in args {
                         // For an arg, we require the type annotation to be inner value,
                         // rather than the outer (which is always a tuple)
                         self.validate_type(ty, &param.ty, span);
+                        param.ty.unify(ty, &mut vars);
                     }
                 }
                 ParamMode::Kwargs => {
@@ -199,11 +225,13 @@ impl TypingContext<'_> {
                         let require = Ty::unions(val_types);
                         for ty in args {
                             self.validate_type(ty, &require, span);
+                            require.unify(ty, &mut vars);
                         }
                     }
                 }
             }
         }
+        vars
     }
 
     fn validate_call(&self, fun: &Ty, args: &[Arg], span: Span) -> Ty {
@@ -245,8 +273,8 @@ impl TypingContext<'_> {
                     }),
                 }
             } else {
-                self.validate_args(&fun.params, args, span);
-                (*fun.result).clone()
+                let vars = self.validate_args(&fun.params, args, span);
+                fun.result.instantiate(&vars)
             };
             let errors_after_this = self.errors.borrow().len();
             if errors_before_this == errors_after_this {
@@ -403,6 +431,12 @@ impl TypingContext<'_> {
     }
 
     pub(crate) fn expression_type(&self, x: &CstExpr) -> Ty {
+        let ty = self.expression_type_impl(x);
+        self.expressions.borrow_mut().insert(x.span, ty.is_any());
+        ty
+    }
+
+    fn expression_type_impl(&self, x: &CstExpr) -> Ty {
         let span = x.span;
         match &**x {
             ExprP::Tuple(xs) => Ty::Tuple(xs.map(|x| self.expression_type(x))),
@@ -441,7 +475,10 @@ impl TypingContext<'_> {
             ExprP::Identifier(x, i) => {
                 if let Some(ResolvedIdent::Slot((_, i))) = i {
                     if let Some(ty) = self.types.get(i) {
-                        return ty.clone();
+                        return match self.narrowings.get(&span) {
+                            None => ty.clone(),
+                            Some(ns) => ns.iter().fold(ty.clone(), |ty, n| self.narrow(ty, n)),
+                        };
                     }
                 }
                 self.builtin(x, x.span)
@@ -454,6 +491,7 @@ impl TypingContext<'_> {
                 AstLiteral::Int(_) => Ty::int(),
                 AstLiteral::Float(_) => Ty::float(),
                 AstLiteral::String(_) => Ty::string(),
+                AstLiteral::Bytes(_) => Ty::bytes(),
             },
             ExprP::Not(x) => {
                 if self.expression_type(x).is_void() {
@@ -528,6 +566,12 @@ impl TypingContext<'_> {
                 self.check_comprehension(b, c);
                 Ty::dict(self.expression_type(&k_v.0), self.expression_type(&k_v.1))
             }
+            ExprP::FString(fstring) => {
+                for expr in &fstring.expressions {
+                    self.expression_type(expr);
+                }
+                Ty::string()
+            }
         }
     }
 }
@@ -23,6 +23,7 @@ use once_cell::sync::Lazy;
 use crate::stdlib::LibraryExtension;
 use crate::syntax::AstModule;
 use crate::syntax::Dialect;
+use crate::typing::typecheck::ExpressionCounts;
 use crate::typing::Approximation;
 use crate::typing::Interface;
 use crate::typing::OracleNoBuiltins;
@@ -54,7 +55,10 @@ fn test_oracle() {
     assert_eq!(
         o.builtin("hash"),
         Some(Ok(Ty::function(
-            vec![Param::pos_or_name("a", Ty::string())],
+            vec![Param::pos_or_name(
+                "a",
+                Ty::union2(Ty::bytes(), Ty::string())
+            )],
             Ty::int()
         )))
     );
@@ -97,7 +101,7 @@ hash(1)
     assert_eq!(errs.len(), 1);
     assert_eq!(
         format!("{:#}", errs[0]),
-        r#"Expected type `"string"` but got `"int"`, at filename:2:1-8"#
+        r#"Expected type `["bytes", "string"]` but got `"int"`, at filename:2:1-8"#
     );
 }
 
@@ -135,3 +139,90 @@ fn test_false_negative() {
     assert!(approx.is_empty());
     assert!(errs.is_empty());
 }
+
+#[test]
+fn test_narrowing() {
+    let (errs, _, interface, _) = typecheck(
+        r#"
+def get() -> [str.type, None]:
+    return None
+x = get()
+y = x if x != None else "default"
+z = x if type(x) == "string" else "default"
+   "#,
+        &HashMap::new(),
+    );
+    assert!(errs.is_empty());
+    assert_eq!(
+        interface.get("x").unwrap(),
+        &Ty::union2(Ty::string(), Ty::None)
+    );
+    assert_eq!(interface.get("y").unwrap(), &Ty::string());
+    assert_eq!(interface.get("z").unwrap(), &Ty::string());
+}
+
+#[test]
+fn test_narrowing_errors() {
+    let (errs, _, _, _) = typecheck(
+        r#"
+def foo(x: [str.type, int.type]):
+    if type(x) == "string":
+        hash(x)
+    else:
+        hash(x)
+
+def bar(x: [int.type, None]):
+    if x != None:
+        return
+    hash(x)
+   "#,
+        &HashMap::new(),
+    );
+    assert_eq!(errs.len(), 2);
+    assert_eq!(
+        format!("{:#}", errs[0]),
+        r#"Expected type `["bytes", "string"]` but got `"int"`, at filename:6:9-16"#
+    );
+    assert_eq!(
+        format!("{:#}", errs[1]),
+        r#"Expected type `["bytes", "string"]` but got `None`, at filename:11:5-12"#
+    );
+}
+
+#[test]
+fn test_generic() {
+    let (errs, _, interface, _) = typecheck(
+        r#"
+def first(xs: ["_a"]) -> "_a":
+    return xs.pop()
+def values(d: {str.type: "_v"}) -> ["_v"]:
+    return d.values()
+x = first([1, 2])
+y = first(["test"])
+z = values({"a": True})
+   "#,
+        &HashMap::new(),
+    );
+    assert!(errs.is_empty());
+    assert_eq!(interface.get("x").unwrap(), &Ty::int());
+    assert_eq!(interface.get("y").unwrap(), &Ty::string());
+    assert_eq!(interface.get("z").unwrap(), &Ty::list(Ty::bool()));
+}
+
+#[test]
+fn test_expression_counts() {
+    let (_, typemap, _, _) = typecheck(
+        r#"
+def f(a):
+    return a
+x = [f(1)]
+   "#,
+        &HashMap::new(),
+    );
+    // `[f(1)]`, `f` and `1` have types, `f(1)` and `a` are `Any`
+    assert_eq!(typemap.expressions, ExpressionCounts { typed: 3, any: 2 });
+    assert_eq!(
+        typemap.expressions_by_function["f"],
+        ExpressionCounts { typed: 0, any: 1 }
+    );
+}
@@ -19,6 +19,7 @@ use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
+use std::mem;
 
 use either::Either;
 
@@ -200,6 +201,10 @@ pub enum Ty {
     /// Will never be a type that can be represented by another operation,
     /// e.g. never `"list"` because `Ty::List` could be used instead.
     Name(TyName),
+    /// A type variable, represented by `"_a"` in the Starlark type.
+    /// When calling a function, the variable is bound to the type of the matching arguments,
+    /// and the result type has the variable replaced. Otherwise behaves like [`Ty::Any`].
+    Var(TyName),
     /// The `None` type.
     None,
     /// Iter is a type that supports iteration, only used as arguments to primitive functions.
@@ -348,6 +353,11 @@ impl Ty {
         Self::name("string")
     }
 
+    /// Create a bytes type.
+    pub fn bytes() -> Self {
+        Self::name("bytes")
+    }
+
     /// Create a list type.
     pub fn list(inner: Ty) -> Self {
         Ty::List(Box::new(inner))
@@ -412,6 +422,20 @@ impl Ty {
         }
     }
 
+    /// Is every value of this type definitely also a value of `other`.
+    /// Only checks the shape, e.g. every list is a `"list"`, and errs on the side of `false`.
+    pub(crate) fn is_instance_of(&self, other: &Ty) -> bool {
+        match (self, other) {
+            (x, y) if x == y => true,
+            (Ty::List(_), Ty::List(y)) => y.is_any(),
+            (Ty::Dict(_), Ty::Dict(y)) => y.0.is_any() && y.1.is_any(),
+            (Ty::Tuple(_), y) => y.is_name("tuple"),
+            (Ty::Function(_), y) => y == &Ty::name("function"),
+            (Ty::Struct { .. }, Ty::Struct { fields, extra }) => fields.is_empty() && *extra,
+            _ => false,
+        }
+    }
+
     /// Create a unions type, which will be normalised before being created.
     pub fn unions(mut xs: Vec<Self>) -> Self {
         xs = xs.into_iter().flat_map(|x| x.into_iter_union()).collect();
@@ -490,6 +514,93 @@ impl Ty {
         }
     }
 
+    /// Given `self` is the type of a parameter, and `arg` the type of the argument passed to it,
+    /// record what each type variable in `self` must be.
+    pub(crate) fn unify(&self, arg: &Ty, vars: &mut BTreeMap<String, Ty>) {
+        match self {
+            Ty::Var(x) => {
+                let ty = vars.entry(x.as_str().to_owned()).or_insert(Ty::Void);
+                *ty = Ty::union2(mem::replace(ty, Ty::Void), arg.clone());
+            }
+            Ty::List(x) => {
+                for a in arg.iter_union() {
+                    if let Ty::List(a) = a {
+                        x.unify(a, vars);
+                    }
+                }
+            }
+            Ty::Dict(x) => {
+                for a in arg.iter_union() {
+                    if let Ty::Dict(a) = a {
+                        x.0.unify(&a.0, vars);
+                        x.1.unify(&a.1, vars);
+                    }
+                }
+            }
+            Ty::Tuple(xs) => {
+                for a in arg.iter_union() {
+                    if let Ty::Tuple(ys) = a {
+                        if xs.len() == ys.len() {
+                            for (x, y) in std::iter::zip(xs, ys) {
+                                x.unify(y, vars);
+                            }
+                        }
+                    }
+                }
+            }
+            Ty::Union(xs) => {
+                // Given `["_a", None]` and `[int, None]` we want `_a` to be `int`,
+                // so variables only get the argument alternatives nothing else matches.
+                let (var, other): (Vec<_>, Vec<_>) = xs
+                    .alternatives()
+                    .iter()
+                    .partition(|x| matches!(x, Ty::Var(_)));
+                for x in &other {
+                    x.unify(arg, vars);
+                }
+                let rest = Ty::unions(
+                    arg.iter_union()
+                        .filter(|a| !other.contains(a))
+                        .cloned()
+                        .collect(),
+                );
+                for x in var {
+                    x.unify(&rest, vars);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Replace the type variables with the types they were bound to by [`Ty::unify`],
+    /// or with [`Ty::Any`] if they were not bound.
+    pub(crate) fn instantiate(&self, vars: &BTreeMap<String, Ty>) -> Ty {
+        match self {
+            Ty::Var(x) => vars.get(x.as_str()).cloned().unwrap_or(Ty::Any),
+            Ty::Union(xs) => Ty::unions(xs.alternatives().map(|x| x.instantiate(vars))),
+            Ty::Iter(x) => Ty::Iter(Box::new(x.instantiate(vars))),
+            Ty::List(x) => Ty::list(x.instantiate(vars)),
+            Ty::Tuple(xs) => Ty::Tuple(xs.map(|x| x.instantiate(vars))),
+            Ty::Dict(k_v) => Ty::dict(k_v.0.instantiate(vars), k_v.1.instantiate(vars)),
+            Ty::Struct { fields, extra } => Ty::Struct {
+                fields: fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.instantiate(vars)))
+                    .collect(),
+                extra: *extra,
+            },
+            Ty::Function(f) => Ty::Function(TyFunction {
+                params: f.params.map(|p| Param {
+                    ty: p.ty.instantiate(vars),
+                    ..p.clone()
+                }),
+                result: Box::new(f.result.instantiate(vars)),
+                ..f.clone()
+            }),
+            Ty::Void | Ty::Any | Ty::Name(_) | Ty::None => self.clone(),
+        }
+    }
+
     /// Returns false on Void, since that is definitely not a list
     pub(crate) fn probably_a_list(&self) -> bool {
         if self.is_void() {
@@ -502,7 +613,7 @@ impl Ty {
     pub(crate) fn attribute(&self, attr: &str, ctx: &TypingContext) -> Result<Ty, ()> {
         // There are some structural types which have to be handled in a specific way
         match self {
-            Ty::Any => Ok(Ty::Any),
+            Ty::Any | Ty::Var(_) => Ok(Ty::Any),
             Ty::Void => Ok(Ty::Void),
             Ty::Union(xs) => {
                 let rs = xs
@@ -544,6 +655,8 @@ impl Ty {
         for x in self.iter_union() {
             for y in other.iter_union() {
                 let b = match (x, y) {
+                    // Variables can be bound to anything
+                    (Ty::Var(_), _) | (_, Ty::Var(_)) => true,
                     (Ty::Name(x), Ty::Name(y)) => equal_names(x, y),
                     (Ty::List(x), Ty::List(y)) => x.intersects(y, ctx),
                     (Ty::Dict(x), Ty::Dict(y)) => {
@@ -601,8 +714,10 @@ impl Ty {
                 }
             },
             ExprP::Literal(AstLiteral::String(x)) => {
-                if x.is_empty() || x.starts_with('_') {
+                if x.is_empty() || x.as_str() == "_" {
                     Ty::Any
+                } else if x.starts_with('_') {
+                    Ty::Var(TyName(x.as_str().to_owned()))
                 } else {
                     Ty::name(x.as_str())
                 }
@@ -709,7 +824,7 @@ impl Display for Ty {
             Ty::Void => write!(f, "Void"),
             Ty::Any => write!(f, "\"\""),
             Ty::Union(xs) => write!(f, "{}", xs),
-            Ty::Name(x) => write!(f, "{}", x),
+            Ty::Name(x) | Ty::Var(x) => write!(f, "{}", x),
             Ty::None => write!(f, "None"),
             Ty::Iter(x) => write!(f, "iter({})", x),
             Ty::List(x) => write!(f, "[{}]", x),
@@ -71,12 +71,31 @@ fn unique_identifiers<'f>(
     (cst, scope)
 }
 
+/// How many expressions were given a type, and how many were only known to be `Any`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ExpressionCounts {
+    pub(crate) typed: usize,
+    pub(crate) any: usize,
+}
+
+impl ExpressionCounts {
+    pub(crate) fn add(&mut self, other: ExpressionCounts) {
+        self.typed += other.typed;
+        self.any += other.any;
+    }
+}
+
 // Things which are None in the map have type void - they are never constructed
 fn solve_bindings(
     oracle: &dyn TypingOracle,
     bindings: Bindings,
     codemap: &CodeMap,
-) -> (Vec<TypingError>, HashMap<BindingId, Ty>, Vec<Approximation>) {
+) -> (
+    Vec<TypingError>,
+    HashMap<BindingId, Ty>,
+    Vec<Approximation>,
+    HashMap<Span, bool>,
+) {
     let mut types = bindings
         .expressions
         .keys()
@@ -93,11 +112,14 @@ fn solve_bindings(
         errors: RefCell::new(Vec::new()),
         approximoations: RefCell::new(Vec::new()),
         types,
+        narrowings: &bindings.narrowings,
+        expressions: RefCell::new(HashMap::new()),
     };
     const ITERATIONS: usize = 100;
     for _iteration in 0..ITERATIONS {
         changed = false;
         ctx.errors.borrow_mut().clear();
+        ctx.expressions.borrow_mut().clear();
         for (name, exprs) in &bindings.expressions {
             for expr in exprs {
                 let ty = ctx.expression_bind_type(expr);
@@ -134,14 +156,42 @@ fn solve_bindings(
         ctx.errors.into_inner(),
         ctx.types,
         ctx.approximoations.into_inner(),
+        ctx.expressions.into_inner(),
     )
 }
 
+/// Count the expressions, both in total and by the innermost function they are in.
+fn count_expressions(
+    expressions: &HashMap<Span, bool>,
+    functions: &[(String, Span)],
+) -> (ExpressionCounts, HashMap<String, ExpressionCounts>) {
+    let mut total = ExpressionCounts::default();
+    let mut by_function: HashMap<String, ExpressionCounts> = HashMap::new();
+    for (span, is_any) in expressions {
+        let count = if *is_any {
+            ExpressionCounts { typed: 0, any: 1 }
+        } else {
+            ExpressionCounts { typed: 1, any: 0 }
+        };
+        total.add(count);
+        let function = functions
+            .iter()
+            .filter(|(_, f)| f.contains(span.begin()))
+            .min_by_key(|(_, f)| f.len());
+        if let Some((name, _)) = function {
+            by_function.entry(name.clone()).or_default().add(count);
+        }
+    }
+    (total, by_function)
+}
+
 /// Structure containing all the inferred types.
 #[derive(Debug)]
 pub struct TypeMap {
     codemap: CodeMap,
     bindings: HashMap<BindingId, (String, Span, Ty)>,
+    pub(crate) expressions: ExpressionCounts,
+    pub(crate) expressions_by_function: HashMap<String, ExpressionCounts>,
 }
 
 impl Display for TypeMap {
@@ -176,8 +226,11 @@ impl AstModule {
         let (cst, scope) = unique_identifiers(&frozen_heap, self, &names);
         let bindings = Bindings::collect(&cst, loads);
         let descriptions = bindings.descriptions.clone();
+        let functions = bindings.functions.clone();
         let mut approximations = bindings.approximations.clone();
-        let (errors, types, solve_approximations) = solve_bindings(oracle, bindings, &codemap);
+        let (errors, types, solve_approximations, expressions) =
+            solve_bindings(oracle, bindings, &codemap);
+        let (expressions, expressions_by_function) = count_expressions(&expressions, &functions);
 
         approximations.extend(solve_approximations);
 
@@ -192,6 +245,8 @@ impl AstModule {
         let typemap = TypeMap {
             bindings: typemap,
             codemap: codemap.dupe(),
+            expressions,
+            expressions_by_function,
         };
 
         let errors = errors.into_map(|x| anyhow::anyhow!(x));
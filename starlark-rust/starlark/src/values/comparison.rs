@@ -77,6 +77,22 @@ pub(crate) fn compare_slice<E, X1, X2>(
     Ok(xs.len().cmp(&ys.len()))
 }
 
+/// Like [`compare_slice`], for elements which may not be ordered (e.g. sets), in which case
+/// the slices are not ordered either.
+pub(crate) fn partial_compare_slice<E, X1, X2>(
+    xs: &[X1],
+    ys: &[X2],
+    f: impl Fn(&X1, &X2) -> Result<Option<Ordering>, E>,
+) -> Result<Option<Ordering>, E> {
+    for (x, y) in xs.iter().zip(ys) {
+        let cmp = f(x, y)?;
+        if cmp != Some(Ordering::Equal) {
+            return Ok(cmp);
+        }
+    }
+    Ok(Some(xs.len().cmp(&ys.len())))
+}
+
 pub(crate) fn compare_small_map<E, K, K2: Ord + Hash, V1, V2>(
     x: &SmallMap<K, V1>,
     y: &SmallMap<K, V2>,
@@ -21,5 +21,6 @@ pub(crate) mod aggregated;
 pub(crate) mod alloc_counts;
 pub(crate) mod arc_str;
 pub(crate) mod by_type;
+pub(crate) mod snapshot;
 pub(crate) mod string_index;
 mod summary_by_function;
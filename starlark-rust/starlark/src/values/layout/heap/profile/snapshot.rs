@@ -0,0 +1,292 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Named snapshots of heap allocations, which can be compared to find what made a heap grow.
+
+use allocative::Allocative;
+use dupe::Dupe;
+use starlark_map::small_map::SmallMap;
+
+use crate::eval::runtime::profile::csv::CsvWriter;
+use crate::eval::runtime::profile::flamegraph::FlameGraphData;
+use crate::values::layout::heap::profile::aggregated::AggregateHeapProfileInfo;
+use crate::values::layout::heap::profile::aggregated::StackFrame;
+use crate::values::layout::heap::profile::alloc_counts::AllocCounts;
+use crate::values::layout::heap::profile::arc_str::ArcStr;
+use crate::values::layout::heap::profile::string_index::StringIndex;
+use crate::values::FrozenHeap;
+use crate::values::Heap;
+
+/// Allocations on a heap at some point in time, by type and allocating call stack.
+///
+/// Call stacks are only known for a [`Heap`] with heap profiling enabled;
+/// otherwise all the allocations are attributed to the root.
+#[derive(Debug, Clone, Allocative)]
+pub struct HeapSnapshot {
+    name: String,
+    info: AggregateHeapProfileInfo,
+}
+
+/// Allocations with the same call stack and type.
+type AllocsByStack = SmallMap<(Vec<ArcStr>, &'static str), AllocCounts>;
+
+impl HeapSnapshot {
+    /// Snapshot of an existing profile, e.g. the retained memory of a frozen module
+    /// returned by [`FrozenModule::aggregated_heap_profile_info`](crate::environment::FrozenModule::aggregated_heap_profile_info).
+    pub fn from_profile(name: &str, info: &AggregateHeapProfileInfo) -> HeapSnapshot {
+        HeapSnapshot {
+            name: name.to_owned(),
+            info: info.clone(),
+        }
+    }
+
+    /// The name given when the snapshot was captured.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Total allocations in the snapshot, as (count, bytes).
+    pub fn total(&self) -> (usize, usize) {
+        let total: AllocCounts = self.allocs_by_stack().values().sum();
+        (total.count, total.bytes)
+    }
+
+    fn allocs_by_stack(&self) -> AllocsByStack {
+        fn visit(
+            frame: &StackFrame,
+            strings: &StringIndex,
+            stack: &mut Vec<ArcStr>,
+            res: &mut AllocsByStack,
+        ) {
+            for (t, allocs) in &frame.allocs.summary {
+                *res.entry((stack.clone(), *t)).or_default() += *allocs;
+            }
+            for (id, callee) in &frame.callees {
+                stack.push(strings.get(*id).dupe());
+                visit(callee, strings, stack, res);
+                stack.pop();
+            }
+        }
+
+        let mut res = SmallMap::new();
+        visit(
+            &self.info.root,
+            &self.info.strings,
+            &mut Vec::new(),
+            &mut res,
+        );
+        res
+    }
+
+    /// Compare with a later snapshot.
+    pub fn diff(&self, after: &HeapSnapshot) -> HeapSnapshotDiff {
+        let before = self.allocs_by_stack();
+        let after_allocs = after.allocs_by_stack();
+        let mut entries: Vec<HeapSnapshotDiffEntry> = before
+            .iter()
+            .filter(|(key, _)| !after_allocs.contains_key(*key))
+            .map(|((stack, typ), before)| HeapSnapshotDiffEntry {
+                stack: stack.clone(),
+                typ,
+                before: *before,
+                after: AllocCounts::default(),
+            })
+            .collect();
+        entries.extend(after_allocs.into_iter().map(|((stack, typ), after)| {
+            HeapSnapshotDiffEntry {
+                before: before
+                    .get(&(stack.clone(), typ))
+                    .copied()
+                    .unwrap_or_default(),
+                stack,
+                typ,
+                after,
+            }
+        }));
+        entries.retain(|e| e.count_delta() != 0 || e.bytes_delta() != 0);
+        entries.sort_by_key(|e| (-e.bytes_delta(), -e.count_delta()));
+        HeapSnapshotDiff {
+            before: self.name.clone(),
+            after: after.name.clone(),
+            entries,
+        }
+    }
+}
+
+impl Heap {
+    /// Capture the values currently allocated on this heap.
+    pub fn snapshot(&self, name: &str) -> HeapSnapshot {
+        HeapSnapshot {
+            name: name.to_owned(),
+            info: AggregateHeapProfileInfo::collect(self, None),
+        }
+    }
+}
+
+impl FrozenHeap {
+    /// Capture the values currently allocated on this heap.
+    /// Frozen heaps don't record call stacks, so the snapshot is only by type.
+    pub fn snapshot(&self, name: &str) -> HeapSnapshot {
+        let mut info = AggregateHeapProfileInfo::default();
+        info.root.allocs = self.allocated_summary();
+        HeapSnapshot {
+            name: name.to_owned(),
+            info,
+        }
+    }
+}
+
+/// The change in allocations of one type with one call stack.
+#[derive(Debug, Clone)]
+struct HeapSnapshotDiffEntry {
+    stack: Vec<ArcStr>,
+    typ: &'static str,
+    before: AllocCounts,
+    after: AllocCounts,
+}
+
+impl HeapSnapshotDiffEntry {
+    fn count_delta(&self) -> isize {
+        self.after.count as isize - self.before.count as isize
+    }
+
+    fn bytes_delta(&self) -> isize {
+        self.after.bytes as isize - self.before.bytes as isize
+    }
+}
+
+/// Difference between two [`HeapSnapshot`]s, by type and allocating call stack.
+#[derive(Debug, Clone)]
+pub struct HeapSnapshotDiff {
+    before: String,
+    after: String,
+    /// Sorted by growth in bytes, largest first.
+    entries: Vec<HeapSnapshotDiffEntry>,
+}
+
+impl HeapSnapshotDiff {
+    /// Change in the total number of bytes allocated.
+    pub fn bytes_delta(&self) -> isize {
+        self.entries.iter().map(|e| e.bytes_delta()).sum()
+    }
+
+    /// Change in the total number of allocations.
+    pub fn count_delta(&self) -> isize {
+        self.entries.iter().map(|e| e.count_delta()).sum()
+    }
+
+    /// One row per call stack and type whose allocations changed, largest growth first.
+    pub fn gen_csv(&self) -> String {
+        let count_before = format!("Count({})", self.before);
+        let count_after = format!("Count({})", self.after);
+        let bytes_before = format!("Bytes({})", self.before);
+        let bytes_after = format!("Bytes({})", self.after);
+        let mut csv = CsvWriter::new([
+            "Stack",
+            "Type",
+            count_before.as_str(),
+            count_after.as_str(),
+            "CountDelta",
+            bytes_before.as_str(),
+            bytes_after.as_str(),
+            "BytesDelta",
+        ]);
+        for e in &self.entries {
+            let stack: Vec<&str> = e.stack.iter().map(|x| x.as_str()).collect();
+            csv.write_value(stack.join(";").as_str());
+            csv.write_value(e.typ);
+            csv.write_value(e.before.count);
+            csv.write_value(e.after.count);
+            csv.write_value(e.count_delta());
+            csv.write_value(e.before.bytes);
+            csv.write_value(e.after.bytes);
+            csv.write_value(e.bytes_delta());
+            csv.finish_row();
+        }
+        csv.finish()
+    }
+
+    /// Growth in bytes in flamegraph.pl format.
+    /// Flame graphs can't show negative values, so allocations which shrank are omitted.
+    pub fn gen_flame_graph(&self) -> String {
+        let mut data = FlameGraphData::default();
+        for e in &self.entries {
+            if e.bytes_delta() <= 0 {
+                continue;
+            }
+            let mut node = data.root();
+            for frame in &e.stack {
+                node = node.child(frame.dupe());
+            }
+            node.child(e.typ.into()).add(e.bytes_delta() as u64);
+        }
+        data.write()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::const_frozen_string;
+    use crate::values::FrozenHeap;
+    use crate::values::Heap;
+
+    #[test]
+    fn test_diff() {
+        let heap = Heap::new();
+        heap.record_call_enter(const_frozen_string!("f").to_value());
+        heap.alloc_str("xxyy");
+        heap.record_call_exit();
+        let before = heap.snapshot("before");
+
+        heap.record_call_enter(const_frozen_string!("g").to_value());
+        heap.alloc_str("zzww");
+        heap.alloc_str("rrtt");
+        heap.record_call_exit();
+        let after = heap.snapshot("after");
+
+        let diff = before.diff(&after);
+        assert_eq!(2, diff.count_delta());
+        assert_eq!(1, diff.entries.len());
+        assert_eq!("g", diff.entries[0].stack[0].as_str());
+        assert_eq!("string", diff.entries[0].typ);
+
+        let csv = diff.gen_csv();
+        assert!(
+            csv.starts_with("Stack,Type,Count(before),Count(after),CountDelta"),
+            "{}",
+            csv
+        );
+        assert!(csv.contains("\"g\",\"string\",0,2,2,"), "{}", csv);
+        assert!(diff.gen_flame_graph().starts_with("g;string "));
+
+        // Reverse diff shrinks, so there is nothing to show in the flame graph.
+        let reverse = after.diff(&before);
+        assert_eq!(-2, reverse.count_delta());
+        assert_eq!("", reverse.gen_flame_graph());
+    }
+
+    #[test]
+    fn test_frozen_heap_snapshot() {
+        let heap = FrozenHeap::new();
+        let before = heap.snapshot("before");
+        heap.alloc_str("xxyy");
+        let diff = before.diff(&heap.snapshot("after"));
+        assert_eq!(1, diff.count_delta());
+        assert!(diff.bytes_delta() > 0);
+        assert_eq!((1, diff.bytes_delta() as usize), heap.snapshot("x").total());
+    }
+}
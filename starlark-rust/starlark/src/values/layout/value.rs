@@ -755,6 +755,13 @@ impl<'v> Value<'v> {
         ValueLike::compare(self, other)
     }
 
+    /// How are two values ordered by the comparison operators. Unlike [`compare`](Value::compare),
+    /// returns [`None`] for values of partially ordered types which are not ordered.
+    pub fn partial_compare(self, other: Value<'v>) -> anyhow::Result<Option<Ordering>> {
+        let _guard = stack_guard::stack_guard()?;
+        self.get_ref().partial_compare(other)
+    }
+
     /// Describe the value, in order to get its metadata in a way that could be used
     /// to generate prototypes, help information or whatever other descriptive text
     /// is required.
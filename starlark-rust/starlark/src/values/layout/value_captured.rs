@@ -72,6 +72,12 @@ impl<'v> ValueCaptured<'v> {
     }
 }
 
+impl FrozenValueCaptured {
+    pub(crate) fn new(payload: Option<FrozenValue>) -> FrozenValueCaptured {
+        FrozenValueCaptured(payload)
+    }
+}
+
 impl<'v> Freeze for ValueCaptured<'v> {
     type Frozen = FrozenValueCaptured;
 
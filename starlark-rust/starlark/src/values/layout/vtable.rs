@@ -496,6 +496,11 @@ impl<'v> AValueDyn<'v> {
         (self.vtable.starlark_value.compare)(StarlarkValueRawPtr::new(self.value), other)
     }
 
+    #[inline]
+    pub(crate) fn partial_compare(self, other: Value<'v>) -> anyhow::Result<Option<Ordering>> {
+        (self.vtable.starlark_value.partial_compare)(StarlarkValueRawPtr::new(self.value), other)
+    }
+
     #[inline]
     pub(crate) fn invoke(
         self,
@@ -52,6 +52,8 @@ pub use crate::values::layout::heap::heap_type::FrozenHeapRef;
 pub use crate::values::layout::heap::heap_type::Heap;
 pub use crate::values::layout::heap::heap_type::Tracer;
 pub use crate::values::layout::heap::profile::aggregated::AggregateHeapProfileInfo;
+pub use crate::values::layout::heap::profile::snapshot::HeapSnapshot;
+pub use crate::values::layout::heap::profile::snapshot::HeapSnapshotDiff;
 pub use crate::values::layout::identity::ValueIdentity;
 pub use crate::values::layout::static_string::constant_string;
 pub use crate::values::layout::static_string::StarlarkStrNRepr;
@@ -71,6 +73,7 @@ pub use crate::values::traits::StarlarkValue;
 pub use crate::values::types::any;
 pub use crate::values::types::array;
 pub use crate::values::types::bool;
+pub use crate::values::types::bytes;
 pub use crate::values::types::dict;
 pub use crate::values::types::enumeration;
 pub use crate::values::types::exported_name;
@@ -82,6 +85,7 @@ pub use crate::values::types::none;
 pub use crate::values::types::range;
 pub use crate::values::types::record;
 pub use crate::values::types::regex;
+pub use crate::values::types::set;
 pub use crate::values::types::string;
 pub use crate::values::types::structs;
 pub use crate::values::types::tuple;
@@ -101,6 +105,7 @@ pub(crate) mod layout;
 pub(crate) mod num;
 mod owned;
 pub(crate) mod recursive_repr_or_json_guard;
+pub mod serialize;
 mod stack_guard;
 mod trace;
 mod traits;
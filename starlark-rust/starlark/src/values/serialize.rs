@@ -0,0 +1,589 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Serialization of frozen values to JSON and CBOR, which can be decoded back into values.
+//!
+//! Unlike [`Value::to_json`], the encoding keeps enough information to rebuild the values:
+//! tuples stay tuples, dictionaries can have any keys, and records and enums remember their type.
+//! Record and enum types are identified by name, so the types must be registered with
+//! [`ValueSerializer::add_type`] or [`ValueSerializer::add_types`] on both sides.
+//! Other types, such as providers defined by an embedder, can be supported with a [`CustomSerializer`].
+//!
+//! ```
+//! # use starlark::assert;
+//! # use starlark::values::serialize::ValueSerializer;
+//! # use starlark::values::FrozenHeap;
+//! let module = assert::pass_module(r#"
+//! Point = record(x = "int", y = "int")
+//! origin = {"p": Point(x = 0, y = 0), "t": (1, None)}
+//! "#);
+//! let mut serializer = ValueSerializer::new();
+//! serializer.add_types(&module);
+//!
+//! let origin = module.get("origin").unwrap();
+//! let json = serializer.to_json(origin.value().unpack_frozen().unwrap()).unwrap();
+//! let heap = FrozenHeap::new();
+//! let decoded = serializer.from_json(&json, &heap).unwrap();
+//! assert_eq!(origin.value().to_repr(), decoded.to_value().to_repr());
+//! ```
+
+use std::str::FromStr;
+
+use num_bigint::BigInt;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::collections::SmallMap;
+use crate::environment::FrozenModule;
+use crate::values::bytes::StarlarkBytes;
+use crate::values::dict::AllocDict;
+use crate::values::dict::DictRef;
+use crate::values::enumeration::EnumType;
+use crate::values::enumeration::EnumValue;
+use crate::values::enumeration::FrozenEnumType;
+use crate::values::float::StarlarkFloat;
+use crate::values::list::AllocList;
+use crate::values::list::ListRef;
+use crate::values::record::FrozenRecordType;
+use crate::values::record::Record;
+use crate::values::record::RecordType;
+use crate::values::recursive_repr_or_json_guard::json_stack_push;
+use crate::values::structs::AllocStruct;
+use crate::values::structs::StructRef;
+use crate::values::tuple::AllocTuple;
+use crate::values::tuple::TupleRef;
+use crate::values::types::bigint::StarlarkBigInt;
+use crate::values::FrozenHeap;
+use crate::values::FrozenValue;
+use crate::values::OwnedFrozenValue;
+use crate::values::Value;
+use crate::values::ValueLike;
+
+#[derive(Debug, Error)]
+enum SerializeError {
+    #[error("Value of type `{0}` can't be serialized")]
+    Unsupported(&'static str),
+    #[error("Value of type `{0}` can't be serialized because its type is not registered")]
+    UnregisteredType(&'static str),
+    #[error("Unknown type `{0}` in serialized value")]
+    UnknownType(String),
+    #[error("Type `{0}` is not a record type")]
+    NotRecordType(String),
+    #[error("Type `{0}` is not an enum type")]
+    NotEnumType(String),
+    #[error("Record type `{0}` has no field `{1}`")]
+    UnknownField(String, String),
+    #[error("Value of type `{0}` contains itself and can't be serialized")]
+    Cycle(&'static str),
+    #[error("Invalid integer `{0}` in serialized value")]
+    InvalidInt(String),
+    #[error("Invalid float `{0}` in serialized value")]
+    InvalidFloat(String),
+    #[error("Value is not frozen")]
+    NotFrozen,
+}
+
+/// A value in a form which can be written with any [`serde`] format, and decoded back into a value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SerializedValue {
+    /// `None`.
+    None,
+    /// A `bool`.
+    Bool(bool),
+    /// An `int` which fits in 32 bits.
+    Int(i32),
+    /// An `int` which doesn't fit in 32 bits, as a decimal string.
+    BigInt(String),
+    /// A finite `float`.
+    Float(f64),
+    /// A `float` which is `nan`, `+inf` or `-inf`, which many formats can't represent as numbers.
+    NonFiniteFloat(String),
+    /// A `string`.
+    String(String),
+    /// A `bytes`.
+    Bytes(Vec<u8>),
+    /// A `list`.
+    List(Vec<SerializedValue>),
+    /// A `tuple`.
+    Tuple(Vec<SerializedValue>),
+    /// A `dict`, with the entries in order.
+    Dict(Vec<(SerializedValue, SerializedValue)>),
+    /// A `struct`.
+    Struct(Vec<(String, SerializedValue)>),
+    /// A record, with the name its type was registered under.
+    Record {
+        /// Name of the record type.
+        typ: String,
+        /// Values of the fields.
+        fields: Vec<(String, SerializedValue)>,
+    },
+    /// An enum element, with the name its type was registered under.
+    Enum {
+        /// Name of the enum type.
+        typ: String,
+        /// The element.
+        value: Box<SerializedValue>,
+    },
+    /// A value encoded by a [`CustomSerializer`].
+    Custom {
+        /// Name the custom serializer was registered under.
+        typ: String,
+        /// The encoding produced by the custom serializer.
+        value: Box<SerializedValue>,
+    },
+}
+
+/// Serialization of values which [`ValueSerializer`] doesn't support natively, e.g. providers.
+pub trait CustomSerializer {
+    /// Encode `value`, or return `None` if it's not a value this serializer handles.
+    /// Values inside `value` can be encoded with `serializer`.
+    fn to_serialized(
+        &self,
+        value: Value,
+        serializer: &ValueSerializer,
+    ) -> Option<anyhow::Result<SerializedValue>>;
+
+    /// Rebuild a value from the encoding returned by `to_serialized`.
+    fn from_serialized(
+        &self,
+        value: &SerializedValue,
+        serializer: &ValueSerializer,
+        heap: &FrozenHeap,
+    ) -> anyhow::Result<FrozenValue>;
+}
+
+/// Encodes frozen values as [`SerializedValue`], JSON or CBOR, and decodes them back.
+#[derive(Default)]
+pub struct ValueSerializer<'a> {
+    /// Record and enum types by name.
+    types: SmallMap<String, OwnedFrozenValue>,
+    custom: SmallMap<String, Box<dyn CustomSerializer + 'a>>,
+}
+
+impl<'a> ValueSerializer<'a> {
+    /// A serializer without any registered types.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a record or enum type under a name.
+    pub fn add_type(&mut self, name: &str, typ: OwnedFrozenValue) {
+        self.types.insert(name.to_owned(), typ);
+    }
+
+    /// Register all the public record and enum types of a module, under their names in the module.
+    pub fn add_types(&mut self, module: &FrozenModule) {
+        for name in module.names() {
+            if let Ok(value) = module.get(name.as_str()) {
+                let v = value.value();
+                if RecordType::is_type(v) || EnumType::is_type(v) {
+                    self.add_type(name.as_str(), value);
+                }
+            }
+        }
+    }
+
+    /// Register a serializer for other types. Values it encodes are tagged with `name`.
+    pub fn add_custom(&mut self, name: &str, custom: impl CustomSerializer + 'a) {
+        self.custom.insert(name.to_owned(), Box::new(custom));
+    }
+
+    fn type_name(&self, typ: Value, value_type: &'static str) -> anyhow::Result<String> {
+        match self.types.iter().find(|(_, t)| t.value().ptr_eq(typ)) {
+            Some((name, _)) => Ok(name.clone()),
+            None => Err(SerializeError::UnregisteredType(value_type).into()),
+        }
+    }
+
+    fn lookup_type(&self, name: &str, heap: &FrozenHeap) -> anyhow::Result<FrozenValue> {
+        match self.types.get(name) {
+            // Safe because the heap keeps a reference to the owner of the type.
+            Some(typ) => Ok(unsafe { typ.owned_frozen_value(heap) }),
+            None => Err(SerializeError::UnknownType(name.to_owned()).into()),
+        }
+    }
+
+    fn to_serialized_list(&self, xs: &[Value]) -> anyhow::Result<Vec<SerializedValue>> {
+        xs.iter().map(|x| self.to_serialized_value(*x)).collect()
+    }
+
+    /// Encode a value, which need not be frozen.
+    pub fn to_serialized_value(&self, value: Value) -> anyhow::Result<SerializedValue> {
+        if value.is_none() {
+            return Ok(SerializedValue::None);
+        }
+        if let Some(x) = value.unpack_bool() {
+            return Ok(SerializedValue::Bool(x));
+        }
+        if let Some(x) = value.unpack_int() {
+            return Ok(SerializedValue::Int(x));
+        }
+        if let Some(x) = value.downcast_ref::<StarlarkBigInt>() {
+            return Ok(SerializedValue::BigInt(x.get().to_string()));
+        }
+        if let Some(x) = value.downcast_ref::<StarlarkFloat>() {
+            return Ok(if x.0.is_nan() {
+                SerializedValue::NonFiniteFloat("nan".to_owned())
+            } else if x.0.is_infinite() {
+                SerializedValue::NonFiniteFloat(if x.0 > 0.0 { "+inf" } else { "-inf" }.to_owned())
+            } else {
+                SerializedValue::Float(x.0)
+            });
+        }
+        if let Some(x) = value.unpack_str() {
+            return Ok(SerializedValue::String(x.to_owned()));
+        }
+        if let Some(x) = value.downcast_ref::<StarlarkBytes>() {
+            return Ok(SerializedValue::Bytes(x.as_bytes().to_vec()));
+        }
+        // The other values may contain themselves, e.g. a list appended to itself.
+        let _guard = match json_stack_push(value) {
+            Ok(guard) => guard,
+            Err(..) => return Err(SerializeError::Cycle(value.get_type()).into()),
+        };
+        if let Some(x) = ListRef::from_value(value) {
+            return Ok(SerializedValue::List(self.to_serialized_list(x.content())?));
+        }
+        if let Some(x) = TupleRef::from_value(value) {
+            return Ok(SerializedValue::Tuple(
+                self.to_serialized_list(x.content())?,
+            ));
+        }
+        if let Some(x) = DictRef::from_value(value) {
+            return Ok(SerializedValue::Dict(
+                x.iter()
+                    .map(|(k, v)| Ok((self.to_serialized_value(k)?, self.to_serialized_value(v)?)))
+                    .collect::<anyhow::Result<_>>()?,
+            ));
+        }
+        if let Some(x) = StructRef::from_value(value) {
+            return Ok(SerializedValue::Struct(
+                x.iter()
+                    .map(|(k, v)| Ok((k.as_str().to_owned(), self.to_serialized_value(v)?)))
+                    .collect::<anyhow::Result<_>>()?,
+            ));
+        }
+        if let Some(x) = Record::from_value(value) {
+            return Ok(SerializedValue::Record {
+                typ: self.type_name(x.record_type(), value.get_type())?,
+                fields: x
+                    .iter()
+                    .map(|(k, v)| Ok((k.to_owned(), self.to_serialized_value(v)?)))
+                    .collect::<anyhow::Result<_>>()?,
+            });
+        }
+        if let Some(x) = EnumValue::from_value(value) {
+            return Ok(SerializedValue::Enum {
+                typ: self.type_name(x.enum_type(), value.get_type())?,
+                value: Box::new(self.to_serialized_value(x.value())?),
+            });
+        }
+        for (name, custom) in &self.custom {
+            if let Some(x) = custom.to_serialized(value, self) {
+                return Ok(SerializedValue::Custom {
+                    typ: name.clone(),
+                    value: Box::new(x?),
+                });
+            }
+        }
+        Err(SerializeError::Unsupported(value.get_type()).into())
+    }
+
+    /// Encode a frozen value.
+    pub fn to_serialized(&self, value: FrozenValue) -> anyhow::Result<SerializedValue> {
+        self.to_serialized_value(value.to_value())
+    }
+
+    /// Rebuild a value on `heap` from its encoding.
+    pub fn from_serialized(
+        &self,
+        x: &SerializedValue,
+        heap: &FrozenHeap,
+    ) -> anyhow::Result<FrozenValue> {
+        let list = |xs: &[SerializedValue]| -> anyhow::Result<Vec<FrozenValue>> {
+            xs.iter().map(|x| self.from_serialized(x, heap)).collect()
+        };
+        Ok(match x {
+            SerializedValue::None => FrozenValue::new_none(),
+            SerializedValue::Bool(x) => FrozenValue::new_bool(*x),
+            SerializedValue::Int(x) => heap.alloc(*x),
+            SerializedValue::BigInt(x) => match BigInt::from_str(x) {
+                Ok(x) => StarlarkBigInt::alloc_bigint_frozen(x, heap),
+                Err(_) => return Err(SerializeError::InvalidInt(x.clone()).into()),
+            },
+            SerializedValue::Float(x) => heap.alloc(*x),
+            SerializedValue::NonFiniteFloat(x) => heap.alloc(match x.as_str() {
+                "nan" => f64::NAN,
+                "+inf" => f64::INFINITY,
+                "-inf" => f64::NEG_INFINITY,
+                _ => return Err(SerializeError::InvalidFloat(x.clone()).into()),
+            }),
+            SerializedValue::String(x) => heap.alloc(x.as_str()),
+            SerializedValue::Bytes(x) => heap.alloc_simple(StarlarkBytes::new(x.as_slice())),
+            SerializedValue::List(xs) => heap.alloc(AllocList(list(xs)?)),
+            SerializedValue::Tuple(xs) => heap.alloc(AllocTuple(list(xs)?)),
+            SerializedValue::Dict(xs) => {
+                let mut entries = Vec::with_capacity(xs.len());
+                for (k, v) in xs {
+                    let k = self.from_serialized(k, heap)?;
+                    // Check the key is hashable before the allocation, which would panic.
+                    k.to_value().get_hashed()?;
+                    entries.push((k, self.from_serialized(v, heap)?));
+                }
+                heap.alloc(AllocDict(entries))
+            }
+            SerializedValue::Struct(xs) => heap.alloc(AllocStruct(
+                xs.iter()
+                    .map(|(k, v)| Ok((k.as_str(), self.from_serialized(v, heap)?)))
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            )),
+            SerializedValue::Record { typ, fields } => {
+                let typ_value = self.lookup_type(typ, heap)?;
+                let record_type = typ_value
+                    .to_value()
+                    .downcast_ref::<FrozenRecordType>()
+                    .ok_or_else(|| SerializeError::NotRecordType(typ.clone()))?;
+                if let Some((name, _)) = fields
+                    .iter()
+                    .find(|(name, _)| !record_type.field_names().any(|x| x == name))
+                {
+                    return Err(SerializeError::UnknownField(typ.clone(), name.clone()).into());
+                }
+                record_type.alloc_record(
+                    typ_value,
+                    |name| match fields.iter().find(|(x, _)| x == name) {
+                        Some((_, v)) => Ok(Some(self.from_serialized(v, heap)?)),
+                        None => Ok(None),
+                    },
+                    heap,
+                )?
+            }
+            SerializedValue::Enum { typ, value } => {
+                let typ_value = self.lookup_type(typ, heap)?;
+                let enum_type = typ_value
+                    .to_value()
+                    .downcast_ref::<FrozenEnumType>()
+                    .ok_or_else(|| SerializeError::NotEnumType(typ.clone()))?;
+                enum_type.construct(self.from_serialized(value, heap)?.to_value())?
+            }
+            SerializedValue::Custom { typ, value } => match self.custom.get(typ) {
+                Some(custom) => custom.from_serialized(value, self, heap)?,
+                None => return Err(SerializeError::UnknownType(typ.clone()).into()),
+            },
+        })
+    }
+
+    /// Encode a frozen value as JSON.
+    pub fn to_json(&self, value: FrozenValue) -> anyhow::Result<String> {
+        Ok(serde_json::to_string(&self.to_serialized(value)?)?)
+    }
+
+    /// Decode a value encoded with [`to_json`](ValueSerializer::to_json).
+    pub fn from_json(&self, json: &str, heap: &FrozenHeap) -> anyhow::Result<FrozenValue> {
+        self.from_serialized(&serde_json::from_str(json)?, heap)
+    }
+
+    /// Encode a frozen value as CBOR.
+    pub fn to_cbor(&self, value: FrozenValue) -> anyhow::Result<Vec<u8>> {
+        let mut res = Vec::new();
+        ciborium::ser::into_writer(&self.to_serialized(value)?, &mut res)?;
+        Ok(res)
+    }
+
+    /// Decode a value encoded with [`to_cbor`](ValueSerializer::to_cbor).
+    pub fn from_cbor(&self, cbor: &[u8], heap: &FrozenHeap) -> anyhow::Result<FrozenValue> {
+        self.from_serialized(&ciborium::de::from_reader(cbor)?, heap)
+    }
+
+    /// Encode an owned frozen value as JSON.
+    pub fn owned_to_json(&self, value: &OwnedFrozenValue) -> anyhow::Result<String> {
+        self.to_json(
+            value
+                .value()
+                .unpack_frozen()
+                .ok_or(SerializeError::NotFrozen)?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+    use crate::values::serialize::CustomSerializer;
+    use crate::values::serialize::SerializedValue;
+    use crate::values::serialize::ValueSerializer;
+    use crate::values::FrozenHeap;
+    use crate::values::FrozenValue;
+    use crate::values::Value;
+
+    const PROGRAM: &str = r#"
+Point = record(x = "int", y = field("int", 0))
+Color = enum("red", "green")
+value = {
+    "points": [Point(x = 1, y = 2), Point(x = 3)],
+    "color": Color("green"),
+    "struct": struct(a = (1, 2.5, None), b = b"\x00\xff"),
+    "big": 123456789012345678901234567890,
+    (1, "k"): True,
+}
+"#;
+
+    #[test]
+    fn test_round_trip() {
+        let module = assert::pass_module(PROGRAM);
+        let mut serializer = ValueSerializer::new();
+        serializer.add_types(&module);
+        let value = module.get("value").unwrap();
+        let frozen = value.value().unpack_frozen().unwrap();
+        let heap = FrozenHeap::new();
+
+        let json = serializer.to_json(frozen).unwrap();
+        let from_json = serializer.from_json(&json, &heap).unwrap();
+        assert!(frozen.to_value().equals(from_json.to_value()).unwrap());
+        assert_eq!(value.value().to_repr(), from_json.to_value().to_repr());
+
+        let cbor = serializer.to_cbor(frozen).unwrap();
+        let from_cbor = serializer.from_cbor(&cbor, &heap).unwrap();
+        assert!(frozen.to_value().equals(from_cbor.to_value()).unwrap());
+        assert_eq!(value.value().to_repr(), from_cbor.to_value().to_repr());
+    }
+
+    #[test]
+    fn test_errors() {
+        let module = assert::pass_module(PROGRAM);
+        let value = module.get("value").unwrap();
+        let frozen = value.value().unpack_frozen().unwrap();
+        let err = ValueSerializer::new().to_json(frozen).unwrap_err();
+        assert!(err.to_string().contains("not registered"), "{}", err);
+
+        let mut serializer = ValueSerializer::new();
+        serializer.add_types(&module);
+        let heap = FrozenHeap::new();
+        let bad = SerializedValue::Record {
+            typ: "Point".to_owned(),
+            fields: vec![("x".to_owned(), SerializedValue::String("1".to_owned()))],
+        };
+        assert!(serializer.from_serialized(&bad, &heap).is_err());
+        let bad = SerializedValue::Record {
+            typ: "Point".to_owned(),
+            fields: vec![("z".to_owned(), SerializedValue::Int(1))],
+        };
+        let err = serializer.from_serialized(&bad, &heap).unwrap_err();
+        assert_eq!("Record type `Point` has no field `z`", err.to_string());
+
+        let f = assert::pass_module("def f(): pass").get("f").unwrap();
+        let err = serializer.owned_to_json(&f).unwrap_err();
+        assert_eq!(
+            "Value of type `function` can't be serialized",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_non_finite_floats() {
+        let module = assert::pass_module(
+            "value = [float('nan'), float('inf'), -float('inf'), 1.5, {'k': float('-inf')}]",
+        );
+        let value = module.get("value").unwrap();
+        let frozen = value.value().unpack_frozen().unwrap();
+        let serializer = ValueSerializer::new();
+        let heap = FrozenHeap::new();
+
+        let json = serializer.to_json(frozen).unwrap();
+        assert!(json.contains(r#"{"non_finite_float":"nan"}"#), "{}", json);
+        let from_json = serializer.from_json(&json, &heap).unwrap();
+        assert_eq!(value.value().to_repr(), from_json.to_value().to_repr());
+
+        let cbor = serializer.to_cbor(frozen).unwrap();
+        let from_cbor = serializer.from_cbor(&cbor, &heap).unwrap();
+        assert_eq!(value.value().to_repr(), from_cbor.to_value().to_repr());
+
+        let bad = SerializedValue::NonFiniteFloat("1.0".to_owned());
+        let err = serializer.from_serialized(&bad, &heap).unwrap_err();
+        assert_eq!("Invalid float `1.0` in serialized value", err.to_string());
+    }
+
+    #[test]
+    fn test_cycle() {
+        let module = assert::pass_module(
+            r#"
+x = []
+x.append(x)
+y = {}
+y["k"] = [y]
+"#,
+        );
+        for name in ["x", "y"] {
+            let value = module.get(name).unwrap();
+            let err = ValueSerializer::new().owned_to_json(&value).unwrap_err();
+            assert!(err.to_string().contains("contains itself"), "{}", err);
+        }
+
+        // The same value twice is not a cycle.
+        let module = assert::pass_module("x = [1]\ny = [x, x, (x, x)]");
+        let value = module.get("y").unwrap();
+        assert_eq!(
+            r#"{"list":[{"list":[{"int":1}]},{"list":[{"int":1}]},{"tuple":[{"list":[{"int":1}]},{"list":[{"int":1}]}]}]}"#,
+            ValueSerializer::new().owned_to_json(&value).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_custom() {
+        /// Encodes functions as their type name, and decodes them as strings.
+        struct Functions;
+
+        impl CustomSerializer for Functions {
+            fn to_serialized(
+                &self,
+                value: Value,
+                _serializer: &ValueSerializer,
+            ) -> Option<anyhow::Result<SerializedValue>> {
+                if value.get_type() == "function" {
+                    Some(Ok(SerializedValue::String(value.get_type().to_owned())))
+                } else {
+                    None
+                }
+            }
+
+            fn from_serialized(
+                &self,
+                value: &SerializedValue,
+                serializer: &ValueSerializer,
+                heap: &FrozenHeap,
+            ) -> anyhow::Result<FrozenValue> {
+                serializer.from_serialized(value, heap)
+            }
+        }
+
+        let f = assert::pass_module("def f(): pass\nx = [f]")
+            .get("x")
+            .unwrap();
+        let mut serializer = ValueSerializer::new();
+        serializer.add_custom("function", Functions);
+        let json = serializer.owned_to_json(&f).unwrap();
+        assert_eq!(
+            r#"{"list":[{"custom":{"typ":"function","value":{"string":"function"}}}]}"#,
+            json
+        );
+        let heap = FrozenHeap::new();
+        let decoded = serializer.from_json(&json, &heap).unwrap();
+        assert_eq!(r#"["function"]"#, decoded.to_value().to_repr());
+    }
+}
@@ -356,6 +356,14 @@ pub trait StarlarkValue<'v>:
         ValueError::unsupported_with(self, "compare", other)
     }
 
+    /// Compare `self` with `other` for the `<`, `<=`, `>` and `>=` operators.
+    /// Types which are only partially ordered (e.g. sets, ordered by inclusion)
+    /// return [`None`] when neither value is ordered before the other.
+    /// Defaults to [`compare`](StarlarkValue::compare).
+    fn partial_compare(&self, other: Value<'v>) -> anyhow::Result<Option<Ordering>> {
+        self.compare(other).map(Some)
+    }
+
     /// Directly invoke a function.
     /// The number of `named` and `names` arguments are guaranteed to be equal.
     fn invoke(
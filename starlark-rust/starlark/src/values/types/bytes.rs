@@ -0,0 +1,328 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `bytes` type, an immutable sequence of bytes.
+
+use std::cmp;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Write;
+use std::hash::Hash;
+use std::ops::Deref;
+
+use allocative::Allocative;
+use serde::Serialize;
+use starlark_derive::starlark_module;
+use starlark_derive::StarlarkDocs;
+
+use crate as starlark;
+use crate::any::ProvidesStaticType;
+use crate::collections::StarlarkHasher;
+use crate::environment::Methods;
+use crate::environment::MethodsBuilder;
+use crate::environment::MethodsStatic;
+use crate::starlark_simple_value;
+use crate::starlark_type;
+use crate::values::index::apply_slice;
+use crate::values::index::convert_index;
+use crate::values::list::AllocList;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::StringValue;
+use crate::values::UnpackValue;
+use crate::values::Value;
+use crate::values::ValueError;
+
+#[derive(Debug, thiserror::Error)]
+enum BytesError {
+    #[error("bytes are not valid UTF-8: {0}")]
+    InvalidUtf8(std::str::Utf8Error),
+    #[error("Expected `bytes` or an int in the range 0-255 with `in`, got `{0}`")]
+    InvalidIn(String),
+}
+
+/// An immutable sequence of bytes, written as `b"..."` in source code.
+///
+/// Unlike strings, indexing into bytes gives an int, and bytes are not iterable,
+/// use `.elems()` to iterate over the values.
+#[derive(
+    ProvidesStaticType,
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    StarlarkDocs,
+    Allocative
+)]
+#[starlark_docs(builtin = "standard")]
+pub struct StarlarkBytes(Box<[u8]>);
+
+starlark_simple_value!(StarlarkBytes);
+
+impl StarlarkBytes {
+    /// The result of calling `type()` on bytes.
+    pub const TYPE: &'static str = "bytes";
+
+    /// Create a new [`StarlarkBytes`] value. Such a value can be allocated on a heap with
+    /// `heap.alloc(StarlarkBytes::new(x))`.
+    pub fn new(x: impl Into<Box<[u8]>>) -> Self {
+        Self(x.into())
+    }
+
+    /// The bytes as a slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Decode the bytes as UTF-8, replacing any invalid sequences with U+FFFD.
+    /// This is how `str(b)` behaves.
+    pub fn to_str_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.0).into_owned()
+    }
+}
+
+impl Deref for StarlarkBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Write the `b"..."` form of a byte string, which round-trips through the lexer.
+fn bytes_repr(xs: &[u8], buffer: &mut String) {
+    buffer.push_str("b\"");
+    for &x in xs {
+        match x {
+            b'\n' => buffer.push_str("\\n"),
+            b'\r' => buffer.push_str("\\r"),
+            b'\t' => buffer.push_str("\\t"),
+            b'"' => buffer.push_str("\\\""),
+            b'\\' => buffer.push_str("\\\\"),
+            0x20..=0x7e => buffer.push(x as char),
+            _ => write!(buffer, "\\x{:02x}", x).unwrap(),
+        }
+    }
+    buffer.push('"');
+}
+
+impl Display for StarlarkBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buffer = String::new();
+        bytes_repr(&self.0, &mut buffer);
+        f.write_str(&buffer)
+    }
+}
+
+/// Bytes serialize with [`serialize_bytes`](serde::Serializer::serialize_bytes),
+/// which JSON represents as an array of ints.
+impl Serialize for StarlarkBytes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'v> StarlarkValue<'v> for StarlarkBytes {
+    starlark_type!(StarlarkBytes::TYPE);
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(bytes_methods)
+    }
+
+    fn collect_repr(&self, buffer: &mut String) {
+        bytes_repr(&self.0, buffer)
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    fn write_hash(&self, hasher: &mut StarlarkHasher) -> anyhow::Result<()> {
+        self.0.hash(hasher);
+        Ok(())
+    }
+
+    fn equals(&self, other: Value) -> anyhow::Result<bool> {
+        match StarlarkBytes::from_value(other) {
+            Some(other) => Ok(self == other),
+            None => Ok(false),
+        }
+    }
+
+    fn compare(&self, other: Value) -> anyhow::Result<Ordering> {
+        match StarlarkBytes::from_value(other) {
+            Some(other) => Ok(self.cmp(other)),
+            None => ValueError::unsupported_with(self, "cmp()", other),
+        }
+    }
+
+    fn at(&self, index: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        let i = convert_index(index, self.0.len() as i32)?;
+        Ok(Value::new_int(self.0[i as usize] as i32))
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        Ok(self.0.len() as i32)
+    }
+
+    fn is_in(&self, other: Value) -> anyhow::Result<bool> {
+        if let Some(needle) = StarlarkBytes::from_value(other) {
+            Ok(needle.is_empty() || self.0.windows(needle.len()).any(|x| x == &**needle))
+        } else if let Some(b) = other.unpack_int().and_then(|x| u8::try_from(x).ok()) {
+            Ok(self.0.contains(&b))
+        } else {
+            Err(BytesError::InvalidIn(other.to_repr()).into())
+        }
+    }
+
+    fn slice(
+        &self,
+        start: Option<Value<'v>>,
+        stop: Option<Value<'v>>,
+        stride: Option<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        let xs = apply_slice(self.as_bytes(), start, stop, stride)?;
+        Ok(heap.alloc(StarlarkBytes::new(xs)))
+    }
+
+    fn add(&self, other: Value<'v>, heap: &'v Heap) -> Option<anyhow::Result<Value<'v>>> {
+        let other = StarlarkBytes::from_value(other)?;
+        Some(Ok(
+            heap.alloc(StarlarkBytes::new([&*self.0, &*other.0].concat()))
+        ))
+    }
+
+    fn mul(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        let l = i32::unpack_param(other)?;
+        Ok(heap.alloc(StarlarkBytes::new(self.0.repeat(cmp::max(0, l) as usize))))
+    }
+}
+
+#[starlark_module]
+fn bytes_methods(builder: &mut MethodsBuilder) {
+    /// [bytes.elems](
+    /// https://github.com/bazelbuild/starlark/blob/master/spec.md#bytes·elems
+    /// ): returns the values of the bytes as ints.
+    ///
+    /// `B.elems()` returns a list of the numeric values of each byte in B,
+    /// each in the range 0-255.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// b"AB\xff".elems() == [65, 66, 255]
+    /// # "#);
+    /// ```
+    #[starlark(return_type = "[int.type]")]
+    fn elems<'v>(this: &StarlarkBytes, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        Ok(heap.alloc(AllocList(this.0.iter().map(|x| *x as i32))))
+    }
+
+    /// bytes.decode: decode the bytes as a UTF-8 string.
+    ///
+    /// Fails if the bytes are not valid UTF-8,
+    /// use `str(B)` to replace invalid sequences with U+FFFD instead.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// b"caf\xc3\xa9".decode() == "café"
+    /// # "#);
+    /// ```
+    fn decode<'v>(this: &StarlarkBytes, heap: &'v Heap) -> anyhow::Result<StringValue<'v>> {
+        match std::str::from_utf8(&this.0) {
+            Ok(s) => Ok(heap.alloc_str(s)),
+            Err(e) => Err(BytesError::InvalidUtf8(e).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+    use crate::assert::Assert;
+    use crate::syntax::Dialect;
+
+    #[test]
+    fn test_bytes_literals() {
+        assert::all_true(
+            r#"
+type(b"abc") == "bytes"
+len(b"abc\x00\xff") == 5
+b"\xff"[0] == 255
+b"\377" == b"\xff"
+b"é" == "é".encode()
+rb"a\n" == b"a\\n"
+b'single' == b"single"
+"#,
+        );
+        assert::eq("b\"\"\"triple\nquoted\"\"\"", "b\"triple\\nquoted\"");
+    }
+
+    #[test]
+    fn test_bytes_dialect() {
+        let mut a = Assert::new();
+        a.dialect(&Dialect::Standard);
+        a.fail("b'x'", "bytes literals are not allowed");
+    }
+
+    #[test]
+    fn test_bytes_operations() {
+        assert::all_true(
+            r#"
+b"abc" + b"def" == b"abcdef"
+b"ab" * 3 == b"ababab"
+b"hello"[1:3] == b"el"
+b"hello"[::-1] == b"olleh"
+b"hello"[-1] == 111
+b"ell" in b"hello"
+b"" in b"hello"
+104 in b"hello"
+not (b"x" in b"hello")
+b"abc" < b"abd"
+b"ab" < b"abc"
+b"abc" != "abc"
+hash(b"abc") == hash("abc")
+{b"x": 1}[b"x"] == 1
+bool(b"") == False
+b"a\xffb".elems() == [97, 255, 98]
+"""café""".encode().decode() == "café"
+str(b"a\xffb") == "a�b"
+repr(b"a\"\n\xff") == 'b"a\\"\\n\\xff"'
+bytes("hi") == b"hi"
+bytes([104, 105]) == b"hi"
+bytes(b"hi") == b"hi"
+"#,
+        );
+        assert::fail("b'\\xff'.decode()", "not valid UTF-8");
+        assert::fail("256 in b'abc'", "range 0-255");
+        assert::fail("b'abc'[3]", "out of bound");
+        assert::fail("bytes([256])", "range 0-255");
+    }
+
+    #[test]
+    fn test_bytes_json() {
+        assert::eq("json.encode(b'AB')", "'[65,66]'");
+    }
+}
@@ -148,6 +148,11 @@ starlark_complex_values!(EnumType);
 starlark_complex_value!(pub EnumValue);
 
 impl<'v> EnumType<'v> {
+    /// Is this value an enum type, mutable or frozen.
+    pub(crate) fn is_type(x: Value<'v>) -> bool {
+        Self::from_value(x).is_some()
+    }
+
     pub(crate) fn new(elements: Vec<Value<'v>>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         // We are constructing the enum and all elements in one go.
         // They both point at each other, which adds to the complexity.
@@ -186,6 +191,16 @@ impl<'v, V: ValueLike<'v>> EnumValueGen<V> {
     /// The result of calling `type()` on an enum value.
     pub const TYPE: &'static str = "enum";
 
+    /// The enum type this value belongs to.
+    pub(crate) fn enum_type(&self) -> V {
+        self.typ
+    }
+
+    /// The value passed to `enum()` for this element.
+    pub(crate) fn value(&self) -> V {
+        self.value
+    }
+
     fn get_enum_type(&self) -> Either<&'v EnumType<'v>, &'v FrozenEnumType> {
         // Safe to unwrap because we always ensure typ is EnumType
         EnumType::from_value(self.typ.to_value()).unwrap()
@@ -45,6 +45,7 @@ use crate::starlark_type;
 use crate::values::array::Array;
 use crate::values::comparison::compare_slice;
 use crate::values::comparison::equals_slice;
+use crate::values::comparison::partial_compare_slice;
 use crate::values::error::ValueError;
 use crate::values::index::apply_slice;
 use crate::values::index::convert_index;
@@ -461,6 +462,15 @@ where
         }
     }
 
+    fn partial_compare(&self, other: Value<'v>) -> anyhow::Result<Option<Ordering>> {
+        match ListRef::from_value(other) {
+            None => ValueError::unsupported_with(self, "compare", other),
+            Some(other) => partial_compare_slice(self.0.content(), &other.content, |x, y| {
+                x.partial_compare(*y)
+            }),
+        }
+    }
+
     fn at(&self, index: Value, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         let i = convert_index(index, self.0.content().len() as i32)? as usize;
         Ok(self.0.content()[i])
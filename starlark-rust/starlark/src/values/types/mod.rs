@@ -20,6 +20,7 @@ pub mod any_array;
 pub mod array;
 pub mod bigint;
 pub mod bool;
+pub mod bytes;
 pub mod dict;
 pub mod enumeration;
 pub mod exported_name;
@@ -32,6 +33,7 @@ pub mod none;
 pub mod range;
 pub mod record;
 pub mod regex;
+pub mod set;
 pub mod string;
 pub mod structs;
 pub mod tuple;
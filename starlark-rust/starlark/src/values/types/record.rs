@@ -75,11 +75,13 @@ use crate::values::types::exported_name::MutableExportedName;
 use crate::values::typing::TypeCompiled;
 use crate::values::Freeze;
 use crate::values::Freezer;
+use crate::values::FrozenHeap;
 use crate::values::FrozenValue;
 use crate::values::Heap;
 use crate::values::StarlarkValue;
 use crate::values::Trace;
 use crate::values::Value;
+use crate::values::ValueError;
 use crate::values::ValueLike;
 
 /// The result of `field()`.
@@ -182,6 +184,11 @@ fn record_fields<'v>(
 }
 
 impl<'v> RecordType<'v> {
+    /// Is this value a record type, mutable or frozen.
+    pub(crate) fn is_type(x: Value<'v>) -> bool {
+        Self::from_value(x).is_some()
+    }
+
     pub(crate) fn new(fields: SmallMap<String, (FieldGen<Value<'v>>, TypeCompiled)>) -> Self {
         let parameter_spec = Self::make_parameter_spec(&fields);
         Self {
@@ -207,10 +214,49 @@ impl<'v> RecordType<'v> {
     }
 }
 
+impl FrozenRecordType {
+    /// Names of the fields, in declaration order.
+    pub(crate) fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.fields.keys().map(String::as_str)
+    }
+
+    /// Allocate a record of this type, which is `me`, checking the types of the fields.
+    /// `field` returns the value of a field, or `None` to use its default.
+    pub(crate) fn alloc_record(
+        &self,
+        me: FrozenValue,
+        mut field: impl FnMut(&str) -> anyhow::Result<Option<FrozenValue>>,
+        heap: &FrozenHeap,
+    ) -> anyhow::Result<FrozenValue> {
+        let mut values = Vec::with_capacity(self.fields.len());
+        for (name, (field_gen, typ)) in &self.fields {
+            let value = match (field(name)?, field_gen.default) {
+                (Some(v), _) => {
+                    v.to_value()
+                        .check_type_compiled(field_gen.typ.to_value(), typ, Some(name))?;
+                    v
+                }
+                (None, Some(default)) => default,
+                (None, None) => return Err(ValueError::MissingRequired(name.clone()).into()),
+            };
+            values.push(value);
+        }
+        Ok(heap.alloc(FrozenRecord {
+            typ: me,
+            values: values.into_boxed_slice(),
+        }))
+    }
+}
+
 impl<'v, V: ValueLike<'v>> RecordGen<V> {
     /// `type(x)` for records.
     pub const TYPE: &'static str = "record";
 
+    /// The record type this record was created by.
+    pub(crate) fn record_type(&self) -> V {
+        self.typ
+    }
+
     fn get_record_type(&self) -> Either<&'v RecordType<'v>, &'v FrozenRecordType> {
         // Safe to unwrap because we always ensure typ is RecordType
         RecordType::from_value(self.typ.to_value()).unwrap()
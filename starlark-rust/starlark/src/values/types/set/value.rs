@@ -0,0 +1,531 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::mem;
+use std::ops::Deref;
+
+use allocative::Allocative;
+use display_container::fmt_container;
+use serde::Serialize;
+use starlark_derive::StarlarkDocs;
+
+use crate as starlark;
+use crate::any::ProvidesStaticType;
+use crate::coerce::coerce;
+use crate::coerce::Coerce;
+use crate::collections::Hashed;
+use crate::collections::SmallSet;
+use crate::environment::Methods;
+use crate::environment::MethodsStatic;
+use crate::starlark_type;
+use crate::values::dict::refcell::unleak_borrow;
+use crate::values::error::ValueError;
+use crate::values::set::SetRef;
+use crate::values::type_repr::StarlarkTypeRepr;
+use crate::values::AllocFrozenValue;
+use crate::values::AllocValue;
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::FrozenHeap;
+use crate::values::FrozenStringValue;
+use crate::values::FrozenValue;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::Trace;
+use crate::values::Value;
+use crate::values::ValueLike;
+
+#[derive(
+    Clone,
+    Default,
+    Trace,
+    Debug,
+    ProvidesStaticType,
+    StarlarkDocs,
+    Allocative
+)]
+#[starlark_docs(builtin = "extension")]
+pub(crate) struct SetGen<T>(pub(crate) T);
+
+impl<'v, T: SetLike<'v>> Display for SetGen<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_container(f, "set([", "])", self.0.content().iter())
+    }
+}
+
+impl<'v> Display for Set<'v> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_container(f, "set([", "])", self.iter())
+    }
+}
+
+/// Define the set type.
+#[derive(Clone, Default, Trace, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub struct Set<'v> {
+    /// The data stored by the set. The values must all be hashable.
+    content: SmallSet<Value<'v>>,
+}
+
+impl<'v> StarlarkTypeRepr for Set<'v> {
+    fn starlark_type_repr() -> String {
+        Set::TYPE.to_owned()
+    }
+}
+
+#[derive(Clone, Default, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub(crate) struct FrozenSetData {
+    /// The data stored by the set. The values must all be hashable.
+    pub(crate) content: SmallSet<FrozenValue>,
+}
+
+/// Alias is used in `StarlarkDocs` derive.
+type FrozenSet = SetGen<FrozenSetData>;
+
+unsafe impl<'v> Coerce<Set<'v>> for FrozenSetData {}
+
+impl<'v> AllocValue<'v> for Set<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex(SetGen(RefCell::new(self)))
+    }
+}
+
+impl AllocFrozenValue for FrozenSetData {
+    fn alloc_frozen_value(self, heap: &FrozenHeap) -> FrozenValue {
+        heap.alloc_simple(SetGen(self))
+    }
+}
+
+impl<'v> Set<'v> {
+    /// The result of calling `type()` on sets.
+    pub const TYPE: &'static str = "set";
+
+    /// Set type string as Starlark frozen string value.
+    pub fn get_type_value_static() -> FrozenStringValue {
+        SetGen::<FrozenSetData>::get_type_value_static()
+    }
+
+    /// Create a new [`Set`].
+    pub fn new(content: SmallSet<Value<'v>>) -> Self {
+        Self { content }
+    }
+
+    /// Number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.content.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    /// Iterate through the values in the set, in insertion order.
+    pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = Value<'v>> + 'a {
+        self.content.iter().copied()
+    }
+
+    /// Iterate through the values in the set, but retaining their hashes.
+    pub fn iter_hashed<'a>(&'a self) -> impl ExactSizeIterator<Item = Hashed<Value<'v>>> + 'a
+    where
+        'v: 'a,
+    {
+        self.content.iter_hashed().map(|x| x.copied())
+    }
+
+    /// Does the set contain the given value? Will be [`Err`] if the value is not hashable.
+    pub fn contains(&self, value: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self.contains_hashed(value.get_hashed()?))
+    }
+
+    /// Does the set contain the given prehashed value?
+    pub fn contains_hashed(&self, value: Hashed<Value<'v>>) -> bool {
+        self.content.contains_hashed(value.as_ref())
+    }
+
+    /// Insert a value into the set. Return `true` if the value was not already present.
+    pub fn insert_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.insert_hashed(value)
+    }
+
+    /// Remove a value from the set. Return `true` if the value was present.
+    pub fn remove_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.remove_hashed(value.as_ref())
+    }
+
+    /// Remove and return the first value in the set.
+    pub fn pop_first(&mut self) -> Option<Value<'v>> {
+        let first = self.content.iter_hashed().next()?.copied();
+        self.content.remove_hashed(first.as_ref());
+        Some(*first.key())
+    }
+
+    /// Remove all elements from the set.
+    pub fn clear(&mut self) {
+        self.content.clear();
+    }
+
+    /// Is every value of this set also in `other`?
+    pub fn is_subset(&self, other: &Set<'v>) -> bool {
+        self.len() <= other.len() && self.iter_hashed().all(|x| other.contains_hashed(x))
+    }
+
+    /// Does this set have no values in common with `other`?
+    pub fn is_disjoint(&self, other: &Set<'v>) -> bool {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        !small.iter_hashed().any(|x| large.contains_hashed(x))
+    }
+
+    /// The values in either this set or `other`, in the order of this set followed by `other`.
+    pub fn union(&self, other: &Set<'v>) -> Set<'v> {
+        let mut content = self.content.clone();
+        for x in other.iter_hashed() {
+            content.insert_hashed(x);
+        }
+        Set::new(content)
+    }
+
+    /// The values in both this set and `other`, in the order of this set.
+    pub fn intersection(&self, other: &Set<'v>) -> Set<'v> {
+        let mut content = SmallSet::new();
+        for x in self.iter_hashed() {
+            if other.contains_hashed(x) {
+                content.insert_hashed_unique_unchecked(x);
+            }
+        }
+        Set::new(content)
+    }
+
+    /// The values in this set which are not in `other`, in the order of this set.
+    pub fn difference(&self, other: &Set<'v>) -> Set<'v> {
+        let mut content = SmallSet::with_capacity(self.len());
+        for x in self.iter_hashed() {
+            if !other.contains_hashed(x) {
+                content.insert_hashed_unique_unchecked(x);
+            }
+        }
+        Set::new(content)
+    }
+
+    /// The values in exactly one of this set and `other`,
+    /// in the order of this set followed by `other`.
+    pub fn symmetric_difference(&self, other: &Set<'v>) -> Set<'v> {
+        let mut content = self.difference(other).content;
+        for x in other.iter_hashed() {
+            if !self.contains_hashed(x) {
+                content.insert_hashed_unique_unchecked(x);
+            }
+        }
+        Set::new(content)
+    }
+}
+
+impl<'v> Freeze for SetGen<RefCell<Set<'v>>> {
+    type Frozen = SetGen<FrozenSetData>;
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        let content = self.0.into_inner().content.freeze(freezer)?;
+        Ok(SetGen(FrozenSetData { content }))
+    }
+}
+
+pub(crate) trait SetLike<'v>: Debug + Allocative {
+    type ContentRef<'a>: Deref<Target = Set<'v>>
+    where
+        Self: 'a,
+        'v: 'a;
+    fn content<'a>(&'a self) -> Self::ContentRef<'a>;
+    // These functions are unsafe for the same reason
+    // `StarlarkValue` iterator functions are unsafe.
+    unsafe fn iter_start(&self);
+    unsafe fn content_unchecked(&self) -> &Set<'v>;
+    unsafe fn iter_stop(&self);
+}
+
+impl<'v> SetLike<'v> for RefCell<Set<'v>> {
+    type ContentRef<'a> = Ref<'a, Set<'v>> where Self: 'a, 'v: 'a;
+
+    fn content<'a>(&'a self) -> Ref<'a, Set<'v>> {
+        self.borrow()
+    }
+
+    #[inline]
+    unsafe fn iter_start(&self) {
+        mem::forget(self.borrow());
+    }
+
+    #[inline]
+    unsafe fn iter_stop(&self) {
+        unleak_borrow(self);
+    }
+
+    #[inline]
+    unsafe fn content_unchecked(&self) -> &Set<'v> {
+        // SAFETY: this function contract is, caller must ensure that the value is borrowed.
+        self.try_borrow_unguarded().ok().unwrap_unchecked()
+    }
+}
+
+impl<'v> SetLike<'v> for FrozenSetData {
+    type ContentRef<'a> = &'a Set<'v> where Self: 'a, 'v: 'a;
+
+    fn content<'a>(&'a self) -> &'a Set<'v> {
+        coerce(self)
+    }
+
+    unsafe fn iter_start(&self) {}
+
+    unsafe fn iter_stop(&self) {}
+
+    unsafe fn content_unchecked(&self) -> &Set<'v> {
+        coerce(self)
+    }
+}
+
+pub(crate) fn set_methods() -> Option<&'static Methods> {
+    static RES: MethodsStatic = MethodsStatic::new();
+    RES.methods(crate::stdlib::set::set_methods)
+}
+
+impl<'v, T: SetLike<'v> + 'v> SetGen<T> {
+    fn binary_op(
+        &self,
+        op: &str,
+        rhs: Value<'v>,
+        heap: &'v Heap,
+        f: impl FnOnce(&Set<'v>, &Set<'v>) -> Set<'v>,
+    ) -> anyhow::Result<Value<'v>>
+    where
+        Self: StarlarkValue<'v>,
+    {
+        let rhs = SetRef::from_value(rhs)
+            .map_or_else(|| ValueError::unsupported_with(self, op, rhs), Ok)?;
+        let lhs = self.0.content();
+        Ok(heap.alloc(f(&lhs, &rhs)))
+    }
+}
+
+impl<'v, T: SetLike<'v> + 'v> StarlarkValue<'v> for SetGen<T>
+where
+    Self: ProvidesStaticType,
+{
+    starlark_type!(Set::TYPE);
+
+    fn get_methods() -> Option<&'static Methods> {
+        set_methods()
+    }
+
+    fn collect_repr(&self, r: &mut String) {
+        r.push_str("set([");
+        for (i, x) in self.0.content().iter().enumerate() {
+            if i != 0 {
+                r.push_str(", ");
+            }
+            x.collect_repr(r);
+        }
+        r.push_str("])");
+    }
+
+    fn collect_repr_cycle(&self, collector: &mut String) {
+        collector.push_str("set([...])");
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.content().is_empty()
+    }
+
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        match SetRef::from_value(other) {
+            None => Ok(false),
+            Some(other) => {
+                let content = self.0.content();
+                Ok(content.len() == other.len() && content.is_subset(&other))
+            }
+        }
+    }
+
+    /// Sets are ordered by inclusion: `a <= b` if `a` is a subset of `b`.
+    fn partial_compare(&self, other: Value<'v>) -> anyhow::Result<Option<Ordering>> {
+        let other = SetRef::from_value(other)
+            .map_or_else(|| ValueError::unsupported_with(self, "compare", other), Ok)?;
+        let content = self.0.content();
+        Ok(match content.len().cmp(&other.len()) {
+            Ordering::Less => content.is_subset(&other).then_some(Ordering::Less),
+            Ordering::Equal => content.is_subset(&other).then_some(Ordering::Equal),
+            Ordering::Greater => other.is_subset(&content).then_some(Ordering::Greater),
+        })
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        Ok(self.0.content().len() as i32)
+    }
+
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        self.0.content().contains(other)
+    }
+
+    unsafe fn iterate(&self, me: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.0.iter_start();
+        Ok(me)
+    }
+
+    unsafe fn iter_size_hint(&self, index: usize) -> (usize, Option<usize>) {
+        debug_assert!(index <= self.0.content().len());
+        let rem = self.0.content().len() - index;
+        (rem, Some(rem))
+    }
+
+    unsafe fn iter_next(&self, index: usize, _heap: &'v Heap) -> Option<Value<'v>> {
+        self.0.content_unchecked().content.get_index(index).copied()
+    }
+
+    unsafe fn iter_stop(&self) {
+        self.0.iter_stop();
+    }
+
+    fn bit_or(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binary_op("|", rhs, heap, Set::union)
+    }
+
+    fn bit_and(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binary_op("&", rhs, heap, Set::intersection)
+    }
+
+    fn bit_xor(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binary_op("^", rhs, heap, Set::symmetric_difference)
+    }
+
+    fn sub(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binary_op("-", rhs, heap, Set::difference)
+    }
+}
+
+impl<'v, T: SetLike<'v>> Serialize for SetGen<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.0.content().iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    #[test]
+    fn test_mutate_set() {
+        assert::is_true(
+            r#"
+x = set([1, 2])
+b1 = str(x) == "set([1, 2])"
+x.add(3)
+x.add(1)
+b2 = str(x) == "set([1, 2, 3])"
+x.remove(2)
+b3 = str(x) == "set([1, 3])"
+b1 and b2 and b3
+"#,
+        );
+    }
+
+    #[test]
+    fn test_set_operators() {
+        assert::all_true(
+            r#"
+set([1, 2]) | set([2, 3]) == set([1, 2, 3])
+set([1, 2]) & set([2, 3]) == set([2])
+set([1, 2]) - set([2, 3]) == set([1])
+set([1, 2]) ^ set([2, 3]) == set([1, 3])
+list(set([3, 1, 2]) | set([0])) == [3, 1, 2, 0]
+set([1, 2]) == set([2, 1])
+set([1, 2]) != set([1])
+"#,
+        );
+        assert::fail("set([1]) | [2]", "not supported");
+    }
+
+    #[test]
+    fn test_set_comparison() {
+        assert::all_true(
+            r#"
+set([1]) <= set([1, 2])
+set([1]) < set([1, 2])
+set([1, 2]) >= set([2])
+set([1, 2]) > set([2])
+set([1, 2]) <= set([2, 1])
+set([1, 2]) >= set([2, 1])
+not (set([1, 2]) < set([2, 1]))
+not (set([1, 2]) > set([2, 1]))
+not (set([1]) < set([2]))
+not (set([1]) <= set([2]))
+not (set([1]) > set([2]))
+not (set([1]) >= set([2]))
+not (set([1, 2]) <= set([1]))
+set() <= set()
+[set([1])] < [set([1, 2])]
+(set([1]), 2) <= (set([1]), 2)
+not ([set([1])] < [set([2])])
+not ([set([1])] >= [set([2])])
+[set([1])] < [set([1]), 1]
+"#,
+        );
+        assert::fail("set([1]) < [1]", "not supported");
+    }
+
+    #[test]
+    fn test_set_iteration_order() {
+        assert::eq("[x for x in set([3, 1, 3, 2, 1])]", "[3, 1, 2]");
+    }
+
+    #[test]
+    fn test_set_mutation_during_iteration() {
+        assert::fail(
+            r#"
+x = set([1, 2])
+def f():
+    for i in x:
+        x.add(i + 10)
+f()
+"#,
+            "mutate an iterable for an iterator while iterating",
+        );
+    }
+
+    #[test]
+    fn test_frozen_set() {
+        let mut a = assert::Assert::new();
+        a.module("m", "s = set([1, 2])");
+        a.is_true("load('m', 's'); 1 in s and len(s) == 2");
+        a.fail("load('m', 's'); s.add(3)", "Immutable");
+    }
+
+    #[test]
+    fn test_unhashable() {
+        assert::fail("set([[1]])", "not hashable");
+    }
+}
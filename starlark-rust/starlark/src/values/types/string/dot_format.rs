@@ -15,11 +15,16 @@
  * limitations under the License.
  */
 
+use std::fmt;
+use std::fmt::Display;
 use std::mem;
 use std::str::FromStr;
 
+use dupe::Dupe;
+
 use crate::collections::string_pool::StringPool;
 use crate::values::dict::Dict;
+use crate::values::string::format_spec::FormatSpec;
 use crate::values::Heap;
 use crate::values::StringValue;
 use crate::values::Value;
@@ -230,41 +235,89 @@ pub(crate) fn format<'v>(
     Ok(r)
 }
 
-fn format_capture<'v, T: Iterator<Item = Value<'v>>>(
-    capture: &str,
-    args: &mut FormatArgs<'v, T>,
-    kwargs: &Dict,
-    result: &mut String,
-) -> anyhow::Result<()> {
-    let (n, conv) = {
-        if let Some((n, conv)) = capture.split_once('!') {
-            (n, conv)
-        } else {
-            (capture, "s")
-        }
-    };
-    let conv_s = |x: Value, result: &mut String| x.collect_str(result);
-    let conv_r = |x: Value, result: &mut String| x.collect_repr(result);
-    let conv: &dyn Fn(Value, &mut String) = match conv {
-        "s" => &conv_s,
-        "r" => &conv_r,
-        c => {
-            return Err(anyhow::anyhow!(
+/// The `!s` or `!r` conversion in a replacement field.
+#[derive(Clone, Copy, Dupe, Debug, PartialEq, Eq)]
+pub(crate) enum FormatConversion {
+    /// `!s`, the default.
+    Str,
+    /// `!r`.
+    Repr,
+}
+
+impl FormatConversion {
+    fn parse(conv: &str) -> anyhow::Result<FormatConversion> {
+        match conv {
+            "s" => Ok(FormatConversion::Str),
+            "r" => Ok(FormatConversion::Repr),
+            c => Err(anyhow::anyhow!(
                 concat!(
                     "'{}' is not a valid format string specifier, only ",
                     "'s' and 'r' are valid specifiers",
                 ),
                 c
-            ));
+            )),
         }
+    }
+
+    fn write(self, value: Value, result: &mut String) {
+        match self {
+            FormatConversion::Str => value.collect_str(result),
+            FormatConversion::Repr => value.collect_repr(result),
+        }
+    }
+}
+
+/// Split a replacement field `name!conversion:spec` into its parts.
+fn parse_capture(
+    capture: &str,
+) -> anyhow::Result<(&str, Option<FormatConversion>, Option<FormatSpec>)> {
+    let (field, spec) = match capture.split_once(':') {
+        Some((field, spec)) if !spec.is_empty() => (field, Some(FormatSpec::parse(spec)?)),
+        Some((field, _)) => (field, None),
+        None => (capture, None),
     };
-    if n.is_empty() {
-        conv(args.next_ordered()?, result);
-        Ok(())
+    match field.split_once('!') {
+        Some((name, conv)) => Ok((name, Some(FormatConversion::parse(conv)?), spec)),
+        None => Ok((field, None, spec)),
+    }
+}
+
+/// Write a value for a replacement field `{!conversion:spec}`.
+/// With an explicit conversion, the spec applies to the converted string.
+fn format_field(
+    value: Value,
+    conversion: Option<FormatConversion>,
+    spec: Option<&FormatSpec>,
+    result: &mut String,
+) -> anyhow::Result<()> {
+    match (conversion, spec) {
+        (conversion, None) => {
+            conversion
+                .unwrap_or(FormatConversion::Str)
+                .write(value, result);
+            Ok(())
+        }
+        (None, Some(spec)) => spec.format_value(value, result),
+        (Some(conversion), Some(spec)) => {
+            let mut converted = String::new();
+            conversion.write(value, &mut converted);
+            spec.format_str(&converted, result)
+        }
+    }
+}
+
+fn format_capture<'v, T: Iterator<Item = Value<'v>>>(
+    capture: &str,
+    args: &mut FormatArgs<'v, T>,
+    kwargs: &Dict<'v>,
+    result: &mut String,
+) -> anyhow::Result<()> {
+    let (n, conv, spec) = parse_capture(capture)?;
+    let value = if n.is_empty() {
+        args.next_ordered()?
     } else if n.chars().all(|c| c.is_ascii_digit()) {
         let i = usize::from_str(n).unwrap();
-        conv(args.by_index(i)?, result);
-        Ok(())
+        args.by_index(i)?
     } else {
         if let Some(x) = n.chars().find(|c| match c {
             '.' | ',' | '[' | ']' => true,
@@ -276,12 +329,95 @@ fn format_capture<'v, T: Iterator<Item = Value<'v>>>(
             ));
         }
         match kwargs.get_str(n) {
-            None => Err(ValueError::KeyNotFound(n.to_owned()).into()),
-            Some(v) => {
-                conv(v, result);
-                Ok(())
+            None => return Err(ValueError::KeyNotFound(n.to_owned()).into()),
+            Some(v) => v,
+        }
+    };
+    format_field(value, conv, spec.as_ref(), result)
+}
+
+/// The format string of an `f"..."` literal, parsed at compile time.
+/// Every replacement field is `{}` optionally followed by a conversion and a spec,
+/// and takes the next value in order.
+#[derive(Debug)]
+pub(crate) struct FStringFormat {
+    /// The text before each replacement field, followed by the text after the last one.
+    literals: Vec<String>,
+    fields: Vec<(Option<FormatConversion>, Option<FormatSpec>)>,
+}
+
+impl FStringFormat {
+    pub(crate) fn parse(format: &str) -> anyhow::Result<FStringFormat> {
+        let mut parser = FormatParser {
+            format_str: format,
+            rem_input: format,
+        };
+        let mut literals = vec![String::new()];
+        let mut fields = Vec::new();
+        while let Some(token) = parser.next()? {
+            match token {
+                FormatToken::Text(text) => literals.last_mut().unwrap().push_str(text),
+                FormatToken::Capture(capture) => {
+                    let (name, conv, spec) = parse_capture(capture)?;
+                    if !name.is_empty() {
+                        return Err(anyhow::anyhow!(
+                            "Replacement field `{}` must not have a name (internal error)",
+                            name
+                        ));
+                    }
+                    fields.push((conv, spec));
+                    literals.push(String::new());
+                }
+            }
+        }
+        Ok(FStringFormat { literals, fields })
+    }
+
+    /// If the format string is `"<before>{}<after>"`, return `before` and `after`.
+    pub(crate) fn as_format_one(&self) -> Option<(&str, &str)> {
+        match (self.literals.as_slice(), self.fields.as_slice()) {
+            ([before, after], [(None, None)]) => Some((before, after)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn format<'v>(
+        &self,
+        args: &[Value<'v>],
+        string_pool: &mut StringPool,
+        heap: &'v Heap,
+    ) -> anyhow::Result<StringValue<'v>> {
+        assert_eq!(args.len(), self.fields.len());
+        let mut result = string_pool.alloc();
+        result.push_str(&self.literals[0]);
+        for ((arg, (conv, spec)), literal) in args.iter().zip(&self.fields).zip(&self.literals[1..])
+        {
+            format_field(*arg, *conv, spec.as_ref(), &mut result)?;
+            result.push_str(literal);
+        }
+        let r = heap.alloc_str(&result);
+        string_pool.release(result);
+        Ok(r)
+    }
+}
+
+impl Display for FStringFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let escape = |s: &str| s.replace('{', "{{").replace('}', "}}");
+        write!(f, "{}", escape(&self.literals[0]))?;
+        for ((conv, spec), literal) in self.fields.iter().zip(&self.literals[1..]) {
+            write!(f, "{{")?;
+            match conv {
+                None => {}
+                Some(FormatConversion::Str) => write!(f, "!s")?,
+                Some(FormatConversion::Repr) => write!(f, "!r")?,
             }
+            if let Some(spec) = spec {
+                write!(f, ":{}", spec)?;
+            }
+            write!(f, "}}{}", escape(literal))?;
         }
+        Ok(())
     }
 }
 
@@ -291,8 +427,10 @@ mod tests {
 
     use crate::assert;
     use crate::coerce::coerce;
+    use crate::collections::string_pool::StringPool;
     use crate::values::dict::Dict;
     use crate::values::string::dot_format::parse_format_one;
+    use crate::values::string::dot_format::FStringFormat;
     use crate::values::string::dot_format::FormatArgs;
     use crate::values::Heap;
     use crate::values::Value;
@@ -300,7 +438,7 @@ mod tests {
     fn format_capture_for_test<'v, T: Iterator<Item = Value<'v>>>(
         capture: &str,
         args: &mut FormatArgs<'v, T>,
-        kwargs: &Dict,
+        kwargs: &Dict<'v>,
     ) -> anyhow::Result<String> {
         let mut result = String::new();
         super::format_capture(capture, args, kwargs, &mut result)?;
@@ -352,6 +490,39 @@ mod tests {
         assert::eq("'a{x}b{y}c{}'.format(1, x=2, y=3)", "'a2b3c1'")
     }
 
+    #[test]
+    fn test_format_spec() {
+        assert::eq("'[{:>10}]'.format('abc')", "'[       abc]'");
+        assert::eq("'{:08.3f}'.format(3.14159)", "'0003.142'");
+        assert::eq("'{:x}'.format(255)", "'ff'");
+        assert::eq("'{1:<3}|{0:^5}'.format('a', 'b')", "'b  |  a  '");
+        assert::eq("'{x:+d}'.format(x=5)", "'+5'");
+        assert::eq("'{!r:>5}'.format('a')", "'  \"a\"'");
+        assert::eq("'{:}'.format(1)", "'1'");
+        assert::fail(
+            "'{!r:d}'.format(1)",
+            "Unknown format code 'd' for object of type 'string'",
+        );
+        assert::fail("'{:10q0}'.format(1)", "Invalid format specifier `10q0`");
+    }
+
+    #[test]
+    fn test_fstring_format() {
+        let heap = Heap::new();
+        let mut string_pool = StringPool::default();
+        let format = FStringFormat::parse("a{{{}}}b{!r:>5}c{:x}").unwrap();
+        assert_eq!("a{{{}}}b{!r:>5}c{:x}", format.to_string());
+        let args = [heap.alloc("x"), heap.alloc("y"), heap.alloc(255)];
+        assert_eq!(
+            "a{x}b  \"y\"cff",
+            format
+                .format(&args, &mut string_pool, &heap)
+                .unwrap()
+                .as_str()
+        );
+        assert!(FStringFormat::parse("{x}").is_err());
+    }
+
     #[test]
     fn test_parse_format_one() {
         assert_eq!(
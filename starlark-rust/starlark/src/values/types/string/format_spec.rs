@@ -0,0 +1,659 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Format specifications, the part after `:` in a `str.format` replacement field.
+//! Based on <https://docs.python.org/3/library/string.html#format-specification-mini-language>
+
+use std::fmt;
+use std::fmt::Display;
+use std::iter;
+
+use num_bigint::Sign;
+use thiserror::Error;
+
+use crate::values::float;
+use crate::values::num::Num;
+use crate::values::Value;
+
+#[derive(Debug, Error)]
+enum FormatSpecError {
+    #[error("Invalid format specifier `{0}`")]
+    Invalid(String),
+    #[error("Unknown format code '{0}' for object of type '{1}'")]
+    UnknownCode(char, &'static str),
+    #[error("{0} not allowed in {1} format specifier")]
+    NotAllowed(&'static str, &'static str),
+    #[error("Cannot specify '{0}' with '{1}'")]
+    Grouping(char, char),
+    #[error("%c arg not in range(0x110000)")]
+    CharOutOfRange,
+    #[error("Width or precision {0} is larger than the maximum {}", MAX_WIDTH)]
+    TooLarge(usize),
+}
+
+/// Largest width or precision accepted, as the formatted string is allocated outside of
+/// the Starlark heap, so a large number would exhaust memory or take forever to write.
+pub(crate) const MAX_WIDTH: usize = 1_000_000;
+
+/// Check a width or precision parsed from a format string against [`MAX_WIDTH`].
+pub(crate) fn check_width(n: usize) -> anyhow::Result<usize> {
+    if n > MAX_WIDTH {
+        return Err(FormatSpecError::TooLarge(n).into());
+    }
+    Ok(n)
+}
+
+/// Alignment of the value within the field width.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FormatAlign {
+    /// `<`.
+    Left,
+    /// `>`.
+    Right,
+    /// `^`.
+    Center,
+    /// `=`, padding goes between the sign and the digits.
+    AfterSign,
+}
+
+impl FormatAlign {
+    fn from_char(c: char) -> Option<FormatAlign> {
+        match c {
+            '<' => Some(FormatAlign::Left),
+            '>' => Some(FormatAlign::Right),
+            '^' => Some(FormatAlign::Center),
+            '=' => Some(FormatAlign::AfterSign),
+            _ => None,
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            FormatAlign::Left => '<',
+            FormatAlign::Right => '>',
+            FormatAlign::Center => '^',
+            FormatAlign::AfterSign => '=',
+        }
+    }
+}
+
+/// Which numbers are written with a sign.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FormatSign {
+    /// `+`, both positive and negative numbers.
+    Plus,
+    /// `-`, only negative numbers, the default.
+    Minus,
+    /// ` `, a space for positive numbers and a minus for negative numbers.
+    Space,
+}
+
+impl FormatSign {
+    fn from_char(c: char) -> Option<FormatSign> {
+        match c {
+            '+' => Some(FormatSign::Plus),
+            '-' => Some(FormatSign::Minus),
+            ' ' => Some(FormatSign::Space),
+            _ => None,
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            FormatSign::Plus => '+',
+            FormatSign::Minus => '-',
+            FormatSign::Space => ' ',
+        }
+    }
+}
+
+/// Parsed format spec, e.g. `*>+#010,.3f`:
+/// `[[fill]align][sign][#][0][width][grouping][.precision][type]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct FormatSpec {
+    pub(crate) fill: Option<char>,
+    pub(crate) align: Option<FormatAlign>,
+    pub(crate) sign: Option<FormatSign>,
+    /// `#`, add a `0x` style prefix to integers, always write a decimal point for floats.
+    pub(crate) alternate: bool,
+    /// `0`, pad numbers with zeros after the sign.
+    pub(crate) zero: bool,
+    pub(crate) width: usize,
+    /// `,` or `_`.
+    pub(crate) grouping: Option<char>,
+    pub(crate) precision: Option<usize>,
+    pub(crate) ty: Option<char>,
+}
+
+impl Display for FormatSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(fill) = self.fill {
+            write!(f, "{}", fill)?;
+        }
+        if let Some(align) = self.align {
+            write!(f, "{}", align.to_char())?;
+        }
+        if let Some(sign) = self.sign {
+            write!(f, "{}", sign.to_char())?;
+        }
+        if self.alternate {
+            write!(f, "#")?;
+        }
+        if self.zero {
+            write!(f, "0")?;
+        }
+        if self.width != 0 {
+            write!(f, "{}", self.width)?;
+        }
+        if let Some(grouping) = self.grouping {
+            write!(f, "{}", grouping)?;
+        }
+        if let Some(precision) = self.precision {
+            write!(f, ".{}", precision)?;
+        }
+        if let Some(ty) = self.ty {
+            write!(f, "{}", ty)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a decimal number starting at `chars[*i]`, if there is one.
+fn parse_number(chars: &[char], i: &mut usize) -> Option<Option<usize>> {
+    let start = *i;
+    let mut res: usize = 0;
+    while let Some(d) = chars.get(*i).and_then(|c| c.to_digit(10)) {
+        res = res.checked_mul(10)?.checked_add(d as usize)?;
+        *i += 1;
+    }
+    Some(if *i == start { None } else { Some(res) })
+}
+
+/// Number of characters needed to write `n` digits with a separator every `every` digits.
+fn grouped_len(n: usize, every: usize) -> usize {
+    n + n.saturating_sub(1) / every
+}
+
+/// Insert `sep` every `every` digits, counting from the right,
+/// first adding leading zeros until the result is at least `min_len` characters.
+fn group_digits(digits: &str, sep: char, every: usize, min_len: usize) -> String {
+    let mut n = digits.len();
+    while grouped_len(n, every) < min_len {
+        n += 1;
+    }
+    let padded = iter::repeat('0')
+        .take(n - digits.len())
+        .chain(digits.chars());
+    let mut res = String::with_capacity(grouped_len(n, every));
+    for (i, c) in padded.enumerate() {
+        if i != 0 && (n - i) % every == 0 {
+            res.push(sep);
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// Python `{:.{precision}e}`, which unlike Rust always writes a signed exponent
+/// with at least two digits.
+fn write_exponent(f: f64, precision: usize, alternate: bool) -> String {
+    let s = format!("{:.*e}", precision, f);
+    let (mantissa, exponent) = s.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let point = if alternate && precision == 0 { "." } else { "" };
+    format!("{}{}e{:+03}", mantissa, point, exponent)
+}
+
+/// Python `{:.{precision}g}`: fixed or scientific notation depending on the exponent,
+/// with insignificant trailing zeros removed unless `alternate` is set.
+fn write_general(f: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    let s = format!("{:.*e}", precision - 1, f);
+    let exponent: i32 = s.split_once('e').unwrap().1.parse().unwrap();
+    let mut res = if -4 <= exponent && exponent < precision as i32 {
+        let mut res = format!("{:.*}", (precision as i32 - 1 - exponent) as usize, f);
+        if alternate && !res.contains('.') {
+            res.push('.');
+        }
+        res
+    } else {
+        write_exponent(f, precision - 1, alternate)
+    };
+    if !alternate {
+        let (mantissa, exponent) = match res.find('e') {
+            Some(i) => res.split_at(i),
+            None => (res.as_str(), ""),
+        };
+        if mantissa.contains('.') {
+            let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+            res = format!("{}{}", mantissa, exponent);
+        }
+    }
+    res
+}
+
+impl FormatSpec {
+    /// Parse the format spec, the part after `:` in `{name!conversion:spec}`.
+    pub(crate) fn parse(spec: &str) -> anyhow::Result<FormatSpec> {
+        let invalid = || FormatSpecError::Invalid(spec.to_owned());
+        let chars: Vec<char> = spec.chars().collect();
+        let mut res = FormatSpec::default();
+        let mut i = 0;
+
+        if let Some(align) = chars.get(1).copied().and_then(FormatAlign::from_char) {
+            res.fill = Some(chars[0]);
+            res.align = Some(align);
+            i = 2;
+        } else if let Some(align) = chars.first().copied().and_then(FormatAlign::from_char) {
+            res.align = Some(align);
+            i = 1;
+        }
+        if let Some(sign) = chars.get(i).copied().and_then(FormatSign::from_char) {
+            res.sign = Some(sign);
+            i += 1;
+        }
+        if chars.get(i) == Some(&'#') {
+            res.alternate = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'0') {
+            res.zero = true;
+            i += 1;
+        }
+        res.width = check_width(
+            parse_number(&chars, &mut i)
+                .ok_or_else(invalid)?
+                .unwrap_or(0),
+        )?;
+        if let Some(c @ (',' | '_')) = chars.get(i) {
+            res.grouping = Some(*c);
+            i += 1;
+        }
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            res.precision = Some(check_width(
+                parse_number(&chars, &mut i)
+                    .ok_or_else(invalid)?
+                    .ok_or_else(invalid)?,
+            )?);
+        }
+        match &chars[i..] {
+            [] => {}
+            [ty] => res.ty = Some(*ty),
+            _ => return Err(invalid().into()),
+        }
+        Ok(res)
+    }
+
+    /// The effective fill character and alignment.
+    fn fill_align(&self, numeric: bool) -> (char, FormatAlign) {
+        let fill = self.fill.unwrap_or(if self.zero { '0' } else { ' ' });
+        let align = self.align.unwrap_or(match (numeric, self.zero) {
+            (true, true) => FormatAlign::AfterSign,
+            (true, false) => FormatAlign::Right,
+            (false, _) => FormatAlign::Left,
+        });
+        (fill, align)
+    }
+
+    /// Write `prefix` (a sign and/or a `0x` style prefix) and `body`, padded to the width.
+    fn pad(&self, prefix: &str, body: &str, numeric: bool, out: &mut String) {
+        let (fill, align) = self.fill_align(numeric);
+        let padding = self
+            .width
+            .saturating_sub(prefix.chars().count() + body.chars().count());
+        let (before, after) = match align {
+            FormatAlign::Left => (0, padding),
+            FormatAlign::Right | FormatAlign::AfterSign => (padding, 0),
+            FormatAlign::Center => (padding / 2, padding - padding / 2),
+        };
+        if align == FormatAlign::AfterSign {
+            out.push_str(prefix);
+            out.extend(iter::repeat(fill).take(before));
+        } else {
+            out.extend(iter::repeat(fill).take(before));
+            out.push_str(prefix);
+        }
+        out.push_str(body);
+        out.extend(iter::repeat(fill).take(after));
+    }
+
+    /// The minimum length of the digits if zero padding is needed to reach the width,
+    /// which with grouping is done by inserting more groups.
+    fn min_digits_len(&self, other_len: usize) -> usize {
+        match self.fill_align(true) {
+            ('0', FormatAlign::AfterSign) => self.width.saturating_sub(other_len),
+            _ => 0,
+        }
+    }
+
+    fn sign_str(&self, negative: bool) -> &'static str {
+        match (negative, self.sign) {
+            (true, _) => "-",
+            (false, Some(FormatSign::Plus)) => "+",
+            (false, Some(FormatSign::Space)) => " ",
+            (false, _) => "",
+        }
+    }
+
+    /// Format any value, picking the formatting based on its type.
+    /// Values other than strings and numbers are formatted as `str(value)`.
+    pub(crate) fn format_value(&self, value: Value, out: &mut String) -> anyhow::Result<()> {
+        if let Some(s) = value.unpack_str() {
+            return self.format_str(s, out);
+        }
+        match value.unpack_num() {
+            Some(Num::Float(f)) => self.format_float(f, out),
+            Some(n) => self.format_int(n, out),
+            None => match self.ty {
+                None | Some('s') => self.format_str(&value.to_str(), out),
+                Some(c) => Err(FormatSpecError::UnknownCode(c, value.get_type()).into()),
+            },
+        }
+    }
+
+    /// Format a string, with the precision being the maximum number of characters.
+    pub(crate) fn format_str(&self, s: &str, out: &mut String) -> anyhow::Result<()> {
+        match self.ty {
+            None | Some('s') => {}
+            Some(c) => return Err(FormatSpecError::UnknownCode(c, "string").into()),
+        }
+        if self.sign.is_some() {
+            return Err(FormatSpecError::NotAllowed("Sign", "string").into());
+        }
+        if self.alternate {
+            return Err(FormatSpecError::NotAllowed("Alternate form (#)", "string").into());
+        }
+        if self.align == Some(FormatAlign::AfterSign) {
+            return Err(FormatSpecError::NotAllowed("'=' alignment", "string").into());
+        }
+        if let Some(grouping) = self.grouping {
+            return Err(FormatSpecError::Grouping(grouping, 's').into());
+        }
+        let s = match self.precision.and_then(|p| s.char_indices().nth(p)) {
+            Some((i, _)) => &s[..i],
+            None => s,
+        };
+        self.pad("", s, false, out);
+        Ok(())
+    }
+
+    fn format_int(&self, n: Num, out: &mut String) -> anyhow::Result<()> {
+        let (radix, prefix) = match self.ty {
+            None | Some('d' | 'n') => (10, ""),
+            Some('b') => (2, "0b"),
+            Some('o') => (8, "0o"),
+            Some('x') => (16, "0x"),
+            Some('X') => (16, "0X"),
+            Some('c') => return self.format_char(n, out),
+            Some('e' | 'E' | 'f' | 'F' | 'g' | 'G' | '%') => {
+                return self.format_float(n.as_float(), out);
+            }
+            Some(c) => return Err(FormatSpecError::UnknownCode(c, "int").into()),
+        };
+        if self.precision.is_some() {
+            return Err(FormatSpecError::NotAllowed("Precision", "integer").into());
+        }
+        let prefix = if self.alternate { prefix } else { "" };
+        self.format_int_radix(n, radix, prefix, 0, out)
+    }
+
+    /// Write an integer in the given radix with at least `min_digits` digits.
+    /// Floats are truncated, which only `%d` relies on.
+    pub(crate) fn format_int_radix(
+        &self,
+        n: Num,
+        radix: u32,
+        prefix: &str,
+        min_digits: usize,
+        out: &mut String,
+    ) -> anyhow::Result<()> {
+        let (negative, mut digits) = match n {
+            Num::Int(i) => {
+                let abs = i.unsigned_abs();
+                let digits = match radix {
+                    2 => format!("{:b}", abs),
+                    8 => format!("{:o}", abs),
+                    16 => format!("{:x}", abs),
+                    _ => abs.to_string(),
+                };
+                (i < 0, digits)
+            }
+            Num::BigInt(b) => (
+                b.get().sign() == Sign::Minus,
+                b.get().magnitude().to_str_radix(radix),
+            ),
+            Num::Float(f) => (f.trunc() < 0.0, format!("{:.0}", f.trunc().abs())),
+        };
+        if self.ty == Some('X') {
+            digits.make_ascii_uppercase();
+        }
+        if digits.len() < min_digits {
+            digits.insert_str(0, &"0".repeat(min_digits - digits.len()));
+        }
+        let sign = self.sign_str(negative);
+        if let Some(grouping) = self.grouping {
+            let every = match (grouping, radix) {
+                (_, 10) => 3,
+                ('_', _) => 4,
+                _ => return Err(FormatSpecError::Grouping(grouping, self.ty.unwrap()).into()),
+            };
+            let min_len = self.min_digits_len(sign.len() + prefix.len());
+            digits = group_digits(&digits, grouping, every, min_len);
+        }
+        self.pad(&format!("{}{}", sign, prefix), &digits, true, out);
+        Ok(())
+    }
+
+    fn format_char(&self, n: Num, out: &mut String) -> anyhow::Result<()> {
+        if self.sign.is_some() {
+            return Err(FormatSpecError::NotAllowed("Sign", "integer 'c'").into());
+        }
+        if self.alternate {
+            return Err(FormatSpecError::NotAllowed("Alternate form (#)", "integer 'c'").into());
+        }
+        let c = match n {
+            Num::Int(i) => u32::try_from(i).ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match c {
+            Some(c) => {
+                self.pad("", c.encode_utf8(&mut [0; 4]), true, out);
+                Ok(())
+            }
+            None => Err(FormatSpecError::CharOutOfRange.into()),
+        }
+    }
+
+    /// Format a float, with the precision defaulting to 6 like in Python.
+    /// Without a type or precision the result matches `str()`.
+    pub(crate) fn format_float(&self, f: f64, out: &mut String) -> anyhow::Result<()> {
+        match self.ty {
+            None | Some('e' | 'E' | 'f' | 'F' | 'g' | 'G' | 'n' | '%') => {}
+            Some(c) => return Err(FormatSpecError::UnknownCode(c, "float").into()),
+        }
+        let abs = f.abs();
+        let mut body = if !f.is_finite() {
+            if f.is_nan() { "nan" } else { "inf" }.to_owned()
+        } else {
+            match self.ty {
+                None => match self.precision {
+                    None => {
+                        let mut body = String::new();
+                        float::write_compact(&mut body, abs, 'e').unwrap();
+                        body
+                    }
+                    Some(p) => {
+                        let mut body = write_general(abs, p, self.alternate);
+                        if !body.contains(['.', 'e']) {
+                            body.push_str(".0");
+                        }
+                        body
+                    }
+                },
+                Some('e' | 'E') => write_exponent(abs, self.precision.unwrap_or(6), self.alternate),
+                Some('f' | 'F') => {
+                    let p = self.precision.unwrap_or(6);
+                    let mut body = format!("{:.*}", p, abs);
+                    if self.alternate && p == 0 {
+                        body.push('.');
+                    }
+                    body
+                }
+                Some('g' | 'G' | 'n') => {
+                    write_general(abs, self.precision.unwrap_or(6), self.alternate)
+                }
+                Some('%') => format!("{:.*}%", self.precision.unwrap_or(6), abs * 100.0),
+                Some(_) => unreachable!("Checked above"),
+            }
+        };
+        if matches!(self.ty, Some('E' | 'F' | 'G')) {
+            body.make_ascii_uppercase();
+        }
+
+        let mut sign = self.sign_str(f.is_sign_negative() && !f.is_nan());
+        // `str()` of an infinity always has a sign, keep it when formatting like `str()`.
+        if f == f64::INFINITY && self.ty.is_none() && self.precision.is_none() && sign.is_empty() {
+            sign = "+";
+        }
+
+        if let (Some(grouping), true) = (self.grouping, f.is_finite()) {
+            let int_len = body
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(body.len());
+            let (int_part, rest) = body.split_at(int_len);
+            let min_len = self.min_digits_len(sign.len() + rest.len());
+            body = format!("{}{}", group_digits(int_part, grouping, 3, min_len), rest);
+        }
+        self.pad(sign, &body, true, out);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+    use crate::values::string::format_spec::FormatSpec;
+
+    fn check(spec: &str, value: &str, expected: &str) {
+        assert::eq(
+            &format!("'{{:{}}}'.format({})", spec, value),
+            &format!("{:?}", expected),
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        for spec in ["", "*>+#010,.3f", "<5", "^", "=+08_x", ".2%", "5s"] {
+            assert_eq!(spec, FormatSpec::parse(spec).unwrap().to_string());
+        }
+        assert_eq!(5, FormatSpec::parse("0<5").unwrap().width);
+        assert!(FormatSpec::parse("10x5").is_err());
+        assert!(FormatSpec::parse(".").is_err());
+        assert!(FormatSpec::parse("99999999999999999999999").is_err());
+        assert!(FormatSpec::parse("1000000").is_ok());
+        assert!(FormatSpec::parse("1000001").is_err());
+        assert!(FormatSpec::parse(".1000001f").is_err());
+    }
+
+    #[test]
+    fn test_too_large() {
+        assert::fail(
+            "'{:10000000000}'.format(1)",
+            "Width or precision 10000000000 is larger than the maximum 1000000",
+        );
+        assert::fail(
+            "'{:.1000000000f}'.format(1.0)",
+            "Width or precision 1000000000 is larger than the maximum",
+        );
+        assert::eq("len('{:1000000}'.format(1))", "1000000");
+    }
+
+    #[test]
+    fn test_str() {
+        check(">10", "'abc'", "       abc");
+        check("<6", "'abc'", "abc   ");
+        check("*^7", "'abc'", "**abc**");
+        check("^6", "'abc'", " abc  ");
+        check(".2", "'abc'", "ab");
+        check("5", "'é'", "é    ");
+        check(">5", "None", " None");
+        assert::fail(
+            "'{:+}'.format('a')",
+            "Sign not allowed in string format specifier",
+        );
+        assert::fail(
+            "'{:d}'.format('a')",
+            "Unknown format code 'd' for object of type 'string'",
+        );
+    }
+
+    #[test]
+    fn test_int() {
+        check("5", "42", "   42");
+        check("<5", "42", "42   ");
+        check("05", "-42", "-0042");
+        check("=+6", "42", "+   42");
+        check(" d", "42", " 42");
+        check("x", "255", "ff");
+        check("#X", "255", "0XFF");
+        check("#010b", "5", "0b00000101");
+        check("o", "-8", "-10");
+        check(",", "1234567", "1,234,567");
+        check("_x", "0xdeadbeef", "dead_beef");
+        check("010,", "1234", "00,001,234");
+        check("08,", "1234", "0,001,234");
+        check(",", "12345678901234567890", "12,345,678,901,234,567,890");
+        check("x", "-(1 << 70)", "-400000000000000000");
+        check("c", "65", "A");
+        check(".2f", "3", "3.00");
+        assert::fail("'{:.2}'.format(1)", "Precision not allowed");
+        assert::fail("'{:,x}'.format(1)", "Cannot specify ',' with 'x'");
+        assert::fail(
+            "'{:s}'.format(1)",
+            "Unknown format code 's' for object of type 'int'",
+        );
+    }
+
+    #[test]
+    fn test_float() {
+        check("", "1.5", "1.5");
+        check("08.3f", "3.14159", "0003.142");
+        check("08.3f", "-3.14159", "-003.142");
+        check("+.1f", "2.26", "+2.3");
+        check(".0f", "2.7", "3");
+        check("#.0f", "2.0", "2.");
+        check("e", "12345.678", "1.234568e+04");
+        check(".2E", "0.000123", "1.23E-04");
+        check("g", "0.00001", "1e-05");
+        check("g", "123456789.0", "1.23457e+08");
+        check("g", "100.0", "100");
+        check(".3", "1.0", "1.0");
+        check(".3", "1234.5", "1.23e+03");
+        check(".1%", "0.256", "25.6%");
+        check(",.2f", "1234567.891", "1,234,567.89");
+        check("010,.1f", "1234.5", "0,001,234.5");
+        check(">6", "float('inf')", "  +inf");
+        check("f", "float('-inf')", "-inf");
+        check("F", "float('nan')", "NAN");
+        check("^9.2f", "1.0", "  1.00   ");
+        assert::fail(
+            "'{:x}'.format(1.5)",
+            "Unknown format code 'x' for object of type 'float'",
+        );
+    }
+}
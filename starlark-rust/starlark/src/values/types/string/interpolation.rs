@@ -20,13 +20,17 @@
 
 use std::fmt::Write;
 
-use dupe::Dupe;
 use thiserror::Error;
 
+use crate::values::dict::DictRef;
 use crate::values::float;
 use crate::values::num;
 use crate::values::num::Num;
 use crate::values::string::dot_format::format_one;
+use crate::values::string::format_spec::check_width;
+use crate::values::string::format_spec::FormatAlign;
+use crate::values::string::format_spec::FormatSign;
+use crate::values::string::format_spec::FormatSpec;
 use crate::values::types::tuple::value::Tuple;
 use crate::values::Heap;
 use crate::values::StringValue;
@@ -36,7 +40,7 @@ use crate::values::ValueError;
 use crate::values::ValueLike;
 
 /// Operator `%` format or evaluation errors
-#[derive(Clone, Dupe, Debug, Error)]
+#[derive(Clone, Debug, Error)]
 enum StringInterpolationError {
     #[error("Too many arguments for format string")]
     TooManyParameters,
@@ -48,6 +52,12 @@ enum StringInterpolationError {
     UnsupportedFormatCharacter(char),
     #[error("Expecting format character (internal error)")]
     ExpectingFormatCharacter,
+    #[error("Incomplete format key")]
+    IncompleteFormatKey,
+    #[error("Format requires a mapping")]
+    FormatRequiresMapping,
+    #[error("Invalid width or precision: {0}")]
+    InvalidWidth(String),
 }
 
 enum PercentSFormat {
@@ -75,13 +85,118 @@ enum PercentSFormat {
     FloatCompactUpper,
 }
 
+/// The optional parts between `%` and the format character, e.g. `(name)-08.3` in `%(name)-08.3f`.
+#[derive(Default)]
+struct PercentSFlags<'a> {
+    /// `(key)`, take the value from a dict.
+    key: Option<&'a str>,
+    /// `-`.
+    left: bool,
+    /// `0`.
+    zero: bool,
+    /// `+`.
+    plus: bool,
+    /// ` `.
+    space: bool,
+    /// `#`.
+    alternate: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+impl<'a> PercentSFlags<'a> {
+    /// Whether anything other than a key was given, which the plain formatting ignores.
+    fn has_spec(&self) -> bool {
+        self.left
+            || self.zero
+            || self.plus
+            || self.space
+            || self.alternate
+            || self.width.is_some()
+            || self.precision.is_some()
+    }
+
+    /// Equivalent format spec, for numbers if `numeric`.
+    fn to_format_spec(&self, numeric: bool) -> FormatSpec {
+        FormatSpec {
+            align: Some(if self.left {
+                FormatAlign::Left
+            } else if numeric && self.zero {
+                FormatAlign::AfterSign
+            } else {
+                FormatAlign::Right
+            }),
+            fill: Some(if numeric && self.zero && !self.left {
+                '0'
+            } else {
+                ' '
+            }),
+            sign: if self.plus {
+                Some(FormatSign::Plus)
+            } else if self.space {
+                Some(FormatSign::Space)
+            } else {
+                None
+            },
+            alternate: self.alternate && numeric,
+            width: self.width.unwrap_or(0),
+            ..FormatSpec::default()
+        }
+    }
+}
+
 struct PercentFormatParser<'a> {
     rem: &'a str,
 }
 
 struct Item<'a> {
     literal: &'a str,
-    format: Option<PercentSFormat>,
+    format: Option<(PercentSFlags<'a>, PercentSFormat)>,
+}
+
+impl<'a> PercentFormatParser<'a> {
+    /// Parse a decimal number at the start of `rem`.
+    fn number(rem: &mut &'a str) -> anyhow::Result<Option<usize>> {
+        let len = rem.bytes().take_while(|c| c.is_ascii_digit()).count();
+        if len == 0 {
+            return Ok(None);
+        }
+        let (digits, after) = rem.split_at(len);
+        *rem = after;
+        match digits.parse() {
+            Ok(n) => Ok(Some(check_width(n)?)),
+            Err(_) => Err(StringInterpolationError::InvalidWidth(digits.to_owned()).into()),
+        }
+    }
+
+    /// Parse the flags after a `%`, returning the remaining string starting at the format character.
+    fn flags(mut rem: &'a str) -> anyhow::Result<(PercentSFlags<'a>, &'a str)> {
+        let mut flags = PercentSFlags::default();
+        if let Some(after) = rem.strip_prefix('(') {
+            let Some((key, after)) = after.split_once(')') else {
+                return Err(StringInterpolationError::IncompleteFormatKey.into());
+            };
+            flags.key = Some(key);
+            rem = after;
+        }
+        loop {
+            match rem.as_bytes().first() {
+                Some(b'-') => flags.left = true,
+                Some(b'0') => flags.zero = true,
+                Some(b'+') => flags.plus = true,
+                Some(b' ') => flags.space = true,
+                Some(b'#') => flags.alternate = true,
+                _ => break,
+            }
+            rem = &rem[1..];
+        }
+        flags.width = Self::number(&mut rem)?;
+        if let Some(after) = rem.strip_prefix('.') {
+            rem = after;
+            flags.precision = Some(Self::number(&mut rem)?.unwrap_or(0));
+        }
+        Ok((flags, rem))
+    }
 }
 
 impl<'a> Iterator for PercentFormatParser<'a> {
@@ -93,78 +208,48 @@ impl<'a> Iterator for PercentFormatParser<'a> {
         if let Some(index_of_percent) = index_of_percent {
             let prev_rem = self.rem;
             let (literal, rem) = self.rem.split_at(index_of_percent);
-            match rem.as_bytes().get(1) {
+            if rem.as_bytes().get(1) == Some(&b'%') {
+                // Include the percent in the literal.
+                self.rem = &rem[2..];
+                return Some(Ok(Item {
+                    literal: &prev_rem[..index_of_percent + 1],
+                    format: None,
+                }));
+            }
+            let (flags, rem) = match Self::flags(&rem[1..]) {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            let format = match rem.as_bytes().first() {
                 None => return Some(Err(StringInterpolationError::IncompleteFormat.into())),
-                Some(f) => {
-                    let res = match f {
-                        b'%' => {
-                            // Include the percent in the literal.
-                            let literal = &prev_rem[..index_of_percent + 1];
-                            Item {
-                                literal,
-                                format: None,
-                            }
-                        }
-                        b's' => Item {
-                            literal,
-                            format: Some(PercentSFormat::Str),
-                        },
-                        b'r' => Item {
-                            literal,
-                            format: Some(PercentSFormat::Repr),
-                        },
-                        b'd' => Item {
-                            literal,
-                            format: Some(PercentSFormat::Dec),
-                        },
-                        b'o' => Item {
-                            literal,
-                            format: Some(PercentSFormat::Oct),
-                        },
-                        b'x' => Item {
-                            literal,
-                            format: Some(PercentSFormat::Hex),
-                        },
-                        b'X' => Item {
-                            literal,
-                            format: Some(PercentSFormat::HexUpper),
-                        },
-                        b'e' => Item {
-                            literal,
-                            format: Some(PercentSFormat::Exp),
-                        },
-                        b'E' => Item {
-                            literal,
-                            format: Some(PercentSFormat::ExpUpper),
-                        },
-                        b'f' | b'F' => Item {
-                            literal,
-                            format: Some(PercentSFormat::Float),
-                        },
-                        b'g' => Item {
-                            literal,
-                            format: Some(PercentSFormat::FloatCompact),
-                        },
-                        b'G' => Item {
-                            literal,
-                            format: Some(PercentSFormat::FloatCompactUpper),
-                        },
-                        _ => {
-                            // Note we need to find the second character, not the second byte.
-                            let Some(c) = rem.chars().nth(1) else {
-                                return Some(Err(StringInterpolationError::ExpectingFormatCharacter.into()));
-                            };
-                            return Some(Err(
-                                StringInterpolationError::UnsupportedFormatCharacter(c).into(),
-                            ));
-                        }
+                Some(b's') => PercentSFormat::Str,
+                Some(b'r') => PercentSFormat::Repr,
+                Some(b'd' | b'i') => PercentSFormat::Dec,
+                Some(b'o') => PercentSFormat::Oct,
+                Some(b'x') => PercentSFormat::Hex,
+                Some(b'X') => PercentSFormat::HexUpper,
+                Some(b'e') => PercentSFormat::Exp,
+                Some(b'E') => PercentSFormat::ExpUpper,
+                Some(b'f' | b'F') => PercentSFormat::Float,
+                Some(b'g') => PercentSFormat::FloatCompact,
+                Some(b'G') => PercentSFormat::FloatCompactUpper,
+                Some(_) => {
+                    // Note we need to find the character, not the byte.
+                    let Some(c) = rem.chars().next() else {
+                        return Some(Err(StringInterpolationError::ExpectingFormatCharacter.into()));
                     };
-                    // We reach here only if format character is ASCII,
-                    // so we can safely skip 2 bytes.
-                    self.rem = &rem[2..];
-                    Some(Ok(res))
+                    return Some(Err(
+                        StringInterpolationError::UnsupportedFormatCharacter(c).into()
+                    ));
                 }
-            }
+            };
+            // We reach here only if format character is ASCII,
+            // so we can safely skip 1 byte.
+            self.rem = &rem[1..];
+            Some(Ok(Item {
+                literal,
+                format: Some((flags, format)),
+            }))
         } else {
             if self.rem.is_empty() {
                 None
@@ -180,6 +265,63 @@ impl<'a> Iterator for PercentFormatParser<'a> {
     }
 }
 
+/// Format a value for a `%` conversion which has flags, a width or a precision.
+fn percent_with_flags(
+    flags: &PercentSFlags,
+    format: &PercentSFormat,
+    value: Value,
+    res: &mut String,
+) -> anyhow::Result<()> {
+    let mut spec = flags.to_format_spec(!matches!(
+        format,
+        PercentSFormat::Str | PercentSFormat::Repr
+    ));
+    let (radix, prefix) = match format {
+        PercentSFormat::Str | PercentSFormat::Repr => {
+            let mut s = String::new();
+            match (format, value.unpack_str()) {
+                (PercentSFormat::Str, Some(v)) => s.push_str(v),
+                _ => value.collect_repr(&mut s),
+            }
+            spec.precision = flags.precision;
+            return spec.format_str(&s, res);
+        }
+        PercentSFormat::Exp
+        | PercentSFormat::ExpUpper
+        | PercentSFormat::Float
+        | PercentSFormat::FloatCompact
+        | PercentSFormat::FloatCompactUpper => {
+            spec.precision = Some(flags.precision.unwrap_or(6));
+            spec.ty = Some(match format {
+                PercentSFormat::Exp => 'e',
+                PercentSFormat::ExpUpper => 'E',
+                PercentSFormat::Float => 'f',
+                PercentSFormat::FloatCompact => 'g',
+                _ => 'G',
+            });
+            let v = Num::unpack_param(value)?.as_float();
+            return spec.format_float(v, res);
+        }
+        PercentSFormat::Dec => (10, ""),
+        PercentSFormat::Oct => (8, "0o"),
+        PercentSFormat::Hex => (16, "0x"),
+        PercentSFormat::HexUpper => {
+            spec.ty = Some('X');
+            (16, "0X")
+        }
+    };
+    let n = match value.unpack_num() {
+        Some(Num::Float(v)) if radix == 10 && v.is_finite() => Num::Float(v),
+        Some(Num::BigInt(b)) => Num::BigInt(b),
+        Some(Num::Float(v)) if radix == 10 => {
+            return ValueError::unsupported(&float::StarlarkFloat(v), "%d");
+        }
+        _ => Num::Int(value.to_int()?),
+    };
+    let prefix = if flags.alternate { prefix } else { "" };
+    spec.format_int_radix(n, radix, prefix, flags.precision.unwrap_or(0), res)
+}
+
 pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
     // NOTE(nga): use could reuse `Evaluator::string_pool` here, but
     //   * we don't have access to `Evaluator` in `StarlarkValue::percent`
@@ -195,28 +337,49 @@ pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
         None => one,
     };
     let mut values = values.iter().copied();
-    let mut next_value = || -> anyhow::Result<Value> {
-        values
-            .next()
-            .ok_or_else(|| StringInterpolationError::NotEnoughParameters.into())
+    let mut used_key = false;
+    let mut next_value = |key: Option<&str>| -> anyhow::Result<Value> {
+        match key {
+            Some(key) => {
+                used_key = true;
+                let Some(dict) = DictRef::from_value(value) else {
+                    return Err(StringInterpolationError::FormatRequiresMapping.into());
+                };
+                match dict.get_str(key) {
+                    Some(v) => Ok(v),
+                    None => Err(ValueError::KeyNotFound(key.to_owned()).into()),
+                }
+            }
+            None => values
+                .next()
+                .ok_or_else(|| StringInterpolationError::NotEnoughParameters.into()),
+        }
     };
 
     // because of the way format is defined, we can deal with it as bytes
     for item in (PercentFormatParser { rem: format }) {
         let item = item?;
         res.push_str(item.literal);
-        match item.format {
-            None => {}
-            Some(PercentSFormat::Str) => {
-                let arg = next_value()?;
+        let (flags, format) = match item.format {
+            None => continue,
+            Some(x) => x,
+        };
+        if flags.has_spec() {
+            percent_with_flags(&flags, &format, next_value(flags.key)?, &mut res)?;
+            continue;
+        }
+        let key = flags.key;
+        match format {
+            PercentSFormat::Str => {
+                let arg = next_value(key)?;
                 match arg.unpack_str() {
                     None => arg.collect_repr(&mut res),
                     Some(s) => res.push_str(s),
                 }
             }
-            Some(PercentSFormat::Repr) => next_value()?.collect_repr(&mut res),
-            Some(PercentSFormat::Dec) => {
-                let value = next_value()?;
+            PercentSFormat::Repr => next_value(key)?.collect_repr(&mut res),
+            PercentSFormat::Dec => {
+                let value = next_value(key)?;
                 if let Some(num::Num::Float(v)) = value.unpack_num() {
                     match num::Num::Float(v.trunc()).as_int() {
                         None => {
@@ -228,8 +391,8 @@ pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
                     write!(res, "{}", value.to_int()?).unwrap()
                 }
             }
-            Some(PercentSFormat::Oct) => {
-                let v = next_value()?.to_int()?;
+            PercentSFormat::Oct => {
+                let v = next_value(key)?.to_int()?;
                 write!(
                     res,
                     "{}{:o}",
@@ -238,8 +401,8 @@ pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
                 )
                 .unwrap();
             }
-            Some(PercentSFormat::Hex) => {
-                let v = next_value()?.to_int()?;
+            PercentSFormat::Hex => {
+                let v = next_value(key)?.to_int()?;
                 write!(
                     res,
                     "{}{:x}",
@@ -248,8 +411,8 @@ pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
                 )
                 .unwrap();
             }
-            Some(PercentSFormat::HexUpper) => {
-                let v = next_value()?.to_int()?;
+            PercentSFormat::HexUpper => {
+                let v = next_value(key)?.to_int()?;
                 write!(
                     res,
                     "{}{:X}",
@@ -258,29 +421,29 @@ pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
                 )
                 .unwrap();
             }
-            Some(PercentSFormat::Exp) => {
-                let v = Num::unpack_param(next_value()?)?.as_float();
+            PercentSFormat::Exp => {
+                let v = Num::unpack_param(next_value(key)?)?.as_float();
                 float::write_scientific(&mut res, v, 'e', false).unwrap()
             }
-            Some(PercentSFormat::ExpUpper) => {
-                let v = Num::unpack_param(next_value()?)?.as_float();
+            PercentSFormat::ExpUpper => {
+                let v = Num::unpack_param(next_value(key)?)?.as_float();
                 float::write_scientific(&mut res, v, 'E', false).unwrap()
             }
-            Some(PercentSFormat::Float) => {
-                let v = Num::unpack_param(next_value()?)?.as_float();
+            PercentSFormat::Float => {
+                let v = Num::unpack_param(next_value(key)?)?.as_float();
                 float::write_decimal(&mut res, v).unwrap()
             }
-            Some(PercentSFormat::FloatCompact) => {
-                let v = Num::unpack_param(next_value()?)?.as_float();
+            PercentSFormat::FloatCompact => {
+                let v = Num::unpack_param(next_value(key)?)?.as_float();
                 float::write_compact(&mut res, v, 'e').unwrap()
             }
-            Some(PercentSFormat::FloatCompactUpper) => {
-                let v = Num::unpack_param(next_value()?)?.as_float();
+            PercentSFormat::FloatCompactUpper => {
+                let v = Num::unpack_param(next_value(key)?)?.as_float();
                 float::write_compact(&mut res, v, 'E').unwrap()
             }
         }
     }
-    if values.next().is_some() {
+    if !used_key && values.next().is_some() {
         Err(StringInterpolationError::TooManyParameters.into())
     } else {
         Ok(res)
@@ -353,6 +516,48 @@ mod tests {
         assert::fail("'xx%qxx' % (1,)", "Unsupported format character: 'q'");
     }
 
+    #[test]
+    fn test_percent_flags() {
+        assert::all_true(
+            r##"
+"[%5s]" % "ab" == "[   ab]"
+"[%-5s]" % "ab" == "[ab   ]"
+"[%.1s]" % "ab" == "[a]"
+"%05d" % -42 == "-0042"
+"%+d" % 42 == "+42"
+"% d" % 42 == " 42"
+"%.3d" % 7 == "007"
+"%#x" % 255 == "0xff"
+"%#o" % 8 == "0o10"
+"%08.3f" % 3.14159 == "0003.142"
+"%-8.2e|" % 1234.5 == "1.23e+03|"
+"%.2g" % 0.0001234 == "0.00012"
+"%x" % 255 == "ff"
+"%i" % 3 == "3"
+"%(a)s-%(b)03d" % {"a": "x", "b": 5} == "x-005"
+"##,
+        );
+        assert::fail("'%(a)s' % (1,)", "Format requires a mapping");
+        assert::fail("'%(a)s' % {}", "Key `a` was not found");
+        assert::fail("'%(a' % {}", "Incomplete format key");
+    }
+
+    #[test]
+    fn test_percent_too_large() {
+        assert::fail(
+            "'%10000000000d' % 1",
+            "Width or precision 10000000000 is larger than the maximum",
+        );
+        assert::fail(
+            "'%.1000000000f' % 1.0",
+            "Width or precision 1000000000 is larger than the maximum",
+        );
+        assert::fail(
+            "'%99999999999999999999999s' % 'a'",
+            "Invalid width or precision",
+        );
+    }
+
     #[test]
     fn test_parse_percent_s_one() {
         assert_eq!(
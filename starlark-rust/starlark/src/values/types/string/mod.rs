@@ -60,6 +60,7 @@ use crate::values::ValueError;
 mod alloc_unpack;
 pub(crate) mod dot_format;
 pub(crate) mod fast_string;
+pub(crate) mod format_spec;
 pub(crate) mod intern;
 pub(crate) mod interpolation;
 pub(crate) mod iter;
@@ -37,6 +37,7 @@ use crate::private::Private;
 use crate::starlark_type;
 use crate::values::comparison::compare_slice;
 use crate::values::comparison::equals_slice;
+use crate::values::comparison::partial_compare_slice;
 use crate::values::index::apply_slice;
 use crate::values::index::convert_index;
 use crate::values::FrozenValue;
@@ -173,6 +174,15 @@ where
         }
     }
 
+    fn partial_compare(&self, other: Value<'v>) -> anyhow::Result<Option<Ordering>> {
+        match Tuple::from_value(other) {
+            None => ValueError::unsupported_with(self, "compare", other),
+            Some(other) => partial_compare_slice(self.content(), other.content(), |x, y| {
+                x.to_value().partial_compare(*y)
+            }),
+        }
+    }
+
     fn at(&self, index: Value, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         let i = convert_index(index, self.len() as i32)? as usize;
         Ok(self.content()[i].to_value())